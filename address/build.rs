@@ -5,7 +5,7 @@ use std::io::{BufReader, BufWriter, Write};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-const FILES: [&'static str; 5] = [
+const FILES: [&str; 5] = [
     "oui-sources/oui.csv",
     "oui-sources/cid.csv",
     "oui-sources/iab.csv",
@@ -46,14 +46,14 @@ fn read_records() -> Result<Vec<Assignment>> {
             let mut addr = u64::from_str_radix(&assignment, 16)?;
             addr <<= assignment.len() * 4;
             out.push(Assignment {
-                addr: addr,
+                addr,
                 prefix_len: assignment.len() * 4,
-                name: org_name,
+                name: org_name.replace(['\u{200B}', '\u{AD}', '\u{2060}'], ""),
             })
         }
     }
 
-    out.sort_by(|l, r| l.addr.cmp(&r.addr));
+    out.sort_by_key(|l| l.addr);
 
     Ok(out)
 }
@@ -91,7 +91,7 @@ impl Abbreviator {
                         tmp.make_ascii_lowercase();
                     }
                 }
-                word.into()
+                word
             })
             .collect();
         let orig = orig.join(" ");
@@ -121,23 +121,23 @@ fn main() -> Result<()> {
     let mut out = BufWriter::new(File::create(
         std::path::Path::new(&std::env::var("OUT_DIR")?).join("oui_assignments.rs"),
     )?);
-    write!(out, "const ASSIGNMENTS: &'static [Assignment] = &[\n")?;
+    writeln!(out, "const ASSIGNMENTS: &[Assignment] = &[")?;
 
     let quote = Regex::new(r#"["]"#)?;
     for rec in records {
         let addr = rec.addr.to_be_bytes();
-        write!(out, "    Assignment {{\n")?;
-        write!(out, "        range: Subnet::new(MacAddress::new([0x{:02X}, 0x{:02X}, 0x{:02X}, 0x{:02X}, 0x{:02X}, 0x{:02X}]), {}),\n", addr[2], addr[3], addr[4], addr[5], addr[6], addr[7], rec.prefix_len)?;
-        write!(out, "        abbrv: \"{}\",\n", abbrv.abbrv(&rec.name)?)?;
-        write!(
+        writeln!(out, "    Assignment {{")?;
+        writeln!(out, "        range: Subnet::new(MacAddress::new([0x{:02X}, 0x{:02X}, 0x{:02X}, 0x{:02X}, 0x{:02X}, 0x{:02X}]), {}),", addr[2], addr[3], addr[4], addr[5], addr[6], addr[7], rec.prefix_len)?;
+        writeln!(out, "        abbrv: \"{}\",", abbrv.abbrv(&rec.name)?)?;
+        writeln!(
             out,
-            "        name: \"{}\",\n",
+            "        name: \"{}\",",
             quote.replace_all(&rec.name, "\\\"")
         )?;
-        write!(out, "    }},\n")?;
+        writeln!(out, "    }},")?;
     }
 
-    write!(out, "];\n")?;
+    writeln!(out, "];")?;
 
     Ok(())
 }
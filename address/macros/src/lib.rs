@@ -13,7 +13,7 @@ fn get_str(input: TokenStream) -> String {
         panic!("Expected an address string literal");
     };
 
-    let TokenTree::Group(grp) = grp  else {
+    let TokenTree::Group(grp) = grp else {
         panic!("Expected an address string literal");
     };
 
@@ -30,11 +30,11 @@ fn get_str(input: TokenStream) -> String {
         panic!("Expected an address string literal");
     };
 
-    if let Some(_) = subiter.next() {
+    if subiter.next().is_some() {
         panic!("Unexpected token after address string literal");
     };
 
-    if let Some(_) = iter.next() {
+    if iter.next().is_some() {
         panic!("Unexpected token after address string literal");
     };
 
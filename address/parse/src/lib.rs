@@ -58,7 +58,7 @@ pub fn parse_ipv6_subnet(s: &str) -> Result<([u8; 16], u32), SubnetParseError> {
 }
 
 pub fn parse_hw(s: &str, addr: &mut [u8]) -> Result<(), AddressParseError> {
-    let mut iter = s.split(|c: char| c == ':' || c == '-');
+    let mut iter = s.split([':', '-']);
     for byte in addr.iter_mut() {
         *byte = u8::from_str_radix(iter.next().ok_or(AddressParseError::InvalidLength)?, 16)?;
     }
@@ -120,7 +120,7 @@ pub fn parse_ipv6(s: &str) -> Result<[u8; 16], AddressParseError> {
                 }
                 Err(e) => {
                     if idx == 12 {
-                        if let Some(_) = iter.next() {
+                        if iter.next().is_some() {
                             return Err(AddressParseError::InvalidLength);
                         }
                         let ipv4 = parse_ipv4(word)?;
@@ -138,7 +138,7 @@ pub fn parse_ipv6(s: &str) -> Result<[u8; 16], AddressParseError> {
     }
 
     if let Some(second) = iter.next() {
-        if let Some(_) = iter.next() {
+        if iter.next().is_some() {
             return Err(AddressParseError::InvalidLength);
         }
 
@@ -161,7 +161,7 @@ pub fn parse_ipv6(s: &str) -> Result<[u8; 16], AddressParseError> {
                     }
                     Err(e) => {
                         if idx == 15 {
-                            if let Some(_) = iter.next() {
+                            if iter.next().is_some() {
                                 return Err(AddressParseError::InvalidLength);
                             }
                             let ipv4 = parse_ipv4(word)?;
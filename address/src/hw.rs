@@ -14,6 +14,10 @@ use crate::{Address, AddressParseError};
 
 use sniffle_address_parse::parse_hw;
 
+/// A 64-bit extended unique identifier, such as the EUI address of a pcapng
+/// interface. See `MacAddress::to_eui64`.
+pub type EuiAddress = HwAddress<8>;
+
 /// Representation of generic hardware address
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
@@ -167,7 +171,7 @@ impl<const LEN: usize> BitAnd<HwAddress<LEN>> for &HwAddress<LEN> {
     type Output = HwAddress<LEN>;
 
     fn bitand(self, rhs: HwAddress<LEN>) -> Self::Output {
-        self.clone().bitand(rhs)
+        (*self).bitand(rhs)
     }
 }
 
@@ -175,7 +179,7 @@ impl<const LEN: usize> BitAnd for &HwAddress<LEN> {
     type Output = HwAddress<LEN>;
 
     fn bitand(self, rhs: Self) -> Self::Output {
-        self.clone().bitand(rhs)
+        (*self).bitand(rhs)
     }
 }
 
@@ -197,7 +201,7 @@ impl<const LEN: usize> BitOr<&HwAddress<LEN>> for HwAddress<LEN> {
     type Output = Self;
 
     fn bitor(mut self, rhs: &Self) -> Self::Output {
-        self &= rhs;
+        self |= rhs;
         self
     }
 }
@@ -214,7 +218,7 @@ impl<const LEN: usize> BitOr<HwAddress<LEN>> for &HwAddress<LEN> {
     type Output = HwAddress<LEN>;
 
     fn bitor(self, rhs: HwAddress<LEN>) -> Self::Output {
-        self.clone().bitor(rhs)
+        (*self).bitor(rhs)
     }
 }
 
@@ -222,7 +226,7 @@ impl<const LEN: usize> BitOr for &HwAddress<LEN> {
     type Output = HwAddress<LEN>;
 
     fn bitor(self, rhs: Self) -> Self::Output {
-        self.clone().bitor(rhs)
+        (*self).bitor(rhs)
     }
 }
 
@@ -244,7 +248,7 @@ impl<const LEN: usize> BitXor<&HwAddress<LEN>> for HwAddress<LEN> {
     type Output = Self;
 
     fn bitxor(mut self, rhs: &Self) -> Self::Output {
-        self &= rhs;
+        self ^= rhs;
         self
     }
 }
@@ -261,7 +265,7 @@ impl<const LEN: usize> BitXor<HwAddress<LEN>> for &HwAddress<LEN> {
     type Output = HwAddress<LEN>;
 
     fn bitxor(self, rhs: HwAddress<LEN>) -> Self::Output {
-        self.clone().bitxor(rhs)
+        (*self).bitxor(rhs)
     }
 }
 
@@ -269,7 +273,7 @@ impl<const LEN: usize> BitXor for &HwAddress<LEN> {
     type Output = HwAddress<LEN>;
 
     fn bitxor(self, rhs: Self) -> Self::Output {
-        self.clone().bitxor(rhs)
+        (*self).bitxor(rhs)
     }
 }
 
@@ -288,7 +292,7 @@ impl<const LEN: usize> Not for &HwAddress<LEN> {
     type Output = HwAddress<LEN>;
 
     fn not(self) -> Self::Output {
-        self.clone().not()
+        (*self).not()
     }
 }
 
@@ -378,11 +382,11 @@ impl<const LEN: usize> Address for HwAddress<LEN> {
                 break;
             }
         }
-        return prefix_len;
+        prefix_len
     }
 
     fn next_addr(&self) -> Self {
-        let mut addr = self.0.clone();
+        let mut addr = self.0;
         let mut idx = LEN - 1;
         loop {
             let (val, ovrflo) = addr[idx].overflowing_add(1);
@@ -396,7 +400,7 @@ impl<const LEN: usize> Address for HwAddress<LEN> {
     }
 
     fn prev_addr(&self) -> Self {
-        let mut addr = self.0.clone();
+        let mut addr = self.0;
         let mut idx = LEN - 1;
         loop {
             let (val, ovrflo) = addr[idx].overflowing_sub(1);
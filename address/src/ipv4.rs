@@ -24,12 +24,53 @@ pub struct Ipv4Address([u8; 4]);
 /// Representtion of an IPv4 subnet
 pub type Ipv4Subnet = Subnet<Ipv4Address>;
 
+impl Ipv4Subnet {
+    const fn mask_value(&self) -> u32 {
+        match self.prefix_len {
+            0 => 0,
+            len if len >= 32 => u32::MAX,
+            len => u32::MAX << (32 - len),
+        }
+    }
+
+    /// `Subnet::mask`, usable in const contexts
+    pub const fn const_mask(&self) -> Ipv4Address {
+        Ipv4Address::from_value(self.mask_value())
+    }
+
+    /// `Subnet::base_addr`, usable in const contexts
+    pub const fn const_base_addr(&self) -> Ipv4Address {
+        Ipv4Address::from_value(self.base.value() & self.mask_value())
+    }
+
+    /// `Subnet::contains`, usable in const contexts
+    pub const fn const_contains(&self, addr: &Ipv4Address) -> bool {
+        let mask = self.mask_value();
+        addr.value() & mask == self.base.value() & mask
+    }
+}
+
 impl Ipv4Address {
-    fn value(&self) -> u32 {
+    const fn value(&self) -> u32 {
         u32::from_be_bytes(self.0)
     }
 
-    fn from_value(val: u32) -> Self {
+    const fn within(&self, subnet: &Ipv4Subnet) -> bool {
+        subnet.const_contains(self)
+    }
+
+    const fn within_any(&self, subnets: &[Ipv4Subnet]) -> bool {
+        let mut i = 0;
+        while i < subnets.len() {
+            if self.within(&subnets[i]) {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    const fn from_value(val: u32) -> Self {
         Self(val.to_be_bytes())
     }
 
@@ -59,6 +100,16 @@ impl Ipv4Address {
         ipv4_subnet!("192.168.0.0/16"),
     ];
 
+    /// Addresses of "this network", which may only be used as a source
+    ///
+    /// `0.0.0.0/8`
+    pub const THIS_NETWORK_SUBNET: Ipv4Subnet = ipv4_subnet!("0.0.0.0/8");
+
+    /// Addresses reserved for IETF protocol assignments
+    ///
+    /// `192.0.0.0/24`
+    pub const IETF_PROTOCOL_SUBNET: Ipv4Subnet = ipv4_subnet!("192.0.0.0/24");
+
     /// Addresses reserved for loopback
     ///
     /// `127.0.0.0/8`
@@ -122,23 +173,23 @@ impl Ipv4Address {
     }
 
     /// Checks if the address is reserved for private networks
-    pub fn is_private(&self) -> bool {
-        for subnet in Self::PRIVATE_SUBNETS.iter() {
-            if subnet.contains(self) {
-                return true;
-            }
-        }
-        false
+    pub const fn is_private(&self) -> bool {
+        self.within_any(&Self::PRIVATE_SUBNETS)
+    }
+
+    /// Checks if the address is the unspecified address, `0.0.0.0`
+    pub const fn is_unspecified(&self) -> bool {
+        self.value() == 0
     }
 
     /// Checks if the address is reserved for loopback
-    pub fn is_loopback(&self) -> bool {
-        Self::LOOPBACK_SUBNET.contains(self)
+    pub const fn is_loopback(&self) -> bool {
+        self.within(&Self::LOOPBACK_SUBNET)
     }
 
     /// Checks if the address is reserved for multicast
-    pub fn is_multicast(&self) -> bool {
-        Self::MULTICAST_SUBNET.contains(self)
+    pub const fn is_multicast(&self) -> bool {
+        self.within(&Self::MULTICAST_SUBNET)
     }
 
     /// Checks if the address is the subnet broadcast address
@@ -149,33 +200,59 @@ impl Ipv4Address {
     }
 
     /// Checks if the address is a link local address
-    pub fn is_link_local(&self) -> bool {
-        Self::LINK_LOCAL_SUBNET.contains(self)
+    pub const fn is_link_local(&self) -> bool {
+        self.within(&Self::LINK_LOCAL_SUBNET)
     }
 
     /// Checks if the address is in the shared address space
-    pub fn is_shared(&self) -> bool {
-        Self::SHARED_SUBNET.contains(self)
+    pub const fn is_shared(&self) -> bool {
+        self.within(&Self::SHARED_SUBNET)
     }
 
     /// Checks if the address is reserved for benchmarking
-    pub fn is_benchmarking(&self) -> bool {
-        Self::BENCHMARKING_SUBNET.contains(self)
+    pub const fn is_benchmarking(&self) -> bool {
+        self.within(&Self::BENCHMARKING_SUBNET)
     }
 
     /// Checks if the address is reserved for future use
-    pub fn is_reserved(&self) -> bool {
-        Self::RESERVED_SUBNET.contains(self)
+    pub const fn is_reserved(&self) -> bool {
+        self.within(&Self::RESERVED_SUBNET)
     }
 
     /// Checks if the address is reserved for documentation purposes
-    pub fn is_documentation(&self) -> bool {
-        for subnet in Self::DOCUMENTATION_SUBNETS.iter() {
-            if subnet.contains(self) {
-                return true;
-            }
+    pub const fn is_documentation(&self) -> bool {
+        self.within_any(&Self::DOCUMENTATION_SUBNETS)
+    }
+
+    /// Checks if the address is globally reachable
+    ///
+    /// This is true unless the address is in one of the special purpose
+    /// ranges above, or is the limited broadcast address. In
+    /// `IETF_PROTOCOL_SUBNET`, only the globally reachable PCP and TURN
+    /// anycast addresses, `192.0.0.9` and `192.0.0.10`, are global.
+    ///
+    /// ## Example
+    /// ```
+    /// # use sniffle_address::{ipv4, Ipv4Address};
+    /// assert!(ipv4!("8.8.8.8").is_global());
+    /// assert!(ipv4!("192.0.0.9").is_global());
+    /// assert!(!ipv4!("10.1.2.3").is_global());
+    /// assert!(!ipv4!("255.255.255.255").is_global());
+    /// ```
+    pub const fn is_global(&self) -> bool {
+        let value = self.value();
+        if self.within(&Self::IETF_PROTOCOL_SUBNET) {
+            return value & 0xff == 9 || value & 0xff == 10;
         }
-        false
+        !(self.within(&Self::THIS_NETWORK_SUBNET)
+            || self.is_private()
+            || self.is_shared()
+            || self.is_loopback()
+            || self.is_link_local()
+            || self.is_documentation()
+            || self.is_benchmarking()
+            || self.is_reserved()
+            || value == u32::MAX)
     }
 }
 
@@ -251,7 +328,7 @@ impl Eq for Ipv4Address {}
 
 impl PartialOrd for Ipv4Address {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.value().partial_cmp(&other.value())
+        Some(self.cmp(other))
     }
 
     fn lt(&self, other: &Self) -> bool {
@@ -332,13 +409,13 @@ impl BitAnd<Ipv4Address> for &Ipv4Address {
 
 impl BitAndAssign for Ipv4Address {
     fn bitand_assign(&mut self, rhs: Self) {
-        *self = &*self & &rhs;
+        *self = *self & rhs;
     }
 }
 
 impl BitAndAssign<&Ipv4Address> for Ipv4Address {
     fn bitand_assign(&mut self, rhs: &Self) {
-        *self = &*self & rhs;
+        *self = *self & rhs;
     }
 }
 
@@ -376,13 +453,13 @@ impl BitOr<Ipv4Address> for &Ipv4Address {
 
 impl BitOrAssign for Ipv4Address {
     fn bitor_assign(&mut self, rhs: Self) {
-        *self = &*self | &rhs;
+        *self = *self | rhs;
     }
 }
 
 impl BitOrAssign<&Ipv4Address> for Ipv4Address {
     fn bitor_assign(&mut self, rhs: &Self) {
-        *self = &*self | rhs;
+        *self = *self | rhs;
     }
 }
 
@@ -420,13 +497,13 @@ impl BitXor<Ipv4Address> for &Ipv4Address {
 
 impl BitXorAssign for Ipv4Address {
     fn bitxor_assign(&mut self, rhs: Self) {
-        *self = &*self ^ &rhs;
+        *self = *self ^ rhs;
     }
 }
 
 impl BitXorAssign<&Ipv4Address> for Ipv4Address {
     fn bitxor_assign(&mut self, rhs: &Self) {
-        *self = &*self ^ rhs;
+        *self = *self ^ rhs;
     }
 }
 
@@ -564,6 +641,28 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn classification() {
+        const { assert!(ipv4!("172.31.0.1").is_private()) };
+        assert!(!ipv4!("172.32.0.1").is_private());
+        assert!(ipv4!("0.0.0.0").is_unspecified());
+        assert!(ipv4!("127.1.2.3").is_loopback());
+        assert!(ipv4!("239.255.255.250").is_multicast());
+        assert!(ipv4!("169.254.1.1").is_link_local());
+        assert!(ipv4!("198.51.100.7").is_documentation());
+        assert!(ipv4!("1.1.1.1").is_global());
+        for addr in [
+            "0.1.2.3",
+            "100.64.0.1",
+            "192.0.0.1",
+            "198.18.0.1",
+            "240.0.0.1",
+            "169.254.0.1",
+        ] {
+            assert!(!Addr::from_str(addr).unwrap().is_global(), "{}", addr);
+        }
+    }
+
     #[test]
     fn addr_to_str() {
         assert_eq!(Addr::new([0, 0, 0, 0]).to_string(), "0.0.0.0");
@@ -617,6 +716,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn const_subnet_ops() {
+        const SUBNET: Subnet = ipv4_subnet!("10.1.2.3/16");
+        const MASK: Addr = SUBNET.const_mask();
+        const BASE: Addr = SUBNET.const_base_addr();
+        const CONTAINED: bool = SUBNET.const_contains(&ipv4!("10.1.200.1"));
+        const NOT_CONTAINED: bool = SUBNET.const_contains(&ipv4!("10.2.0.1"));
+
+        assert_eq!(MASK, SUBNET.mask());
+        assert_eq!(BASE, SUBNET.base_addr());
+        const { assert!(CONTAINED) };
+        const { assert!(!NOT_CONTAINED) };
+        assert_eq!(
+            Subnet::new(Addr::new([1, 2, 3, 4]), 0).const_mask(),
+            Addr::new([0, 0, 0, 0])
+        );
+        assert_eq!(
+            Subnet::new(Addr::new([1, 2, 3, 4]), 32).const_mask(),
+            Addr::new([0xFF, 0xFF, 0xFF, 0xFF])
+        );
+    }
+
     #[test]
     fn from_prefix_len() {
         assert_eq!(Addr::from_prefix_len(0), Addr::new([0, 0, 0, 0]));
@@ -12,8 +12,6 @@ use sniffle_ende::{
     nom::combinator::map,
 };
 
-use bytemuck;
-
 use crate::{ipv6, ipv6_subnet, Address, AddressParseError, Subnet};
 
 use sniffle_address_parse::parse_ipv6;
@@ -26,12 +24,42 @@ pub struct Ipv6Address([u8; 16]);
 /// Representtion of an IPv4 subnet
 pub type Ipv6Subnet = Subnet<Ipv6Address>;
 
+impl Ipv6Subnet {
+    const fn mask_value(&self) -> u128 {
+        match self.prefix_len {
+            0 => 0,
+            len if len >= 128 => u128::MAX,
+            len => u128::MAX << (128 - len),
+        }
+    }
+
+    /// `Subnet::mask`, usable in const contexts
+    pub const fn const_mask(&self) -> Ipv6Address {
+        Ipv6Address::from_value(self.mask_value())
+    }
+
+    /// `Subnet::base_addr`, usable in const contexts
+    pub const fn const_base_addr(&self) -> Ipv6Address {
+        Ipv6Address::from_value(self.base.value() & self.mask_value())
+    }
+
+    /// `Subnet::contains`, usable in const contexts
+    pub const fn const_contains(&self, addr: &Ipv6Address) -> bool {
+        let mask = self.mask_value();
+        addr.value() & mask == self.base.value() & mask
+    }
+}
+
 impl Ipv6Address {
-    fn value(&self) -> u128 {
+    const fn value(&self) -> u128 {
         u128::from_be_bytes(self.0)
     }
 
-    fn from_value(val: u128) -> Self {
+    const fn within(&self, subnet: &Ipv6Subnet) -> bool {
+        subnet.const_contains(self)
+    }
+
+    const fn from_value(val: u128) -> Self {
         Self(val.to_be_bytes())
     }
 
@@ -60,6 +88,26 @@ impl Ipv6Address {
     /// `fe80::/10`
     pub const UNICAST_LINK_LOCAL_SUBNET: Ipv6Subnet = ipv6_subnet!("fe80::/10");
 
+    /// Addresses reserved for discard-only use
+    ///
+    /// `100::/64`
+    pub const DISCARD_SUBNET: Ipv6Subnet = ipv6_subnet!("100::/64");
+
+    /// Addresses reserved for IETF protocol assignments
+    ///
+    /// `2001::/23`
+    pub const IETF_PROTOCOL_SUBNET: Ipv6Subnet = ipv6_subnet!("2001::/23");
+
+    /// Addresses reserved for the 6to4 transition mechanism
+    ///
+    /// `2002::/16`
+    pub const SIX_TO_FOUR_SUBNET: Ipv6Subnet = ipv6_subnet!("2002::/16");
+
+    /// Addresses reserved for local use IPv4/IPv6 translation
+    ///
+    /// `64:ff9b:1::/48`
+    pub const LOCAL_TRANSLATION_SUBNET: Ipv6Subnet = ipv6_subnet!("64:ff9b:1::/48");
+
     /// Addresses reserved for documentation
     ///
     /// `2001:db8::/32`
@@ -140,84 +188,142 @@ impl Ipv6Address {
     }
 
     /// Returns true if this address is the loopback address, `::1`
-    pub fn is_loopback(&self) -> bool {
-        return *self == Self::LOCALHOST;
+    pub const fn is_loopback(&self) -> bool {
+        self.value() == 1
+    }
+
+    /// Returns true if this is the unspecified address, `::`
+    pub const fn is_unspecified(&self) -> bool {
+        self.value() == 0
     }
 
     /// Returns true if this is a unique local address
-    pub fn is_unique_local(&self) -> bool {
-        Self::UNIQUE_LOCAL_SUBNET.contains(self)
+    pub const fn is_unique_local(&self) -> bool {
+        self.within(&Self::UNIQUE_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a multicast address
-    pub fn is_multicast(&self) -> bool {
-        Self::MULTICAST_SUBNET.contains(self)
+    pub const fn is_multicast(&self) -> bool {
+        self.within(&Self::MULTICAST_SUBNET)
     }
 
     /// Returns true if this is a unicast link-local address
-    pub fn is_unicast_link_local(&self) -> bool {
-        Self::UNICAST_LINK_LOCAL_SUBNET.contains(self)
+    pub const fn is_unicast_link_local(&self) -> bool {
+        self.within(&Self::UNICAST_LINK_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a reserved for documentation address
-    pub fn is_documentation(&self) -> bool {
-        Self::DOCUMENTATION_SUBNET.contains(self)
+    pub const fn is_documentation(&self) -> bool {
+        self.within(&Self::DOCUMENTATION_SUBNET)
     }
 
     /// Returns true if this is a reserved for benchmarking address
-    pub fn is_benchmarking(&self) -> bool {
-        Self::BENCHMARKING_SUBNET.contains(self)
+    pub const fn is_benchmarking(&self) -> bool {
+        self.within(&Self::BENCHMARKING_SUBNET)
     }
 
     /// Returns true if this is a interface-local scoped multicast address
-    pub fn is_multicast_interface_local(&self) -> bool {
-        Self::MULTICAST_INTERFACE_LOCAL_SUBNET.contains(self)
+    pub const fn is_multicast_interface_local(&self) -> bool {
+        self.within(&Self::MULTICAST_INTERFACE_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a link-local scoped multicast address
-    pub fn is_multicast_link_local(&self) -> bool {
-        Self::MULTICAST_LINK_LOCAL_SUBNET.contains(self)
+    pub const fn is_multicast_link_local(&self) -> bool {
+        self.within(&Self::MULTICAST_LINK_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a realm-local scoped multicast address
-    pub fn is_multicast_realm_local(&self) -> bool {
-        Self::MULTICAST_REALM_LOCAL_SUBNET.contains(self)
+    pub const fn is_multicast_realm_local(&self) -> bool {
+        self.within(&Self::MULTICAST_REALM_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a admin-local scoped multicast address
-    pub fn is_multicast_admin_local(&self) -> bool {
-        Self::MULTICAST_ADMIN_LOCAL_SUBNET.contains(self)
+    pub const fn is_multicast_admin_local(&self) -> bool {
+        self.within(&Self::MULTICAST_ADMIN_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a site-local scoped multicast address
-    pub fn is_multicast_site_local(&self) -> bool {
-        Self::MULTICAST_SITE_LOCAL_SUBNET.contains(self)
+    pub const fn is_multicast_site_local(&self) -> bool {
+        self.within(&Self::MULTICAST_SITE_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a organization-local scoped multicast address
-    pub fn is_multicast_organization_local(&self) -> bool {
-        Self::MULTICAST_ORGANIZATION_LOCAL_SUBNET.contains(self)
+    pub const fn is_multicast_organization_local(&self) -> bool {
+        self.within(&Self::MULTICAST_ORGANIZATION_LOCAL_SUBNET)
     }
 
     /// Returns true if this is a global scoped multicast address
-    pub fn is_multicast_global(&self) -> bool {
-        Self::MULTICAST_GLOBAL_SUBNET.contains(self)
+    pub const fn is_multicast_global(&self) -> bool {
+        self.within(&Self::MULTICAST_GLOBAL_SUBNET)
     }
 
     /// Returns true if this is an IPv4 mapped address
-    pub fn is_ipv4_mapped(&self) -> bool {
-        Self::IPV4_MAPPED_SUBNET.contains(self)
+    pub const fn is_ipv4_mapped(&self) -> bool {
+        self.within(&Self::IPV4_MAPPED_SUBNET)
     }
 
     /// Returns true if this is an IPv4 compatible address
-    pub fn is_ipv4_compatible(&self) -> bool {
-        Self::IPV4_COMPAT_SUBNET.contains(self)
+    pub const fn is_ipv4_compatible(&self) -> bool {
+        self.within(&Self::IPV4_COMPAT_SUBNET)
     }
 
     /// Returns true if this is a unicast address
-    pub fn is_unicast(&self) -> bool {
+    pub const fn is_unicast(&self) -> bool {
         !self.is_multicast()
     }
+
+    /// Returns true if this is a unique local address. Unique local
+    /// addresses are the IPv6 counterpart of IPv4 private addresses.
+    pub const fn is_private(&self) -> bool {
+        self.is_unique_local()
+    }
+
+    /// Returns true if this is a link-local address, either unicast or
+    /// multicast
+    pub const fn is_link_local(&self) -> bool {
+        self.is_unicast_link_local() || self.is_multicast_link_local()
+    }
+
+    /// Returns true if this address is globally reachable
+    ///
+    /// Multicast addresses are global if they have global scope. Unicast
+    /// addresses are global unless they are in one of the special purpose
+    /// ranges above. In `IETF_PROTOCOL_SUBNET`, only the globally reachable
+    /// assignments, such as the PCP and TURN anycast addresses and AMT and
+    /// ORCHIDv2 ranges, are global.
+    ///
+    /// ## Example
+    /// ```
+    /// # use sniffle_address::{ipv6, Ipv6Address};
+    /// assert!(ipv6!("2606:4700::1111").is_global());
+    /// assert!(ipv6!("ff0e::1").is_global());
+    /// assert!(!ipv6!("fe80::1").is_global());
+    /// assert!(!ipv6!("2001:db8::1").is_global());
+    /// ```
+    pub const fn is_global(&self) -> bool {
+        if self.is_multicast() {
+            return self.is_multicast_global();
+        }
+        if self.within(&Self::IETF_PROTOCOL_SUBNET) {
+            let value = self.value();
+            // 2001:1::1, 2001:1::2, 2001:3::/32, 2001:4:112::/48, 2001:20::/27
+            return value == 0x2001_0001_0000_0000_0000_0000_0000_0001
+                || value == 0x2001_0001_0000_0000_0000_0000_0000_0002
+                || value >> 96 == 0x2001_0003
+                || value >> 80 == 0x2001_0004_0112
+                || (value >> 101) == (0x2001_0020 >> 5);
+        }
+        !(self.is_unspecified()
+            || self.is_loopback()
+            || self.is_ipv4_mapped()
+            || self.within(&Self::LOCAL_TRANSLATION_SUBNET)
+            || self.within(&Self::DISCARD_SUBNET)
+            || self.within(&Self::SIX_TO_FOUR_SUBNET)
+            || self.is_unique_local()
+            || self.is_unicast_link_local()
+            || self.is_documentation()
+            || self.is_benchmarking())
+    }
 }
 
 impl From<crate::Ipv4Address> for Ipv6Address {
@@ -322,7 +428,7 @@ impl Eq for Ipv6Address {}
 
 impl PartialOrd for Ipv6Address {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.value().partial_cmp(&other.value())
+        Some(self.cmp(other))
     }
 
     fn lt(&self, other: &Self) -> bool {
@@ -403,13 +509,13 @@ impl BitAnd<Ipv6Address> for &Ipv6Address {
 
 impl BitAndAssign for Ipv6Address {
     fn bitand_assign(&mut self, rhs: Self) {
-        *self = &*self & &rhs;
+        *self = *self & rhs;
     }
 }
 
 impl BitAndAssign<&Ipv6Address> for Ipv6Address {
     fn bitand_assign(&mut self, rhs: &Self) {
-        *self = &*self & rhs;
+        *self = *self & rhs;
     }
 }
 
@@ -447,13 +553,13 @@ impl BitOr<Ipv6Address> for &Ipv6Address {
 
 impl BitOrAssign for Ipv6Address {
     fn bitor_assign(&mut self, rhs: Self) {
-        *self = &*self | &rhs;
+        *self = *self | rhs;
     }
 }
 
 impl BitOrAssign<&Ipv6Address> for Ipv6Address {
     fn bitor_assign(&mut self, rhs: &Self) {
-        *self = &*self | rhs;
+        *self = *self | rhs;
     }
 }
 
@@ -491,13 +597,13 @@ impl BitXor<Ipv6Address> for &Ipv6Address {
 
 impl BitXorAssign for Ipv6Address {
     fn bitxor_assign(&mut self, rhs: Self) {
-        *self = &*self ^ &rhs;
+        *self = *self ^ rhs;
     }
 }
 
 impl BitXorAssign<&Ipv6Address> for Ipv6Address {
     fn bitxor_assign(&mut self, rhs: &Self) {
-        *self = &*self ^ rhs;
+        *self = *self ^ rhs;
     }
 }
 
@@ -698,6 +804,29 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn classification() {
+        const { assert!(Addr::LOCALHOST.is_loopback()) };
+        assert!(Addr::UNSPECIFIED.is_unspecified());
+        assert!(Addr::from_str("fd00::1").unwrap().is_private());
+        assert!(Addr::from_str("fe80::1").unwrap().is_link_local());
+        assert!(Addr::from_str("ff02::1").unwrap().is_link_local());
+        assert!(!Addr::from_str("ff02::1").unwrap().is_global());
+        assert!(Addr::from_str("2001:4860::8888").unwrap().is_global());
+        assert!(Addr::from_str("2001:1::1").unwrap().is_global());
+        assert!(Addr::from_str("2001:20::1").unwrap().is_global());
+        for addr in [
+            "::",
+            "::1",
+            "::ffff:102:304",
+            "100::1",
+            "2001::1",
+            "2002::1",
+        ] {
+            assert!(!Addr::from_str(addr).unwrap().is_global(), "{}", addr);
+        }
+    }
+
     #[test]
     fn addr_to_str() {
         assert_eq!(
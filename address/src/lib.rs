@@ -238,7 +238,7 @@ impl<A: Address> Subnet<A> {
     }
 
     /// The prefix length of the subnet
-    pub fn prefix_len(&self) -> u32 {
+    pub const fn prefix_len(&self) -> u32 {
         self.prefix_len
     }
 
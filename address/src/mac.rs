@@ -16,7 +16,7 @@ use sniffle_ende::{
 
 use sniffle_uint::{IntoMasked, U48};
 
-use crate::{Address, AddressParseError, HwAddress, Subnet};
+use crate::{Address, AddressParseError, EuiAddress, HwAddress, Ipv6Address, Subnet};
 
 use sniffle_address_parse::parse_hw;
 
@@ -28,7 +28,7 @@ use sniffle_address_parse::parse_hw;
 /// does not. HwAddress is intended for use with generic addresses, and MacAddress is
 /// specifically for MAC addresses. MacAddress and HwAddress<6> can be infallibly
 /// converted from one to the other if desired.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 #[repr(transparent)]
 pub struct MacAddress([u8; 8]);
 
@@ -105,6 +105,76 @@ impl MacAddress {
         ])
     }
 
+    /// Converts a MAC address to an EUI-64 address
+    ///
+    /// This conversion is performed by inserting bytes `ff:fe` directly in the
+    /// middle of the MAC address. Unlike `to_eui`, which produces the
+    /// modified EUI-64 used in IPv6 interface identifiers, the 7th bit is left
+    /// as is.
+    ///
+    /// ## Example
+    /// ```
+    /// # use sniffle_address::{hw, mac, MacAddress};
+    /// assert_eq!(mac!("12:34:56:78:9a:bc").to_eui64(), hw!("12:34:56:ff:fe:78:9a:bc"));
+    /// ```
+    pub fn to_eui64(&self) -> EuiAddress {
+        HwAddress::new([
+            self.0[2], self.0[3], self.0[4], 0xff, 0xfe, self.0[5], self.0[6], self.0[7],
+        ])
+    }
+
+    /// Returns the IPv6 link-local address with an interface identifier
+    /// derived from this address, as with SLAAC
+    ///
+    /// ## Example
+    /// ```
+    /// # use sniffle_address::{ipv6, mac, MacAddress};
+    /// assert_eq!(
+    ///     mac!("12:34:56:78:9a:bc").to_ipv6_link_local(),
+    ///     ipv6!("fe80::1034:56ff:fe78:9abc")
+    /// );
+    /// ```
+    pub fn to_ipv6_link_local(&self) -> Ipv6Address {
+        let mut bytes = [0u8; 16];
+        bytes[0] = 0xfe;
+        bytes[1] = 0x80;
+        bytes[8..].copy_from_slice(&self.to_eui()[..]);
+        Ipv6Address::new(bytes)
+    }
+
+    /// Returns true if this is a unicast address, i.e. the least
+    /// significant bit of the first byte is clear
+    pub fn is_unicast(&self) -> bool {
+        !self.is_multicast()
+    }
+
+    /// Returns true if this is a multicast address, including the
+    /// broadcast address
+    ///
+    /// ## Example
+    /// ```
+    /// # use sniffle_address::{mac, MacAddress};
+    /// assert!(mac!("01:00:5e:00:00:01").is_multicast());
+    /// assert!(MacAddress::BROADCAST.is_multicast());
+    /// assert!(mac!("00:00:5e:00:00:01").is_unicast());
+    /// ```
+    pub fn is_multicast(&self) -> bool {
+        self.0[2] & 0x01 != 0
+    }
+
+    /// Returns true if this address is locally administered, rather than
+    /// universally administered by an OUI assignment
+    ///
+    /// ## Example
+    /// ```
+    /// # use sniffle_address::{mac, MacAddress};
+    /// assert!(mac!("02:00:00:00:00:01").is_locally_administered());
+    /// assert!(!mac!("00:00:5e:00:00:01").is_locally_administered());
+    /// ```
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[2] & 0x02 != 0
+    }
+
     /// Returns the OUI assignment corresponding to this address, if any.
     ///
     /// NOTE: Runs in `O(log(n))` time, where `n` is the length of `oui::Assignment::DATABASE`.
@@ -124,12 +194,6 @@ impl MacAddress {
     }
 }
 
-impl Default for MacAddress {
-    fn default() -> Self {
-        Self([0u8; 8])
-    }
-}
-
 impl From<[u8; 6]> for MacAddress {
     fn from(raw: [u8; 6]) -> Self {
         Self::new(raw)
@@ -193,7 +257,7 @@ impl Eq for MacAddress {}
 
 impl PartialOrd for MacAddress {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.value().partial_cmp(&other.value())
+        Some(self.cmp(other))
     }
 
     fn lt(&self, other: &Self) -> bool {
@@ -274,7 +338,7 @@ impl BitAnd for MacAddress {
 
 impl BitAndAssign<&MacAddress> for MacAddress {
     fn bitand_assign(&mut self, rhs: &Self) {
-        *self = &*self & rhs;
+        *self = *self & rhs;
     }
 }
 
@@ -318,7 +382,7 @@ impl BitOr for MacAddress {
 
 impl BitOrAssign<&MacAddress> for MacAddress {
     fn bitor_assign(&mut self, rhs: &Self) {
-        *self = &*self | rhs;
+        *self = *self | rhs;
     }
 }
 
@@ -362,7 +426,7 @@ impl BitXor for MacAddress {
 
 impl BitXorAssign<&MacAddress> for MacAddress {
     fn bitxor_assign(&mut self, rhs: &Self) {
-        *self = &*self ^ rhs;
+        *self = *self ^ rhs;
     }
 }
 
@@ -412,30 +476,12 @@ impl Decode for MacAddress {
     fn decode(buf: &[u8]) -> DResult<'_, Self> {
         map(<[u8; 6]>::decode, Self::from)(buf)
     }
-
-    fn decode_many<const LEN: usize>(buf: &[u8]) -> DResult<'_, [Self; LEN]> {
-        unsafe { cast(buf) }
-    }
 }
 
 impl Encode for MacAddress {
     fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> std::io::Result<()> {
         encoder.encode(&self.0[2..]).map(|_| ())
     }
-
-    fn encode_many<'a, W: Encoder<'a> + ?Sized>(
-        slice: &[Self],
-        encoder: &mut W,
-    ) -> std::io::Result<()> {
-        unsafe {
-            encoder
-                .encode(std::slice::from_raw_parts(
-                    slice.as_ptr() as *const u8,
-                    slice.len() * 4,
-                ))
-                .map(|_| ())
-        }
-    }
 }
 
 impl Address for MacAddress {
@@ -0,0 +1,666 @@
+//! Rewrites captures so they can be shared, for example when filing a bug
+//! report, without revealing the addresses of the hosts involved or the
+//! data they exchanged.
+//!
+//! MAC, IPv4 and IPv6 addresses are replaced with pseudonyms derived from
+//! a secret key, so an address is replaced with the same pseudonym
+//! everywhere in a capture, and in every capture rewritten with the same
+//! key. Broadcast, multicast, loopback and unspecified addresses are kept,
+//! since they reveal nothing about the hosts. IPv4 addresses can optionally
+//! be mapped such that addresses sharing a prefix still share a prefix of
+//! the same length after anonymization, which keeps subnets recognizable.
+//!
+//! Addresses are rewritten in Ethernet (including VLAN tags), Linux cooked
+//! capture, ARP, IPv4, IPv6, and the IP header quoted by ICMP errors.
+//! Packets of other link types are copied unchanged, except for payload
+//! zeroing. Timestamps, lengths and application layer content, such as
+//! DNS names, are not modified unless payloads are zeroed.
+
+use sniffle_core::{Error, Ipv4Address, Ipv6Address, LinkType, MacAddress, RawPacket};
+use sniffle_core::{SniffRaw, Transmit};
+use sniffle_utils::checksum::{
+    IpPseudoHeader, Ipv4PseudoHeader, Ipv6PseudoHeader, U16OnesComplement,
+};
+use sniffle_utils::hash::Sha256;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+
+#[cfg(feature = "fs")]
+use crate::{pcap, pcapng, FileSniffer, Sniffer};
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+
+const PROTO_ICMP: u8 = 1;
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+const PROTO_ICMPV6: u8 = 58;
+
+/// A protocol layer, above which `Anonymizer` zeroes payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    /// Everything after the link layer header is zeroed, including
+    /// network addresses.
+    Link,
+    /// Everything after the IP header (or ARP message) is zeroed.
+    Network,
+    /// Everything after the TCP, UDP or ICMP header is zeroed.
+    Transport,
+}
+
+/// Rewrites packet data with pseudonymized addresses.
+///
+/// ```
+/// # use sniffle_capfile::anonymize::{Anonymizer, Layer};
+/// let anonymizer = Anonymizer::with_key(b"bug report 1234")
+///     .prefix_preserving(true)
+///     .zero_payloads_above(Layer::Transport);
+/// ```
+#[derive(Clone)]
+pub struct Anonymizer {
+    key: [u8; 32],
+    prefix_preserving: bool,
+    zero_above: Option<Layer>,
+    macs: HashMap<[u8; 6], [u8; 6]>,
+    ipv4: HashMap<[u8; 4], [u8; 4]>,
+    ipv6: HashMap<[u8; 16], [u8; 16]>,
+}
+
+#[derive(Clone, Copy)]
+struct IpInfo {
+    proto: u8,
+    // Offset of the upper layer header
+    start: usize,
+    // End of the IP packet within the captured data
+    end: usize,
+    // True if the whole IP packet was captured
+    complete: bool,
+    // True for non-first fragments, which have no upper layer header
+    fragment: bool,
+    pseudo_header: IpPseudoHeader,
+}
+
+impl Anonymizer {
+    /// Creates an anonymizer with a random key, so pseudonyms differ from
+    /// those of any other run.
+    pub fn new() -> Self {
+        let mut seed = [0u8; 16];
+        for chunk in seed.chunks_exact_mut(8) {
+            let rand = std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish();
+            chunk.copy_from_slice(&rand.to_le_bytes());
+        }
+        Self::with_key(&seed[..])
+    }
+
+    /// Creates an anonymizer that derives pseudonyms from `key`. Rewriting
+    /// captures with the same key produces the same pseudonyms, so separate
+    /// captures of the same network can still be correlated.
+    pub fn with_key(key: &[u8]) -> Self {
+        Self {
+            key: Sha256::digest(key),
+            prefix_preserving: false,
+            zero_above: None,
+            macs: HashMap::new(),
+            ipv4: HashMap::new(),
+            ipv6: HashMap::new(),
+        }
+    }
+
+    /// Maps IPv4 addresses such that two addresses sharing an n-bit prefix
+    /// are mapped to addresses that share an n-bit prefix. Off by default.
+    pub fn prefix_preserving(mut self, enable: bool) -> Self {
+        self.prefix_preserving = enable;
+        self.ipv4.clear();
+        self
+    }
+
+    /// Zeroes everything above `layer` in each packet. Protocols that are
+    /// not recognized are zeroed from the first unrecognized header, since
+    /// it is unknown where their payload starts.
+    pub fn zero_payloads_above(mut self, layer: Layer) -> Self {
+        self.zero_above = Some(layer);
+        self
+    }
+
+    pub fn map_mac(&mut self, addr: MacAddress) -> MacAddress {
+        let raw: [u8; 6] = addr.into();
+        MacAddress::new(self.map_mac_raw(raw))
+    }
+
+    pub fn map_ipv4(&mut self, addr: Ipv4Address) -> Ipv4Address {
+        let raw: [u8; 4] = addr.into();
+        Ipv4Address::new(self.map_ipv4_raw(raw))
+    }
+
+    pub fn map_ipv6(&mut self, addr: Ipv6Address) -> Ipv6Address {
+        Ipv6Address::new(self.map_ipv6_raw(addr.into()))
+    }
+
+    /// Anonymizes the data of a packet of link type `datalink` in place.
+    /// Checksums covering modified data are recalculated when the packet
+    /// was captured in full, and left untouched otherwise.
+    pub fn rewrite(&mut self, datalink: LinkType, data: &mut [u8]) {
+        match datalink.0 {
+            1 => self.rewrite_ethernet(data),
+            113 => self.rewrite_sll(data),
+            101 | 228 | 229 => self.rewrite_ip(data, 0),
+            _ => {}
+        }
+    }
+
+    fn rewrite_ethernet(&mut self, data: &mut [u8]) {
+        if data.len() < 14 {
+            return;
+        }
+        self.rewrite_mac(&mut data[0..6]);
+        self.rewrite_mac(&mut data[6..12]);
+        let mut ethertype = be16(&data[12..]);
+        let mut off = 14;
+        while ETHERTYPE_VLAN.contains(&ethertype) && data.len() >= off + 4 {
+            ethertype = be16(&data[off + 2..]);
+            off += 4;
+        }
+        self.rewrite_ethertype(ethertype, data, off);
+    }
+
+    fn rewrite_sll(&mut self, data: &mut [u8]) {
+        if data.len() < 16 {
+            return;
+        }
+        if be16(&data[4..]) == 6 {
+            self.rewrite_mac(&mut data[6..12]);
+        }
+        let ethertype = be16(&data[14..]);
+        self.rewrite_ethertype(ethertype, data, 16);
+    }
+
+    fn rewrite_ethertype(&mut self, ethertype: u16, data: &mut [u8], off: usize) {
+        match ethertype {
+            _ if self.zero_above == Some(Layer::Link) => self.zero(data, off, data.len()),
+            ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => self.rewrite_ip(data, off),
+            ETHERTYPE_ARP => self.rewrite_arp(data, off),
+            _ => self.zero_unknown(data, off, data.len()),
+        }
+    }
+
+    fn rewrite_arp(&mut self, data: &mut [u8], off: usize) {
+        // Only Ethernet/IPv4 ARP is understood
+        if data.len() < off + 28 || be16(&data[off..]) != 1 || be16(&data[off + 2..]) != 0x0800 {
+            self.zero_unknown(data, off, data.len());
+            return;
+        }
+        self.rewrite_mac(&mut data[off + 8..off + 14]);
+        self.rewrite_ipv4(&mut data[off + 14..off + 18]);
+        self.rewrite_mac(&mut data[off + 18..off + 24]);
+        self.rewrite_ipv4(&mut data[off + 24..off + 28]);
+        self.zero_unknown(data, off + 28, data.len());
+    }
+
+    fn rewrite_ip(&mut self, data: &mut [u8], off: usize) {
+        if self.zero_above == Some(Layer::Link) {
+            self.zero(data, off, data.len());
+            return;
+        }
+        let info = match data.get(off).map(|b| b >> 4) {
+            Some(4) => self.rewrite_ipv4_header(data, off),
+            Some(6) => self.rewrite_ipv6_header(data, off),
+            _ => None,
+        };
+        let Some(info) = info else {
+            self.zero_unknown(data, off, data.len());
+            return;
+        };
+        if self.zero_above == Some(Layer::Network) || info.fragment {
+            self.zero_unknown(data, info.start, info.end);
+        } else {
+            self.rewrite_transport(data, info);
+        }
+        // Link layer padding and trailers are not part of the IP packet
+        self.zero_unknown(data, info.end, data.len());
+    }
+
+    fn rewrite_ipv4_header(&mut self, data: &mut [u8], off: usize) -> Option<IpInfo> {
+        let ihl = (data.get(off)? & 0x0f) as usize * 4;
+        if ihl < 20 || data.len() < off + ihl {
+            return None;
+        }
+        self.rewrite_ipv4(&mut data[off + 12..off + 16]);
+        self.rewrite_ipv4(&mut data[off + 16..off + 20]);
+        data[off + 10] = 0;
+        data[off + 11] = 0;
+        let chksum = ones_complement(None, 0, &data[off..off + ihl]);
+        data[off + 10..off + 12].copy_from_slice(&chksum.to_be_bytes());
+
+        let total_len = (be16(&data[off + 2..]) as usize).max(ihl);
+        let fragment = be16(&data[off + 6..]) & 0x1fff != 0;
+        let pseudo_header = IpPseudoHeader::V4(Ipv4PseudoHeader {
+            src: Ipv4Address::new(data[off + 12..off + 16].try_into().unwrap()),
+            dst: Ipv4Address::new(data[off + 16..off + 20].try_into().unwrap()),
+        });
+        Some(IpInfo {
+            proto: data[off + 9],
+            start: off + ihl,
+            end: data.len().min(off + total_len),
+            complete: data.len() >= off + total_len,
+            fragment,
+            pseudo_header,
+        })
+    }
+
+    fn rewrite_ipv6_header(&mut self, data: &mut [u8], off: usize) -> Option<IpInfo> {
+        if data.len() < off + 40 {
+            return None;
+        }
+        self.rewrite_ipv6(&mut data[off + 8..off + 24]);
+        self.rewrite_ipv6(&mut data[off + 24..off + 40]);
+
+        let payload_len = be16(&data[off + 4..]) as usize;
+        // A zero payload length is used by jumbograms
+        let total_len = if payload_len == 0 {
+            data.len() - off
+        } else {
+            40 + payload_len
+        };
+        let mut proto = data[off + 6];
+        let mut start = off + 40;
+        let mut fragment = false;
+        loop {
+            match proto {
+                // Hop-by-hop, routing and destination options
+                0 | 43 | 60 if data.len() >= start + 2 => {
+                    proto = data[start];
+                    start += (data[start + 1] as usize + 1) * 8;
+                }
+                44 if data.len() >= start + 8 => {
+                    fragment = be16(&data[start + 2..]) & 0xfff8 != 0;
+                    proto = data[start];
+                    start += 8;
+                }
+                0 | 43 | 44 | 60 => return None,
+                _ => break,
+            }
+        }
+        let pseudo_header = IpPseudoHeader::V6(Ipv6PseudoHeader {
+            src: Ipv6Address::new(data[off + 8..off + 24].try_into().unwrap()),
+            dst: Ipv6Address::new(data[off + 24..off + 40].try_into().unwrap()),
+        });
+        let end = data.len().min(off + total_len);
+        Some(IpInfo {
+            proto,
+            start: start.min(end),
+            end,
+            complete: data.len() >= off + total_len,
+            fragment,
+            pseudo_header,
+        })
+    }
+
+    fn rewrite_transport(&mut self, data: &mut [u8], info: IpInfo) {
+        let IpInfo { start, end, .. } = info;
+        let (hdr_len, chksum_off) = match info.proto {
+            PROTO_TCP if end >= start + 20 => ((data[start + 12] >> 4) as usize * 4, 16),
+            PROTO_UDP => (8, 6),
+            PROTO_ICMP | PROTO_ICMPV6 => (8, 2),
+            _ => {
+                self.zero_unknown(data, start, end);
+                return;
+            }
+        };
+        if end < start + hdr_len {
+            self.zero_unknown(data, start, end);
+            return;
+        }
+        if self.zero_above == Some(Layer::Transport) {
+            self.zero(data, start + hdr_len, end);
+        } else if matches!(info.proto, PROTO_ICMP | PROTO_ICMPV6) {
+            self.rewrite_icmp_quote(data, info.proto, start + hdr_len, end);
+        }
+        if !info.complete {
+            return;
+        }
+        let chksum_at = start + chksum_off;
+        let old = be16(&data[chksum_at..]);
+        // A zero UDP checksum over IPv4 means no checksum was computed
+        if info.proto == PROTO_UDP
+            && old == 0
+            && matches!(info.pseudo_header, IpPseudoHeader::V4(_))
+        {
+            return;
+        }
+        data[chksum_at] = 0;
+        data[chksum_at + 1] = 0;
+        let pseudo_header = match info.proto {
+            PROTO_ICMP => None,
+            _ => Some(&info.pseudo_header),
+        };
+        let mut chksum = ones_complement(pseudo_header, info.proto, &data[start..end]);
+        if info.proto == PROTO_UDP && chksum == 0 {
+            chksum = 0xffff;
+        }
+        data[chksum_at..chksum_at + 2].copy_from_slice(&chksum.to_be_bytes());
+    }
+
+    // ICMP errors quote the header of the packet that caused them, which
+    // contains the same addresses as the packets around it
+    fn rewrite_icmp_quote(&mut self, data: &mut [u8], proto: u8, off: usize, end: usize) {
+        let icmp_type = data[off - 8];
+        let is_error = match proto {
+            PROTO_ICMP => matches!(icmp_type, 3 | 4 | 5 | 11 | 12),
+            _ => (1..=4).contains(&icmp_type),
+        };
+        if !is_error {
+            return;
+        }
+        let quote = &mut data[off..end];
+        match quote.first().map(|b| b >> 4) {
+            Some(4) if quote.len() >= 20 => {
+                self.rewrite_ipv4(&mut quote[12..16]);
+                self.rewrite_ipv4(&mut quote[16..20]);
+                let ihl = (quote[0] & 0x0f) as usize * 4;
+                if ihl >= 20 && quote.len() >= ihl {
+                    quote[10] = 0;
+                    quote[11] = 0;
+                    let chksum = ones_complement(None, 0, &quote[..ihl]);
+                    quote[10..12].copy_from_slice(&chksum.to_be_bytes());
+                }
+            }
+            Some(6) if quote.len() >= 40 => {
+                self.rewrite_ipv6(&mut quote[8..24]);
+                self.rewrite_ipv6(&mut quote[24..40]);
+            }
+            _ => {}
+        }
+    }
+
+    fn zero(&self, data: &mut [u8], start: usize, end: usize) {
+        if start < end && end <= data.len() {
+            data[start..end].fill(0);
+        }
+    }
+
+    fn zero_unknown(&self, data: &mut [u8], start: usize, end: usize) {
+        if self.zero_above.is_some() {
+            self.zero(data, start, end);
+        }
+    }
+
+    fn rewrite_mac(&mut self, field: &mut [u8]) {
+        let mapped = self.map_mac_raw(field.try_into().unwrap());
+        field.copy_from_slice(&mapped[..]);
+    }
+
+    fn rewrite_ipv4(&mut self, field: &mut [u8]) {
+        let mapped = self.map_ipv4_raw(field.try_into().unwrap());
+        field.copy_from_slice(&mapped[..]);
+    }
+
+    fn rewrite_ipv6(&mut self, field: &mut [u8]) {
+        let mapped = self.map_ipv6_raw(field.try_into().unwrap());
+        field.copy_from_slice(&mapped[..]);
+    }
+
+    fn hash(&self, domain: &[u8], input: &[u8]) -> [u8; 32] {
+        let mut hash = Sha256::new();
+        hash.update(&self.key[..]);
+        hash.update(domain);
+        hash.update(input);
+        hash.finish()
+    }
+
+    fn map_mac_raw(&mut self, addr: [u8; 6]) -> [u8; 6] {
+        // Group addresses, including broadcast, don't identify a host
+        if addr[0] & 0x01 != 0 || addr == [0; 6] {
+            return addr;
+        }
+        if let Some(mapped) = self.macs.get(&addr) {
+            return *mapped;
+        }
+        let hash = self.hash(b"mac", &addr[..]);
+        let mut mapped = [0u8; 6];
+        mapped.copy_from_slice(&hash[..6]);
+        // Unicast and locally administered, so it can't be mistaken for a
+        // real vendor address
+        mapped[0] = (mapped[0] & !0x01) | 0x02;
+        self.macs.insert(addr, mapped);
+        mapped
+    }
+
+    fn map_ipv4_raw(&mut self, addr: [u8; 4]) -> [u8; 4] {
+        let ip = Ipv4Address::new(addr);
+        if ip.is_unspecified() || ip.is_loopback() || ip.is_multicast() || addr == [255; 4] {
+            return addr;
+        }
+        if let Some(mapped) = self.ipv4.get(&addr) {
+            return *mapped;
+        }
+        let val = u32::from_be_bytes(addr);
+        let mapped = if self.prefix_preserving {
+            // Each bit is flipped based on the bits before it, as in
+            // Crypto-PAn, so equal prefixes map to equal prefixes
+            let mut mapped = 0u32;
+            for bit in 0..32 {
+                let prefix = val.checked_shr(32 - bit).unwrap_or(0);
+                let mut input = [0u8; 5];
+                input[0] = bit as u8;
+                input[1..].copy_from_slice(&prefix.to_be_bytes());
+                let flip = (self.hash(b"ipv4-prefix", &input[..])[0] & 1) as u32;
+                mapped |= (((val >> (31 - bit)) & 1) ^ flip) << (31 - bit);
+            }
+            mapped
+        } else {
+            // A Feistel network, so that distinct addresses never collide
+            let (mut left, mut right) = ((val >> 16) as u16, val as u16);
+            for round in 0u8..4 {
+                let hash = self.hash(b"ipv4", &[round, (right >> 8) as u8, right as u8]);
+                let next = left ^ u16::from_be_bytes([hash[0], hash[1]]);
+                left = right;
+                right = next;
+            }
+            ((left as u32) << 16) | right as u32
+        };
+        let mapped = mapped.to_be_bytes();
+        self.ipv4.insert(addr, mapped);
+        mapped
+    }
+
+    fn map_ipv6_raw(&mut self, addr: [u8; 16]) -> [u8; 16] {
+        let ip = Ipv6Address::new(addr);
+        if ip.is_unspecified() || ip.is_loopback() || ip.is_multicast() {
+            return addr;
+        }
+        if let Some(mapped) = self.ipv6.get(&addr) {
+            return *mapped;
+        }
+        let hash = self.hash(b"ipv6", &addr[..]);
+        let mut mapped = [0u8; 16];
+        mapped.copy_from_slice(&hash[..16]);
+        // Keep link-local addresses recognizable as such
+        if ip.is_unicast_link_local() {
+            mapped[..8].copy_from_slice(&addr[..8]);
+        }
+        self.ipv6.insert(addr, mapped);
+        mapped
+    }
+}
+
+impl Default for Anonymizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Anonymizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Anonymizer")
+            .field("prefix_preserving", &self.prefix_preserving)
+            .field("zero_above", &self.zero_above)
+            .finish_non_exhaustive()
+    }
+}
+
+fn be16(data: &[u8]) -> u16 {
+    u16::from_be_bytes([data[0], data[1]])
+}
+
+fn ones_complement(pseudo_header: Option<&IpPseudoHeader>, proto: u8, data: &[u8]) -> u16 {
+    let mut acc = match pseudo_header {
+        Some(ph) => U16OnesComplement::with_pseudo_header(ph, proto, data.len()),
+        None => U16OnesComplement::new(),
+    };
+    let _ = acc.write_all(data);
+    acc.checksum()
+}
+
+/// Copies every packet from `src` to `dst`, anonymized by `anonymizer`, and
+/// returns the number of packets copied. Capture metadata that may identify
+/// the network, such as the capture device and packet comments, is dropped.
+pub async fn anonymize_packets<S: SniffRaw + ?Sized, T: Transmit + ?Sized>(
+    src: &mut S,
+    dst: &mut T,
+    anonymizer: &mut Anonymizer,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    let mut buf = Vec::new();
+    while let Some(packet) = src.sniff_raw().await? {
+        buf.clear();
+        buf.extend_from_slice(packet.data());
+        anonymizer.rewrite(packet.datalink(), &mut buf[..]);
+        let out = RawPacket::new(
+            packet.datalink(),
+            packet.precise_timestamp(),
+            packet.orig_len(),
+            Some(packet.snaplen()),
+            &buf[..],
+            None,
+        );
+        dst.transmit_raw(out).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Copies the capture file at `src` to a new capture file at `dst`,
+/// anonymized by `anonymizer`. The new file has the same format as `src`,
+/// except that ERF files are copied to pcapng files, but pcapng interface
+/// names, descriptions, and comments are not copied.
+/// Returns the number of packets copied.
+#[cfg(feature = "fs")]
+pub async fn anonymize<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+    src: P,
+    dst: Q,
+    anonymizer: &mut Anonymizer,
+) -> Result<usize, Error> {
+    match FileSniffer::open_raw(src).await? {
+        Sniffer::Pcap(mut src) => {
+            let tsprec = src.reader().timestamp_precision();
+            let mut dst = pcap::FileRecorder::create_with_tsprec(dst, tsprec).await?;
+            let count = anonymize_packets(&mut src, &mut dst, anonymizer).await?;
+            dst.flush().await?;
+            Ok(count)
+        }
+        Sniffer::PcapNG(mut src) => {
+            let mut dst = pcapng::FileRecorder::create(dst).await?;
+            let count = anonymize_packets(&mut src, &mut dst, anonymizer).await?;
+            dst.flush().await?;
+            Ok(count)
+        }
+        Sniffer::Erf(mut src) => {
+            let mut dst = pcapng::FileRecorder::create(dst).await?;
+            let count = anonymize_packets(&mut src, &mut dst, anonymizer).await?;
+            dst.flush().await?;
+            Ok(count)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame(src_ip: [u8; 4], dst_ip: [u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        data.extend_from_slice(&[0x00, 0x66, 0x77, 0x88, 0x99, 0xaa]);
+        data.extend_from_slice(&[0x08, 0x00]);
+        let total = (20 + 8 + payload.len()) as u16;
+        data.extend_from_slice(&[0x45, 0, (total >> 8) as u8, total as u8, 0, 0, 0, 0]);
+        data.extend_from_slice(&[64, PROTO_UDP, 0, 0]);
+        data.extend_from_slice(&src_ip);
+        data.extend_from_slice(&dst_ip);
+        let udp_len = (8 + payload.len()) as u16;
+        data.extend_from_slice(&[0x30, 0x39, 0x00, 0x35]);
+        data.extend_from_slice(&[(udp_len >> 8) as u8, udp_len as u8, 0xde, 0xad]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn verify_checksums(data: &[u8]) {
+        assert_eq!(ones_complement(None, 0, &data[14..34]), 0);
+        let ph = IpPseudoHeader::V4(Ipv4PseudoHeader {
+            src: Ipv4Address::new(data[26..30].try_into().unwrap()),
+            dst: Ipv4Address::new(data[30..34].try_into().unwrap()),
+        });
+        assert_eq!(ones_complement(Some(&ph), PROTO_UDP, &data[34..]), 0);
+    }
+
+    #[test]
+    fn consistent_mapping() {
+        let mut anon = Anonymizer::with_key(b"test");
+        let mut a = frame([10, 0, 0, 1], [10, 0, 0, 2], b"secret");
+        let mut b = frame([10, 0, 0, 2], [10, 0, 0, 1], b"secret");
+        anon.rewrite(LinkType(1), &mut a[..]);
+        anon.rewrite(LinkType(1), &mut b[..]);
+
+        assert_eq!(a[0..6], b[0..6]);
+        assert_ne!(a[0..6], [0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(a[0] & 0x03, 0x02);
+        assert_eq!(a[26..30], b[30..34]);
+        assert_ne!(a[26..30], [10, 0, 0, 1]);
+        assert_eq!(&a[42..], b"secret");
+        verify_checksums(&a[..]);
+        verify_checksums(&b[..]);
+
+        let mut again = Anonymizer::with_key(b"test");
+        let mut c = frame([10, 0, 0, 1], [255, 255, 255, 255], b"");
+        again.rewrite(LinkType(1), &mut c[..]);
+        assert_eq!(c[26..30], a[26..30]);
+        assert_eq!(c[30..34], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn prefix_preserving() {
+        let mut anon = Anonymizer::with_key(b"test").prefix_preserving(true);
+        let a = u32::from_be_bytes(anon.map_ipv4(Ipv4Address::new([192, 168, 1, 10])).into());
+        let b = u32::from_be_bytes(anon.map_ipv4(Ipv4Address::new([192, 168, 1, 200])).into());
+        let c = u32::from_be_bytes(anon.map_ipv4(Ipv4Address::new([192, 169, 1, 10])).into());
+        assert_eq!((a ^ b).leading_zeros(), 24);
+        assert_eq!((a ^ c).leading_zeros(), 15);
+    }
+
+    #[test]
+    fn zero_payloads() {
+        let mut anon = Anonymizer::with_key(b"test").zero_payloads_above(Layer::Transport);
+        let mut data = frame([10, 0, 0, 1], [10, 0, 0, 2], b"secret");
+        anon.rewrite(LinkType(1), &mut data[..]);
+        assert_eq!(data[42..], [0; 6]);
+        assert_eq!(data[34..36], [0x30, 0x39]);
+        verify_checksums(&data[..]);
+
+        let mut anon = Anonymizer::with_key(b"test").zero_payloads_above(Layer::Network);
+        let mut data = frame([10, 0, 0, 1], [10, 0, 0, 2], b"secret");
+        anon.rewrite(LinkType(1), &mut data[..]);
+        assert!(data[34..].iter().all(|b| *b == 0));
+        assert_ne!(data[26..30], [0; 4]);
+
+        // Truncated packets keep their checksum, since it can't be computed
+        let mut anon = Anonymizer::with_key(b"test");
+        let mut data = frame([10, 0, 0, 1], [10, 0, 0, 2], b"secret");
+        data.truncate(42);
+        anon.rewrite(LinkType(1), &mut data[..]);
+        assert_eq!(data[40..42], [0xde, 0xad]);
+    }
+}
@@ -0,0 +1,486 @@
+//! Compressed capture files, such as `.pcap.gz` and `.pcapng.zst`.
+//!
+//! `Sniffer::open` and friends detect compressed files by their magic
+//! number and decompress them transparently. Since reading a capture file
+//! requires seeking, the file is first decompressed into a temporary file.
+//!
+//! Compressed output is written through `CompressedWriter`, which the
+//! recorders' `create_compressed` constructors set up. Compressed files
+//! must be finished with the recorder's `close`, which writes the end of
+//! the compressed stream.
+
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite};
+
+/// A compression format for capture files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Compression {
+    #[cfg(feature = "flate2")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Writes compressed data to an underlying writer.
+///
+/// A `CompressedWriter` created with `new` doesn't support seeking, other
+/// than to the current position. One created with `seekable` allows
+/// seeking anywhere in the data written since the last flush, which is
+/// kept uncompressed in memory until then. This is enough for the pcapng
+/// recorder, which goes back to fill in the length of each block after
+/// writing it, as long as it is flushed periodically.
+///
+/// The compressed stream is only complete after `shutdown`.
+pub struct CompressedWriter<W: AsyncWrite + Unpin> {
+    inner: W,
+    encoder: Option<Encoder>,
+    out: Vec<u8>,
+    out_pos: usize,
+    window: Vec<u8>,
+    window_start: u64,
+    pos: u64,
+    seekable: bool,
+}
+
+#[cfg(feature = "fs")]
+pub type CompressedFile = CompressedWriter<tokio::io::BufWriter<tokio::fs::File>>;
+
+enum Encoder {
+    #[cfg(feature = "flate2")]
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+#[cfg(feature = "fs")]
+enum Decoder {
+    #[cfg(feature = "flate2")]
+    Gzip(flate2::write::MultiGzDecoder<Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+impl Compression {
+    /// Detects the compression format from the first bytes of a file.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        #[cfg(feature = "flate2")]
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            return Some(Self::Gzip);
+        }
+        #[cfg(feature = "zstd")]
+        if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            return Some(Self::Zstd);
+        }
+        None
+    }
+
+    /// Detects the compression format of `file` from its first bytes, and
+    /// seeks back to where reading started.
+    pub async fn from_file<F: AsyncRead + AsyncSeek + Unpin>(
+        file: &mut F,
+    ) -> Result<Option<Self>, std::io::Error> {
+        let mut magic = [0u8; 4];
+        let mut len = 0;
+        while len < magic.len() {
+            match file.read(&mut magic[len..]).await? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        file.seek(std::io::SeekFrom::Current(-(len as i64))).await?;
+        Ok(Self::detect(&magic[..len]))
+    }
+
+    /// The conventional file name extension, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip => "gz",
+            #[cfg(feature = "zstd")]
+            Self::Zstd => "zst",
+        }
+    }
+}
+
+impl Encoder {
+    fn new(compression: Compression) -> std::io::Result<Self> {
+        Ok(match compression {
+            #[cfg(feature = "flate2")]
+            Compression::Gzip => Self::Gzip(flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Self::Zstd(zstd::stream::write::Encoder::new(Vec::new(), 0)?),
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(enc) => enc.write_all(data),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(enc) => enc.write_all(data),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(enc) => enc.flush(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(enc) => enc.flush(),
+        }
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(enc) => std::mem::take(enc.get_mut()),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(enc) => std::mem::take(enc.get_mut()),
+        }
+    }
+
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(enc) => enc.finish(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(enc) => enc.finish(),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Decoder {
+    fn new(compression: Compression) -> std::io::Result<Self> {
+        Ok(match compression {
+            #[cfg(feature = "flate2")]
+            Compression::Gzip => Self::Gzip(flate2::write::MultiGzDecoder::new(Vec::new())),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Self::Zstd(zstd::stream::write::Decoder::new(Vec::new())?),
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(dec) => dec.write_all(data),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(dec) => dec.write_all(data),
+        }
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(dec) => std::mem::take(dec.get_mut()),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(dec) => std::mem::take(dec.get_mut()),
+        }
+    }
+
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(dec) => dec.finish(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(mut dec) => {
+                dec.flush()?;
+                Ok(dec.into_inner())
+            }
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> CompressedWriter<W> {
+    /// Creates a writer that compresses data as it is written.
+    pub fn new(inner: W, compression: Compression) -> std::io::Result<Self> {
+        Self::with_seekable(inner, compression, false)
+    }
+
+    /// Creates a writer that allows seeking within the data written since
+    /// the last flush, and compresses it when flushed.
+    pub fn seekable(inner: W, compression: Compression) -> std::io::Result<Self> {
+        Self::with_seekable(inner, compression, true)
+    }
+
+    fn with_seekable(inner: W, compression: Compression, seekable: bool) -> std::io::Result<Self> {
+        Ok(Self {
+            inner,
+            encoder: Some(Encoder::new(compression)?),
+            out: Vec::new(),
+            out_pos: 0,
+            window: Vec::new(),
+            window_start: 0,
+            pos: 0,
+            seekable,
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the underlying writer. Unless the writer has been shut
+    /// down, the compressed stream written to it is incomplete.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn commit(&mut self) -> std::io::Result<()> {
+        if let Some(encoder) = self.encoder.as_mut() {
+            if !self.window.is_empty() {
+                encoder.write(&self.window[..])?;
+                self.window_start += self.window.len() as u64;
+                self.window.clear();
+            }
+            let out = encoder.take_output();
+            self.out.extend_from_slice(&out[..]);
+        }
+        Ok(())
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        while self.out_pos < self.out.len() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.out[self.out_pos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+            }
+            self.out_pos += n;
+        }
+        self.out.clear();
+        self.out_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn closed() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::BrokenPipe,
+        "Compressed stream has been shut down",
+    )
+}
+
+fn unseekable() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Compressed output can only seek within data written since the last flush",
+    )
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CompressedWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        if this.encoder.is_none() {
+            return Poll::Ready(Err(closed()));
+        }
+        if this.pos < this.window_start {
+            return Poll::Ready(Err(unseekable()));
+        }
+        let offset = (this.pos - this.window_start) as usize;
+        let overlap = buf.len().min(this.window.len() - offset);
+        this.window[offset..offset + overlap].copy_from_slice(&buf[..overlap]);
+        this.window.extend_from_slice(&buf[overlap..]);
+        this.pos += buf.len() as u64;
+        if !this.seekable {
+            this.commit()?;
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if this.pos == this.window_start + this.window.len() as u64 {
+            this.commit()?;
+            if let Some(encoder) = this.encoder.as_mut() {
+                encoder.flush()?;
+            }
+            this.commit()?;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        this.commit()?;
+        if let Some(encoder) = this.encoder.take() {
+            let out = encoder.finish()?;
+            this.out.extend_from_slice(&out[..]);
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncSeek for CompressedWriter<W> {
+    fn start_seek(self: Pin<&mut Self>, position: std::io::SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+        let end = this.window_start + this.window.len() as u64;
+        let target = match position {
+            std::io::SeekFrom::Start(pos) => Some(pos),
+            std::io::SeekFrom::Current(off) => this.pos.checked_add_signed(off),
+            std::io::SeekFrom::End(off) => end.checked_add_signed(off),
+        };
+        match target {
+            Some(target) if target >= this.window_start && target <= end => {
+                this.pos = target;
+                Ok(())
+            }
+            _ => Err(unseekable()),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
+
+/// Decompresses `src` into an unnamed temporary file, which is returned
+/// positioned at its start.
+#[cfg(feature = "fs")]
+pub(crate) async fn decompress(
+    mut src: tokio::fs::File,
+    compression: Compression,
+) -> Result<tokio::fs::File, sniffle_core::Error> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::AsyncWriteExt;
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let path = std::env::temp_dir().join(format!(
+        "sniffle-{}-{}.cap",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut dst = tokio::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .await?;
+    // Removing the file while it is open leaves it accessible through
+    // `dst` until it is closed, on platforms that allow it
+    let _ = tokio::fs::remove_file(&path).await;
+
+    let mut decoder = Decoder::new(compression)?;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = src.read(&mut buf[..]).await?;
+        if n == 0 {
+            break;
+        }
+        decoder.write(&buf[..n])?;
+        dst.write_all(&decoder.take_output()[..]).await?;
+    }
+    dst.write_all(&decoder.finish()?[..]).await?;
+    dst.flush().await?;
+    dst.seek(std::io::SeekFrom::Start(0)).await?;
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn compressions() -> Vec<Compression> {
+        vec![
+            #[cfg(feature = "flate2")]
+            Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+        ]
+    }
+
+    #[test]
+    fn seekable_writer() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        for compression in compressions() {
+            let out = rt
+                .block_on(async {
+                    let mut w = CompressedWriter::seekable(Vec::new(), compression)?;
+                    w.write_all(b"hello ????").await?;
+                    w.seek(std::io::SeekFrom::Start(6)).await?;
+                    w.write_all(b"world").await?;
+                    w.flush().await?;
+                    assert!(w.seek(std::io::SeekFrom::Start(0)).await.is_err());
+                    w.write_all(b"!").await?;
+                    w.shutdown().await?;
+                    Ok::<_, std::io::Error>(w.into_inner())
+                })
+                .unwrap();
+            assert_eq!(Compression::detect(&out[..]), Some(compression));
+            let mut decoder = Decoder::new(compression).unwrap();
+            decoder.write(&out[..]).unwrap();
+            assert_eq!(decoder.finish().unwrap(), b"hello world!");
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn compressed_files() {
+        use crate::{pcap, pcapng, FileSniffer, Sniffer};
+        use sniffle_core::{Error, LinkType, RawPacket, SniffRaw, Timestamp, Transmit};
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        for compression in compressions() {
+            let dir = std::env::temp_dir();
+            let id = std::process::id();
+            let pcap_path = dir.join(format!("sniffle-{}.pcap.{}", id, compression.extension()));
+            let pcapng_path =
+                dir.join(format!("sniffle-{}.pcapng.{}", id, compression.extension()));
+            let res = rt.block_on(async {
+                let ts = Timestamp::new(1_700_000_000, 0);
+                let mut rec = pcap::FileRecorder::create_compressed(
+                    &pcap_path,
+                    pcap::TsPrecision::Micro,
+                    compression,
+                )
+                .await?;
+                let mut rec_ng =
+                    pcapng::FileRecorder::create_compressed(&pcapng_path, compression).await?;
+                for n in 0u8..3 {
+                    let data = [n; 3];
+                    let pkt = RawPacket::new(LinkType::ETHERNET, ts, 3, None, &data[..], None);
+                    rec.transmit_raw(pkt).await?;
+                    let pkt = RawPacket::new(LinkType::ETHERNET, ts, 3, None, &data[..], None);
+                    rec_ng.transmit_raw(pkt).await?;
+                    rec_ng.flush().await?;
+                }
+                rec.close().await?;
+                rec_ng.close().await?;
+
+                let mut out = Vec::new();
+                for path in [&pcap_path, &pcapng_path] {
+                    let mut src = FileSniffer::open_raw(path).await?;
+                    out.push(matches!(src, Sniffer::Pcap(_)));
+                    while let Some(packet) = src.sniff_raw().await? {
+                        out.push(packet.data() == [packet.data()[0]; 3]);
+                    }
+                }
+                Ok::<_, Error>(out)
+            });
+            let _ = std::fs::remove_file(&pcap_path);
+            let _ = std::fs::remove_file(&pcapng_path);
+            assert_eq!(
+                res.unwrap(),
+                [true, true, true, true, false, true, true, true]
+            );
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use sniffle_core::{
+    AnyPdu, Error, LinkTypeTable, Packet, RawPacket, RawPdu, Session, SniffRaw, Transmit,
+};
+
+#[cfg(feature = "fs")]
+use crate::{pcap, pcapng, FileSniffer, Sniffer};
+
+async fn copy<S, T, F>(
+    src: &mut S,
+    dst: &mut T,
+    session: &Session,
+    mut f: F,
+) -> Result<usize, Error>
+where
+    S: SniffRaw + ?Sized,
+    T: Transmit + ?Sized,
+    F: FnMut(&mut Packet) -> Result<bool, Error>,
+{
+    let mut count = 0;
+    let mut buf = Vec::new();
+    while let Some(raw) = src.sniff_raw().await? {
+        let datalink = raw.datalink();
+        let meta = raw.meta().cloned();
+        let pdu = match session.table_dissect::<LinkTypeTable>(&datalink, raw.data(), None) {
+            Ok((_rem, pdu)) => pdu,
+            Err(_) => AnyPdu::new(RawPdu::new(Vec::from(raw.data()))),
+        };
+        let mut packet = Packet::new(
+            raw.precise_timestamp(),
+            pdu,
+            Some(raw.orig_len()),
+            Some(raw.snaplen()),
+            raw.share_device(),
+        );
+        drop(raw);
+
+        let truncated = packet.len().saturating_sub(packet.captured_len());
+        if !f(&mut packet)? {
+            continue;
+        }
+        if truncated == 0 {
+            packet.make_canonical();
+        }
+
+        buf.clear();
+        packet.serialize(&mut buf)?;
+        let mut out = RawPacket::new(
+            datalink,
+            packet.precise_timestamp(),
+            buf.len() + truncated,
+            Some(packet.snaplen()),
+            &buf[..],
+            packet.share_device(),
+        );
+        if let Some(meta) = meta {
+            out = out.with_meta(meta);
+        }
+        dst.transmit_raw(out).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Dissects every packet from `src` with `session`, passes it to `f`, and
+/// writes the result to `dst`. Returns the number of packets written.
+///
+/// `f` may modify the packet in any way, and returns false to drop it. An
+/// error returned by `f` stops the copy. Edited packets are made canonical
+/// before they are written, so lengths and checksums don't need to be
+/// fixed up by hand. Packets that were truncated by the snaplen are written
+/// as edited, since their lengths and checksums can't be recomputed from
+/// partial data. Timestamps, capture devices, and packet metadata are
+/// preserved.
+pub async fn edit_packets<S, T, F>(
+    src: &mut S,
+    dst: &mut T,
+    session: &Session,
+    f: F,
+) -> Result<usize, Error>
+where
+    S: SniffRaw + ?Sized,
+    T: Transmit + ?Sized,
+    F: FnMut(&mut Packet) -> Result<bool, Error>,
+{
+    copy(src, dst, session, f).await
+}
+
+/// Copies the capture file at `src` to a new capture file at `dst`, editing
+/// each packet with `f` as described by `edit_packets`. The new file has
+/// the same format as `src`, except that ERF files are copied to pcapng
+/// files. For pcapng files, interface names,
+/// descriptions, and addresses are preserved, and each packet is written
+/// to the same interface it was read from. Returns the number of packets
+/// written.
+#[cfg(feature = "fs")]
+pub async fn edit<P, Q, F>(src: P, dst: Q, session: &Session, f: F) -> Result<usize, Error>
+where
+    P: AsRef<std::path::Path>,
+    Q: AsRef<std::path::Path>,
+    F: FnMut(&mut Packet) -> Result<bool, Error>,
+{
+    match FileSniffer::open_raw(src).await? {
+        Sniffer::Pcap(mut src) => {
+            let tsprec = src.reader().timestamp_precision();
+            let mut dst = pcap::FileRecorder::create_with_tsprec(dst, tsprec).await?;
+            let count = copy(&mut src, &mut dst, session, f).await?;
+            dst.flush().await?;
+            Ok(count)
+        }
+        Sniffer::PcapNG(mut src) => {
+            let mut dst = pcapng::FileRecorder::create(dst).await?;
+            let count = copy(&mut src, &mut dst, session, f).await?;
+            dst.flush().await?;
+            Ok(count)
+        }
+        Sniffer::Erf(mut src) => {
+            let mut dst = pcapng::FileRecorder::create(dst).await?;
+            let count = copy(&mut src, &mut dst, session, f).await?;
+            dst.flush().await?;
+            Ok(count)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn edit_pcap() {
+        let mut file = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        file.extend_from_slice(&[0u8; 8][..]);
+        file.extend_from_slice(&0xffffu32.to_le_bytes()[..]);
+        file.extend_from_slice(&147u32.to_le_bytes()[..]);
+        for sec in 0u32..6 {
+            for field in [sec, 0, 2, 2] {
+                file.extend_from_slice(&field.to_le_bytes()[..]);
+            }
+            file.extend_from_slice(&[sec as u8, 0xaa][..]);
+        }
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let out = rt
+            .block_on(async {
+                let mut src = crate::pcap::Sniffer::new_raw(Cursor::new(file)).await?;
+                let mut dst = crate::pcap::Recorder::new(Vec::new());
+                let session = Session::new();
+                let count = edit_packets(&mut src, &mut dst, &session, |packet| {
+                    let raw = packet.find_mut::<RawPdu>().unwrap();
+                    let mut data = Vec::from(&raw.data()[..]);
+                    if data[0] % 2 == 1 {
+                        return Ok(false);
+                    }
+                    data[1] = 0xbb;
+                    data.push(0xcc);
+                    raw.set_data(data);
+                    Ok(true)
+                })
+                .await?;
+                assert_eq!(count, 3);
+
+                let mut out = Vec::new();
+                let mut src = crate::pcap::Sniffer::new_raw(Cursor::new(dst.into_inner())).await?;
+                while let Some(packet) = src.sniff_raw().await? {
+                    assert_eq!(packet.orig_len(), 3);
+                    out.push((packet.timestamp(), Vec::from(packet.data())));
+                }
+                Ok::<_, Error>(out)
+            })
+            .unwrap();
+        let start = std::time::SystemTime::UNIX_EPOCH;
+        let expected: Vec<_> = [0u8, 2, 4]
+            .into_iter()
+            .map(|sec| {
+                let ts = start + std::time::Duration::from_secs(sec as u64);
+                (ts, vec![sec, 0xbb, 0xcc])
+            })
+            .collect();
+        assert_eq!(out, expected);
+    }
+}
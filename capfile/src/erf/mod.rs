@@ -0,0 +1,236 @@
+//! Extensible Record Format (ERF) files, as written by Endace DAG capture
+//! cards.
+//!
+//! ERF files have no file header, and are just a sequence of records. Each
+//! record has its own type, which determines the link type of the packet it
+//! contains. Records that don't contain a packet, such as padding and
+//! metadata records, are skipped by the sniffer.
+
+pub mod reader;
+mod sniffer;
+
+#[cfg(feature = "fs")]
+pub use sniffer::FileSniffer;
+pub use sniffer::Sniffer;
+
+use sniffle_core::LinkType;
+
+pub const TYPE_HDLC_POS: u8 = 1;
+pub const TYPE_ETH: u8 = 2;
+pub const TYPE_ATM: u8 = 3;
+pub const TYPE_AAL5: u8 = 4;
+pub const TYPE_MC_HDLC: u8 = 5;
+pub const TYPE_MC_RAW: u8 = 6;
+pub const TYPE_MC_ATM: u8 = 7;
+pub const TYPE_MC_RAW_CHANNEL: u8 = 8;
+pub const TYPE_MC_AAL5: u8 = 9;
+pub const TYPE_COLOR_HDLC_POS: u8 = 10;
+pub const TYPE_COLOR_ETH: u8 = 11;
+pub const TYPE_MC_AAL2: u8 = 12;
+pub const TYPE_IP_COUNTER: u8 = 13;
+pub const TYPE_TCP_FLOW_COUNTER: u8 = 14;
+pub const TYPE_DSM_COLOR_HDLC_POS: u8 = 15;
+pub const TYPE_DSM_COLOR_ETH: u8 = 16;
+pub const TYPE_COLOR_MC_HDLC_POS: u8 = 17;
+pub const TYPE_AAL2: u8 = 18;
+pub const TYPE_INFINIBAND: u8 = 19;
+pub const TYPE_IPV4: u8 = 20;
+pub const TYPE_IPV6: u8 = 21;
+pub const TYPE_RAW_LINK: u8 = 22;
+pub const TYPE_INFINIBAND_LINK: u8 = 23;
+pub const TYPE_META: u8 = 27;
+pub const TYPE_PAD: u8 = 48;
+
+/// Highest record type that is recognized when probing a file.
+const TYPE_MAX: u8 = 48;
+
+/// Set in the type field when extension headers follow the record header.
+const TYPE_EXT_HDR: u8 = 0x80;
+
+const FLAG_PORT: u8 = 0x03;
+const FLAG_TRUNCATED: u8 = 0x08;
+const FLAG_RX_ERROR: u8 = 0x10;
+
+pub struct RecordHeader {
+    /// Fixed point timestamp. The upper 32 bits are seconds since the Unix
+    /// epoch, and the lower 32 bits are the binary fraction of a second.
+    pub ts: u64,
+    pub rec_type: u8,
+    pub flags: u8,
+    /// Length of the record, including all headers and padding.
+    pub rlen: u16,
+    /// Loss counter. The number of records dropped between this record and
+    /// the previous one.
+    pub lctr: u16,
+    /// Length of the packet on the wire.
+    pub wlen: u16,
+    pub ext_headers: Vec<u64>,
+}
+
+impl RecordHeader {
+    /// The record type, without the extension header bit.
+    pub fn record_type(&self) -> u8 {
+        self.rec_type & !TYPE_EXT_HDR
+    }
+
+    /// The capture port the record was received on, from 0 to 3.
+    pub fn port(&self) -> u8 {
+        self.flags & FLAG_PORT
+    }
+
+    /// True if the record was truncated because the card ran out of buffer
+    /// space.
+    pub fn is_truncated(&self) -> bool {
+        self.flags & FLAG_TRUNCATED != 0
+    }
+
+    pub fn has_rx_error(&self) -> bool {
+        self.flags & FLAG_RX_ERROR != 0
+    }
+
+    /// Seconds and nanoseconds since the Unix epoch.
+    pub fn timestamp(&self) -> (u32, u32) {
+        let frac = self.ts & 0xFFFFFFFF;
+        (
+            (self.ts >> 32) as u32,
+            ((frac * 1_000_000_000) >> 32) as u32,
+        )
+    }
+
+    /// The link type of the packet in the record, and the offset of the
+    /// packet in the record payload. Returns `None` for record types that
+    /// don't contain a packet, or whose packets have no matching link type.
+    pub fn packet_location(&self, payload: &[u8]) -> Option<(LinkType, usize)> {
+        match self.record_type() {
+            TYPE_ETH | TYPE_COLOR_ETH | TYPE_DSM_COLOR_ETH => Some((LinkType::ETHERNET, 2)),
+            TYPE_HDLC_POS | TYPE_COLOR_HDLC_POS | TYPE_DSM_COLOR_HDLC_POS => {
+                Some((hdlc_link_type(payload), 0))
+            }
+            // Multichannel records start with a 4 byte channel header
+            TYPE_MC_HDLC | TYPE_COLOR_MC_HDLC_POS => {
+                Some((hdlc_link_type(payload.get(4..).unwrap_or_default()), 4))
+            }
+            TYPE_INFINIBAND | TYPE_INFINIBAND_LINK => Some((LinkType::INFINIBAND, 0)),
+            TYPE_IPV4 => Some((LinkType::IPV4, 0)),
+            TYPE_IPV6 => Some((LinkType::IPV6, 0)),
+            _ => None,
+        }
+    }
+}
+
+/// PPP in HDLC-like framing always starts with the all-stations address
+/// and the unnumbered information control field. Anything else is taken to
+/// be Cisco HDLC.
+fn hdlc_link_type(data: &[u8]) -> LinkType {
+    if data.starts_with(&[0xFF, 0x03]) {
+        LinkType::PPP_HDLC
+    } else {
+        LinkType::C_HDLC
+    }
+}
+
+/// Checks whether `hdr`, the first 16 bytes of a file, look like an ERF
+/// record header. ERF files have no magic number, so this is only a
+/// heuristic, and should be tried after every format that does.
+pub fn probe(hdr: &[u8]) -> bool {
+    if hdr.len() < 16 {
+        return false;
+    }
+    let ts_sec = u32::from_le_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]);
+    let rec_type = hdr[8] & !TYPE_EXT_HDR;
+    let rlen = u16::from_be_bytes([hdr[10], hdr[11]]);
+    ts_sec != 0 && rec_type != 0 && rec_type <= TYPE_MAX && rlen >= 16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(rec_type: u8, flags: u8) -> RecordHeader {
+        RecordHeader {
+            ts: 0,
+            rec_type,
+            flags,
+            rlen: 16,
+            lctr: 0,
+            wlen: 0,
+            ext_headers: Vec::new(),
+        }
+    }
+
+    fn probe_header(ts_sec: u32, rec_type: u8, rlen: u16) -> Vec<u8> {
+        let mut hdr = vec![0; 4];
+        hdr.extend_from_slice(&ts_sec.to_le_bytes());
+        hdr.extend_from_slice(&[rec_type, 0]);
+        hdr.extend_from_slice(&rlen.to_be_bytes());
+        hdr.extend_from_slice(&[0; 4]);
+        hdr
+    }
+
+    #[test]
+    fn probe_record_header() {
+        assert!(probe(&probe_header(1, TYPE_ETH, 80)));
+        assert!(probe(&probe_header(1, TYPE_ETH | TYPE_EXT_HDR, 80)));
+        assert!(probe(&probe_header(1, TYPE_PAD, 16)));
+    }
+
+    #[test]
+    fn probe_rejects() {
+        assert!(!probe(&probe_header(1, TYPE_ETH, 80)[..15]));
+        assert!(!probe(&probe_header(0, TYPE_ETH, 80)));
+        assert!(!probe(&probe_header(1, 0, 80)));
+        assert!(!probe(&probe_header(1, TYPE_MAX + 1, 80)));
+        assert!(!probe(&probe_header(1, TYPE_ETH, 15)));
+    }
+
+    #[test]
+    fn timestamp() {
+        let mut hdr = header(TYPE_ETH, 0);
+        hdr.ts = (10 << 32) | (1 << 31);
+        assert_eq!(hdr.timestamp(), (10, 500_000_000));
+        hdr.ts = (10 << 32) | 0xffff_ffff;
+        assert_eq!(hdr.timestamp(), (10, 999_999_999));
+    }
+
+    #[test]
+    fn flags() {
+        let hdr = header(TYPE_ETH | TYPE_EXT_HDR, 0x02 | FLAG_TRUNCATED);
+        assert_eq!(hdr.record_type(), TYPE_ETH);
+        assert_eq!(hdr.port(), 2);
+        assert!(hdr.is_truncated());
+        assert!(!hdr.has_rx_error());
+        assert!(header(TYPE_ETH, FLAG_RX_ERROR).has_rx_error());
+    }
+
+    #[test]
+    fn packet_location() {
+        let loc = |rec_type, payload: &[u8]| header(rec_type, 0).packet_location(payload);
+        assert_eq!(loc(TYPE_ETH, &[]), Some((LinkType::ETHERNET, 2)));
+        assert_eq!(loc(TYPE_COLOR_ETH, &[]), Some((LinkType::ETHERNET, 2)));
+        assert_eq!(loc(TYPE_IPV4, &[]), Some((LinkType::IPV4, 0)));
+        assert_eq!(loc(TYPE_IPV6, &[]), Some((LinkType::IPV6, 0)));
+        assert_eq!(loc(TYPE_INFINIBAND, &[]), Some((LinkType::INFINIBAND, 0)));
+        assert_eq!(loc(TYPE_PAD, &[]), None);
+        assert_eq!(loc(TYPE_META, &[]), None);
+        assert_eq!(loc(TYPE_ATM, &[]), None);
+    }
+
+    #[test]
+    fn hdlc_packet_location() {
+        let loc = |rec_type, payload: &[u8]| header(rec_type, 0).packet_location(payload);
+        assert_eq!(
+            loc(TYPE_HDLC_POS, &[0xff, 0x03, 0, 0x21]),
+            Some((LinkType::PPP_HDLC, 0))
+        );
+        assert_eq!(
+            loc(TYPE_HDLC_POS, &[0x0f, 0x00, 0x08, 0x00]),
+            Some((LinkType::C_HDLC, 0))
+        );
+        // Multichannel records have a channel header before the frame
+        assert_eq!(
+            loc(TYPE_MC_HDLC, &[0, 0, 0, 1, 0xff, 0x03]),
+            Some((LinkType::PPP_HDLC, 4))
+        );
+        assert_eq!(loc(TYPE_MC_HDLC, &[0, 0]), Some((LinkType::C_HDLC, 4)));
+    }
+}
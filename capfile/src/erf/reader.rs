@@ -0,0 +1,211 @@
+use super::*;
+use sniffle_core::Error;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+pub struct Reader<F: tokio::io::AsyncBufRead + Send + Unpin> {
+    file: F,
+    pos: u64,
+    base: Option<u64>,
+}
+
+#[cfg(feature = "fs")]
+pub type FileReader = Reader<tokio::io::BufReader<tokio::fs::File>>;
+
+impl<F: tokio::io::AsyncBufRead + Send + Unpin> Reader<F> {
+    pub fn new(file: F) -> Self {
+        Self {
+            file,
+            pos: 0,
+            base: None,
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn open<P: AsRef<std::path::Path>>(path: P) -> Result<FileReader, Error> {
+        Ok(FileReader::new(tokio::io::BufReader::new(
+            tokio::fs::File::open(path).await?,
+        )))
+    }
+
+    /// Offset of the next record, relative to the start of the file.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Reads the next record of any type. `buffer` is filled with the
+    /// record payload, which follows the record header and any extension
+    /// headers.
+    pub async fn next_record(
+        &mut self,
+        buffer: &mut Vec<u8>,
+    ) -> Result<Option<RecordHeader>, Error> {
+        let mut hdr = [0u8; 16];
+        match self.file.read_exact(&mut hdr[..]).await {
+            Ok(_) => {}
+            Err(e) => {
+                let kind = e.kind();
+                match kind {
+                    std::io::ErrorKind::UnexpectedEof => {
+                        return Ok(None);
+                    }
+                    _ => {
+                        return Err(Error::from(e));
+                    }
+                }
+            }
+        }
+
+        let mut hdr = RecordHeader {
+            ts: u64::from_le_bytes([
+                hdr[0], hdr[1], hdr[2], hdr[3], hdr[4], hdr[5], hdr[6], hdr[7],
+            ]),
+            rec_type: hdr[8],
+            flags: hdr[9],
+            rlen: u16::from_be_bytes([hdr[10], hdr[11]]),
+            lctr: u16::from_be_bytes([hdr[12], hdr[13]]),
+            wlen: u16::from_be_bytes([hdr[14], hdr[15]]),
+            ext_headers: Vec::new(),
+        };
+        let mut len = usize::from(hdr.rlen)
+            .checked_sub(16)
+            .ok_or(Error::MalformedCapture)?;
+
+        // Each extension header has the same flag in its top bit when
+        // another one follows it
+        let mut more = hdr.rec_type & TYPE_EXT_HDR != 0;
+        while more {
+            len = len.checked_sub(8).ok_or(Error::MalformedCapture)?;
+            let ext = self.file.read_u64().await?;
+            more = ext >> 63 != 0;
+            hdr.ext_headers.push(ext);
+        }
+
+        buffer.resize(len, 0);
+        self.file.read_exact(&mut buffer[..]).await?;
+        self.pos += u64::from(hdr.rlen);
+        Ok(Some(hdr))
+    }
+}
+
+impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> Reader<F> {
+    /// Moves to the record at `pos`, as returned by `position`.
+    pub async fn seek(&mut self, pos: u64) -> Result<(), Error> {
+        let base = match self.base {
+            Some(base) => base,
+            None => {
+                let base = self.file.stream_position().await? - self.pos;
+                self.base = Some(base);
+                base
+            }
+        };
+        self.file.seek(std::io::SeekFrom::Start(base + pos)).await?;
+        self.pos = pos;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn header(rec_type: u8, rlen: u16) -> Vec<u8> {
+        let mut hdr = Vec::new();
+        hdr.extend_from_slice(&(10u64 << 32).to_le_bytes());
+        hdr.extend_from_slice(&[rec_type, 0]);
+        hdr.extend_from_slice(&rlen.to_be_bytes());
+        hdr.extend_from_slice(&[0, 1, 0, 4]);
+        hdr
+    }
+
+    fn read(file: Vec<u8>) -> Result<Vec<(RecordHeader, Vec<u8>)>, Error> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut reader = Reader::new(Cursor::new(file));
+            let mut records = Vec::new();
+            let mut buf = Vec::new();
+            while let Some(hdr) = reader.next_record(&mut buf).await? {
+                records.push((hdr, buf.clone()));
+            }
+            Ok(records)
+        })
+    }
+
+    #[test]
+    fn records() {
+        let mut file = header(TYPE_IPV4, 20);
+        file.extend_from_slice(&[0x45; 4]);
+        file.extend(header(TYPE_PAD, 16));
+        let records = read(file).unwrap();
+        assert_eq!(records.len(), 2);
+        let (hdr, payload) = &records[0];
+        assert_eq!((hdr.record_type(), hdr.lctr, hdr.wlen), (TYPE_IPV4, 1, 4));
+        assert_eq!(payload, &[0x45; 4]);
+        assert!(records[1].1.is_empty());
+    }
+
+    #[test]
+    fn extension_headers() {
+        let mut file = header(TYPE_IPV4 | TYPE_EXT_HDR, 36);
+        file.extend_from_slice(&((1u64 << 63) | 1).to_be_bytes());
+        file.extend_from_slice(&2u64.to_be_bytes());
+        file.extend_from_slice(&[0x45; 4]);
+        let records = read(file).unwrap();
+        let (hdr, payload) = &records[0];
+        assert_eq!(hdr.ext_headers, [(1 << 63) | 1, 2]);
+        assert_eq!(payload, &[0x45; 4]);
+    }
+
+    #[test]
+    fn position_and_seek() {
+        let mut file = header(TYPE_IPV4, 20);
+        file.extend_from_slice(&[1; 4]);
+        file.extend(header(TYPE_IPV4, 20));
+        file.extend_from_slice(&[2; 4]);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut reader = Reader::new(Cursor::new(file));
+            let mut buf = Vec::new();
+            assert_eq!(reader.position(), 0);
+            reader.next_record(&mut buf).await.unwrap().unwrap();
+            assert_eq!(reader.position(), 20);
+            reader.next_record(&mut buf).await.unwrap().unwrap();
+            assert_eq!(buf, [2; 4]);
+            reader.seek(0).await.unwrap();
+            reader.next_record(&mut buf).await.unwrap().unwrap();
+            assert_eq!(buf, [1; 4]);
+        });
+    }
+
+    #[test]
+    fn record_shorter_than_header() {
+        assert!(matches!(
+            read(header(TYPE_IPV4, 15)),
+            Err(Error::MalformedCapture)
+        ));
+    }
+
+    #[test]
+    fn extension_headers_longer_than_record() {
+        let mut file = header(TYPE_IPV4 | TYPE_EXT_HDR, 20);
+        file.extend_from_slice(&1u64.to_be_bytes());
+        assert!(matches!(read(file), Err(Error::MalformedCapture)));
+    }
+
+    #[test]
+    fn truncated_record() {
+        // A partial header is the end of the file
+        assert!(read(header(TYPE_IPV4, 20)[..10].to_vec())
+            .unwrap()
+            .is_empty());
+
+        // A partial payload is an error
+        let mut file = header(TYPE_IPV4, 20);
+        file.extend_from_slice(&[0x45; 2]);
+        assert!(matches!(read(file), Err(Error::Io(_))));
+    }
+}
@@ -0,0 +1,333 @@
+use super::reader::*;
+use crate::index::{IndexEntry, IndexedSniff, PacketIndex};
+use async_trait::async_trait;
+use sniffle_core::{
+    Bytes, Device, DeviceBuilder, Error, RawPacket, Session, SniffRaw, Timestamp,
+    TimestampPrecision,
+};
+use std::sync::Arc;
+
+const PORT_NAMES: [&str; 4] = ["Port A", "Port B", "Port C", "Port D"];
+
+pub struct Sniffer<F: tokio::io::AsyncBufRead + Send + Unpin> {
+    reader: Reader<F>,
+    buf: Vec<u8>,
+    ports: [Option<Arc<Device>>; 4],
+    index: PacketIndex,
+    next: usize,
+}
+
+#[cfg(feature = "fs")]
+pub type FileSniffer = Sniffer<tokio::io::BufReader<tokio::fs::File>>;
+
+impl<F: tokio::io::AsyncBufRead + Send + Unpin> Sniffer<F> {
+    pub async fn new_raw(file: F) -> Result<Self, Error> {
+        Ok(Self::with_reader(Reader::new(file)))
+    }
+
+    pub async fn new(file: F) -> Result<sniffle_core::Sniffer<Self>, Error> {
+        Ok(sniffle_core::Sniffer::new(Self::new_raw(file).await?))
+    }
+
+    pub async fn new_with_session(
+        file: F,
+        session: Session,
+    ) -> Result<sniffle_core::Sniffer<Self>, Error> {
+        Ok(sniffle_core::Sniffer::with_session(
+            Self::new_raw(file).await?,
+            session,
+        ))
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn open_raw<P: AsRef<std::path::Path>>(path: P) -> Result<FileSniffer, Error> {
+        Ok(FileSniffer::with_reader(FileReader::open(path).await?))
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn open<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<sniffle_core::Sniffer<FileSniffer>, Error> {
+        Ok(sniffle_core::Sniffer::new(Self::open_raw(path).await?))
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn open_with_session<P: AsRef<std::path::Path>>(
+        path: P,
+        session: Session,
+    ) -> Result<sniffle_core::Sniffer<FileSniffer>, Error> {
+        Ok(sniffle_core::Sniffer::with_session(
+            Self::open_raw(path).await?,
+            session,
+        ))
+    }
+
+    fn with_reader(reader: Reader<F>) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            ports: Default::default(),
+            index: PacketIndex::new(),
+            next: 0,
+        }
+    }
+
+    pub fn reader(&self) -> &Reader<F> {
+        &self.reader
+    }
+
+    pub fn reader_mut(&mut self) -> &mut Reader<F> {
+        &mut self.reader
+    }
+
+    /// The device for capture port `port`, which is created the first time
+    /// a packet from that port is read.
+    fn port_device(&mut self, port: u8) -> Arc<Device> {
+        self.ports[usize::from(port)]
+            .get_or_insert_with(|| {
+                Arc::new(
+                    DeviceBuilder::new()
+                        .name(PORT_NAMES[usize::from(port)].into())
+                        .device(),
+                )
+            })
+            .clone()
+    }
+}
+
+#[async_trait]
+impl<F: tokio::io::AsyncBufRead + Send + Unpin> SniffRaw for Sniffer<F> {
+    async fn sniff_raw(&mut self) -> Result<Option<RawPacket<'_>>, Error> {
+        loop {
+            let offset = self.reader.position();
+            let mut buf = std::mem::take(&mut self.buf);
+            let hdr = self.reader.next_record(&mut buf).await;
+            self.buf = buf;
+            let hdr = match hdr? {
+                Some(hdr) => hdr,
+                None => {
+                    self.index.finish(self.next);
+                    return Ok(None);
+                }
+            };
+            let (datalink, start) = match hdr.packet_location(&self.buf[..]) {
+                Some(loc) => loc,
+                None => continue,
+            };
+
+            // Records are padded, and may also include data past the end of
+            // the packet, so the captured length is limited by the wire
+            // length
+            let start = start.min(self.buf.len());
+            let end = self.buf.len().min(start + usize::from(hdr.wlen));
+            let (secs, nanos) = hdr.timestamp();
+            let ts = Timestamp::new(secs.into(), nanos).with_precision(TimestampPrecision::Nanos);
+            self.index.record(
+                self.next,
+                IndexEntry {
+                    offset,
+                    ts: ts.to_system_time(),
+                    caplen: (end - start) as u32,
+                    section: 0,
+                    ifaces: 0,
+                },
+            );
+            self.next += 1;
+            let device = self.port_device(hdr.port());
+            let data = Bytes::from(std::mem::take(&mut self.buf)).slice(start..end);
+            return Ok(Some(RawPacket::from_bytes(
+                datalink,
+                ts,
+                usize::from(hdr.wlen),
+                None,
+                data,
+                Some(device),
+            )));
+        }
+    }
+}
+
+#[async_trait]
+impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> IndexedSniff for Sniffer<F> {
+    fn index(&self) -> &PacketIndex {
+        &self.index
+    }
+
+    fn packet_number(&self) -> usize {
+        self.next
+    }
+
+    async fn seek_packet(&mut self, n: usize) -> Result<bool, Error> {
+        if let Some(entry) = self.index.get(n) {
+            self.reader.seek(entry.offset).await?;
+            self.next = n;
+            return Ok(true);
+        }
+
+        // Resume reading after the last indexed packet. Records that aren't
+        // packets may follow it, so it is read again to find the next one.
+        match self.index.entries().last().copied() {
+            Some(last) => {
+                self.reader.seek(last.offset).await?;
+                self.next = self.index.len() - 1;
+                if self.sniff_raw().await?.is_none() {
+                    return Ok(false);
+                }
+            }
+            None => {
+                self.reader.seek(0).await?;
+                self.next = 0;
+            }
+        }
+        while self.next < n {
+            if self.sniff_raw().await?.is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::erf::{TYPE_ETH, TYPE_HDLC_POS, TYPE_IPV4, TYPE_PAD};
+    use sniffle_core::LinkType;
+    use std::io::Cursor;
+
+    fn record(rec_type: u8, port: u8, ts: u64, wlen: u16, payload: &[u8]) -> Vec<u8> {
+        let rlen = (16 + payload.len() + 7) & !7;
+        let mut rec = Vec::new();
+        rec.extend_from_slice(&ts.to_le_bytes()[..]);
+        rec.push(rec_type);
+        rec.push(port | 0x04);
+        rec.extend_from_slice(&(rlen as u16).to_be_bytes()[..]);
+        rec.extend_from_slice(&0u16.to_be_bytes()[..]);
+        rec.extend_from_slice(&wlen.to_be_bytes()[..]);
+        rec.extend_from_slice(payload);
+        rec.resize(rlen, 0);
+        rec
+    }
+
+    /// An Ethernet packet, a padding record, a PPP packet and an IPv4
+    /// packet.
+    fn file() -> Vec<u8> {
+        let mut file = Vec::new();
+        let mut eth = vec![0, 0];
+        eth.extend_from_slice(&[0xee; 14][..]);
+        file.extend(record(TYPE_ETH, 1, (10 << 32) | (1 << 31), 64, &eth[..]));
+        file.extend(record(TYPE_PAD, 0, 11 << 32, 0, &[0; 8][..]));
+        file.extend(record(
+            TYPE_HDLC_POS,
+            0,
+            12 << 32,
+            4,
+            &[0xff, 0x03, 0, 0x21][..],
+        ));
+        file.extend(record(TYPE_IPV4, 2, 13 << 32, 3, &[0x45; 3][..]));
+        file
+    }
+
+    fn block_on<F: std::future::Future<Output = Result<(), Error>>>(f: F) {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(f)
+            .unwrap()
+    }
+
+    #[test]
+    fn erf_records() {
+        let secs = |secs, millis| {
+            std::time::SystemTime::UNIX_EPOCH
+                + std::time::Duration::from_secs(secs)
+                + std::time::Duration::from_millis(millis)
+        };
+        block_on(async {
+            let mut sniffer = crate::Sniffer::new_raw(Cursor::new(file())).await?;
+            let mut out = Vec::new();
+            while let Some(pkt) = sniffer.sniff_raw().await? {
+                out.push((
+                    pkt.datalink(),
+                    pkt.timestamp(),
+                    Vec::from(pkt.data()),
+                    pkt.orig_len(),
+                    String::from(pkt.device().unwrap().name()),
+                ));
+            }
+            assert_eq!(
+                out,
+                [
+                    (
+                        LinkType::ETHERNET,
+                        secs(10, 500),
+                        vec![0xee; 14],
+                        64,
+                        String::from("Port B")
+                    ),
+                    (
+                        LinkType::PPP_HDLC,
+                        secs(12, 0),
+                        vec![0xff, 0x03, 0, 0x21],
+                        4,
+                        String::from("Port A")
+                    ),
+                    (
+                        LinkType::IPV4,
+                        secs(13, 0),
+                        vec![0x45; 3],
+                        3,
+                        String::from("Port C")
+                    ),
+                ]
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn probe_file() {
+        block_on(async {
+            let file = file();
+            let ty = crate::CapfileType::from_file(&mut Cursor::new(&file[..])).await?;
+            assert!(ty.is_erf());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn seek_packet() {
+        block_on(async {
+            let mut sniffer = crate::Sniffer::new_raw(Cursor::new(file())).await?;
+            // Past the padding record, before the packets are indexed
+            assert!(sniffer.seek_packet(2).await?);
+            let pkt = sniffer.sniff_raw().await?.unwrap();
+            assert_eq!(pkt.datalink(), LinkType::IPV4);
+
+            // Back to an indexed packet
+            assert!(sniffer.seek_packet(1).await?);
+            let pkt = sniffer.sniff_raw().await?.unwrap();
+            assert_eq!(pkt.datalink(), LinkType::PPP_HDLC);
+
+            // The end of the file, and past it
+            assert!(sniffer.seek_packet(3).await?);
+            assert!(sniffer.sniff_raw().await?.is_none());
+            assert!(!sniffer.seek_packet(4).await?);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn truncated_record() {
+        block_on(async {
+            let mut file = file();
+            file.truncate(file.len() - 4);
+            let mut sniffer = crate::Sniffer::new_raw(Cursor::new(file)).await?;
+            for _ in 0..2 {
+                assert!(sniffer.sniff_raw().await?.is_some());
+            }
+            assert!(sniffer.sniff_raw().await.is_err());
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,208 @@
+//! Importers for packets stored in non-capture formats.
+
+use async_trait::async_trait;
+use sniffle_core::{Bytes, Error, LinkType, RawPacket, Session, SniffRaw};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncBufReadExt;
+
+/// Reads packets from a text hex dump, in the style of `text2pcap`.
+///
+/// Each line of a packet starts with the hex offset of its first byte,
+/// optionally prefixed with `0x` and/or followed by `:`, and then the bytes
+/// of the line as hex. Bytes may be grouped in pairs, as in `tcpdump -xx`.
+/// An offset of zero begins a new packet. Lines that don't start with an
+/// offset, such as packet headers, annotations, and blank lines, are
+/// ignored, as is any trailing ASCII column. This accepts the output of
+/// tcpdump, Wireshark's "Copy as Hex Dump", and `sniffle_core::HexDumper`.
+///
+/// Dumps carry no link type or timestamps, so packets are given the link
+/// type set with `datalink` (Ethernet by default) and timestamps one
+/// microsecond apart, starting from the time set with `start_time` (the
+/// current time by default).
+pub struct HexDumpSniffer<R: tokio::io::AsyncBufRead + Send + Unpin> {
+    reader: R,
+    datalink: LinkType,
+    start: SystemTime,
+    count: u32,
+    line: String,
+    started: bool,
+    next: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+/// Creates a `HexDumpSniffer` reading the hex dump from `reader`.
+pub fn from_hex_dump<R: tokio::io::AsyncBufRead + Send + Unpin>(reader: R) -> HexDumpSniffer<R> {
+    HexDumpSniffer::new_raw(reader)
+}
+
+impl<R: tokio::io::AsyncBufRead + Send + Unpin> HexDumpSniffer<R> {
+    pub fn new_raw(reader: R) -> Self {
+        Self {
+            reader,
+            datalink: LinkType::ETHERNET,
+            start: SystemTime::now(),
+            count: 0,
+            line: String::new(),
+            started: false,
+            next: Vec::new(),
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn new(reader: R) -> sniffle_core::Sniffer<Self> {
+        sniffle_core::Sniffer::new(Self::new_raw(reader))
+    }
+
+    pub fn new_with_session(reader: R, session: Session) -> sniffle_core::Sniffer<Self> {
+        sniffle_core::Sniffer::with_session(Self::new_raw(reader), session)
+    }
+
+    /// Sets the link type given to imported packets.
+    pub fn datalink(mut self, datalink: LinkType) -> Self {
+        self.datalink = datalink;
+        self
+    }
+
+    /// Sets the timestamp of the first imported packet.
+    pub fn start_time(mut self, start: SystemTime) -> Self {
+        self.start = start;
+        self
+    }
+
+    fn emit(&mut self) -> RawPacket<'_> {
+        let ts = self
+            .start
+            .checked_add(Duration::from_micros(self.count.into()))
+            .unwrap_or(self.start);
+        self.count += 1;
+        let data = Bytes::from(std::mem::take(&mut self.buf));
+        RawPacket::from_bytes(self.datalink, ts, data.len(), None, data, None)
+    }
+}
+
+#[async_trait]
+impl<R: tokio::io::AsyncBufRead + Send + Unpin> SniffRaw for HexDumpSniffer<R> {
+    async fn sniff_raw(&mut self) -> Result<Option<RawPacket<'_>>, Error> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line).await? == 0 {
+                if !self.started {
+                    return Ok(None);
+                }
+                self.started = false;
+                std::mem::swap(&mut self.buf, &mut self.next);
+                self.next.clear();
+                return Ok(Some(self.emit()));
+            }
+
+            let (offset, bytes) = match parse_line(&self.line[..]) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+
+            if offset == 0 {
+                let done = self.started;
+                if done {
+                    std::mem::swap(&mut self.buf, &mut self.next);
+                }
+                self.started = true;
+                self.next.clear();
+                self.next.extend_from_slice(&bytes[..]);
+                if done {
+                    return Ok(Some(self.emit()));
+                }
+            } else if self.started && offset <= self.next.len() {
+                // A lower offset than expected means the previous line's ASCII
+                // column was mistaken for hex bytes.
+                self.next.truncate(offset);
+                self.next.extend_from_slice(&bytes[..]);
+            } else {
+                return Err(Error::MalformedCapture);
+            }
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Option<(usize, Vec<u8>)> {
+    let line = line.trim();
+    let end = line.find(char::is_whitespace)?;
+    let offset = &line[..end];
+    let offset = offset.strip_suffix(':').unwrap_or(offset);
+    let offset = offset
+        .strip_prefix("0x")
+        .or_else(|| offset.strip_prefix("0X"))
+        .unwrap_or(offset);
+    if offset.len() < 2 || !offset.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let offset = usize::from_str_radix(offset, 16).ok()?;
+
+    let mut bytes = Vec::new();
+    let mut rest = &line[end..];
+    while bytes.len() < 16 {
+        let trimmed = rest.trim_start();
+        let gap = rest.len() - trimmed.len();
+        if trimmed.is_empty() || (gap >= 3 && !bytes.is_empty()) {
+            break;
+        }
+        let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+        let token = &trimmed[..end];
+        if (token.len() != 2 && token.len() != 4) || !token.chars().all(|c| c.is_ascii_hexdigit()) {
+            break;
+        }
+        for i in (0..token.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&token[i..i + 2], 16).ok()?);
+        }
+        rest = &trimmed[end..];
+    }
+
+    if bytes.is_empty() {
+        None
+    } else {
+        Some((offset, bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn import_hex_dump() {
+        let dump = "\
+Packet 1
+0000  ff ff ff ff ff ff 02 00  00 00 00 01 08 00 45 00  ..............E.
+0010  00 1c                                             ..
+0000-000d  Ethernet II
+
+12:00:00.000000 IP 192.168.0.1 > 192.168.0.255: ICMP echo request
+\t0x0000:  4500 001c 0000 4000 4001 0000 c0a8 0001  E.....@.@.......
+\t0x0010:  c0a8 00ff                                ....
+";
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+        let pkts = rt
+            .block_on(async {
+                let mut sniffer = from_hex_dump(dump.as_bytes())
+                    .datalink(LinkType::RAW)
+                    .start_time(start);
+                let mut pkts = Vec::new();
+                while let Some(pkt) = sniffer.sniff_raw().await? {
+                    pkts.push((pkt.datalink(), pkt.timestamp(), pkt.data().to_vec()));
+                }
+                Ok::<_, Error>(pkts)
+            })
+            .unwrap();
+
+        assert_eq!(pkts.len(), 2);
+        assert_eq!(pkts[0].0, LinkType::RAW);
+        assert_eq!(pkts[0].1, start);
+        assert_eq!(pkts[0].2.len(), 18);
+        assert_eq!(pkts[0].2[12..], [0x08, 0x00, 0x45, 0x00, 0x00, 0x1c]);
+        assert_eq!(pkts[1].1, start + Duration::from_micros(1));
+        assert_eq!(pkts[1].2.len(), 20);
+        assert_eq!(pkts[1].2[16..], [0xc0, 0xa8, 0x00, 0xff]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Random access to the packets of a capture file.
+//!
+//! Sniffers of seekable capture files record where each packet is as they
+//! read them. The resulting `PacketIndex` allows jumping straight to packet
+//! N, or to the first packet at or after a point in time, without reading
+//! the packets in between again.
+
+use async_trait::async_trait;
+use sniffle_core::{Error, SniffRaw};
+use std::time::SystemTime;
+
+/// The location of a packet in a capture file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub(crate) offset: u64,
+    pub(crate) ts: SystemTime,
+    pub(crate) caplen: u32,
+    pub(crate) section: u32,
+    pub(crate) ifaces: u32,
+}
+
+/// Packet locations collected by a sniffer. Packets are numbered from zero,
+/// in the order they appear in the file.
+#[derive(Debug, Clone, Default)]
+pub struct PacketIndex {
+    entries: Vec<IndexEntry>,
+    complete: bool,
+}
+
+/// A sniffer that can seek to any packet of its capture file.
+///
+/// The index is built as packets are read. Seeking past the indexed packets
+/// reads ahead to the requested packet, adding to the index on the way.
+#[async_trait]
+pub trait IndexedSniff: Send {
+    fn index(&self) -> &PacketIndex;
+
+    /// The number of the packet the next call to `sniff_raw` returns.
+    fn packet_number(&self) -> usize;
+
+    /// Positions the sniffer so that the next packet read is packet `n`.
+    /// Returns false, leaving the sniffer at the end of the file, if the
+    /// file has fewer than `n` packets.
+    async fn seek_packet(&mut self, n: usize) -> Result<bool, Error>;
+
+    /// Indexes the whole file. The sniffer is left at the same packet.
+    async fn build_index(&mut self) -> Result<&PacketIndex, Error> {
+        if !self.index().is_complete() {
+            let curr = self.packet_number();
+            self.seek_packet(usize::MAX).await?;
+            self.seek_packet(curr).await?;
+        }
+        Ok(self.index())
+    }
+
+    /// Positions the sniffer at the first packet with a timestamp at or
+    /// after `ts`. Returns false, leaving the sniffer at the end of the file,
+    /// if there is no such packet.
+    async fn seek_time(&mut self, ts: SystemTime) -> Result<bool, Error> {
+        let n = self.build_index().await?.find_time(ts);
+        self.seek_packet(n.unwrap_or(usize::MAX)).await
+    }
+}
+
+#[async_trait]
+impl<S: SniffRaw + IndexedSniff> IndexedSniff for sniffle_core::Sniffer<S> {
+    fn index(&self) -> &PacketIndex {
+        self.raw().index()
+    }
+
+    fn packet_number(&self) -> usize {
+        self.raw().packet_number()
+    }
+
+    async fn seek_packet(&mut self, n: usize) -> Result<bool, Error> {
+        self.raw_mut().seek_packet(n).await
+    }
+}
+
+impl IndexEntry {
+    /// Offset of the packet's record or block within the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn timestamp(&self) -> SystemTime {
+        self.ts
+    }
+
+    /// Number of bytes of the packet that were captured.
+    pub fn caplen(&self) -> u32 {
+        self.caplen
+    }
+}
+
+impl PacketIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of packets indexed so far.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns true once the end of the file has been reached, meaning every
+    /// packet is indexed.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn get(&self, n: usize) -> Option<&IndexEntry> {
+        self.entries.get(n)
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries[..]
+    }
+
+    /// Number of the first indexed packet with a timestamp at or after `ts`.
+    /// Captures are not required to be in timestamp order.
+    pub fn find_time(&self, ts: SystemTime) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.ts >= ts)
+    }
+
+    /// Records packet `n`, if it is the next unindexed packet.
+    pub(crate) fn record(&mut self, n: usize, entry: IndexEntry) {
+        if n == self.entries.len() {
+            self.entries.push(entry);
+        }
+    }
+
+    /// Records the end of the file, if every packet before it is indexed.
+    pub(crate) fn finish(&mut self, n: usize) {
+        if n == self.entries.len() {
+            self.complete = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{pcap, pcapng};
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    fn secs(ts: SystemTime) -> u64 {
+        ts.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn pcap_file() -> Vec<u8> {
+        let mut file = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        file.extend_from_slice(&[0u8; 8][..]);
+        file.extend_from_slice(&0xffffu32.to_le_bytes()[..]);
+        file.extend_from_slice(&1u32.to_le_bytes()[..]);
+        for sec in [10u32, 20, 30, 40] {
+            let len = sec / 10;
+            for field in [sec, 0, len, len] {
+                file.extend_from_slice(&field.to_le_bytes()[..]);
+            }
+            file.resize(file.len() + len as usize, sec as u8);
+        }
+        file
+    }
+
+    fn block(file: &mut Vec<u8>, id: u32, body: &[u8]) {
+        let len = (body.len() + 12) as u32;
+        file.extend_from_slice(&id.to_le_bytes()[..]);
+        file.extend_from_slice(&len.to_le_bytes()[..]);
+        file.extend_from_slice(body);
+        file.extend_from_slice(&len.to_le_bytes()[..]);
+    }
+
+    fn pcapng_file() -> Vec<u8> {
+        let mut file = Vec::new();
+        for (iface, sec) in [(0u32, 10u32), (0, 20), (1, 30), (0, 40)] {
+            if sec == 10 || sec == 30 {
+                let mut shb = vec![0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0];
+                shb.extend_from_slice(&[0xff; 8][..]);
+                block(&mut file, 0x0A0D0D0A, &shb[..]);
+                block(&mut file, 1, &[1, 0, 0, 0, 0xff, 0xff, 0, 0][..]);
+            }
+            if sec == 30 {
+                block(&mut file, 1, &[101, 0, 0, 0, 0xff, 0xff, 0, 0][..]);
+            }
+            let mut epb = Vec::new();
+            let ts = u64::from(sec) * 1_000_000;
+            for field in [iface, (ts >> 32) as u32, ts as u32, 4, 4] {
+                epb.extend_from_slice(&field.to_le_bytes()[..]);
+            }
+            epb.extend_from_slice(&[sec as u8; 4][..]);
+            block(&mut file, 6, &epb[..]);
+        }
+        file
+    }
+
+    async fn check<S: SniffRaw + IndexedSniff>(sniffer: &mut S) -> Result<(), Error> {
+        let pkt = sniffer.sniff_raw().await?.unwrap();
+        assert_eq!(secs(pkt.timestamp()), 10);
+        assert_eq!(sniffer.index().len(), 1);
+
+        assert!(sniffer.seek_packet(2).await?);
+        assert_eq!(sniffer.index().len(), 2);
+        let pkt = sniffer.sniff_raw().await?.unwrap();
+        assert_eq!(secs(pkt.timestamp()), 30);
+        let datalink = pkt.datalink();
+
+        assert!(
+            sniffer
+                .seek_time(SystemTime::UNIX_EPOCH + Duration::from_secs(15))
+                .await?
+        );
+        assert!(sniffer.index().is_complete());
+        assert_eq!(sniffer.index().len(), 4);
+        assert_eq!(sniffer.packet_number(), 1);
+        let pkt = sniffer.sniff_raw().await?.unwrap();
+        assert_eq!(secs(pkt.timestamp()), 20);
+
+        assert!(sniffer.seek_packet(2).await?);
+        let pkt = sniffer.sniff_raw().await?.unwrap();
+        assert_eq!(pkt.datalink(), datalink);
+        assert_eq!(secs(pkt.timestamp()), 30);
+        let pkt = sniffer.sniff_raw().await?.unwrap();
+        assert_eq!(secs(pkt.timestamp()), 40);
+        assert!(sniffer.sniff_raw().await?.is_none());
+
+        assert!(!sniffer.seek_packet(5).await?);
+        assert!(sniffer.sniff_raw().await?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn seek_packets() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut sniffer = pcap::Sniffer::new_raw(Cursor::new(pcap_file())).await?;
+            check(&mut sniffer).await?;
+            assert_eq!(sniffer.index().get(3).unwrap().caplen(), 4);
+
+            let mut sniffer = pcapng::Sniffer::new_raw(Cursor::new(pcapng_file())).await?;
+            check(&mut sniffer).await
+        })
+        .unwrap();
+    }
+}
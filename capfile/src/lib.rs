@@ -1,10 +1,28 @@
 #![doc = include_str!("../README.md")]
 
+pub mod anonymize;
+#[cfg(any(feature = "flate2", feature = "zstd"))]
+pub mod compress;
+mod edit;
+pub mod erf;
+pub mod import;
+pub mod index;
 pub mod pcap;
 pub mod pcapng;
+#[cfg(feature = "fs")]
+pub mod ring;
+mod slice;
 
 use async_trait::async_trait;
-use sniffle_core::{Error, RawPacket, Session, SniffRaw};
+use index::{IndexedSniff, PacketIndex};
+use sniffle_core::{Error, NameResolver, RawPacket, Session, SniffRaw};
+
+#[cfg(feature = "fs")]
+pub use edit::edit;
+pub use edit::edit_packets;
+#[cfg(feature = "fs")]
+pub use slice::slice;
+pub use slice::{slice_packets, SliceFilter};
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 #[non_exhaustive]
@@ -12,30 +30,45 @@ pub enum CapfileType {
     Unknown,
     Pcap,
     PcapNG,
+    Erf,
 }
 
 #[non_exhaustive]
 pub enum Sniffer<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> {
     Pcap(pcap::Sniffer<F>),
     PcapNG(pcapng::Sniffer<F>),
+    Erf(erf::Sniffer<F>),
 }
 
+#[cfg(feature = "fs")]
 pub type FileSniffer = Sniffer<tokio::io::BufReader<tokio::fs::File>>;
 
 impl CapfileType {
+    /// Detects the type of the capture file from its first bytes. The file
+    /// is left at the same position.
     pub async fn from_file<F: tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin>(
         file: &mut F,
     ) -> Result<Self, std::io::Error> {
-        let mut magic = [0u8; 4];
-        file.read_exact(&mut magic[..]).await?;
-        file.seek(std::io::SeekFrom::Current(-4)).await?;
-        let magic = u32::from_le_bytes(magic);
+        let mut hdr = [0u8; 16];
+        let mut len = 0;
+        while len < hdr.len() {
+            match file.read(&mut hdr[len..]).await? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        file.seek(std::io::SeekFrom::Current(-(len as i64))).await?;
+        if len < 4 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        let magic = u32::from_le_bytes([hdr[0], hdr[1], hdr[2], hdr[3]]);
         Ok(match magic {
             0x0A0D0D0A => Self::PcapNG,
             0xA1B2C3D4 => Self::Pcap,
             0xD4C3B2A1 => Self::Pcap,
             0xA1B23C4D => Self::Pcap,
             0x4D3CB2A1 => Self::Pcap,
+            _ if erf::probe(&hdr[..len]) => Self::Erf,
             _ => Self::Unknown,
         })
     }
@@ -48,6 +81,10 @@ impl CapfileType {
         matches!(self, Self::PcapNG)
     }
 
+    pub fn is_erf(&self) -> bool {
+        matches!(self, Self::Erf)
+    }
+
     pub fn is_unknown(&self) -> bool {
         matches!(self, Self::Unknown)
     }
@@ -59,6 +96,7 @@ impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> Sniffer<F
         Ok(match ft {
             CapfileType::Pcap => Self::Pcap(pcap::Sniffer::new_raw(file).await?),
             CapfileType::PcapNG => Self::PcapNG(pcapng::Sniffer::new_raw(file).await?),
+            CapfileType::Erf => Self::Erf(erf::Sniffer::new_raw(file).await?),
             CapfileType::Unknown => {
                 return Err(Error::MalformedCapture);
             }
@@ -73,39 +111,50 @@ impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> Sniffer<F
         file: F,
         session: Session,
     ) -> Result<sniffle_core::Sniffer<Self>, Error> {
-        Ok(sniffle_core::Sniffer::with_session(
-            Self::new_raw(file).await?,
-            session,
-        ))
+        Ok(Self::new_raw(file).await?.with_session(session))
     }
 
+    /// Opens the capture file at `path`. With the `flate2` or `zstd`
+    /// features, gzip or zstd compressed files are decompressed into a
+    /// temporary file first.
+    #[cfg(feature = "fs")]
     pub async fn open_raw<P: AsRef<std::path::Path>>(path: P) -> Result<FileSniffer, Error> {
-        FileSniffer::new_raw(tokio::io::BufReader::new(
-            tokio::fs::File::open(path).await?,
-        ))
-        .await
+        #[allow(unused_mut)]
+        let mut file = tokio::fs::File::open(path).await?;
+        #[cfg(any(feature = "flate2", feature = "zstd"))]
+        if let Some(compression) = compress::Compression::from_file(&mut file).await? {
+            file = compress::decompress(file, compression).await?;
+        }
+        FileSniffer::new_raw(tokio::io::BufReader::new(file)).await
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open<P: AsRef<std::path::Path>>(
         path: P,
     ) -> Result<sniffle_core::Sniffer<FileSniffer>, Error> {
         Ok(sniffle_core::Sniffer::new(Self::open_raw(path).await?))
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open_with_session<P: AsRef<std::path::Path>>(
         path: P,
         session: Session,
     ) -> Result<sniffle_core::Sniffer<FileSniffer>, Error> {
-        Ok(sniffle_core::Sniffer::with_session(
-            Self::open_raw(path).await?,
-            session,
-        ))
+        Ok(Self::open_raw(path).await?.with_session(session))
+    }
+
+    fn with_session(mut self, session: Session) -> sniffle_core::Sniffer<Self> {
+        if let (Self::PcapNG(pcapng), Some(resolver)) = (&mut self, session.get::<NameResolver>()) {
+            pcapng.set_name_resolver(resolver.clone());
+        }
+        sniffle_core::Sniffer::with_session(self, session)
     }
 
     pub fn capfile_type(&self) -> CapfileType {
         match self {
             Self::Pcap(_) => CapfileType::Pcap,
             Self::PcapNG(_) => CapfileType::PcapNG,
+            Self::Erf(_) => CapfileType::Erf,
         }
     }
 }
@@ -116,6 +165,34 @@ impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> SniffRaw
         match self {
             Self::Pcap(pcap) => pcap.sniff_raw().await,
             Self::PcapNG(pcapng) => pcapng.sniff_raw().await,
+            Self::Erf(erf) => erf.sniff_raw().await,
+        }
+    }
+}
+
+#[async_trait]
+impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> IndexedSniff for Sniffer<F> {
+    fn index(&self) -> &PacketIndex {
+        match self {
+            Self::Pcap(pcap) => pcap.index(),
+            Self::PcapNG(pcapng) => pcapng.index(),
+            Self::Erf(erf) => erf.index(),
+        }
+    }
+
+    fn packet_number(&self) -> usize {
+        match self {
+            Self::Pcap(pcap) => pcap.packet_number(),
+            Self::PcapNG(pcapng) => pcapng.packet_number(),
+            Self::Erf(erf) => erf.packet_number(),
+        }
+    }
+
+    async fn seek_packet(&mut self, n: usize) -> Result<bool, Error> {
+        match self {
+            Self::Pcap(pcap) => pcap.seek_packet(n).await,
+            Self::PcapNG(pcapng) => pcapng.seek_packet(n).await,
+            Self::Erf(erf) => erf.seek_packet(n).await,
         }
     }
 }
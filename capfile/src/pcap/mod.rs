@@ -3,11 +3,26 @@ mod recorder;
 mod sniffer;
 pub mod writer;
 
-pub use recorder::{FileRecorder, Recorder};
-pub use sniffer::{FileSniffer, Sniffer};
+#[cfg(feature = "fs")]
+pub use recorder::FileRecorder;
+pub use recorder::Recorder;
+#[cfg(feature = "fs")]
+pub use sniffer::FileSniffer;
+pub use sniffer::Sniffer;
 
-use pcaprs::TsPrecision;
+#[cfg(feature = "libpcap")]
+pub use pcaprs::TsPrecision;
 
+/// Timestamp precision of a pcap file. This is the same type as
+/// `pcaprs::TsPrecision` when the `libpcap` feature is enabled.
+#[cfg(not(feature = "libpcap"))]
+#[derive(Debug, Clone, Copy)]
+pub enum TsPrecision {
+    Micro,
+    Nano,
+}
+
+#[derive(Clone)]
 pub struct Header {
     pub magic: u32,
     pub version_major: u16,
@@ -31,6 +46,23 @@ const BE_MAGIC_N: u32 = u32::from_ne_bytes([0xA1, 0xB2, 0x3C, 0x4D]);
 const LE_MAGIC_N: u32 = u32::from_ne_bytes([0x4D, 0x3C, 0xB2, 0xA1]);
 
 impl Header {
+    /// Creates a header for a native endian file of link type `network`,
+    /// with timestamps of precision `tsprec`.
+    pub fn new(network: u32, snaplen: u32, tsprec: TsPrecision) -> Self {
+        Self {
+            magic: match tsprec {
+                TsPrecision::Nano => LE_MAGIC_N,
+                TsPrecision::Micro => LE_MAGIC_U,
+            },
+            version_major: 2,
+            version_minor: 4,
+            thiszone: 0,
+            sigfigs: 0,
+            snaplen,
+            network,
+        }
+    }
+
     pub fn is_big_endian(&self) -> bool {
         matches!(self.magic, BE_MAGIC_U | BE_MAGIC_N)
     }
@@ -46,4 +78,14 @@ impl Header {
     pub fn is_nano(&self) -> bool {
         matches!(self.magic, LE_MAGIC_N | BE_MAGIC_N)
     }
+
+    /// Precision of the `ts_frac` field of records, as selected by the
+    /// magic number.
+    pub fn timestamp_precision(&self) -> TsPrecision {
+        if self.is_nano() {
+            TsPrecision::Nano
+        } else {
+            TsPrecision::Micro
+        }
+    }
 }
@@ -1,14 +1,17 @@
 use super::*;
 use sniffle_core::Error;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 pub struct Reader<F: tokio::io::AsyncBufRead + Send + Unpin> {
     file: F,
     hdr: Header,
     be: bool,
     nano: bool,
+    pos: u64,
+    base: Option<u64>,
 }
 
+#[cfg(feature = "fs")]
 pub type FileReader = Reader<tokio::io::BufReader<tokio::fs::File>>;
 
 impl<F: tokio::io::AsyncBufRead + Send + Unpin> Reader<F> {
@@ -54,9 +57,12 @@ impl<F: tokio::io::AsyncBufRead + Send + Unpin> Reader<F> {
             hdr,
             be,
             nano,
+            pos: 24,
+            base: None,
         })
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open<P: AsRef<std::path::Path>>(path: P) -> Result<FileReader, Error> {
         FileReader::new(tokio::io::BufReader::new(
             tokio::fs::File::open(path).await?,
@@ -84,6 +90,11 @@ impl<F: tokio::io::AsyncBufRead + Send + Unpin> Reader<F> {
         }
     }
 
+    /// Offset of the next record, relative to the start of the file header.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
     pub async fn next_record(
         &mut self,
         buffer: &mut Vec<u8>,
@@ -122,6 +133,24 @@ impl<F: tokio::io::AsyncBufRead + Send + Unpin> Reader<F> {
 
         buffer.resize(hdr.incl_len as usize, 0);
         self.file.read_exact(&mut buffer[..]).await?;
+        self.pos += 16 + u64::from(hdr.incl_len);
         Ok(Some(hdr))
     }
 }
+
+impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> Reader<F> {
+    /// Moves to the record at `pos`, as returned by `position`.
+    pub async fn seek(&mut self, pos: u64) -> Result<(), Error> {
+        let base = match self.base {
+            Some(base) => base,
+            None => {
+                let base = self.file.stream_position().await? - self.pos;
+                self.base = Some(base);
+                base
+            }
+        };
+        self.file.seek(std::io::SeekFrom::Start(base + pos)).await?;
+        self.pos = pos;
+        Ok(())
+    }
+}
@@ -1,8 +1,8 @@
 use super::writer::*;
 use super::*;
 use async_trait::async_trait;
-use sniffle_core::{Error, RawPacket, Transmit};
-use std::time::{Duration, SystemTime};
+use sniffle_core::{Error, RawPacket, Timestamp, Transmit};
+use tokio::io::AsyncWriteExt;
 
 enum FileOrWriter<F: tokio::io::AsyncWrite + Send + Unpin> {
     File(F),
@@ -16,6 +16,7 @@ pub struct Recorder<F: tokio::io::AsyncWrite + Send + Unpin> {
     nano: bool,
 }
 
+#[cfg(feature = "fs")]
 pub type FileRecorder = Recorder<tokio::io::BufWriter<tokio::fs::File>>;
 
 impl<F: tokio::io::AsyncWrite + Send + Unpin> Recorder<F> {
@@ -27,6 +28,9 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Recorder<F> {
         Self::new_with_tsprec(file, TsPrecision::Nano)
     }
 
+    /// Creates a recorder that writes timestamps with precision `tsprec`.
+    /// Nanosecond precision files are written with the 0xA1B23C4D magic
+    /// number. Timestamps more precise than the file are truncated.
     pub fn new_with_tsprec(file: F, tsprec: TsPrecision) -> Self {
         Self {
             out: FileOrWriter::File(file),
@@ -35,14 +39,17 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Recorder<F> {
         }
     }
 
+    #[cfg(feature = "fs")]
     pub async fn create<P: AsRef<std::path::Path>>(path: P) -> Result<FileRecorder, Error> {
         FileRecorder::create_with_tsprec(path, TsPrecision::Micro).await
     }
 
+    #[cfg(feature = "fs")]
     pub async fn create_nano<P: AsRef<std::path::Path>>(path: P) -> Result<FileRecorder, Error> {
         FileRecorder::create_with_tsprec(path, TsPrecision::Nano).await
     }
 
+    #[cfg(feature = "fs")]
     pub async fn create_with_tsprec<P: AsRef<std::path::Path>>(
         path: P,
         tsprec: TsPrecision,
@@ -52,6 +59,134 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Recorder<F> {
             tsprec,
         ))
     }
+
+    /// Creates a recorder for a new compressed file at `path`. The file is
+    /// only complete after `close`.
+    #[cfg(all(feature = "fs", any(feature = "flate2", feature = "zstd")))]
+    pub async fn create_compressed<P: AsRef<std::path::Path>>(
+        path: P,
+        tsprec: TsPrecision,
+        compression: crate::compress::Compression,
+    ) -> Result<Recorder<crate::compress::CompressedFile>, Error> {
+        let file = tokio::io::BufWriter::new(tokio::fs::File::create(path).await?);
+        Ok(Recorder::new_with_tsprec(
+            crate::compress::CompressedWriter::new(file, compression)?,
+            tsprec,
+        ))
+    }
+
+    /// Opens the pcap file at `path` to record more packets after its last
+    /// record, with the timestamp precision of the file. Packets must have
+    /// the link type in the file header. A trailing record that was only
+    /// partially written, as when the recording process was killed, is
+    /// discarded. If the file doesn't exist or is empty, a new file is
+    /// created, as by `create`.
+    #[cfg(feature = "fs")]
+    pub async fn append<P: AsRef<std::path::Path>>(path: P) -> Result<FileRecorder, Error> {
+        FileRecorder::append_impl(path.as_ref(), None).await
+    }
+
+    /// Like `append`, but fails if the file has a timestamp precision other
+    /// than `tsprec`. A new file is created with precision `tsprec`.
+    #[cfg(feature = "fs")]
+    pub async fn append_with_tsprec<P: AsRef<std::path::Path>>(
+        path: P,
+        tsprec: TsPrecision,
+    ) -> Result<FileRecorder, Error> {
+        FileRecorder::append_impl(path.as_ref(), Some(tsprec)).await
+    }
+
+    #[cfg(feature = "fs")]
+    async fn append_impl(
+        path: &std::path::Path,
+        tsprec: Option<TsPrecision>,
+    ) -> Result<FileRecorder, Error> {
+        use tokio::io::AsyncSeekExt;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await?;
+        if file.metadata().await?.len() == 0 {
+            return Ok(FileRecorder::new_with_tsprec(
+                tokio::io::BufWriter::new(file),
+                tsprec.unwrap_or(TsPrecision::Micro),
+            ));
+        }
+
+        let mut reader = super::reader::Reader::new(tokio::io::BufReader::new(&mut file)).await?;
+        let mut buf = Vec::new();
+        loop {
+            match reader.next_record(&mut buf).await {
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+        let header = reader.header().clone();
+        let end = reader.position();
+        drop(reader);
+
+        if let Some(tsprec) = tsprec {
+            if header.is_nano() != matches!(tsprec, TsPrecision::Nano) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Timestamp precision does not match the existing file",
+                )
+                .into());
+            }
+        }
+        file.set_len(end).await?;
+        file.seek(std::io::SeekFrom::Start(end)).await?;
+        let nano = header.is_nano();
+        Ok(FileRecorder {
+            out: FileOrWriter::Writer(Writer::resume(tokio::io::BufWriter::new(file), &header)?),
+            buf: Vec::new(),
+            nano,
+        })
+    }
+
+    pub fn timestamp_precision(&self) -> TsPrecision {
+        if self.nano {
+            TsPrecision::Nano
+        } else {
+            TsPrecision::Micro
+        }
+    }
+
+    /// Flushes records written so far to the underlying file.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        match &mut self.out {
+            FileOrWriter::File(file) => {
+                file.flush().await?;
+                Ok(())
+            }
+            FileOrWriter::Writer(writer) => writer.flush().await,
+            FileOrWriter::Empty => Ok(()),
+        }
+    }
+
+    /// Flushes records written so far and shuts down the underlying file,
+    /// which completes compressed files.
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.flush().await?;
+        self.into_inner().shutdown().await?;
+        Ok(())
+    }
+
+    /// Returns the underlying file. Records that haven't been flushed may
+    /// still be buffered in it.
+    pub fn into_inner(self) -> F {
+        match self.out {
+            FileOrWriter::File(file) => file,
+            FileOrWriter::Writer(writer) => writer.into_inner(),
+            FileOrWriter::Empty => panic!("Recorder in erroneous state!"),
+        }
+    }
 }
 
 #[async_trait]
@@ -60,31 +195,39 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Transmit for Recorder<F> {
         let fow = std::mem::replace(&mut self.out, FileOrWriter::Empty);
         let mut writer = match fow {
             FileOrWriter::File(file) => {
-                let hdr = Header {
-                    magic: if self.nano { LE_MAGIC_N } else { LE_MAGIC_U },
-                    version_major: 2,
-                    version_minor: 4,
-                    thiszone: 0,
-                    sigfigs: 0,
-                    snaplen: packet.snaplen() as u32,
-                    network: packet.datalink().0.into(),
-                };
+                let mut hdr = Header::new(
+                    packet.datalink().0.into(),
+                    packet.snaplen() as u32,
+                    self.timestamp_precision(),
+                );
+                hdr.thiszone = packet
+                    .precise_timestamp()
+                    .utc_offset()
+                    .and_then(|offset| i32::try_from(offset).ok())
+                    .unwrap_or(0);
                 Writer::new(file, &hdr).await?
             }
             FileOrWriter::Writer(writer) => writer,
             _ => panic!("Recorder in erroneous state!"),
         };
+        // A pcap file has a single link type, set by its first packet
+        if writer.network() != u32::from(packet.datalink().0) {
+            self.out = FileOrWriter::Writer(writer);
+            return Err(Error::UnknownLinkType);
+        }
 
-        let dur = match packet.timestamp().duration_since(SystemTime::UNIX_EPOCH) {
-            Ok(val) => val,
-            Err(_) => Duration::new(0, 0),
+        let ts = packet.precise_timestamp();
+        let ts = if ts.secs() < 0 {
+            Timestamp::UNIX_EPOCH
+        } else {
+            ts
         };
         let hdr = RecordHeader {
-            ts_sec: dur.as_secs() as u32,
+            ts_sec: ts.secs() as u32,
             ts_frac: if self.nano {
-                dur.subsec_nanos()
+                ts.subsec_nanos()
             } else {
-                dur.subsec_micros()
+                ts.subsec_nanos() / 1000
             },
             incl_len: packet.data().len() as u32,
             orig_len: packet.orig_len() as u32,
@@ -98,3 +241,88 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Transmit for Recorder<F> {
         Some(&mut self.buf)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle_core::{LinkType, SniffRaw, TimestampPrecision};
+    use std::io::Cursor;
+
+    fn record(tsprec: TsPrecision) -> (Vec<u8>, Timestamp) {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut rec = Recorder::new_with_tsprec(Vec::new(), tsprec);
+            let ts = Timestamp::new(1_700_000_000, 123_456_789);
+            let packet = RawPacket::new(LinkType(1), ts, 1, None, &[0xab][..], None);
+            rec.transmit_raw(packet).await?;
+            let file = rec.into_inner();
+
+            let mut src = crate::pcap::Sniffer::new_raw(Cursor::new(file.clone())).await?;
+            let ts = src.sniff_raw().await?.unwrap().precise_timestamp();
+            Ok::<_, Error>((file, ts))
+        })
+        .unwrap()
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn append() {
+        let path = std::env::temp_dir().join(format!("sniffle-append-{}.pcap", std::process::id()));
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let res = rt.block_on(async {
+            let _ = tokio::fs::remove_file(&path).await;
+            let ts = Timestamp::new(1_700_000_000, 0);
+            for n in 0u8..2 {
+                let mut rec = FileRecorder::append_with_tsprec(&path, TsPrecision::Nano).await?;
+                let data = [n];
+                let packet = RawPacket::new(LinkType(1), ts, 1, None, &data[..], None);
+                rec.transmit_raw(packet).await?;
+                rec.flush().await?;
+            }
+
+            // Simulate a record cut short by a crash
+            let mut file = tokio::fs::read(&path).await?;
+            file.extend_from_slice(&[0u8; 8][..]);
+            file.extend_from_slice(&[100, 0, 0, 0, 100, 0, 0, 0, 0xee][..]);
+            tokio::fs::write(&path, &file).await?;
+
+            assert!(FileRecorder::append_with_tsprec(&path, TsPrecision::Micro)
+                .await
+                .is_err());
+            let mut rec = FileRecorder::append(&path).await?;
+            assert!(matches!(rec.timestamp_precision(), TsPrecision::Nano));
+            let packet = RawPacket::new(LinkType(101), ts, 1, None, &[0xff][..], None);
+            assert!(rec.transmit_raw(packet).await.is_err());
+            let packet = RawPacket::new(LinkType(1), ts, 1, None, &[2][..], None);
+            rec.transmit_raw(packet).await?;
+            rec.flush().await?;
+            drop(rec);
+
+            let mut src = crate::pcap::FileSniffer::open_raw(&path).await?;
+            let mut out = Vec::new();
+            while let Some(packet) = src.sniff_raw().await? {
+                out.push(packet.data()[0]);
+            }
+            Ok::<_, Error>(out)
+        });
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(res.unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn timestamp_precision() {
+        let (file, ts) = record(TsPrecision::Nano);
+        assert_eq!(file[..4], [0x4d, 0x3c, 0xb2, 0xa1]);
+        assert_eq!(ts.subsec_nanos(), 123_456_789);
+        assert_eq!(ts.precision(), TimestampPrecision::Nanos);
+
+        let (file, ts) = record(TsPrecision::Micro);
+        assert_eq!(file[..4], [0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(ts.subsec_nanos(), 123_456_000);
+        assert_eq!(ts.precision(), TimestampPrecision::Micros);
+    }
+}
@@ -1,14 +1,19 @@
 use super::reader::*;
 use super::*;
+use crate::index::{IndexEntry, IndexedSniff, PacketIndex};
 use async_trait::async_trait;
-use sniffle_core::{Error, LinkType, RawPacket, Session, SniffRaw};
-use std::time::{Duration, SystemTime};
+use sniffle_core::{
+    Bytes, Error, LinkType, RawPacket, Session, SniffRaw, Timestamp, TimestampPrecision,
+};
 
 pub struct Sniffer<F: tokio::io::AsyncBufRead + Send + Unpin> {
     reader: Reader<F>,
     buf: Vec<u8>,
+    index: PacketIndex,
+    next: usize,
 }
 
+#[cfg(feature = "fs")]
 pub type FileSniffer = Sniffer<tokio::io::BufReader<tokio::fs::File>>;
 
 impl<F: tokio::io::AsyncBufRead + Send + Unpin> Sniffer<F> {
@@ -16,6 +21,8 @@ impl<F: tokio::io::AsyncBufRead + Send + Unpin> Sniffer<F> {
         Ok(Self {
             reader: Reader::new(file).await?,
             buf: Vec::new(),
+            index: PacketIndex::new(),
+            next: 0,
         })
     }
 
@@ -33,19 +40,24 @@ impl<F: tokio::io::AsyncBufRead + Send + Unpin> Sniffer<F> {
         ))
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open_raw<P: AsRef<std::path::Path>>(path: P) -> Result<FileSniffer, Error> {
         Ok(FileSniffer {
             reader: FileReader::open(path).await?,
             buf: Vec::new(),
+            index: PacketIndex::new(),
+            next: 0,
         })
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open<P: AsRef<std::path::Path>>(
         path: P,
     ) -> Result<sniffle_core::Sniffer<FileSniffer>, Error> {
         Ok(sniffle_core::Sniffer::new(Self::open_raw(path).await?))
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open_with_session<P: AsRef<std::path::Path>>(
         path: P,
         session: Session,
@@ -68,28 +80,147 @@ impl<F: tokio::io::AsyncBufRead + Send + Unpin> Sniffer<F> {
 #[async_trait]
 impl<F: tokio::io::AsyncBufRead + Send + Unpin> SniffRaw for Sniffer<F> {
     async fn sniff_raw(&mut self) -> Result<Option<RawPacket<'_>>, Error> {
+        let offset = self.reader.position();
         let mut buf = std::mem::take(&mut self.buf);
-        let hdr = match self.reader.next_record(&mut buf).await? {
+        let hdr = self.reader.next_record(&mut buf).await;
+        self.buf = buf;
+        let hdr = match hdr? {
             Some(hdr) => hdr,
             None => {
+                self.index.finish(self.next);
                 return Ok(None);
             }
         };
-        self.buf = buf;
-        Ok(Some(RawPacket::new(
-            LinkType(self.reader.header().network as u16),
-            match self.reader.timestamp_precision() {
-                TsPrecision::Nano => SystemTime::UNIX_EPOCH
-                    .checked_add(Duration::new(hdr.ts_sec as u64, hdr.ts_frac))
-                    .unwrap_or(SystemTime::UNIX_EPOCH),
-                TsPrecision::Micro => SystemTime::UNIX_EPOCH
-                    .checked_add(Duration::new(hdr.ts_sec as u64, hdr.ts_frac * 1000))
-                    .unwrap_or(SystemTime::UNIX_EPOCH),
+        let (nanos, precision) = match self.reader.timestamp_precision() {
+            TsPrecision::Nano => (hdr.ts_frac, TimestampPrecision::Nanos),
+            TsPrecision::Micro => (hdr.ts_frac.saturating_mul(1000), TimestampPrecision::Micros),
+        };
+        let thiszone = self.reader.header().thiszone;
+        let ts = Timestamp::new(hdr.ts_sec.into(), nanos)
+            .with_precision(precision)
+            .with_utc_offset((thiszone != 0).then_some(thiszone.into()));
+        self.index.record(
+            self.next,
+            IndexEntry {
+                offset,
+                ts: ts.to_system_time(),
+                caplen: hdr.incl_len,
+                section: 0,
+                ifaces: 0,
             },
+        );
+        self.next += 1;
+        // Each packet is read into its own buffer, which is handed over to
+        // the packet
+        Ok(Some(RawPacket::from_bytes(
+            LinkType(self.reader.header().network as u16),
+            ts,
             hdr.orig_len as usize,
             Some(self.reader.header().snaplen as usize),
-            &self.buf[..],
+            Bytes::from(std::mem::take(&mut self.buf)),
             None,
         )))
     }
 }
+
+#[async_trait]
+impl<F: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Send + Unpin> IndexedSniff for Sniffer<F> {
+    fn index(&self) -> &PacketIndex {
+        &self.index
+    }
+
+    fn packet_number(&self) -> usize {
+        self.next
+    }
+
+    async fn seek_packet(&mut self, n: usize) -> Result<bool, Error> {
+        if let Some(entry) = self.index.get(n) {
+            self.reader.seek(entry.offset).await?;
+            self.next = n;
+            return Ok(true);
+        }
+
+        // Resume reading after the last indexed packet
+        match self.index.entries().last() {
+            Some(last) => {
+                let offset = last.offset + 16 + u64::from(last.caplen);
+                self.reader.seek(offset).await?;
+                self.next = self.index.len();
+            }
+            None => {
+                self.reader.seek(24).await?;
+                self.next = 0;
+            }
+        }
+        while self.next < n {
+            if self.sniff_raw().await?.is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures_core::Stream;
+    use std::pin::Pin;
+
+    fn pcap_file() -> Vec<u8> {
+        let mut file = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        file.extend_from_slice(&[0u8; 8][..]);
+        file.extend_from_slice(&0xffffu32.to_le_bytes()[..]);
+        file.extend_from_slice(&1u32.to_le_bytes()[..]);
+        for sec in [10u32, 20] {
+            for field in [sec, 0, 2, 2] {
+                file.extend_from_slice(&field.to_le_bytes()[..]);
+            }
+            file.extend_from_slice(&[sec as u8; 2][..]);
+        }
+        file
+    }
+
+    async fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[test]
+    fn packet_stream() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut sniffer = Sniffer::new(std::io::Cursor::new(pcap_file()))
+                .await
+                .unwrap();
+            let pkt = next(&mut sniffer).await.unwrap().unwrap();
+            assert_eq!(pkt.len(), 2);
+            assert_eq!(pkt.precise_timestamp(), Timestamp::new(10, 0));
+            let pkt = next(&mut sniffer).await.unwrap().unwrap();
+            assert_eq!(pkt.precise_timestamp(), Timestamp::new(20, 0));
+            assert!(next(&mut sniffer).await.is_none());
+            assert_eq!(sniffer.raw().reader().position(), 60);
+        });
+    }
+
+    #[test]
+    fn packet_stream_ends_after_error() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut file = pcap_file();
+            // A record cut short
+            for field in [30u32, 0, 10, 10] {
+                file.extend_from_slice(&field.to_le_bytes()[..]);
+            }
+            file.extend_from_slice(&[30u8; 2][..]);
+            let mut sniffer = Sniffer::new(std::io::Cursor::new(file)).await.unwrap();
+            assert!(next(&mut sniffer).await.unwrap().is_ok());
+            assert!(next(&mut sniffer).await.unwrap().is_ok());
+            assert!(next(&mut sniffer).await.unwrap().is_err());
+            assert!(next(&mut sniffer).await.is_none());
+        });
+    }
+}
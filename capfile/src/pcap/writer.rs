@@ -5,22 +5,17 @@ use tokio::io::AsyncWriteExt;
 pub struct Writer<F: tokio::io::AsyncWrite + Send + Unpin> {
     file: F,
     snaplen: u32,
+    network: u32,
     be: bool,
+    nano: bool,
 }
 
+#[cfg(feature = "fs")]
 pub type FileWriter = Writer<tokio::io::BufWriter<tokio::fs::File>>;
 
 impl<F: tokio::io::AsyncWrite + Send + Unpin> Writer<F> {
     pub async fn new(mut file: F, header: &Header) -> Result<Self, Error> {
-        let be = match header.magic {
-            BE_MAGIC_U => true,
-            BE_MAGIC_N => true,
-            LE_MAGIC_U => false,
-            LE_MAGIC_N => false,
-            _ => {
-                return Err(Error::MalformedCapture);
-            }
-        };
+        let be = Self::check_magic(header)?;
         file.write_all(&header.magic.to_ne_bytes()[..]).await?;
         if be {
             file.write_all(&header.version_major.to_be_bytes()[..])
@@ -41,13 +36,32 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Writer<F> {
             file.write_all(&header.snaplen.to_le_bytes()[..]).await?;
             file.write_all(&header.network.to_le_bytes()[..]).await?;
         }
+        Self::resume(file, header)
+    }
+
+    /// Creates a writer that appends records to `file`, which must already
+    /// contain `header` and be positioned after its last complete record.
+    pub fn resume(file: F, header: &Header) -> Result<Self, Error> {
         Ok(Writer {
             file,
             snaplen: header.snaplen,
-            be,
+            network: header.network,
+            be: Self::check_magic(header)?,
+            nano: header.is_nano(),
         })
     }
 
+    fn check_magic(header: &Header) -> Result<bool, Error> {
+        if header.is_big_endian() {
+            Ok(true)
+        } else if header.is_little_endian() {
+            Ok(false)
+        } else {
+            Err(Error::MalformedCapture)
+        }
+    }
+
+    #[cfg(feature = "fs")]
     pub async fn create<P: AsRef<std::path::Path>>(
         path: P,
         header: &Header,
@@ -59,10 +73,27 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Writer<F> {
         .await
     }
 
+    /// The link type of the file, from its header.
+    pub fn network(&self) -> u32 {
+        self.network
+    }
+
+    /// Precision of the `ts_frac` field of records, as selected by the
+    /// header the file was created with.
+    pub fn timestamp_precision(&self) -> TsPrecision {
+        if self.nano {
+            TsPrecision::Nano
+        } else {
+            TsPrecision::Micro
+        }
+    }
+
     pub async fn write_record(&mut self, header: &RecordHeader, data: &[u8]) -> Result<(), Error> {
+        let frac_limit = if self.nano { 1_000_000_000 } else { 1_000_000 };
         if header.incl_len as usize != data.len()
             || header.incl_len > header.orig_len
             || header.incl_len > self.snaplen
+            || header.ts_frac >= frac_limit
         {
             return Err(Error::MalformedCapture);
         }
@@ -97,4 +128,13 @@ impl<F: tokio::io::AsyncWrite + Send + Unpin> Writer<F> {
         self.file.write_all(data).await?;
         Ok(())
     }
+
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().await?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
 }
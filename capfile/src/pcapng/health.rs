@@ -0,0 +1,757 @@
+use super::reader::{Block, EpbOption, IdbOption, IsbOption, Reader};
+use super::sniffer::ts_calc;
+use sniffle_core::{Error, LinkType};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufRead, AsyncSeek};
+
+/// Builds a `HealthReport` from the statistics recorded in a pcapng file.
+///
+/// Capture loss is taken from Interface Statistics Blocks (`isb_ifdrop` and
+/// `isb_osdrop`) and from the `epb_dropcount` option of Enhanced Packet
+/// Blocks. Since ISB counters are cumulative, each pair of consecutive ISBs
+/// for an interface forms one `DropInterval` of the interface's timeline.
+#[derive(Debug, Clone)]
+pub struct HealthAnalyzer {
+    spike_ratio: f64,
+    loss_ratio: f64,
+}
+
+/// Capture health of each interface in a pcapng file. See `HealthAnalyzer`.
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    interfaces: Vec<InterfaceHealth>,
+    warnings: Vec<HealthWarning>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InterfaceHealth {
+    section: usize,
+    id: u32,
+    name: Option<String>,
+    link: LinkType,
+    speed: Option<u64>,
+    packets: u64,
+    bytes: u64,
+    first: Option<SystemTime>,
+    last: Option<SystemTime>,
+    received: Option<u64>,
+    dropped: u64,
+    has_stats: bool,
+    timeline: Vec<DropInterval>,
+}
+
+/// Capture statistics between two consecutive ISBs of an interface.
+#[derive(Debug, Clone)]
+pub struct DropInterval {
+    start: SystemTime,
+    end: SystemTime,
+    received: Option<u64>,
+    dropped: u64,
+    packets: u64,
+    bytes: u64,
+    utilization: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum HealthWarning {
+    /// The interface lost a significant share of its packets overall.
+    Loss {
+        section: usize,
+        interface: u32,
+        dropped: u64,
+        ratio: f64,
+    },
+    /// The interface lost a significant share of its packets within one
+    /// interval of its timeline.
+    DropSpike {
+        section: usize,
+        interface: u32,
+        start: SystemTime,
+        end: SystemTime,
+        dropped: u64,
+        ratio: f64,
+    },
+    /// The interface has packets, but no ISB or EPB drop counts, so capture
+    /// loss cannot be ruled out.
+    NoStatistics { section: usize, interface: u32 },
+}
+
+#[derive(Default)]
+struct Counters {
+    received: Option<u64>,
+    if_drop: Option<u64>,
+    os_drop: Option<u64>,
+}
+
+struct IfaceState {
+    health: InterfaceHealth,
+    tsresol: u8,
+    tsoffset: i64,
+    prev: Counters,
+    prev_time: Option<SystemTime>,
+    packets: u64,
+    bytes: u64,
+    epb_drops: u64,
+    interval_first: Option<SystemTime>,
+}
+
+fn delta(curr: Option<u64>, prev: Option<u64>) -> Option<u64> {
+    // A counter that goes backwards was reset, so it counts from zero again.
+    curr.map(|curr| match prev {
+        Some(prev) if prev <= curr => curr - prev,
+        _ => curr,
+    })
+}
+
+fn ratio(dropped: u64, received: Option<u64>, packets: u64) -> f64 {
+    let total = match received {
+        Some(received) if received >= dropped && received > 0 => received,
+        _ => packets + dropped,
+    };
+    if total == 0 {
+        0.0
+    } else {
+        dropped as f64 / total as f64
+    }
+}
+
+fn utilization(bytes: u64, speed: Option<u64>, duration: Duration) -> Option<f64> {
+    let secs = duration.as_secs_f64();
+    match speed {
+        Some(speed) if speed > 0 && secs > 0.0 => Some((bytes * 8) as f64 / (secs * speed as f64)),
+        _ => None,
+    }
+}
+
+fn elapsed(start: SystemTime, end: SystemTime) -> Duration {
+    end.duration_since(start).unwrap_or_default()
+}
+
+impl HealthAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            spike_ratio: 0.01,
+            loss_ratio: 0.001,
+        }
+    }
+
+    /// Share of packets that must be lost within a single interval for it to
+    /// be reported as a drop spike. Defaults to 1%.
+    pub fn spike_ratio(self, ratio: f64) -> Self {
+        let mut analyzer = self;
+        analyzer.spike_ratio = ratio;
+        analyzer
+    }
+
+    /// Share of packets that must be lost over the whole capture for an
+    /// interface to be reported as lossy. Defaults to 0.1%.
+    pub fn loss_ratio(self, ratio: f64) -> Self {
+        let mut analyzer = self;
+        analyzer.loss_ratio = ratio;
+        analyzer
+    }
+
+    /// Reads all remaining blocks from `reader` and builds the report.
+    pub async fn analyze<F: AsyncBufRead + AsyncSeek + Send + Unpin>(
+        &self,
+        reader: &mut Reader<F>,
+    ) -> Result<HealthReport, Error> {
+        let mut done = Vec::new();
+        let mut ifaces: Vec<IfaceState> = Vec::new();
+        let mut section = 0usize;
+        let mut started = false;
+
+        while let Some(block) = reader.next_block().await? {
+            match block {
+                Block::Shb(_) => {
+                    if started {
+                        section += 1;
+                    }
+                    started = true;
+                    done.extend(ifaces.drain(..).map(IfaceState::finish));
+                }
+                Block::Idb(mut idb) => {
+                    let mut name = None;
+                    let mut speed = None;
+                    let mut rx_speed = None;
+                    let mut tsresol = 6u8;
+                    let mut tsoffset = 0i64;
+                    while let Some(opt) = idb.next_option().await? {
+                        match opt {
+                            IdbOption::Name(mut opt) => {
+                                let mut s = String::new();
+                                opt.string(&mut s).await?;
+                                name = Some(s);
+                            }
+                            IdbOption::Speed(mut opt) => {
+                                speed = Some(opt.value().await?);
+                            }
+                            IdbOption::RxSpeed(mut opt) => {
+                                rx_speed = Some(opt.value().await?);
+                            }
+                            IdbOption::TsResol(mut opt) => {
+                                tsresol = opt.value().await?;
+                            }
+                            IdbOption::TsOffset(mut opt) => {
+                                tsoffset = opt.value().await?;
+                            }
+                            _ => {}
+                        }
+                    }
+                    let link = LinkType(idb.link_type().await?);
+                    ifaces.push(IfaceState::new(
+                        InterfaceHealth {
+                            section,
+                            id: ifaces.len() as u32,
+                            name,
+                            link,
+                            speed: rx_speed.or(speed),
+                            packets: 0,
+                            bytes: 0,
+                            first: None,
+                            last: None,
+                            received: None,
+                            dropped: 0,
+                            has_stats: false,
+                            timeline: Vec::new(),
+                        },
+                        tsresol,
+                        tsoffset,
+                    ));
+                }
+                Block::Epb(mut epb) => {
+                    let iface = ifaces
+                        .get_mut(epb.interface_id().await? as usize)
+                        .ok_or(Error::MalformedCapture)?;
+                    let ts = ts_calc(epb.timestamp().await?, iface.tsresol, iface.tsoffset);
+                    let len = epb.original_length().await? as u64;
+                    let mut drops = None;
+                    while let Some(opt) = epb.next_option().await? {
+                        if let EpbOption::DropCount(mut opt) = opt {
+                            drops = Some(opt.value().await?);
+                        }
+                    }
+                    iface.add_packet(ts, len, drops);
+                }
+                Block::Spb(mut spb) => {
+                    let iface = ifaces.get_mut(0).ok_or(Error::MalformedCapture)?;
+                    let len = spb.original_length().await? as u64;
+                    iface.packets += 1;
+                    iface.bytes += len;
+                    iface.health.packets += 1;
+                    iface.health.bytes += len;
+                }
+                Block::Isb(mut isb) => {
+                    let iface = ifaces
+                        .get_mut(isb.interface_id().await? as usize)
+                        .ok_or(Error::MalformedCapture)?;
+                    let ts = ts_calc(isb.timestamp().await?, iface.tsresol, iface.tsoffset);
+                    let mut counters = Counters::default();
+                    let mut start = None;
+                    while let Some(opt) = isb.next_option().await? {
+                        match opt {
+                            IsbOption::StartTime(mut opt) => {
+                                start = Some(ts_calc(
+                                    opt.timestamp().await?,
+                                    iface.tsresol,
+                                    iface.tsoffset,
+                                ));
+                            }
+                            IsbOption::IfRecv(mut opt) => {
+                                counters.received = Some(opt.value().await?);
+                            }
+                            IsbOption::IfDrop(mut opt) => {
+                                counters.if_drop = Some(opt.value().await?);
+                            }
+                            IsbOption::OsDrop(mut opt) => {
+                                counters.os_drop = Some(opt.value().await?);
+                            }
+                            _ => {}
+                        }
+                    }
+                    iface.add_stats(ts, start, counters);
+                }
+                _ => {}
+            }
+        }
+        done.extend(ifaces.drain(..).map(IfaceState::finish));
+
+        let mut report = HealthReport {
+            interfaces: done,
+            warnings: Vec::new(),
+        };
+        for iface in report.interfaces.iter() {
+            for interval in iface.timeline.iter() {
+                let ratio = interval.drop_ratio();
+                if interval.dropped > 0 && ratio >= self.spike_ratio {
+                    report.warnings.push(HealthWarning::DropSpike {
+                        section: iface.section,
+                        interface: iface.id,
+                        start: interval.start,
+                        end: interval.end,
+                        dropped: interval.dropped,
+                        ratio,
+                    });
+                }
+            }
+            let ratio = iface.drop_ratio();
+            if iface.dropped > 0 && ratio >= self.loss_ratio {
+                report.warnings.push(HealthWarning::Loss {
+                    section: iface.section,
+                    interface: iface.id,
+                    dropped: iface.dropped,
+                    ratio,
+                });
+            } else if !iface.has_stats && iface.packets > 0 {
+                report.warnings.push(HealthWarning::NoStatistics {
+                    section: iface.section,
+                    interface: iface.id,
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    /// Opens the pcapng file at `path` and builds its report.
+    #[cfg(feature = "fs")]
+    pub async fn analyze_file<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+    ) -> Result<HealthReport, Error> {
+        let mut reader = super::reader::FileReader::open(path).await?;
+        self.analyze(&mut reader).await
+    }
+}
+
+impl Default for HealthAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IfaceState {
+    fn new(health: InterfaceHealth, tsresol: u8, tsoffset: i64) -> Self {
+        Self {
+            health,
+            tsresol,
+            tsoffset,
+            prev: Counters::default(),
+            prev_time: None,
+            packets: 0,
+            bytes: 0,
+            epb_drops: 0,
+            interval_first: None,
+        }
+    }
+
+    fn add_packet(&mut self, ts: SystemTime, len: u64, drops: Option<u64>) {
+        let health = &mut self.health;
+        health.packets += 1;
+        health.bytes += len;
+        health.first = Some(health.first.map_or(ts, |first| first.min(ts)));
+        health.last = Some(health.last.map_or(ts, |last| last.max(ts)));
+        self.packets += 1;
+        self.bytes += len;
+        self.interval_first = Some(self.interval_first.map_or(ts, |first| first.min(ts)));
+        if let Some(drops) = drops {
+            health.has_stats = true;
+            self.epb_drops += drops;
+        }
+    }
+
+    fn add_stats(&mut self, ts: SystemTime, start: Option<SystemTime>, counters: Counters) {
+        let received = delta(counters.received, self.prev.received);
+        let isb_drops = match (
+            delta(counters.if_drop, self.prev.if_drop),
+            delta(counters.os_drop, self.prev.os_drop),
+        ) {
+            (None, None) => None,
+            (if_drop, os_drop) => Some(if_drop.unwrap_or(0) + os_drop.unwrap_or(0)),
+        };
+        // EPB drop counts and ISB drop counters usually describe the same
+        // losses, so they are not added together.
+        let dropped = isb_drops.unwrap_or(0).max(self.epb_drops);
+        let start = self
+            .prev_time
+            .or(start)
+            .or(self.interval_first)
+            .unwrap_or(ts);
+
+        let health = &mut self.health;
+        health.has_stats = health.has_stats || received.is_some() || isb_drops.is_some();
+        if counters.received.is_some() {
+            health.received = Some(health.received.unwrap_or(0) + received.unwrap_or(0));
+        }
+        health.dropped += dropped;
+        health.timeline.push(DropInterval {
+            start,
+            end: ts,
+            received,
+            dropped,
+            packets: self.packets,
+            bytes: self.bytes,
+            utilization: utilization(self.bytes, health.speed, elapsed(start, ts)),
+        });
+
+        self.prev = Counters {
+            received: counters.received.or(self.prev.received),
+            if_drop: counters.if_drop.or(self.prev.if_drop),
+            os_drop: counters.os_drop.or(self.prev.os_drop),
+        };
+        self.prev_time = Some(ts);
+        self.packets = 0;
+        self.bytes = 0;
+        self.epb_drops = 0;
+        self.interval_first = None;
+    }
+
+    fn finish(self) -> InterfaceHealth {
+        let mut health = self.health;
+        health.dropped += self.epb_drops;
+        health
+    }
+}
+
+impl HealthReport {
+    pub fn interfaces(&self) -> &[InterfaceHealth] {
+        &self.interfaces[..]
+    }
+
+    pub fn warnings(&self) -> &[HealthWarning] {
+        &self.warnings[..]
+    }
+
+    /// True if nothing suggests that analysis of the capture may be skewed
+    /// by capture loss.
+    pub fn is_healthy(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Total number of packets lost across all interfaces.
+    pub fn dropped(&self) -> u64 {
+        self.interfaces.iter().map(|iface| iface.dropped).sum()
+    }
+}
+
+impl InterfaceHealth {
+    /// Index of the section header block the interface belongs to.
+    pub fn section(&self) -> usize {
+        self.section
+    }
+
+    /// Interface ID within its section.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn link_type(&self) -> LinkType {
+        self.link
+    }
+
+    /// Interface speed in bits per second, if recorded.
+    pub fn speed(&self) -> Option<u64> {
+        self.speed
+    }
+
+    /// Number of packets in the file.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Number of bytes on the wire, using original packet lengths.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn first_timestamp(&self) -> Option<SystemTime> {
+        self.first
+    }
+
+    pub fn last_timestamp(&self) -> Option<SystemTime> {
+        self.last
+    }
+
+    pub fn duration(&self) -> Duration {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => elapsed(first, last),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Number of packets received by the interface according to its ISBs.
+    pub fn received(&self) -> Option<u64> {
+        self.received
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Share of packets that were lost, between 0 and 1.
+    pub fn drop_ratio(&self) -> f64 {
+        ratio(self.dropped, self.received, self.packets)
+    }
+
+    /// True if the file has any ISB counters or EPB drop counts for the
+    /// interface.
+    pub fn has_statistics(&self) -> bool {
+        self.has_stats
+    }
+
+    /// Average share of the interface speed used over the capture, or `None`
+    /// if the speed is unknown.
+    pub fn utilization(&self) -> Option<f64> {
+        utilization(self.bytes, self.speed, self.duration())
+    }
+
+    /// Highest utilization of any interval in the timeline.
+    pub fn peak_utilization(&self) -> Option<f64> {
+        self.timeline
+            .iter()
+            .filter_map(DropInterval::utilization)
+            .reduce(f64::max)
+    }
+
+    pub fn timeline(&self) -> &[DropInterval] {
+        &self.timeline[..]
+    }
+}
+
+impl DropInterval {
+    pub fn start(&self) -> SystemTime {
+        self.start
+    }
+
+    pub fn end(&self) -> SystemTime {
+        self.end
+    }
+
+    pub fn duration(&self) -> Duration {
+        elapsed(self.start, self.end)
+    }
+
+    /// Number of packets received by the interface during the interval, if
+    /// the ISBs include `isb_ifrecv`.
+    pub fn received(&self) -> Option<u64> {
+        self.received
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Number of packets in the file during the interval.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Share of packets that were lost, between 0 and 1.
+    pub fn drop_ratio(&self) -> f64 {
+        ratio(self.dropped, self.received, self.packets)
+    }
+
+    pub fn utilization(&self) -> Option<f64> {
+        self.utilization
+    }
+}
+
+impl std::fmt::Display for HealthWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Loss {
+                section,
+                interface,
+                dropped,
+                ratio,
+            } => write!(
+                f,
+                "interface {}:{} lost {} packets ({:.2}%), results may be skewed",
+                section,
+                interface,
+                dropped,
+                ratio * 100.0
+            ),
+            Self::DropSpike {
+                section,
+                interface,
+                start,
+                end,
+                dropped,
+                ratio,
+            } => write!(
+                f,
+                "interface {}:{} lost {} packets ({:.2}%) over {:.3}s",
+                section,
+                interface,
+                dropped,
+                ratio * 100.0,
+                elapsed(*start, *end).as_secs_f64()
+            ),
+            Self::NoStatistics { section, interface } => write!(
+                f,
+                "interface {}:{} has no drop statistics, capture loss is unknown",
+                section, interface
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for iface in self.interfaces.iter() {
+            write!(f, "Interface {}:{}", iface.section, iface.id)?;
+            if let Some(name) = iface.name() {
+                write!(f, " ({})", name)?;
+            }
+            write!(
+                f,
+                ": {} packets, {} bytes, {} dropped ({:.2}%)",
+                iface.packets,
+                iface.bytes,
+                iface.dropped,
+                iface.drop_ratio() * 100.0
+            )?;
+            if let Some(util) = iface.utilization() {
+                write!(f, ", {:.2}% utilization", util * 100.0)?;
+            }
+            writeln!(f)?;
+            let base = iface.timeline.first().map(DropInterval::start);
+            for interval in iface.timeline.iter() {
+                let base = base.unwrap_or(interval.start);
+                write!(
+                    f,
+                    "  +{:.3}s..+{:.3}s: {} packets, {} dropped ({:.2}%)",
+                    elapsed(base, interval.start).as_secs_f64(),
+                    elapsed(base, interval.end).as_secs_f64(),
+                    interval.packets,
+                    interval.dropped,
+                    interval.drop_ratio() * 100.0
+                )?;
+                if let Some(util) = interval.utilization {
+                    write!(f, ", {:.2}% utilization", util * 100.0)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        for warning in self.warnings.iter() {
+            writeln!(f, "Warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn block(file: &mut Vec<u8>, id: u32, body: &[u8]) {
+        let len = (body.len() + 12) as u32;
+        file.extend_from_slice(&id.to_le_bytes()[..]);
+        file.extend_from_slice(&len.to_le_bytes()[..]);
+        file.extend_from_slice(body);
+        file.extend_from_slice(&len.to_le_bytes()[..]);
+    }
+
+    fn option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+        body.extend_from_slice(&code.to_le_bytes()[..]);
+        body.extend_from_slice(&(value.len() as u16).to_le_bytes()[..]);
+        body.extend_from_slice(value);
+        body.resize(body.len() + (4 - value.len() % 4) % 4, 0);
+    }
+
+    fn timestamp(body: &mut Vec<u8>, ts: u64) {
+        body.extend_from_slice(&((ts >> 32) as u32).to_le_bytes()[..]);
+        body.extend_from_slice(&(ts as u32).to_le_bytes()[..]);
+    }
+
+    fn capture() -> Vec<u8> {
+        let mut file = Vec::new();
+        block(
+            &mut file,
+            0x0A0D0D0A,
+            &[
+                0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            ][..],
+        );
+        let mut idb = vec![1, 0, 0, 0, 0xff, 0xff, 0, 0];
+        option(&mut idb, 2, b"eth0");
+        option(&mut idb, 8, &8_000u64.to_le_bytes()[..]);
+        option(&mut idb, 0, &[]);
+        block(&mut file, 1, &idb[..]);
+        for (sec, drops) in [(0u64, 0u64), (1, 0), (2, 5), (3, 0)] {
+            let mut epb = vec![0, 0, 0, 0];
+            timestamp(&mut epb, sec * 1_000_000);
+            epb.extend_from_slice(&4u32.to_le_bytes()[..]);
+            epb.extend_from_slice(&100u32.to_le_bytes()[..]);
+            epb.extend_from_slice(&[0u8; 4][..]);
+            option(&mut epb, 4, &drops.to_le_bytes()[..]);
+            option(&mut epb, 0, &[]);
+            block(&mut file, 6, &epb[..]);
+            if sec % 2 == 1 {
+                let mut isb = vec![0, 0, 0, 0];
+                timestamp(&mut isb, sec * 1_000_000);
+                if sec == 1 {
+                    option(&mut isb, 2, &[0u8; 8][..]);
+                }
+                option(&mut isb, 4, &(sec * 50).to_le_bytes()[..]);
+                option(&mut isb, 5, &(sec / 3 * 5).to_le_bytes()[..]);
+                option(&mut isb, 0, &[]);
+                block(&mut file, 5, &isb[..]);
+            }
+        }
+        file
+    }
+
+    #[test]
+    fn capture_health() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let report = rt
+            .block_on(async {
+                let mut reader = Reader::new(Cursor::new(capture())).await?;
+                HealthAnalyzer::new().analyze(&mut reader).await
+            })
+            .unwrap();
+
+        assert_eq!(report.interfaces().len(), 1);
+        let iface = &report.interfaces()[0];
+        assert_eq!(iface.name(), Some("eth0"));
+        assert_eq!(iface.packets(), 4);
+        assert_eq!(iface.bytes(), 400);
+        assert_eq!(iface.received(), Some(150));
+        assert_eq!(iface.dropped(), 5);
+        assert_eq!(iface.utilization(), Some(3200.0 / 24_000.0));
+
+        let timeline = iface.timeline();
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[0].duration(), Duration::from_secs(1));
+        assert_eq!(timeline[0].received(), Some(50));
+        assert_eq!(timeline[0].dropped(), 0);
+        assert_eq!(timeline[1].packets(), 2);
+        assert_eq!(timeline[1].received(), Some(100));
+        assert_eq!(timeline[1].dropped(), 5);
+        assert_eq!(iface.peak_utilization(), Some(0.2));
+
+        assert!(!report.is_healthy());
+        assert_eq!(report.warnings().len(), 2);
+        assert!(matches!(
+            report.warnings()[0],
+            HealthWarning::DropSpike { dropped: 5, .. }
+        ));
+        assert!(matches!(
+            report.warnings()[1],
+            HealthWarning::Loss { dropped: 5, .. }
+        ));
+    }
+}
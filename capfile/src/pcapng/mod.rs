@@ -1,15 +1,92 @@
+mod health;
 pub mod reader;
 mod recorder;
 mod sniffer;
 pub mod writer;
 
-pub use recorder::{FileRecorder, Recorder};
-pub use sniffer::{FileSniffer, Sniffer};
+pub use health::{DropInterval, HealthAnalyzer, HealthReport, HealthWarning, InterfaceHealth};
+#[cfg(feature = "fs")]
+pub use recorder::FileRecorder;
+pub use recorder::{Recorder, SectionInfo, TsResolution};
+#[cfg(feature = "fs")]
+pub use sniffer::FileSniffer;
+pub use sniffer::Sniffer;
 
-use sniffle_core::HwAddress;
 use sniffle_core::Ipv4Address;
 use sniffle_core::Ipv6Address;
 use sniffle_core::MacAddress;
+use sniffle_core::{EuiAddress, HwAddress};
+
+pub use sniffle_core::{Direction, ReceptionType};
+
+/// A block of a type the reader doesn't interpret, copied out of a file
+/// with `RawBlock::to_custom`. `Writer::write_custom_block` writes it back
+/// byte for byte, so unknown blocks survive reading and rewriting a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomBlock {
+    id: u32,
+    big_endian: bool,
+    content: Vec<u8>,
+}
+
+/// An option the reader doesn't interpret, copied out of a file with
+/// `RawOpt::to_custom`. The `write_custom_option` method of each option
+/// writer writes it back byte for byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomOption {
+    code: u16,
+    big_endian: bool,
+    content: Vec<u8>,
+}
+
+impl CustomBlock {
+    /// `content` is everything between the block length fields, including
+    /// padding and options.
+    pub fn new(id: u32, big_endian: bool, content: Vec<u8>) -> Self {
+        Self {
+            id,
+            big_endian,
+            content,
+        }
+    }
+
+    pub fn block_id(&self) -> u32 {
+        self.id
+    }
+
+    /// True if the block was read from a big endian section.
+    pub fn big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content[..]
+    }
+}
+
+impl CustomOption {
+    /// `content` is the option value, without padding.
+    pub fn new(code: u16, big_endian: bool, content: Vec<u8>) -> Self {
+        Self {
+            code,
+            big_endian,
+            content,
+        }
+    }
+
+    pub fn option_code(&self) -> u16 {
+        self.code
+    }
+
+    /// True if the option was read from a big endian section.
+    pub fn big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content[..]
+    }
+}
 
 const BE_MAGIC: u32 = u32::from_ne_bytes([0x1A, 0x2B, 0x3C, 0x4D]);
 const LE_MAGIC: u32 = u32::from_ne_bytes([0x4D, 0x3C, 0x2B, 0x1A]);
@@ -82,17 +159,3 @@ const ISB_ID: u32 = 0x00000005;
 const SJB_ID: u32 = 0x00000009;
 const DSB_ID: u32 = 0x0000000A;
 //const OPB_ID: u32 = 0x00000002;
-
-pub enum Direction {
-    Inbound,
-    Outbound,
-    Unknown,
-}
-
-pub enum ReceptionType {
-    Unicast,
-    Multicast,
-    Broadcast,
-    Promiscuous,
-    Unspecified,
-}
@@ -11,6 +11,7 @@ pub struct Reader<F: AsyncBufRead + AsyncSeek + Send + Unpin> {
     first_snaplen: Option<u32>,
 }
 
+#[cfg(feature = "fs")]
 pub type FileReader = Reader<tokio::io::BufReader<tokio::fs::File>>;
 
 pub enum Block<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> {
@@ -90,7 +91,7 @@ pub struct MacOpt<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> {
 
 pub struct EuiOpt<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> {
     reader: &'a mut Reader<F>,
-    addr: Option<HwAddress<8>>,
+    addr: Option<EuiAddress>,
 }
 
 pub struct TimestampOpt<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> {
@@ -286,6 +287,7 @@ pub struct SimplePacketBlock<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> {
     reader: &'a mut Reader<F>,
     data: Option<Spb>,
     offset: u64,
+    len: u32,
 }
 
 struct Spb {
@@ -396,6 +398,7 @@ impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> Reader<F> {
         Ok(Self::init(file, pos))
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<FileReader> {
         Ok(FileReader::init(
             tokio::io::BufReader::new(tokio::fs::File::open(path).await?),
@@ -520,6 +523,28 @@ impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> Reader<F> {
         }
     }
 
+    /// Offset of the block most recently returned by `next_block`.
+    pub fn block_offset(&self) -> u64 {
+        self.curr
+    }
+
+    /// Offset of the block the next call to `next_block` returns.
+    pub fn next_block_offset(&self) -> u64 {
+        self.next
+    }
+
+    /// Returns true if the current section is big endian encoded.
+    pub fn big_endian_encoded(&self) -> bool {
+        self.be
+    }
+
+    /// Moves to the block at `offset`, within a section with the given
+    /// endianness.
+    pub fn seek_block(&mut self, offset: u64, big_endian: bool) {
+        self.next = offset;
+        self.be = big_endian;
+    }
+
     pub async fn next_block(&mut self) -> Result<Option<Block<'_, F>>, Error> {
         let id = match self.read_u32_at(self.next).await {
             Ok(id) => id,
@@ -699,7 +724,7 @@ impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> InterfaceDescriptionBlock<'
         rdr: &'a mut Reader<F>,
         len: u32,
     ) -> Result<InterfaceDescriptionBlock<'a, F>, Error> {
-        let opt_end = rdr.pos + ((len as u64) - 20);
+        let opt_end = rdr.pos + ((len as u64) - 12);
         let next = rdr.pos + 8;
         let mut blk = Self {
             reader: rdr,
@@ -957,7 +982,7 @@ impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> EnhancedPacketBlock<'a, F>
         },
         EPB_QUEUE => {
             if len != 4 { return Err(Error::MalformedCapture); }
-            EpbOption::PacketId(U64Opt {
+            EpbOption::Queue(U32Opt {
                 reader: self.reader,
                 value: None,
             })
@@ -1029,8 +1054,8 @@ impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> EnhancedPacketBlock<'a, F>
 }
 
 impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> SimplePacketBlock<'a, F> {
-    fn new(rdr: &'a mut Reader<F>, _len: u32) -> Result<Self, Error> {
-        if rdr.first_snaplen.is_none() {
+    fn new(rdr: &'a mut Reader<F>, len: u32) -> Result<Self, Error> {
+        if len < 16 {
             return Err(Error::MalformedCapture);
         }
 
@@ -1039,15 +1064,22 @@ impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> SimplePacketBlock<'a, F> {
             reader: rdr,
             data: None,
             offset,
+            len,
         })
     }
 
     async fn data(&mut self) -> Result<&mut Spb, Error> {
         let ready = self.data.is_some();
         if !ready {
-            let snaplen = self.reader.first_snaplen.unwrap_or(0);
+            // The captured length is the original length, truncated to the
+            // snaplen of the first interface (zero meaning no limit). The
+            // block length bounds it too, in case the interface is unknown,
+            // as when the reader was moved with `seek_block`.
             let orig_len = self.reader.read_u32_at(self.offset - 4).await?;
-            let cap_len = std::cmp::min(snaplen, orig_len);
+            let mut cap_len = std::cmp::min(orig_len, self.len - 16);
+            if let Some(snaplen) = self.reader.first_snaplen.filter(|snaplen| *snaplen != 0) {
+                cap_len = std::cmp::min(cap_len, snaplen);
+            }
             self.data = Some(Spb { cap_len, orig_len });
         }
         Ok(guarantee(self.data.as_mut()))
@@ -1338,6 +1370,13 @@ impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> RawBlock<'a, F> {
         self.reader.read_buf_at(&mut buf[..], self.offset).await?;
         Ok(())
     }
+
+    /// Copies the block out of the file, to be written back unchanged.
+    pub async fn to_custom(&mut self) -> Result<CustomBlock, Error> {
+        let mut content = Vec::new();
+        self.content(&mut content).await?;
+        Ok(CustomBlock::new(self.id, self.reader.be, content))
+    }
 }
 
 impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> RawOpt<'a, F> {
@@ -1354,6 +1393,13 @@ impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> RawOpt<'a, F> {
         self.reader.read_buf_at(&mut buf[..], self.offset).await?;
         Ok(())
     }
+
+    /// Copies the option out of the file, to be written back unchanged.
+    pub async fn to_custom(&mut self) -> Result<CustomOption, Error> {
+        let mut content = Vec::new();
+        self.content(&mut content).await?;
+        Ok(CustomOption::new(self.code, self.reader.be, content))
+    }
 }
 
 impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> StringOpt<'a, F> {
@@ -1530,7 +1576,7 @@ impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> MacOpt<'a, F> {
 }
 
 impl<'a, F: AsyncBufRead + AsyncSeek + Send + Unpin> EuiOpt<'a, F> {
-    pub async fn address(&mut self) -> Result<HwAddress<8>, Error> {
+    pub async fn address(&mut self) -> Result<EuiAddress, Error> {
         let ready = self.addr.is_some();
         if !ready {
             let mut addr = [0u8; 8];
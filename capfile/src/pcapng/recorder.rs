@@ -1,10 +1,11 @@
 use super::writer::*;
 use super::*;
 use async_trait::async_trait;
-use sniffle_core::{Device, Error, LinkType, RawPacket, Transmit};
+#[cfg(feature = "libpcap")]
+use sniffle_core::SniffRaw;
+use sniffle_core::{CaptureStats, Device, Error, LinkType, RawPacket, Timestamp, Transmit};
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::time::SystemTime;
 use tokio::io::{AsyncSeek, AsyncWrite, AsyncWriteExt};
 
 struct IfaceKey {
@@ -13,17 +14,43 @@ struct IfaceKey {
     snaplen: u32,
 }
 
+#[derive(Clone, Copy)]
 struct IfaceInfo {
     id: u32,
     ts_offset: i64,
+    tsresol: TsResolution,
+}
+
+/// The timestamp resolution of an interface, written as its `if_tsresol`
+/// option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TsResolution {
+    /// Units of 10^-n seconds.
+    Decimal(u8),
+    /// Units of 2^-n seconds.
+    Binary(u8),
+}
+
+/// Descriptive options written to the Section Header Block of a recording.
+/// The OS is also written as the `if_os` option of every interface, like
+/// dumpcap does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SectionInfo {
+    hardware: Option<String>,
+    os: Option<String>,
+    user_app: Option<String>,
 }
 
 pub struct Recorder<F: AsyncWrite + AsyncSeek + Send + Unpin> {
     writer: Writer<F>,
     ifaces: HashMap<IfaceKey, IfaceInfo>,
+    tsresol: TsResolution,
+    iface_tsresol: HashMap<String, TsResolution>,
+    iface_os: Option<String>,
     buf: Vec<u8>,
 }
 
+#[cfg(feature = "fs")]
 pub type FileRecorder = Recorder<tokio::io::BufWriter<tokio::fs::File>>;
 
 impl Hash for IfaceKey {
@@ -49,21 +76,177 @@ impl PartialEq for IfaceKey {
 
 impl Eq for IfaceKey {}
 
+impl IfaceInfo {
+    /// Converts `ts` to the interface's timestamp units.
+    fn ticks(&self, ts: Timestamp) -> u64 {
+        let secs = ts.secs().wrapping_sub(self.ts_offset) as u64;
+        self.tsresol.ticks(secs, ts.subsec_nanos())
+    }
+}
+
+impl TsResolution {
+    pub const MICROS: Self = Self::Decimal(6);
+    pub const NANOS: Self = Self::Decimal(9);
+
+    /// Decodes the value of an `if_tsresol` option.
+    pub fn from_tsresol(tsresol: u8) -> Self {
+        if (tsresol & 0b1000_0000) == 0 {
+            Self::Decimal(tsresol)
+        } else {
+            Self::Binary(tsresol & 0b0111_1111)
+        }
+    }
+
+    /// Encodes the resolution as the value of an `if_tsresol` option.
+    /// Returns `None` if the exponent doesn't fit in 7 bits.
+    pub fn tsresol(&self) -> Option<u8> {
+        match *self {
+            Self::Decimal(n) if n < 0x80 => Some(n),
+            Self::Binary(n) if n < 0x80 => Some(n | 0b1000_0000),
+            _ => None,
+        }
+    }
+
+    pub(super) fn ticks_per_sec(&self) -> u128 {
+        match *self {
+            Self::Decimal(n) => 10u128.saturating_pow(n.into()),
+            Self::Binary(n) => 1u128.checked_shl(n.into()).unwrap_or(u128::MAX),
+        }
+    }
+
+    /// Converts seconds and nanoseconds to a count of ticks, truncating to
+    /// the resolution. Wraps if the count doesn't fit in 64 bits.
+    fn ticks(&self, secs: u64, nanos: u32) -> u64 {
+        let per_sec = self.ticks_per_sec();
+        let frac = u128::from(nanos).saturating_mul(per_sec) / 1_000_000_000;
+        u128::from(secs).wrapping_mul(per_sec).wrapping_add(frac) as u64
+    }
+}
+
+fn encode_tsresol(tsresol: TsResolution) -> Result<u8, Error> {
+    tsresol.tsresol().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Timestamp resolution exponent must be less than 128",
+        )
+        .into()
+    })
+}
+
+impl Default for TsResolution {
+    fn default() -> Self {
+        Self::NANOS
+    }
+}
+
+impl SectionInfo {
+    /// Creates an empty description, for which no options are written.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describes the machine and program doing the recording: the
+    /// operating system, the CPU architecture, and this library's version.
+    pub fn host() -> Self {
+        Self {
+            hardware: Some(String::from(std::env::consts::ARCH)),
+            os: Some(host_os()),
+            user_app: Some(format!("sniffle {}", env!("CARGO_PKG_VERSION"))),
+        }
+    }
+
+    pub fn with_hardware<S: Into<String>>(mut self, hardware: S) -> Self {
+        self.hardware = Some(hardware.into());
+        self
+    }
+
+    pub fn with_os<S: Into<String>>(mut self, os: S) -> Self {
+        self.os = Some(os.into());
+        self
+    }
+
+    pub fn with_user_app<S: Into<String>>(mut self, user_app: S) -> Self {
+        self.user_app = Some(user_app.into());
+        self
+    }
+
+    pub fn hardware(&self) -> Option<&str> {
+        self.hardware.as_deref()
+    }
+
+    pub fn os(&self) -> Option<&str> {
+        self.os.as_deref()
+    }
+
+    pub fn user_app(&self) -> Option<&str> {
+        self.user_app.as_deref()
+    }
+}
+
+/// The OS name, followed by the kernel release where it can be read, as in
+/// "linux 6.1.0".
+fn host_os() -> String {
+    let os = std::env::consts::OS;
+    match std::fs::read_to_string("/proc/sys/kernel/osrelease") {
+        Ok(release) if !release.trim().is_empty() => format!("{} {}", os, release.trim()),
+        _ => String::from(os),
+    }
+}
+
 impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Recorder<F> {
     pub async fn new(file: F) -> Result<Self, Error> {
+        Self::with_section_info(file, &SectionInfo::new()).await
+    }
+
+    /// Creates a recorder whose section header carries the options in
+    /// `info`.
+    pub async fn with_section_info(file: F, info: &SectionInfo) -> Result<Self, Error> {
         let mut writer = Writer::new(file);
-        writer
+        let mut opts = writer
             .write_shb(0x01020304u32.to_ne_bytes() == [1, 2, 3, 4], 1, 0)
-            .await?
-            .finish()
             .await?;
+        if let Some(hardware) = info.hardware() {
+            opts.write_hardware(hardware).await?;
+        }
+        if let Some(os) = info.os() {
+            opts.write_os(os).await?;
+        }
+        if let Some(user_app) = info.user_app() {
+            opts.write_user_app(user_app).await?;
+        }
+        opts.finish().await?;
         Ok(Self {
             writer,
             ifaces: HashMap::new(),
+            tsresol: TsResolution::default(),
+            iface_tsresol: HashMap::new(),
+            iface_os: info.os.clone(),
             buf: Vec::new(),
         })
     }
 
+    /// Sets the timestamp resolution of interfaces written after this call.
+    /// Defaults to nanoseconds.
+    pub fn set_tsresol(&mut self, tsresol: TsResolution) -> Result<(), Error> {
+        encode_tsresol(tsresol)?;
+        self.tsresol = tsresol;
+        Ok(())
+    }
+
+    /// Sets the timestamp resolution of the interface for the device named
+    /// `device`, overriding `set_tsresol`. Only affects the interface if it
+    /// hasn't been written yet.
+    pub fn set_iface_tsresol<S: Into<String>>(
+        &mut self,
+        device: S,
+        tsresol: TsResolution,
+    ) -> Result<(), Error> {
+        encode_tsresol(tsresol)?;
+        self.iface_tsresol.insert(device.into(), tsresol);
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
     pub async fn create<P: AsRef<std::path::Path>>(path: P) -> Result<FileRecorder, Error> {
         FileRecorder::new(tokio::io::BufWriter::new(
             tokio::fs::File::create(path).await?,
@@ -71,7 +254,182 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Recorder<F> {
         .await
     }
 
-    async fn write_iface(&mut self, packet: &RawPacket<'_>, ts_offset: i64) -> Result<(), Error> {
+    /// Creates a file recorder whose section header carries the options in
+    /// `info`.
+    #[cfg(feature = "fs")]
+    pub async fn create_with_section_info<P: AsRef<std::path::Path>>(
+        path: P,
+        info: &SectionInfo,
+    ) -> Result<FileRecorder, Error> {
+        FileRecorder::with_section_info(
+            tokio::io::BufWriter::new(tokio::fs::File::create(path).await?),
+            info,
+        )
+        .await
+    }
+
+    /// Creates a recorder for a new compressed file at `path`. Blocks are
+    /// kept uncompressed in memory until the recorder is flushed, so flush
+    /// it periodically. The file is only complete after `close`.
+    #[cfg(all(feature = "fs", any(feature = "flate2", feature = "zstd")))]
+    pub async fn create_compressed<P: AsRef<std::path::Path>>(
+        path: P,
+        compression: crate::compress::Compression,
+    ) -> Result<Recorder<crate::compress::CompressedFile>, Error> {
+        let file = tokio::io::BufWriter::new(tokio::fs::File::create(path).await?);
+        Recorder::new(crate::compress::CompressedWriter::seekable(
+            file,
+            compression,
+        )?)
+        .await
+    }
+
+    /// Opens the pcapng file at `path` to record more packets after its last
+    /// block. A new section is started, so interfaces are described again
+    /// as packets from them are recorded. A trailing block that was only
+    /// partially written, as when the recording process was killed, is
+    /// discarded. If the file doesn't exist or is empty, a new file is
+    /// created, as by `create`.
+    #[cfg(feature = "fs")]
+    pub async fn append<P: AsRef<std::path::Path>>(path: P) -> Result<FileRecorder, Error> {
+        FileRecorder::append_with_section_info(path, &SectionInfo::new()).await
+    }
+
+    /// Like `append`, but the new section header carries the options in
+    /// `info`.
+    #[cfg(feature = "fs")]
+    pub async fn append_with_section_info<P: AsRef<std::path::Path>>(
+        path: P,
+        info: &SectionInfo,
+    ) -> Result<FileRecorder, Error> {
+        use tokio::io::AsyncSeekExt;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await?;
+        let end = complete_blocks_len(&mut file).await?;
+        file.set_len(end).await?;
+        file.seek(std::io::SeekFrom::Start(end)).await?;
+        FileRecorder::with_section_info(tokio::io::BufWriter::new(file), info).await
+    }
+
+    /// Writes an Interface Statistics Block for each interface recorded from
+    /// `device`, like dumpcap does when a capture ends. The counts are
+    /// written as `isb_filteraccept` (`received`), `isb_osdrop` (`dropped`),
+    /// `isb_ifdrop` (`iface_dropped`), and `isb_ifrecv` (their sum). `end`
+    /// is the block timestamp. Returns false, writing nothing, if no packets
+    /// from `device` have been recorded.
+    pub async fn write_stats(
+        &mut self,
+        device: &Device,
+        stats: &CaptureStats,
+        start: Option<Timestamp>,
+        end: Timestamp,
+    ) -> Result<bool, Error> {
+        let mut ifaces: Vec<IfaceInfo> = self
+            .ifaces
+            .iter()
+            .filter(|(key, _)| key.iface.as_ref().map(|dev| dev.name()) == Some(device.name()))
+            .map(|(_, info)| *info)
+            .collect();
+        ifaces.sort_unstable_by_key(|iface| iface.id);
+        for iface in ifaces.iter() {
+            let mut opts = self.writer.write_isb(iface.id, iface.ticks(end)).await?;
+            if let Some(start) = start {
+                opts.write_start_time(iface.ticks(start)).await?;
+            }
+            opts.write_end_time(iface.ticks(end)).await?;
+            opts.write_ifrecv(stats.received + stats.dropped + stats.iface_dropped)
+                .await?;
+            opts.write_ifdrop(stats.iface_dropped).await?;
+            opts.write_filter_accept(stats.received).await?;
+            opts.write_osdrop(stats.dropped).await?;
+            opts.finish().await?;
+        }
+        Ok(!ifaces.is_empty())
+    }
+
+    /// Writes the current statistics of `sniffer` with `write_stats`,
+    /// covering the time from the start of the capture until now.
+    #[cfg(feature = "libpcap")]
+    pub async fn write_device_stats(
+        &mut self,
+        sniffer: &sniffle_core::DeviceSniffer,
+    ) -> Result<bool, Error> {
+        let stats = sniffer.stats()?;
+        self.write_stats(
+            sniffer.device(),
+            &stats,
+            Some(sniffer.start_time()),
+            Timestamp::now(),
+        )
+        .await
+    }
+
+    /// Records packets from `sniffer` until it ends or `limit` packets have
+    /// been recorded, then writes its statistics with `write_device_stats`.
+    /// The interface is described from the sniffer's device, with a
+    /// timestamp resolution matching the device's precision. Returns the
+    /// number of packets recorded.
+    #[cfg(feature = "libpcap")]
+    pub async fn record_device(
+        &mut self,
+        sniffer: &mut sniffle_core::DeviceSniffer,
+        limit: Option<u64>,
+    ) -> Result<u64, Error> {
+        let tsresol = match sniffer.timestamp_precision() {
+            sniffle_core::DeviceTsPrecision::Micro => TsResolution::MICROS,
+            sniffle_core::DeviceTsPrecision::Nano => TsResolution::NANOS,
+        };
+        self.set_iface_tsresol(sniffer.device().name(), tsresol)?;
+        let mut count = 0u64;
+        while limit.map(|limit| count < limit).unwrap_or(true) {
+            match sniffer.sniff_raw().await? {
+                Some(packet) => self.transmit_raw(packet).await?,
+                None => break,
+            }
+            count += 1;
+        }
+        self.write_device_stats(sniffer).await?;
+        Ok(count)
+    }
+
+    /// Writes a block read from another file, unchanged, such as one from
+    /// `Sniffer::take_custom_blocks`. Fails if the block was read from a big
+    /// endian section and this machine is little endian, or vice versa.
+    pub async fn write_custom_block(&mut self, block: &CustomBlock) -> Result<(), Error> {
+        self.writer.write_custom_block(block).await
+    }
+
+    /// Flushes blocks written so far to the underlying file.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush().await
+    }
+
+    /// Flushes blocks written so far and shuts down the underlying file,
+    /// which completes compressed files.
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.flush().await?;
+        self.into_inner().shutdown().await?;
+        Ok(())
+    }
+
+    /// Returns the underlying file. Blocks that haven't been flushed may
+    /// still be buffered in it.
+    pub fn into_inner(self) -> F {
+        self.writer.into_inner()
+    }
+
+    async fn write_iface(
+        &mut self,
+        packet: &RawPacket<'_>,
+        ts_offset: i64,
+        tsresol: TsResolution,
+    ) -> Result<(), Error> {
         let mut opts = self
             .writer
             .write_idb(packet.datalink().0, packet.snaplen() as u32)
@@ -98,12 +456,75 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Recorder<F> {
                 opts.write_mac_address(*addr).await?;
             }
         }
+        if let Some(os) = self.iface_os.as_deref() {
+            opts.write_os(os).await?;
+        }
         opts.write_tsoffset(ts_offset).await?;
-        opts.write_tsresol(9).await?;
+        opts.write_tsresol(encode_tsresol(tsresol)?).await?;
         opts.finish().await
     }
 }
 
+/// Length of the prefix of `file` made of complete blocks. Fails if `file`
+/// is not empty and doesn't start with a Section Header Block.
+#[cfg(feature = "fs")]
+async fn complete_blocks_len(file: &mut tokio::fs::File) -> Result<u64, Error> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    const SHB: u32 = 0x0A0D0D0A;
+    const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+    let len = file.metadata().await?.len();
+    let mut pos = 0u64;
+    let mut be = false;
+    let mut hdr = [0u8; 12];
+    while len - pos >= 12 {
+        file.seek(std::io::SeekFrom::Start(pos)).await?;
+        file.read_exact(&mut hdr[..]).await?;
+        let [t0, t1, t2, t3, l0, l1, l2, l3, m0, m1, m2, m3] = hdr;
+        // The block type of a Section Header Block is a palindrome, so it
+        // can be recognized before the byte order is known
+        let is_shb = u32::from_ne_bytes([t0, t1, t2, t3]) == SHB;
+        if is_shb {
+            if u32::from_be_bytes([m0, m1, m2, m3]) == BYTE_ORDER_MAGIC {
+                be = true;
+            } else if u32::from_le_bytes([m0, m1, m2, m3]) == BYTE_ORDER_MAGIC {
+                be = false;
+            } else {
+                break;
+            }
+        } else if pos == 0 {
+            return Err(Error::MalformedCapture);
+        }
+        let block_len = if be {
+            u32::from_be_bytes([l0, l1, l2, l3])
+        } else {
+            u32::from_le_bytes([l0, l1, l2, l3])
+        };
+        let block_len = u64::from(block_len);
+        if block_len < 12 || block_len % 4 != 0 || block_len > len - pos {
+            break;
+        }
+        let mut trailer = [0u8; 4];
+        file.seek(std::io::SeekFrom::Start(pos + block_len - 4))
+            .await?;
+        file.read_exact(&mut trailer[..]).await?;
+        let trailer = if be {
+            u32::from_be_bytes(trailer)
+        } else {
+            u32::from_le_bytes(trailer)
+        };
+        if u64::from(trailer) != block_len {
+            break;
+        }
+        pos += block_len;
+    }
+    if pos == 0 && len > 0 {
+        return Err(Error::MalformedCapture);
+    }
+    Ok(pos)
+}
+
 #[async_trait]
 impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Transmit for Recorder<F> {
     async fn transmit_raw(&mut self, packet: RawPacket<'_>) -> Result<(), Error> {
@@ -114,55 +535,40 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Transmit for Recorder<F> {
             link_type,
             snaplen: packet.snaplen() as u32,
         };
+        let tsresol = packet
+            .device()
+            .and_then(|dev| self.iface_tsresol.get(dev.name()))
+            .copied()
+            .unwrap_or(self.tsresol);
         let next_id = self.ifaces.len() as u32;
         let iface_info = self.ifaces.entry(iface).or_insert(IfaceInfo {
             id: next_id,
             ts_offset: 0,
+            tsresol,
         });
         let id = iface_info.id;
 
+        let ts = packet.precise_timestamp();
         let ts = if id == next_id {
-            let (ts_offset, ts) = match packet.timestamp().duration_since(SystemTime::UNIX_EPOCH) {
-                Ok(dur) => (dur.as_secs() as i64, dur.subsec_nanos() as u64),
-                Err(e) => {
-                    let dur = e.duration();
-                    let secs = dur.as_secs();
-                    let nanos = dur.subsec_nanos() as u64;
-                    if nanos > 0 {
-                        (-(secs as i64) - 1, 1_000_000_000 - nanos)
-                    } else {
-                        (-(secs as i64), 0)
-                    }
-                }
-            };
-            iface_info.ts_offset = ts_offset;
+            iface_info.ts_offset = ts.secs();
             let _ = iface_info;
-            self.write_iface(&packet, ts_offset).await?;
-            ts
+            self.write_iface(&packet, ts.secs(), tsresol).await?;
+            tsresol.ticks(0, ts.subsec_nanos())
         } else {
-            let ts = match packet.timestamp().duration_since(SystemTime::UNIX_EPOCH) {
-                Ok(dur) => {
-                    ((dur.as_secs() as i64 - iface_info.ts_offset) as u64 * 1_000_000_000)
-                        + (dur.subsec_nanos() as u64)
-                }
-                Err(e) => {
-                    let dur = e.duration();
-                    let secs = (-(dur.as_secs() as i64) - iface_info.ts_offset) as u64;
-                    let nanos = dur.subsec_nanos() as u64;
-                    if nanos > 0 {
-                        ((secs - 1) * 1_000_000_000) + (1_000_000_000 - nanos)
-                    } else {
-                        secs * 1_000_000_000
-                    }
-                }
-            };
-            let _ = iface_info;
-            ts
+            iface_info.ticks(ts)
         };
 
         let mut data = self.writer.write_epb(id, ts).await?;
         data.write_all(packet.data()).await?;
-        data.finish().await?;
+        data.write_original_length(packet.orig_len() as u32).await?;
+        match packet.meta() {
+            Some(meta) if !meta.is_empty() => {
+                let mut opts = data.write_options().await?;
+                opts.write_meta(meta).await?;
+                opts.finish().await?;
+            }
+            _ => data.finish().await?,
+        }
         Ok(())
     }
 
@@ -170,3 +576,253 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Transmit for Recorder<F> {
         Some(&mut self.buf)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle_core::{DeviceBuilder, SniffRaw, Timestamp};
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn record_tsresol() {
+        let eth0 = std::sync::Arc::new(DeviceBuilder::new().name("eth0".into()).device());
+        let eth1 = std::sync::Arc::new(DeviceBuilder::new().name("eth1".into()).device());
+        let start = Timestamp::new(1_700_000_000, 123_456_789);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let out = rt
+            .block_on(async {
+                let mut rec = Recorder::new(Cursor::new(Vec::new())).await?;
+                rec.set_tsresol(TsResolution::Binary(20))?;
+                rec.set_iface_tsresol("eth1", TsResolution::Decimal(3))?;
+                assert!(rec.set_tsresol(TsResolution::Decimal(128)).is_err());
+                for (i, dev) in [&eth0, &eth1, &eth0, &eth1].into_iter().enumerate() {
+                    let ts = start + Duration::from_secs(i as u64);
+                    let dev = Some(dev.clone());
+                    let pkt = RawPacket::new(LinkType::ETHERNET, ts, 1, None, &[0][..], dev);
+                    rec.transmit_raw(pkt).await?;
+                }
+
+                let mut file = rec.into_inner();
+                file.set_position(0);
+                let mut sniffer = Sniffer::new_raw(file).await?;
+                let mut out = Vec::new();
+                while let Some(pkt) = sniffer.sniff_raw().await? {
+                    let ts = pkt.precise_timestamp();
+                    out.push((ts.secs() - start.secs(), ts.subsec_nanos()));
+                }
+                Ok::<_, Error>(out)
+            })
+            .unwrap();
+        // 0.123456789 truncated to 2^-20 and 10^-3 second units.
+        let binary = ((123_456_789u64 * (1 << 20) / 1_000_000_000 * 1_000_000_000) >> 20) as u32;
+        assert_eq!(
+            out,
+            [(0, binary), (1, 123_000_000), (2, binary), (3, 123_000_000)]
+        );
+    }
+
+    #[test]
+    fn record_stats() {
+        let eth0 = DeviceBuilder::new().name("eth0".into()).device();
+        let eth1 = DeviceBuilder::new().name("eth1".into()).device();
+        let start = Timestamp::new(1_700_000_000, 0);
+        let stats = CaptureStats {
+            received: 2,
+            dropped: 3,
+            iface_dropped: 1,
+        };
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let report = rt
+            .block_on(async {
+                let mut rec = Recorder::new(Cursor::new(Vec::new())).await?;
+                assert!(!rec.write_stats(&eth0, &stats, None, start).await?);
+                let dev = Some(std::sync::Arc::new(eth0.clone()));
+                for i in 0..2 {
+                    let ts = start + Duration::from_secs(i);
+                    let pkt =
+                        RawPacket::new(LinkType::ETHERNET, ts, 1, None, &[0][..], dev.clone());
+                    rec.transmit_raw(pkt).await?;
+                }
+                let end = start + Duration::from_secs(5);
+                assert!(rec.write_stats(&eth0, &stats, Some(start), end).await?);
+                assert!(!rec.write_stats(&eth1, &stats, Some(start), end).await?);
+
+                let mut file = rec.into_inner();
+                file.set_position(0);
+                let mut reader = super::super::reader::Reader::new(file).await?;
+                super::super::HealthAnalyzer::new()
+                    .analyze(&mut reader)
+                    .await
+            })
+            .unwrap();
+        let iface = &report.interfaces()[0];
+        assert_eq!(iface.name(), Some("eth0"));
+        assert_eq!(iface.packets(), 2);
+        assert_eq!(iface.received(), Some(6));
+        assert_eq!(iface.dropped(), 4);
+    }
+
+    #[test]
+    fn record_packet_meta() {
+        use sniffle_core::{Direction, PacketHash, PacketMeta, ReceptionType};
+
+        let meta = PacketMeta::new()
+            .with_direction(Direction::Outbound)
+            .with_reception_type(ReceptionType::Multicast)
+            .with_hash(PacketHash::Crc32([1, 2, 3, 4]))
+            .with_hash(PacketHash::Other(9, vec![5, 6, 7]))
+            .with_drop_count(3)
+            .with_packet_id(0x0102_0304_0506_0708)
+            .with_queue(2);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let out = rt
+            .block_on(async {
+                let mut rec = Recorder::new(Cursor::new(Vec::new())).await?;
+                let ts = Timestamp::new(1_700_000_000, 0);
+                let pkt = RawPacket::new(LinkType::ETHERNET, ts, 1, None, &[0][..], None);
+                rec.transmit_raw(pkt.with_meta(meta.clone())).await?;
+                let pkt = RawPacket::new(LinkType::ETHERNET, ts, 1, None, &[0][..], None);
+                rec.transmit_raw(pkt).await?;
+
+                let mut file = rec.into_inner();
+                file.set_position(0);
+                let mut sniffer = Sniffer::new_raw(file).await?;
+                let mut out = Vec::new();
+                while let Some(pkt) = sniffer.sniff_raw().await? {
+                    out.push(pkt.meta().cloned());
+                }
+                Ok::<_, Error>(out)
+            })
+            .unwrap();
+        assert_eq!(out, [Some(meta.clone()), None]);
+        assert_eq!(meta.direction(), Some(Direction::Outbound));
+        assert_eq!(meta.reception_type(), Some(ReceptionType::Multicast));
+        assert_eq!(meta.flags(), Some(0b01010));
+    }
+
+    #[test]
+    fn record_section_info() {
+        use super::super::reader::{Block, IdbOption, Reader, ShbOption};
+
+        let info = SectionInfo::new()
+            .with_hardware("x86_64")
+            .with_os("linux 6.1.0")
+            .with_user_app("sniffle test");
+        let dev = std::sync::Arc::new(DeviceBuilder::new().name("eth0".into()).device());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (shb, idb) = rt
+            .block_on(async {
+                let mut rec = Recorder::with_section_info(Cursor::new(Vec::new()), &info).await?;
+                let ts = Timestamp::new(1_700_000_000, 0);
+                let pkt = RawPacket::new(LinkType::ETHERNET, ts, 1, None, &[0][..], Some(dev));
+                rec.transmit_raw(pkt).await?;
+
+                let mut file = rec.into_inner();
+                file.set_position(0);
+                let mut reader = Reader::new(file).await?;
+                let mut shb = Vec::new();
+                let mut idb = Vec::new();
+                while let Some(block) = reader.next_block().await? {
+                    match block {
+                        Block::Shb(mut block) => {
+                            while let Some(opt) = block.next_option().await? {
+                                let mut s = String::new();
+                                match opt {
+                                    ShbOption::Hardware(mut opt) => opt.string(&mut s).await?,
+                                    ShbOption::Os(mut opt) => opt.string(&mut s).await?,
+                                    ShbOption::UserApplication(mut opt) => {
+                                        opt.string(&mut s).await?
+                                    }
+                                    _ => continue,
+                                }
+                                shb.push(s);
+                            }
+                        }
+                        Block::Idb(mut block) => {
+                            while let Some(opt) = block.next_option().await? {
+                                let mut s = String::new();
+                                match opt {
+                                    IdbOption::Name(mut opt) => opt.string(&mut s).await?,
+                                    IdbOption::Os(mut opt) => opt.string(&mut s).await?,
+                                    _ => continue,
+                                }
+                                idb.push(s);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok::<_, Error>((shb, idb))
+            })
+            .unwrap();
+        assert_eq!(shb, ["x86_64", "linux 6.1.0", "sniffle test"]);
+        assert_eq!(idb, ["eth0", "linux 6.1.0"]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn append() {
+        let path =
+            std::env::temp_dir().join(format!("sniffle-append-{}.pcapng", std::process::id()));
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let res = rt.block_on(async {
+            let _ = tokio::fs::remove_file(&path).await;
+            let eth0 = std::sync::Arc::new(DeviceBuilder::new().name("eth0".into()).device());
+            let ts = Timestamp::new(1_700_000_000, 0);
+            for n in 0u8..2 {
+                let mut rec = FileRecorder::append(&path).await?;
+                let data = [n];
+                let dev = Some(eth0.clone());
+                let pkt = RawPacket::new(LinkType::ETHERNET, ts, 1, None, &data[..], dev);
+                rec.transmit_raw(pkt).await?;
+                rec.flush().await?;
+            }
+
+            // Simulate a block cut short by a crash
+            let mut file = tokio::fs::read(&path).await?;
+            file.extend_from_slice(&[6, 0, 0, 0, 64, 0, 0, 0, 0][..]);
+            tokio::fs::write(&path, &file).await?;
+
+            let mut rec = FileRecorder::append(&path).await?;
+            let pkt = RawPacket::new(LinkType::ETHERNET, ts, 1, None, &[2][..], Some(eth0));
+            rec.transmit_raw(pkt).await?;
+            rec.flush().await?;
+            drop(rec);
+
+            let mut reader = super::super::reader::FileReader::open(&path).await?;
+            let mut sections = 0;
+            while let Some(block) = reader.next_block().await? {
+                if let super::super::reader::Block::Shb(_) = block {
+                    sections += 1;
+                }
+            }
+            let mut src = FileSniffer::open_raw(&path).await?;
+            let mut out = Vec::new();
+            while let Some(packet) = src.sniff_raw().await? {
+                assert_eq!(packet.device().map(|dev| dev.name()), Some("eth0"));
+                out.push(packet.data()[0]);
+            }
+            Ok::<_, Error>((sections, out))
+        });
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(res.unwrap(), (3, vec![0, 1, 2]));
+
+        rt.block_on(async {
+            tokio::fs::write(&path, b"not a pcapng file").await.unwrap();
+            assert!(FileRecorder::append(&path).await.is_err());
+            assert_eq!(tokio::fs::read(&path).await.unwrap(), b"not a pcapng file");
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+}
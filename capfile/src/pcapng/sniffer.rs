@@ -1,7 +1,11 @@
 use super::reader::*;
+use super::{CustomBlock, CustomOption, TsResolution};
+use crate::index::{IndexEntry, IndexedSniff, PacketIndex};
 use async_trait::async_trait;
 use sniffle_core::{
-    Device, DeviceBuilder, DeviceIpv4, DeviceIpv6, Error, LinkType, RawPacket, Session, SniffRaw,
+    Bytes, Device, DeviceBuilder, DeviceIpv4, DeviceIpv6, Error, LinkType, NameResolver,
+    NameSource, PacketHash, PacketMeta, RawPacket, Session, SniffRaw, Timestamp,
+    TimestampPrecision,
 };
 use std::time::{Duration, SystemTime};
 use tokio::io::{AsyncBufRead, AsyncSeek};
@@ -14,28 +18,47 @@ struct Iface {
     tsoffset: i64,
 }
 
+struct Section {
+    be: bool,
+    ifaces: Vec<Iface>,
+}
+
 pub struct Sniffer<F: AsyncBufRead + AsyncSeek + Send + Unpin> {
     file: Reader<F>,
-    ifaces: Vec<Iface>,
+    start: u64,
+    // Interfaces are kept for every section, so that seeking back to an
+    // earlier section doesn't require reading its IDBs again.
+    sections: Vec<Section>,
+    section: usize,
+    idbs: usize,
     buf: Vec<u8>,
+    index: PacketIndex,
+    next: usize,
+    resolver: Option<NameResolver>,
+    custom: Option<Custom>,
 }
 
+#[derive(Default)]
+struct Custom {
+    blocks: Vec<CustomBlock>,
+    options: Vec<CustomOption>,
+}
+
+#[cfg(feature = "fs")]
 pub type FileSniffer = Sniffer<tokio::io::BufReader<tokio::fs::File>>;
 
-fn ts_calc(ts: u64, tsresol: u8, tsoffset: i64) -> SystemTime {
-    let (secs, nanos) = if (tsresol & 0b1000_0000) == 0 {
-        let mut mag: u64 = 1;
-        for _ in 0..tsresol {
-            mag *= 10;
-        }
-        let ts_secs = ts / mag;
-        (ts_secs, (ts - (ts_secs * mag)) * 1_000_000_000 / mag)
-    } else {
-        let tsresol = tsresol & 0b0111_1111;
-        let ts_secs = ts >> tsresol;
-        let ts_frac = ts & !(u64::MAX << tsresol);
-        (ts_secs, ts_frac * 1_000_000_000 / (1u64 << tsresol))
-    };
+fn find_iface(sections: &[Section], section: usize, id: usize) -> Result<&Iface, Error> {
+    section
+        .checked_sub(1)
+        .and_then(|section| sections[section].ifaces.get(id))
+        .ok_or(Error::MalformedCapture)
+}
+
+pub(super) fn ts_calc(ts: u64, tsresol: u8, tsoffset: i64) -> SystemTime {
+    let per_sec = TsResolution::from_tsresol(tsresol).ticks_per_sec();
+    let ts = u128::from(ts);
+    let secs = (ts / per_sec) as u64;
+    let nanos = (ts % per_sec) * 1_000_000_000 / per_sec;
     let secs = if tsoffset < 0 {
         match tsoffset.checked_neg() {
             Some(tsoffset) => match u64::try_from(tsoffset) {
@@ -53,13 +76,127 @@ fn ts_calc(ts: u64, tsresol: u8, tsoffset: i64) -> SystemTime {
     SystemTime::UNIX_EPOCH + Duration::new(secs, nanos as u32)
 }
 
+async fn read_names<F: AsyncBufRead + AsyncSeek + Send + Unpin>(
+    nrb: &mut NameResolutionBlock<'_, F>,
+    resolver: &NameResolver,
+) -> Result<(), Error> {
+    let mut name = String::new();
+    while let Some(record) = nrb.next_record().await? {
+        name.clear();
+        match record {
+            NameRecord::Ipv4(mut rec) => {
+                let addr = std::net::Ipv4Addr::from(rec.address().await?);
+                if rec.next_name(&mut name).await?.is_some() {
+                    resolver.insert(addr, &name[..], NameSource::Capture);
+                }
+            }
+            NameRecord::Ipv6(mut rec) => {
+                let addr = std::net::Ipv6Addr::from(rec.address().await?);
+                if rec.next_name(&mut name).await?.is_some() {
+                    resolver.insert(addr, &name[..], NameSource::Capture);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+async fn read_hash<F: AsyncBufRead + AsyncSeek + Send + Unpin>(
+    opt: HashOpt<'_, F>,
+    buf: &mut Vec<u8>,
+) -> Result<PacketHash, Error> {
+    Ok(match opt {
+        HashOpt::TwosComplement(mut opt) => {
+            opt.hash(buf).await?;
+            PacketHash::TwosComplement(buf.clone())
+        }
+        HashOpt::Xor(mut opt) => {
+            opt.hash(buf).await?;
+            PacketHash::Xor(buf.clone())
+        }
+        HashOpt::Crc32(mut opt) => PacketHash::Crc32(opt.hash().await?),
+        HashOpt::Md5(mut opt) => PacketHash::Md5(opt.hash().await?),
+        HashOpt::Sha1(mut opt) => PacketHash::Sha1(opt.hash().await?),
+        HashOpt::Toeplitz(mut opt) => PacketHash::Toeplitz(opt.hash().await?),
+        HashOpt::Unknown(mut opt) => {
+            opt.hash_data(buf).await?;
+            PacketHash::Other(opt.hash_type(), buf.clone())
+        }
+    })
+}
+
+/// The decimal precision needed to represent timestamps of resolution
+/// `tsresol`.
+fn ts_precision(tsresol: u8) -> TimestampPrecision {
+    if (tsresol & 0b1000_0000) == 0 {
+        TimestampPrecision::from_digits(tsresol.into())
+    } else {
+        // Each binary digit needs log10(2) decimal digits.
+        TimestampPrecision::from_digits(
+            (u32::from(tsresol & 0b0111_1111) * 30103).div_ceil(100_000),
+        )
+    }
+}
+
 impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> Sniffer<F> {
     pub async fn new_raw(file: F) -> Result<Self, Error> {
-        Ok(Self {
-            file: Reader::new(file).await?,
-            ifaces: Vec::new(),
+        Ok(Self::init(Reader::new(file).await?))
+    }
+
+    fn init(file: Reader<F>) -> Self {
+        Self {
+            start: file.next_block_offset(),
+            file,
+            sections: Vec::new(),
+            section: 0,
+            idbs: 0,
             buf: Vec::new(),
-        })
+            index: PacketIndex::new(),
+            next: 0,
+            resolver: None,
+            custom: None,
+        }
+    }
+
+    /// Enables keeping blocks and packet options that the sniffer doesn't
+    /// interpret, so they can be written back with `Recorder` or `Writer`.
+    /// See `take_custom_blocks` and `custom_options`.
+    pub fn preserve_custom(&mut self, preserve: bool) {
+        self.custom = preserve.then(Custom::default);
+    }
+
+    /// Returns the unknown blocks read since the last call, in file order.
+    /// After a packet is sniffed, these are the blocks that preceded it.
+    /// Always empty unless `preserve_custom` is enabled.
+    pub fn take_custom_blocks(&mut self) -> Vec<CustomBlock> {
+        self.custom
+            .as_mut()
+            .map(|custom| std::mem::take(&mut custom.blocks))
+            .unwrap_or_default()
+    }
+
+    /// The unknown options of the Enhanced Packet Block of the last packet
+    /// sniffed. Always empty unless `preserve_custom` is enabled.
+    pub fn custom_options(&self) -> &[CustomOption] {
+        self.custom
+            .as_ref()
+            .map(|custom| &custom.options[..])
+            .unwrap_or(&[])
+    }
+
+    /// Adds the names of Name Resolution Blocks to `resolver` as they are
+    /// read. Sniffers created with a session do this for the session's
+    /// `NameResolver`, if it has one.
+    pub fn set_name_resolver(&mut self, resolver: NameResolver) {
+        self.resolver = Some(resolver);
+    }
+
+    fn with_session(mut self, session: Session) -> sniffle_core::Sniffer<Self> {
+        if let Some(resolver) = session.get::<NameResolver>() {
+            self.resolver = Some(resolver.clone());
+        }
+        sniffle_core::Sniffer::with_session(self, session)
     }
 
     pub async fn new(file: F) -> Result<sniffle_core::Sniffer<Self>, Error> {
@@ -70,34 +207,35 @@ impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> Sniffer<F> {
         file: F,
         session: Session,
     ) -> Result<sniffle_core::Sniffer<Self>, Error> {
-        Ok(sniffle_core::Sniffer::with_session(
-            Self::new_raw(file).await?,
-            session,
-        ))
+        Ok(Self::new_raw(file).await?.with_session(session))
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open_raw<P: AsRef<std::path::Path>>(path: P) -> Result<FileSniffer, Error> {
-        Ok(FileSniffer {
-            file: FileReader::open(path).await?,
-            ifaces: Vec::new(),
-            buf: Vec::new(),
-        })
+        Ok(FileSniffer::init(FileReader::open(path).await?))
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open<P: AsRef<std::path::Path>>(
         path: P,
     ) -> Result<sniffle_core::Sniffer<FileSniffer>, Error> {
         Ok(sniffle_core::Sniffer::new(Self::open_raw(path).await?))
     }
 
+    #[cfg(feature = "fs")]
     pub async fn open_with_session<P: AsRef<std::path::Path>>(
         path: P,
         session: Session,
     ) -> Result<sniffle_core::Sniffer<FileSniffer>, Error> {
-        Ok(sniffle_core::Sniffer::with_session(
-            Self::open_raw(path).await?,
-            session,
-        ))
+        Ok(Self::open_raw(path).await?.with_session(session))
+    }
+
+    fn seek_entry(&mut self, entry: &IndexEntry) {
+        let section = entry.section as usize;
+        self.file
+            .seek_block(entry.offset, self.sections[section].be);
+        self.section = section + 1;
+        self.idbs = entry.ifaces as usize;
     }
 }
 
@@ -108,9 +246,24 @@ impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> SniffRaw for Sniffer<F> {
             match self.file.next_block().await? {
                 Some(block) => match block {
                     Block::Shb(_) => {
-                        self.ifaces.clear();
+                        self.section += 1;
+                        self.idbs = 0;
+                        if self.section > self.sections.len() {
+                            self.sections.push(Section {
+                                be: self.file.big_endian_encoded(),
+                                ifaces: Vec::new(),
+                            });
+                        }
                     }
                     Block::Idb(mut idb) => {
+                        let section = match self.section.checked_sub(1) {
+                            Some(section) => section,
+                            None => return Err(Error::MalformedCapture),
+                        };
+                        self.idbs += 1;
+                        if self.idbs <= self.sections[section].ifaces.len() {
+                            continue;
+                        }
                         let mut bldr = DeviceBuilder::new();
                         let mut tsresol = 6u8;
                         let mut tsoffset = 0i64;
@@ -149,13 +302,16 @@ impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> SniffRaw for Sniffer<F> {
                                 IdbOption::TsOffset(mut opt) => {
                                     tsoffset = opt.value().await?;
                                 }
+                                IdbOption::FcsLen(mut opt) => {
+                                    bldr.fcs_length(opt.value().await?);
+                                }
                                 _ => {}
                             }
                         }
                         let link = LinkType(idb.link_type().await?);
                         let snaplen = idb.snaplen().await?;
                         let _ = idb;
-                        self.ifaces.push(Iface {
+                        self.sections[section].ifaces.push(Iface {
                             device: std::sync::Arc::new(bldr.into_device()),
                             link,
                             snaplen,
@@ -165,44 +321,329 @@ impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> SniffRaw for Sniffer<F> {
                     }
                     Block::Epb(mut epb) => {
                         let iface_id = epb.interface_id().await? as usize;
-                        let tsresol = self.ifaces[iface_id].tsresol;
-                        let tsoffset = self.ifaces[iface_id].tsoffset;
-                        let link = self.ifaces[iface_id].link;
-                        let snaplen = self.ifaces[iface_id].snaplen;
-                        let device = self.ifaces[iface_id].device.clone();
-                        let ts = ts_calc(epb.timestamp().await?, tsresol, tsoffset);
+                        let iface = find_iface(&self.sections, self.section, iface_id)?;
+                        let tsresol = iface.tsresol;
+                        let tsoffset = iface.tsoffset;
+                        let link = iface.link;
+                        let snaplen = iface.snaplen;
+                        let device = iface.device.clone();
+                        let ts =
+                            Timestamp::from(ts_calc(epb.timestamp().await?, tsresol, tsoffset))
+                                .with_precision(ts_precision(tsresol))
+                                .with_utc_offset((tsoffset != 0).then_some(tsoffset));
                         let orig_len = epb.original_length().await?;
                         epb.packet_data(&mut self.buf).await?;
-                        break Ok(Some(RawPacket::new(
+                        if let Some(custom) = self.custom.as_mut() {
+                            custom.options.clear();
+                        }
+                        let mut meta = PacketMeta::new();
+                        let mut hash = Vec::new();
+                        while let Some(opt) = epb.next_option().await? {
+                            meta = match opt {
+                                EpbOption::Flags(mut opt) => {
+                                    meta.with_flags(opt.raw_flags().await?)
+                                }
+                                EpbOption::Hash(opt) => {
+                                    meta.with_hash(read_hash(opt, &mut hash).await?)
+                                }
+                                EpbOption::DropCount(mut opt) => {
+                                    meta.with_drop_count(opt.value().await?)
+                                }
+                                EpbOption::PacketId(mut opt) => {
+                                    meta.with_packet_id(opt.value().await?)
+                                }
+                                EpbOption::Queue(mut opt) => meta.with_queue(opt.value().await?),
+                                EpbOption::Unknown(mut opt) => {
+                                    if let Some(custom) = self.custom.as_mut() {
+                                        custom.options.push(opt.to_custom().await?);
+                                    }
+                                    meta
+                                }
+                                _ => meta,
+                            };
+                        }
+                        self.record(ts.to_system_time());
+                        let pkt = RawPacket::from_bytes(
                             link,
                             ts,
                             orig_len as usize,
                             Some(snaplen as usize),
-                            &self.buf[..],
+                            Bytes::from(std::mem::take(&mut self.buf)),
                             Some(device),
-                        )));
+                        );
+                        break Ok(Some(if meta.is_empty() {
+                            pkt
+                        } else {
+                            pkt.with_meta(meta)
+                        }));
                     }
                     Block::Spb(mut spb) => {
-                        let link = self.ifaces[0].link;
-                        let snaplen = self.ifaces[0].snaplen;
-                        let device = self.ifaces[0].device.clone();
+                        let iface = find_iface(&self.sections, self.section, 0)?;
+                        let link = iface.link;
+                        let snaplen = iface.snaplen;
+                        let device = iface.device.clone();
                         let orig_len = spb.original_length().await?;
                         spb.packet_data(&mut self.buf).await?;
-                        break Ok(Some(RawPacket::new(
+                        if let Some(custom) = self.custom.as_mut() {
+                            custom.options.clear();
+                        }
+                        self.record(SystemTime::UNIX_EPOCH);
+                        break Ok(Some(RawPacket::from_bytes(
                             link,
                             SystemTime::UNIX_EPOCH,
                             orig_len as usize,
                             Some(snaplen as usize),
-                            &self.buf[..],
+                            Bytes::from(std::mem::take(&mut self.buf)),
                             Some(device),
                         )));
                     }
+                    Block::Nrb(mut nrb) => {
+                        if let Some(resolver) = self.resolver.as_ref() {
+                            read_names(&mut nrb, resolver).await?;
+                        }
+                    }
+                    Block::Other(mut block) => {
+                        if let Some(custom) = self.custom.as_mut() {
+                            custom.blocks.push(block.to_custom().await?);
+                        }
+                    }
                     _ => {}
                 },
                 None => {
+                    self.index.finish(self.next);
                     break Ok(None);
                 }
             }
         }
     }
 }
+
+impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> Sniffer<F> {
+    fn record(&mut self, ts: SystemTime) {
+        self.index.record(
+            self.next,
+            IndexEntry {
+                offset: self.file.block_offset(),
+                ts,
+                caplen: self.buf.len() as u32,
+                section: (self.section - 1) as u32,
+                ifaces: self.idbs as u32,
+            },
+        );
+        self.next += 1;
+    }
+}
+
+#[async_trait]
+impl<F: AsyncBufRead + AsyncSeek + Send + Unpin> IndexedSniff for Sniffer<F> {
+    fn index(&self) -> &PacketIndex {
+        &self.index
+    }
+
+    fn packet_number(&self) -> usize {
+        self.next
+    }
+
+    async fn seek_packet(&mut self, n: usize) -> Result<bool, Error> {
+        if let Some(entry) = self.index.get(n).copied() {
+            self.seek_entry(&entry);
+            self.next = n;
+            return Ok(true);
+        }
+
+        // Resume reading after the last indexed packet
+        match self.index.entries().last().copied() {
+            Some(last) => {
+                self.seek_entry(&last);
+                self.next = self.index.len() - 1;
+                if self.sniff_raw().await?.is_none() {
+                    return Ok(false);
+                }
+            }
+            None => {
+                self.file.seek_block(self.start, false);
+                self.section = 0;
+                self.idbs = 0;
+                self.next = 0;
+            }
+        }
+        while self.next < n {
+            if self.sniff_raw().await?.is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::writer::Writer;
+    use super::*;
+    use std::io::Cursor;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn nrb_names() {
+        let resolver = NameResolver::new();
+        resolver.insert(Ipv4Addr::new(10, 0, 0, 2), "hosts", NameSource::Hosts);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut writer = Writer::new(Cursor::new(Vec::new()));
+            writer.write_shb(false, 1, 0).await?.finish().await?;
+            writer.write_idb(1, 0xFFFF).await?.finish().await?;
+            let mut nrb = writer.write_nrb().await?;
+            let mut rec = nrb.write_ipv4_record([10, 0, 0, 1].into()).await?;
+            rec.write_name("one").await?;
+            rec.write_name("alias").await?;
+            rec.finish().await?;
+            let mut rec = nrb.write_ipv4_record([10, 0, 0, 2].into()).await?;
+            rec.write_name("two").await?;
+            rec.finish().await?;
+            let mut rec = nrb
+                .write_ipv6_record(Ipv6Addr::LOCALHOST.octets().into())
+                .await?;
+            rec.write_name("localhost").await?;
+            rec.finish().await?;
+            nrb.finish().await?;
+
+            let mut file = writer.into_inner();
+            file.set_position(0);
+            let mut sniffer = Sniffer::new_raw(file).await?;
+            sniffer.set_name_resolver(resolver.clone());
+            assert!(sniffer.sniff_raw().await?.is_none());
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+        assert_eq!(
+            resolver.lookup(Ipv4Addr::new(10, 0, 0, 1)).as_deref(),
+            Some("one")
+        );
+        assert_eq!(
+            resolver.lookup(Ipv4Addr::new(10, 0, 0, 2)).as_deref(),
+            Some("hosts")
+        );
+        assert_eq!(
+            resolver.lookup(Ipv6Addr::LOCALHOST).as_deref(),
+            Some("localhost")
+        );
+    }
+
+    #[test]
+    fn simple_packet_blocks() {
+        use tokio::io::AsyncWriteExt;
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let out = rt
+            .block_on(async {
+                let mut writer = Writer::new(Cursor::new(Vec::new()));
+                writer.write_shb(false, 1, 0).await?.finish().await?;
+                // A snaplen of zero means packets are not truncated
+                writer.write_idb(1, 0).await?.finish().await?;
+                let mut spb = writer.write_spb().await?;
+                spb.write_all(&[0xaa; 5][..]).await?;
+                spb.finish().await?;
+                // Truncated SPB data can't be told apart from its padding,
+                // so it must be a multiple of 4 bytes to round trip
+                let mut spb = writer.write_spb().await?;
+                spb.write_all(&[0xbb; 16][..]).await?;
+                spb.write_original_length(60).await?;
+                spb.finish().await?;
+
+                let mut file = writer.into_inner();
+                file.set_position(0);
+                let mut sniffer = Sniffer::new_raw(file).await?;
+                let mut out = Vec::new();
+                while let Some(pkt) = sniffer.sniff_raw().await? {
+                    assert_eq!(pkt.datalink(), LinkType::ETHERNET);
+                    out.push((Vec::from(pkt.data()), pkt.orig_len()));
+                }
+                Ok::<_, Error>(out)
+            })
+            .unwrap();
+        assert_eq!(out, [(vec![0xaa; 5], 5), (vec![0xbb; 16], 60)]);
+    }
+
+    async fn custom_capture(blocks: &[CustomBlock], opts: &[CustomOption]) -> Vec<u8> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        writer
+            .write_shb(false, 1, 0)
+            .await
+            .unwrap()
+            .finish()
+            .await
+            .unwrap();
+        writer
+            .write_idb(1, 0xFFFF)
+            .await
+            .unwrap()
+            .finish()
+            .await
+            .unwrap();
+        for block in blocks {
+            writer.write_custom_block(block).await.unwrap();
+        }
+        let mut epb = writer.write_epb(0, 1).await.unwrap();
+        epb.write_all(&[0xaa; 14][..]).await.unwrap();
+        let mut epb = epb.write_options().await.unwrap();
+        for opt in opts {
+            epb.write_custom_option(opt).await.unwrap();
+        }
+        epb.finish().await.unwrap();
+        writer.into_inner().into_inner()
+    }
+
+    #[test]
+    fn preserve_custom() {
+        let blocks = [CustomBlock::new(
+            0x4000_0bad,
+            false,
+            vec![1, 2, 3, 4, 5, 6, 7, 8],
+        )];
+        let opts = [
+            CustomOption::new(0x8001, false, b"abc".to_vec()),
+            CustomOption::new(0x8002, false, Vec::new()),
+        ];
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let original = custom_capture(&blocks[..], &opts[..]).await;
+
+            let mut sniffer = Sniffer::new_raw(Cursor::new(original.clone()))
+                .await
+                .unwrap();
+            sniffer.preserve_custom(true);
+            assert!(sniffer.sniff_raw().await.unwrap().is_some());
+            let read_blocks = sniffer.take_custom_blocks();
+            assert_eq!(read_blocks, blocks);
+            assert_eq!(sniffer.custom_options(), &opts[..]);
+            assert!(sniffer.take_custom_blocks().is_empty());
+            assert_eq!(
+                custom_capture(&read_blocks[..], sniffer.custom_options()).await,
+                original
+            );
+
+            let be = CustomBlock::new(0x4000_0bad, true, Vec::new());
+            let mut writer = Writer::new(Cursor::new(Vec::new()));
+            writer
+                .write_shb(false, 1, 0)
+                .await
+                .unwrap()
+                .finish()
+                .await
+                .unwrap();
+            assert!(writer.write_custom_block(&be).await.is_err());
+
+            let mut sniffer = Sniffer::new_raw(Cursor::new(original)).await.unwrap();
+            assert!(sniffer.sniff_raw().await.unwrap().is_some());
+            assert!(sniffer.take_custom_blocks().is_empty());
+        });
+    }
+}
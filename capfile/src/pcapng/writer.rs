@@ -1,5 +1,5 @@
 use super::*;
-use sniffle_core::Error;
+use sniffle_core::{Error, PacketHash, PacketMeta};
 use std::io::SeekFrom;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -9,9 +9,11 @@ pub struct Writer<F: AsyncWrite + AsyncSeek + Send + Unpin> {
     file: F,
     be: bool,
     section_start: u64,
+    section_body: u64,
     first_snaplen: Option<u32>,
 }
 
+#[cfg(feature = "fs")]
 pub type FileWriter = Writer<tokio::io::BufWriter<tokio::fs::File>>;
 
 pub struct RawBlockWriter<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> {
@@ -107,6 +109,19 @@ fn guarantee<T>(opt: Option<T>) -> T {
     }
 }
 
+/// Opaque blocks and options can only be copied into a section with the
+/// same byte order, since their content can't be byte swapped.
+fn check_byte_order(section_be: bool, be: bool) -> Result<(), Error> {
+    if section_be == be {
+        Ok(())
+    } else {
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "byte order of pcapng section differs from copied block or option",
+        )))
+    }
+}
+
 async fn write_padding<F: AsyncWrite + Unpin>(file: &mut F, len: usize) -> std::io::Result<()> {
     const PADDING: [u8; 4] = [0u8; 4];
     file.write_all(&PADDING[..((4 - (len % 4)) % 4)]).await
@@ -117,30 +132,41 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Writer<F> {
         Self {
             file,
             be: false,
-            section_start: 0,
+            section_start: u64::MAX,
+            section_body: u64::MAX,
             first_snaplen: None,
         }
     }
 
+    #[cfg(feature = "fs")]
     pub async fn create<P: AsRef<std::path::Path>>(path: P) -> Result<FileWriter, Error> {
         Ok(FileWriter::new(tokio::io::BufWriter::new(
             tokio::fs::File::create(path).await?,
         )))
     }
 
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().await?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
     async fn finish_section(&mut self) -> std::io::Result<()> {
-        if self.section_start != u64::MAX {
+        if self.section_body != u64::MAX {
             let end = self.file.seek(SeekFrom::End(0)).await?;
             self.file
                 .seek(SeekFrom::Start(self.section_start + 16))
                 .await?;
             if self.be {
                 self.file
-                    .write_all(&(end - self.section_start).to_be_bytes()[..])
+                    .write_all(&(end - self.section_body).to_be_bytes()[..])
                     .await?;
             } else {
                 self.file
-                    .write_all(&(end - self.section_start).to_le_bytes()[..])
+                    .write_all(&(end - self.section_body).to_le_bytes()[..])
                     .await?;
             }
             self.file.seek(SeekFrom::End(0)).await?;
@@ -155,6 +181,8 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Writer<F> {
         version_minor: u16,
     ) -> Result<ShbOptionWriter<'_, F>, Error> {
         self.finish_section().await?;
+        self.section_start = self.file.stream_position().await?;
+        self.section_body = u64::MAX;
         let mut block = self.write_raw_block(SHB_ID).await?;
         block.writer.be = big_endian;
         block.write_u32(0x1A2B3C4D).await?;
@@ -192,6 +220,8 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Writer<F> {
         block.write_u32(iface_id).await?;
         block.write_u32((timestamp >> 32) as u32).await?;
         block.write_u32((timestamp & 0xFFFFFFFF) as u32).await?;
+        block.write_u32(0).await?;
+        block.write_u32(0).await?;
         Ok(EpbDataWriter {
             block: Some(block),
             custom_orig_len: false,
@@ -199,8 +229,10 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Writer<F> {
     }
 
     pub async fn write_spb(&mut self) -> Result<SpbDataWriter<'_, F>, Error> {
+        let mut block = self.write_raw_block(SPB_ID).await?;
+        block.write_u32(0).await?;
         Ok(SpbDataWriter {
-            block: self.write_raw_block(SPB_ID).await?,
+            block,
             custom_orig_len: false,
             finished: false,
         })
@@ -248,6 +280,14 @@ impl<F: AsyncWrite + AsyncSeek + Send + Unpin> Writer<F> {
     pub async fn write_raw_block(&mut self, block_id: u32) -> Result<RawBlockWriter<'_, F>, Error> {
         Ok(RawBlockWriter::new(self, block_id).await?)
     }
+
+    /// Writes a block read from another file, unchanged. See `CustomBlock`.
+    pub async fn write_custom_block(&mut self, block: &CustomBlock) -> Result<(), Error> {
+        check_byte_order(self.be, block.big_endian())?;
+        let mut raw = self.write_raw_block(block.block_id()).await?;
+        raw.write_all(block.content()).await?;
+        raw.finish().await
+    }
 }
 
 impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> RawBlockWriter<'a, F> {
@@ -270,7 +310,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> RawBlockWriter<'a, F> {
     async fn finish_impl(&mut self) -> Result<(), Error> {
         self.finished = true;
         let end = self.writer.file.seek(SeekFrom::End(0)).await?;
-        let len = 12u64 + (end - self.body_start);
+        let len = (12 + end - self.body_start) as u32;
         let len = if self.writer.be {
             len.to_be_bytes()
         } else {
@@ -451,11 +491,11 @@ impl<'a, 'b, F: AsyncWrite + AsyncSeek + Send + Unpin> RawOptionWriter<'a, 'b, F
         let end = self.block.seek(SeekFrom::End(0)).await?;
         let len = (end - self.body_start) as u16;
         self.block
-            .seek(SeekFrom::Start(self.body_start - 4))
+            .seek(SeekFrom::Start(self.body_start - 2))
             .await?;
         self.block.write_u16(len).await?;
         self.block.seek(SeekFrom::End(0)).await?;
-        write_padding(&mut self.block, ((4 - (len % 4)) % 4) as usize).await?;
+        write_padding(&mut self.block, len as usize).await?;
         Ok(())
     }
 
@@ -674,6 +714,10 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> ShbOptionWriter<'a, F> {
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finished = true;
         self.block.write_all(&END_OPT[..]).await?;
+        self.block.finish_impl().await?;
+        // The section length counts the bytes following the SHB.
+        let writer = &mut self.block.writer;
+        writer.section_body = writer.file.stream_position().await?;
         Ok(())
     }
 
@@ -682,6 +726,15 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> ShbOptionWriter<'a, F> {
         Ok(self.block.write_raw_option(id).await?)
     }
 
+    /// Writes an option read from another file, unchanged. See
+    /// `CustomOption`.
+    pub async fn write_custom_option(&mut self, opt: &CustomOption) -> Result<(), Error> {
+        check_byte_order(self.block.big_endian(), opt.big_endian())?;
+        let mut raw = self.write_raw_option(opt.option_code()).await?;
+        raw.write_all(opt.content()).await?;
+        raw.finish().await
+    }
+
     pub async fn write_comment(&mut self, comment: &str) -> Result<(), Error> {
         let mut opt = self.write_raw_option(OPT_COMMENT).await?;
         opt.write_all(comment.as_bytes()).await?;
@@ -719,7 +772,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> IdbOptionWriter<'a, F> {
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finished = true;
         self.block.write_all(&END_OPT[..]).await?;
-        Ok(())
+        self.block.finish_impl().await
     }
 
     pub async fn write_raw_option(&mut self, id: u16) -> Result<RawOptionWriter<'_, 'a, F>, Error> {
@@ -727,6 +780,15 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> IdbOptionWriter<'a, F> {
         Ok(self.block.write_raw_option(id).await?)
     }
 
+    /// Writes an option read from another file, unchanged. See
+    /// `CustomOption`.
+    pub async fn write_custom_option(&mut self, opt: &CustomOption) -> Result<(), Error> {
+        check_byte_order(self.block.big_endian(), opt.big_endian())?;
+        let mut raw = self.write_raw_option(opt.option_code()).await?;
+        raw.write_all(opt.content()).await?;
+        raw.finish().await
+    }
+
     pub async fn write_comment(&mut self, comment: &str) -> Result<(), Error> {
         let mut opt = self.write_raw_option(OPT_COMMENT).await?;
         opt.write_all(comment.as_bytes()).await?;
@@ -777,7 +839,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> IdbOptionWriter<'a, F> {
         opt.finish().await
     }
 
-    pub async fn write_eui_address(&mut self, addr: HwAddress<8>) -> Result<(), Error> {
+    pub async fn write_eui_address(&mut self, addr: EuiAddress) -> Result<(), Error> {
         let buf: [u8; 8] = addr.into();
         let mut opt = self.write_raw_option(IF_EUIADDR).await?;
         opt.write_all(&buf[..]).await?;
@@ -876,33 +938,26 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> EpbDataWriter<'a, F> {
         let custom_orig_len = self.custom_orig_len;
         if let Some(block) = self.block.as_mut() {
             let end = block.seek(SeekFrom::End(0)).await?;
-            if end < 16 {
-                block.write_u32(0).await?;
-                block.write_u32(0).await?;
-            } else {
-                block.seek(SeekFrom::Start(16)).await?;
-                let len = (end - 20) as u32;
+            block.seek(SeekFrom::Start(12)).await?;
+            let len = (end - 20) as u32;
+            block.write_u32(len).await?;
+            if !custom_orig_len {
                 block.write_u32(len).await?;
-                if !custom_orig_len {
-                    block.write_u32(len).await?;
-                }
-                block.seek(SeekFrom::End(0)).await?;
-                write_padding(block, len as usize).await?;
             }
+            block.seek(SeekFrom::End(0)).await?;
+            write_padding(block, len as usize).await?;
         }
         Ok(())
     }
 
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finish_impl().await?;
-        guarantee(std::mem::replace(&mut self.block, None))
-            .finish()
-            .await
+        guarantee(self.block.take()).finish().await
     }
 
     pub async fn write_options(mut self) -> Result<EpbOptionWriter<'a, F>, Error> {
         self.finish_impl().await?;
-        let block = guarantee(std::mem::replace(&mut self.block, None));
+        let block = guarantee(self.block.take());
         Ok(EpbOptionWriter {
             block,
             finished: true,
@@ -960,7 +1015,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> EpbOptionWriter<'a, F> {
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finished = true;
         self.block.write_all(&END_OPT[..]).await?;
-        Ok(())
+        self.block.finish_impl().await
     }
 
     pub async fn write_raw_option(&mut self, id: u16) -> Result<RawOptionWriter<'_, 'a, F>, Error> {
@@ -968,6 +1023,15 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> EpbOptionWriter<'a, F> {
         Ok(self.block.write_raw_option(id).await?)
     }
 
+    /// Writes an option read from another file, unchanged. See
+    /// `CustomOption`.
+    pub async fn write_custom_option(&mut self, opt: &CustomOption) -> Result<(), Error> {
+        check_byte_order(self.block.big_endian(), opt.big_endian())?;
+        let mut raw = self.write_raw_option(opt.option_code()).await?;
+        raw.write_all(opt.content()).await?;
+        raw.finish().await
+    }
+
     pub async fn write_comment(&mut self, comment: &str) -> Result<(), Error> {
         let mut opt = self.write_raw_option(OPT_COMMENT).await?;
         opt.write_all(comment.as_bytes()).await?;
@@ -989,6 +1053,51 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> EpbOptionWriter<'a, F> {
             finished: false,
         })
     }
+
+    pub async fn write_hash(&mut self, hash: &PacketHash) -> Result<(), Error> {
+        let mut opt = self.write_raw_option(EPB_HASH).await?;
+        opt.write_u8(hash.algorithm()).await?;
+        opt.write_all(hash.data()).await?;
+        opt.finish().await
+    }
+
+    pub async fn write_drop_count(&mut self, count: u64) -> Result<(), Error> {
+        let mut opt = self.write_raw_option(EPB_DROPCOUNT).await?;
+        opt.write_u64(count).await?;
+        opt.finish().await
+    }
+
+    pub async fn write_packet_id(&mut self, id: u64) -> Result<(), Error> {
+        let mut opt = self.write_raw_option(EPB_PACKETID).await?;
+        opt.write_u64(id).await?;
+        opt.finish().await
+    }
+
+    pub async fn write_queue(&mut self, queue: u32) -> Result<(), Error> {
+        let mut opt = self.write_raw_option(EPB_QUEUE).await?;
+        opt.write_u32(queue).await?;
+        opt.finish().await
+    }
+
+    /// Writes an option for each item set in `meta`.
+    pub async fn write_meta(&mut self, meta: &PacketMeta) -> Result<(), Error> {
+        if let Some(flags) = meta.flags() {
+            self.write_raw_packet_flags(flags).await?;
+        }
+        for hash in meta.hashes() {
+            self.write_hash(hash).await?;
+        }
+        if let Some(count) = meta.drop_count() {
+            self.write_drop_count(count).await?;
+        }
+        if let Some(id) = meta.packet_id() {
+            self.write_packet_id(id).await?;
+        }
+        if let Some(queue) = meta.queue() {
+            self.write_queue(queue).await?;
+        }
+        Ok(())
+    }
 }
 
 impl<'a, 'b, F: AsyncWrite + AsyncSeek + Send + Unpin> PacketFlagsOptionWriter<'a, 'b, F> {
@@ -1155,19 +1264,15 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> SpbDataWriter<'a, F> {
             }
         };
         let end = self.block.seek(SeekFrom::End(0)).await?;
-        if end < 4 {
-            self.block.write_u32(0).await?;
-        } else {
-            let len = (end - 4) as u32;
-            if len > snaplen {
-                return Err(Error::MalformedCapture);
-            } else if !custom_orig_len {
-                self.block.seek(SeekFrom::Start(0)).await?;
-                self.block.write_u32(len).await?;
-            }
-            self.block.seek(SeekFrom::End(0)).await?;
-            write_padding(&mut self.block, len as usize).await?;
+        let len = (end - 4) as u32;
+        if snaplen != 0 && len > snaplen {
+            return Err(Error::MalformedCapture);
+        } else if !custom_orig_len {
+            self.block.seek(SeekFrom::Start(0)).await?;
+            self.block.write_u32(len).await?;
         }
+        self.block.seek(SeekFrom::End(0)).await?;
+        write_padding(&mut self.block, len as usize).await?;
         self.block.finish_impl().await
     }
 
@@ -1243,7 +1348,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> NrbRecordWriter<'a, F> {
         addr: Ipv6Address,
     ) -> Result<NrbNameWriter<'_, 'a, F>, Error> {
         let block = guarantee(self.block.as_mut());
-        block.write_u16(NRB_RECORD_IPV4).await?;
+        block.write_u16(NRB_RECORD_IPV6).await?;
         let len_pos = block.stream_position().await?;
         block.write_u16(0).await?;
         block.write_all(&addr[..]).await?;
@@ -1252,15 +1357,13 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> NrbRecordWriter<'a, F> {
 
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finish_impl().await?;
-        guarantee(std::mem::replace(&mut self.block, None))
-            .finish()
-            .await
+        guarantee(self.block.take()).finish().await
     }
 
     pub async fn write_options(mut self) -> Result<NrbOptionWriter<'a, F>, Error> {
         self.finish_impl().await?;
         Ok(NrbOptionWriter {
-            block: guarantee(std::mem::replace(&mut self.block, None)),
+            block: guarantee(self.block.take()),
             finished: true,
         })
     }
@@ -1282,12 +1385,13 @@ impl<'a, 'b, F: AsyncWrite + AsyncSeek + Send + Unpin> NrbNameWriter<'a, 'b, F>
     }
 
     pub async fn finish(mut self) -> Result<(), Error> {
-        self.len_pos = u64::MAX;
+        let len_pos = std::mem::replace(&mut self.len_pos, u64::MAX);
         let end = self.block.stream_position().await?;
-        let len = end - self.len_pos - 2;
-        self.block.seek(SeekFrom::Start(self.len_pos)).await?;
+        let len = end - len_pos - 2;
+        self.block.seek(SeekFrom::Start(len_pos)).await?;
         self.block.write_u16(len as u16).await?;
         self.block.seek(SeekFrom::End(0)).await?;
+        write_padding(&mut self.block, len as usize).await?;
         Ok(())
     }
 }
@@ -1304,7 +1408,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> NrbOptionWriter<'a, F> {
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finished = true;
         self.block.write_all(&END_OPT[..]).await?;
-        Ok(())
+        self.block.finish_impl().await
     }
 
     pub async fn write_raw_option(&mut self, id: u16) -> Result<RawOptionWriter<'_, 'a, F>, Error> {
@@ -1312,6 +1416,15 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> NrbOptionWriter<'a, F> {
         Ok(self.block.write_raw_option(id).await?)
     }
 
+    /// Writes an option read from another file, unchanged. See
+    /// `CustomOption`.
+    pub async fn write_custom_option(&mut self, opt: &CustomOption) -> Result<(), Error> {
+        check_byte_order(self.block.big_endian(), opt.big_endian())?;
+        let mut raw = self.write_raw_option(opt.option_code()).await?;
+        raw.write_all(opt.content()).await?;
+        raw.finish().await
+    }
+
     pub async fn write_comment(&mut self, comment: &str) -> Result<(), Error> {
         let mut opt = self.write_raw_option(OPT_COMMENT).await?;
         opt.write_all(comment.as_bytes()).await?;
@@ -1349,7 +1462,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> IsbOptionWriter<'a, F> {
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finished = true;
         self.block.write_all(&END_OPT[..]).await?;
-        Ok(())
+        self.block.finish_impl().await
     }
 
     pub async fn write_raw_option(&mut self, id: u16) -> Result<RawOptionWriter<'_, 'a, F>, Error> {
@@ -1357,6 +1470,15 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> IsbOptionWriter<'a, F> {
         Ok(self.block.write_raw_option(id).await?)
     }
 
+    /// Writes an option read from another file, unchanged. See
+    /// `CustomOption`.
+    pub async fn write_custom_option(&mut self, opt: &CustomOption) -> Result<(), Error> {
+        check_byte_order(self.block.big_endian(), opt.big_endian())?;
+        let mut raw = self.write_raw_option(opt.option_code()).await?;
+        raw.write_all(opt.content()).await?;
+        raw.finish().await
+    }
+
     pub async fn write_comment(&mut self, comment: &str) -> Result<(), Error> {
         let mut opt = self.write_raw_option(OPT_COMMENT).await?;
         opt.write_all(comment.as_bytes()).await?;
@@ -1421,7 +1543,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> SjbEntryWriter<'a, F> {
         self.finished = true;
         let end = self.block.seek(SeekFrom::End(0)).await?;
         let len = end - self.block.body_start;
-        write_padding(&mut self.block, ((4 - (len % 4)) % 4) as usize).await?;
+        write_padding(&mut self.block, len as usize).await?;
         Ok(())
     }
 }
@@ -1478,14 +1600,12 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> DsbSecretsWriter<'a, F> {
 
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finish_impl().await?;
-        guarantee(std::mem::replace(&mut self.block, None))
-            .finish()
-            .await
+        guarantee(self.block.take()).finish().await
     }
 
     pub async fn write_options(mut self) -> Result<DsbOptionWriter<'a, F>, Error> {
         self.finish_impl().await?;
-        let block = guarantee(std::mem::replace(&mut self.block, None));
+        let block = guarantee(self.block.take());
         Ok(DsbOptionWriter {
             block,
             finished: true,
@@ -1535,7 +1655,7 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> DsbOptionWriter<'a, F> {
     pub async fn finish(mut self) -> Result<(), Error> {
         self.finished = true;
         self.block.write_all(&END_OPT[..]).await?;
-        Ok(())
+        self.block.finish_impl().await
     }
 
     pub async fn write_raw_option(&mut self, id: u16) -> Result<RawOptionWriter<'_, 'a, F>, Error> {
@@ -1543,6 +1663,15 @@ impl<'a, F: AsyncWrite + AsyncSeek + Send + Unpin> DsbOptionWriter<'a, F> {
         Ok(self.block.write_raw_option(id).await?)
     }
 
+    /// Writes an option read from another file, unchanged. See
+    /// `CustomOption`.
+    pub async fn write_custom_option(&mut self, opt: &CustomOption) -> Result<(), Error> {
+        check_byte_order(self.block.big_endian(), opt.big_endian())?;
+        let mut raw = self.write_raw_option(opt.option_code()).await?;
+        raw.write_all(opt.content()).await?;
+        raw.finish().await
+    }
+
     pub async fn write_comment(&mut self, comment: &str) -> Result<(), Error> {
         let mut opt = self.write_raw_option(OPT_COMMENT).await?;
         opt.write_all(comment.as_bytes()).await?;
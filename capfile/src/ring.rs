@@ -0,0 +1,356 @@
+//! Continuous capture into a ring buffer of pcapng files.
+//!
+//! A `RingRecorder` records packets into a numbered sequence of pcapng
+//! files in a directory, starting a new file when the current one reaches
+//! a size limit, and deleting the oldest files when the total size of the
+//! files exceeds another. When something interesting happens, the window
+//! of packets around it can be frozen, so it isn't deleted, and then
+//! exported to its own file once the packets after it have been captured.
+//!
+//! ```ignore
+//! let mut ring = RingRecorder::create(RingConfig::new("/var/capture").max_size(1 << 30)).await?;
+//! let mut snapshot = None;
+//! while let Some(packet) = sniffer.sniff_raw().await? {
+//!     let ts = packet.timestamp();
+//!     let alert = is_interesting(&packet);
+//!     ring.transmit_raw(packet).await?;
+//!     if alert {
+//!         snapshot = Some(ring.freeze(ts, Duration::from_secs(60), Duration::from_secs(10)));
+//!     }
+//!     if let Some(snap) = snapshot.take_if(|snap| ts > snap.end()) {
+//!         ring.export(&snap, "/var/capture/alert.pcapng").await?;
+//!         ring.release(snap);
+//!     }
+//! }
+//! ```
+
+use crate::{pcapng, slice_packets, SliceFilter};
+use async_trait::async_trait;
+use sniffle_core::{Error, RawPacket, SniffRaw, Transmit};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Configures a `RingRecorder`.
+///
+/// ```
+/// # use sniffle_capfile::ring::RingConfig;
+/// let config = RingConfig::new("/var/capture")
+///     .prefix("eth0")
+///     .file_size(16 << 20)
+///     .max_size(1 << 30);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingConfig {
+    dir: PathBuf,
+    prefix: String,
+    file_size: u64,
+    max_size: u64,
+}
+
+/// A continuous capture into a rotating set of pcapng files. See the
+/// module documentation.
+pub struct RingRecorder {
+    config: RingConfig,
+    files: VecDeque<RingFile>,
+    current: Option<pcapng::FileRecorder>,
+    next_seq: u64,
+    frozen: Vec<Snapshot>,
+    next_snapshot: u64,
+}
+
+/// A window of time frozen with `RingRecorder::freeze`. Files with packets
+/// in the window aren't deleted until the snapshot is released.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    id: u64,
+    start: SystemTime,
+    end: SystemTime,
+}
+
+#[derive(Debug)]
+struct RingFile {
+    path: PathBuf,
+    first: SystemTime,
+    last: SystemTime,
+    size: u64,
+}
+
+impl RingConfig {
+    /// Records into files in `dir`, which is created if it doesn't exist.
+    /// By default, files are named `capture_00000.pcapng`,
+    /// `capture_00001.pcapng`, etc., are started every 64 MiB, and are
+    /// limited to 1 GiB in total.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: String::from("capture"),
+            file_size: 64 << 20,
+            max_size: 1 << 30,
+        }
+    }
+
+    /// Sets the start of the file names, before the sequence number.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the size in bytes at which a new file is started.
+    pub fn file_size(mut self, size: u64) -> Self {
+        self.file_size = size;
+        self
+    }
+
+    /// Sets the total size in bytes of the files to keep. The oldest files
+    /// are deleted when a new file is started past this size. The file
+    /// being written to is never deleted, and neither are files holding
+    /// packets in a frozen snapshot.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = size;
+        self
+    }
+}
+
+impl RingRecorder {
+    pub async fn create(config: RingConfig) -> Result<Self, Error> {
+        tokio::fs::create_dir_all(&config.dir).await?;
+        Ok(Self {
+            config,
+            files: VecDeque::new(),
+            current: None,
+            next_seq: 0,
+            frozen: Vec::new(),
+            next_snapshot: 0,
+        })
+    }
+
+    pub fn config(&self) -> &RingConfig {
+        &self.config
+    }
+
+    /// The paths of the files currently in the ring, from oldest to newest.
+    pub fn files(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.files.iter().map(|file| file.path.as_path())
+    }
+
+    /// The total size in bytes of the files in the ring. The size of the
+    /// file being written to is an estimate until it's closed.
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
+    /// Closes the current file, so the next packet starts a new one.
+    pub async fn rotate(&mut self) -> Result<(), Error> {
+        let Some(current) = self.current.take() else {
+            return Ok(());
+        };
+        current.close().await?;
+        if let Some(file) = self.files.back_mut() {
+            file.size = tokio::fs::metadata(&file.path).await?.len();
+        }
+        Ok(())
+    }
+
+    /// Records every packet from `src` until it runs out of packets, and
+    /// returns the number of packets recorded.
+    pub async fn record<S: SniffRaw + ?Sized>(&mut self, src: &mut S) -> Result<usize, Error> {
+        let mut count = 0;
+        while let Some(packet) = src.sniff_raw().await? {
+            self.transmit_raw(packet).await?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Freezes the packets from `before` until `around` to `after` it, so
+    /// the files holding them aren't deleted. This also covers packets
+    /// that haven't been captured yet, so the snapshot is usually exported
+    /// once a packet after the end of the window has been recorded.
+    pub fn freeze(&mut self, around: SystemTime, before: Duration, after: Duration) -> Snapshot {
+        let snapshot = Snapshot {
+            id: self.next_snapshot,
+            start: around.checked_sub(before).unwrap_or(SystemTime::UNIX_EPOCH),
+            end: around.checked_add(after).unwrap_or(around),
+        };
+        self.next_snapshot += 1;
+        self.frozen.push(snapshot.clone());
+        snapshot
+    }
+
+    /// Copies the packets in the window of `snapshot` that have been
+    /// recorded so far to a new pcapng file at `dst`, and returns the number
+    /// of packets copied. The snapshot stays frozen until it's released.
+    pub async fn export<P: AsRef<Path>>(
+        &mut self,
+        snapshot: &Snapshot,
+        dst: P,
+    ) -> Result<usize, Error> {
+        if let Some(current) = self.current.as_mut() {
+            current.flush().await?;
+        }
+        let filter = SliceFilter::new().time(snapshot.start..=snapshot.end);
+        let mut dst = pcapng::FileRecorder::create(dst).await?;
+        let mut count = 0;
+        for file in self.files.iter().filter(|file| snapshot.overlaps(file)) {
+            let mut src = pcapng::FileSniffer::open_raw(&file.path).await?;
+            count += slice_packets(&mut src, &mut dst, &filter).await?;
+        }
+        dst.close().await?;
+        Ok(count)
+    }
+
+    /// Releases a frozen snapshot, so its files can be deleted when the
+    /// ring is next over its size limit.
+    pub fn release(&mut self, snapshot: Snapshot) {
+        self.frozen.retain(|frozen| frozen.id != snapshot.id);
+    }
+
+    /// Closes the current file. The files in the ring are kept.
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.rotate().await
+    }
+
+    async fn start_file(&mut self, ts: SystemTime) -> Result<(), Error> {
+        let path = self.config.dir.join(format!(
+            "{}_{:05}.pcapng",
+            self.config.prefix, self.next_seq
+        ));
+        self.next_seq += 1;
+        self.current = Some(pcapng::FileRecorder::create(&path).await?);
+        self.files.push_back(RingFile {
+            path,
+            first: ts,
+            last: ts,
+            size: 0,
+        });
+        self.enforce_retention().await
+    }
+
+    /// Deletes the oldest files that aren't frozen until the ring fits in
+    /// its size limit, keeping the current file.
+    async fn enforce_retention(&mut self) -> Result<(), Error> {
+        let mut total = self.total_size();
+        let mut idx = 0;
+        while total > self.config.max_size && idx + 1 < self.files.len() {
+            let file = &self.files[idx];
+            if self.frozen.iter().any(|snapshot| snapshot.overlaps(file)) {
+                idx += 1;
+                continue;
+            }
+            match tokio::fs::remove_file(&file.path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            total -= file.size;
+            self.files.remove(idx);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Transmit for RingRecorder {
+    async fn transmit_raw(&mut self, packet: RawPacket<'_>) -> Result<(), Error> {
+        let full = self
+            .files
+            .back()
+            .is_some_and(|file| file.size >= self.config.file_size);
+        if self.current.is_some() && full {
+            self.rotate().await?;
+        }
+        let ts = packet.timestamp();
+        if self.current.is_none() {
+            self.start_file(ts).await?;
+        }
+
+        let len = packet.data().len() as u64;
+        if let Some(current) = self.current.as_mut() {
+            current.transmit_raw(packet).await?;
+        }
+        if let Some(file) = self.files.back_mut() {
+            file.first = file.first.min(ts);
+            file.last = file.last.max(ts);
+            // Enhanced packet block header and trailer, and padded data
+            file.size += 32 + ((len + 3) & !3);
+        }
+        Ok(())
+    }
+}
+
+impl Snapshot {
+    pub fn start(&self) -> SystemTime {
+        self.start
+    }
+
+    pub fn end(&self) -> SystemTime {
+        self.end
+    }
+
+    fn overlaps(&self, file: &RingFile) -> bool {
+        file.first <= self.end && file.last >= self.start
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle_core::LinkType;
+
+    #[test]
+    fn ring_rotation() {
+        let dir = std::env::temp_dir().join(format!("sniffle-ring-{}", std::process::id()));
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let res = rt.block_on(async {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            let config = RingConfig::new(&dir)
+                .prefix("test")
+                .file_size(200)
+                .max_size(800);
+            let mut ring = RingRecorder::create(config).await?;
+            let secs = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            let data = [0u8; 64];
+            let mut snapshot = None;
+            for n in 0u8..40 {
+                let mut data = data;
+                data[0] = n;
+                let pkt = RawPacket::new(LinkType::ETHERNET, secs(n.into()), 64, None, &data, None);
+                ring.transmit_raw(pkt).await?;
+                if n == 5 {
+                    snapshot = Some(ring.freeze(secs(5), Duration::from_secs(2), Duration::ZERO));
+                }
+            }
+
+            // Three packets fit in a file, and only two files fit in the
+            // ring, but the file with the frozen packets is kept
+            let files: Vec<_> = ring.files().map(Path::to_path_buf).collect();
+            assert_eq!(files.len(), 3);
+            assert_eq!(files[0], dir.join("test_00001.pcapng"));
+            assert!(!dir.join("test_00000.pcapng").exists());
+            assert_eq!(files[2], dir.join("test_00013.pcapng"));
+
+            let snapshot = snapshot.unwrap();
+            let out = dir.join("snapshot.pcapng");
+            assert_eq!(ring.export(&snapshot, &out).await?, 3);
+            let mut src = pcapng::FileSniffer::open_raw(&out).await?;
+            let mut exported = Vec::new();
+            while let Some(packet) = src.sniff_raw().await? {
+                exported.push(packet.data()[0]);
+            }
+            assert_eq!(exported, [3, 4, 5]);
+
+            ring.release(snapshot);
+            for n in 40u8..43 {
+                let pkt = RawPacket::new(LinkType::ETHERNET, secs(n.into()), 64, None, &data, None);
+                ring.transmit_raw(pkt).await?;
+            }
+            assert!(!files[0].exists());
+            ring.close().await?;
+            Ok::<_, Error>(())
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+        res.unwrap();
+    }
+}
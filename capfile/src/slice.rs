@@ -0,0 +1,254 @@
+use sniffle_core::{Error, RawPacket, SniffRaw, Transmit};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+#[cfg(feature = "fs")]
+use crate::{index::IndexedSniff, pcap, pcapng, FileSniffer, Sniffer};
+
+type Predicate = Arc<dyn Fn(&RawPacket<'_>) -> Result<bool, Error> + Send + Sync>;
+
+/// Selects the packets copied by `slice`. Packets must match every
+/// criterion that is set. By default, every packet is selected.
+///
+/// ```
+/// # use sniffle_capfile::SliceFilter;
+/// # use std::time::{Duration, SystemTime};
+/// let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+/// let filter = SliceFilter::new()
+///     .packets(100..200)
+///     .time(start..start + Duration::from_secs(60));
+/// ```
+#[derive(Clone)]
+pub struct SliceFilter {
+    packets: (Bound<usize>, Bound<usize>),
+    time: (Bound<SystemTime>, Bound<SystemTime>),
+    predicates: Vec<Predicate>,
+}
+
+impl SliceFilter {
+    pub fn new() -> Self {
+        Self {
+            packets: (Bound::Unbounded, Bound::Unbounded),
+            time: (Bound::Unbounded, Bound::Unbounded),
+            predicates: Vec::new(),
+        }
+    }
+
+    /// Selects packets by number. The first packet in the file is number 0.
+    pub fn packets<R: RangeBounds<usize>>(mut self, range: R) -> Self {
+        self.packets = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    /// Selects packets by timestamp.
+    pub fn time<R: RangeBounds<SystemTime>>(mut self, range: R) -> Self {
+        self.time = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    /// Selects packets for which `predicate` returns true. An error returned
+    /// by `predicate` stops the copy.
+    pub fn matching<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&RawPacket<'_>) -> Result<bool, Error> + Send + Sync + 'static,
+    {
+        self.predicates.push(Arc::new(predicate));
+        self
+    }
+
+    /// Selects packets matching a BPF filter expression, such as
+    /// `"tcp port 80"`. The expression is compiled for each link type
+    /// encountered, so an invalid expression is reported by `slice`.
+    #[cfg(feature = "libpcap")]
+    pub fn bpf<S: Into<String>>(self, filter: S) -> Self {
+        let expr = filter.into();
+        let compiled = std::sync::Mutex::new(std::collections::HashMap::new());
+        self.matching(move |packet| {
+            let mut compiled = compiled.lock().unwrap_or_else(|e| e.into_inner());
+            let filter = match compiled.entry(packet.datalink()) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(pcaprs::OfflineFilter::new(
+                        pcaprs::LinkType(packet.datalink().0),
+                        packet.snaplen() as u32,
+                        &expr[..],
+                        true,
+                    )?)
+                }
+            };
+            Ok(filter.filter_partial(packet.data(), packet.orig_len() as u32))
+        })
+    }
+
+    #[cfg(feature = "fs")]
+    fn first_packet(&self) -> usize {
+        match self.packets.0 {
+            Bound::Included(n) => n,
+            Bound::Excluded(n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        }
+    }
+
+    fn past_last_packet(&self, n: usize) -> bool {
+        match self.packets.1 {
+            Bound::Included(last) => n > last,
+            Bound::Excluded(end) => n >= end,
+            Bound::Unbounded => false,
+        }
+    }
+
+    fn matches(&self, n: usize, packet: &RawPacket<'_>) -> Result<bool, Error> {
+        if !self.packets.contains(&n) || !self.time.contains(&packet.timestamp()) {
+            return Ok(false);
+        }
+        for predicate in self.predicates.iter() {
+            if !predicate(packet)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Default for SliceFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for SliceFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SliceFilter")
+            .field("packets", &self.packets)
+            .field("time", &self.time)
+            .field("predicates", &self.predicates.len())
+            .finish()
+    }
+}
+
+async fn copy<S: SniffRaw + ?Sized, T: Transmit + ?Sized>(
+    src: &mut S,
+    dst: &mut T,
+    filter: &SliceFilter,
+    first: usize,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    let mut n = first;
+    while let Some(packet) = src.sniff_raw().await? {
+        if filter.past_last_packet(n) {
+            break;
+        }
+        n += 1;
+        if !filter.matches(n - 1, &packet)? {
+            continue;
+        }
+        dst.transmit_raw(packet).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Copies the packets selected by `filter` from `src` to `dst`, and returns
+/// the number of packets copied. Packets are numbered starting from the
+/// next packet `src` returns.
+pub async fn slice_packets<S: SniffRaw + ?Sized, T: Transmit + ?Sized>(
+    src: &mut S,
+    dst: &mut T,
+    filter: &SliceFilter,
+) -> Result<usize, Error> {
+    copy(src, dst, filter, 0).await
+}
+
+/// Copies the packets selected by `filter` from the capture file at `src` to
+/// a new capture file at `dst`, like `editcap`. The new file has the same
+/// format as `src`, except that ERF files are copied to pcapng files, with
+/// an interface per capture port. For pcapng files, interface names, descriptions, and
+/// addresses are preserved. Returns the number of packets copied.
+#[cfg(feature = "fs")]
+pub async fn slice<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+    src: P,
+    dst: Q,
+    filter: &SliceFilter,
+) -> Result<usize, Error> {
+    let mut src = FileSniffer::open_raw(src).await?;
+    let first = filter.first_packet();
+    let found = src.seek_packet(first).await?;
+    match src {
+        Sniffer::Pcap(mut src) => {
+            let tsprec = src.reader().timestamp_precision();
+            let mut dst = pcap::FileRecorder::create_with_tsprec(dst, tsprec).await?;
+            let count = if found {
+                copy(&mut src, &mut dst, filter, first).await?
+            } else {
+                0
+            };
+            dst.flush().await?;
+            Ok(count)
+        }
+        Sniffer::PcapNG(mut src) => {
+            let mut dst = pcapng::FileRecorder::create(dst).await?;
+            let count = if found {
+                copy(&mut src, &mut dst, filter, first).await?
+            } else {
+                0
+            };
+            dst.flush().await?;
+            Ok(count)
+        }
+        Sniffer::Erf(mut src) => {
+            let mut dst = pcapng::FileRecorder::create(dst).await?;
+            let count = if found {
+                copy(&mut src, &mut dst, filter, first).await?
+            } else {
+                0
+            };
+            dst.flush().await?;
+            Ok(count)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn slice_pcap() {
+        let mut file = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        file.extend_from_slice(&[0u8; 8][..]);
+        file.extend_from_slice(&0xffffu32.to_le_bytes()[..]);
+        file.extend_from_slice(&1u32.to_le_bytes()[..]);
+        for sec in 0u32..10 {
+            for field in [sec, 0, 1, 1] {
+                file.extend_from_slice(&field.to_le_bytes()[..]);
+            }
+            file.push(sec as u8);
+        }
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let out = rt
+            .block_on(async {
+                let mut src = crate::pcap::Sniffer::new_raw(Cursor::new(file)).await?;
+                let mut dst = crate::pcap::Recorder::new(Vec::new());
+                let start = SystemTime::UNIX_EPOCH + Duration::from_secs(4);
+                let filter = SliceFilter::new()
+                    .packets(2..=7)
+                    .time(start..start + Duration::from_secs(5));
+                assert_eq!(slice_packets(&mut src, &mut dst, &filter).await?, 4);
+
+                let mut out = Vec::new();
+                let mut src = crate::pcap::Sniffer::new_raw(Cursor::new(dst.into_inner())).await?;
+                while let Some(packet) = src.sniff_raw().await? {
+                    out.push(packet.data()[0]);
+                }
+                Ok::<_, Error>(out)
+            })
+            .unwrap();
+        assert_eq!(out, [4, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,43 @@
+#ifndef SNIFFLE_H
+#define SNIFFLE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SNIFFLE_FORMAT_PCAP 0
+#define SNIFFLE_FORMAT_PCAPNG 1
+
+typedef struct SniffleSniffer SniffleSniffer;
+typedef struct SnifflePacket SnifflePacket;
+typedef struct SniffleWriter SniffleWriter;
+
+/* Description of the last error on the calling thread, or NULL. */
+const char *sniffle_last_error(void);
+
+SniffleSniffer *sniffle_open_file(const char *path);
+SniffleSniffer *sniffle_open_device(const char *name);
+void sniffle_sniffer_free(SniffleSniffer *sniffer);
+
+/* Returns NULL at the end of the capture or on error. */
+SnifflePacket *sniffle_next_packet(SniffleSniffer *sniffer);
+void sniffle_packet_free(SnifflePacket *packet);
+size_t sniffle_packet_length(const SnifflePacket *packet);
+void sniffle_packet_timestamp(const SnifflePacket *packet, int64_t *secs, uint32_t *nanos);
+size_t sniffle_packet_data(const SnifflePacket *packet, uint8_t *buf, size_t len);
+/* Returns the length of the value, or -1 if there is no such field. */
+ptrdiff_t sniffle_packet_field(const SnifflePacket *packet, const char *path, char *buf,
+                               size_t len);
+
+SniffleWriter *sniffle_writer_create(const char *path, int format);
+int sniffle_writer_write(SniffleWriter *writer, const SnifflePacket *packet);
+int sniffle_writer_close(SniffleWriter *writer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,418 @@
+#![doc = include_str!("../README.md")]
+
+use sniffle::capfile::{pcap, pcapng, FileSniffer};
+use sniffle::sniff::Sniff;
+use sniffle::transmit::Transmit;
+use sniffle::{Error, Packet};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+use tokio::runtime::Runtime;
+
+/// `sniffle_writer_create` format for pcap files.
+pub const SNIFFLE_FORMAT_PCAP: c_int = 0;
+/// `sniffle_writer_create` format for pcapng files.
+pub const SNIFFLE_FORMAT_PCAPNG: c_int = 1;
+
+/// A source of dissected packets, either a capture file or a device.
+pub struct SniffleSniffer {
+    rt: Runtime,
+    sniffer: Box<dyn Sniff>,
+}
+
+/// A dissected packet.
+pub struct SnifflePacket {
+    packet: Packet,
+    data: Vec<u8>,
+}
+
+/// A capture file being written.
+pub struct SniffleWriter {
+    rt: Runtime,
+    recorder: Recorder,
+}
+
+enum Recorder {
+    Pcap(pcap::FileRecorder),
+    PcapNG(pcapng::FileRecorder),
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error<E: std::fmt::Display>(err: E) {
+    let msg = CString::new(err.to_string().replace('\0', " ")).ok();
+    LAST_ERROR.with(|last| *last.borrow_mut() = msg);
+}
+
+fn clear_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+fn runtime() -> Result<Runtime, Error> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
+
+unsafe fn c_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        set_error("Null string argument");
+        return None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(e) => {
+            set_error(e);
+            None
+        }
+    }
+}
+
+fn into_raw<T, E: std::fmt::Display>(res: Result<T, E>) -> *mut T {
+    match res {
+        Ok(val) => Box::into_raw(Box::new(val)),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn status(res: Result<(), Error>) -> c_int {
+    match res {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Returns a description of the last error on the calling thread, or null
+/// if the last call succeeded. The string is valid until the next call into
+/// the library on the same thread.
+#[no_mangle]
+pub extern "C" fn sniffle_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_ref() {
+        Some(msg) => msg.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Opens a pcap or pcapng file. Returns null on error.
+///
+/// # Safety
+/// `path` must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_open_file(path: *const c_char) -> *mut SniffleSniffer {
+    clear_error();
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    into_raw(runtime().and_then(|rt| {
+        let sniffer = rt.block_on(FileSniffer::open(path))?;
+        Ok::<_, Error>(SniffleSniffer {
+            rt,
+            sniffer: Box::new(sniffer),
+        })
+    }))
+}
+
+/// Opens a network device for capture by name. Returns null on error, or
+/// if the library was built without the `libpcap` feature.
+///
+/// # Safety
+/// `name` must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_open_device(name: *const c_char) -> *mut SniffleSniffer {
+    clear_error();
+    let Some(name) = c_str(name) else {
+        return ptr::null_mut();
+    };
+    open_device(name)
+}
+
+#[cfg(feature = "libpcap")]
+fn open_device(name: &str) -> *mut SniffleSniffer {
+    use sniffle::device::{Device, DeviceSnifferConfig};
+
+    let Some(device) = Device::lookup(name) else {
+        set_error(format!("No device named {}", name));
+        return ptr::null_mut();
+    };
+    into_raw(runtime().and_then(|rt| {
+        let sniffer = rt.block_on(async { DeviceSnifferConfig::create(device).open() })?;
+        Ok::<_, Error>(SniffleSniffer {
+            rt,
+            sniffer: Box::new(sniffer),
+        })
+    }))
+}
+
+#[cfg(not(feature = "libpcap"))]
+fn open_device(_name: &str) -> *mut SniffleSniffer {
+    set_error("Built without device capture support");
+    ptr::null_mut()
+}
+
+/// Closes a sniffer. Does nothing if `sniffer` is null.
+///
+/// # Safety
+/// `sniffer` must be null or returned by `sniffle_open_file` or
+/// `sniffle_open_device`, and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_sniffer_free(sniffer: *mut SniffleSniffer) {
+    if !sniffer.is_null() {
+        drop(Box::from_raw(sniffer));
+    }
+}
+
+/// Reads and dissects the next packet. Returns null at the end of the
+/// capture, or on error, in which case `sniffle_last_error` is not null.
+///
+/// # Safety
+/// `sniffer` must be a valid sniffer.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_next_packet(sniffer: *mut SniffleSniffer) -> *mut SnifflePacket {
+    clear_error();
+    let sniffer = &mut *sniffer;
+    match sniffer.rt.block_on(sniffer.sniffer.sniff()) {
+        Ok(Some(packet)) => new_packet(packet),
+        Ok(None) => ptr::null_mut(),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn new_packet(packet: Packet) -> *mut SnifflePacket {
+    let mut data = Vec::new();
+    if packet.make_raw(&mut data).is_err() {
+        data.clear();
+    }
+    Box::into_raw(Box::new(SnifflePacket { packet, data }))
+}
+
+/// Frees a packet. Does nothing if `packet` is null.
+///
+/// # Safety
+/// `packet` must be null or returned by `sniffle_next_packet`, and not
+/// already freed.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_packet_free(packet: *mut SnifflePacket) {
+    if !packet.is_null() {
+        drop(Box::from_raw(packet));
+    }
+}
+
+/// Returns the length of the packet on the wire, which may be more than
+/// was captured.
+///
+/// # Safety
+/// `packet` must be a valid packet.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_packet_length(packet: *const SnifflePacket) -> usize {
+    (*packet).packet.len()
+}
+
+/// Stores the capture time of the packet as seconds and nanoseconds since
+/// the Unix epoch. Either pointer may be null.
+///
+/// # Safety
+/// `packet` must be a valid packet, and `secs` and `nanos` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_packet_timestamp(
+    packet: *const SnifflePacket,
+    secs: *mut i64,
+    nanos: *mut u32,
+) {
+    let ts = (*packet).packet.precise_timestamp();
+    if !secs.is_null() {
+        *secs = ts.secs();
+    }
+    if !nanos.is_null() {
+        *nanos = ts.subsec_nanos();
+    }
+}
+
+/// Copies up to `len` bytes of the captured packet data into `buf`, and
+/// returns the full length of the data.
+///
+/// # Safety
+/// `packet` must be a valid packet, and `buf` must be valid for writes of
+/// `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_packet_data(
+    packet: *const SnifflePacket,
+    buf: *mut u8,
+    len: usize,
+) -> usize {
+    let data = &(*packet).data;
+    if !buf.is_null() {
+        ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len().min(len));
+    }
+    data.len()
+}
+
+/// Looks up a dissected field by path, such as `"Ipv4.Source Address"`,
+/// and copies its value into `buf` as a nul terminated string, truncated to
+/// fit `len` bytes. Returns the full length of the value, not counting the
+/// nul terminator, or -1 if the packet has no such field.
+///
+/// # Safety
+/// `packet` must be a valid packet, `path` must be a nul terminated string,
+/// and `buf` must be null or valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_packet_field(
+    packet: *const SnifflePacket,
+    path: *const c_char,
+    buf: *mut c_char,
+    len: usize,
+) -> isize {
+    clear_error();
+    let Some(path) = c_str(path) else {
+        return -1;
+    };
+    let Some(value) = (*packet).packet.field_value(path) else {
+        return -1;
+    };
+    if !buf.is_null() && len > 0 {
+        let copied = value.len().min(len - 1);
+        ptr::copy_nonoverlapping(value.as_ptr().cast(), buf, copied);
+        *buf.add(copied) = 0;
+    }
+    value.len() as isize
+}
+
+/// Creates a capture file, in either `SNIFFLE_FORMAT_PCAP` or
+/// `SNIFFLE_FORMAT_PCAPNG` format. Returns null on error.
+///
+/// # Safety
+/// `path` must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_writer_create(
+    path: *const c_char,
+    format: c_int,
+) -> *mut SniffleWriter {
+    clear_error();
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    if format != SNIFFLE_FORMAT_PCAP && format != SNIFFLE_FORMAT_PCAPNG {
+        set_error(format!("Unknown capture format {}", format));
+        return ptr::null_mut();
+    }
+    into_raw(runtime().and_then(|rt| {
+        let recorder = rt.block_on(async {
+            Ok::<_, Error>(match format {
+                SNIFFLE_FORMAT_PCAP => Recorder::Pcap(pcap::FileRecorder::create(path).await?),
+                _ => Recorder::PcapNG(pcapng::FileRecorder::create(path).await?),
+            })
+        })?;
+        Ok::<_, Error>(SniffleWriter { rt, recorder })
+    }))
+}
+
+/// Writes a packet to a capture file. Returns 0 on success and -1 on
+/// error.
+///
+/// # Safety
+/// `writer` must be a valid writer and `packet` a valid packet.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_writer_write(
+    writer: *mut SniffleWriter,
+    packet: *const SnifflePacket,
+) -> c_int {
+    clear_error();
+    let writer = &mut *writer;
+    let packet = &(*packet).packet;
+    status(writer.rt.block_on(async {
+        match &mut writer.recorder {
+            Recorder::Pcap(rec) => rec.transmit(packet).await,
+            Recorder::PcapNG(rec) => rec.transmit(packet).await,
+        }
+    }))
+}
+
+/// Flushes and closes a capture file, freeing the writer. Returns 0 on
+/// success and -1 on error. Does nothing if `writer` is null.
+///
+/// # Safety
+/// `writer` must be null or returned by `sniffle_writer_create`, and not
+/// already closed.
+#[no_mangle]
+pub unsafe extern "C" fn sniffle_writer_close(writer: *mut SniffleWriter) -> c_int {
+    clear_error();
+    if writer.is_null() {
+        return 0;
+    }
+    let mut writer = Box::from_raw(writer);
+    let SniffleWriter { rt, recorder } = &mut *writer;
+    status(rt.block_on(async {
+        match recorder {
+            Recorder::Pcap(rec) => rec.flush().await,
+            Recorder::PcapNG(rec) => rec.flush().await,
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle::dissect::Session;
+    use sniffle::sniff::{LinkType, LinkTypeTable};
+
+    #[test]
+    fn packet_fields() {
+        let frame: Vec<u8> = [
+            &[0xff; 6][..],
+            &[2, 0, 0, 0, 0, 1],
+            &[0x12, 0x34],
+            &[0xde, 0xad],
+        ]
+        .concat();
+        let session = Session::new();
+        let (_, pdu) = session
+            .table_dissect::<LinkTypeTable>(&LinkType::ETHERNET, &frame[..], None)
+            .unwrap();
+        let ts = sniffle::Timestamp::new(1_700_000_000, 5_000);
+        let pkt = new_packet(Packet::new(ts, pdu, Some(60), None, None));
+
+        unsafe {
+            assert_eq!(sniffle_packet_length(pkt), 60);
+            let (mut secs, mut nanos) = (0, 0);
+            sniffle_packet_timestamp(pkt, &mut secs, &mut nanos);
+            assert_eq!((secs, nanos), (1_700_000_000, 5_000));
+            let mut data = [0u8; 32];
+            assert_eq!(
+                sniffle_packet_data(pkt, data.as_mut_ptr(), data.len()),
+                frame.len()
+            );
+            assert_eq!(&data[..frame.len()], &frame[..]);
+
+            let mut value = [0 as c_char; 8];
+            let len = sniffle_packet_field(
+                pkt,
+                c"Ethernet II.Src Address".as_ptr(),
+                value.as_mut_ptr(),
+                value.len(),
+            );
+            assert_eq!(len, 12);
+            assert_eq!(CStr::from_ptr(value.as_ptr()).to_str(), Ok("0200000"));
+            assert!(sniffle_last_error().is_null());
+
+            let path = c"Ethernet II.Bogus".as_ptr();
+            assert_eq!(sniffle_packet_field(pkt, path, ptr::null_mut(), 0), -1);
+            assert_eq!(
+                sniffle_packet_field(pkt, ptr::null(), ptr::null_mut(), 0),
+                -1
+            );
+            assert!(!sniffle_last_error().is_null());
+            sniffle_packet_free(pkt);
+        }
+    }
+}
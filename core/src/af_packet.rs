@@ -0,0 +1,571 @@
+//! Live capture on Linux through `AF_PACKET` sockets with `TPACKET_V3` ring
+//! buffers, without libpcap.
+
+use super::{
+    CaptureStats, Device, Error, LinkType, RawPacket, Session, SniffRaw, Sniffer, Timestamp,
+    TimestampPrecision, Transmit,
+};
+use async_trait::async_trait;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+
+// Definitions from <linux/if_packet.h> and <linux/if_arp.h>.
+const PACKET_ADD_MEMBERSHIP: libc::c_int = 1;
+const PACKET_RX_RING: libc::c_int = 5;
+const PACKET_STATISTICS: libc::c_int = 6;
+const PACKET_VERSION: libc::c_int = 10;
+const PACKET_MR_PROMISC: libc::c_ushort = 1;
+const TPACKET_V3: libc::c_int = 2;
+const TP_STATUS_KERNEL: u32 = 0;
+const TP_STATUS_USER: u32 = 1;
+const TPACKET_ALIGNMENT: u32 = 16;
+
+const ARPHRD_ETHER: u16 = 1;
+const ARPHRD_PPP: u16 = 512;
+const ARPHRD_LOOPBACK: u16 = 772;
+const ARPHRD_IEEE80211: u16 = 801;
+const ARPHRD_IEEE80211_RADIOTAP: u16 = 803;
+const ARPHRD_NONE: u16 = 0xfffe;
+
+#[repr(C)]
+struct TpacketReq3 {
+    block_size: libc::c_uint,
+    block_nr: libc::c_uint,
+    frame_size: libc::c_uint,
+    frame_nr: libc::c_uint,
+    retire_blk_tov: libc::c_uint,
+    sizeof_priv: libc::c_uint,
+    feature_req_word: libc::c_uint,
+}
+
+#[repr(C)]
+struct TpacketBdTs {
+    ts_sec: libc::c_uint,
+    ts_nsec: libc::c_uint,
+}
+
+#[repr(C)]
+struct TpacketBlockDesc {
+    version: u32,
+    offset_to_priv: u32,
+    block_status: u32,
+    num_pkts: u32,
+    offset_to_first_pkt: u32,
+    blk_len: u32,
+    seq_num: u64,
+    ts_first_pkt: TpacketBdTs,
+    ts_last_pkt: TpacketBdTs,
+}
+
+#[repr(C)]
+struct Tpacket3Hdr {
+    next_offset: u32,
+    sec: u32,
+    nsec: u32,
+    snaplen: u32,
+    len: u32,
+    status: u32,
+    mac: u16,
+    net: u16,
+    rxhash: u32,
+    vlan_tci: u32,
+    vlan_tpid: u16,
+    padding: [u8; 10],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct TpacketStatsV3 {
+    packets: libc::c_uint,
+    drops: libc::c_uint,
+    freeze_q_cnt: libc::c_uint,
+}
+
+#[repr(C)]
+struct PacketMreq {
+    ifindex: libc::c_int,
+    mr_type: libc::c_ushort,
+    alen: libc::c_ushort,
+    address: [libc::c_uchar; 8],
+}
+
+/// Configuration for an [`AfPacketSniffer`].
+///
+/// The ring buffer is made of `block_count` blocks of `block_size` bytes,
+/// which the kernel hands over to user space once a block fills or the
+/// block timeout expires.
+pub struct AfPacketConfig {
+    device: Arc<Device>,
+    block_size: u32,
+    block_count: u32,
+    frame_size: u32,
+    block_timeout: Duration,
+    promisc: bool,
+}
+
+struct Ring {
+    ptr: NonNull<u8>,
+    block_size: usize,
+    block_count: usize,
+}
+
+struct BlockCursor {
+    remaining: u32,
+    offset: usize,
+}
+
+/// A live capture on a Linux network interface, implemented directly on an
+/// `AF_PACKET` socket. Packets can also be transmitted on the interface
+/// through the same socket.
+pub struct AfPacketSniffer {
+    fd: AsyncFd<OwnedFd>,
+    ring: Ring,
+    block: usize,
+    cursor: Option<BlockCursor>,
+    datalink: LinkType,
+    dev: Arc<Device>,
+    started: Timestamp,
+    received: AtomicU64,
+    dropped: AtomicU64,
+}
+
+// The ring is only accessed through `&mut self`, or read-only through
+// packets borrowed from it.
+unsafe impl Send for AfPacketSniffer {}
+
+unsafe impl Sync for AfPacketSniffer {}
+
+fn last_os_error<T>() -> Result<T, Error> {
+    Err(Error::Io(std::io::Error::last_os_error()))
+}
+
+fn setsockopt<T>(fd: &OwnedFd, opt: libc::c_int, val: &T) -> Result<(), Error> {
+    let rc = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_PACKET,
+            opt,
+            (val as *const T).cast(),
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        last_os_error()
+    } else {
+        Ok(())
+    }
+}
+
+fn datalink_from_hatype(hatype: u16) -> Option<LinkType> {
+    match hatype {
+        ARPHRD_ETHER | ARPHRD_LOOPBACK => Some(LinkType::ETHERNET),
+        ARPHRD_IEEE80211 => Some(LinkType::IEEE802_11),
+        ARPHRD_IEEE80211_RADIOTAP => Some(LinkType::IEEE802_11_RADIOTAP),
+        ARPHRD_PPP | ARPHRD_NONE => Some(LinkType::RAW),
+        _ => None,
+    }
+}
+
+impl Ring {
+    /// Maps the ring buffer set up on `fd` by `req`. The mapping is released
+    /// when the `Ring` is dropped.
+    fn map(fd: &OwnedFd, req: &TpacketReq3) -> Result<Self, Error> {
+        let block_size = req.block_size as usize;
+        let block_count = req.block_nr as usize;
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                block_size * block_count,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return last_os_error();
+        }
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).expect("mmap returned null"),
+            block_size,
+            block_count,
+        })
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.block_size * self.block_count);
+        }
+    }
+}
+
+impl AfPacketConfig {
+    pub fn create(device: Device) -> Self {
+        Self {
+            device: Arc::new(device),
+            block_size: 1 << 20,
+            block_count: 16,
+            frame_size: 2048,
+            block_timeout: Duration::from_millis(100),
+            promisc: false,
+        }
+    }
+
+    /// Sets the size of each ring buffer block, in bytes. Must be a
+    /// multiple of the page size and of the frame size.
+    pub fn block_size(mut self, size: u32) -> Self {
+        self.block_size = size;
+        self
+    }
+
+    /// Sets the number of blocks in the ring buffer.
+    pub fn block_count(mut self, count: u32) -> Self {
+        self.block_count = count;
+        self
+    }
+
+    /// Sets the nominal frame size, in bytes. `TPACKET_V3` packs packets of
+    /// any size into a block, so this only affects the ring geometry the
+    /// kernel validates.
+    pub fn frame_size(mut self, size: u32) -> Self {
+        self.frame_size = size;
+        self
+    }
+
+    /// Sets how long the kernel waits before handing over a partially
+    /// filled block.
+    pub fn block_timeout(mut self, dur: Duration) -> Self {
+        self.block_timeout = dur;
+        self
+    }
+
+    pub fn promiscuous_mode(mut self, enable: bool) -> Self {
+        self.promisc = enable;
+        self
+    }
+
+    pub fn open_raw(self) -> Result<AfPacketSniffer, Error> {
+        AfPacketSniffer::open_raw(self)
+    }
+
+    pub fn open(self) -> Result<Sniffer<AfPacketSniffer>, Error> {
+        AfPacketSniffer::open(self)
+    }
+
+    pub fn open_with_session(self, session: Session) -> Result<Sniffer<AfPacketSniffer>, Error> {
+        AfPacketSniffer::open_with_session(self, session)
+    }
+
+    fn ring_request(&self) -> Result<TpacketReq3, Error> {
+        if self.block_size == 0
+            || self.block_count == 0
+            || self.frame_size == 0
+            || !self.frame_size.is_multiple_of(TPACKET_ALIGNMENT)
+            || !self.block_size.is_multiple_of(self.frame_size)
+        {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid AF_PACKET ring geometry",
+            )));
+        }
+        Ok(TpacketReq3 {
+            block_size: self.block_size,
+            block_nr: self.block_count,
+            frame_size: self.frame_size,
+            frame_nr: (self.block_size / self.frame_size) * self.block_count,
+            retire_blk_tov: self.block_timeout.as_millis().clamp(1, u32::MAX as u128) as u32,
+            sizeof_priv: 0,
+            feature_req_word: 0,
+        })
+    }
+}
+
+impl AfPacketSniffer {
+    /// Opens a capture on the configured interface. Fails with
+    /// `Error::UnknownLinkType` if the interface's hardware type has no
+    /// known link type.
+    pub fn open_raw(config: AfPacketConfig) -> Result<Self, Error> {
+        let req = config.ring_request()?;
+        let proto = (libc::ETH_P_ALL as u16).to_be();
+
+        // The socket is created with protocol 0 so it receives nothing until
+        // it is bound to the interface. Otherwise packets from every
+        // interface would be queued before the bind.
+        let fd = unsafe {
+            let fd = libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                0,
+            );
+            if fd < 0 {
+                return last_os_error();
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+
+        let name = std::ffi::CString::new(config.device.name())
+            .map_err(|e| Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return last_os_error();
+        }
+
+        let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as libc::c_ushort;
+        addr.sll_protocol = proto;
+        addr.sll_ifindex = ifindex as libc::c_int;
+        let mut addr_len = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+        unsafe {
+            let addr_ptr = (&mut addr as *mut libc::sockaddr_ll).cast::<libc::sockaddr>();
+            if libc::bind(fd.as_raw_fd(), addr_ptr, addr_len) != 0
+                || libc::getsockname(fd.as_raw_fd(), addr_ptr, &mut addr_len) != 0
+            {
+                return last_os_error();
+            }
+        }
+        let datalink = datalink_from_hatype(addr.sll_hatype).ok_or(Error::UnknownLinkType)?;
+
+        setsockopt(&fd, PACKET_VERSION, &TPACKET_V3)?;
+        setsockopt(&fd, PACKET_RX_RING, &req)?;
+        let ring = Ring::map(&fd, &req)?;
+
+        if config.promisc {
+            let mreq = PacketMreq {
+                ifindex: ifindex as libc::c_int,
+                mr_type: PACKET_MR_PROMISC,
+                alen: 0,
+                address: [0; 8],
+            };
+            setsockopt(&fd, PACKET_ADD_MEMBERSHIP, &mreq)?;
+        }
+
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+            ring,
+            block: 0,
+            cursor: None,
+            datalink,
+            dev: config.device,
+            started: Timestamp::now(),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    pub fn open(config: AfPacketConfig) -> Result<Sniffer<Self>, Error> {
+        Ok(Sniffer::new(Self::open_raw(config)?))
+    }
+
+    pub fn open_with_session(
+        config: AfPacketConfig,
+        session: Session,
+    ) -> Result<Sniffer<Self>, Error> {
+        Ok(Sniffer::with_session(Self::open_raw(config)?, session))
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.dev
+    }
+
+    pub fn device_mut(&mut self) -> Option<&mut Device> {
+        Arc::get_mut(&mut self.dev)
+    }
+
+    pub fn share_device(&self) -> Arc<Device> {
+        self.dev.clone()
+    }
+
+    /// The link type of the interface, determined from its hardware type.
+    pub fn datalink(&self) -> LinkType {
+        self.datalink
+    }
+
+    /// The time the capture was started.
+    pub fn start_time(&self) -> Timestamp {
+        self.started
+    }
+
+    /// Packet counts since the capture was started. Interface drops are not
+    /// reported by `AF_PACKET` and are always zero.
+    pub fn stats(&self) -> Result<CaptureStats, Error> {
+        let mut stats = TpacketStatsV3::default();
+        let mut len = std::mem::size_of::<TpacketStatsV3>() as libc::socklen_t;
+        let rc = unsafe {
+            libc::getsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_PACKET,
+                PACKET_STATISTICS,
+                (&mut stats as *mut TpacketStatsV3).cast(),
+                &mut len,
+            )
+        };
+        if rc != 0 {
+            return last_os_error();
+        }
+        // The kernel resets its counters each time they are read.
+        let received = self
+            .received
+            .fetch_add(stats.packets.into(), Ordering::Relaxed)
+            + u64::from(stats.packets);
+        let dropped = self
+            .dropped
+            .fetch_add(stats.drops.into(), Ordering::Relaxed)
+            + u64::from(stats.drops);
+        Ok(CaptureStats {
+            received,
+            dropped,
+            iface_dropped: 0,
+        })
+    }
+
+    fn block_desc(&self, block: usize) -> *mut TpacketBlockDesc {
+        unsafe {
+            self.ring
+                .ptr
+                .as_ptr()
+                .add(block * self.ring.block_size)
+                .cast()
+        }
+    }
+
+    fn block_status(&self, block: usize) -> u32 {
+        unsafe { std::ptr::addr_of!((*self.block_desc(block)).block_status).read_volatile() }
+    }
+
+    fn release_block(&mut self) {
+        let desc = self.block_desc(self.block);
+        std::sync::atomic::fence(Ordering::Release);
+        unsafe {
+            std::ptr::addr_of_mut!((*desc).block_status).write_volatile(TP_STATUS_KERNEL);
+        }
+        self.block = (self.block + 1) % self.ring.block_count;
+        self.cursor = None;
+    }
+
+    fn next_in_block(&mut self) -> Option<RawPacket<'_>> {
+        let cursor = self.cursor.as_mut()?;
+        if cursor.remaining == 0 {
+            return None;
+        }
+        let base = self.block * self.ring.block_size + cursor.offset;
+        let (hdr, data) = unsafe {
+            let hdr = &*self.ring.ptr.as_ptr().add(base).cast::<Tpacket3Hdr>();
+            let data = std::slice::from_raw_parts(
+                self.ring.ptr.as_ptr().add(base + hdr.mac as usize),
+                hdr.snaplen as usize,
+            );
+            (hdr, data)
+        };
+        cursor.remaining -= 1;
+        cursor.offset += hdr.next_offset as usize;
+        let ts = Timestamp::new(hdr.sec.into(), hdr.nsec).with_precision(TimestampPrecision::Nanos);
+        Some(RawPacket::new(
+            self.datalink,
+            ts,
+            hdr.len as usize,
+            None,
+            data,
+            Some(self.dev.clone()),
+        ))
+    }
+}
+
+#[async_trait]
+impl SniffRaw for AfPacketSniffer {
+    async fn sniff_raw(&mut self) -> Result<Option<RawPacket<'_>>, Error> {
+        loop {
+            // Packets from the previous call borrow the current block, so it
+            // is only handed back to the kernel once they can no longer be
+            // used.
+            if matches!(self.cursor, Some(BlockCursor { remaining: 0, .. })) {
+                self.release_block();
+            }
+
+            if self.cursor.is_some() {
+                break;
+            }
+
+            if self.block_status(self.block) & TP_STATUS_USER != 0 {
+                std::sync::atomic::fence(Ordering::Acquire);
+                let desc = unsafe { &*self.block_desc(self.block) };
+                self.cursor = Some(BlockCursor {
+                    remaining: desc.num_pkts,
+                    offset: desc.offset_to_first_pkt as usize,
+                });
+                continue;
+            }
+
+            let mut guard = self.fd.readable().await?;
+            if self.block_status(self.block) & TP_STATUS_USER == 0 {
+                guard.clear_ready();
+            }
+        }
+        Ok(self.next_in_block())
+    }
+}
+
+#[async_trait]
+impl Transmit for AfPacketSniffer {
+    async fn transmit_raw(&mut self, packet: RawPacket<'_>) -> Result<(), Error> {
+        let data = packet.data();
+        loop {
+            let mut guard = self.fd.writable().await?;
+            let res = guard.try_io(|fd| {
+                let rc = unsafe { libc::send(fd.as_raw_fd(), data.as_ptr().cast(), data.len(), 0) };
+                if rc < 0 {
+                    Err(std::io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            });
+            if let Ok(res) = res {
+                return Ok(res?);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ring_header_layout() {
+        assert_eq!(std::mem::size_of::<Tpacket3Hdr>(), 48);
+        assert_eq!(std::mem::size_of::<TpacketBlockDesc>(), 48);
+        assert_eq!(std::mem::size_of::<TpacketReq3>(), 28);
+    }
+
+    #[test]
+    fn ring_geometry() {
+        let config = AfPacketConfig::create(crate::DeviceBuilder::new().into_device())
+            .block_size(1 << 16)
+            .block_count(4)
+            .frame_size(2048);
+        let req = config.ring_request().unwrap();
+        assert_eq!(req.frame_nr, 128);
+        assert_eq!(req.retire_blk_tov, 100);
+
+        let config = AfPacketConfig::create(crate::DeviceBuilder::new().into_device())
+            .block_size(1 << 16)
+            .frame_size(2000);
+        assert!(config.ring_request().is_err());
+    }
+
+    #[test]
+    fn hatype_datalinks() {
+        assert_eq!(datalink_from_hatype(ARPHRD_ETHER), Some(LinkType::ETHERNET));
+        assert_eq!(
+            datalink_from_hatype(ARPHRD_IEEE80211_RADIOTAP),
+            Some(LinkType::IEEE802_11_RADIOTAP)
+        );
+        assert_eq!(datalink_from_hatype(ARPHRD_NONE), Some(LinkType::RAW));
+        assert_eq!(datalink_from_hatype(0x1234), None);
+    }
+}
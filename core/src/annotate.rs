@@ -0,0 +1,299 @@
+use super::{Dump, DumpValue};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Metadata about an address, such as where it is located and which
+/// autonomous system it belongs to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotation {
+    /// ISO 3166-1 alpha-2 country code, such as `"US"`
+    pub country: Option<String>,
+    /// Autonomous system number
+    pub asn: Option<u32>,
+    /// Organization of the autonomous system
+    pub as_org: Option<String>,
+}
+
+/// Looks up metadata about addresses.
+///
+/// Set an annotator on a packet with `Packet::set_annotator`, or register an
+/// `Arc<dyn PacketAnnotator>` in a `Session` to have sniffers set it on every
+/// packet. PDUs then dump an annotation node beside their source and
+/// destination addresses, which can be found with `Packet::get` and `Query`.
+/// See `NodeDumper::add_annotation`.
+///
+/// A pair of annotators fills in the fields missing from the first with the
+/// second, such as when combining a country database with an ASN database.
+pub trait PacketAnnotator: Send + Sync {
+    fn annotate(&self, addr: IpAddr) -> Option<Annotation>;
+}
+
+/// A dumper that adds the annotations of a `PacketAnnotator` beside
+/// addresses. See `Dump::annotate_addr`.
+pub struct AnnotatingDumper<D: Dump> {
+    dumper: D,
+    annotator: Arc<dyn PacketAnnotator>,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.country.is_none() && self.asn.is_none() && self.as_org.is_none()
+    }
+
+    /// Fills in fields that are `None` from `other`.
+    pub fn or(self, other: Annotation) -> Self {
+        Self {
+            country: self.country.or(other.country),
+            asn: self.asn.or(other.asn),
+            as_org: self.as_org.or(other.as_org),
+        }
+    }
+}
+
+impl<A: PacketAnnotator + ?Sized> PacketAnnotator for Arc<A> {
+    fn annotate(&self, addr: IpAddr) -> Option<Annotation> {
+        A::annotate(self, addr)
+    }
+}
+
+impl<A: PacketAnnotator + ?Sized> PacketAnnotator for Box<A> {
+    fn annotate(&self, addr: IpAddr) -> Option<Annotation> {
+        A::annotate(self, addr)
+    }
+}
+
+impl<A: PacketAnnotator, B: PacketAnnotator> PacketAnnotator for (A, B) {
+    fn annotate(&self, addr: IpAddr) -> Option<Annotation> {
+        match (self.0.annotate(addr), self.1.annotate(addr)) {
+            (Some(first), Some(second)) => Some(first.or(second)),
+            (first, second) => first.or(second),
+        }
+    }
+}
+
+impl<D: Dump> AnnotatingDumper<D> {
+    pub fn new(dumper: D, annotator: Arc<dyn PacketAnnotator>) -> Self {
+        Self { dumper, annotator }
+    }
+
+    pub fn annotator(&self) -> &Arc<dyn PacketAnnotator> {
+        &self.annotator
+    }
+
+    pub fn into_inner(self) -> D {
+        self.dumper
+    }
+}
+
+impl<D: Dump> std::ops::Deref for AnnotatingDumper<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        &self.dumper
+    }
+}
+
+impl<D: Dump> std::ops::DerefMut for AnnotatingDumper<D> {
+    fn deref_mut(&mut self) -> &mut D {
+        &mut self.dumper
+    }
+}
+
+impl<D: Dump> Dump for AnnotatingDumper<D> {
+    type Error = D::Error;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        self.dumper.start_packet()
+    }
+
+    fn end_packet(&mut self) {
+        self.dumper.end_packet()
+    }
+
+    fn start_node(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_node(name, descr)
+    }
+
+    fn end_node(&mut self) {
+        self.dumper.end_node()
+    }
+
+    fn add_field(
+        &mut self,
+        name: &str,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        self.dumper.add_field(name, value, descr)
+    }
+
+    fn add_info(&mut self, name: &str, descr: &str) -> Result<(), Self::Error> {
+        self.dumper.add_info(name, descr)
+    }
+
+    fn start_list(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_list(name, descr)
+    }
+
+    fn end_list(&mut self) {
+        self.dumper.end_list()
+    }
+
+    fn add_list_item(
+        &mut self,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        self.dumper.add_list_item(value, descr)
+    }
+
+    fn start_list_node(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_list_node(descr)
+    }
+
+    fn end_list_node(&mut self) {
+        self.dumper.end_list_node()
+    }
+
+    fn start_list_sublist(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_list_sublist(descr)
+    }
+
+    fn end_list_sublist(&mut self) {
+        self.dumper.end_list_sublist()
+    }
+
+    fn set_byte_range(&mut self, offset: usize, len: usize) {
+        self.dumper.set_byte_range(offset, len)
+    }
+
+    fn resolve_name(&self, addr: IpAddr) -> Option<String> {
+        self.dumper.resolve_name(addr)
+    }
+
+    fn annotate_addr(&self, addr: IpAddr) -> Option<Annotation> {
+        self.annotator.annotate(addr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diff::DiffCollector;
+    use crate::Dumper;
+    use std::net::Ipv4Addr;
+
+    fn annotation(country: Option<&str>, asn: Option<u32>) -> Annotation {
+        Annotation {
+            country: country.map(String::from),
+            asn,
+            as_org: None,
+        }
+    }
+
+    /// Annotates 10.0.0.0/8 with a fixed annotation.
+    struct Fixed(Annotation);
+
+    impl PacketAnnotator for Fixed {
+        fn annotate(&self, addr: IpAddr) -> Option<Annotation> {
+            match addr {
+                IpAddr::V4(addr) if addr.octets()[0] == 10 => Some(self.0.clone()),
+                _ => None,
+            }
+        }
+    }
+
+    const INSIDE: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const OUTSIDE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1));
+
+    #[test]
+    fn is_empty() {
+        assert!(Annotation::default().is_empty());
+        assert!(!annotation(None, Some(1)).is_empty());
+        assert!(!Annotation {
+            as_org: Some("Org".into()),
+            ..Default::default()
+        }
+        .is_empty());
+    }
+
+    #[test]
+    fn or_fills_missing_fields() {
+        let first = annotation(Some("US"), None);
+        let second = annotation(Some("CA"), Some(64512));
+        assert_eq!(first.or(second), annotation(Some("US"), Some(64512)));
+        assert_eq!(
+            Annotation::default().or(Annotation::default()),
+            Annotation::default()
+        );
+    }
+
+    #[test]
+    fn pair_of_annotators() {
+        let pair = (
+            Fixed(annotation(Some("US"), None)),
+            Fixed(annotation(Some("CA"), Some(64512))),
+        );
+        assert_eq!(
+            pair.annotate(INSIDE),
+            Some(annotation(Some("US"), Some(64512)))
+        );
+        assert_eq!(pair.annotate(OUTSIDE), None);
+    }
+
+    #[test]
+    fn shared_annotators() {
+        let boxed: Box<dyn PacketAnnotator> = Box::new(Fixed(annotation(None, Some(1))));
+        assert_eq!(boxed.annotate(INSIDE), Some(annotation(None, Some(1))));
+        let pair = (Arc::new(Fixed(Annotation::default())), boxed);
+        assert_eq!(pair.annotate(INSIDE), Some(annotation(None, Some(1))));
+        assert_eq!(pair.annotate(OUTSIDE), None);
+    }
+
+    #[test]
+    fn pair_with_one_missing() {
+        struct Never;
+
+        impl PacketAnnotator for Never {
+            fn annotate(&self, _addr: IpAddr) -> Option<Annotation> {
+                None
+            }
+        }
+
+        let first = (Never, Fixed(annotation(Some("US"), None)));
+        assert_eq!(first.annotate(INSIDE), Some(annotation(Some("US"), None)));
+        let second = (Fixed(annotation(Some("US"), None)), Never);
+        assert_eq!(second.annotate(INSIDE), Some(annotation(Some("US"), None)));
+        assert_eq!((Never, Never).annotate(INSIDE), None);
+    }
+
+    #[test]
+    fn annotating_dumper() {
+        let annotator: Arc<dyn PacketAnnotator> = Arc::new(Fixed(Annotation {
+            country: Some("US".into()),
+            asn: Some(64512),
+            as_org: Some("Example".into()),
+        }));
+        let mut collector = DiffCollector::default();
+        let mut dumper = Dumper::new(AnnotatingDumper::new(&mut collector, annotator));
+        assert_eq!(dumper.annotate_addr(INSIDE).unwrap().asn, Some(64512));
+        assert_eq!(dumper.annotate_addr(OUTSIDE), None);
+        {
+            let mut packet = dumper.add_packet().unwrap();
+            packet.add_annotation("Source", INSIDE).unwrap();
+            // Addresses without an annotation have no node
+            packet.add_annotation("Destination", OUTSIDE).unwrap();
+        }
+        drop(dumper);
+        let paths: Vec<_> = collector.entries.iter().map(|e| &e.path[..]).collect();
+        assert_eq!(
+            paths,
+            [
+                "Source",
+                "Source.Country",
+                "Source.ASN",
+                "Source.AS Organization"
+            ]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use super::{Error, MergePolicy, MergeSniffer, Packet, Sniff};
+use async_trait::async_trait;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Combinators for `Sniff` implementations.
+pub trait SniffExt: Sniff + Sized + 'static {
+    /// Returns the packets of `self`, followed by the packets of `next`,
+    /// such as consecutive files of a rotating capture.
+    fn chain<S: Sniff + 'static>(self, next: S) -> Chain {
+        Chain::new().chain(self).chain(next)
+    }
+
+    /// Merges the packets of `self` and `other` in timestamp order, such as
+    /// captures of multiple interfaces taken at the same time. Must be
+    /// called from within a tokio runtime. See `MergeSniffer`.
+    fn merge<S: Sniff + 'static>(self, other: S) -> MergeSniffer {
+        MergeSniffer::new(MergePolicy::Timestamp)
+            .merge(self)
+            .merge(other)
+    }
+
+    /// Converts `self` into a `Stream` of packets, for use with stream
+    /// adaptors such as those of the `futures` crate. `Sniffer` implements
+    /// `Stream` itself, so this is only needed for other sniffers.
+    fn into_stream(self) -> SniffStream<Self> {
+        SniffStream::new(self)
+    }
+}
+
+impl<S: Sniff + Sized + 'static> SniffExt for S {}
+
+/// Returns the packets of each source in turn. See `SniffExt::chain`.
+#[derive(Default)]
+pub struct Chain {
+    sources: VecDeque<Box<dyn Sniff>>,
+}
+
+type PendingSniff<S> = Pin<Box<dyn Future<Output = (S, Result<Option<Packet>, Error>)> + Send>>;
+
+/// A `Stream` of the packets of a sniffer. See `SniffExt::into_stream`.
+///
+/// The stream ends when the sniffer returns `None`, or after yielding the
+/// first error, since most sniffers can't continue after one. The sniffer
+/// can still be used with `into_inner` afterwards.
+pub struct SniffStream<S: Sniff + 'static> {
+    sniffer: Option<S>,
+    pending: Option<PendingSniff<S>>,
+    done: bool,
+}
+
+// The sniffer is only ever moved, never pinned in place.
+impl<S: Sniff + 'static> Unpin for SniffStream<S> {}
+
+impl Chain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source after the existing ones.
+    pub fn chain<S: Sniff + 'static>(mut self, next: S) -> Self {
+        self.push(next);
+        self
+    }
+
+    pub fn push<S: Sniff + 'static>(&mut self, next: S) {
+        self.sources.push_back(Box::new(next));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+impl<S: Sniff + 'static> FromIterator<S> for Chain {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut chain = Self::new();
+        for source in iter {
+            chain.push(source);
+        }
+        chain
+    }
+}
+
+#[async_trait]
+impl Sniff for Chain {
+    async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
+        while let Some(source) = self.sources.front_mut() {
+            if let Some(pkt) = source.sniff().await? {
+                return Ok(Some(pkt));
+            }
+            self.sources.pop_front();
+        }
+        Ok(None)
+    }
+}
+
+impl<S: Sniff + 'static> SniffStream<S> {
+    pub fn new(sniffer: S) -> Self {
+        Self {
+            sniffer: Some(sniffer),
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Returns the sniffer, or `None` if the stream was dropped in the
+    /// middle of reading a packet.
+    pub fn into_inner(self) -> Option<S> {
+        self.sniffer
+    }
+}
+
+impl<S: Sniff + 'static> Stream for SniffStream<S> {
+    type Item = Result<Packet, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        if self.pending.is_none() {
+            let mut sniffer = match self.sniffer.take() {
+                Some(sniffer) => sniffer,
+                None => return Poll::Ready(None),
+            };
+            self.pending = Some(Box::pin(async move {
+                let res = sniffer.sniff().await;
+                (sniffer, res)
+            }));
+        }
+        let (sniffer, res) = match self.pending.as_mut().map(|fut| fut.as_mut().poll(cx)) {
+            Some(Poll::Ready(ret)) => ret,
+            _ => return Poll::Pending,
+        };
+        self.pending = None;
+        self.sniffer = Some(sniffer);
+        match res {
+            Ok(Some(pkt)) => Poll::Ready(Some(Ok(pkt))),
+            Ok(None) => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+            Err(e) => {
+                self.done = true;
+                Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RawPdu;
+    use std::time::{Duration, SystemTime};
+
+    struct Source(Vec<u64>);
+
+    #[async_trait]
+    impl Sniff for Source {
+        async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
+            if self.0.is_empty() {
+                return Ok(None);
+            }
+            let secs = self.0.remove(0);
+            Ok(Some(Packet::new(
+                SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                RawPdu::new(vec![secs as u8]),
+                None,
+                None,
+                None,
+            )))
+        }
+    }
+
+    async fn collect<S: Sniff>(sniffer: &mut S) -> Vec<u64> {
+        let mut out = Vec::new();
+        while let Some(pkt) = sniffer.sniff().await.unwrap() {
+            let ts = pkt.timestamp().duration_since(SystemTime::UNIX_EPOCH);
+            out.push(ts.unwrap().as_secs());
+        }
+        out
+    }
+
+    #[test]
+    fn chain_and_merge() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut chain = Source(vec![3, 4])
+                .chain(Source(vec![]))
+                .chain(Source(vec![1]));
+            assert_eq!(chain.len(), 3);
+            assert_eq!(collect(&mut chain).await, [3, 4, 1]);
+            assert!(chain.is_empty());
+
+            let mut merge = Source(vec![1, 4, 5])
+                .merge(Source(vec![2, 3, 6]))
+                .merge(Source(vec![0, 7]));
+            assert_eq!(merge.sources().count(), 3);
+            assert_eq!(collect(&mut merge).await, [0, 1, 2, 3, 4, 5, 6, 7]);
+            assert!(merge.is_empty());
+
+            let mut merge: MergeSniffer = [Source(vec![1, 2]), Source(vec![1, 3])]
+                .into_iter()
+                .collect();
+            assert_eq!(collect(&mut merge).await, [1, 1, 2, 3]);
+
+            let mut stream = Source(vec![5, 6]).chain(Source(vec![7])).into_stream();
+            let mut out = Vec::new();
+            while let Some(pkt) =
+                std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+            {
+                let ts = pkt
+                    .unwrap()
+                    .timestamp()
+                    .duration_since(SystemTime::UNIX_EPOCH);
+                out.push(ts.unwrap().as_secs());
+            }
+            assert_eq!(out, [5, 6, 7]);
+            assert!(
+                std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                    .await
+                    .is_none()
+            );
+            assert!(stream.into_inner().unwrap().is_empty());
+        });
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl Sniff for Failing {
+        async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
+            Err(Error::MalformedCapture)
+        }
+    }
+
+    async fn next<S: Sniff>(stream: &mut SniffStream<S>) -> Option<Result<Packet, Error>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[test]
+    fn stream_ends_after_error() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut stream = Source(vec![1]).chain(Failing).into_stream();
+            assert!(next(&mut stream).await.unwrap().is_ok());
+            assert!(matches!(
+                next(&mut stream).await,
+                Some(Err(Error::MalformedCapture))
+            ));
+            assert!(next(&mut stream).await.is_none());
+            assert!(next(&mut stream).await.is_none());
+            // The sniffer is given back after the error
+            assert_eq!(stream.into_inner().unwrap().len(), 1);
+        });
+    }
+}
@@ -40,6 +40,18 @@ pub struct Device {
     mac_addrs: Vec<MacAddress>,
     ipv4_addrs: Vec<DeviceIpv4>,
     ipv6_addrs: Vec<DeviceIpv6>,
+    fcs_len: Option<u8>,
+}
+
+/// Packet counts reported by a capture device. See `DeviceSniffer::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    /// Number of packets that passed the capture filter
+    pub received: u64,
+    /// Number of packets dropped because the capture buffer was full
+    pub dropped: u64,
+    /// Number of packets dropped by the interface or its driver
+    pub iface_dropped: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -132,6 +144,13 @@ impl Device {
         &self.ipv6_addrs[..]
     }
 
+    /// Length in bytes of the frame check sequence included at the end of
+    /// captured frames, if known. For capture files, this comes from the
+    /// pcapng `if_fcslen` option.
+    pub fn fcs_length(&self) -> Option<u8> {
+        self.fcs_len
+    }
+
     pub fn is_loopback(&self) -> bool {
         (self.flags & IS_LOOPBACK) > 0
     }
@@ -231,6 +250,7 @@ impl From<pcaprs::Device> for Device {
                 .iter()
                 .map(|addr| DeviceIpv6::from(addr.clone()))
                 .collect(),
+            fcs_len: None,
         }
     }
 }
@@ -267,6 +287,7 @@ impl DeviceBuilder {
                 mac_addrs: Vec::new(),
                 ipv4_addrs: Vec::new(),
                 ipv6_addrs: Vec::new(),
+                fcs_len: None,
             },
         }
     }
@@ -296,6 +317,11 @@ impl DeviceBuilder {
         self
     }
 
+    pub fn fcs_length(&mut self, len: u8) -> &mut Self {
+        self.device.fcs_len = Some(len);
+        self
+    }
+
     pub fn loopback(&mut self, is_loopback: bool) -> &mut Self {
         if is_loopback {
             self.device.flags |= IS_LOOPBACK;
@@ -1,13 +1,25 @@
-use super::{Device, Error, LinkType, RawPacket, Session, SniffRaw, Sniffer};
+use super::{
+    CaptureStats, Device, Error, LinkType, RawPacket, Session, SniffRaw, Sniffer, Timestamp,
+};
 use async_trait::async_trait;
-use pcaprs::{AsyncCapture, Capture, Pcap, PcapConfig, TsPrecision, TsType};
+use pcaprs::{AsyncCapture, Capture, Direction, Pcap, PcapConfig, TsPrecision, TsType};
+
+#[cfg(feature = "npcap")]
+use pcaprs::{RemoteAuth, RemoteConfig, RemoteDevice, Sampling};
 
 pub type DeviceTsType = TsType;
 pub type DeviceTsPrecision = TsPrecision;
+pub type DeviceDirection = Direction;
+
+#[cfg(feature = "npcap")]
+pub type RemoteSnifferAuth = RemoteAuth;
+#[cfg(feature = "npcap")]
+pub type RemoteSnifferSampling = Sampling;
 
 pub struct DeviceSniffer {
     pcap: AsyncCapture<Pcap>,
     dev: std::sync::Arc<Device>,
+    started: Timestamp,
 }
 
 pub struct DeviceSnifferConfig {
@@ -15,12 +27,19 @@ pub struct DeviceSnifferConfig {
     device: std::sync::Arc<Device>,
 }
 
+#[cfg(feature = "npcap")]
+pub struct RemoteSnifferConfig {
+    config: RemoteConfig,
+    device: std::sync::Arc<Device>,
+}
+
 impl DeviceSniffer {
     pub fn open_raw(config: DeviceSnifferConfig) -> Result<Self, Error> {
         let DeviceSnifferConfig { config, device } = config;
         Ok(Self {
             pcap: config.activate()?.into_async()?,
             dev: device,
+            started: Timestamp::now(),
         })
     }
 
@@ -50,6 +69,37 @@ impl DeviceSniffer {
     pub fn share_device(&self) -> std::sync::Arc<Device> {
         self.dev.clone()
     }
+
+    /// The maximum number of bytes captured from each packet.
+    pub fn snaplen(&self) -> Result<u32, Error> {
+        Ok(self.pcap.snaplen()?)
+    }
+
+    /// The size of the kernel capture buffer, in bytes.
+    #[cfg(feature = "npcap")]
+    pub fn buffer_size(&self) -> Result<u32, Error> {
+        Ok(self.pcap.buffer_size()?)
+    }
+
+    /// The precision of the timestamps reported by the device.
+    pub fn timestamp_precision(&self) -> DeviceTsPrecision {
+        self.pcap.timestamp_precision()
+    }
+
+    /// The time the capture was started.
+    pub fn start_time(&self) -> Timestamp {
+        self.started
+    }
+
+    /// Packet counts since the capture was started.
+    pub fn stats(&self) -> Result<CaptureStats, Error> {
+        let stats = self.pcap.stats()?;
+        Ok(CaptureStats {
+            received: stats.received().into(),
+            dropped: stats.dropped().into(),
+            iface_dropped: stats.iface_dropped().into(),
+        })
+    }
 }
 
 #[async_trait]
@@ -94,6 +144,14 @@ impl DeviceSnifferConfig {
         DeviceSniffer::open_with_session(self, session)
     }
 
+    /// Checks the configured options for values that would be rejected
+    /// or ignored when the sniffer is opened.
+    pub fn validate(&self) -> Result<(), Error> {
+        Ok(self.config.validate()?)
+    }
+
+    /// Sets the maximum number of bytes captured from each packet. Must be
+    /// nonzero.
     pub fn snaplen(self, snaplen: u32) -> Self {
         let mut config = self;
         let _ = config.config.snaplen(snaplen);
@@ -106,12 +164,28 @@ impl DeviceSnifferConfig {
         config
     }
 
+    /// Puts a wireless adapter into monitor mode. Opening the sniffer
+    /// fails if the device does not support monitor mode; use
+    /// [`can_set_rfmon`](Self::can_set_rfmon) to check beforehand.
     pub fn rfmon_mode(self, enable: bool) -> Self {
         let mut config = self;
         let _ = config.config.rfmon_mode(enable);
         config
     }
 
+    /// Checks whether the device can be put into monitor mode.
+    pub fn can_set_rfmon(&self) -> Result<bool, Error> {
+        Ok(self.config.can_set_rfmon()?)
+    }
+
+    /// Restricts capture to packets received by, sent by, or both
+    /// received and sent by the device.
+    pub fn direction(self, direction: DeviceDirection) -> Self {
+        let mut config = self;
+        let _ = config.config.direction(direction);
+        config
+    }
+
     pub fn timeout(self, dur: std::time::Duration) -> Self {
         let mut config = self;
         let _ = config.config.timeout(dur);
@@ -124,12 +198,16 @@ impl DeviceSnifferConfig {
         config
     }
 
+    /// Delivers packets as soon as they arrive instead of waiting for the
+    /// kernel buffer to fill or the timeout to expire.
     pub fn immediate_mode(self, enable: bool) -> Self {
         let mut config = self;
         let _ = config.config.immediate_mode(enable);
         config
     }
 
+    /// Sets the size of the kernel capture buffer, in bytes. Larger buffers
+    /// reduce drops at high packet rates.
     pub fn buffer_size(self, size: u32) -> Self {
         let mut config = self;
         let _ = config.config.buffer_size(size);
@@ -142,3 +220,98 @@ impl DeviceSnifferConfig {
         config
     }
 }
+
+#[cfg(feature = "npcap")]
+impl RemoteSnifferConfig {
+    /// Creates a configuration for an rpcap source, such as
+    /// `rpcap://host:2002/eth0`.
+    pub fn create(source: &str) -> Self {
+        let mut device = crate::DeviceBuilder::new();
+        device.name(String::from(source));
+        Self {
+            config: RemoteConfig::create(source),
+            device: std::sync::Arc::new(device.into_device()),
+        }
+    }
+
+    /// Lists the devices available from an rpcap source, such as
+    /// `rpcap://host:2002/`, returning a configuration for each.
+    pub fn list(source: &str, auth: RemoteSnifferAuth) -> Result<Vec<Self>, Error> {
+        Ok(RemoteDevice::all(source, auth)?
+            .into_iter()
+            .map(|dev| Self {
+                config: dev.config(),
+                device: std::sync::Arc::new(Device::from(dev.into_device())),
+            })
+            .collect())
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn open_raw(self) -> Result<DeviceSniffer, Error> {
+        let Self { config, device } = self;
+        Ok(DeviceSniffer {
+            pcap: config.open()?.into_async()?,
+            dev: device,
+            started: Timestamp::now(),
+        })
+    }
+
+    pub fn open(self) -> Result<Sniffer<DeviceSniffer>, Error> {
+        Ok(Sniffer::new(self.open_raw()?))
+    }
+
+    pub fn open_with_session(self, session: Session) -> Result<Sniffer<DeviceSniffer>, Error> {
+        Ok(Sniffer::with_session(self.open_raw()?, session))
+    }
+
+    pub fn snaplen(self, snaplen: u32) -> Self {
+        let mut config = self;
+        let _ = config.config.snaplen(snaplen);
+        config
+    }
+
+    pub fn promiscuous_mode(self, enable: bool) -> Self {
+        let mut config = self;
+        let _ = config.config.promiscuous_mode(enable);
+        config
+    }
+
+    pub fn udp_transfer(self, enable: bool) -> Self {
+        let mut config = self;
+        let _ = config.config.udp_transfer(enable);
+        config
+    }
+
+    pub fn no_capture_rpcap(self, enable: bool) -> Self {
+        let mut config = self;
+        let _ = config.config.no_capture_rpcap(enable);
+        config
+    }
+
+    pub fn max_responsiveness(self, enable: bool) -> Self {
+        let mut config = self;
+        let _ = config.config.max_responsiveness(enable);
+        config
+    }
+
+    pub fn timeout(self, dur: std::time::Duration) -> Self {
+        let mut config = self;
+        let _ = config.config.timeout(dur);
+        config
+    }
+
+    pub fn auth(self, auth: RemoteSnifferAuth) -> Self {
+        let mut config = self;
+        let _ = config.config.auth(auth);
+        config
+    }
+
+    pub fn sampling(self, sampling: RemoteSnifferSampling) -> Self {
+        let mut config = self;
+        let _ = config.config.sampling(sampling);
+        config
+    }
+}
@@ -0,0 +1,315 @@
+use super::{AnnotatingDumper, Dump, DumpValue, Dumper, FieldValue, Packet, Pdu};
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+/// A node or field that differs between two Pdu chains. See `diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    path: String,
+    left: Option<String>,
+    right: Option<String>,
+}
+
+pub(crate) struct Entry {
+    pub(crate) path: String,
+    value: Option<String>,
+    pub(crate) field: Option<FieldValue>,
+    pub(crate) descr: Option<String>,
+    present: bool,
+}
+
+/// Flattens a dumped Pdu chain into a list of nodes and fields.
+#[derive(Default)]
+pub(crate) struct DiffCollector {
+    pub(crate) entries: Vec<Entry>,
+    scopes: Vec<(String, usize)>,
+}
+
+impl FieldDiff {
+    /// Path of the node or field, made of the names of its enclosing nodes
+    /// and its own name, separated by `.`. List items are named by their
+    /// index.
+    pub fn path(&self) -> &str {
+        &self.path[..]
+    }
+
+    /// Value in the first Pdu chain, or `None` if it has no such node or
+    /// field. Nodes have an empty value.
+    pub fn left(&self) -> Option<&str> {
+        self.left.as_deref()
+    }
+
+    /// Value in the second Pdu chain, or `None` if it has no such node or
+    /// field. Nodes have an empty value.
+    pub fn right(&self) -> Option<&str> {
+        self.right.as_deref()
+    }
+
+    /// True if the node or field is only in the second Pdu chain
+    pub fn is_added(&self) -> bool {
+        self.left.is_none()
+    }
+
+    /// True if the node or field is only in the first Pdu chain
+    pub fn is_removed(&self) -> bool {
+        self.right.is_none()
+    }
+}
+
+impl std::fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => write!(f, "{}: {} -> {}", self.path, left, right),
+            (Some(left), None) => write!(f, "-{}: {}", self.path, left),
+            (None, Some(right)) => write!(f, "+{}: {}", self.path, right),
+            (None, None) => write!(f, "{}", self.path),
+        }
+    }
+}
+
+impl DiffCollector {
+    pub(crate) fn collect<P: Pdu>(pdu: &P) -> Vec<Entry> {
+        let mut collector = Self::default();
+        match Dumper::new(&mut collector).dump_pdu(pdu) {
+            Ok(()) => {}
+            Err(e) => match e {},
+        }
+        collector.entries
+    }
+
+    /// Like `collect`, with the packet's annotations. See `PacketAnnotator`.
+    pub(crate) fn collect_packet(packet: &Packet) -> Vec<Entry> {
+        let mut collector = Self::default();
+        let res = match packet.annotator() {
+            Some(annotator) => {
+                Dumper::new(AnnotatingDumper::new(&mut collector, annotator.clone()))
+                    .dump_pdu(packet.pdu())
+            }
+            None => Dumper::new(&mut collector).dump_pdu(packet.pdu()),
+        };
+        match res {
+            Ok(()) => {}
+            Err(e) => match e {},
+        }
+        collector.entries
+    }
+
+    fn path(&mut self, name: Option<&str>) -> String {
+        let (prefix, items) = match self.scopes.last_mut() {
+            Some(scope) => scope,
+            None => return name.map(String::from).unwrap_or_default(),
+        };
+        let name = match name {
+            Some(name) => String::from(name),
+            None => {
+                *items += 1;
+                (*items - 1).to_string()
+            }
+        };
+        if prefix.is_empty() {
+            name
+        } else {
+            format!("{}.{}", prefix, name)
+        }
+    }
+
+    fn add(
+        &mut self,
+        name: Option<&str>,
+        value: Option<DumpValue<'_>>,
+        descr: Option<&str>,
+    ) -> String {
+        let path = self.path(name);
+        self.entries.push(Entry {
+            path: path.clone(),
+            value: value.as_ref().map(DumpValue::to_string),
+            field: value.map(FieldValue::from),
+            descr: descr.map(String::from),
+            present: false,
+        });
+        path
+    }
+
+    fn open(&mut self, name: Option<&str>, descr: Option<&str>) {
+        let path = self.add(name, None, descr);
+        self.scopes.push((path, 0));
+    }
+
+    fn close(&mut self) {
+        let _ = self.scopes.pop();
+    }
+}
+
+impl Dump for DiffCollector {
+    type Error = Infallible;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        self.scopes.clear();
+        self.scopes.push((String::new(), 0));
+        Ok(())
+    }
+
+    fn end_packet(&mut self) {
+        self.scopes.clear();
+    }
+
+    fn start_node(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(Some(name), descr);
+        Ok(())
+    }
+
+    fn end_node(&mut self) {
+        self.close();
+    }
+
+    fn add_field(
+        &mut self,
+        name: &str,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let _ = self.add(Some(name), Some(value), descr);
+        Ok(())
+    }
+
+    fn add_info(&mut self, name: &str, descr: &str) -> Result<(), Self::Error> {
+        let _ = self.add(Some(name), Some(DumpValue::Text(descr)), None);
+        Ok(())
+    }
+
+    fn start_list(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(Some(name), descr);
+        Ok(())
+    }
+
+    fn end_list(&mut self) {
+        self.close();
+    }
+
+    fn add_list_item(
+        &mut self,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let _ = self.add(None, Some(value), descr);
+        Ok(())
+    }
+
+    fn start_list_node(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(None, descr);
+        Ok(())
+    }
+
+    fn end_list_node(&mut self) {
+        self.close();
+    }
+
+    fn start_list_sublist(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(None, descr);
+        Ok(())
+    }
+
+    fn end_list_sublist(&mut self) {
+        self.close();
+    }
+}
+
+/// Compares two Pdus, and all of their inner Pdus, field by field.
+///
+/// Both Pdu chains are dumped (see `Dumper::dump_pdu`), and nodes and
+/// fields are matched up by path. When a path occurs more than once, such
+/// as when the same protocol appears at two layers, occurrences are matched
+/// in order. Fields are compared by their dumped value; descriptions are
+/// ignored. The returned differences are in the order they appear in `a`,
+/// followed by anything only in `b`.
+///
+/// This is mainly intended for tests that check a re-serialized packet
+/// against the original capture.
+pub fn diff<A: Pdu, B: Pdu>(a: &A, b: &B) -> Vec<FieldDiff> {
+    let left = DiffCollector::collect(a);
+    let mut right = DiffCollector::collect(b);
+
+    let mut index: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, entry) in right.iter().enumerate().rev() {
+        index.entry(&entry.path[..]).or_default().push(i);
+    }
+    let mut matches = Vec::with_capacity(left.len());
+    for entry in left.iter() {
+        matches.push(index.get_mut(&entry.path[..]).and_then(|idxs| idxs.pop()));
+    }
+    drop(index);
+
+    let mut diffs = Vec::new();
+    for (entry, matched) in left.into_iter().zip(matches) {
+        match matched {
+            Some(i) => {
+                let other = &mut right[i];
+                other.present = true;
+                if entry.value != other.value {
+                    diffs.push(FieldDiff {
+                        path: entry.path,
+                        left: Some(entry.value.unwrap_or_default()),
+                        right: Some(other.value.take().unwrap_or_default()),
+                    });
+                }
+            }
+            None => diffs.push(FieldDiff {
+                path: entry.path,
+                left: Some(entry.value.unwrap_or_default()),
+                right: None,
+            }),
+        }
+    }
+    for entry in right.into_iter().filter(|entry| !entry.present) {
+        diffs.push(FieldDiff {
+            path: entry.path,
+            left: None,
+            right: Some(entry.value.unwrap_or_default()),
+        });
+    }
+    diffs
+}
+
+/// Returns the dumped value of the first field at `path` in a Pdu chain.
+/// Paths are the same as in `FieldDiff::path`.
+pub(crate) fn field_value<P: Pdu>(pdu: &P, path: &str) -> Option<String> {
+    DiffCollector::collect(pdu)
+        .into_iter()
+        .find(|entry| entry.path == path)
+        .and_then(|entry| entry.value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PduExt, RawPdu};
+
+    #[test]
+    fn pdu_diff() {
+        let layered = || {
+            let mut pdu = RawPdu::new(vec![1, 2]);
+            pdu.set_inner_pdu(RawPdu::new(vec![3]));
+            pdu
+        };
+        let a = layered();
+        let b = RawPdu::new(vec![1, 2]);
+        assert!(diff(&a, &layered()).is_empty());
+        assert!(diff(&b, &b.clone()).is_empty());
+
+        let diffs = diff(&a, &b);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs.iter().all(FieldDiff::is_removed));
+        assert_eq!(diffs[1].to_string(), "-Raw Bytes.Data: 03");
+
+        let diffs = diff(&b, &RawPdu::new(vec![1, 3]));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path(), "Raw Bytes.Data");
+        assert_eq!(diffs[0].left(), Some("0102"));
+        assert_eq!(diffs[0].right(), Some("0103"));
+        assert_eq!(diffs[0].to_string(), "Raw Bytes.Data: 0102 -> 0103");
+
+        assert_eq!(field_value(&a, "Raw Bytes.Data").as_deref(), Some("0102"));
+        assert_eq!(field_value(&a, "Raw Bytes"), None);
+        assert_eq!(field_value(&a, "Bogus"), None);
+    }
+}
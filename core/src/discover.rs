@@ -0,0 +1,293 @@
+use super::{
+    Device, DeviceInjector, DeviceSnifferConfig, Error, Ipv4Address, Ipv4Subnet, Ipv6Address,
+    Ipv6Subnet, LinkType, MacAddress, SniffRaw,
+};
+use sniffle_utils::checksum::{Ipv6PseudoHeader, U16OnesComplement};
+use std::io::Write;
+use std::time::Duration;
+
+/// Subnets are limited to 65536 addresses, since each address is probed
+const MAX_PREFIX_BITS: u32 = 16;
+
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const IP_PROTO_ICMPV6: u8 = 58;
+const ND_NEIGHBOR_SOLICIT: u8 = 135;
+const ND_NEIGHBOR_ADVERT: u8 = 136;
+
+/// Discovers the hosts in `subnet` on the local link of an Ethernet device,
+/// by broadcasting an ARP request for each address and listening for
+/// replies until `timeout` elapses.
+///
+/// Returns the address and MAC address of each host that replied, sorted by
+/// address. Requests are sent from the device's address in `subnet`, or as
+/// ARP probes if the device has none.
+pub async fn discover_ipv4(
+    device: Device,
+    subnet: Ipv4Subnet,
+    timeout: Duration,
+) -> Result<Vec<(Ipv4Address, MacAddress)>, Error> {
+    check_prefix(32 - subnet.prefix_len())?;
+    let src_mac = device_mac(&device)?;
+    let src_ip = device
+        .ipv4_addresses()
+        .iter()
+        .map(|ipv4| *ipv4.address())
+        .find(|addr| subnet.contains(addr))
+        .unwrap_or_default();
+    let requests = ipv4_hosts(&subnet)
+        .filter(|addr| *addr != src_ip)
+        .map(|addr| arp_request(src_mac, src_ip, addr))
+        .collect();
+    let mut found = exchange(device, requests, timeout, |frame| {
+        parse_arp_reply(frame).filter(|(addr, _)| subnet.contains(addr))
+    })
+    .await?;
+    found.sort();
+    found.dedup_by_key(|(addr, _)| *addr);
+    Ok(found)
+}
+
+/// Discovers the hosts in `subnet` on the local link of an Ethernet device,
+/// by sending an ICMPv6 neighbor solicitation for each address and listening
+/// for neighbor advertisements until `timeout` elapses.
+///
+/// Returns the address and MAC address of each host that replied, sorted by
+/// address. Solicitations are sent from the device's link-local address, or
+/// from the link-local address derived from its MAC address.
+pub async fn discover_ipv6(
+    device: Device,
+    subnet: Ipv6Subnet,
+    timeout: Duration,
+) -> Result<Vec<(Ipv6Address, MacAddress)>, Error> {
+    check_prefix(128 - subnet.prefix_len())?;
+    let src_mac = device_mac(&device)?;
+    let src_ip = device
+        .ipv6_addresses()
+        .iter()
+        .map(|ipv6| *ipv6.address())
+        .find(|addr| addr.is_link_local())
+        .unwrap_or_else(|| src_mac.to_ipv6_link_local());
+    let requests = subnet
+        .iter()
+        .filter(|addr| *addr != src_ip)
+        .map(|addr| neighbor_solicit(src_mac, src_ip, addr))
+        .collect();
+    let mut found = exchange(device, requests, timeout, |frame| {
+        parse_neighbor_advert(frame).filter(|(addr, _)| subnet.contains(addr))
+    })
+    .await?;
+    found.sort();
+    found.dedup_by_key(|(addr, _)| *addr);
+    Ok(found)
+}
+
+fn invalid_input(msg: &'static str) -> Error {
+    Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg))
+}
+
+fn check_prefix(host_bits: u32) -> Result<(), Error> {
+    if host_bits > MAX_PREFIX_BITS {
+        Err(invalid_input("subnet is too large to discover"))
+    } else {
+        Ok(())
+    }
+}
+
+fn device_mac(device: &Device) -> Result<MacAddress, Error> {
+    device
+        .mac_addresses()
+        .first()
+        .copied()
+        .ok_or_else(|| invalid_input("device has no MAC address"))
+}
+
+/// The usable host addresses of an IPv4 subnet, excluding the broadcast
+/// address
+fn ipv4_hosts(subnet: &Ipv4Subnet) -> impl Iterator<Item = Ipv4Address> + '_ {
+    let broadcast = subnet.last();
+    let point_to_point = subnet.prefix_len() >= 31;
+    subnet
+        .iter()
+        .filter(move |addr| point_to_point || *addr != broadcast)
+}
+
+/// Sends `requests` on `device` and collects the replies recognized by
+/// `parse` until `timeout` elapses.
+async fn exchange<T, F>(
+    device: Device,
+    requests: Vec<Vec<u8>>,
+    timeout: Duration,
+    parse: F,
+) -> Result<Vec<T>, Error>
+where
+    F: Fn(&[u8]) -> Option<T>,
+{
+    let mut sniffer = DeviceSnifferConfig::create(device.clone())
+        .immediate_mode(true)
+        .open_raw()?;
+    let mut injector = DeviceInjector::new(device)?;
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut found = Vec::new();
+
+    for request in requests.iter() {
+        injector.inject_raw(&request[..]).await?;
+        // Replies to earlier requests may arrive while still sending
+        while let Ok(res) = tokio::time::timeout(Duration::ZERO, sniffer.sniff_raw()).await {
+            match res? {
+                Some(pkt) if pkt.datalink() == LinkType::ETHERNET => {
+                    found.extend(parse(pkt.data()))
+                }
+                Some(_) => return Err(Error::UnknownLinkType),
+                None => return Ok(found),
+            }
+        }
+    }
+
+    while let Ok(res) = tokio::time::timeout_at(deadline, sniffer.sniff_raw()).await {
+        match res? {
+            Some(pkt) if pkt.datalink() == LinkType::ETHERNET => found.extend(parse(pkt.data())),
+            Some(_) => return Err(Error::UnknownLinkType),
+            None => break,
+        }
+    }
+    Ok(found)
+}
+
+fn ethernet_header(frame: &mut Vec<u8>, dst: MacAddress, src: MacAddress, ethertype: u16) {
+    frame.extend_from_slice(&<[u8; 6]>::from(dst));
+    frame.extend_from_slice(&<[u8; 6]>::from(src));
+    frame.extend_from_slice(&ethertype.to_be_bytes());
+}
+
+fn arp_request(src_mac: MacAddress, src_ip: Ipv4Address, target: Ipv4Address) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(42);
+    ethernet_header(&mut frame, MacAddress::BROADCAST, src_mac, ETHERTYPE_ARP);
+    // Ethernet, IPv4, address lengths, request
+    frame.extend_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x01]);
+    frame.extend_from_slice(&<[u8; 6]>::from(src_mac));
+    frame.extend_from_slice(&src_ip[..]);
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&target[..]);
+    frame
+}
+
+fn parse_arp_reply(frame: &[u8]) -> Option<(Ipv4Address, MacAddress)> {
+    if frame.len() < 42 || frame[12..14] != ETHERTYPE_ARP.to_be_bytes() {
+        return None;
+    }
+    let arp = &frame[14..];
+    if arp[..8] != [0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x02] {
+        return None;
+    }
+    let mac = <[u8; 6]>::try_from(&arp[8..14]).ok()?;
+    let addr = <[u8; 4]>::try_from(&arp[14..18]).ok()?;
+    Some((addr.into(), mac.into()))
+}
+
+fn neighbor_solicit(src_mac: MacAddress, src_ip: Ipv6Address, target: Ipv6Address) -> Vec<u8> {
+    // Solicited-node multicast address, ff02::1:ffXX:XXXX
+    let mut dst_ip = [0u8; 16];
+    dst_ip[..2].copy_from_slice(&[0xff, 0x02]);
+    dst_ip[11..13].copy_from_slice(&[0x01, 0xff]);
+    dst_ip[13..].copy_from_slice(&target[13..]);
+    let mut dst_mac = [0x33, 0x33, 0, 0, 0, 0];
+    dst_mac[2..].copy_from_slice(&dst_ip[12..]);
+
+    let mut icmp = vec![ND_NEIGHBOR_SOLICIT, 0, 0, 0, 0, 0, 0, 0];
+    icmp.extend_from_slice(&target[..]);
+    // Source link-layer address option
+    icmp.extend_from_slice(&[1, 1]);
+    icmp.extend_from_slice(&<[u8; 6]>::from(src_mac));
+    let checksum = icmpv6_checksum(src_ip, dst_ip.into(), &icmp[..]);
+    icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut frame = Vec::with_capacity(54 + icmp.len());
+    ethernet_header(&mut frame, dst_mac.into(), src_mac, ETHERTYPE_IPV6);
+    frame.extend_from_slice(&[0x60, 0, 0, 0]);
+    frame.extend_from_slice(&(icmp.len() as u16).to_be_bytes());
+    // Neighbor discovery requires a hop limit of 255
+    frame.extend_from_slice(&[IP_PROTO_ICMPV6, 255]);
+    frame.extend_from_slice(&src_ip[..]);
+    frame.extend_from_slice(&dst_ip);
+    frame.extend_from_slice(&icmp[..]);
+    frame
+}
+
+fn parse_neighbor_advert(frame: &[u8]) -> Option<(Ipv6Address, MacAddress)> {
+    if frame.len() < 78
+        || frame[12..14] != ETHERTYPE_IPV6.to_be_bytes()
+        || frame[20] != IP_PROTO_ICMPV6
+        || frame[54] != ND_NEIGHBOR_ADVERT
+    {
+        return None;
+    }
+    let icmp = &frame[54..];
+    let addr = <[u8; 16]>::try_from(&icmp[8..24]).ok()?;
+    // Prefer the target link-layer address option over the frame's source
+    let mut mac = <[u8; 6]>::try_from(&frame[6..12]).ok()?;
+    let mut opts = &icmp[24..];
+    while opts.len() >= 8 && opts[1] != 0 {
+        let len = opts[1] as usize * 8;
+        if opts[0] == 2 {
+            mac.copy_from_slice(&opts[2..8]);
+        }
+        opts = opts.get(len..)?;
+    }
+    Some((addr.into(), mac.into()))
+}
+
+fn icmpv6_checksum(src: Ipv6Address, dst: Ipv6Address, icmp: &[u8]) -> u16 {
+    let pseudo_header = Ipv6PseudoHeader { src, dst };
+    let mut acc =
+        U16OnesComplement::with_pseudo_header(&pseudo_header, IP_PROTO_ICMPV6, icmp.len());
+    let _ = acc.write_all(icmp);
+    acc.checksum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn discovery_frames() {
+        let mac = MacAddress::new([0x02, 0, 0, 0, 0, 0x01]);
+        let peer = MacAddress::new([0x02, 0, 0, 0, 0, 0x02]);
+
+        let mut reply = arp_request(
+            peer,
+            Ipv4Address::new([192, 168, 0, 2]),
+            Ipv4Address::new([192, 168, 0, 1]),
+        );
+        assert_eq!(reply.len(), 42);
+        assert_eq!(parse_arp_reply(&reply[..]), None);
+        reply[21] = 0x02;
+        assert_eq!(
+            parse_arp_reply(&reply[..]),
+            Some((Ipv4Address::new([192, 168, 0, 2]), peer))
+        );
+
+        let target = peer.to_ipv6_link_local();
+        let solicit = neighbor_solicit(mac, mac.to_ipv6_link_local(), target);
+        assert_eq!(&solicit[..6], &[0x33, 0x33, 0xff, 0x00, 0x00, 0x02]);
+        let addr = |bytes: &[u8]| Ipv6Address::from(<[u8; 16]>::try_from(bytes).unwrap());
+        assert_eq!(
+            icmpv6_checksum(
+                addr(&solicit[22..38]),
+                addr(&solicit[38..54]),
+                &solicit[54..]
+            ),
+            0
+        );
+
+        let mut advert = solicit.clone();
+        advert[54] = ND_NEIGHBOR_ADVERT;
+        advert[78] = 2;
+        advert[80..86].copy_from_slice(&<[u8; 6]>::from(peer));
+        assert_eq!(parse_neighbor_advert(&advert[..]), Some((target, peer)));
+
+        let subnet = Ipv4Subnet::new(Ipv4Address::new([10, 0, 0, 0]), 30);
+        assert_eq!(ipv4_hosts(&subnet).count(), 2);
+        assert!(check_prefix(17).is_err());
+    }
+}
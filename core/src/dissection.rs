@@ -1,14 +1,158 @@
-use super::{AnyPdu, Pdu, RawPdu, Session, TempPdu};
-use sniffle_ende::decode::Decode;
+use super::{AnyPdu, ErrorPdu, Pdu, PduExt, PduType, RawPdu, Session, TempPdu};
+use sniffle_ende::decode::{Decode, DecodeBe, DecodeLe};
 use sniffle_ende::nom::{self, combinator::map, Parser};
 use std::marker::PhantomData;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Priority(pub i32);
 
+/// Session option that chooses what happens when a dissector in a
+/// dissector table fails. Register it with `Session::register` or
+/// `SessionBuilder::state`.
+///
+/// In `Strict` mode, the default, a dissector returning `nom::Err::Failure`
+/// aborts dissection of the whole packet. In `Tolerant` mode, the failed
+/// layer and everything after it is kept as an `ErrorPdu`, recording the
+/// reason and offset of the failure, while the outer layers remain
+/// available.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DissectMode {
+    #[default]
+    Strict,
+    Tolerant,
+}
+
 pub use sniffle_ende::decode::DResult;
 pub use sniffle_ende::decode::DecodeError as DissectError;
 
+/// Result of a single step of decoding with a `DecodeCursor`. Unlike
+/// `DResult`, the remaining input is tracked by the cursor.
+pub type DecodeResult<'a, T> = Result<T, nom::Err<DissectError<'a>>>;
+
+/// Decodes values one after another from a buffer, without nom
+/// combinators.
+///
+/// The cursor reports errors the same way as nom parsers built from the
+/// `Decode` traits: running out of input is `nom::Err::Incomplete`, and
+/// anything else is `nom::Err::Error`. `decode_with` turns a function using
+/// a cursor into a nom parser, and `DecodeCursor::parse` runs a nom parser
+/// on the cursor, so the two styles can be mixed where needed.
+///
+/// ```ignore
+/// fn dissect_header(buf: &[u8]) -> DResult<'_, Header> {
+///     decode_with(|cur| {
+///         let kind: u8 = cur.decode()?;
+///         let len: u16 = cur.decode_be()?;
+///         let data = Vec::from(cur.take(len.into())?);
+///         Ok(Header { kind, data })
+///     })(buf)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeCursor<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> DecodeCursor<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// The bytes that haven't been decoded yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn decode<D: Decode>(&mut self) -> DecodeResult<'a, D> {
+        self.parse(D::decode)
+    }
+
+    pub fn decode_be<D: DecodeBe>(&mut self) -> DecodeResult<'a, D> {
+        self.parse(D::decode_be)
+    }
+
+    pub fn decode_le<D: DecodeLe>(&mut self) -> DecodeResult<'a, D> {
+        self.parse(D::decode_le)
+    }
+
+    /// Runs a nom parser on the remaining bytes.
+    pub fn parse<O, P: Parser<&'a [u8], O, DissectError<'a>>>(
+        &mut self,
+        mut parser: P,
+    ) -> DecodeResult<'a, O> {
+        let (rem, value) = parser.parse(self.buf)?;
+        self.buf = rem;
+        Ok(value)
+    }
+
+    /// Takes the next `len` bytes.
+    pub fn take(&mut self, len: usize) -> DecodeResult<'a, &'a [u8]> {
+        match self.buf.len().checked_sub(len) {
+            Some(_) => {
+                let (data, rem) = self.buf.split_at(len);
+                self.buf = rem;
+                Ok(data)
+            }
+            None => Err(nom::Err::Incomplete(nom::Needed::new(len - self.buf.len()))),
+        }
+    }
+
+    /// Takes all of the remaining bytes.
+    pub fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.buf)
+    }
+
+    /// Takes the next `len` bytes as a separate cursor, such as for the
+    /// body of a length-value field.
+    pub fn sub(&mut self, len: usize) -> DecodeResult<'a, DecodeCursor<'a>> {
+        self.take(len).map(DecodeCursor::new)
+    }
+
+    /// Fails unless all of the bytes have been decoded.
+    pub fn finish(&self) -> DecodeResult<'a, ()> {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            Err(nom::Err::Error(DissectError::Malformed))
+        }
+    }
+}
+
+/// Adapts a function that decodes with a `DecodeCursor` into a nom parser.
+pub fn decode_with<'a, T, F>(mut f: F) -> impl FnMut(&'a [u8]) -> DResult<'a, T>
+where
+    F: FnMut(&mut DecodeCursor<'a>) -> DecodeResult<'a, T>,
+{
+    move |buf| {
+        let mut cur = DecodeCursor::new(buf);
+        let value = f(&mut cur)?;
+        Ok((cur.remaining(), value))
+    }
+}
+
+/// A nom parser for a `Decode` type.
+pub fn decode_parser<'a, D: Decode>() -> impl FnMut(&'a [u8]) -> DResult<'a, D> {
+    D::decode
+}
+
+/// A nom parser for a `DecodeBe` type.
+pub fn decode_be_parser<'a, D: DecodeBe>() -> impl FnMut(&'a [u8]) -> DResult<'a, D> {
+    D::decode_be
+}
+
+/// A nom parser for a `DecodeLe` type.
+pub fn decode_le_parser<'a, D: DecodeLe>() -> impl FnMut(&'a [u8]) -> DResult<'a, D> {
+    D::decode_le
+}
+
 pub trait Dissect: Pdu {
     fn dissect<'a>(
         buf: &'a [u8],
@@ -42,13 +186,17 @@ pub trait Dissector {
     ) -> DResult<'a, Self::Out>;
 }
 
-pub struct AnyDissector(Box<dyn Dissector<Out = AnyPdu> + Send + Sync + 'static>);
+pub struct AnyDissector {
+    dissector: Box<dyn Dissector<Out = AnyPdu> + Send + Sync + 'static>,
+    pdu_type: PduType,
+    pdu_type_name: &'static str,
+}
 
 pub struct DissectorTableParser<'a, T: DissectorTable> {
-    table: Option<&'a T>,
-    param: &'a T::Param,
+    dissectors: &'a [AnyDissector],
     session: &'a Session,
     parent: Option<TempPdu<'a>>,
+    _marker: PhantomData<fn(&T)>,
 }
 
 pub trait DissectorTable: Default {
@@ -63,6 +211,15 @@ pub trait DissectorTable: Default {
 
     fn find(&self, param: &Self::Param) -> Option<&[AnyDissector]>;
 
+    fn load_all<I>(&mut self, dissectors: I)
+    where
+        I: IntoIterator<Item = (Self::Param, Priority, AnyDissector)>,
+    {
+        for (param, priority, dissector) in dissectors {
+            self.load(param, priority, dissector);
+        }
+    }
+
     fn dissector<'a>(
         &'a self,
         param: &'a Self::Param,
@@ -70,10 +227,10 @@ pub trait DissectorTable: Default {
         parent: Option<TempPdu<'a>>,
     ) -> DissectorTableParser<'a, Self> {
         DissectorTableParser {
-            table: Some(self),
-            param,
+            dissectors: self.find(param).unwrap_or(&[]),
             session,
             parent,
+            _marker: PhantomData,
         }
     }
 
@@ -95,14 +252,53 @@ pub trait DissectorTable: Default {
         parent: Option<TempPdu<'_>>,
     ) -> DResult<'a, AnyPdu> {
         self.dissector(param, session, parent)
-            .or(map(RawPdu::decode, AnyPdu::new))
+            .or(map(RawPdu::dissector(session, None), AnyPdu::new))
             .parse(buffer)
     }
 }
 
+/// A dissector table keyed by name, such as an ALPN protocol ID, a MIME
+/// type or a DNS service name. Tables declared with
+/// `dissector_table!(Name, str)` implement this trait, which allows
+/// dispatching on a borrowed `&str` without allocating a `String` for the
+/// lookup.
+///
+/// Keys are matched exactly, so callers dispatching on case-insensitive
+/// names should normalize them first, both when loading and when looking
+/// up.
+pub trait StrDissectorTable: DissectorTable<Param = String> {
+    fn find_str(&self, name: &str) -> Option<&[AnyDissector]>;
+
+    fn str_dissector<'a>(
+        &'a self,
+        name: &str,
+        session: &'a Session,
+        parent: Option<TempPdu<'a>>,
+    ) -> DissectorTableParser<'a, Self> {
+        DissectorTableParser {
+            dissectors: self.find_str(name).unwrap_or(&[]),
+            session,
+            parent,
+            _marker: PhantomData,
+        }
+    }
+
+    fn dissect_str<'a>(
+        &self,
+        name: &str,
+        buffer: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, AnyPdu> {
+        self.str_dissector(name, session, parent).parse(buffer)
+    }
+}
+
 impl<'a, 'b, D: Dissect> Parser<&'a [u8], D, DissectError<'a>> for DissectParser<'b, D> {
     fn parse(&mut self, input: &'a [u8]) -> DResult<'a, D> {
-        D::dissect(input, self.session, self.parent.clone())
+        let (rem, mut pdu) = D::dissect(input, self.session, self.parent.clone())?;
+        record_bytes(&mut pdu, self.session, input, rem);
+        Ok((rem, pdu))
     }
 }
 
@@ -110,34 +306,67 @@ impl<'a, 'b, T: DissectorTable> Parser<&'a [u8], AnyPdu, DissectError<'a>>
     for DissectorTableParser<'b, T>
 {
     fn parse(&mut self, input: &'a [u8]) -> DResult<'a, AnyPdu> {
-        if let Some(table) = self.table {
-            for dissector in table.find(self.param).unwrap_or(&[]) {
-                match Dissector::dissect(dissector, input, self.session, self.parent.clone()) {
-                    Ok((buf, pdu)) => {
-                        return Ok((buf, pdu));
-                    }
-                    Err(nom::Err::Failure(e)) => {
-                        return Err(nom::Err::Failure(e));
-                    }
-                    _ => {}
+        let mut needed = None;
+        for dissector in self.dissectors {
+            match Dissector::dissect(dissector, input, self.session, self.parent.clone()) {
+                Ok((buf, mut pdu)) => {
+                    record_bytes(&mut pdu, self.session, input, buf);
+                    return Ok((buf, pdu));
+                }
+                Err(e @ nom::Err::Failure(_))
+                    if self.session.dissect_mode() == DissectMode::Tolerant =>
+                {
+                    let pdu = ErrorPdu::from_error(&e, input);
+                    return Ok((&input[input.len()..], self.session.pool().pdu(pdu)));
+                }
+                Err(nom::Err::Failure(e)) => {
+                    return Err(nom::Err::Failure(e));
                 }
+                Err(nom::Err::Incomplete(n)) => {
+                    needed.get_or_insert(n);
+                }
+                _ => {}
             }
         }
+        // A layer cut short by the snaplen is kept as raw bytes, marked as
+        // truncated, rather than being treated as malformed
+        let truncated = self.session.truncated_len();
+        if let (Some(needed), true) = (needed, truncated > 0) {
+            let mut raw = RawPdu::new(Vec::from(input));
+            raw.base_pdu_mut().set_truncated_len(match needed {
+                nom::Needed::Size(n) => n.get().min(truncated),
+                nom::Needed::Unknown => truncated,
+            });
+            return Ok((&input[input.len()..], self.session.pool().pdu(raw)));
+        }
         Err(nom::Err::Error(DissectError::Malformed))
     }
 }
 
+/// Records the bytes consumed by a dissector as the dissected bytes of
+/// `pdu`, if they are part of the packet buffer.
+fn record_bytes<P: Pdu>(pdu: &mut P, session: &Session, input: &[u8], rem: &[u8]) {
+    let consumed = &input[..input.len().saturating_sub(rem.len())];
+    if let Some(bytes) = session.shared_packet_bytes(consumed) {
+        pdu.set_dissected_bytes(bytes);
+    }
+}
+
 impl<'a, T: DissectorTable> DissectorTableParser<'a, T> {
     pub fn null_parser(
-        param: &'a T::Param,
+        _param: &'a T::Param,
         session: &'a Session,
         parent: Option<TempPdu<'a>>,
     ) -> Self {
+        Self::empty(session, parent)
+    }
+
+    pub(crate) fn empty(session: &'a Session, parent: Option<TempPdu<'a>>) -> Self {
         Self {
-            table: None,
-            param,
+            dissectors: &[],
             session,
             parent,
+            _marker: PhantomData,
         }
     }
 }
@@ -151,7 +380,7 @@ impl Dissector for AnyDissector {
         session: &Session,
         parent: Option<TempPdu<'_>>,
     ) -> DResult<'a, Self::Out> {
-        self.0.dissect(buffer, session, parent)
+        self.dissector.dissect(buffer, session, parent)
     }
 }
 
@@ -185,18 +414,48 @@ impl<D: Dissector> Dissector for DissectorAdapter<D> {
     ) -> DResult<'a, Self::Out> {
         self.0
             .dissect(buffer, session, parent)
-            .map(|(rem, pdu)| (rem, AnyPdu::new(pdu)))
+            .map(|(rem, pdu)| (rem, session.pool().pdu(pdu)))
     }
 }
 
 impl AnyDissector {
     pub fn new<D: Dissector + Send + Sync + 'static>(dissector: D) -> Self {
-        Self(Box::new(DissectorAdapter(dissector)))
+        // Avoid double wrapping, which would also lose the Pdu type
+        let mut dissector = Some(dissector);
+        if let Some(any) = (&mut dissector as &mut dyn std::any::Any)
+            .downcast_mut::<Option<AnyDissector>>()
+            .and_then(Option::take)
+        {
+            return any;
+        }
+        let dissector = dissector.unwrap();
+        Self {
+            dissector: Box::new(DissectorAdapter(dissector)),
+            pdu_type: PduType::of::<D::Out>(),
+            pdu_type_name: std::any::type_name::<D::Out>(),
+        }
+    }
+
+    /// Returns the type of Pdu produced by the dissector.
+    pub fn pdu_type(&self) -> PduType {
+        self.pdu_type
+    }
+
+    /// Returns the name of the type of Pdu produced by the dissector.
+    pub fn pdu_type_name(&self) -> &'static str {
+        self.pdu_type_name
     }
 }
 
 #[macro_export]
 macro_rules! dissector_table {
+    (__impl_str, $name:ident) => {
+        impl $crate::StrDissectorTable for $name {
+            fn find_str(&self, name: &str) -> Option<&[$crate::AnyDissector]> {
+                self.0.get(name).map(|table| &table.1[..])
+            }
+        }
+    };
     ($name:ident) => {
         dissector_table!(__priv_decl, $name, ());
         dissector_table!(__impl, $name, ());
@@ -205,6 +464,16 @@ macro_rules! dissector_table {
         dissector_table!(__pub_decl, $name, ());
         dissector_table!(__impl, $name, ());
     };
+    ($name:ident, str) => {
+        dissector_table!(__priv_decl, $name, ::std::string::String);
+        dissector_table!(__impl, $name, ::std::string::String);
+        dissector_table!(__impl_str, $name);
+    };
+    (pub $name:ident, str) => {
+        dissector_table!(__pub_decl, $name, ::std::string::String);
+        dissector_table!(__impl, $name, ::std::string::String);
+        dissector_table!(__impl_str, $name);
+    };
     ($name:ident, $param:ty) => {
         dissector_table!(__priv_decl, $name, $param);
         dissector_table!(__impl, $name, $param);
@@ -249,10 +518,40 @@ macro_rules! dissector_table {
         );
     };
     (__impl, $name:ident, ()) => {
+        #[allow(dead_code)]
         impl $name {
             pub fn new() -> Self {
                 Self(::std::vec::Vec::new(), ::std::vec::Vec::new())
             }
+
+            /// Iterates over the loaded dissectors in priority order.
+            pub fn entries(
+                &self,
+            ) -> impl ::std::iter::Iterator<Item = ($crate::Priority, &$crate::AnyDissector)> {
+                self.0.iter().copied().zip(self.1.iter())
+            }
+
+            /// Removes dissectors for which `f` returns false.
+            pub fn retain<F>(&mut self, mut f: F)
+            where
+                F: FnMut($crate::Priority, &$crate::AnyDissector) -> bool,
+            {
+                let mut idx = 0;
+                while idx < self.1.len() {
+                    if f(self.0[idx], &self.1[idx]) {
+                        idx += 1;
+                    } else {
+                        let _ = self.0.remove(idx);
+                        let _ = self.1.remove(idx);
+                    }
+                }
+            }
+
+            /// Removes all dissectors.
+            pub fn clear(&mut self) {
+                self.0.clear();
+                self.1.clear();
+            }
         }
 
         impl ::std::default::Default for $name {
@@ -285,10 +584,66 @@ macro_rules! dissector_table {
         }
     };
     (__impl, $name:ident, $param:ty) => {
+        #[allow(dead_code)]
         impl $name {
             pub fn new() -> Self {
                 Self(::std::collections::HashMap::new())
             }
+
+            /// Iterates over the parameters that have dissectors loaded.
+            pub fn params(&self) -> impl ::std::iter::Iterator<Item = &$param> {
+                self.0.keys()
+            }
+
+            /// Iterates over the dissectors loaded for `param` in priority
+            /// order.
+            pub fn entries<'a, Q>(
+                &'a self,
+                param: &Q,
+            ) -> impl ::std::iter::Iterator<Item = ($crate::Priority, &'a $crate::AnyDissector)>
+            where
+                $param: ::std::borrow::Borrow<Q>,
+                Q: ::std::hash::Hash + ::std::cmp::Eq + ?Sized,
+            {
+                self.0
+                    .get(param)
+                    .into_iter()
+                    .flat_map(|table| table.0.iter().copied().zip(table.1.iter()))
+            }
+
+            /// Removes all dissectors loaded for `param`, returning how many
+            /// were removed.
+            pub fn unload<Q>(&mut self, param: &Q) -> usize
+            where
+                $param: ::std::borrow::Borrow<Q>,
+                Q: ::std::hash::Hash + ::std::cmp::Eq + ?Sized,
+            {
+                self.0.remove(param).map(|table| table.1.len()).unwrap_or(0)
+            }
+
+            /// Removes dissectors for which `f` returns false.
+            pub fn retain<F>(&mut self, mut f: F)
+            where
+                F: FnMut(&$param, $crate::Priority, &$crate::AnyDissector) -> bool,
+            {
+                for (param, table) in self.0.iter_mut() {
+                    let mut idx = 0;
+                    while idx < table.1.len() {
+                        if f(param, table.0[idx], &table.1[idx]) {
+                            idx += 1;
+                        } else {
+                            let _ = table.0.remove(idx);
+                            let _ = table.1.remove(idx);
+                        }
+                    }
+                }
+                self.0.retain(|_, table| !table.1.is_empty());
+            }
+
+            /// Removes all dissectors.
+            pub fn clear(&mut self) {
+                self.0.clear();
+            }
         }
 
         impl ::std::default::Default for $name {
@@ -328,3 +683,158 @@ macro_rules! dissector_table {
         }
     };
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PduExt;
+    use sniffle_ende::decode::Decode;
+
+    dissector_table!(TestTable, u8);
+    dissector_table!(AlpnTable, str);
+
+    fn raw<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, RawPdu> {
+        RawPdu::decode(buf)
+    }
+
+    #[test]
+    fn table_introspection() {
+        let mut table = TestTable::new();
+        table.load_all([
+            (1, Priority(0), AnyDissector::new(raw)),
+            (1, Priority(5), AnyDissector::new(raw)),
+            (2, Priority(0), AnyDissector::new(raw)),
+        ]);
+
+        let mut params: Vec<_> = table.params().copied().collect();
+        params.sort();
+        assert_eq!(params, [1, 2]);
+
+        let entries: Vec<_> = table.entries(&1).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0 .0, 5);
+        assert_eq!(entries[0].1.pdu_type(), PduType::of::<RawPdu>());
+
+        table.retain(|_, pri, _| pri.0 == 0);
+        assert_eq!(table.entries(&1).count(), 1);
+        assert_eq!(table.unload(&2), 1);
+        assert_eq!(table.params().count(), 1);
+    }
+
+    fn outer<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, RawPdu> {
+        let mut pdu = RawPdu::new(Vec::from(&buf[..2]));
+        let (rem, inner) = session.table_dissect::<TestTable>(&2, &buf[2..], None)?;
+        pdu.set_inner_pdu(inner);
+        Ok((rem, pdu))
+    }
+
+    fn broken<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, RawPdu> {
+        Err(nom::Err::Failure(DissectError::Nom(
+            nom::error::Error::new(&buf[1..], nom::error::ErrorKind::Verify),
+        )))
+    }
+
+    fn short<'a>(
+        _buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, RawPdu> {
+        Err(nom::Err::Incomplete(nom::Needed::new(4)))
+    }
+
+    #[test]
+    fn truncated_dissection() {
+        let session = Session::builder()
+            .default_dissectors(false)
+            .table(TestTable::new())
+            .dissector::<TestTable, _>(1, Priority(0), outer)
+            .dissector::<TestTable, _>(2, Priority(0), short)
+            .build();
+        let data = [1u8, 2, 3];
+        assert!(session
+            .table_dissect::<TestTable>(&1, &data[..], None)
+            .is_err());
+
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(session.last_info_mut(|info| info.truncated = 10));
+        let (_, pdu) = session
+            .table_dissect::<TestTable>(&1, &data[..], None)
+            .unwrap();
+        assert_eq!(pdu.total_len(), 3);
+        assert_eq!(pdu.wire_len(), 7);
+        assert!(pdu.is_truncated());
+        let inner = pdu.inner_pdu().unwrap();
+        assert_eq!(inner.base_pdu().truncated_len(), 4);
+    }
+
+    #[test]
+    fn tolerant_dissection() {
+        let builder = || {
+            Session::builder()
+                .default_dissectors(false)
+                .table(TestTable::new())
+                .dissector::<TestTable, _>(1, Priority(0), outer)
+                .dissector::<TestTable, _>(2, Priority(0), broken)
+        };
+        let data = [1u8, 2, 3, 4, 5];
+
+        let session = builder().build();
+        assert_eq!(session.dissect_mode(), DissectMode::Strict);
+        assert!(matches!(
+            session.table_dissect::<TestTable>(&1, &data[..], None),
+            Err(nom::Err::Failure(_))
+        ));
+
+        let session = builder().dissect_mode(DissectMode::Tolerant).build();
+        let (rem, pdu) = session
+            .table_dissect::<TestTable>(&1, &data[..], None)
+            .unwrap();
+        assert!(rem.is_empty());
+        let err = pdu.find::<ErrorPdu>().unwrap();
+        assert_eq!(err.reason(), "Malformed (Verify)");
+        assert_eq!(err.offset(), 1);
+        assert_eq!(err.data()[..], data[2..]);
+        assert_eq!(pdu.total_len(), data.len());
+    }
+
+    #[test]
+    fn str_table() {
+        let session = Session::builder()
+            .default_dissectors(false)
+            .table(AlpnTable::new())
+            .dissector::<AlpnTable, _>("h2".into(), Priority(0), raw)
+            .build();
+        let table = session.get::<AlpnTable>().unwrap();
+        assert_eq!(table.entries("h2").count(), 1);
+        assert!(table.find_str("http/1.1").is_none());
+
+        let data = [1u8, 2, 3];
+        let (rem, pdu) = session
+            .table_dissect_str::<AlpnTable>("h2", &data[..], None)
+            .unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(pdu.total_len(), 3);
+        assert!(session
+            .table_dissect_str::<AlpnTable>("h3", &data[..], None)
+            .is_err());
+
+        let session = Session::builder().default_dissectors(false).build();
+        assert!(session
+            .table_dissect_str::<AlpnTable>("h2", &data[..], None)
+            .is_err());
+    }
+}
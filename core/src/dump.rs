@@ -1,4 +1,4 @@
-use crate::Packet;
+use crate::{Annotation, Packet, Pdu, PduExt};
 use chrono::{offset::Utc, DateTime};
 use std::any::Any;
 use std::io::Write;
@@ -54,6 +54,26 @@ pub trait Dump {
     fn start_list_sublist(&mut self, descr: Option<&str>) -> Result<(), Self::Error>;
 
     fn end_list_sublist(&mut self);
+
+    /// Sets the range of packet bytes covered by the next node, field, or
+    /// list item. The offset is relative to the start of the range of the
+    /// enclosing node, or to the start of the packet at the top level.
+    /// Dumpers that do not make use of byte ranges can ignore this.
+    fn set_byte_range(&mut self, _offset: usize, _len: usize) {}
+
+    /// Returns the host name of `addr`, which PDUs show beside the address.
+    /// Dumpers that do not resolve names can ignore this. See
+    /// `ResolvingDumper`.
+    fn resolve_name(&self, _addr: std::net::IpAddr) -> Option<String> {
+        None
+    }
+
+    /// Returns metadata about `addr`, which PDUs dump beside the address.
+    /// Dumpers that do not annotate addresses can ignore this. See
+    /// `AnnotatingDumper`.
+    fn annotate_addr(&self, _addr: std::net::IpAddr) -> Option<Annotation> {
+        None
+    }
 }
 
 pub struct Dumper<D: Dump>(D);
@@ -97,7 +117,7 @@ impl<'a> std::fmt::Display for ByteDumpFormatter<'a> {
     }
 }
 
-impl<'a, D: Dump + ?Sized> Dump for &'a mut D {
+impl<D: Dump + ?Sized> Dump for &mut D {
     type Error = D::Error;
 
     fn start_packet(&mut self) -> Result<(), Self::Error> {
@@ -160,6 +180,18 @@ impl<'a, D: Dump + ?Sized> Dump for &'a mut D {
     fn end_list_sublist(&mut self) {
         D::end_list_sublist(*self)
     }
+
+    fn set_byte_range(&mut self, offset: usize, len: usize) {
+        D::set_byte_range(*self, offset, len)
+    }
+
+    fn resolve_name(&self, addr: std::net::IpAddr) -> Option<String> {
+        D::resolve_name(*self, addr)
+    }
+
+    fn annotate_addr(&self, addr: std::net::IpAddr) -> Option<Annotation> {
+        D::annotate_addr(*self, addr)
+    }
 }
 
 fn to_boxed_any<T: Any + Send + Sync + 'static>(val: T) -> Box<dyn Any + Send + Sync + 'static> {
@@ -229,6 +261,18 @@ impl<'a, D: Dump + ?Sized> Dump for DynDumpWrapper<'a, D> {
     fn end_list_sublist(&mut self) {
         self.0.end_list_sublist()
     }
+
+    fn set_byte_range(&mut self, offset: usize, len: usize) {
+        self.0.set_byte_range(offset, len)
+    }
+
+    fn resolve_name(&self, addr: std::net::IpAddr) -> Option<String> {
+        self.0.resolve_name(addr)
+    }
+
+    fn annotate_addr(&self, addr: std::net::IpAddr) -> Option<Annotation> {
+        self.0.annotate_addr(addr)
+    }
 }
 
 impl<D: Dump> Dumper<D> {
@@ -240,6 +284,23 @@ impl<D: Dump> Dumper<D> {
         self.0.start_packet()?;
         Ok(NodeDumper(&mut self.0, NodeKind::Packet))
     }
+
+    /// Dumps a Pdu and all of its inner Pdus as a packet, without any of
+    /// the capture information `Packet::dump` includes.
+    pub fn dump_pdu<P: Pdu>(&mut self, pdu: &P) -> Result<(), D::Error> {
+        let mut node = self.add_packet()?;
+        node.byte_range(0, pdu.header_len());
+        pdu.dump(&mut node)?;
+        let mut offset = pdu.header_len();
+        let mut inner = pdu.inner_pdu();
+        while let Some(curr) = inner {
+            node.byte_range(offset, curr.header_len());
+            curr.dump(&mut node)?;
+            offset += curr.header_len();
+            inner = curr.inner_pdu();
+        }
+        Ok(())
+    }
 }
 
 impl<D: Dump> std::ops::Deref for Dumper<D> {
@@ -288,6 +349,42 @@ impl<'a, D: Dump + ?Sized> NodeDumper<'a, D> {
         Ok(ListDumper(self.0, ListKind::List))
     }
 
+    /// Sets the byte range of the next node, field, or list added. See
+    /// `Dump::set_byte_range`.
+    pub fn byte_range(&mut self, offset: usize, len: usize) -> &mut Self {
+        self.0.set_byte_range(offset, len);
+        self
+    }
+
+    /// Formats `addr` for a field description, followed by its host name in
+    /// parentheses if the dumper knows it. See `Dump::resolve_name`.
+    pub fn describe_addr(&self, addr: std::net::IpAddr) -> String {
+        match self.0.resolve_name(addr) {
+            Some(name) => format!("{} ({})", addr, name),
+            None => addr.to_string(),
+        }
+    }
+
+    /// Adds a node named `name` holding the annotation of `addr`, if the
+    /// dumper has one. See `Dump::annotate_addr`.
+    pub fn add_annotation(&mut self, name: &str, addr: std::net::IpAddr) -> Result<(), D::Error> {
+        let annot = match self.0.annotate_addr(addr) {
+            Some(annot) if !annot.is_empty() => annot,
+            _ => return Ok(()),
+        };
+        let mut node = self.add_node(name, None)?;
+        if let Some(country) = &annot.country {
+            node.add_field("Country", DumpValue::Text(&country[..]), None)?;
+        }
+        if let Some(asn) = annot.asn {
+            node.add_field("ASN", DumpValue::UInt(asn.into()), None)?;
+        }
+        if let Some(org) = &annot.as_org {
+            node.add_field("AS Organization", DumpValue::Text(&org[..]), None)?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn as_dyn_dumper<F>(&mut self, f: F) -> Result<(), D::Error>
     where
         F: for<'b, 'c> Fn(
@@ -337,6 +434,13 @@ impl<'a, D: Dump + ?Sized> ListDumper<'a, D> {
         self.0.start_list_sublist(descr)?;
         Ok(ListDumper(self.0, ListKind::SubList))
     }
+
+    /// Sets the byte range of the next item, node, or list added. See
+    /// `Dump::set_byte_range`.
+    pub fn byte_range(&mut self, offset: usize, len: usize) -> &mut Self {
+        self.0.set_byte_range(offset, len);
+        self
+    }
 }
 
 impl<'a, D: Dump + ?Sized> Drop for ListDumper<'a, D> {
@@ -445,7 +549,7 @@ impl<W: tokio::io::AsyncWrite + Send + Unpin> LogDumper<W> {
     }
 
     fn check_err(&mut self) -> std::io::Result<()> {
-        if let Some(e) = std::mem::replace(&mut self.err, None) {
+        if let Some(e) = self.err.take() {
             Err(e)
         } else {
             Ok(())
@@ -0,0 +1,173 @@
+use super::{BasePdu, DissectError, Dump, DumpValue, NodeDumper, Pdu};
+use sniffle_ende::encode::Encoder;
+use sniffle_ende::nom;
+
+/// The bytes of a layer that failed to dissect, in place of the layer.
+///
+/// Produced by dissector tables in `DissectMode::Tolerant` sessions when the
+/// dissector for a layer fails, so that the outer layers of the packet are
+/// still available. Find it in a packet with `Packet::find::<ErrorPdu>()`.
+#[derive(Debug)]
+pub struct ErrorPdu {
+    base: BasePdu,
+    reason: String,
+    offset: usize,
+    data: Vec<u8>,
+}
+
+impl ErrorPdu {
+    pub fn new<R: Into<String>>(reason: R, offset: usize, data: Vec<u8>) -> Self {
+        Self {
+            base: BasePdu::default(),
+            reason: reason.into(),
+            offset,
+            data,
+        }
+    }
+
+    /// Creates an `ErrorPdu` from the error of dissecting `data`.
+    pub fn from_error(err: &nom::Err<DissectError<'_>>, data: &[u8]) -> Self {
+        let (reason, offset) = match err {
+            nom::Err::Incomplete(nom::Needed::Size(needed)) => (
+                format!("Truncated ({} more bytes needed)", needed),
+                data.len(),
+            ),
+            nom::Err::Incomplete(nom::Needed::Unknown) => ("Truncated".into(), data.len()),
+            nom::Err::Error(DissectError::Nom(e)) | nom::Err::Failure(DissectError::Nom(e)) => {
+                let start = data.as_ptr() as usize;
+                let at = e.input.as_ptr() as usize;
+                let offset = if at >= start && at <= start + data.len() {
+                    at - start
+                } else {
+                    0
+                };
+                (format!("Malformed ({:?})", e.code), offset)
+            }
+            _ => ("Malformed".into(), 0),
+        };
+        Self::new(reason, offset, Vec::from(data))
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason[..]
+    }
+
+    /// Offset of the error within `data`.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn data(&self) -> &Vec<u8> {
+        &self.data
+    }
+}
+
+impl Clone for ErrorPdu {
+    fn clone(&self) -> Self {
+        Self {
+            base: BasePdu::default(),
+            reason: self.reason.clone(),
+            offset: self.offset,
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl Pdu for ErrorPdu {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.data[..]).map(|_| ())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<'_, D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("Error", Some(&self.reason[..]))?;
+        node.add_field("Reason", DumpValue::Text(&self.reason[..]), None)?;
+        node.add_field("Offset", DumpValue::UInt(self.offset as u64), None)?;
+        node.byte_range(0, self.data.len()).add_field(
+            "Data",
+            DumpValue::Bytes(&self.data[..]),
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::num::NonZeroUsize;
+
+    const DATA: &[u8] = &[1, 2, 3, 4];
+
+    #[test]
+    fn truncated() {
+        let err = nom::Err::Incomplete(nom::Needed::Size(NonZeroUsize::new(3).unwrap()));
+        let pdu = ErrorPdu::from_error(&err, DATA);
+        assert_eq!(pdu.reason(), "Truncated (3 more bytes needed)");
+        assert_eq!(pdu.offset(), DATA.len());
+
+        let pdu = ErrorPdu::from_error(&nom::Err::Incomplete(nom::Needed::Unknown), DATA);
+        assert_eq!(pdu.reason(), "Truncated");
+        assert_eq!(pdu.offset(), DATA.len());
+    }
+
+    #[test]
+    fn nom_error_offset() {
+        let err = nom::Err::Error(DissectError::Nom(nom::error::Error::new(
+            &DATA[2..],
+            nom::error::ErrorKind::Tag,
+        )));
+        let pdu = ErrorPdu::from_error(&err, DATA);
+        assert_eq!(pdu.reason(), "Malformed (Tag)");
+        assert_eq!(pdu.offset(), 2);
+
+        let err = nom::Err::Failure(DissectError::Nom(nom::error::Error::new(
+            &DATA[4..],
+            nom::error::ErrorKind::Eof,
+        )));
+        assert_eq!(ErrorPdu::from_error(&err, DATA).offset(), 4);
+    }
+
+    #[test]
+    fn nom_error_outside_data() {
+        // An error in another buffer, such as a reassembled one
+        let other = [0u8; 8];
+        let err = nom::Err::Error(DissectError::Nom(nom::error::Error::new(
+            &other[1..],
+            nom::error::ErrorKind::Tag,
+        )));
+        let pdu = ErrorPdu::from_error(&err, DATA);
+        assert_eq!(pdu.reason(), "Malformed (Tag)");
+        assert_eq!(pdu.offset(), 0);
+    }
+
+    #[test]
+    fn malformed() {
+        let pdu = ErrorPdu::from_error(&nom::Err::Error(DissectError::Malformed), DATA);
+        assert_eq!(pdu.reason(), "Malformed");
+        assert_eq!(pdu.offset(), 0);
+        assert_eq!(&pdu.data()[..], DATA);
+    }
+
+    #[test]
+    fn serialize() {
+        let pdu = ErrorPdu::new("Malformed", 1, DATA.to_vec());
+        assert_eq!(pdu.header_len(), DATA.len());
+        let mut buf = Vec::new();
+        pdu.clone().serialize(&mut buf).unwrap();
+        assert_eq!(buf, DATA);
+    }
+}
@@ -0,0 +1,170 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+trait AnyClone: Any + Send + Sync {
+    fn clone_box(&self) -> Box<dyn AnyClone>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Clone + Send + Sync + 'static> AnyClone for T {
+    fn clone_box(&self) -> Box<dyn AnyClone> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl Clone for Box<dyn AnyClone> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// Data attached to a packet by dissectors and analysis passes, keyed by
+/// type, such as a flow ID or a geolocation result.
+///
+/// At most one value of each type is stored. Use a newtype to store
+/// several values of the same underlying type. Values are cloned along
+/// with the packet, and no memory is allocated until the first insert.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn AnyClone>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores `value`, returning the previously stored value of the same
+    /// type, if any.
+    pub fn insert<T: Clone + Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|prev| prev.into_any().downcast().ok().map(|prev| *prev))
+    }
+
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| (**value).as_any().downcast_ref())
+    }
+
+    pub fn get_mut<T: Clone + Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| (**value).as_any_mut().downcast_mut())
+    }
+
+    /// Returns the stored value of type `T`, inserting the result of `f`
+    /// first if there is none.
+    pub fn get_or_insert_with<T: Clone + Send + Sync + 'static, F: FnOnce() -> T>(
+        &mut self,
+        f: F,
+    ) -> &mut T {
+        let value = self
+            .map
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(f()));
+        (**value)
+            .as_any_mut()
+            .downcast_mut()
+            .expect("extension stored under the wrong type")
+    }
+
+    pub fn remove<T: Clone + Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|prev| prev.into_any().downcast().ok().map(|prev| *prev))
+    }
+
+    pub fn contains<T: Clone + Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Moves every value from `other` into `self`, replacing values of the
+    /// same type.
+    pub fn extend(&mut self, other: Extensions) {
+        self.map.extend(other.map);
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct FlowId(u64);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Country(String);
+
+    #[test]
+    fn insert_get_remove() {
+        let mut ext = Extensions::new();
+        assert!(ext.is_empty());
+        assert_eq!(ext.get::<FlowId>(), None);
+
+        assert_eq!(ext.insert(FlowId(1)), None);
+        assert_eq!(ext.insert(Country("NL".into())), None);
+        assert_eq!(ext.insert(FlowId(2)), Some(FlowId(1)));
+        assert_eq!(ext.len(), 2);
+
+        ext.get_mut::<FlowId>().unwrap().0 += 1;
+        assert_eq!(ext.get::<FlowId>(), Some(&FlowId(3)));
+        assert!(ext.contains::<Country>());
+
+        assert_eq!(ext.remove::<Country>(), Some(Country("NL".into())));
+        assert!(!ext.contains::<Country>());
+        assert_eq!(ext.len(), 1);
+    }
+
+    #[test]
+    fn clone_is_deep() {
+        let mut ext = Extensions::new();
+        ext.get_or_insert_with(|| FlowId(7));
+        let mut copy = ext.clone();
+        copy.get_or_insert_with(|| FlowId(0)).0 = 8;
+        assert_eq!(ext.get::<FlowId>(), Some(&FlowId(7)));
+        assert_eq!(copy.get::<FlowId>(), Some(&FlowId(8)));
+
+        let mut other = Extensions::new();
+        other.insert(Country("BR".into()));
+        ext.extend(other);
+        assert_eq!(ext.len(), 2);
+        ext.clear();
+        assert!(ext.is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use super::{Error, Packet, Sniff};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// What a `PacketFanout` does when a consumer's buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The packet is dropped for that consumer, and counted in its stats.
+    /// Use this for consumers that can tolerate loss, such as a UI, so
+    /// they never hold up capture.
+    Drop,
+    /// The fanout waits for room, which holds up every other consumer and
+    /// the capture. Use this for consumers that must see every packet,
+    /// such as a recorder.
+    Wait,
+}
+
+/// Identifies a consumer subscribed to a `PacketFanout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConsumerId(u64);
+
+/// Delivery counts of a `PacketFanout` consumer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FanoutStats {
+    /// Packets queued for the consumer.
+    pub delivered: u64,
+    /// Packets dropped because the consumer's buffer was full.
+    pub dropped: u64,
+}
+
+/// Shares packets with any number of async consumers, such as a recorder,
+/// an analyzer, and a UI, each with its own bounded buffer.
+///
+/// Packets are shared in an `Arc`, so they are not copied per consumer.
+/// When a consumer falls behind and its buffer fills up, its
+/// `OverflowPolicy` decides whether the packet is dropped for it alone, or
+/// the fanout waits. Consumers unsubscribe by dropping their
+/// `FanoutReceiver`.
+///
+/// ```ignore
+/// let mut fanout = PacketFanout::new();
+/// let mut recorder = fanout.subscribe(1024, OverflowPolicy::Wait);
+/// let mut ui = fanout.subscribe(16, OverflowPolicy::Drop);
+/// tokio::spawn(async move {
+///     while let Some(packet) = ui.recv().await {
+///         show(&packet);
+///     }
+/// });
+/// fanout.run(&mut sniffer).await?;
+/// ```
+#[derive(Default)]
+pub struct PacketFanout {
+    consumers: Vec<Consumer>,
+    next_id: u64,
+}
+
+/// Receives packets from a `PacketFanout`.
+pub struct FanoutReceiver {
+    id: ConsumerId,
+    rx: mpsc::Receiver<Arc<Packet>>,
+    counters: Arc<Counters>,
+}
+
+struct Consumer {
+    id: ConsumerId,
+    policy: OverflowPolicy,
+    tx: mpsc::Sender<Arc<Packet>>,
+    counters: Arc<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl PacketFanout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a consumer that can have up to `buffer` packets waiting.
+    pub fn subscribe(&mut self, buffer: usize, policy: OverflowPolicy) -> FanoutReceiver {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        let id = ConsumerId(self.next_id);
+        self.next_id += 1;
+        let counters = Arc::new(Counters::default());
+        self.consumers.push(Consumer {
+            id,
+            policy,
+            tx,
+            counters: counters.clone(),
+        });
+        FanoutReceiver { id, rx, counters }
+    }
+
+    /// IDs of the consumers that haven't unsubscribed, as of the last
+    /// packet sent.
+    pub fn consumers(&self) -> impl Iterator<Item = ConsumerId> + '_ {
+        self.consumers.iter().map(|consumer| consumer.id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.consumers.is_empty()
+    }
+
+    pub fn stats(&self, id: ConsumerId) -> Option<FanoutStats> {
+        self.consumers
+            .iter()
+            .find(|consumer| consumer.id == id)
+            .map(|consumer| consumer.counters.stats())
+    }
+
+    /// Sends a packet to every consumer, and returns the number of
+    /// consumers it was queued for.
+    pub async fn send(&mut self, packet: Packet) -> usize {
+        let packet = Arc::new(packet);
+        let mut queued = 0;
+        let mut idx = 0;
+        while idx < self.consumers.len() {
+            let consumer = &self.consumers[idx];
+            let sent = match consumer.policy {
+                OverflowPolicy::Drop => match consumer.tx.try_send(packet.clone()) {
+                    Ok(()) => Some(true),
+                    Err(mpsc::error::TrySendError::Full(_)) => Some(false),
+                    Err(mpsc::error::TrySendError::Closed(_)) => None,
+                },
+                OverflowPolicy::Wait => consumer.tx.send(packet.clone()).await.ok().map(|_| true),
+            };
+            match sent {
+                Some(true) => {
+                    consumer.counters.delivered.fetch_add(1, Ordering::Relaxed);
+                    queued += 1;
+                }
+                Some(false) => {
+                    consumer.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                None => {
+                    self.consumers.remove(idx);
+                    continue;
+                }
+            }
+            idx += 1;
+        }
+        queued
+    }
+
+    /// Sends every packet from `src` until it runs out of packets or every
+    /// consumer has unsubscribed, and returns the number of packets sent.
+    pub async fn run<S: Sniff + ?Sized>(&mut self, src: &mut S) -> Result<usize, Error> {
+        let mut count = 0;
+        while !self.consumers.is_empty() {
+            match src.sniff().await? {
+                Some(packet) => {
+                    self.send(packet).await;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl FanoutReceiver {
+    pub fn id(&self) -> ConsumerId {
+        self.id
+    }
+
+    /// Receives the next packet, or `None` once the fanout is dropped and
+    /// every waiting packet has been received.
+    pub async fn recv(&mut self) -> Option<Arc<Packet>> {
+        self.rx.recv().await
+    }
+
+    /// Receives the next packet if one is waiting.
+    pub fn try_recv(&mut self) -> Option<Arc<Packet>> {
+        self.rx.try_recv().ok()
+    }
+
+    pub fn stats(&self) -> FanoutStats {
+        self.counters.stats()
+    }
+}
+
+impl Counters {
+    fn stats(&self) -> FanoutStats {
+        FanoutStats {
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PduExt, RawPdu};
+    use std::time::SystemTime;
+
+    fn packet(n: u8) -> Packet {
+        Packet::new(
+            SystemTime::UNIX_EPOCH,
+            RawPdu::new(vec![n]),
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn fanout() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut fanout = PacketFanout::new();
+            let mut fast = fanout.subscribe(8, OverflowPolicy::Wait);
+            let mut slow = fanout.subscribe(2, OverflowPolicy::Drop);
+            let gone = fanout.subscribe(1, OverflowPolicy::Wait);
+            drop(gone);
+
+            for n in 0..5 {
+                fanout.send(packet(n)).await;
+            }
+            assert_eq!(fanout.consumers().count(), 2);
+            assert_eq!(
+                fast.stats(),
+                FanoutStats {
+                    delivered: 5,
+                    dropped: 0
+                }
+            );
+            assert_eq!(
+                fanout.stats(slow.id()),
+                Some(FanoutStats {
+                    delivered: 2,
+                    dropped: 3
+                })
+            );
+
+            let data = |packet: Arc<Packet>| {
+                packet
+                    .pdu()
+                    .downcast_ref::<RawPdu>()
+                    .map(|raw| raw.data()[0])
+            };
+            let first = fast.recv().await.unwrap();
+            let shared = slow.recv().await.unwrap();
+            assert!(Arc::ptr_eq(&first, &shared));
+            assert_eq!(data(slow.recv().await.unwrap()), Some(1));
+            assert!(slow.try_recv().is_none());
+
+            drop(fanout);
+            let mut rest = Vec::new();
+            while let Some(packet) = fast.recv().await {
+                rest.extend(data(packet));
+            }
+            assert_eq!(rest, [1, 2, 3, 4]);
+        });
+    }
+}
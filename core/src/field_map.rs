@@ -0,0 +1,351 @@
+use super::{Dump, DumpValue, Dumper, Packet, Pdu};
+use std::convert::Infallible;
+
+/// The byte offsets and lengths of the fields of a packet or Pdu.
+///
+/// A `FieldMap` is built from the byte ranges a Pdu reports while being
+/// dumped (see `Dump::set_byte_range`), so it covers the same nodes and
+/// fields a dumper would show. Nodes, fields, and list items without a byte
+/// range are left out. Each entry is identified by a path made of the
+/// names of its enclosing nodes and its own name, separated by `.`, such as
+/// `"Ethernet II.Src Address"`. List items are named by their index.
+#[derive(Debug, Clone, Default)]
+pub struct FieldMap {
+    fields: Vec<FieldRange>,
+}
+
+/// The location of a single node or field. See `FieldMap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRange {
+    path: String,
+    offset: usize,
+    len: usize,
+    depth: usize,
+}
+
+struct Scope {
+    path: String,
+    base: usize,
+    items: usize,
+}
+
+struct FieldMapBuilder {
+    map: FieldMap,
+    scopes: Vec<Scope>,
+    range: Option<(usize, usize)>,
+}
+
+impl FieldRange {
+    /// The full path of the field.
+    pub fn path(&self) -> &str {
+        &self.path[..]
+    }
+
+    /// The name of the field, which is the last part of its path.
+    pub fn name(&self) -> &str {
+        match self.path.rfind('.') {
+            Some(idx) => &self.path[idx + 1..],
+            None => &self.path[..],
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of nodes enclosing the field.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset..(self.offset + self.len)
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.range().contains(&offset)
+    }
+}
+
+impl FieldMap {
+    /// Builds the field map of a Pdu and all of its inner Pdus. Offsets are
+    /// relative to the start of `pdu`.
+    pub fn new<P: Pdu>(pdu: &P) -> Self {
+        let mut builder = FieldMapBuilder::new();
+        match Dumper::new(&mut builder).dump_pdu(pdu) {
+            Ok(()) => {}
+            Err(e) => match e {},
+        }
+        builder.map
+    }
+
+    /// Builds the field map of a packet. Offsets are relative to the start
+    /// of the captured frame.
+    pub fn from_packet(pkt: &Packet) -> Self {
+        let mut builder = FieldMapBuilder::new();
+        match pkt.dump(&mut Dumper::new(&mut builder)) {
+            Ok(()) => {}
+            Err(e) => match e {},
+        }
+        builder.map
+    }
+
+    /// Returns the first field with the given path.
+    pub fn get(&self, path: &str) -> Option<&FieldRange> {
+        self.fields.iter().find(|field| field.path == path)
+    }
+
+    /// Returns the fields that contain the byte at `offset`, outermost
+    /// first.
+    pub fn at(&self, offset: usize) -> impl Iterator<Item = &FieldRange> + '_ {
+        self.fields
+            .iter()
+            .filter(move |field| field.contains(offset))
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, FieldRange> {
+        self.fields.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a FieldMap {
+    type Item = &'a FieldRange;
+    type IntoIter = std::slice::Iter<'a, FieldRange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.iter()
+    }
+}
+
+impl FieldMapBuilder {
+    fn new() -> Self {
+        Self {
+            map: FieldMap::default(),
+            scopes: Vec::new(),
+            range: None,
+        }
+    }
+
+    fn path(&mut self, name: Option<&str>) -> String {
+        let scope = match self.scopes.last_mut() {
+            Some(scope) => scope,
+            None => return name.map(String::from).unwrap_or_default(),
+        };
+        let name = match name {
+            Some(name) => String::from(name),
+            None => {
+                scope.items += 1;
+                (scope.items - 1).to_string()
+            }
+        };
+        if scope.path.is_empty() {
+            name
+        } else {
+            format!("{}.{}", scope.path, name)
+        }
+    }
+
+    fn add(&mut self, name: Option<&str>) -> (String, usize) {
+        let path = self.path(name);
+        let base = self.scopes.last().map(|scope| scope.base).unwrap_or(0);
+        let base = match self.range.take() {
+            Some((offset, len)) => {
+                let offset = base + offset;
+                self.map.fields.push(FieldRange {
+                    path: path.clone(),
+                    offset,
+                    len,
+                    depth: self.scopes.len().saturating_sub(1),
+                });
+                offset
+            }
+            None => base,
+        };
+        (path, base)
+    }
+
+    fn open(&mut self, name: Option<&str>) {
+        let (path, base) = self.add(name);
+        self.scopes.push(Scope {
+            path,
+            base,
+            items: 0,
+        });
+    }
+
+    fn close(&mut self) {
+        let _ = self.scopes.pop();
+    }
+}
+
+impl Dump for FieldMapBuilder {
+    type Error = Infallible;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        self.scopes.clear();
+        self.scopes.push(Scope {
+            path: String::new(),
+            base: 0,
+            items: 0,
+        });
+        self.range = None;
+        Ok(())
+    }
+
+    fn end_packet(&mut self) {
+        self.scopes.clear();
+    }
+
+    fn start_node(&mut self, name: &str, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(Some(name));
+        Ok(())
+    }
+
+    fn end_node(&mut self) {
+        self.close();
+    }
+
+    fn add_field(
+        &mut self,
+        name: &str,
+        _value: DumpValue<'_>,
+        _descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let _ = self.add(Some(name));
+        Ok(())
+    }
+
+    fn add_info(&mut self, name: &str, _descr: &str) -> Result<(), Self::Error> {
+        let _ = self.add(Some(name));
+        Ok(())
+    }
+
+    fn start_list(&mut self, name: &str, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(Some(name));
+        Ok(())
+    }
+
+    fn end_list(&mut self) {
+        self.close();
+    }
+
+    fn add_list_item(
+        &mut self,
+        _value: DumpValue<'_>,
+        _descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let _ = self.add(None);
+        Ok(())
+    }
+
+    fn start_list_node(&mut self, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(None);
+        Ok(())
+    }
+
+    fn end_list_node(&mut self) {
+        self.close();
+    }
+
+    fn start_list_sublist(&mut self, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(None);
+        Ok(())
+    }
+
+    fn end_list_sublist(&mut self) {
+        self.close();
+    }
+
+    fn set_byte_range(&mut self, offset: usize, len: usize) {
+        self.range = Some((offset, len));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{NodeDumper, PduExt, RawPdu};
+    use sniffle_ende::encode::Encoder;
+
+    #[derive(Debug, Clone, Default)]
+    struct Header {
+        base: crate::BasePdu,
+    }
+
+    impl Pdu for Header {
+        fn base_pdu(&self) -> &crate::BasePdu {
+            &self.base
+        }
+
+        fn base_pdu_mut(&mut self) -> &mut crate::BasePdu {
+            &mut self.base
+        }
+
+        fn header_len(&self) -> usize {
+            4
+        }
+
+        fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+            &self,
+            encoder: &mut W,
+        ) -> std::io::Result<()> {
+            encoder.encode(&[0u8; 4][..]).map(|_| ())
+        }
+
+        fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<'_, D>) -> Result<(), D::Error> {
+            let mut node = dumper.add_node("Header", None)?;
+            node.byte_range(0, 2)
+                .add_field("Kind", DumpValue::UInt(0), None)?;
+            node.add_field("Unmapped", DumpValue::UInt(0), None)?;
+            let mut list = node.byte_range(2, 2).add_list("Flags", None)?;
+            list.add_item(DumpValue::Bool(false), None)?;
+            list.byte_range(1, 1)
+                .add_item(DumpValue::Bool(true), None)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn field_map() {
+        let mut pdu = Header::default();
+        pdu.set_inner_pdu(RawPdu::new(vec![1, 2, 3]));
+        let map = FieldMap::new(&pdu);
+
+        let paths: Vec<_> = map
+            .iter()
+            .map(|field| (field.path(), field.range(), field.depth()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("Header", 0..4, 0),
+                ("Header.Kind", 0..2, 1),
+                ("Header.Flags", 2..4, 1),
+                ("Header.Flags.1", 3..4, 2),
+                ("Raw Bytes", 4..7, 0),
+                ("Raw Bytes.Data", 4..7, 1),
+            ]
+        );
+        assert_eq!(map.get("Header.Flags.1").unwrap().name(), "1");
+        assert_eq!(
+            map.at(3).map(FieldRange::path).collect::<Vec<_>>(),
+            ["Header", "Header.Flags", "Header.Flags.1"]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use super::{
+    Conversation, EndpointAddress, EndpointKey, Error, Ipv4Address, Timestamp, TrafficSummary,
+};
+use std::io::Write;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::SystemTime;
+
+const IPFIX_VERSION: u16 = 10;
+const TEMPLATE_SET_ID: u16 = 2;
+const TEMPLATE_ID: u16 = 256;
+const MESSAGE_HEADER_LEN: usize = 16;
+const SET_HEADER_LEN: usize = 4;
+const RECORD_LEN: usize = 45;
+
+/// Information elements of the exported records, as (ID, length)
+const TEMPLATE: [(u16, u16); 9] = [
+    (8, 4),   // sourceIPv4Address
+    (12, 4),  // destinationIPv4Address
+    (7, 2),   // sourceTransportPort
+    (11, 2),  // destinationTransportPort
+    (4, 1),   // protocolIdentifier
+    (86, 8),  // packetTotalCount
+    (85, 8),  // octetTotalCount
+    (152, 8), // flowStartMilliseconds
+    (153, 8), // flowEndMilliseconds
+];
+
+const TEMPLATE_SET_LEN: usize = SET_HEADER_LEN + 4 + 4 * TEMPLATE.len();
+
+/// One direction of a TCP or UDP conversation, as exported by
+/// `IpfixExporter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowRecord {
+    pub src_addr: Ipv4Address,
+    pub dst_addr: Ipv4Address,
+    pub src_port: u16,
+    pub dst_port: u16,
+    /// IP protocol number
+    pub protocol: u8,
+    pub packets: u64,
+    pub bytes: u64,
+    /// Timestamp of the first packet of the conversation, in either
+    /// direction
+    pub start: Timestamp,
+    /// Timestamp of the last packet of the conversation, in either
+    /// direction
+    pub end: Timestamp,
+}
+
+/// Exports the TCP and UDP conversations of a `TrafficSummary` as IPFIX
+/// (RFC 7011) flow records, one for each direction with traffic.
+///
+/// Every message starts with the template describing the records, so a
+/// collector can decode any message on its own, as recommended for UDP
+/// transport. Packet and byte counts are totals since the start of each
+/// conversation, so exporting the same summary again reports the same
+/// flows with updated counts.
+///
+/// ```ignore
+/// let mut exporter = IpfixExporter::new(1);
+/// let socket = UdpSocket::bind("0.0.0.0:0")?;
+/// loop {
+///     summary.add(&sniffer.sniff().await?.unwrap());
+///     if export_due() {
+///         exporter.send(&summary, &socket, "collector:4739")?;
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct IpfixExporter {
+    domain_id: u32,
+    seq: u32,
+    max_message_len: usize,
+}
+
+impl FlowRecord {
+    /// The records of both directions of a TCP or UDP conversation, as
+    /// found with the `"TCP"` and `"UDP"` conversation kinds. Other kinds of
+    /// conversations, and conversations between endpoints that aren't an
+    /// IPv4 address and port, have no records.
+    pub fn from_conversation(conv: &Conversation) -> Vec<Self> {
+        let protocol = match conv.kind {
+            "TCP" => 6,
+            "UDP" => 17,
+            _ => return Vec::new(),
+        };
+        let endpoint = |key: &EndpointKey| match (&key.address, key.port) {
+            (EndpointAddress::Ipv4(addr), Some(port)) => Some((*addr, port)),
+            _ => None,
+        };
+        let (Some((a_addr, a_port)), Some((b_addr, b_port))) =
+            (endpoint(&conv.a), endpoint(&conv.b))
+        else {
+            return Vec::new();
+        };
+        let record = |src_addr, src_port, dst_addr, dst_port, packets, bytes| Self {
+            src_addr,
+            dst_addr,
+            src_port,
+            dst_port,
+            protocol,
+            packets,
+            bytes,
+            start: conv.first,
+            end: conv.last,
+        };
+        let mut records = Vec::new();
+        if conv.a_to_b_packets > 0 {
+            records.push(record(
+                a_addr,
+                a_port,
+                b_addr,
+                b_port,
+                conv.a_to_b_packets,
+                conv.a_to_b_bytes,
+            ));
+        }
+        if conv.b_to_a_packets > 0 {
+            records.push(record(
+                b_addr,
+                b_port,
+                a_addr,
+                a_port,
+                conv.b_to_a_packets,
+                conv.b_to_a_bytes,
+            ));
+        }
+        records
+    }
+
+    /// The records of the TCP and UDP conversations of `summary`.
+    pub fn from_summary(summary: &TrafficSummary) -> Vec<Self> {
+        ["TCP", "UDP"]
+            .iter()
+            .flat_map(|kind| summary.conversations(kind))
+            .flat_map(Self::from_conversation)
+            .collect()
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        let millis = |ts: Timestamp| (ts.as_nanos() / 1_000_000).max(0) as u64;
+        out.extend_from_slice(&<[u8; 4]>::from(self.src_addr)[..]);
+        out.extend_from_slice(&<[u8; 4]>::from(self.dst_addr)[..]);
+        out.extend_from_slice(&self.src_port.to_be_bytes()[..]);
+        out.extend_from_slice(&self.dst_port.to_be_bytes()[..]);
+        out.push(self.protocol);
+        out.extend_from_slice(&self.packets.to_be_bytes()[..]);
+        out.extend_from_slice(&self.bytes.to_be_bytes()[..]);
+        out.extend_from_slice(&millis(self.start).to_be_bytes()[..]);
+        out.extend_from_slice(&millis(self.end).to_be_bytes()[..]);
+    }
+}
+
+impl IpfixExporter {
+    /// Creates an exporter for the observation domain `domain_id`, with
+    /// messages of at most 1400 bytes.
+    pub fn new(domain_id: u32) -> Self {
+        Self {
+            domain_id,
+            seq: 0,
+            max_message_len: 1400,
+        }
+    }
+
+    /// Sets the maximum length of a message, which should fit in a UDP
+    /// datagram without fragmentation. At least one record is put in each
+    /// message, regardless of this limit.
+    pub fn max_message_len(mut self, len: usize) -> Self {
+        self.max_message_len = len.min(u16::MAX as usize);
+        self
+    }
+
+    pub fn domain_id(&self) -> u32 {
+        self.domain_id
+    }
+
+    /// Number of records exported so far, modulo 2^32, which is the
+    /// sequence number of the next message.
+    pub fn sequence_number(&self) -> u32 {
+        self.seq
+    }
+
+    /// Encodes `records` into IPFIX messages.
+    pub fn encode(&mut self, records: &[FlowRecord], export_time: SystemTime) -> Vec<Vec<u8>> {
+        let export_time = export_time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|time| time.as_secs() as u32)
+            .unwrap_or(0);
+        let per_message = (self
+            .max_message_len
+            .saturating_sub(MESSAGE_HEADER_LEN + TEMPLATE_SET_LEN + SET_HEADER_LEN)
+            / RECORD_LEN)
+            .max(1);
+        records
+            .chunks(per_message)
+            .map(|records| {
+                let data_set_len = SET_HEADER_LEN + RECORD_LEN * records.len();
+                let len = MESSAGE_HEADER_LEN + TEMPLATE_SET_LEN + data_set_len;
+                let mut msg = Vec::with_capacity(len);
+                msg.extend_from_slice(&IPFIX_VERSION.to_be_bytes()[..]);
+                msg.extend_from_slice(&(len as u16).to_be_bytes()[..]);
+                msg.extend_from_slice(&export_time.to_be_bytes()[..]);
+                msg.extend_from_slice(&self.seq.to_be_bytes()[..]);
+                msg.extend_from_slice(&self.domain_id.to_be_bytes()[..]);
+
+                msg.extend_from_slice(&TEMPLATE_SET_ID.to_be_bytes()[..]);
+                msg.extend_from_slice(&(TEMPLATE_SET_LEN as u16).to_be_bytes()[..]);
+                msg.extend_from_slice(&TEMPLATE_ID.to_be_bytes()[..]);
+                msg.extend_from_slice(&(TEMPLATE.len() as u16).to_be_bytes()[..]);
+                for (id, len) in TEMPLATE.iter() {
+                    msg.extend_from_slice(&id.to_be_bytes()[..]);
+                    msg.extend_from_slice(&len.to_be_bytes()[..]);
+                }
+
+                msg.extend_from_slice(&TEMPLATE_ID.to_be_bytes()[..]);
+                msg.extend_from_slice(&(data_set_len as u16).to_be_bytes()[..]);
+                for record in records.iter() {
+                    record.encode(&mut msg);
+                }
+                self.seq = self.seq.wrapping_add(records.len() as u32);
+                msg
+            })
+            .collect()
+    }
+
+    /// Writes the flows of `summary` to `dst` as consecutive IPFIX
+    /// messages, such as to a file, and returns the number of records
+    /// written.
+    pub fn write<W: Write + ?Sized>(
+        &mut self,
+        summary: &TrafficSummary,
+        dst: &mut W,
+    ) -> Result<usize, Error> {
+        let records = FlowRecord::from_summary(summary);
+        for msg in self.encode(&records[..], SystemTime::now()) {
+            dst.write_all(&msg[..])?;
+        }
+        Ok(records.len())
+    }
+
+    /// Sends the flows of `summary` to the collector at `collector`, one
+    /// message per datagram, and returns the number of records sent.
+    pub fn send<A: ToSocketAddrs>(
+        &mut self,
+        summary: &TrafficSummary,
+        socket: &UdpSocket,
+        collector: A,
+    ) -> Result<usize, Error> {
+        let collector = collector
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::AddrNotAvailable))?;
+        let records = FlowRecord::from_summary(summary);
+        for msg in self.encode(&records[..], SystemTime::now()) {
+            socket.send_to(&msg[..], collector)?;
+        }
+        Ok(records.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ipfix_export() {
+        let conv = Conversation {
+            kind: "TCP",
+            a: EndpointKey {
+                address: EndpointAddress::Ipv4([10, 0, 0, 1].into()),
+                port: Some(50000),
+            },
+            b: EndpointKey {
+                address: EndpointAddress::Ipv4([10, 0, 0, 2].into()),
+                port: Some(443),
+            },
+            a_to_b_packets: 3,
+            a_to_b_bytes: 300,
+            b_to_a_packets: 2,
+            b_to_a_bytes: 2000,
+            first: Timestamp::new(1_700_000_000, 500_000_000),
+            last: Timestamp::new(1_700_000_002, 0),
+        };
+        let records = FlowRecord::from_conversation(&conv);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].src_addr, [10, 0, 0, 2].into());
+        assert_eq!((records[1].src_port, records[1].dst_port), (443, 50000));
+        assert_eq!((records[1].packets, records[1].bytes), (2, 2000));
+        let ipv4 = Conversation {
+            kind: "IPv4",
+            a: EndpointKey {
+                port: None,
+                ..conv.a.clone()
+            },
+            b: EndpointKey {
+                port: None,
+                ..conv.b.clone()
+            },
+            ..conv.clone()
+        };
+        assert!(FlowRecord::from_conversation(&ipv4).is_empty());
+
+        // Room for two records per message
+        let mut exporter = IpfixExporter::new(7).max_message_len(160);
+        let records = [records[0], records[1], records[0]];
+        let export_time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_010);
+        let msgs = exporter.encode(&records[..], export_time);
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(exporter.sequence_number(), 3);
+
+        let msg = &msgs[0];
+        let u16_at = |msg: &[u8], at: usize| u16::from_be_bytes([msg[at], msg[at + 1]]);
+        let u32_at =
+            |msg: &[u8], at: usize| u32::from_be_bytes(msg[at..at + 4].try_into().unwrap());
+        assert_eq!(u16_at(msg, 0), 10);
+        assert_eq!(u16_at(msg, 2) as usize, msg.len());
+        assert_eq!(msg.len(), 16 + 44 + 4 + 2 * 45);
+        assert_eq!(u32_at(msg, 4), 1_700_000_010);
+        assert_eq!(u32_at(msg, 8), 0);
+        assert_eq!(u32_at(msg, 12), 7);
+        assert_eq!(u16_at(msg, 16), 2);
+        assert_eq!(u16_at(msg, 20), 256);
+        assert_eq!(u16_at(msg, 22), 9);
+
+        let data = &msg[60..];
+        assert_eq!(u16_at(data, 0), 256);
+        assert_eq!(u16_at(data, 2), 94);
+        let record = &data[4..49];
+        assert_eq!(record[..8], [10, 0, 0, 1, 10, 0, 0, 2]);
+        assert_eq!((u16_at(record, 8), u16_at(record, 10)), (50000, 443));
+        assert_eq!(record[12], 6);
+        assert_eq!(record[13..21], 3u64.to_be_bytes());
+        assert_eq!(record[21..29], 300u64.to_be_bytes());
+        assert_eq!(record[29..37], 1_700_000_000_500u64.to_be_bytes());
+        assert_eq!(record[37..45], 1_700_000_002_000u64.to_be_bytes());
+
+        assert_eq!(u32_at(&msgs[1], 8), 2);
+        assert_eq!(msgs[1].len(), 16 + 44 + 4 + 45);
+
+        let mut out = Vec::new();
+        let count = exporter.write(&TrafficSummary::new(), &mut out).unwrap();
+        assert_eq!(count, 0);
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+use super::{ByteDumpFormatter, Dump, DumpValue, Dumper, Packet};
+use std::convert::Infallible;
+use std::io::Write;
+use tokio::io::AsyncWriteExt;
+
+/// Dumps the raw bytes of packets in the classic offset/hex/ASCII layout.
+///
+/// When annotation is enabled, the hex dump of each packet is followed by
+/// the nodes and fields that were given a byte range (see
+/// `Dump::set_byte_range`), along with the range of bytes each one covers.
+pub struct HexDumper<W: tokio::io::AsyncWrite + Send + Unpin> {
+    writer: W,
+    buf: Vec<u8>,
+    data: Vec<u8>,
+    count: u64,
+    annotate: bool,
+    scopes: Vec<usize>,
+    range: Option<(usize, usize)>,
+    annotations: Vec<Annotation>,
+}
+
+struct Annotation {
+    depth: usize,
+    pos: usize,
+    size: usize,
+    label: String,
+}
+
+fn field_label(name: Option<&str>, value: DumpValue<'_>, descr: Option<&str>) -> String {
+    let value = match (descr, value) {
+        (Some(descr), _) => String::from(descr),
+        (None, DumpValue::Bytes(bytes)) => ByteDumpFormatter(bytes).to_string(),
+        (None, value) => value.to_string(),
+    };
+    match name {
+        Some(name) => format!("{}: {}", name, value),
+        None => value,
+    }
+}
+
+fn node_label(name: &str, descr: Option<&str>) -> String {
+    match descr {
+        Some(descr) => format!("{}, {}", name, descr),
+        None => String::from(name),
+    }
+}
+
+fn write_hex<T: Write>(out: &mut T, data: &[u8]) -> std::io::Result<()> {
+    for (i, line) in data.chunks(16).enumerate() {
+        write!(out, "{:04x} ", i * 16)?;
+        for j in 0..16 {
+            if j == 8 {
+                write!(out, " ")?;
+            }
+            match line.get(j) {
+                Some(byte) => write!(out, " {:02x}", byte)?,
+                None => write!(out, "   ")?,
+            }
+        }
+        write!(out, "  ")?;
+        for byte in line {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                write!(out, "{}", *byte as char)?;
+            } else {
+                write!(out, ".")?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+impl<W: tokio::io::AsyncWrite + Send + Unpin> HexDumper<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buf: Vec::new(),
+            data: Vec::new(),
+            count: 0,
+            annotate: false,
+            scopes: Vec::new(),
+            range: None,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Enables or disables listing the byte ranges of nodes and fields
+    /// after each packet.
+    pub fn annotate(self, enable: bool) -> Self {
+        let mut dumper = self;
+        dumper.annotate = enable;
+        dumper
+    }
+
+    pub fn as_inner(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn packet_count(&self) -> u64 {
+        self.count
+    }
+
+    pub async fn flush(&mut self) -> std::io::Result<()> {
+        let buf = std::mem::take(&mut self.buf);
+        let res = self.writer.write_all(&buf).await;
+        self.buf = buf;
+        self.buf.clear();
+        res
+    }
+
+    pub async fn dump(&mut self, pkt: &Packet) -> std::io::Result<()> {
+        self.data.clear();
+        pkt.serialize(&mut self.data)?;
+        self.count += 1;
+        writeln!(self.buf, "Packet {}", self.count)?;
+        write_hex(&mut self.buf, &self.data[..])?;
+
+        if self.annotate {
+            {
+                let mut dumper = &mut *self;
+                match pkt.dump(&mut Dumper::new(&mut dumper)) {
+                    Ok(()) => {}
+                    Err(e) => match e {},
+                }
+            }
+            if !self.annotations.is_empty() {
+                writeln!(self.buf)?;
+            }
+            for ann in std::mem::take(&mut self.annotations) {
+                write!(self.buf, "{:04x}-{:04x}  ", ann.pos, ann.pos + ann.size - 1)?;
+                for _ in 0..ann.depth {
+                    write!(self.buf, "  ")?;
+                }
+                writeln!(self.buf, "{}", ann.label)?;
+            }
+        }
+        writeln!(self.buf)?;
+        self.flush().await
+    }
+
+    fn depth(&self) -> usize {
+        self.scopes.len().saturating_sub(1)
+    }
+
+    fn base(&self) -> usize {
+        self.scopes.last().copied().unwrap_or(0)
+    }
+
+    fn take_range(&mut self) -> Option<(usize, usize)> {
+        let base = self.base();
+        self.range.take().map(|(pos, size)| (base + pos, size))
+    }
+
+    fn annotate_range(&mut self, range: Option<(usize, usize)>, label: impl FnOnce() -> String) {
+        if let Some((pos, size)) = range {
+            if size > 0 {
+                self.annotations.push(Annotation {
+                    depth: self.depth(),
+                    pos,
+                    size,
+                    label: label(),
+                });
+            }
+        }
+    }
+
+    fn open(&mut self, label: impl FnOnce() -> String) {
+        let range = self.take_range();
+        self.annotate_range(range, label);
+        let base = range.map(|(pos, _)| pos).unwrap_or_else(|| self.base());
+        self.scopes.push(base);
+    }
+
+    fn close(&mut self) {
+        let _ = self.scopes.pop();
+    }
+}
+
+impl<W: tokio::io::AsyncWrite + Send + Unpin> Dump for HexDumper<W> {
+    type Error = Infallible;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        self.scopes.clear();
+        self.scopes.push(0);
+        self.range = None;
+        self.annotations.clear();
+        Ok(())
+    }
+
+    fn end_packet(&mut self) {
+        self.scopes.clear();
+    }
+
+    fn start_node(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(|| node_label(name, descr));
+        Ok(())
+    }
+
+    fn end_node(&mut self) {
+        self.close();
+    }
+
+    fn add_field(
+        &mut self,
+        name: &str,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let range = self.take_range();
+        self.annotate_range(range, || field_label(Some(name), value, descr));
+        Ok(())
+    }
+
+    fn add_info(&mut self, name: &str, descr: &str) -> Result<(), Self::Error> {
+        let range = self.take_range();
+        self.annotate_range(range, || format!("{}: {}", name, descr));
+        Ok(())
+    }
+
+    fn start_list(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(|| node_label(name, descr));
+        Ok(())
+    }
+
+    fn end_list(&mut self) {
+        self.close();
+    }
+
+    fn add_list_item(
+        &mut self,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let range = self.take_range();
+        self.annotate_range(range, || field_label(None, value, descr));
+        Ok(())
+    }
+
+    fn start_list_node(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(|| String::from(descr.unwrap_or("item")));
+        Ok(())
+    }
+
+    fn end_list_node(&mut self) {
+        self.close();
+    }
+
+    fn start_list_sublist(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.open(|| String::from(descr.unwrap_or("item")));
+        Ok(())
+    }
+
+    fn end_list_sublist(&mut self) {
+        self.close();
+    }
+
+    fn set_byte_range(&mut self, offset: usize, len: usize) {
+        self.range = Some((offset, len));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PduExt, RawPdu};
+    use std::time::SystemTime;
+
+    #[test]
+    fn hex_dump() {
+        let data: Vec<u8> = (0x1c..0x3c).chain([0x7f, 0x80]).collect();
+        let pkt = Packet::new(SystemTime::UNIX_EPOCH, RawPdu::new(data), None, None, None);
+        let mut dumper = HexDumper::new(Vec::new()).annotate(true);
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(dumper.dump(&pkt))
+            .unwrap();
+        let out = String::from_utf8(std::mem::take(dumper.as_inner_mut())).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "Packet 1",
+                "0000  1c 1d 1e 1f 20 21 22 23  24 25 26 27 28 29 2a 2b  .... !\"#$%&'()*+",
+                "0010  2c 2d 2e 2f 30 31 32 33  34 35 36 37 38 39 3a 3b  ,-./0123456789:;",
+                "0020  7f 80                                             ..",
+                "",
+                "0000-0021  Capture",
+                "0000-0021  Raw Bytes",
+                &format!(
+                    "0000-0021    Data: {}",
+                    ByteDumpFormatter(&pkt.pdu().downcast_ref::<RawPdu>().unwrap().data()[..])
+                ),
+                "",
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use super::diff::DiffCollector;
+use super::summary::LayerName;
+use super::{Error, Packet, Pdu, PduExt, Query, Sniff, Timestamp};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Bins packets into fixed intervals, counting the packets and bytes of
+/// each interval, of each protocol, and of each filter, for plotting the
+/// traffic over time.
+///
+/// Intervals start at the first packet, or at the time set with `start`.
+/// Only intervals with packets have a bucket, so a gap in the capture
+/// doesn't take up memory. Packets earlier than the start are not counted.
+///
+/// Protocol specific counts, such as TCP retransmissions, are counted with
+/// filters on the fields the dissectors set, for example with the
+/// `TcpAnalyzer` of the TCP dissector registered in the session:
+///
+/// ```ignore
+/// let mut graph = IoGraph::new(Duration::from_secs(1))
+///     .filter("HTTPS", Query::parse("tcp.port == 443")?)
+///     .filter("Retransmissions", Query::parse("tcp.analysis.retransmission")?);
+/// while let Some(packet) = sniffer.sniff().await? {
+///     graph.add(&packet);
+/// }
+/// for bucket in graph.into_buckets() {
+///     println!("{} {} {}", bucket.start, bucket.packets, bucket.filters[0].packets);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct IoGraph {
+    interval: Duration,
+    start: Option<Timestamp>,
+    filters: Vec<(String, Query)>,
+    /// Buckets by the index of their interval
+    buckets: BTreeMap<u64, Bucket>,
+}
+
+/// The traffic of one interval of an `IoGraph`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bucket {
+    pub start: Timestamp,
+    pub packets: u64,
+    pub bytes: u64,
+    /// Packets and bytes of each protocol, as dumped, in the order first
+    /// seen in the interval. The bytes of a protocol are the bytes of its
+    /// PDUs, including the PDUs inside them.
+    pub protocols: Vec<IoCounter>,
+    /// Packets and bytes that match each filter, in the order the filters
+    /// were added
+    pub filters: Vec<IoCounter>,
+}
+
+/// Packets and bytes counted by a `Bucket`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IoCounter {
+    pub name: String,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl IoGraph {
+    /// Creates a graph with intervals of `interval`, which must not be zero.
+    pub fn new(interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "IoGraph interval must not be zero");
+        Self {
+            interval,
+            start: None,
+            filters: Vec::new(),
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the start of the first interval, instead of the timestamp of
+    /// the first packet.
+    pub fn start(mut self, start: Timestamp) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Adds a filter, counted in each bucket as `name`.
+    pub fn filter<S: Into<String>>(mut self, name: S, query: Query) -> Self {
+        self.filters.push((name.into(), query));
+        self
+    }
+
+    /// Bins all packets from `sniffer`.
+    pub async fn from_sniffer<S: Sniff + ?Sized>(
+        interval: Duration,
+        sniffer: &mut S,
+    ) -> Result<Vec<Bucket>, Error> {
+        let mut graph = Self::new(interval);
+        while let Some(packet) = sniffer.sniff().await? {
+            graph.add(&packet);
+        }
+        Ok(graph.into_buckets())
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn add(&mut self, packet: &Packet) {
+        let ts = packet.precise_timestamp();
+        let start = *self.start.get_or_insert(ts);
+        let Some(offset) = ts.duration_since(start) else {
+            return;
+        };
+        let interval = self.interval.as_nanos();
+        let idx = (offset.as_nanos() / interval) as u64;
+        let into_interval = Duration::from_nanos((offset.as_nanos() % interval) as u64);
+        let bucket = self.buckets.entry(idx).or_insert_with(|| Bucket {
+            start: start + (offset - into_interval),
+            packets: 0,
+            bytes: 0,
+            protocols: Vec::new(),
+            filters: self
+                .filters
+                .iter()
+                .map(|(name, _)| IoCounter {
+                    name: name.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+        });
+
+        let len = packet.len() as u64;
+        bucket.packets += 1;
+        bucket.bytes += len;
+        let mut layer = Some(packet.pdu());
+        while let Some(pdu) = layer {
+            if let Some(name) = LayerName::of(pdu) {
+                let counter = match bucket.protocols.iter().position(|c| c.name == name) {
+                    Some(idx) => &mut bucket.protocols[idx],
+                    None => {
+                        bucket.protocols.push(IoCounter {
+                            name,
+                            ..Default::default()
+                        });
+                        bucket.protocols.last_mut().unwrap()
+                    }
+                };
+                counter.packets += 1;
+                counter.bytes += pdu.total_len() as u64;
+            }
+            layer = pdu.inner_pdu();
+        }
+
+        if self.filters.is_empty() {
+            return;
+        }
+        let entries = DiffCollector::collect_packet(packet);
+        for ((_, query), counter) in self.filters.iter().zip(bucket.filters.iter_mut()) {
+            if query.matches_entries(&entries[..]) {
+                counter.packets += 1;
+                counter.bytes += len;
+            }
+        }
+    }
+
+    /// The buckets of the intervals with packets, in order of time.
+    pub fn buckets(&self) -> impl Iterator<Item = &Bucket> {
+        self.buckets.values()
+    }
+
+    pub fn into_buckets(self) -> Vec<Bucket> {
+        self.buckets.into_values().collect()
+    }
+}
+
+impl Bucket {
+    /// The counter of the protocol `name`, if it was seen in the interval.
+    pub fn protocol(&self, name: &str) -> Option<&IoCounter> {
+        self.protocols.iter().find(|counter| counter.name == name)
+    }
+
+    /// The counter of the filter `name`.
+    pub fn filter(&self, name: &str) -> Option<&IoCounter> {
+        self.filters.iter().find(|counter| counter.name == name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LinkType, LinkTypeTable, PluginContext, PluginDissector, PluginGuest};
+    use crate::{PluginValue, Priority, Session};
+
+    /// A TCP-like header of a source port, destination port, and sequence
+    /// number byte.
+    struct Tcp;
+
+    impl PluginGuest for Tcp {
+        fn dissect(&self, ctx: &mut PluginContext<'_>) -> Option<usize> {
+            let mut hdr = [0u8; 3];
+            if ctx.read(0, &mut hdr[..]) != 3 {
+                return None;
+            }
+            ctx.emit_field("Source Port", 0, 1, PluginValue::UInt(hdr[0].into()));
+            ctx.emit_field("Destination Port", 1, 1, PluginValue::UInt(hdr[1].into()));
+            ctx.emit_field("Sequence Number", 2, 1, PluginValue::UInt(hdr[2].into()));
+            Some(3)
+        }
+    }
+
+    fn session() -> Session {
+        Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(
+                LinkType::USER0,
+                Priority(0),
+                PluginDissector::new("TCP", Tcp),
+            )
+            .build()
+    }
+
+    #[test]
+    fn io_graph() {
+        let session = session();
+        let mut graph = IoGraph::new(Duration::from_secs(1))
+            .filter("To 2", Query::parse("tcp.destination_port == 2").unwrap());
+        for (ts, data) in [
+            (10_000, &[1u8, 2, 0, 0xaa, 0xbb][..]),
+            (10_500, &[1, 2, 2, 0xcc]),
+            (10_900, &[2, 1, 0]),
+            (12_100, &[1, 2, 0, 0xaa, 0xbb]),
+            (12_200, &[1, 2, 3, 0xdd]),
+        ] {
+            let (_, pdu) = session
+                .table_dissect::<LinkTypeTable>(&LinkType::USER0, data, None)
+                .unwrap();
+            let ts = Timestamp::from_nanos(ts * 1_000_000);
+            graph.add(&Packet::new(ts, pdu, None, None, None));
+        }
+
+        let buckets = graph.into_buckets();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[1].start, Timestamp::new(12, 0));
+        assert_eq!((buckets[0].packets, buckets[0].bytes), (3, 12));
+        assert_eq!((buckets[1].packets, buckets[1].bytes), (2, 9));
+
+        let raw = buckets[0].protocol("Raw Bytes").unwrap();
+        assert_eq!((raw.packets, raw.bytes), (2, 3));
+        assert_eq!(buckets[0].protocol("TCP").unwrap().packets, 3);
+        assert_eq!(buckets[0].filter("To 2").unwrap().packets, 2);
+        assert_eq!(buckets[1].filter("To 2").unwrap().packets, 2);
+    }
+
+    #[test]
+    fn sparse_intervals() {
+        let session = session();
+        let mut graph = IoGraph::new(Duration::from_millis(1)).start(Timestamp::new(100, 0));
+        // Before the start, then a gap of a hundred years of intervals
+        for ts in [
+            Timestamp::new(99, 0),
+            Timestamp::new(100, 500_000),
+            Timestamp::new(3_155_760_100, 2_500_000),
+        ] {
+            let (_, pdu) = session
+                .table_dissect::<LinkTypeTable>(&LinkType::USER0, &[1, 2, 0], None)
+                .unwrap();
+            graph.add(&Packet::new(ts, pdu, None, None, None));
+        }
+        let starts: Vec<_> = graph.buckets().map(|bucket| bucket.start).collect();
+        assert_eq!(
+            starts,
+            [
+                Timestamp::new(100, 0),
+                Timestamp::new(3_155_760_100, 2_000_000)
+            ]
+        );
+    }
+}
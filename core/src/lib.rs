@@ -1,24 +1,65 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(all(target_os = "linux", feature = "afpacket"))]
+mod af_packet;
+mod annotate;
+mod combine;
 mod device;
 #[cfg(feature = "pcaprs")]
 mod device_injector;
 #[cfg(feature = "pcaprs")]
 mod device_sniffer;
+mod diff;
+#[cfg(feature = "pcaprs")]
+mod discover;
 mod dissection;
 pub(crate) mod dump;
+mod error_pdu;
+mod extensions;
+mod fanout;
+mod field_map;
+mod flow_export;
+mod hex_dump;
+mod io_graph;
 mod link_type;
+#[cfg(feature = "maxmind")]
+mod maxmind;
+mod memory;
+mod merge;
 mod packet;
+mod packet_meta;
+mod pdml_dump;
 mod pdu;
+mod plugin;
+mod pool;
+mod query;
 mod raw_pdu;
+mod replay;
+mod resolve;
+#[cfg(feature = "json")]
+mod serde_dump;
 mod session;
+mod sink;
 mod sniff;
+mod stream;
+mod summary;
+mod timestamp;
 mod transmit;
+mod validation;
+#[cfg(feature = "wasm-plugins")]
+mod wasm_plugin;
 
 pub use ctor;
 pub use paste;
 
-pub use device::{ConnectionStatus, Device, DeviceBuilder, DeviceIpv4, DeviceIpv6};
+#[cfg(all(target_os = "linux", feature = "afpacket"))]
+pub use af_packet::{AfPacketConfig, AfPacketSniffer};
+
+pub use annotate::{AnnotatingDumper, Annotation, PacketAnnotator};
+
+pub use combine::{Chain, SniffExt, SniffStream};
+
+pub use device::{CaptureStats, ConnectionStatus, Device, DeviceBuilder, DeviceIpv4, DeviceIpv6};
 
 #[cfg(feature = "pcaprs")]
 pub use device::AllDevicesIter;
@@ -27,14 +68,45 @@ pub use device::AllDevicesIter;
 pub use device_injector::DeviceInjector;
 
 #[cfg(feature = "pcaprs")]
-pub use device_sniffer::{DeviceSniffer, DeviceSnifferConfig, DeviceTsPrecision, DeviceTsType};
+pub use device_sniffer::{
+    DeviceDirection, DeviceSniffer, DeviceSnifferConfig, DeviceTsPrecision, DeviceTsType,
+};
+
+#[cfg(feature = "npcap")]
+pub use device_sniffer::{RemoteSnifferAuth, RemoteSnifferConfig, RemoteSnifferSampling};
+
+#[cfg(feature = "pcaprs")]
+pub use discover::{discover_ipv4, discover_ipv6};
+
+pub use diff::{diff, FieldDiff};
 
 pub use dissection::{
-    AnyDissector, DResult, Dissect, DissectError, DissectParser, Dissector, DissectorTable,
-    DissectorTableParser, Priority,
+    decode_be_parser, decode_le_parser, decode_parser, decode_with, AnyDissector, DResult,
+    DecodeCursor, DecodeResult, Dissect, DissectError, DissectMode, DissectParser, Dissector,
+    DissectorTable, DissectorTableParser, Priority, StrDissectorTable,
 };
 
-pub use dump::{Dump, DumpValue, Dumper, ListDumper, LogDumper, NodeDumper};
+pub use dump::{ByteDumpFormatter, Dump, DumpValue, Dumper, ListDumper, LogDumper, NodeDumper};
+
+pub use error_pdu::ErrorPdu;
+
+pub use extensions::Extensions;
+
+pub use hex_dump::HexDumper;
+
+pub use io_graph::{Bucket, IoCounter, IoGraph};
+
+#[cfg(feature = "json")]
+pub use serde_dump::{JsonDumper, SerdeDumper};
+
+#[cfg(feature = "json")]
+pub use serde_json;
+
+pub use fanout::{ConsumerId, FanoutReceiver, FanoutStats, OverflowPolicy, PacketFanout};
+
+pub use field_map::{FieldMap, FieldRange};
+
+pub use flow_export::{FlowRecord, IpfixExporter};
 
 pub use sniffle_address::*;
 
@@ -43,21 +115,70 @@ pub use link_type::{LinkType, LinkTypeTable};
 #[doc(hidden)]
 pub use link_type::_register_link_layer_pdu;
 
-pub use packet::Packet;
+#[cfg(feature = "maxmind")]
+pub use maxmind::MaxMindDb;
+
+pub use bytes::Bytes;
+
+pub use memory::{LruMap, MemoryAccount, MemoryBudget, MemoryStats};
+
+pub use merge::{MergeHandle, MergePolicy, MergeSniffer, SourceId};
+
+pub use packet::{Packet, PacketBuilder};
+
+pub use packet_meta::{Direction, PacketHash, PacketMeta, ReceptionType};
+
+pub use pdml_dump::PdmlDumper;
 
 pub use pdu::{AnyPdu, BasePdu, Pdu, PduExt, PduType, TempPdu};
 
-pub use raw_pdu::RawPdu;
+pub use plugin::{
+    PluginContext, PluginDissector, PluginField, PluginGuest, PluginPdu, PluginValue,
+    PLUGIN_ABI_VERSION,
+};
+
+pub use pool::{Pool, PoolStats, Poolable, Pooled};
+
+pub use query::{BpfFilter, Field, FieldValue, Query, QueryError};
+
+#[doc(hidden)]
+pub use query::_register_field;
+
+pub use raw_pdu::{RawData, RawPdu};
+
+pub use replay::{Replay, ReplaySummary};
 
-pub use session::{Session, Virtual};
+#[cfg(unix)]
+pub use resolve::SystemReverseLookup;
+pub use resolve::{NameResolver, NameSource, ResolvingDumper, ReverseLookup};
+
+pub use session::{Session, SessionBuilder, Virtual};
 
 #[doc(hidden)]
 pub use session::{_register_dissector, _register_dissector_table};
 
+pub use sink::{PacketSink, SinkFormat, SummarySink};
+
 pub use sniff::{RawPacket, Sniff, SniffRaw, Sniffer};
 
+pub use stream::{BodyTracker, StreamDissect, StreamDissector, StreamEvent};
+
+pub use summary::{
+    Conversation, Endpoint, EndpointAddress, EndpointKey, ProtocolNode, TrafficSummary,
+};
+
+#[doc(hidden)]
+pub use summary::_register_conversation;
+
+pub use timestamp::{Timestamp, TimestampPrecision};
+
 pub use transmit::Transmit;
 
+pub use validation::{Issue, Validation, ValidationReport, Validator};
+
+#[cfg(feature = "wasm-plugins")]
+pub use wasm_plugin::WasmPlugin;
+
 #[derive(thiserror::Error, Debug)]
 #[non_exhaustive]
 pub enum Error {
@@ -65,11 +186,18 @@ pub enum Error {
     MalformedCapture,
     #[error("Packet does not have a valid link type")]
     UnknownLinkType,
+    #[error("Malformed stream")]
+    MalformedStream,
+    #[error("Stream buffer limit exceeded")]
+    StreamBufferFull,
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[cfg(feature = "pcaprs")]
     #[error(transparent)]
     Pcap(#[from] pcaprs::PcapError),
+    #[cfg(feature = "wasm-plugins")]
+    #[error("WebAssembly plugin error: {0}")]
+    Wasm(wasmtime::Error),
     #[error(transparent)]
     User(#[from] Box<dyn std::error::Error + Send + 'static>),
 }
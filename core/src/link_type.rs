@@ -1,4 +1,7 @@
-use super::{dissector_table, register_dissector_table, Pdu, PduExt, PduType};
+use super::{
+    dissector_table, register_dissector_table, AnyPdu, DResult, Dissector, DissectorTable, Pdu,
+    PduExt, PduType, Priority, Session, TempPdu,
+};
 use lazy_static::*;
 #[cfg(feature = "pcaprs")]
 pub use pcaprs::ParseLinkTypeError;
@@ -62,6 +65,38 @@ impl LinkType {
         LINK_TYPE_PDUS.read().get(&pdu.pdu_type()).copied()
     }
 
+    /// Associates a link layer Pdu type with a link type at runtime, as
+    /// `register_link_layer_pdu!` does at startup. Returns the link type
+    /// the Pdu was previously associated with, if any.
+    pub fn register_pdu<P: Pdu>(link_type: LinkType) -> Option<LinkType> {
+        LINK_TYPE_PDUS.write().insert(PduType::of::<P>(), link_type)
+    }
+
+    /// Removes the link type association for a link layer Pdu type.
+    pub fn unregister_pdu<P: Pdu>() -> Option<LinkType> {
+        LINK_TYPE_PDUS.write().remove(&PduType::of::<P>())
+    }
+
+    /// Returns `USER0` through `USER15` for `n` of 0 through 15.
+    pub fn user(n: u8) -> Option<Self> {
+        (n < 16).then(|| Self(Self::USER0.0 + u16::from(n)))
+    }
+
+    /// True for `USER0` through `USER15`, which are reserved for private
+    /// use and have no standard dissector.
+    pub fn is_user(&self) -> bool {
+        (Self::USER0.0..=Self::USER15.0).contains(&self.0)
+    }
+
+    /// Returns all registered link layer Pdu types and their link types.
+    pub fn registered_pdus() -> Vec<(PduType, LinkType)> {
+        LINK_TYPE_PDUS
+            .read()
+            .iter()
+            .map(|(pdu_type, link_type)| (*pdu_type, *link_type))
+            .collect()
+    }
+
     link_types::for_each_link_type!(link_type);
 }
 
@@ -94,6 +129,71 @@ impl From<LinkType> for link_types::LinkType {
 dissector_table!(pub LinkTypeTable, LinkType);
 register_dissector_table!(LinkTypeTable);
 
+/// Dissects a packet with the dissectors of another link type.
+struct LinkTypeAlias(LinkType);
+
+impl Dissector for LinkTypeAlias {
+    type Out = AnyPdu;
+
+    fn dissect<'a>(
+        &self,
+        buffer: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self::Out> {
+        session.table_dissect::<LinkTypeTable>(&self.0, buffer, parent)
+    }
+}
+
+impl LinkTypeTable {
+    /// Binds a user link type (`USER0` through `USER15`) to `dissector`,
+    /// replacing anything previously loaded for it. Returns false, doing
+    /// nothing, if `user` is not a user link type.
+    pub fn bind_user<D: Dissector + Send + Sync + 'static>(
+        &mut self,
+        user: LinkType,
+        dissector: D,
+    ) -> bool {
+        if !user.is_user() {
+            return false;
+        }
+        self.unload(&user);
+        self.load(user, Priority(0), dissector);
+        true
+    }
+
+    /// Binds a user link type to the dissectors of `target`, so that
+    /// packets of the user link type are dissected as if they were of
+    /// `target`. Returns false if `user` is not a user link type, or is
+    /// `target`.
+    pub fn bind_user_to(&mut self, user: LinkType, target: LinkType) -> bool {
+        user != target && self.bind_user(user, LinkTypeAlias(target))
+    }
+
+    /// Binds a user link type to the link type whose dissector produces the
+    /// Pdu named `pdu_name`, such as `"EthernetII"`. The name is compared
+    /// to the last path segment of the Pdu's type name, ignoring case.
+    /// Returns the link type bound to, or `None` if no loaded dissector
+    /// matches or `user` is not a user link type.
+    pub fn bind_user_by_name(&mut self, user: LinkType, pdu_name: &str) -> Option<LinkType> {
+        let mut targets: Vec<LinkType> = self
+            .params()
+            .copied()
+            .filter(|link_type| !link_type.is_user())
+            .filter(|link_type| {
+                self.entries(link_type).any(|(_, dissector)| {
+                    let name = dissector.pdu_type_name();
+                    let name = name.rsplit("::").next().unwrap_or(name);
+                    name.eq_ignore_ascii_case(pdu_name)
+                })
+            })
+            .collect();
+        targets.sort_by_key(|link_type| link_type.0);
+        let target = targets.first().copied()?;
+        self.bind_user_to(user, target).then_some(target)
+    }
+}
+
 pub fn _register_link_layer_pdu<P: Pdu>(link_type: LinkType) {
     if LINK_TYPE_PDUS
         .write()
@@ -116,3 +216,46 @@ macro_rules! register_link_layer_pdu {
         }
     };
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RawPdu;
+
+    fn raw<'a>(buf: &'a [u8], _: &Session, _: Option<TempPdu<'_>>) -> DResult<'a, RawPdu> {
+        Ok((&buf[buf.len()..], RawPdu::new(buf.to_vec())))
+    }
+
+    #[test]
+    fn bind_user_link_types() {
+        assert_eq!(LinkType::user(15), Some(LinkType::USER15));
+        assert_eq!(LinkType::user(16), None);
+        assert!(!LinkType::ETHERNET.is_user());
+
+        let mut session = Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(LinkType::ETHERNET, Priority(0), raw)
+            .build();
+        let data = [1u8, 2, 3];
+        assert!(session
+            .table_dissect::<LinkTypeTable>(&LinkType::USER3, &data[..], None)
+            .is_err());
+
+        let table = session.get_mut::<LinkTypeTable>().unwrap();
+        assert!(!table.bind_user(LinkType::ETHERNET, raw));
+        assert!(!table.bind_user_to(LinkType::USER3, LinkType::USER3));
+        assert_eq!(table.bind_user_by_name(LinkType::USER3, "Ipv4"), None);
+        assert_eq!(
+            table.bind_user_by_name(LinkType::USER3, "rawpdu"),
+            Some(LinkType::ETHERNET)
+        );
+        assert!(table.bind_user(LinkType::USER4, raw));
+
+        for user in [LinkType::USER3, LinkType::USER4] {
+            let (_, pdu) = session
+                .table_dissect::<LinkTypeTable>(&user, &data[..], None)
+                .unwrap();
+            assert_eq!(&pdu.downcast_ref::<RawPdu>().unwrap().data()[..], &data[..]);
+        }
+    }
+}
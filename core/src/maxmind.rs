@@ -0,0 +1,481 @@
+use super::{Annotation, Error, PacketAnnotator};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+const METADATA_MAX_LEN: usize = 128 * 1024;
+const MAX_DEPTH: usize = 32;
+/// The most values decoded for one record, counting each use of a pointer
+/// target, so that pointers reused many times can't expand a small database
+/// into a huge record.
+const MAX_VALUES: usize = 64 * 1024;
+
+/// A MaxMind DB file, such as GeoLite2 Country or GeoLite2 ASN, used as a
+/// `PacketAnnotator`.
+///
+/// Country codes are read from `country.iso_code`, falling back to
+/// `registered_country.iso_code`, and autonomous systems from
+/// `autonomous_system_number` and `autonomous_system_organization`. To
+/// annotate with both a country and an ASN database, use a pair of
+/// `MaxMindDb`s as the annotator.
+#[derive(Debug, Clone)]
+pub struct MaxMindDb {
+    data: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u16,
+    data_start: usize,
+    ipv4_start: usize,
+    database_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Float(f64),
+    Bytes,
+    UInt(u128),
+    Int(i32),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Bool(bool),
+}
+
+fn malformed() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "malformed MaxMind DB",
+    ))
+}
+
+impl MaxMindDb {
+    pub async fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        Self::from_bytes(tokio::fs::read(path).await?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        let search = data.len().saturating_sub(METADATA_MAX_LEN);
+        let meta_start = data[search..]
+            .windows(METADATA_MARKER.len())
+            .rposition(|window| window == METADATA_MARKER)
+            .map(|pos| search + pos + METADATA_MARKER.len())
+            .ok_or_else(malformed)?;
+        let (meta, _) = Decoder::new(&data[meta_start..]).decode(0, 0)?;
+        let uint = |name: &str| match get(&meta, &[name]) {
+            Some(Value::UInt(value)) => usize::try_from(*value).map_err(|_| malformed()),
+            _ => Err(malformed()),
+        };
+        let node_count = uint("node_count")?;
+        let record_size = uint("record_size")?;
+        let ip_version = uint("ip_version")? as u16;
+        if !matches!(record_size, 24 | 28 | 32) || !matches!(ip_version, 4 | 6) {
+            return Err(malformed());
+        }
+        let tree_len = node_count
+            .checked_mul(record_size / 4)
+            .ok_or_else(malformed)?;
+        let data_start = tree_len.checked_add(16).ok_or_else(malformed)?;
+        if data_start > meta_start {
+            return Err(malformed());
+        }
+        let database_type = match get(&meta, &["database_type"]) {
+            Some(Value::Text(name)) => name.clone(),
+            _ => String::new(),
+        };
+        let mut db = Self {
+            data,
+            node_count,
+            record_size,
+            ip_version,
+            data_start,
+            ipv4_start: 0,
+            database_type,
+        };
+        if ip_version == 6 {
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= node_count {
+                    break;
+                }
+                node = db.record(node, false);
+            }
+            db.ipv4_start = node;
+        }
+        Ok(db)
+    }
+
+    /// The type of the database, such as `"GeoLite2-Country"`.
+    pub fn database_type(&self) -> &str {
+        &self.database_type[..]
+    }
+
+    /// Looks up the annotation of `addr`. Returns `None` if the database has
+    /// no record of the address or the record is malformed.
+    pub fn lookup(&self, addr: IpAddr) -> Option<Annotation> {
+        let (bits, start): (Vec<bool>, usize) = match addr {
+            IpAddr::V4(addr) => (to_bits(&addr.octets()), self.ipv4_start),
+            IpAddr::V6(addr) if self.ip_version == 6 => (to_bits(&addr.octets()), 0),
+            IpAddr::V6(addr) => (to_bits(&addr.to_ipv4_mapped()?.octets()), 0),
+        };
+        let mut node = start;
+        for bit in bits {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.record(node, bit);
+        }
+        if node <= self.node_count {
+            return None;
+        }
+        let offset = (node - self.node_count).checked_sub(16)?;
+        let section = self.data.get(self.data_start..)?;
+        let (record, _) = Decoder::new(section).decode(offset, 0).ok()?;
+        let text = |path: &[&str]| match get(&record, path) {
+            Some(Value::Text(text)) => Some(text.clone()),
+            _ => None,
+        };
+        let annot = Annotation {
+            country: text(&["country", "iso_code"])
+                .or_else(|| text(&["registered_country", "iso_code"])),
+            asn: match get(&record, &["autonomous_system_number"]) {
+                Some(Value::UInt(asn)) => u32::try_from(*asn).ok(),
+                _ => None,
+            },
+            as_org: text(&["autonomous_system_organization"]),
+        };
+        Some(annot)
+    }
+
+    fn record(&self, node: usize, right: bool) -> usize {
+        let len = self.record_size / 4;
+        let bytes = match self.data.get(node * len..node * len + len) {
+            Some(bytes) => bytes,
+            None => return self.node_count,
+        };
+        let be = |bytes: &[u8]| bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        match (self.record_size, right) {
+            (24, false) => be(&bytes[0..3]),
+            (24, true) => be(&bytes[3..6]),
+            (28, false) => ((bytes[3] as usize & 0xf0) << 20) | be(&bytes[0..3]),
+            (28, true) => ((bytes[3] as usize & 0x0f) << 24) | be(&bytes[4..7]),
+            (_, false) => be(&bytes[0..4]),
+            (_, true) => be(&bytes[4..8]),
+        }
+    }
+}
+
+impl PacketAnnotator for MaxMindDb {
+    fn annotate(&self, addr: IpAddr) -> Option<Annotation> {
+        self.lookup(addr).filter(|annot| !annot.is_empty())
+    }
+}
+
+fn to_bits(octets: &[u8]) -> Vec<bool> {
+    octets
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| b & (1 << i) != 0))
+        .collect()
+}
+
+fn get<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+        _ => None,
+    })
+}
+
+/// Decodes values of a data section. Each pointer target is decoded once,
+/// and the values decoded, including reused pointer targets, are limited to
+/// `MAX_VALUES`.
+struct Decoder<'a> {
+    section: &'a [u8],
+    /// Decoded pointer targets, with the number of values in each
+    pointers: HashMap<usize, (Value, usize)>,
+    values: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(section: &'a [u8]) -> Self {
+        Self {
+            section,
+            pointers: HashMap::new(),
+            values: 0,
+        }
+    }
+
+    fn count(&mut self, values: usize) -> Result<(), Error> {
+        self.values = self.values.saturating_add(values);
+        if self.values > MAX_VALUES {
+            return Err(malformed());
+        }
+        Ok(())
+    }
+
+    /// Decodes the value at `offset`, returning the value and the offset
+    /// following it.
+    fn decode(&mut self, offset: usize, depth: usize) -> Result<(Value, usize), Error> {
+        if depth > MAX_DEPTH {
+            return Err(malformed());
+        }
+        let section = self.section;
+        let byte = |pos: usize| section.get(pos).copied().ok_or_else(malformed);
+        let be = |start: usize, len: usize| -> Result<u128, Error> {
+            let bytes = section.get(start..start + len).ok_or_else(malformed)?;
+            Ok(bytes.iter().fold(0u128, |acc, b| (acc << 8) | *b as u128))
+        };
+
+        let ctrl = byte(offset)?;
+        let mut pos = offset + 1;
+        let mut kind = ctrl >> 5;
+        if kind == 1 {
+            let ss = ((ctrl >> 3) & 0x3) as usize;
+            let vvv = (ctrl & 0x7) as u128;
+            let target = match ss {
+                0 => (vvv << 8) | be(pos, 1)?,
+                1 => ((vvv << 16) | be(pos, 2)?) + 2048,
+                2 => ((vvv << 24) | be(pos, 3)?) + 526_336,
+                _ => be(pos, 4)?,
+            };
+            let target = usize::try_from(target).map_err(|_| malformed())?;
+            let next = pos + ss + 1;
+            if let Some((value, values)) = self.pointers.get(&target) {
+                let (value, values) = (value.clone(), *values);
+                self.count(values)?;
+                return Ok((value, next));
+            }
+            // A pointer to a pointer is not valid
+            if byte(target)? >> 5 == 1 {
+                return Err(malformed());
+            }
+            let before = self.values;
+            let (value, _) = self.decode(target, depth + 1)?;
+            self.pointers
+                .insert(target, (value.clone(), self.values - before));
+            return Ok((value, next));
+        }
+        self.count(1)?;
+        if kind == 0 {
+            kind = byte(pos)?.checked_add(7).ok_or_else(malformed)?;
+            pos += 1;
+        }
+        let size = match ctrl & 0x1f {
+            29 => {
+                pos += 1;
+                29 + be(pos - 1, 1)? as usize
+            }
+            30 => {
+                pos += 2;
+                285 + be(pos - 2, 2)? as usize
+            }
+            31 => {
+                pos += 3;
+                65_821 + be(pos - 3, 3)? as usize
+            }
+            size => size as usize,
+        };
+
+        let bytes = |pos: usize| section.get(pos..pos + size).ok_or_else(malformed);
+        match kind {
+            2 => {
+                let text = std::str::from_utf8(bytes(pos)?).map_err(|_| malformed())?;
+                Ok((Value::Text(text.into()), pos + size))
+            }
+            3 if size == 8 => Ok((Value::Float(f64::from_bits(be(pos, 8)? as u64)), pos + 8)),
+            4 => Ok((Value::Bytes, pos + bytes(pos)?.len())),
+            5 | 6 | 9 | 10 if size <= 16 => Ok((Value::UInt(be(pos, size)?), pos + size)),
+            7 => {
+                let mut entries = Vec::new();
+                for _ in 0..size {
+                    let (key, next) = self.decode(pos, depth + 1)?;
+                    let (value, next) = self.decode(next, depth + 1)?;
+                    pos = next;
+                    match key {
+                        Value::Text(key) => entries.push((key, value)),
+                        _ => return Err(malformed()),
+                    }
+                }
+                Ok((Value::Map(entries), pos))
+            }
+            8 if size <= 4 => {
+                let value = be(pos, size)? as u32;
+                Ok((Value::Int(value as i32), pos + size))
+            }
+            11 => {
+                let mut items = Vec::new();
+                for _ in 0..size {
+                    let (item, next) = self.decode(pos, depth + 1)?;
+                    pos = next;
+                    items.push(item);
+                }
+                Ok((Value::Array(items), pos))
+            }
+            14 => Ok((Value::Bool(size != 0), pos)),
+            15 if size == 4 => {
+                let value = f32::from_bits(be(pos, 4)? as u32);
+                Ok((Value::Float(value.into()), pos + 4))
+            }
+            _ => Err(malformed()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn text(out: &mut Vec<u8>, text: &str) {
+        match text.len() {
+            len @ 0..=28 => out.push((2 << 5) | len as u8),
+            len => out.extend_from_slice(&[(2 << 5) | 29, (len - 29) as u8]),
+        }
+        out.extend_from_slice(text.as_bytes());
+    }
+
+    fn uint(out: &mut Vec<u8>, kind: u8, value: u32) {
+        out.push((kind << 5) | 4);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn pointer(out: &mut Vec<u8>, target: u16) {
+        assert!(target < 2048);
+        out.extend_from_slice(&[(1 << 5) | (target >> 8) as u8, target as u8]);
+    }
+
+    fn metadata(out: &mut Vec<u8>, node_count: u32) {
+        out.extend_from_slice(METADATA_MARKER);
+        out.push((7 << 5) | 4);
+        text(out, "node_count");
+        uint(out, 6, node_count);
+        text(out, "record_size");
+        uint(out, 5, 24);
+        text(out, "ip_version");
+        uint(out, 5, 4);
+        text(out, "database_type");
+        text(out, "Test");
+    }
+
+    /// A database of one node: addresses in 0.0.0.0/1 have the record at
+    /// `offset` of `data`, others have no record.
+    fn database(data: &[u8], offset: u32) -> MaxMindDb {
+        let left = (17 + offset).to_be_bytes();
+        let mut db = vec![left[1], left[2], left[3], 0, 0, 1];
+        db.extend_from_slice(&[0; 16]);
+        db.extend_from_slice(data);
+        metadata(&mut db, 1);
+        MaxMindDb::from_bytes(db).unwrap()
+    }
+
+    const ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+    #[test]
+    fn maxmind_lookup() {
+        let mut data = vec![(7 << 5) | 3];
+        text(&mut data, "country");
+        data.push((7 << 5) | 1);
+        text(&mut data, "iso_code");
+        text(&mut data, "US");
+        text(&mut data, "autonomous_system_number");
+        uint(&mut data, 6, 64512);
+        text(&mut data, "autonomous_system_organization");
+        // The "US" string, at offset 19
+        pointer(&mut data, 19);
+
+        let db = database(&data[..], 0);
+        assert_eq!(db.database_type(), "Test");
+        assert_eq!(
+            db.annotate(ADDR.into()),
+            Some(Annotation {
+                country: Some("US".into()),
+                asn: Some(64512),
+                as_org: Some("US".into()),
+            })
+        );
+        assert_eq!(db.annotate(Ipv4Addr::new(192, 168, 0, 1).into()), None);
+    }
+
+    #[test]
+    fn missing_metadata() {
+        assert!(MaxMindDb::from_bytes(Vec::new()).is_err());
+        assert!(MaxMindDb::from_bytes(vec![0; 64]).is_err());
+    }
+
+    #[test]
+    fn truncated_tree() {
+        // The metadata claims more nodes than there are bytes before it
+        let mut db = vec![0, 0, 17, 0, 0, 1];
+        db.extend_from_slice(&[0; 16]);
+        metadata(&mut db, 100);
+        assert!(MaxMindDb::from_bytes(db).is_err());
+    }
+
+    #[test]
+    fn record_outside_data() {
+        let mut data = vec![(7 << 5) | 1];
+        text(&mut data, "asn");
+        uint(&mut data, 6, 1);
+        assert_eq!(database(&data[..], 1000).lookup(ADDR.into()), None);
+    }
+
+    #[test]
+    fn truncated_record() {
+        let mut data = vec![(7 << 5) | 2];
+        text(&mut data, "country");
+        data.push((2 << 5) | 20);
+        data.extend_from_slice(b"short");
+        assert_eq!(database(&data[..], 0).lookup(ADDR.into()), None);
+    }
+
+    #[test]
+    fn bad_pointers() {
+        // A pointer past the end of the data section
+        let mut data = vec![(7 << 5) | 1];
+        text(&mut data, "country");
+        pointer(&mut data, 1000);
+        assert_eq!(database(&data[..], 0).lookup(ADDR.into()), None);
+
+        // A pointer to itself
+        let mut data = vec![(7 << 5) | 1];
+        text(&mut data, "country");
+        pointer(&mut data, 9);
+        assert_eq!(database(&data[..], 0).lookup(ADDR.into()), None);
+    }
+
+    #[test]
+    fn reused_pointers() {
+        // Each level is an array of four pointers to the level before, so the
+        // record at the top would expand to 4^10 values.
+        let mut data = Vec::new();
+        uint(&mut data, 6, 1);
+        let mut level = 0;
+        for _ in 0..10 {
+            let next = data.len() as u16;
+            // An array of four, with the extended type byte
+            data.extend_from_slice(&[4, 11 - 7]);
+            for _ in 0..4 {
+                pointer(&mut data, level);
+            }
+            level = next;
+        }
+        let record = data.len() as u32;
+        data.push((7 << 5) | 1);
+        text(&mut data, "country");
+        pointer(&mut data, level);
+        assert_eq!(database(&data[..], record).lookup(ADDR.into()), None);
+
+        // Reusing a pointer target within the limit is fine
+        let mut data = Vec::new();
+        text(&mut data, "US");
+        let record = data.len() as u32;
+        data.push((7 << 5) | 2);
+        text(&mut data, "country");
+        data.push((7 << 5) | 1);
+        text(&mut data, "iso_code");
+        pointer(&mut data, 0);
+        text(&mut data, "autonomous_system_organization");
+        pointer(&mut data, 0);
+        let annot = database(&data[..], record).lookup(ADDR.into()).unwrap();
+        assert_eq!(annot.country.as_deref(), Some("US"));
+        assert_eq!(annot.as_org.as_deref(), Some("US"));
+    }
+}
@@ -0,0 +1,565 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+const UNLIMITED: usize = usize::MAX;
+
+/// Memory budget owned by a `Session`, shared by the stateful subsystems
+/// that keep data across packets, such as stream reassembly and
+/// conversation tracking.
+///
+/// The budget has an optional global limit, and each subsystem has its own
+/// optional limit on top of it. Subsystems charge what they keep to a
+/// `MemoryAccount` taken from the budget with `MemoryBudget::account`, and
+/// usually keep it in an `LruMap`, which evicts the least recently used
+/// entries when a limit is reached. Nothing is limited by default.
+pub struct MemoryBudget {
+    shared: Arc<Shared>,
+    accounts: parking_lot::Mutex<HashMap<&'static str, Arc<AccountState>>>,
+}
+
+struct Shared {
+    limit: AtomicUsize,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+struct AccountState {
+    limit: AtomicUsize,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+    evictions: AtomicU64,
+    evicted_bytes: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// A subsystem's share of a `MemoryBudget`. Clones refer to the same
+/// account, so a subsystem with many independent tables can charge all of
+/// them to one account.
+#[derive(Clone)]
+pub struct MemoryAccount {
+    name: &'static str,
+    shared: Arc<Shared>,
+    state: Arc<AccountState>,
+}
+
+/// Memory usage statistics of a `MemoryBudget` or one of its accounts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Number of bytes currently charged
+    pub used: usize,
+    /// Largest number of bytes charged at once
+    pub peak: usize,
+    pub limit: Option<usize>,
+    /// Number of entries evicted to make room for new ones
+    pub evictions: u64,
+    /// Number of bytes freed by evictions
+    pub evicted_bytes: u64,
+    /// Number of entries dropped because they didn't fit in the budget on
+    /// their own
+    pub rejected: u64,
+}
+
+/// A map that charges the size of its entries to a `MemoryAccount`, and
+/// evicts its least recently used entries when the account or the budget
+/// it belongs to is full.
+///
+/// Entries are only evicted from the map that needs the room, so a map can
+/// fail to make room when the global budget is used up by other
+/// subsystems. Evicted entries are returned to the caller, so they can be
+/// flushed or reported rather than silently lost.
+pub struct LruMap<K: Hash + Eq + Clone, V> {
+    entries: HashMap<K, LruEntry<V>>,
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    used: usize,
+    account: MemoryAccount,
+}
+
+struct LruEntry<V> {
+    value: V,
+    size: usize,
+    tick: u64,
+}
+
+fn to_limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(UNLIMITED)
+}
+
+fn from_limit(limit: usize) -> Option<usize> {
+    (limit != UNLIMITED).then_some(limit)
+}
+
+/// Adds `bytes` to `used`, unless that would go over `limit`.
+fn try_add(used: &AtomicUsize, peak: &AtomicUsize, limit: usize, bytes: usize) -> bool {
+    let res = used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+        used.checked_add(bytes).filter(|used| *used <= limit)
+    });
+    match res {
+        Ok(prev) => {
+            peak.fetch_max(prev + bytes, Ordering::Relaxed);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+impl MemoryBudget {
+    /// Stream and message reassembly
+    pub const REASSEMBLY: &'static str = "reassembly";
+    /// Conversation and endpoint tracking
+    pub const CONVERSATIONS: &'static str = "conversations";
+    /// IP fragment reassembly
+    pub const DEFRAGMENTATION: &'static str = "defragmentation";
+
+    /// Creates a budget without a global limit.
+    pub fn new() -> Self {
+        Self::with_limit(None)
+    }
+
+    /// Creates a budget that allows at most `limit` bytes to be charged
+    /// across all subsystems.
+    pub fn with_limit(limit: Option<usize>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                limit: AtomicUsize::new(to_limit(limit)),
+                used: AtomicUsize::new(0),
+                peak: AtomicUsize::new(0),
+            }),
+            accounts: parking_lot::Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.shared.limit.store(to_limit(limit), Ordering::Relaxed);
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        from_limit(self.shared.limit.load(Ordering::Relaxed))
+    }
+
+    /// Limits the memory charged by the subsystem `name`, such as
+    /// `MemoryBudget::REASSEMBLY`. Lowering a limit does not evict
+    /// anything until the subsystem next needs room.
+    pub fn set_subsystem_limit(&mut self, name: &'static str, limit: Option<usize>) {
+        self.state(name)
+            .limit
+            .store(to_limit(limit), Ordering::Relaxed);
+    }
+
+    pub fn subsystem_limit(&self, name: &str) -> Option<usize> {
+        self.accounts
+            .lock()
+            .get(name)
+            .and_then(|state| from_limit(state.limit.load(Ordering::Relaxed)))
+    }
+
+    /// The account of the subsystem `name`, which is created without a
+    /// limit of its own if it doesn't exist yet.
+    pub fn account(&self, name: &'static str) -> MemoryAccount {
+        MemoryAccount {
+            name,
+            shared: self.shared.clone(),
+            state: self.state(name),
+        }
+    }
+
+    fn state(&self, name: &'static str) -> Arc<AccountState> {
+        self.accounts
+            .lock()
+            .entry(name)
+            .or_insert_with(|| {
+                Arc::new(AccountState {
+                    limit: AtomicUsize::new(UNLIMITED),
+                    used: AtomicUsize::new(0),
+                    peak: AtomicUsize::new(0),
+                    evictions: AtomicU64::new(0),
+                    evicted_bytes: AtomicU64::new(0),
+                    rejected: AtomicU64::new(0),
+                })
+            })
+            .clone()
+    }
+
+    /// Usage across all subsystems. Evictions and rejections are the totals
+    /// of all subsystems.
+    pub fn stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats {
+            used: self.shared.used.load(Ordering::Relaxed),
+            peak: self.shared.peak.load(Ordering::Relaxed),
+            limit: self.limit(),
+            ..Default::default()
+        };
+        for state in self.accounts.lock().values() {
+            let sub = state.stats();
+            stats.evictions += sub.evictions;
+            stats.evicted_bytes += sub.evicted_bytes;
+            stats.rejected += sub.rejected;
+        }
+        stats
+    }
+
+    /// Usage of the subsystem `name`, if it has an account.
+    pub fn subsystem_stats(&self, name: &str) -> Option<MemoryStats> {
+        self.accounts.lock().get(name).map(|state| state.stats())
+    }
+
+    /// Usage of every subsystem with an account, sorted by name.
+    pub fn subsystems(&self) -> Vec<(&'static str, MemoryStats)> {
+        let mut subsystems: Vec<_> = self
+            .accounts
+            .lock()
+            .iter()
+            .map(|(name, state)| (*name, state.stats()))
+            .collect();
+        subsystems.sort_by_key(|(name, _)| *name);
+        subsystems
+    }
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("stats", &self.stats())
+            .field("subsystems", &self.subsystems())
+            .finish()
+    }
+}
+
+impl AccountState {
+    fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            used: self.used.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            limit: from_limit(self.limit.load(Ordering::Relaxed)),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            evicted_bytes: self.evicted_bytes.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl MemoryAccount {
+    /// An account of its own budget, without any limits.
+    pub fn unlimited(name: &'static str) -> Self {
+        MemoryBudget::new().account(name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Charges `bytes` to the account. Returns false, charging nothing, if
+    /// that would go over the account's limit or the global limit.
+    pub fn try_reserve(&self, bytes: usize) -> bool {
+        let state = &self.state;
+        let limit = state.limit.load(Ordering::Relaxed);
+        if !try_add(&state.used, &state.peak, limit, bytes) {
+            return false;
+        }
+        let limit = self.shared.limit.load(Ordering::Relaxed);
+        if !try_add(&self.shared.used, &self.shared.peak, limit, bytes) {
+            state.used.fetch_sub(bytes, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Gives back `bytes` previously charged with `try_reserve`.
+    pub fn release(&self, bytes: usize) {
+        self.state.used.fetch_sub(bytes, Ordering::Relaxed);
+        self.shared.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Records that an entry of `bytes` bytes was evicted to make room.
+    pub fn record_eviction(&self, bytes: usize) {
+        self.state.evictions.fetch_add(1, Ordering::Relaxed);
+        self.state
+            .evicted_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records that an entry was dropped because there was no room for it.
+    pub fn record_rejection(&self) {
+        self.state.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        self.state.stats()
+    }
+}
+
+impl std::fmt::Debug for MemoryAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryAccount")
+            .field("name", &self.name)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> LruMap<K, V> {
+    pub fn new(account: MemoryAccount) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            used: 0,
+            account,
+        }
+    }
+
+    pub fn account(&self) -> &MemoryAccount {
+        &self.account
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of bytes charged for the entries of this map.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Looks up an entry without marking it as used.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Looks up an entry, marking it as the most recently used.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        self.order.insert(tick, key.clone());
+        entry.tick = tick;
+        Some(&mut entry.value)
+    }
+
+    /// Inserts an entry of `size` bytes, replacing any entry with the same
+    /// key, and returns the entries evicted to make room for it. If the
+    /// entry doesn't fit even after evicting everything else, it is not
+    /// inserted, and is returned along with the evicted entries.
+    pub fn insert(&mut self, key: K, value: V, size: usize) -> Vec<(K, V)> {
+        self.remove(&key);
+        let mut evicted = Vec::new();
+        if !self.make_room(size, None, &mut evicted) {
+            self.account.record_rejection();
+            evicted.push((key, value));
+            return evicted;
+        }
+        let tick = self.next_tick();
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, LruEntry { value, size, tick });
+        self.used += size;
+        evicted
+    }
+
+    /// Changes the size charged for the entry `key`, after its value grew or
+    /// shrank, and marks it as the most recently used. Returns the entries
+    /// evicted to make room. If the entry no longer fits on its own, it is
+    /// removed and returned last.
+    pub fn resize(&mut self, key: &K, size: usize) -> Vec<(K, V)> {
+        let mut evicted = Vec::new();
+        let old = match self.entries.get(key) {
+            Some(entry) => entry.size,
+            None => return evicted,
+        };
+        self.get_mut(key);
+        if size <= old {
+            self.account.release(old - size);
+            self.used -= old - size;
+        } else if self.make_room(size - old, Some(key), &mut evicted) {
+            self.used += size - old;
+        } else {
+            self.account.record_rejection();
+            if let Some(value) = self.remove(key) {
+                evicted.push((key.clone(), value));
+            }
+            return evicted;
+        }
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.size = size;
+        }
+        evicted
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.account.release(entry.size);
+        self.used -= entry.size;
+        Some(entry.value)
+    }
+
+    /// Removes the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let (_, key) = self.order.pop_first()?;
+        let entry = self.entries.remove(&key)?;
+        self.account.release(entry.size);
+        self.used -= entry.size;
+        Some((key, entry.value))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, entry)| (key, &entry.value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values().map(|entry| &entry.value)
+    }
+
+    pub fn clear(&mut self) {
+        self.account.release(self.used);
+        self.used = 0;
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Reserves `size` bytes, evicting the least recently used entries
+    /// other than `keep` until they fit.
+    fn make_room(&mut self, size: usize, keep: Option<&K>, evicted: &mut Vec<(K, V)>) -> bool {
+        while !self.account.try_reserve(size) {
+            let victim = self.order.values().find(|key| Some(*key) != keep).cloned();
+            let key = match victim {
+                Some(key) => key,
+                None => return false,
+            };
+            let size = self.entries.get(&key).map_or(0, |entry| entry.size);
+            if let Some(value) = self.remove(&key) {
+                self.account.record_eviction(size);
+                evicted.push((key, value));
+            }
+        }
+        true
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Default for LruMap<K, V> {
+    fn default() -> Self {
+        Self::new(MemoryAccount::unlimited("default"))
+    }
+}
+
+/// Clones are charged to the same account. The account may go over its
+/// limit until the clone next needs room.
+impl<K: Hash + Eq + Clone, V: Clone> Clone for LruMap<K, V> {
+    fn clone(&self) -> Self {
+        let account = self.account.clone();
+        account.state.used.fetch_add(self.used, Ordering::Relaxed);
+        account.shared.used.fetch_add(self.used, Ordering::Relaxed);
+        Self {
+            entries: self
+                .entries
+                .iter()
+                .map(|(key, entry)| {
+                    (
+                        key.clone(),
+                        LruEntry {
+                            value: entry.value.clone(),
+                            size: entry.size,
+                            tick: entry.tick,
+                        },
+                    )
+                })
+                .collect(),
+            order: self.order.clone(),
+            tick: self.tick,
+            used: self.used,
+            account,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone + std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug for LruMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Drop for LruMap<K, V> {
+    fn drop(&mut self) {
+        self.account.release(self.used);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lru_eviction() {
+        let mut budget = MemoryBudget::new();
+        budget.set_subsystem_limit(MemoryBudget::CONVERSATIONS, Some(30));
+        let mut map = LruMap::new(budget.account(MemoryBudget::CONVERSATIONS));
+        assert!(map.insert(1, "a", 10).is_empty());
+        assert!(map.insert(2, "b", 10).is_empty());
+        assert!(map.insert(3, "c", 10).is_empty());
+        map.get_mut(&1);
+        assert_eq!(map.insert(4, "d", 10), [(2, "b")]);
+        assert_eq!(map.resize(&4, 20), [(3, "c")]);
+        assert_eq!(map.insert(5, "e", 40), [(1, "a"), (4, "d"), (5, "e")]);
+        assert!(map.is_empty());
+
+        let stats = budget.subsystem_stats(MemoryBudget::CONVERSATIONS).unwrap();
+        assert_eq!(
+            stats,
+            MemoryStats {
+                used: 0,
+                peak: 30,
+                limit: Some(30),
+                evictions: 4,
+                evicted_bytes: 50,
+                rejected: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn global_limit() {
+        let budget = MemoryBudget::with_limit(Some(25));
+        let mut a = LruMap::new(budget.account(MemoryBudget::REASSEMBLY));
+        let mut b = LruMap::new(budget.account(MemoryBudget::CONVERSATIONS));
+        assert!(a.insert("x", (), 20).is_empty());
+        // The other subsystem's entries are never evicted
+        assert_eq!(b.insert("y", (), 10), [("y", ())]);
+        assert!(b.insert("z", (), 5).is_empty());
+        assert_eq!(budget.stats().used, 25);
+        drop(a);
+        assert_eq!(budget.stats().used, 5);
+        assert_eq!(budget.stats().rejected, 1);
+        assert_eq!(
+            budget
+                .subsystems()
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>(),
+            [MemoryBudget::CONVERSATIONS, MemoryBudget::REASSEMBLY]
+        );
+    }
+}
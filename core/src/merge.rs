@@ -0,0 +1,446 @@
+use super::{Error, Packet, Sniff};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// How `MergeSniffer` chooses which source the next packet comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Packets are returned in timestamp order. A packet is only returned
+    /// once every source has a packet ready or has finished, so a single
+    /// idle live source stalls the merge.
+    Timestamp,
+    /// Sources with a packet ready take turns.
+    RoundRobin,
+    /// The highest priority source with a packet ready wins. Sources with
+    /// equal priority take turns.
+    Priority,
+}
+
+/// Identifies a source added to a `MergeSniffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceId(u64);
+
+/// Merges packets from any number of `Sniff` implementations, such as
+/// capture files, devices, or simulators, into a single `Sniff`.
+///
+/// Each source is driven by its own tokio task, which sends packets to the
+/// merger over a bounded channel. When the consumer falls behind, a source's
+/// channel fills up and its task stops sniffing until there is room again,
+/// so back-pressure reaches every source instead of packets piling up in
+/// memory.
+///
+/// Sources can be added and removed at any time, either directly or through
+/// a `MergeHandle`. `sniff` returns `None` once there are no sources left.
+/// An error from a source is returned from `sniff`, and that source is
+/// removed.
+pub struct MergeSniffer {
+    policy: MergePolicy,
+    buffer: usize,
+    sources: Vec<Source>,
+    next: usize,
+    ids: Arc<AtomicU64>,
+    cmd_tx: mpsc::UnboundedSender<Command>,
+    cmd_rx: mpsc::UnboundedReceiver<Command>,
+}
+
+/// Adds and removes sources of a `MergeSniffer` from elsewhere, such as
+/// another task.
+#[derive(Clone)]
+pub struct MergeHandle {
+    ids: Arc<AtomicU64>,
+    cmd_tx: mpsc::UnboundedSender<Command>,
+}
+
+struct Source {
+    id: SourceId,
+    priority: i32,
+    rx: mpsc::Receiver<Result<Packet, Error>>,
+    task: JoinHandle<()>,
+    pending: Option<Packet>,
+    done: bool,
+}
+
+enum Command {
+    Add(SourceId, Box<dyn Sniff>, i32),
+    Remove(SourceId),
+}
+
+fn next_id(ids: &AtomicU64) -> SourceId {
+    SourceId(ids.fetch_add(1, Ordering::Relaxed))
+}
+
+impl MergeSniffer {
+    pub fn new(policy: MergePolicy) -> Self {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        Self {
+            policy,
+            buffer: 64,
+            sources: Vec::new(),
+            next: 0,
+            ids: Arc::new(AtomicU64::new(0)),
+            cmd_tx,
+            cmd_rx,
+        }
+    }
+
+    /// Sets how many packets each source may have waiting before it is
+    /// paused. Only affects sources added afterwards. Defaults to 64.
+    pub fn buffer(mut self, packets: usize) -> Self {
+        self.buffer = packets.max(1);
+        self
+    }
+
+    /// Adds a source with priority 0. Must be called from within a tokio
+    /// runtime.
+    pub fn merge<S: Sniff + 'static>(mut self, source: S) -> Self {
+        self.add_source(source);
+        self
+    }
+
+    pub fn policy(&self) -> MergePolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: MergePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn handle(&self) -> MergeHandle {
+        MergeHandle {
+            ids: self.ids.clone(),
+            cmd_tx: self.cmd_tx.clone(),
+        }
+    }
+
+    /// Adds a source with priority 0. Must be called from within a tokio
+    /// runtime.
+    pub fn add_source<S: Sniff + 'static>(&mut self, source: S) -> SourceId {
+        self.add_source_with_priority(source, 0)
+    }
+
+    /// Adds a source. Higher priorities are preferred by
+    /// `MergePolicy::Priority`. Must be called from within a tokio runtime.
+    pub fn add_source_with_priority<S: Sniff + 'static>(
+        &mut self,
+        source: S,
+        priority: i32,
+    ) -> SourceId {
+        let id = next_id(&self.ids);
+        self.spawn(id, Box::new(source), priority);
+        id
+    }
+
+    /// Removes a source, discarding any packets it has waiting. Returns
+    /// false if there is no such source.
+    pub fn remove_source(&mut self, id: SourceId) -> bool {
+        match self.sources.iter().position(|src| src.id == id) {
+            Some(idx) => {
+                self.remove_at(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// IDs of the sources that have not finished yet, highest priority
+    /// first.
+    pub fn sources(&self) -> impl Iterator<Item = SourceId> + '_ {
+        self.sources.iter().map(|src| src.id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    fn spawn(&mut self, id: SourceId, mut source: Box<dyn Sniff>, priority: i32) {
+        let (tx, rx) = mpsc::channel(self.buffer);
+        let task = tokio::spawn(async move {
+            loop {
+                let res = match source.sniff().await {
+                    Ok(Some(pkt)) => Ok(pkt),
+                    Ok(None) => break,
+                    Err(e) => Err(e),
+                };
+                let failed = res.is_err();
+                if tx.send(res).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let src = Source {
+            id,
+            priority,
+            rx,
+            task,
+            pending: None,
+            done: false,
+        };
+        // Keep sources sorted by descending priority, oldest first within a
+        // priority, so the priority policy can scan from the front.
+        let idx = self
+            .sources
+            .iter()
+            .position(|other| other.priority < priority)
+            .unwrap_or(self.sources.len());
+        self.sources.insert(idx, src);
+        if idx < self.next {
+            self.next += 1;
+        }
+    }
+
+    fn remove_at(&mut self, idx: usize) {
+        let src = self.sources.remove(idx);
+        src.task.abort();
+        if idx < self.next {
+            self.next -= 1;
+        }
+        if self.next >= self.sources.len() {
+            self.next = 0;
+        }
+    }
+
+    fn poll_commands(&mut self, cx: &mut Context<'_>) {
+        while let Poll::Ready(Some(cmd)) = self.cmd_rx.poll_recv(cx) {
+            match cmd {
+                Command::Add(id, source, priority) => self.spawn(id, source, priority),
+                Command::Remove(id) => {
+                    let _ = self.remove_source(id);
+                }
+            }
+        }
+    }
+
+    /// Polls source `idx` for a packet, unless it already has one pending.
+    fn fill(&mut self, idx: usize, cx: &mut Context<'_>) -> Result<(), Error> {
+        let src = &mut self.sources[idx];
+        if src.pending.is_some() || src.done {
+            return Ok(());
+        }
+        match src.rx.poll_recv(cx) {
+            Poll::Ready(Some(Ok(pkt))) => src.pending = Some(pkt),
+            Poll::Ready(Some(Err(e))) => {
+                src.done = true;
+                return Err(e);
+            }
+            Poll::Ready(None) => src.done = true,
+            Poll::Pending => {}
+        }
+        Ok(())
+    }
+
+    fn prune(&mut self) {
+        let mut idx = 0;
+        while idx < self.sources.len() {
+            if self.sources[idx].done && self.sources[idx].pending.is_none() {
+                self.remove_at(idx);
+            } else {
+                idx += 1;
+            }
+        }
+    }
+
+    fn take(&mut self, idx: usize) -> Option<Packet> {
+        self.sources[idx].pending.take()
+    }
+
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Packet>, Error>> {
+        self.poll_commands(cx);
+        let res = match self.policy {
+            MergePolicy::Timestamp => self.poll_timestamp(cx),
+            MergePolicy::RoundRobin => self.poll_rotate(cx, 0, self.sources.len()),
+            MergePolicy::Priority => self.poll_priority(cx),
+        };
+        self.prune();
+        match res {
+            Poll::Pending if self.sources.is_empty() => Poll::Ready(Ok(None)),
+            res => res,
+        }
+    }
+
+    fn poll_timestamp(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Packet>, Error>> {
+        let mut ready = true;
+        for idx in 0..self.sources.len() {
+            if let Err(e) = self.fill(idx, cx) {
+                return Poll::Ready(Err(e));
+            }
+            let src = &self.sources[idx];
+            if src.pending.is_none() && !src.done {
+                ready = false;
+            }
+        }
+        if !ready {
+            return Poll::Pending;
+        }
+        let first = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, src)| src.pending.as_ref().map(|pkt| (idx, pkt.timestamp())))
+            .min_by_key(|(_, ts)| *ts)
+            .map(|(idx, _)| idx);
+        match first {
+            Some(idx) => Poll::Ready(Ok(self.take(idx))),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Takes the first ready packet from `sources[start..end]`, starting
+    /// from `self.next` if it is in range.
+    fn poll_rotate(
+        &mut self,
+        cx: &mut Context<'_>,
+        start: usize,
+        end: usize,
+    ) -> Poll<Result<Option<Packet>, Error>> {
+        let count = end - start;
+        let first = if (start..end).contains(&self.next) {
+            self.next
+        } else {
+            start
+        };
+        for i in 0..count {
+            let idx = start + (first - start + i) % count;
+            if let Err(e) = self.fill(idx, cx) {
+                return Poll::Ready(Err(e));
+            }
+            if let Some(pkt) = self.take(idx) {
+                self.next = idx + 1;
+                return Poll::Ready(Ok(Some(pkt)));
+            }
+        }
+        Poll::Pending
+    }
+
+    fn poll_priority(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Packet>, Error>> {
+        let mut start = 0;
+        while start < self.sources.len() {
+            let priority = self.sources[start].priority;
+            let end = self.sources[start..]
+                .iter()
+                .position(|src| src.priority != priority)
+                .map_or(self.sources.len(), |len| start + len);
+            match self.poll_rotate(cx, start, end) {
+                Poll::Pending => start = end,
+                res => return res,
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl MergeHandle {
+    /// Adds a source with priority 0.
+    pub fn add_source<S: Sniff + 'static>(&self, source: S) -> SourceId {
+        self.add_source_with_priority(source, 0)
+    }
+
+    /// Adds a source with the given priority. The source is started the
+    /// next time the `MergeSniffer` is polled.
+    pub fn add_source_with_priority<S: Sniff + 'static>(
+        &self,
+        source: S,
+        priority: i32,
+    ) -> SourceId {
+        let id = next_id(&self.ids);
+        let _ = self
+            .cmd_tx
+            .send(Command::Add(id, Box::new(source), priority));
+        id
+    }
+
+    pub fn remove_source(&self, id: SourceId) {
+        let _ = self.cmd_tx.send(Command::Remove(id));
+    }
+}
+
+impl<S: Sniff + 'static> FromIterator<S> for MergeSniffer {
+    /// Merges the sources in timestamp order. Must be called from within a
+    /// tokio runtime.
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut merge = Self::new(MergePolicy::Timestamp);
+        for source in iter {
+            merge.add_source(source);
+        }
+        merge
+    }
+}
+
+impl Drop for MergeSniffer {
+    fn drop(&mut self) {
+        for src in self.sources.iter() {
+            src.task.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl Sniff for MergeSniffer {
+    async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
+        std::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RawPdu;
+    use std::time::{Duration, SystemTime};
+
+    struct Source(Vec<u64>);
+
+    #[async_trait]
+    impl Sniff for Source {
+        async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
+            if self.0.is_empty() {
+                return Ok(None);
+            }
+            let secs = self.0.remove(0);
+            Ok(Some(Packet::new(
+                SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                RawPdu::new(vec![secs as u8]),
+                None,
+                None,
+                None,
+            )))
+        }
+    }
+
+    async fn collect(merge: &mut MergeSniffer) -> Vec<u64> {
+        let mut out = Vec::new();
+        while let Some(pkt) = merge.sniff().await.unwrap() {
+            let ts = pkt.timestamp().duration_since(SystemTime::UNIX_EPOCH);
+            out.push(ts.unwrap().as_secs());
+        }
+        out
+    }
+
+    #[test]
+    fn merge_sources() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut merge = MergeSniffer::new(MergePolicy::Timestamp);
+            merge.add_source(Source(vec![1, 4, 5]));
+            merge.add_source(Source(vec![2, 3, 6]));
+            let handle = merge.handle();
+            handle.add_source(Source(vec![0, 7]));
+            assert_eq!(collect(&mut merge).await, [0, 1, 2, 3, 4, 5, 6, 7]);
+            assert!(merge.is_empty());
+
+            let mut merge = MergeSniffer::new(MergePolicy::Priority).buffer(8);
+            merge.add_source_with_priority(Source(vec![10, 11]), 0);
+            merge.add_source_with_priority(Source(vec![20, 21]), 1);
+            let removed = merge.add_source_with_priority(Source(vec![30]), 2);
+            assert!(merge.remove_source(removed));
+            for _ in 0..4 {
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(collect(&mut merge).await, [20, 21, 10, 11]);
+        });
+    }
+}
@@ -1,23 +1,47 @@
 #![allow(clippy::len_without_is_empty)]
 
 use super::{
-    AnyPdu, Device, Dump, DumpValue, Dumper, Error, LinkType, Pdu, PduExt, RawPacket, Virtual,
+    AnnotatingDumper, AnyPdu, Bytes, Device, Dump, DumpValue, Dumper, Error, Extensions, Field,
+    FieldMap, Issue, LinkType, PacketAnnotator, Pdu, PduExt, Query, QueryError, RawPacket, RawPdu,
+    Timestamp, Validation, ValidationReport, Validator, Virtual,
 };
 use sniffle_ende::encode::Encoder;
 use std::time::SystemTime;
 
 #[derive(Clone)]
 pub struct Packet {
-    ts: SystemTime,
+    ts: Timestamp,
     pdu: AnyPdu,
     len: usize,
     snaplen: usize,
     dev: Option<std::sync::Arc<Device>>,
+    report: Option<ValidationReport>,
+    annotator: Option<std::sync::Arc<dyn PacketAnnotator>>,
+    extensions: Extensions,
+    buffer: Option<Bytes>,
+}
+
+/// Assembles a packet one layer at a time, from the outer most PDU inward.
+///
+/// Each layer becomes the inner PDU of the layer before it, replacing any
+/// inner PDU it already had. `make_all_canonical` is run on the finished
+/// chain, so lengths, protocol identifiers, and checksums don't need to be
+/// filled in by hand. Protocol specific shorthands such as `ethernet` and
+/// `ipv4` are provided by `PacketBuilderExt` in the protocols crate.
+#[derive(Clone, Default)]
+pub struct PacketBuilder {
+    layers: Vec<AnyPdu>,
+    ts: Option<Timestamp>,
+    dev: Option<std::sync::Arc<Device>>,
 }
 
 impl Packet {
-    pub fn new<P: Pdu>(
-        timestamp: SystemTime,
+    pub fn builder() -> PacketBuilder {
+        PacketBuilder::new()
+    }
+
+    pub fn new<P: Pdu, T: Into<Timestamp>>(
+        timestamp: T,
         pdu: P,
         length: Option<usize>,
         snaplen: Option<usize>,
@@ -25,11 +49,15 @@ impl Packet {
     ) -> Self {
         let len = length.unwrap_or_else(|| pdu.total_len());
         Self {
-            ts: timestamp,
+            ts: timestamp.into(),
             pdu: AnyPdu::new(pdu),
             len,
             snaplen: snaplen.unwrap_or(65535),
             dev: device,
+            report: None,
+            annotator: None,
+            extensions: Extensions::new(),
+            buffer: None,
         }
     }
 
@@ -49,7 +77,22 @@ impl Packet {
         self.len
     }
 
+    /// Number of bytes of the packet that were captured. This is less than
+    /// `len` when the packet was longer than the snaplen.
+    pub fn captured_len(&self) -> usize {
+        self.pdu.total_len()
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.captured_len() < self.len
+    }
+
     pub fn timestamp(&self) -> SystemTime {
+        self.ts.to_system_time()
+    }
+
+    /// The timestamp, along with its capture precision and UTC offset.
+    pub fn precise_timestamp(&self) -> Timestamp {
         self.ts
     }
 
@@ -77,10 +120,86 @@ impl Packet {
         self.pdu.make_all_canonical();
     }
 
+    /// The validation report of the packet. Packets are only validated when
+    /// sniffed with a `Validation` registered in the session, or after a
+    /// call to `validate`.
+    pub fn validation(&self) -> Option<&ValidationReport> {
+        self.report.as_ref()
+    }
+
+    /// Checks each PDU for problems and records them in the packet's
+    /// validation report.
+    pub fn validate(&mut self, options: &Validation) -> &ValidationReport {
+        let mut report = ValidationReport::new();
+        let caplen = self.captured_len();
+        if caplen < self.len {
+            report.push(Issue::Truncated {
+                captured: caplen,
+                original: self.len,
+            });
+        }
+
+        self.pdu.link_parent_pdus();
+        let mut validator = Validator::new(options, &mut report);
+        let mut pdu = Some(&self.pdu);
+        while let Some(curr) = pdu {
+            curr.validate(&mut validator);
+            pdu = curr.inner_pdu();
+        }
+        self.report.insert(report)
+    }
+
+    /// Sets the annotator used to add metadata beside addresses when the
+    /// packet is dumped or queried. See `PacketAnnotator`.
+    pub fn set_annotator(&mut self, annotator: Option<std::sync::Arc<dyn PacketAnnotator>>) {
+        self.annotator = annotator;
+    }
+
+    pub fn annotator(&self) -> Option<&std::sync::Arc<dyn PacketAnnotator>> {
+        self.annotator.as_ref()
+    }
+
+    /// The captured bytes the packet was dissected from. Payloads and other
+    /// byte strings in the packet's PDUs may reference this buffer, rather
+    /// than holding copies.
+    ///
+    /// This is `None` for packets that were not dissected from a capture,
+    /// such as those built with `PacketBuilder`. The buffer is not updated
+    /// when the PDUs are modified, so `serialize` should be used to get the
+    /// current bytes of the packet.
+    pub fn buffer(&self) -> Option<&Bytes> {
+        self.buffer.as_ref()
+    }
+
+    pub fn set_buffer(&mut self, buffer: Option<Bytes>) {
+        self.buffer = buffer;
+    }
+
+    /// Data attached to the packet by dissectors and analysis passes.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     pub fn dump<D: Dump>(&self, dumper: &mut Dumper<D>) -> Result<(), D::Error> {
+        match &self.annotator {
+            Some(annotator) => self.dump_impl(&mut Dumper::new(AnnotatingDumper::new(
+                &mut **dumper,
+                annotator.clone(),
+            ))),
+            None => self.dump_impl(dumper),
+        }
+    }
+
+    fn dump_impl<D: Dump>(&self, dumper: &mut Dumper<D>) -> Result<(), D::Error> {
         let mut node = dumper.add_packet()?;
-        node.add_field("Timestamp", DumpValue::Time(self.ts), None)?;
-        let mut capnode = node.add_node("Capture", None)?;
+        node.add_field("Timestamp", DumpValue::Time(self.ts.to_system_time()), None)?;
+        let mut capnode = node
+            .byte_range(0, self.pdu.total_len())
+            .add_node("Capture", None)?;
         if let Ok(len) = self.len.try_into() {
             capnode.add_field("Length", DumpValue::UInt(len), None)?;
         }
@@ -103,8 +222,11 @@ impl Packet {
         }
         drop(capnode);
         let mut pdu = self.pdu();
+        let mut offset = 0;
         loop {
+            node.byte_range(offset, pdu.header_len());
             pdu.dump(&mut node)?;
+            offset += pdu.header_len();
             let next = pdu.inner_pdu();
             pdu = match next {
                 Some(next) => next,
@@ -115,6 +237,34 @@ impl Packet {
         }
     }
 
+    /// Returns the byte offsets and lengths of the fields of the packet
+    /// within the captured frame.
+    pub fn field_map(&self) -> FieldMap {
+        FieldMap::from_packet(self)
+    }
+
+    /// Returns the value of a field as it would be dumped, such as
+    /// `packet.field_value("Ipv4.Source Address")`. The path is made of the
+    /// names of the field's enclosing nodes and its own name, separated by
+    /// `.`. If the path occurs more than once, the first is used.
+    pub fn field_value(&self, path: &str) -> Option<String> {
+        crate::diff::field_value(&self.pdu, path)
+    }
+
+    /// Looks up a field by name, as in a `Query`, such as
+    /// `packet.get("ipv4.ttl")`. If the packet has the field more than once,
+    /// the first is returned.
+    pub fn get(&self, name: &str) -> Option<Field> {
+        crate::query::get(self, name)
+    }
+
+    /// Tests the packet against a `Query`, such as
+    /// `packet.matches("tcp.port == 443 && ipv4.ttl < 10")`. To test many
+    /// packets against the same query, parse it once with `Query::parse`.
+    pub fn matches(&self, query: &str) -> Result<bool, QueryError> {
+        Ok(Query::parse(query)?.matches(self))
+    }
+
     pub fn find<P: Pdu>(&self) -> Option<&P> {
         self.pdu.find::<P>()
     }
@@ -147,14 +297,74 @@ impl Packet {
         Ok(RawPacket::new(
             datalink,
             self.ts,
-            self.snaplen,
-            Some(self.len),
+            self.len,
+            Some(self.snaplen),
             &buf[..],
             self.dev.clone(),
         ))
     }
 }
 
+impl PacketBuilder {
+    pub fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            ts: None,
+            dev: None,
+        }
+    }
+
+    /// Adds a PDU as the new inner most layer.
+    pub fn push<P: Pdu>(mut self, pdu: P) -> Self {
+        self.layers.push(AnyPdu::new(pdu));
+        self
+    }
+
+    /// Adds raw bytes as the inner most layer.
+    pub fn payload<B: Into<Vec<u8>>>(self, data: B) -> Self {
+        self.push(RawPdu::new(data.into()))
+    }
+
+    /// Sets the packet timestamp. Defaults to the time the packet is built.
+    pub fn timestamp<T: Into<Timestamp>>(mut self, ts: T) -> Self {
+        self.ts = Some(ts.into());
+        self
+    }
+
+    pub fn device(mut self, device: std::sync::Arc<Device>) -> Self {
+        self.dev = Some(device);
+        self
+    }
+
+    /// Links the layers and makes them canonical. An empty builder produces
+    /// an empty `RawPdu`.
+    pub fn build_pdu(self) -> AnyPdu {
+        let mut layers = self.layers;
+        let mut pdu = layers
+            .pop()
+            .unwrap_or_else(|| AnyPdu::new(RawPdu::new(Vec::new())));
+        while let Some(mut outer) = layers.pop() {
+            outer.set_inner_pdu(pdu);
+            pdu = outer;
+        }
+        pdu.make_all_canonical();
+        pdu
+    }
+
+    pub fn build(self) -> Packet {
+        let ts = self.ts.unwrap_or_else(Timestamp::now);
+        let dev = self.dev.clone();
+        Packet::new(ts, self.build_pdu(), None, None, dev)
+    }
+
+    /// Builds the packet and serializes it.
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let _ = self.build_pdu().serialize(&mut buf);
+        buf
+    }
+}
+
 impl<P: Pdu> From<P> for Packet {
     fn from(pdu: P) -> Self {
         Self::new(SystemTime::now(), pdu, None, None, None)
@@ -0,0 +1,314 @@
+/// Direction of a packet relative to the capturing interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+    Unknown,
+}
+
+/// How a packet was addressed when it was received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReceptionType {
+    Unicast,
+    Multicast,
+    Broadcast,
+    Promiscuous,
+    Unspecified,
+}
+
+/// A hash of a packet's data, such as the pcapng `epb_hash` option. The
+/// algorithm codes are those used by pcapng.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PacketHash {
+    TwosComplement(Vec<u8>),
+    Xor(Vec<u8>),
+    Crc32([u8; 4]),
+    Md5([u8; 16]),
+    Sha1([u8; 20]),
+    Toeplitz([u8; 4]),
+    /// An algorithm without a variant of its own, and the hash data.
+    Other(u8, Vec<u8>),
+}
+
+/// Per-packet metadata recorded by the capture, other than the timestamp
+/// and lengths. Every item is optional.
+///
+/// Direction, reception type, and the other link layer flags are stored
+/// together as the 32-bit pcapng `epb_flags` word.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PacketMeta {
+    flags: Option<u32>,
+    hashes: Vec<PacketHash>,
+    drop_count: Option<u64>,
+    packet_id: Option<u64>,
+    queue: Option<u32>,
+}
+
+impl Direction {
+    fn from_flags(flags: u32) -> Option<Self> {
+        match flags & 0b11 {
+            0b00 => Some(Self::Unknown),
+            0b01 => Some(Self::Inbound),
+            0b10 => Some(Self::Outbound),
+            _ => None,
+        }
+    }
+
+    fn to_flags(self) -> u32 {
+        match self {
+            Self::Unknown => 0b00,
+            Self::Inbound => 0b01,
+            Self::Outbound => 0b10,
+        }
+    }
+}
+
+impl ReceptionType {
+    fn from_flags(flags: u32) -> Option<Self> {
+        match (flags >> 2) & 0b111 {
+            0b000 => Some(Self::Unspecified),
+            0b001 => Some(Self::Unicast),
+            0b010 => Some(Self::Multicast),
+            0b011 => Some(Self::Broadcast),
+            0b100 => Some(Self::Promiscuous),
+            _ => None,
+        }
+    }
+
+    fn to_flags(self) -> u32 {
+        (match self {
+            Self::Unspecified => 0b000,
+            Self::Unicast => 0b001,
+            Self::Multicast => 0b010,
+            Self::Broadcast => 0b011,
+            Self::Promiscuous => 0b100,
+        }) << 2
+    }
+}
+
+impl PacketHash {
+    /// Creates a hash from its algorithm code and data. Data of the wrong
+    /// length for a known algorithm is kept as `Other`.
+    pub fn new(algorithm: u8, data: &[u8]) -> Self {
+        match algorithm {
+            0 => Self::TwosComplement(data.to_vec()),
+            1 => Self::Xor(data.to_vec()),
+            2 => data
+                .try_into()
+                .map(Self::Crc32)
+                .unwrap_or_else(|_| Self::Other(algorithm, data.to_vec())),
+            3 => data
+                .try_into()
+                .map(Self::Md5)
+                .unwrap_or_else(|_| Self::Other(algorithm, data.to_vec())),
+            4 => data
+                .try_into()
+                .map(Self::Sha1)
+                .unwrap_or_else(|_| Self::Other(algorithm, data.to_vec())),
+            5 => data
+                .try_into()
+                .map(Self::Toeplitz)
+                .unwrap_or_else(|_| Self::Other(algorithm, data.to_vec())),
+            _ => Self::Other(algorithm, data.to_vec()),
+        }
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        match self {
+            Self::TwosComplement(_) => 0,
+            Self::Xor(_) => 1,
+            Self::Crc32(_) => 2,
+            Self::Md5(_) => 3,
+            Self::Sha1(_) => 4,
+            Self::Toeplitz(_) => 5,
+            Self::Other(algorithm, _) => *algorithm,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Self::TwosComplement(data) | Self::Xor(data) | Self::Other(_, data) => &data[..],
+            Self::Crc32(data) | Self::Toeplitz(data) => &data[..],
+            Self::Md5(data) => &data[..],
+            Self::Sha1(data) => &data[..],
+        }
+    }
+}
+
+impl PacketMeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True if no metadata is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The raw link layer flags word.
+    pub fn flags(&self) -> Option<u32> {
+        self.flags
+    }
+
+    pub fn with_flags(mut self, flags: u32) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    /// The direction bits of the flags. `None` if no flags are set, or the
+    /// bits are invalid.
+    pub fn direction(&self) -> Option<Direction> {
+        self.flags.and_then(Direction::from_flags)
+    }
+
+    pub fn with_direction(mut self, dir: Direction) -> Self {
+        self.flags = Some((self.flags.unwrap_or(0) & !0b11) | dir.to_flags());
+        self
+    }
+
+    /// The reception type bits of the flags. `None` if no flags are set, or
+    /// the bits are invalid.
+    pub fn reception_type(&self) -> Option<ReceptionType> {
+        self.flags.and_then(ReceptionType::from_flags)
+    }
+
+    pub fn with_reception_type(mut self, rtype: ReceptionType) -> Self {
+        self.flags = Some((self.flags.unwrap_or(0) & !(0b111 << 2)) | rtype.to_flags());
+        self
+    }
+
+    pub fn hashes(&self) -> &[PacketHash] {
+        &self.hashes[..]
+    }
+
+    pub fn with_hash(mut self, hash: PacketHash) -> Self {
+        self.hashes.push(hash);
+        self
+    }
+
+    /// Number of packets lost between this packet and the preceding one.
+    pub fn drop_count(&self) -> Option<u64> {
+        self.drop_count
+    }
+
+    pub fn with_drop_count(mut self, count: u64) -> Self {
+        self.drop_count = Some(count);
+        self
+    }
+
+    /// Identifier of the packet, shared by copies of the same packet
+    /// captured on different interfaces.
+    pub fn packet_id(&self) -> Option<u64> {
+        self.packet_id
+    }
+
+    pub fn with_packet_id(mut self, id: u64) -> Self {
+        self.packet_id = Some(id);
+        self
+    }
+
+    /// The interface queue the packet was received on.
+    pub fn queue(&self) -> Option<u32> {
+        self.queue
+    }
+
+    pub fn with_queue(mut self, queue: u32) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let meta = PacketMeta::new();
+        assert!(meta.is_empty());
+        assert_eq!(meta.flags(), None);
+        assert_eq!(meta.direction(), None);
+        assert_eq!(meta.reception_type(), None);
+        assert!(!meta.with_queue(0).is_empty());
+    }
+
+    #[test]
+    fn direction() {
+        for dir in [Direction::Inbound, Direction::Outbound, Direction::Unknown] {
+            let meta = PacketMeta::new().with_direction(dir);
+            assert_eq!(meta.direction(), Some(dir));
+            assert_eq!(meta.reception_type(), Some(ReceptionType::Unspecified));
+        }
+        // Both direction bits set is invalid
+        assert_eq!(PacketMeta::new().with_flags(0b11).direction(), None);
+    }
+
+    #[test]
+    fn reception_type() {
+        for rtype in [
+            ReceptionType::Unicast,
+            ReceptionType::Multicast,
+            ReceptionType::Broadcast,
+            ReceptionType::Promiscuous,
+            ReceptionType::Unspecified,
+        ] {
+            let meta = PacketMeta::new().with_reception_type(rtype);
+            assert_eq!(meta.reception_type(), Some(rtype));
+        }
+        assert_eq!(
+            PacketMeta::new().with_flags(0b101 << 2).reception_type(),
+            None
+        );
+    }
+
+    #[test]
+    fn flags_keep_other_bits() {
+        let meta = PacketMeta::new()
+            .with_flags(0xffff_0000)
+            .with_direction(Direction::Outbound)
+            .with_reception_type(ReceptionType::Broadcast);
+        assert_eq!(meta.flags(), Some(0xffff_0000 | (0b011 << 2) | 0b10));
+        let meta = meta.with_direction(Direction::Inbound);
+        assert_eq!(meta.flags(), Some(0xffff_0000 | (0b011 << 2) | 0b01));
+        assert_eq!(meta.reception_type(), Some(ReceptionType::Broadcast));
+    }
+
+    #[test]
+    fn hash() {
+        let crc = PacketHash::new(2, &[1, 2, 3, 4]);
+        assert_eq!(crc, PacketHash::Crc32([1, 2, 3, 4]));
+        assert_eq!((crc.algorithm(), crc.data()), (2, &[1, 2, 3, 4][..]));
+        assert_eq!(
+            PacketHash::new(0, &[1]),
+            PacketHash::TwosComplement(vec![1])
+        );
+        assert_eq!(PacketHash::new(1, &[1]), PacketHash::Xor(vec![1]));
+        assert_eq!(PacketHash::new(3, &[0; 16]), PacketHash::Md5([0; 16]));
+        assert_eq!(PacketHash::new(4, &[0; 20]), PacketHash::Sha1([0; 20]));
+        assert_eq!(PacketHash::new(5, &[0; 4]), PacketHash::Toeplitz([0; 4]));
+    }
+
+    #[test]
+    fn hash_of_wrong_length() {
+        let hash = PacketHash::new(3, &[1, 2, 3, 4]);
+        assert_eq!(hash, PacketHash::Other(3, vec![1, 2, 3, 4]));
+        assert_eq!((hash.algorithm(), hash.data()), (3, &[1, 2, 3, 4][..]));
+        let hash = PacketHash::new(200, &[9]);
+        assert_eq!((hash.algorithm(), hash.data()), (200, &[9][..]));
+    }
+
+    #[test]
+    fn counters() {
+        let meta = PacketMeta::new()
+            .with_drop_count(3)
+            .with_packet_id(0x1234)
+            .with_queue(2)
+            .with_hash(PacketHash::Xor(vec![0xaa]))
+            .with_hash(PacketHash::Crc32([0; 4]));
+        assert_eq!(meta.drop_count(), Some(3));
+        assert_eq!(meta.packet_id(), Some(0x1234));
+        assert_eq!(meta.queue(), Some(2));
+        assert_eq!(meta.hashes().len(), 2);
+        assert_eq!(meta.hashes()[1].algorithm(), 2);
+    }
+}
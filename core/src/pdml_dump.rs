@@ -0,0 +1,450 @@
+use super::{ByteDumpFormatter, Dump, DumpValue, Dumper, Packet};
+use std::io::Write;
+use tokio::io::AsyncWriteExt;
+
+/// Dumps packets as Wireshark compatible PDML XML.
+///
+/// Each node directly under a packet becomes a `<proto>` element, and all
+/// other nodes, fields, and lists become nested `<field>` elements. Packet
+/// level fields, such as the timestamp, are grouped under a `geninfo`
+/// proto. Protos and fields that were given a byte range (see
+/// `Dump::set_byte_range`) get `pos` and `size` attributes. Byte fields are
+/// also given a hex `value`.
+pub struct PdmlDumper<W: tokio::io::AsyncWrite + Send + Unpin> {
+    writer: W,
+    buf: Vec<u8>,
+    count: u64,
+    started: bool,
+    geninfo: bool,
+    scopes: Vec<Scope>,
+    range: Option<(usize, usize)>,
+    err: Option<std::io::Error>,
+}
+
+struct Scope {
+    tag: &'static str,
+    prefix: String,
+    base: usize,
+}
+
+fn abbrev(name: &str) -> String {
+    let mut ret = String::with_capacity(name.len());
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            ret.push(ch.to_ascii_lowercase());
+        } else if !ret.is_empty() && !ret.ends_with('_') {
+            ret.push('_');
+        }
+    }
+    while ret.ends_with('_') {
+        let _ = ret.pop();
+    }
+    ret
+}
+
+struct Escape<'a>(&'a str);
+
+impl<'a> std::fmt::Display for Escape<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for ch in self.0.chars() {
+            match ch {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&apos;")?,
+                '\n' => f.write_str("&#10;")?,
+                ch => write!(f, "{}", ch)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: tokio::io::AsyncWrite + Send + Unpin> PdmlDumper<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buf: Vec::new(),
+            count: 0,
+            started: false,
+            geninfo: false,
+            scopes: Vec::new(),
+            range: None,
+            err: None,
+        }
+    }
+
+    pub fn as_inner(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn packet_count(&self) -> u64 {
+        self.count
+    }
+
+    pub async fn flush(&mut self) -> std::io::Result<()> {
+        let buf = std::mem::take(&mut self.buf);
+        let res = self.writer.write_all(&buf).await;
+        self.buf = buf;
+        self.buf.clear();
+        res
+    }
+
+    pub async fn dump(&mut self, pkt: &Packet) -> std::io::Result<()> {
+        {
+            let mut dumper = &mut *self;
+            pkt.dump(&mut Dumper::new(&mut dumper))?;
+        }
+        self.flush().await
+    }
+
+    /// Writes the closing `</pdml>` tag. No more packets should be dumped
+    /// afterward.
+    pub async fn finish(&mut self) -> std::io::Result<()> {
+        self.check_err()?;
+        self.write_header()?;
+        writeln!(self.buf, "</pdml>")?;
+        self.flush().await
+    }
+
+    fn check_err(&mut self) -> std::io::Result<()> {
+        if let Some(e) = self.err.take() {
+            Err(e)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        if !self.started {
+            self.started = true;
+            writeln!(self.buf, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+            writeln!(
+                self.buf,
+                "<pdml version=\"0\" creator=\"sniffle/{}\">",
+                env!("CARGO_PKG_VERSION")
+            )?;
+        }
+        Ok(())
+    }
+
+    fn indent(&mut self) -> std::io::Result<()> {
+        for _ in 0..=self.scopes.len() {
+            write!(self.buf, "  ")?;
+        }
+        Ok(())
+    }
+
+    fn prefix(&self) -> &str {
+        self.scopes
+            .last()
+            .map(|scope| &scope.prefix[..])
+            .unwrap_or("")
+    }
+
+    fn base(&self) -> usize {
+        self.scopes.last().map(|scope| scope.base).unwrap_or(0)
+    }
+
+    fn take_range(&mut self) -> Option<(usize, usize)> {
+        let base = self.base();
+        self.range.take().map(|(pos, size)| (base + pos, size))
+    }
+
+    fn push_scope(&mut self, tag: &'static str, prefix: String, range: Option<(usize, usize)>) {
+        let base = range.map(|(pos, _)| pos).unwrap_or_else(|| self.base());
+        self.scopes.push(Scope { tag, prefix, base });
+    }
+
+    fn field_name(&self, name: &str) -> String {
+        let prefix = self.prefix();
+        if prefix.is_empty() {
+            abbrev(name)
+        } else {
+            format!("{}.{}", prefix, abbrev(name))
+        }
+    }
+
+    fn open_geninfo(&mut self) -> std::io::Result<()> {
+        if self.scopes.is_empty() && !self.geninfo {
+            self.geninfo = true;
+            self.indent()?;
+            writeln!(
+                self.buf,
+                "<proto name=\"geninfo\" showname=\"General information\">"
+            )?;
+            self.push_scope("proto", String::new(), None);
+        }
+        Ok(())
+    }
+
+    fn close_geninfo(&mut self) -> std::io::Result<()> {
+        if self.geninfo && self.scopes.len() == 1 {
+            self.geninfo = false;
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> std::io::Result<()> {
+        if let Some(scope) = self.scopes.pop() {
+            self.indent()?;
+            writeln!(self.buf, "</{}>", scope.tag)?;
+        }
+        Ok(())
+    }
+
+    fn close_or_defer(&mut self) {
+        if self.err.is_none() {
+            if let Err(e) = self.close() {
+                self.err = Some(e);
+            }
+        }
+    }
+
+    fn open(&mut self, name: &str, showname: &str) -> std::io::Result<()> {
+        self.close_geninfo()?;
+        let range = self.take_range();
+        if self.scopes.is_empty() {
+            let proto = abbrev(name);
+            self.indent()?;
+            write!(
+                self.buf,
+                "<proto name=\"{}\" showname=\"{}\"",
+                Escape(&proto),
+                Escape(showname)
+            )?;
+            if let Some((pos, size)) = range {
+                write!(self.buf, " size=\"{}\" pos=\"{}\"", size, pos)?;
+            }
+            writeln!(self.buf, ">")?;
+            self.push_scope("proto", proto, range);
+        } else {
+            let field = self.field_name(name);
+            self.indent()?;
+            write!(
+                self.buf,
+                "<field name=\"{}\" showname=\"{}\"",
+                Escape(&field),
+                Escape(showname)
+            )?;
+            if let Some((pos, size)) = range {
+                write!(self.buf, " size=\"{}\" pos=\"{}\"", size, pos)?;
+            }
+            writeln!(self.buf, ">")?;
+            self.push_scope("field", field, range);
+        }
+        Ok(())
+    }
+
+    fn field(
+        &mut self,
+        name: &str,
+        label: Option<&str>,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> std::io::Result<()> {
+        self.open_geninfo()?;
+        let range = self.take_range();
+        let field = self.field_name(name);
+        let show = value.to_string();
+        let showname = match (label, descr) {
+            (Some(label), Some(descr)) => format!("{}: {}", label, descr),
+            (Some(label), None) => format!("{}: {}", label, show),
+            (None, Some(descr)) => String::from(descr),
+            (None, None) => show.clone(),
+        };
+        self.indent()?;
+        write!(
+            self.buf,
+            "<field name=\"{}\" showname=\"{}\"",
+            Escape(&field),
+            Escape(&showname)
+        )?;
+        if let Some((pos, size)) = range {
+            write!(self.buf, " size=\"{}\" pos=\"{}\"", size, pos)?;
+        }
+        if let DumpValue::Bytes(bytes) = value {
+            if range.is_none() {
+                write!(self.buf, " size=\"{}\"", bytes.len())?;
+            }
+            write!(
+                self.buf,
+                " show=\"{}\" value=\"{}\"",
+                Escape(descr.unwrap_or(&show)),
+                ByteDumpFormatter(bytes).to_string().to_ascii_lowercase()
+            )?;
+        } else {
+            write!(self.buf, " show=\"{}\"", Escape(&show))?;
+        }
+        writeln!(self.buf, "/>")
+    }
+}
+
+impl<W: tokio::io::AsyncWrite + Send + Unpin> Dump for PdmlDumper<W> {
+    type Error = std::io::Error;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        self.check_err()?;
+        self.write_header()?;
+        self.count += 1;
+        self.scopes.clear();
+        self.range = None;
+        self.geninfo = false;
+        writeln!(self.buf, "<packet>")
+    }
+
+    fn end_packet(&mut self) {
+        if self.err.is_none() {
+            while !self.scopes.is_empty() {
+                self.close_or_defer();
+            }
+            if let Err(e) = writeln!(self.buf, "</packet>") {
+                self.err = Some(e);
+            }
+        }
+    }
+
+    fn start_node(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.check_err()?;
+        let showname = match descr {
+            Some(descr) => format!("{}, {}", name, descr),
+            None => String::from(name),
+        };
+        self.open(name, &showname)
+    }
+
+    fn end_node(&mut self) {
+        self.close_or_defer();
+    }
+
+    fn add_field(
+        &mut self,
+        name: &str,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        self.check_err()?;
+        self.field(name, Some(name), value, descr)
+    }
+
+    fn add_info(&mut self, name: &str, descr: &str) -> Result<(), Self::Error> {
+        self.check_err()?;
+        self.field(name, Some(name), DumpValue::Text(descr), None)
+    }
+
+    fn start_list(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.check_err()?;
+        self.open_geninfo()?;
+        let range = self.take_range();
+        let showname = match descr {
+            Some(descr) => format!("{}: {}", name, descr),
+            None => String::from(name),
+        };
+        let field = self.field_name(name);
+        self.indent()?;
+        write!(
+            self.buf,
+            "<field name=\"{}\" showname=\"{}\"",
+            Escape(&field),
+            Escape(&showname)
+        )?;
+        if let Some((pos, size)) = range {
+            write!(self.buf, " size=\"{}\" pos=\"{}\"", size, pos)?;
+        }
+        writeln!(self.buf, ">")?;
+        self.push_scope("field", field, range);
+        Ok(())
+    }
+
+    fn end_list(&mut self) {
+        self.close_or_defer();
+    }
+
+    fn add_list_item(
+        &mut self,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        self.check_err()?;
+        self.field("item", None, value, descr)
+    }
+
+    fn start_list_node(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.check_err()?;
+        self.open("item", descr.unwrap_or("item"))
+    }
+
+    fn end_list_node(&mut self) {
+        self.close_or_defer();
+    }
+
+    fn start_list_sublist(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.check_err()?;
+        self.open("item", descr.unwrap_or("item"))
+    }
+
+    fn end_list_sublist(&mut self) {
+        self.close_or_defer();
+    }
+
+    fn set_byte_range(&mut self, offset: usize, len: usize) {
+        self.range = Some((offset, len));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pdml_dump() {
+        let mut pdml = PdmlDumper::new(Vec::new());
+        {
+            let mut dumper = Dumper::new(&mut pdml);
+            let mut pkt = dumper.add_packet().unwrap();
+            pkt.add_field("Timestamp", DumpValue::UInt(1), None)
+                .unwrap();
+            let mut node = pkt
+                .byte_range(14, 14)
+                .add_node("Ethernet II", Some("a->b"))
+                .unwrap();
+            node.byte_range(6, 2)
+                .add_field(
+                    "Dst Address",
+                    DumpValue::Bytes(&[0xAB, 0x01]),
+                    Some("ab:01"),
+                )
+                .unwrap();
+            let mut list = node.add_list("Tags", None).unwrap();
+            list.add_item(DumpValue::Text("<x>"), None).unwrap();
+        }
+        let out = String::from_utf8(std::mem::take(&mut pdml.buf)).unwrap();
+        assert_eq!(
+            out,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+                "<pdml version=\"0\" creator=\"sniffle/0.1.0\">\n",
+                "<packet>\n",
+                "  <proto name=\"geninfo\" showname=\"General information\">\n",
+                "    <field name=\"timestamp\" showname=\"Timestamp: 1\" show=\"1\"/>\n",
+                "  </proto>\n",
+                "  <proto name=\"ethernet_ii\" showname=\"Ethernet II, a-&gt;b\" ",
+                "size=\"14\" pos=\"14\">\n",
+                "    <field name=\"ethernet_ii.dst_address\" showname=\"Dst Address: ab:01\" ",
+                "size=\"2\" pos=\"20\" show=\"ab:01\" value=\"ab01\"/>\n",
+                "    <field name=\"ethernet_ii.tags\" showname=\"Tags\">\n",
+                "      <field name=\"ethernet_ii.tags.item\" showname=\"&lt;x&gt;\" ",
+                "show=\"&lt;x&gt;\"/>\n",
+                "    </field>\n",
+                "  </proto>\n",
+                "</packet>\n",
+            )
+        );
+    }
+}
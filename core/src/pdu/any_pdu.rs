@@ -1,10 +1,9 @@
 use super::{
-    super::{Dump, NodeDumper},
-    BasePdu, Pdu, PduExt, PduType,
+    super::{Dump, NodeDumper, Pool, Validator},
+    BasePdu, Pdu, PduExt,
 };
 use sniffle_ende::encode::{DynEncoder, Encoder};
 use std::any::Any;
-use std::convert::AsRef;
 
 pub struct AnyPdu {
     pub(super) pdu: Box<dyn DynPdu + Send + Sync + 'static>,
@@ -13,11 +12,15 @@ pub struct AnyPdu {
 pub trait DynPdu: std::fmt::Debug {
     fn dyn_base_pdu(&self) -> &BasePdu;
     fn dyn_base_pdu_mut(&mut self) -> &mut BasePdu;
-    fn dyn_pdu_type(&self) -> PduType;
+    fn dyn_as_any(&self) -> &dyn Any;
+    fn dyn_as_any_mut(&mut self) -> &mut dyn Any;
+    fn dyn_into_any(self: Box<Self>) -> Box<dyn Any>;
     fn dyn_header_len(&self) -> usize;
     fn dyn_trailer_len(&self) -> usize;
     fn dyn_total_len(&self) -> usize;
+    fn dyn_wire_len(&self) -> usize;
     fn dyn_make_canonical(&mut self);
+    fn dyn_validate(&self, validator: &mut Validator<'_>);
     fn dyn_serialize_header(&self, encoder: &mut DynEncoder<'_>) -> std::io::Result<()>;
     fn dyn_serialize_trailer(&self, encoder: &mut DynEncoder<'_>) -> std::io::Result<()>;
     fn dyn_serialize(&self, encoder: &mut DynEncoder<'_>) -> std::io::Result<()>;
@@ -27,6 +30,7 @@ pub trait DynPdu: std::fmt::Debug {
     ) -> Result<(), Box<dyn Any + Send + Sync + 'static>>;
     fn dyn_debug(&self) -> &(dyn std::fmt::Debug + Send + Sync + 'static);
     fn dyn_clone(&self) -> Box<dyn DynPdu + Send + Sync + 'static>;
+    fn dyn_recycle(self: Box<Self>, pool: &Pool);
 }
 
 impl<P: Pdu> DynPdu for P {
@@ -38,8 +42,16 @@ impl<P: Pdu> DynPdu for P {
         self.base_pdu_mut()
     }
 
-    fn dyn_pdu_type(&self) -> PduType {
-        self.pdu_type()
+    fn dyn_as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn dyn_into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
     }
 
     fn dyn_header_len(&self) -> usize {
@@ -54,10 +66,18 @@ impl<P: Pdu> DynPdu for P {
         self.total_len()
     }
 
+    fn dyn_wire_len(&self) -> usize {
+        self.wire_len()
+    }
+
     fn dyn_make_canonical(&mut self) {
         self.make_canonical();
     }
 
+    fn dyn_validate(&self, validator: &mut Validator<'_>) {
+        self.validate(validator);
+    }
+
     fn dyn_serialize_header(&self, encoder: &mut DynEncoder<'_>) -> std::io::Result<()> {
         self.serialize_header(encoder)
     }
@@ -84,13 +104,20 @@ impl<P: Pdu> DynPdu for P {
     fn dyn_clone(&self) -> Box<dyn DynPdu + Send + Sync + 'static> {
         Box::new(self.clone())
     }
+
+    fn dyn_recycle(self: Box<Self>, pool: &Pool) {
+        pool.recycle_pdu_box(self);
+    }
 }
 
 impl Clone for AnyPdu {
     fn clone(&self) -> Self {
-        Self {
+        let mut pdu = Self {
             pdu: self.pdu.dyn_clone(),
-        }
+        };
+        // Link the copy's inner PDU to the copy
+        let _ = pdu.inner_pdu_mut();
+        pdu
     }
 }
 
@@ -103,10 +130,6 @@ impl Pdu for AnyPdu {
         self.pdu.dyn_base_pdu_mut()
     }
 
-    unsafe fn unsafe_pdu_type(&self) -> PduType {
-        self.pdu.dyn_pdu_type()
-    }
-
     fn header_len(&self) -> usize {
         self.pdu.dyn_header_len()
     }
@@ -119,41 +142,28 @@ impl Pdu for AnyPdu {
         self.pdu.dyn_total_len()
     }
 
+    fn wire_len(&self) -> usize {
+        self.pdu.dyn_wire_len()
+    }
+
     fn make_canonical(&mut self) {
         self.pdu.dyn_make_canonical();
     }
 
-    unsafe fn unsafe_into_any_pdu(self) -> AnyPdu {
-        self
+    fn validate(&self, validator: &mut Validator<'_>) {
+        self.pdu.dyn_validate(validator);
     }
 
-    unsafe fn unsafe_downcast<P: Pdu>(self) -> Result<P, Self> {
-        let is_type = self.is::<P>();
-        if is_type {
-            let ptr = Box::into_raw(self.pdu);
-            Ok(*Box::from_raw(ptr as *mut P))
-        } else {
-            Err(self)
-        }
+    fn as_any(&self) -> &dyn Any {
+        self.pdu.dyn_as_any()
     }
 
-    unsafe fn unsafe_downcast_ref<P: Pdu>(&self) -> Option<&P> {
-        if self.is::<P>() {
-            let ptr = self.pdu.as_ref() as *const dyn DynPdu as *const P;
-            Some(&*ptr)
-        } else {
-            None
-        }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self.pdu.dyn_as_any_mut()
     }
 
-    unsafe fn unsafe_downcast_mut<P: Pdu>(&mut self) -> Option<&mut P> {
-        let is_type = self.is::<P>();
-        if is_type {
-            let ptr = self.pdu.as_mut() as *mut dyn DynPdu as *mut P;
-            Some(&mut *ptr)
-        } else {
-            None
-        }
+    fn boxed(self) -> AnyPdu {
+        self
     }
 
     fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
@@ -171,6 +181,10 @@ impl Pdu for AnyPdu {
     }
 
     fn serialize<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> std::io::Result<()> {
+        // Checked here as well, for Pdus that override `serialize`
+        if let Some(bytes) = self.unmodified_bytes() {
+            return encoder.encode(&bytes[..]).map(|_| ());
+        }
         self.pdu.dyn_serialize(encoder.as_dyn_mut())
     }
 
@@ -183,6 +197,16 @@ impl AnyPdu {
     pub fn new<P: Pdu>(pdu: P) -> AnyPdu {
         PduExt::into_any_pdu(pdu)
     }
+
+    pub(crate) fn from_box<P: Pdu>(pdu: Box<P>) -> AnyPdu {
+        AnyPdu { pdu }
+    }
+
+    /// Gives the box holding the PDU to `pool`. The inner PDU must already
+    /// be taken.
+    pub(crate) fn recycle(self, pool: &Pool) {
+        self.pdu.dyn_recycle(pool);
+    }
 }
 
 impl std::fmt::Debug for AnyPdu {
@@ -1,31 +1,42 @@
-use super::{Dump, NodeDumper};
+use super::{Bytes, Dump, NodeDumper, Validator};
 use sniffle_ende::encode::Encoder;
 use std::any::Any;
+use std::sync::Arc;
 
 mod any_pdu;
 mod temp_pdu;
 
 pub use any_pdu::AnyPdu;
-pub(self) use any_pdu::DynPdu;
+use any_pdu::DynPdu;
 pub use temp_pdu::TempPdu;
 
 pub type PduType = std::any::TypeId;
 
+/// State shared by every Pdu: its inner Pdu, and a link to its parent.
+///
+/// Each Pdu owns its inner Pdu. The parent link is a shared copy of the
+/// header of the Pdu that contains it, without its inner Pdu, which is in
+/// turn linked to a copy of its own parent. Links are set by
+/// `PduExt::link_parent_pdus`, which `make_all_canonical` and
+/// `Packet::validate` call, and cleared when the inner Pdu is replaced or
+/// detached from its parent.
+///
+/// A dissected Pdu also keeps the bytes it was dissected from. Until the
+/// Pdu or one of its inner Pdus is marked dirty, those bytes are written
+/// as is by `serialize`, and `make_all_canonical` leaves the Pdu alone.
 #[derive(Default)]
 pub struct BasePdu {
-    parent: Option<AnyPdu>,
+    parent: Option<Arc<AnyPdu>>,
     inner: Option<AnyPdu>,
+    truncated: usize,
+    original: Option<Bytes>,
+    dirty: bool,
 }
 
 pub trait Pdu: 'static + Any + Clone + std::fmt::Debug + Send + Sync {
     fn base_pdu(&self) -> &BasePdu;
     fn base_pdu_mut(&mut self) -> &mut BasePdu;
 
-    #[doc(hidden)]
-    unsafe fn unsafe_pdu_type(&self) -> PduType {
-        self.type_id()
-    }
-
     fn header_len(&self) -> usize;
 
     fn trailer_len(&self) -> usize {
@@ -41,6 +52,19 @@ pub trait Pdu: 'static + Any + Clone + std::fmt::Debug + Send + Sync {
             + self.trailer_len()
     }
 
+    /// Length of the PDU as it was on the wire. This is more than
+    /// `total_len` when the capture was cut short by the snaplen, and the
+    /// PDU or its inner PDUs were marked with
+    /// `BasePdu::set_truncated_len`.
+    fn wire_len(&self) -> usize {
+        self.total_len()
+            + self.base_pdu().truncated
+            + match self.base_pdu().inner {
+                Some(ref pdu) => pdu.wire_len() - pdu.total_len(),
+                None => 0,
+            }
+    }
+
     fn serialize_header<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W)
         -> std::io::Result<()>;
 
@@ -52,6 +76,9 @@ pub trait Pdu: 'static + Any + Clone + std::fmt::Debug + Send + Sync {
     }
 
     fn serialize<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> std::io::Result<()> {
+        if let Some(bytes) = self.unmodified_bytes() {
+            return encoder.encode(&bytes[..]).map(|_| ());
+        }
         self.serialize_header(encoder)?;
         self.base_pdu()
             .inner
@@ -68,57 +95,51 @@ pub trait Pdu: 'static + Any + Clone + std::fmt::Debug + Send + Sync {
     /// other operations to conform to protocol standards.
     fn make_canonical(&mut self) {}
 
-    #[doc(hidden)]
-    unsafe fn unsafe_into_any_pdu(self) -> AnyPdu {
-        AnyPdu {
-            pdu: Box::new(self),
-        }
-    }
+    /// Checks the Pdu for problems such as bad checksums, and records them
+    /// with the validator. This is the counterpart of `make_canonical`.
+    fn validate(&self, _validator: &mut Validator<'_>) {}
 
     #[doc(hidden)]
-    unsafe fn unsafe_downcast<P: Pdu>(self) -> Result<P, Self> {
-        let is_type = self.is::<P>();
-        if is_type {
-            let mut s = self;
-            let pdu = std::ptr::read(std::mem::transmute::<&mut Self, &mut P>(&mut s));
-            std::mem::forget(s);
-            Ok(pdu)
-        } else {
-            Err(self)
-        }
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 
     #[doc(hidden)]
-    unsafe fn unsafe_downcast_ref<P: Pdu>(&self) -> Option<&P> {
-        if self.is::<P>() {
-            Some(std::mem::transmute::<&Self, &P>(self))
-        } else {
-            None
-        }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 
     #[doc(hidden)]
-    unsafe fn unsafe_downcast_mut<P: Pdu>(&mut self) -> Option<&mut P> {
-        let is_type = self.is::<P>();
-        if is_type {
-            Some(std::mem::transmute::<&mut Self, &mut P>(self))
-        } else {
-            None
-        }
+    fn boxed(self) -> AnyPdu {
+        AnyPdu::from_box(Box::new(self))
     }
 }
 
 pub trait PduExt: Pdu {
     fn pdu_type(&self) -> PduType {
-        unsafe { self.unsafe_pdu_type() }
+        self.as_any().type_id()
     }
 
     fn is<P: Pdu>(&self) -> bool {
         self.pdu_type() == PduType::of::<P>()
     }
 
+    /// Returns a copy of the header of the Pdu containing this one. See
+    /// `BasePdu` for when the link is set.
     fn parent_pdu(&self) -> Option<&AnyPdu> {
-        self.base_pdu().parent.as_ref()
+        self.base_pdu().parent.as_deref()
+    }
+
+    /// Links each inner PDU to a copy of the header of its parent, for
+    /// `parent_pdu`. The copies reflect the PDUs as they are now.
+    fn link_parent_pdus(&mut self) {
+        let mut parent = Arc::new(header_copy(self));
+        let mut inner = self.base_pdu_mut().inner.as_mut();
+        while let Some(pdu) = inner {
+            pdu.base_pdu_mut().parent = Some(parent);
+            parent = Arc::new(header_copy(pdu));
+            inner = pdu.base_pdu_mut().inner.as_mut();
+        }
     }
 
     fn inner_pdu(&self) -> Option<&AnyPdu> {
@@ -130,29 +151,79 @@ pub trait PduExt: Pdu {
     }
 
     fn replace_inner_pdu<P: Pdu>(&mut self, new_inner: Option<P>) -> Option<AnyPdu> {
-        let parent = unsafe { fake_any_pdu(self) };
+        self.base_pdu_mut().mark_dirty();
         std::mem::replace(
             &mut self.base_pdu_mut().inner,
-            new_inner.map(move |mut pdu| {
-                pdu.base_pdu_mut().parent = Some(parent);
-                PduExt::into_any_pdu(pdu)
-            }),
+            new_inner.map(|pdu| detach(PduExt::into_any_pdu(pdu))),
         )
+        .map(detach)
     }
 
     fn take_inner_pdu(&mut self) -> Option<AnyPdu> {
-        std::mem::replace(&mut self.base_pdu_mut().inner, None).map(|mut pdu| {
-            if let Some(pdu) = std::mem::replace(&mut pdu.base_pdu_mut().parent, None) {
-                let _ = Box::into_raw(pdu.pdu);
-            }
-            pdu
-        })
+        self.base_pdu_mut().mark_dirty();
+        self.base_pdu_mut().inner.take().map(detach)
     }
 
     fn set_inner_pdu<P: Pdu>(&mut self, pdu: P) {
-        let mut pdu = pdu;
-        pdu.base_pdu_mut().parent = Some(unsafe { fake_any_pdu(self) });
-        self.base_pdu_mut().inner = Some(PduExt::into_any_pdu(pdu));
+        let pdu = detach(PduExt::into_any_pdu(pdu));
+        let base = self.base_pdu_mut();
+        base.mark_dirty();
+        base.inner = Some(pdu);
+    }
+
+    /// Whether the PDU or any of its inner PDUs has been marked dirty since
+    /// it was dissected.
+    fn is_modified(&self) -> bool {
+        self.base_pdu().is_dirty()
+            || self
+                .inner_pdu()
+                .map(|inner| inner.is_modified())
+                .unwrap_or(false)
+    }
+
+    /// The bytes the PDU was dissected from, including its inner PDUs, if
+    /// none of them has been modified since.
+    fn unmodified_bytes(&self) -> Option<&Bytes> {
+        if self.is_modified() {
+            None
+        } else {
+            self.base_pdu().original_bytes()
+        }
+    }
+
+    /// Records the bytes the PDU, along with its inner PDUs, was just
+    /// dissected from, and marks it clean. This is done by dissector tables
+    /// and `Dissect::dissector` for each dissected PDU.
+    ///
+    /// Inner PDUs that the dissector modified after dissecting them are
+    /// marked clean as well, and forget their own bytes, since those are
+    /// covered by `bytes`.
+    fn set_dissected_bytes(&mut self, bytes: Bytes) {
+        let mut inner = self.inner_pdu_mut();
+        while let Some(pdu) = inner {
+            let base = pdu.base_pdu_mut();
+            if base.dirty {
+                base.original = None;
+                base.dirty = false;
+            }
+            inner = pdu.inner_pdu_mut();
+        }
+        let base = self.base_pdu_mut();
+        base.original = Some(bytes);
+        base.dirty = false;
+    }
+
+    /// Forgets the bytes the PDU and its inner PDUs were dissected from,
+    /// so `serialize` encodes every PDU from its fields. Round trip checks
+    /// use this, since serializing an unmodified PDU would otherwise only
+    /// copy the captured bytes.
+    fn forget_dissected_bytes(&mut self) {
+        self.base_pdu_mut().original = None;
+        let mut inner = self.inner_pdu_mut();
+        while let Some(pdu) = inner {
+            pdu.base_pdu_mut().original = None;
+            inner = pdu.inner_pdu_mut();
+        }
     }
 
     fn find<P: Pdu>(&self) -> Option<&P> {
@@ -177,38 +248,116 @@ pub trait PduExt: Pdu {
         }
     }
 
+    /// Whether the PDU or any of its inner PDUs extended beyond the end of
+    /// the capture.
+    fn is_truncated(&self) -> bool {
+        self.wire_len() > self.total_len()
+    }
+
     fn into_any_pdu(self) -> AnyPdu {
-        unsafe { self.unsafe_into_any_pdu() }
+        self.boxed()
     }
 
     fn downcast<P: Pdu>(self) -> Result<P, Self> {
-        unsafe { self.unsafe_downcast::<P>() }
+        if !self.is::<P>() {
+            return Err(self);
+        }
+        let mut pdu = Some(self);
+        if let Some(pdu) = (&mut pdu as &mut dyn Any).downcast_mut::<Option<P>>() {
+            return Ok(pdu.take().unwrap());
+        }
+        // Only an `AnyPdu` can be a different type than the one it holds
+        match pdu
+            .unwrap()
+            .into_any_pdu()
+            .pdu
+            .dyn_into_any()
+            .downcast::<P>()
+        {
+            Ok(pdu) => Ok(*pdu),
+            Err(_) => unreachable!("Pdu type was checked"),
+        }
     }
 
     fn downcast_ref<P: Pdu>(&self) -> Option<&P> {
-        unsafe { self.unsafe_downcast_ref::<P>() }
+        self.as_any().downcast_ref::<P>()
     }
 
+    /// Returns the PDU as a `P`, if it is one. The PDU is marked dirty,
+    /// since it may be modified through the returned reference.
     fn downcast_mut<P: Pdu>(&mut self) -> Option<&mut P> {
-        unsafe { self.unsafe_downcast_mut::<P>() }
+        let pdu = self.as_any_mut().downcast_mut::<P>()?;
+        pdu.base_pdu_mut().mark_dirty();
+        Some(pdu)
     }
 
+    /// Calls `make_canonical` on each PDU, starting with the inner most.
+    /// Parent links are set first with `link_parent_pdus`, so each PDU can
+    /// rely on `parent_pdu` (e.g. for a checksum pseudo-header).
+    ///
+    /// For a dissected PDU, only the PDUs that need it are made canonical:
+    /// the dirty PDUs, the PDUs inside them, which may depend on their
+    /// headers, and the PDUs containing them, whose lengths and checksums
+    /// may change. Those PDUs are marked dirty. An unmodified packet is left
+    /// untouched.
     fn make_all_canonical(&mut self) {
-        if let Some(inner) = self.inner_pdu_mut() {
-            inner.make_all_canonical();
+        fn make_canonical<P: Pdu>(pdu: &mut P, outer_dirty: bool) -> bool {
+            let dirty = outer_dirty || pdu.base_pdu().is_dirty();
+            let inner_dirty = match pdu.inner_pdu_mut() {
+                Some(inner) => make_canonical(inner, dirty),
+                None => false,
+            };
+            if dirty || inner_dirty {
+                pdu.make_canonical();
+                pdu.base_pdu_mut().mark_dirty();
+                true
+            } else {
+                false
+            }
+        }
+
+        // Without the original bytes, every PDU is made canonical
+        let dirty = self.base_pdu().original_bytes().is_none();
+        if dirty || self.is_modified() {
+            self.link_parent_pdus();
+            make_canonical(self, dirty);
         }
-        self.make_canonical();
     }
 }
 
 impl<P: Pdu> PduExt for P {}
 
-impl Drop for BasePdu {
-    fn drop(&mut self) {
-        if let Some(pdu) = std::mem::replace(&mut self.parent, None) {
-            let _ = Box::into_raw(pdu.pdu);
-        }
-        let _ = std::mem::replace(&mut self.inner, None);
+impl BasePdu {
+    /// Number of bytes of the PDU, beyond its header and captured inner
+    /// PDUs, that were not captured.
+    pub fn truncated_len(&self) -> usize {
+        self.truncated
+    }
+
+    pub fn set_truncated_len(&mut self, len: usize) {
+        self.truncated = len;
+    }
+
+    /// The bytes the PDU, including its inner PDUs, was dissected from.
+    pub fn original_bytes(&self) -> Option<&Bytes> {
+        self.original.as_ref()
+    }
+
+    /// Whether the PDU has been modified since it was dissected. See
+    /// `mark_dirty`.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Marks the PDU as modified, so it is encoded from its fields by
+    /// `serialize`, rather than copied from the captured bytes.
+    ///
+    /// This is done by `PduExt::downcast_mut`, `find_mut`, the inner PDU
+    /// setters, and the field setters and `*_mut` accessors of each PDU, so
+    /// only code that modifies the fields of a PDU directly, such as the
+    /// PDU's own implementation, needs to call this itself.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
     }
 }
 
@@ -225,12 +374,162 @@ impl Clone for BasePdu {
         BasePdu {
             parent: None,
             inner: self.inner.clone(),
+            truncated: self.truncated,
+            original: self.original.clone(),
+            dirty: self.dirty,
         }
     }
 }
 
-pub(self) unsafe fn fake_any_pdu<P: Pdu>(pdu: &mut P) -> AnyPdu {
-    AnyPdu {
-        pdu: Box::from_raw(pdu as *mut P as *mut (dyn DynPdu + Send + Sync)),
+/// Clears the parent link of a Pdu that was removed from its parent.
+fn detach(mut pdu: AnyPdu) -> AnyPdu {
+    pdu.base_pdu_mut().parent = None;
+    pdu
+}
+
+/// Copies `pdu` without its inner PDU, keeping its link to its parent.
+fn header_copy<P: Pdu>(pdu: &mut P) -> AnyPdu {
+    let inner = pdu.base_pdu_mut().inner.take();
+    let mut copy = PduExt::into_any_pdu(pdu.clone());
+    copy.base_pdu_mut().parent = pdu.base_pdu().parent.clone();
+    pdu.base_pdu_mut().inner = inner;
+    copy
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RawPdu;
+
+    #[test]
+    fn parent_links() {
+        let mut pdu = RawPdu::new(vec![1]);
+        let mut inner = RawPdu::new(vec![2]);
+        inner.set_inner_pdu(RawPdu::new(vec![3]));
+        pdu.set_inner_pdu(inner);
+        let mut pdu = pdu.into_any_pdu();
+        assert!(pdu.inner_pdu().unwrap().parent_pdu().is_none());
+        pdu.link_parent_pdus();
+
+        let inner = pdu.inner_pdu().unwrap();
+        let innermost = inner.inner_pdu().unwrap();
+        let parent = innermost.parent_pdu().unwrap();
+        assert_eq!(
+            &parent.downcast_ref::<RawPdu>().unwrap().data()[..],
+            &[2][..]
+        );
+        assert!(parent.inner_pdu().is_none());
+        let root = parent.parent_pdu().unwrap();
+        assert_eq!(&root.downcast_ref::<RawPdu>().unwrap().data()[..], &[1][..]);
+        assert!(root.parent_pdu().is_none());
+
+        // The copies are of the PDUs as they were when linked
+        pdu.downcast_mut::<RawPdu>().unwrap().set_data(vec![9]);
+        let root = |pdu: &AnyPdu| {
+            let innermost = pdu.inner_pdu().unwrap().inner_pdu().unwrap();
+            let root = innermost.parent_pdu().unwrap().parent_pdu().unwrap();
+            root.downcast_ref::<RawPdu>().unwrap().data()[0]
+        };
+        assert_eq!(root(&pdu), 1);
+        pdu.link_parent_pdus();
+        assert_eq!(root(&pdu), 9);
+
+        let innermost = pdu.inner_pdu_mut().unwrap().take_inner_pdu().unwrap();
+        assert!(innermost.parent_pdu().is_none());
+        let innermost = match innermost.downcast::<crate::ErrorPdu>() {
+            Ok(_) => panic!("downcast to the wrong type"),
+            Err(pdu) => pdu.downcast::<RawPdu>().unwrap(),
+        };
+        assert_eq!(&innermost.data()[..], &[3][..]);
+
+        // Moving a linked PDU to another parent clears its link
+        assert!(pdu.inner_pdu().unwrap().parent_pdu().is_some());
+        let inner = pdu.take_inner_pdu().unwrap();
+        let mut other = RawPdu::new(vec![4]);
+        other.set_inner_pdu(inner);
+        assert!(other.inner_pdu().unwrap().parent_pdu().is_none());
+    }
+
+    #[test]
+    fn dirty_tracking() {
+        use crate::{Dissect, Session};
+        use sniffle_ende::nom::Parser;
+
+        let session = Session::new_from_scratch();
+        let buffer = Bytes::from(vec![1u8, 2, 3]);
+        session.with_packet_buffer(&buffer, || {
+            let (_, mut pdu) = RawPdu::dissector(&session, None)
+                .parse(&buffer[..])
+                .unwrap();
+            assert_eq!(pdu.unmodified_bytes(), Some(&buffer));
+
+            // Field setters mark the PDU dirty, so the edit is serialized
+            pdu.set_data(vec![4, 5]);
+            assert!(pdu.is_modified());
+            let mut out = Vec::new();
+            pdu.serialize(&mut out).unwrap();
+            assert_eq!(out, [4, 5]);
+
+            // As do edits of a PDU taken out of an `AnyPdu`
+            let any = RawPdu::dissector(&session, None)
+                .parse(&buffer[..])
+                .unwrap()
+                .1
+                .into_any_pdu();
+            let mut pdu = any.downcast::<RawPdu>().unwrap();
+            assert!(!pdu.is_modified());
+            pdu.set_data(vec![7]);
+            out.clear();
+            pdu.serialize(&mut out).unwrap();
+            assert_eq!(out, [7]);
+
+            let (_, mut pdu) = RawPdu::dissector(&session, None)
+                .parse(&buffer[1..])
+                .unwrap();
+            let mut any = RawPdu::dissector(&session, None)
+                .parse(&buffer[1..])
+                .unwrap()
+                .1
+                .into_any_pdu();
+            pdu.set_inner_pdu(RawPdu::new(vec![6]));
+            assert!(pdu.is_modified());
+            assert!(!any.is_modified());
+            any.make_all_canonical();
+            assert!(!any.is_modified());
+            any.downcast_mut::<RawPdu>().unwrap();
+            assert!(any.is_modified());
+            assert!(any.unmodified_bytes().is_none());
+        });
+    }
+
+    #[test]
+    fn forget_dissected_bytes() {
+        use crate::{Dissect, Session};
+        use sniffle_ende::nom::Parser;
+
+        let session = Session::new_from_scratch();
+        let buffer = Bytes::from(vec![1u8, 2, 3]);
+        let (_, mut pdu) = session.with_packet_buffer(&buffer, || {
+            RawPdu::dissector(&session, None)
+                .parse(&buffer[..])
+                .unwrap()
+        });
+        pdu.set_inner_pdu(RawPdu::new(vec![4]));
+        pdu.set_dissected_bytes(buffer.slice(..));
+        pdu.inner_pdu_mut()
+            .unwrap()
+            .set_dissected_bytes(buffer.slice(2..));
+        pdu.forget_dissected_bytes();
+        assert!(pdu.base_pdu().original_bytes().is_none());
+        assert!(pdu
+            .inner_pdu()
+            .unwrap()
+            .base_pdu()
+            .original_bytes()
+            .is_none());
+        assert!(!pdu.is_modified());
+        let mut out = Vec::new();
+        pdu.serialize(&mut out).unwrap();
+        assert_eq!(out, [1, 2, 3, 4]);
     }
 }
@@ -1,10 +1,12 @@
-use super::{AnyPdu, DynPdu, Pdu, PduExt};
-use std::marker::PhantomData;
+use super::{DynPdu, Pdu, PduType};
 
+/// A borrowed Pdu, linked to the borrowed Pdus containing it. Dissectors
+/// are given a `TempPdu` for the layers that have already been dissected,
+/// before those layers are assembled into a chain of owned Pdus.
+#[derive(Clone)]
 pub struct TempPdu<'a> {
-    pdu: Option<AnyPdu>,
+    pdu: &'a (dyn DynPdu + Send + Sync + 'static),
     parent: Option<&'a TempPdu<'a>>,
-    _marker: PhantomData<&'a (dyn DynPdu + Send + Sync + 'static)>,
 }
 
 impl<'a> TempPdu<'a> {
@@ -13,16 +15,9 @@ impl<'a> TempPdu<'a> {
         'b: 'a,
         'c: 'a,
     {
-        let pdu: &'a (dyn DynPdu + Send + Sync + 'static) = pdu;
-        let pdu: *const (dyn DynPdu + Send + Sync + 'static) = pdu;
         Self {
-            pdu: Some(unsafe {
-                AnyPdu {
-                    pdu: Box::from_raw(std::mem::transmute(pdu)),
-                }
-            }),
+            pdu,
             parent: parent.as_ref(),
-            _marker: PhantomData,
         }
     }
 
@@ -31,16 +26,9 @@ impl<'a> TempPdu<'a> {
         'a: 'c,
         'b: 'c,
     {
-        let pdu: &'c (dyn DynPdu + Send + Sync + 'static) = pdu;
-        let pdu: *const (dyn DynPdu + Send + Sync + 'static) = pdu;
         TempPdu {
-            pdu: Some(unsafe {
-                AnyPdu {
-                    pdu: Box::from_raw(std::mem::transmute(pdu)),
-                }
-            }),
+            pdu,
             parent: Some(self),
-            _marker: PhantomData,
         }
     }
 
@@ -48,12 +36,20 @@ impl<'a> TempPdu<'a> {
         self.parent
     }
 
-    pub fn pdu(&self) -> &AnyPdu {
-        self.pdu.as_ref().unwrap()
+    pub fn pdu_type(&self) -> PduType {
+        self.pdu.dyn_as_any().type_id()
     }
 
-    pub fn find_pdu<P: Pdu>(&self) -> Option<&P> {
-        match self.pdu.as_ref().unwrap().downcast_ref::<P>() {
+    pub fn is<P: Pdu>(&self) -> bool {
+        self.pdu_type() == PduType::of::<P>()
+    }
+
+    pub fn downcast_ref<P: Pdu>(&self) -> Option<&'a P> {
+        self.pdu.dyn_as_any().downcast_ref::<P>()
+    }
+
+    pub fn find_pdu<P: Pdu>(&self) -> Option<&'a P> {
+        match self.downcast_ref::<P>() {
             Some(pdu) => Some(pdu),
             None => match self.parent {
                 Some(parent) => parent.find_pdu::<P>(),
@@ -63,7 +59,7 @@ impl<'a> TempPdu<'a> {
     }
 
     pub fn find_temp_pdu<P: Pdu>(&self) -> Option<&TempPdu<'a>> {
-        if self.pdu.as_ref().unwrap().is::<P>() {
+        if self.is::<P>() {
             Some(self)
         } else {
             match self.parent {
@@ -73,27 +69,3 @@ impl<'a> TempPdu<'a> {
         }
     }
 }
-
-impl<'a> Clone for TempPdu<'a> {
-    fn clone(&self) -> Self {
-        let pdu: &(dyn DynPdu + Send + Sync + 'static) = &*self.pdu.as_ref().unwrap().pdu;
-        let pdu: *const (dyn DynPdu + Send + Sync + 'static) = pdu;
-        Self {
-            pdu: Some(unsafe {
-                AnyPdu {
-                    pdu: Box::from_raw(std::mem::transmute(pdu)),
-                }
-            }),
-            parent: self.parent,
-            _marker: PhantomData,
-        }
-    }
-}
-
-impl<'a> Drop for TempPdu<'a> {
-    fn drop(&mut self) {
-        if let Some(pdu) = self.pdu.take() {
-            let _ = Box::into_raw(pdu.pdu);
-        }
-    }
-}
@@ -0,0 +1,323 @@
+//! Dissector plugins.
+//!
+//! A plugin dissects one protocol header through a small, stable API: it
+//! can read the bytes of the buffer being dissected, name the PDU, and
+//! emit fields. The header it reports becomes a `PluginPdu`, and the bytes
+//! that follow become its inner `RawPdu`.
+//!
+//! The API is shaped so it can be exposed to WebAssembly guests as plain
+//! imports and exports, using only integers and guest memory pointers:
+//!
+//! | Guest side                                      | Host side                     |
+//! |-------------------------------------------------|-------------------------------|
+//! | export `sniffle_abi_version() -> i32`           | `PLUGIN_ABI_VERSION`          |
+//! | export `sniffle_dissect() -> i32`               | `PluginGuest::dissect`        |
+//! | import `sniffle.len() -> i32`                   | `PluginContext::len`          |
+//! | import `sniffle.read(off, ptr, len) -> i32`     | `PluginContext::read`         |
+//! | import `sniffle.name(ptr, len)`                 | `PluginContext::set_name`     |
+//! | import `sniffle.field_uint(np, nl, off, len, v)`| `PluginContext::emit_field`   |
+//! | import `sniffle.field_int(np, nl, off, len, v)` | `PluginContext::emit_field`   |
+//! | import `sniffle.field_text(np, nl, off, len, tp, tl)` | `PluginContext::emit_field` |
+//! | import `sniffle.field_bytes(np, nl, off, len)`  | `PluginContext::emit_field`   |
+//!
+//! `sniffle_dissect` returns the header length, or a negative number if the
+//! buffer does not hold the plugin's protocol. Guests also export their
+//! linear memory as `memory`. With the `wasm-plugins` feature, `WasmPlugin`
+//! loads such a module with wasmtime and implements `PluginGuest` by
+//! forwarding these calls.
+
+use super::{
+    BasePdu, DResult, DissectError, Dissector, Dump, DumpValue, NodeDumper, Pdu, PduExt, RawPdu,
+    Session, TempPdu,
+};
+use sniffle_ende::encode::Encoder;
+use sniffle_ende::nom;
+use std::sync::Arc;
+
+/// Version of the plugin API described in the module documentation.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// A dissector provided by a plugin.
+pub trait PluginGuest: Send + Sync {
+    /// Dissects the buffer of `ctx`, returning the length of the header,
+    /// or `None` if the buffer does not hold the plugin's protocol.
+    fn dissect(&self, ctx: &mut PluginContext<'_>) -> Option<usize>;
+}
+
+/// The value of a field emitted by a plugin.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginValue {
+    Int(i64),
+    UInt(u64),
+    Text(String),
+    /// The bytes of the field's range in the buffer.
+    Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginField {
+    name: String,
+    offset: usize,
+    len: usize,
+    value: PluginValue,
+}
+
+/// The host side of the plugin API for one call to `PluginGuest::dissect`.
+pub struct PluginContext<'a> {
+    buf: &'a [u8],
+    name: Option<String>,
+    fields: Vec<PluginField>,
+}
+
+/// Adapts a `PluginGuest` to a `Dissector`, to be loaded into a dissector
+/// table with `Session::register_dissector`.
+#[derive(Clone)]
+pub struct PluginDissector {
+    name: Arc<str>,
+    guest: Arc<dyn PluginGuest>,
+}
+
+/// A PDU dissected by a plugin.
+#[derive(Debug)]
+pub struct PluginPdu {
+    base: BasePdu,
+    name: Arc<str>,
+    data: Vec<u8>,
+    fields: Vec<PluginField>,
+}
+
+impl PluginField {
+    pub fn name(&self) -> &str {
+        &self.name[..]
+    }
+
+    /// Offset of the field from the start of the PDU.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn value(&self) -> &PluginValue {
+        &self.value
+    }
+}
+
+impl<'a> PluginContext<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            name: None,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Copies buffer bytes starting at `offset` into `out`, returning the
+    /// number of bytes copied.
+    pub fn read(&self, offset: usize, out: &mut [u8]) -> usize {
+        let src = self.buf.get(offset..).unwrap_or(&[]);
+        let len = src.len().min(out.len());
+        out[..len].copy_from_slice(&src[..len]);
+        len
+    }
+
+    /// Overrides the name of the PDU, which defaults to the plugin's name.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.into());
+    }
+
+    /// Adds a field covering `len` bytes at `offset`. Returns false, and adds
+    /// nothing, if the range is outside the buffer.
+    pub fn emit_field(
+        &mut self,
+        name: &str,
+        offset: usize,
+        len: usize,
+        value: PluginValue,
+    ) -> bool {
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > self.buf.len())
+        {
+            return false;
+        }
+        self.fields.push(PluginField {
+            name: name.into(),
+            offset,
+            len,
+            value,
+        });
+        true
+    }
+}
+
+impl PluginDissector {
+    pub fn new<N: Into<Arc<str>>, G: PluginGuest + 'static>(name: N, guest: G) -> Self {
+        Self {
+            name: name.into(),
+            guest: Arc::new(guest),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name[..]
+    }
+}
+
+impl Dissector for PluginDissector {
+    type Out = PluginPdu;
+
+    fn dissect<'a>(
+        &self,
+        buffer: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self::Out> {
+        let mut ctx = PluginContext::new(buffer);
+        let len = match self.guest.dissect(&mut ctx) {
+            Some(len) if len <= buffer.len() => len,
+            _ => return Err(nom::Err::Error(DissectError::Malformed)),
+        };
+        let mut pdu = PluginPdu {
+            base: BasePdu::default(),
+            name: ctx.name.map(Arc::from).unwrap_or_else(|| self.name.clone()),
+            data: Vec::from(&buffer[..len]),
+            fields: ctx
+                .fields
+                .into_iter()
+                .filter(|field| field.offset + field.len <= len)
+                .collect(),
+        };
+        if len < buffer.len() {
+            pdu.set_inner_pdu(RawPdu::new(Vec::from(&buffer[len..])));
+        }
+        Ok((&buffer[buffer.len()..], pdu))
+    }
+}
+
+impl PluginPdu {
+    pub fn name(&self) -> &str {
+        &self.name[..]
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    pub fn fields(&self) -> &[PluginField] {
+        &self.fields[..]
+    }
+
+    pub fn field(&self, name: &str) -> Option<&PluginField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+impl Clone for PluginPdu {
+    fn clone(&self) -> Self {
+        Self {
+            base: BasePdu::default(),
+            name: self.name.clone(),
+            data: self.data.clone(),
+            fields: self.fields.clone(),
+        }
+    }
+}
+
+impl Pdu for PluginPdu {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.data[..]).map(|_| ())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<'_, D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node(&self.name[..], None)?;
+        for field in self.fields.iter() {
+            let bytes = &self.data[field.offset..field.offset + field.len];
+            let value = match &field.value {
+                PluginValue::Int(value) => DumpValue::Int(*value),
+                PluginValue::UInt(value) => DumpValue::UInt(*value),
+                PluginValue::Text(value) => DumpValue::Text(&value[..]),
+                PluginValue::Bytes => DumpValue::Bytes(bytes),
+            };
+            node.byte_range(field.offset, field.len)
+                .add_field(&field.name[..], value, None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LinkType, LinkTypeTable, Priority};
+
+    /// A two byte header: a type byte and a length byte.
+    struct TypeLength;
+
+    impl PluginGuest for TypeLength {
+        fn dissect(&self, ctx: &mut PluginContext<'_>) -> Option<usize> {
+            let mut hdr = [0u8; 2];
+            if ctx.read(0, &mut hdr[..]) != 2 {
+                return None;
+            }
+            ctx.emit_field("Type", 0, 1, PluginValue::UInt(hdr[0].into()));
+            ctx.emit_field("Length", 1, 1, PluginValue::UInt(hdr[1].into()));
+            assert!(!ctx.emit_field("Bogus", ctx.len(), 1, PluginValue::Bytes));
+            Some(2)
+        }
+    }
+
+    #[test]
+    fn plugin_dissect() {
+        let session = Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(
+                LinkType::USER0,
+                Priority(0),
+                PluginDissector::new("Type-Length", TypeLength),
+            )
+            .build();
+        let data = [7u8, 3, 1, 2, 3];
+        let (_, pdu) = session
+            .table_dissect::<LinkTypeTable>(&LinkType::USER0, &data[..], None)
+            .unwrap();
+        let pdu = pdu.downcast_ref::<PluginPdu>().unwrap();
+        assert_eq!(pdu.name(), "Type-Length");
+        assert_eq!(pdu.header_len(), 2);
+        assert_eq!(pdu.fields().len(), 2);
+        assert_eq!(pdu.field("Type").unwrap().value(), &PluginValue::UInt(7));
+        assert_eq!(pdu.total_len(), 5);
+        assert!(session
+            .table_dissect::<LinkTypeTable>(&LinkType::USER0, &data[..1], None)
+            .is_err());
+    }
+}
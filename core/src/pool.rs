@@ -0,0 +1,366 @@
+use super::{AnyPdu, Pdu, PduExt};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Types that can be cached and reused by a `Pool`.
+pub trait Poolable: Default + Send + 'static {
+    /// Returns the current allocated capacity.
+    fn capacity(&self) -> usize;
+
+    /// Clears the contents, keeping the allocation.
+    fn reset(&mut self);
+}
+
+/// Allocation pool owned by a `Session`.
+///
+/// Dissectors can take buffers, strings, and vectors from the pool
+/// via `Session::pool()` while building Pdus. Items are returned to
+/// the pool when the `Pooled` guard is dropped, or explicitly with
+/// `Pool::recycle`, so their allocations can be reused for the next
+/// packet.
+///
+/// The pool also caches the boxes that hold each layer of a dissected
+/// packet. Dissected Pdus are boxed with `Pool::pdu`, and a packet's layers
+/// can be given back with `Pool::recycle_pdu` once the packet is no longer
+/// needed, so a steady stream of packets doesn't allocate a new box for
+/// every layer.
+pub struct Pool {
+    free: parking_lot::Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+    pdus: parking_lot::Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+    max_cached: usize,
+    max_capacity: usize,
+    allocations: AtomicU64,
+    reuses: AtomicU64,
+    recycled: AtomicU64,
+    discarded: AtomicU64,
+}
+
+/// Allocation statistics for a `Pool`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Number of items newly allocated because the pool was empty
+    pub allocations: u64,
+    /// Number of items served from the pool
+    pub reuses: u64,
+    /// Number of items returned to the pool
+    pub recycled: u64,
+    /// Number of items dropped instead of being returned to the pool,
+    /// because the pool was full or the item was too large
+    pub discarded: u64,
+}
+
+/// An item borrowed from a `Pool`, returned to the pool when dropped.
+pub struct Pooled<'a, T: Poolable> {
+    pool: &'a Pool,
+    item: Option<T>,
+}
+
+impl<T: Send + 'static> Poolable for Vec<T> {
+    fn capacity(&self) -> usize {
+        Vec::capacity(self)
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl Poolable for String {
+    fn capacity(&self) -> usize {
+        String::capacity(self)
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl Pool {
+    pub fn new() -> Self {
+        Self::with_limits(64, 0x10000)
+    }
+
+    /// Creates a pool that caches at most `max_cached` items of each type,
+    /// and does not cache items with a capacity larger than `max_capacity`.
+    pub fn with_limits(max_cached: usize, max_capacity: usize) -> Self {
+        Self {
+            free: parking_lot::Mutex::new(HashMap::new()),
+            pdus: parking_lot::Mutex::new(HashMap::new()),
+            max_cached,
+            max_capacity,
+            allocations: AtomicU64::new(0),
+            reuses: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_max_cached(&mut self, max_cached: usize) {
+        self.max_cached = max_cached;
+    }
+
+    pub fn set_max_capacity(&mut self, max_capacity: usize) {
+        self.max_capacity = max_capacity;
+    }
+
+    /// Takes an empty item from the pool, allocating a new one if none
+    /// are cached.
+    pub fn get<T: Poolable>(&self) -> Pooled<'_, T> {
+        Pooled {
+            pool: self,
+            item: Some(self.take()),
+        }
+    }
+
+    /// Takes an empty item from the pool that is detached from the pool.
+    /// The item can be given back with `Pool::recycle`.
+    pub fn take<T: Poolable>(&self) -> T {
+        let item = self
+            .free
+            .lock()
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|free| free.downcast_mut::<Vec<T>>())
+            .and_then(|free| free.pop());
+        match item {
+            Some(item) => {
+                self.reuses.fetch_add(1, Ordering::Relaxed);
+                item
+            }
+            None => {
+                self.allocations.fetch_add(1, Ordering::Relaxed);
+                T::default()
+            }
+        }
+    }
+
+    /// Returns an item to the pool for reuse.
+    pub fn recycle<T: Poolable>(&self, item: T) {
+        let mut item = item;
+        if item.capacity() == 0 || item.capacity() > self.max_capacity {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        item.reset();
+        let mut free = self.free.lock();
+        let free = free
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<T>::new()))
+            .downcast_mut::<Vec<T>>()
+            .expect("pool free list has the wrong type");
+        if free.len() < self.max_cached {
+            free.push(item);
+            self.recycled.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Takes an empty byte buffer from the pool.
+    pub fn bytes(&self) -> Pooled<'_, Vec<u8>> {
+        self.get()
+    }
+
+    /// Takes an empty string from the pool.
+    pub fn string(&self) -> Pooled<'_, String> {
+        self.get()
+    }
+
+    /// Takes an empty vector from the pool.
+    pub fn vec<T: Send + 'static>(&self) -> Pooled<'_, Vec<T>> {
+        self.get()
+    }
+
+    /// Wraps `pdu` in an `AnyPdu`, reusing a box recycled with
+    /// `Pool::recycle_pdu` if one of the same type is cached.
+    pub fn pdu<P: Pdu>(&self, pdu: P) -> AnyPdu {
+        // Already boxed
+        let mut pdu = Some(pdu);
+        if let Some(any) = (&mut pdu as &mut dyn Any).downcast_mut::<Option<AnyPdu>>() {
+            return any.take().unwrap();
+        }
+        let pdu = pdu.unwrap();
+
+        let cached = self
+            .pdus
+            .lock()
+            .get_mut(&TypeId::of::<P>())
+            .and_then(|free| free.downcast_mut::<Vec<Box<MaybeUninit<P>>>>())
+            .and_then(Vec::pop);
+        match cached {
+            Some(slot) => {
+                self.reuses.fetch_add(1, Ordering::Relaxed);
+                AnyPdu::from_box(Box::write(slot, pdu))
+            }
+            None => {
+                self.allocations.fetch_add(1, Ordering::Relaxed);
+                AnyPdu::new(pdu)
+            }
+        }
+    }
+
+    /// Returns the boxes holding `pdu` and each of its inner Pdus to the
+    /// pool, so they can be reused by `Pool::pdu`. The Pdus are dropped
+    /// right away, so the packet buffers they reference are released.
+    pub fn recycle_pdu(&self, pdu: AnyPdu) {
+        let mut next = Some(pdu);
+        while let Some(mut pdu) = next {
+            next = pdu.take_inner_pdu();
+            pdu.recycle(self);
+        }
+    }
+
+    pub(crate) fn recycle_pdu_box<P: Pdu>(&self, pdu: Box<P>) {
+        let mut free = self.pdus.lock();
+        let free = free
+            .entry(TypeId::of::<P>())
+            .or_insert_with(|| Box::new(Vec::<Box<MaybeUninit<P>>>::new()))
+            .downcast_mut::<Vec<Box<MaybeUninit<P>>>>()
+            .expect("pool free list has the wrong type");
+        if free.len() < self.max_cached {
+            let slot = Box::into_raw(pdu);
+            // SAFETY: `slot` came from a `Box<P>`, and `MaybeUninit<P>` has the
+            // same layout as `P`. The Pdu is dropped exactly once, here, and
+            // the slot is only reused after being written by `Pool::pdu`.
+            let slot = unsafe {
+                std::ptr::drop_in_place(slot);
+                Box::from_raw(slot.cast::<MaybeUninit<P>>())
+            };
+            free.push(slot);
+            self.recycled.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            allocations: self.allocations.load(Ordering::Relaxed),
+            reuses: self.reuses.load(Ordering::Relaxed),
+            recycled: self.recycled.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Drops all cached items. Statistics are not reset.
+    pub fn clear(&self) {
+        self.free.lock().clear();
+        self.pdus.lock().clear();
+    }
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Pool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pool")
+            .field("max_cached", &self.max_cached)
+            .field("max_capacity", &self.max_capacity)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl<'a, T: Poolable> Pooled<'a, T> {
+    /// Detaches the item from the pool, so it is not returned when dropped.
+    /// This is typically used to move the item into a Pdu.
+    pub fn into_inner(self) -> T {
+        let mut this = self;
+        this.item.take().unwrap()
+    }
+}
+
+impl<'a, T: Poolable> Deref for Pooled<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<'a, T: Poolable> DerefMut for Pooled<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+impl<'a, T: Poolable> Drop for Pooled<'a, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.pool.recycle(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RawPdu;
+
+    #[test]
+    fn pool_reuse() {
+        let pool = Pool::with_limits(1, 16);
+        {
+            let mut buf = pool.bytes();
+            buf.extend_from_slice(b"abc");
+            let mut s = pool.string();
+            s.push_str("abc");
+        }
+        let buf = pool.bytes();
+        assert!(buf.is_empty());
+        assert!(buf.capacity() >= 3);
+
+        let big = pool.take::<Vec<u8>>();
+        pool.recycle(vec![0u8; 32]);
+        pool.recycle(big);
+        drop(buf);
+        pool.recycle(vec![0u8; 4]);
+
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                allocations: 3,
+                reuses: 1,
+                recycled: 3,
+                discarded: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn pdu_reuse() {
+        let pool = Pool::with_limits(1, 16);
+        let mut pdu = pool.pdu(RawPdu::new(vec![1, 2, 3]));
+        pdu.set_inner_pdu(pool.pdu(RawPdu::new(vec![4])));
+        let addr = pdu.downcast_ref::<RawPdu>().unwrap() as *const RawPdu;
+        pool.recycle_pdu(pdu);
+
+        let pdu = pool.pdu(RawPdu::new(vec![5]));
+        assert_eq!(pdu.downcast_ref::<RawPdu>().unwrap() as *const RawPdu, addr);
+        assert_eq!(pdu.downcast_ref::<RawPdu>().unwrap().data(), &[5][..]);
+        assert!(pdu.inner_pdu().is_none());
+
+        // Recycled PDUs don't hold on to the buffers they reference
+        let buffer = crate::Bytes::from(vec![6u8, 7]);
+        pool.recycle_pdu(pool.pdu(RawPdu::new(buffer.clone())));
+        assert!(buffer.is_unique());
+
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                allocations: 3,
+                reuses: 1,
+                recycled: 2,
+                discarded: 1,
+            }
+        );
+    }
+}
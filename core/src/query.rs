@@ -0,0 +1,764 @@
+use super::diff::{DiffCollector, Entry};
+use super::{DumpValue, Packet};
+use lazy_static::*;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// An owned copy of a dumped field value. See `DumpValue`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    Time(SystemTime),
+    Duration(Duration),
+}
+
+/// A field of a dissected packet, found with `Packet::get`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    path: String,
+    value: FieldValue,
+    descr: Option<String>,
+}
+
+/// A filter expression over the fields of dissected packets, similar to
+/// a display filter.
+///
+/// Fields are named by their dump path, in lower case with spaces replaced
+/// by `_`, such as `ipv4.time_to_live`, or by a shorter name registered by
+/// the dissector with `register_field!`, such as `ipv4.ttl`. A field alone
+/// tests that the packet has it. Fields can be compared to numbers,
+/// `true` and `false`, quoted strings, and bare words such as `10.0.0.1`
+/// with `==`, `!=`, `<`, `<=`, `>`, and `>=`. Non-numeric fields compare
+/// equal to a string that matches either their value or their description.
+/// Comparisons are combined with `&&`, `||`, `!`, `and`, `or`, `not`, and
+/// parentheses.
+///
+/// A comparison is true if any occurrence of the field satisfies it, and
+/// `a != b` is the same as `!(a == b)`.
+///
+/// ```ignore
+/// let query = Query::parse("tcp.port == 443 && ipv4.ttl < 10")?;
+/// if query.matches(&packet) { ... }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    expr: Expr,
+}
+
+/// A BPF filter expression translated from a `Query` with `Query::to_bpf`,
+/// which can be compiled into the kernel for a live capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BpfFilter {
+    expr: String,
+    exact: bool,
+}
+
+/// An error parsing a `Query`.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("{msg} at offset {offset}")]
+pub struct QueryError {
+    offset: usize,
+    msg: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(String),
+    Compare(String, CmpOp, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+/// How a field with a known dump path is tested in BPF.
+#[derive(Debug, Clone, Copy)]
+enum BpfField {
+    /// The protocol itself, named by its BPF keyword.
+    Proto(&'static str),
+    /// An IPv4 address, with the BPF qualifiers to test it.
+    Host(&'static str),
+    /// A port, with its protocol and direction.
+    Port(&'static str, &'static str),
+    /// An Ethernet address, with the BPF qualifiers to test it.
+    Ether(&'static str),
+    /// The IPv4 protocol number.
+    IpProto,
+}
+
+/// Fields that map cleanly to BPF, by normalized dump path.
+const BPF_FIELDS: &[(&str, BpfField)] = &[
+    ("ipv4", BpfField::Proto("ip")),
+    ("tcp", BpfField::Proto("tcp")),
+    ("udp", BpfField::Proto("udp")),
+    ("ipv4.source_address", BpfField::Host("ip src host")),
+    ("ipv4.destination_address", BpfField::Host("ip dst host")),
+    ("ipv4.protocol", BpfField::IpProto),
+    ("tcp.source_port", BpfField::Port("tcp", "src")),
+    ("tcp.destination_port", BpfField::Port("tcp", "dst")),
+    ("udp.source_port", BpfField::Port("udp", "src")),
+    ("udp.destination_port", BpfField::Port("udp", "dst")),
+    ("ethernet_ii.src_address", BpfField::Ether("ether src")),
+    ("ethernet_ii.dst_address", BpfField::Ether("ether dst")),
+];
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+lazy_static! {
+    static ref FIELDS: parking_lot::RwLock<HashMap<String, Vec<String>>> =
+        parking_lot::RwLock::new(HashMap::new());
+}
+
+pub fn _register_field(name: &str, path: &str) {
+    FIELDS
+        .write()
+        .entry(normalize(name))
+        .or_default()
+        .push(normalize(path));
+}
+
+/// Registers a short name for one or more fields, for use in `Query` and
+/// `Packet::get`. The paths are dump paths, such as `"TCP.Source Port"`.
+/// When a name has more than one path, any of them can match.
+///
+/// ```ignore
+/// register_field!(tcp_port, "tcp.port" => "TCP.Source Port", "TCP.Destination Port");
+/// ```
+#[macro_export]
+macro_rules! register_field {
+    ($id:ident, $name:expr => $($path:expr),+ $(,)?) => {
+        $crate::paste::paste! {
+            #[$crate::ctor::ctor]
+            #[allow(non_snake_case)]
+            fn [<__sniffle_field_ $id>]() {
+                $($crate::_register_field($name, $path);)+
+            }
+        }
+    };
+}
+
+/// Lower cases `path` and replaces spaces and dashes with `_`, dropping
+/// other punctuation, so `"Ipv4.Don't Fragment"` becomes
+/// `"ipv4.dont_fragment"`.
+fn normalize(path: &str) -> String {
+    path.chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '.' || c == '_' => Some(c.to_ascii_lowercase()),
+            ' ' | '-' => Some('_'),
+            _ => None,
+        })
+        .collect()
+}
+
+fn paths(name: &str) -> Vec<String> {
+    let name = normalize(name);
+    match FIELDS.read().get(&name) {
+        Some(paths) => paths.clone(),
+        None => vec![name],
+    }
+}
+
+fn find<'a>(entries: &'a [Entry], name: &str) -> impl Iterator<Item = &'a Entry> + 'a {
+    let paths = paths(name);
+    entries
+        .iter()
+        .filter(move |entry| paths.iter().any(|path| *path == normalize(&entry.path)))
+}
+
+impl FieldValue {
+    pub fn as_dump_value(&self) -> DumpValue<'_> {
+        match self {
+            Self::Bool(val) => DumpValue::Bool(*val),
+            Self::Int(val) => DumpValue::Int(*val),
+            Self::UInt(val) => DumpValue::UInt(*val),
+            Self::Float(val) => DumpValue::Float(*val),
+            Self::Text(val) => DumpValue::Text(&val[..]),
+            Self::Bytes(val) => DumpValue::Bytes(&val[..]),
+            Self::Time(val) => DumpValue::Time(*val),
+            Self::Duration(val) => DumpValue::Duration(*val),
+        }
+    }
+
+    fn number(&self) -> Option<Number> {
+        match self {
+            Self::Bool(val) => Some(Number::Int(*val as i128)),
+            Self::Int(val) => Some(Number::Int((*val).into())),
+            Self::UInt(val) => Some(Number::Int((*val).into())),
+            Self::Float(val) => Some(Number::Float(*val)),
+            _ => None,
+        }
+    }
+}
+
+impl From<DumpValue<'_>> for FieldValue {
+    fn from(value: DumpValue<'_>) -> Self {
+        match value {
+            DumpValue::Bool(val) => Self::Bool(val),
+            DumpValue::Int(val) => Self::Int(val),
+            DumpValue::UInt(val) => Self::UInt(val),
+            DumpValue::Float(val) => Self::Float(val),
+            DumpValue::Text(val) => Self::Text(String::from(val)),
+            DumpValue::Bytes(val) => Self::Bytes(Vec::from(val)),
+            DumpValue::Time(val) => Self::Time(val),
+            DumpValue::Duration(val) => Self::Duration(val),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_dump_value().fmt(f)
+    }
+}
+
+impl Field {
+    /// The dump path of the field, such as `"Ipv4.Time to Live"`.
+    pub fn path(&self) -> &str {
+        &self.path[..]
+    }
+
+    pub fn value(&self) -> &FieldValue {
+        &self.value
+    }
+
+    /// The description of the value, such as an address in its usual
+    /// notation.
+    pub fn descr(&self) -> Option<&str> {
+        self.descr.as_deref()
+    }
+}
+
+impl std::fmt::Display for Field {
+    /// Formats the description if there is one, and the value otherwise.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.descr {
+            Some(descr) => f.write_str(&descr[..]),
+            None => self.value.fmt(f),
+        }
+    }
+}
+
+impl Number {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "true" => return Some(Self::Int(1)),
+            "false" => return Some(Self::Int(0)),
+            _ => {}
+        }
+        let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => i128::from_str_radix(hex, 16),
+            None => s.parse::<i128>(),
+        };
+        match parsed {
+            Ok(val) => Some(Self::Int(val)),
+            Err(_) => s.parse::<f64>().ok().map(Self::Float),
+        }
+    }
+
+    fn cmp(self, other: Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(&b)),
+            (Self::Int(a), Self::Float(b)) => (a as f64).partial_cmp(&b),
+            (Self::Float(a), Self::Int(b)) => a.partial_cmp(&(b as f64)),
+            (Self::Float(a), Self::Float(b)) => a.partial_cmp(&b),
+        }
+    }
+}
+
+impl CmpOp {
+    fn test(self, ord: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            Self::Eq => ord == Equal,
+            Self::Ne => ord != Equal,
+            Self::Lt => ord == Less,
+            Self::Le => ord != Greater,
+            Self::Gt => ord == Greater,
+            Self::Ge => ord != Less,
+        }
+    }
+}
+
+fn compare(entry: &Entry, op: CmpOp, lit: &str) -> bool {
+    let Some(value) = entry.field.as_ref() else {
+        return false;
+    };
+    if let (Some(a), Some(b)) = (value.number(), Number::parse(lit)) {
+        return a.cmp(b).is_some_and(|ord| op.test(ord));
+    }
+    let text = value.to_string();
+    if op == CmpOp::Eq {
+        let descr = entry.descr.as_deref().unwrap_or("");
+        text.eq_ignore_ascii_case(lit)
+            || descr == lit
+            || descr.split(' ').next().is_some_and(|word| word == lit)
+    } else {
+        op.test(text.as_str().cmp(lit))
+    }
+}
+
+impl Expr {
+    fn eval(&self, entries: &[Entry]) -> bool {
+        match self {
+            Self::And(a, b) => a.eval(entries) && b.eval(entries),
+            Self::Or(a, b) => a.eval(entries) || b.eval(entries),
+            Self::Not(a) => !a.eval(entries),
+            Self::Exists(name) => find(entries, name).next().is_some(),
+            Self::Compare(name, CmpOp::Ne, lit) => {
+                !find(entries, name).any(|entry| compare(entry, CmpOp::Eq, lit))
+            }
+            Self::Compare(name, op, lit) => {
+                find(entries, name).any(|entry| compare(entry, *op, lit))
+            }
+        }
+    }
+}
+
+impl BpfField {
+    fn lookup(path: &str) -> Option<Self> {
+        BPF_FIELDS
+            .iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, field)| *field)
+    }
+
+    fn exists(self) -> Option<String> {
+        match self {
+            Self::Proto(proto) | Self::Port(proto, _) => Some(String::from(proto)),
+            Self::Host(_) | Self::IpProto => Some(String::from("ip")),
+            Self::Ether(_) => None,
+        }
+    }
+
+    /// Translates `field op lit`, for any `op` but `CmpOp::Ne`.
+    fn compare(self, op: CmpOp, lit: &str) -> Option<String> {
+        let int = || match Number::parse(lit)? {
+            Number::Int(val) => Some(val),
+            Number::Float(_) => None,
+        };
+        match (self, op) {
+            (Self::Proto(_), _) => None,
+            (Self::Host(quals), CmpOp::Eq) => {
+                let addr: std::net::Ipv4Addr = lit.parse().ok()?;
+                Some(format!("{quals} {addr}"))
+            }
+            (Self::Ether(quals), CmpOp::Eq) => {
+                let octets: Vec<_> = lit.split([':', '-']).collect();
+                let valid = octets.len() == 6
+                    && octets
+                        .iter()
+                        .all(|octet| octet.len() == 2 && u8::from_str_radix(octet, 16).is_ok());
+                valid.then(|| format!("{quals} {}", octets.join(":").to_ascii_lowercase()))
+            }
+            (Self::Host(_) | Self::Ether(_), _) => None,
+            (Self::Port(proto, dir), op) => {
+                let port = int()?;
+                let (lo, hi) = match op {
+                    CmpOp::Lt => (0, port - 1),
+                    CmpOp::Le => (0, port),
+                    CmpOp::Gt => (port + 1, 0xFFFF),
+                    CmpOp::Ge => (port, 0xFFFF),
+                    _ => (port, port),
+                };
+                let (lo, hi) = (lo.max(0), hi.min(0xFFFF));
+                if lo > hi {
+                    None
+                } else if lo == hi {
+                    Some(format!("{proto} {dir} port {lo}"))
+                } else {
+                    Some(format!("{proto} {dir} portrange {lo}-{hi}"))
+                }
+            }
+            (Self::IpProto, op) => {
+                let proto = int().filter(|proto| (0..=0xFF).contains(proto))?;
+                Some(match op {
+                    CmpOp::Eq => format!("ip proto {proto}"),
+                    CmpOp::Lt => format!("ip[9] < {proto}"),
+                    CmpOp::Le => format!("ip[9] <= {proto}"),
+                    CmpOp::Gt => format!("ip[9] > {proto}"),
+                    _ => format!("ip[9] >= {proto}"),
+                })
+            }
+        }
+    }
+}
+
+/// Joins the translations of each of a field's paths, since the field
+/// matches if any of them do. Every path must translate.
+fn bpf_any(name: &str, f: impl Fn(BpfField) -> Option<String>) -> Option<String> {
+    let exprs = paths(name)
+        .iter()
+        .map(|path| BpfField::lookup(path).and_then(&f))
+        .collect::<Option<Vec<_>>>()?;
+    match exprs.len() {
+        1 => exprs.into_iter().next(),
+        _ => Some(format!("({})", exprs.join(" or "))),
+    }
+}
+
+impl Expr {
+    /// The BPF expression and whether it's exact, or `None` if no part of
+    /// the expression can be narrowed down in BPF.
+    fn to_bpf(&self) -> Option<(String, bool)> {
+        match self {
+            Self::And(a, b) => match (a.to_bpf(), b.to_bpf()) {
+                (Some((a, a_exact)), Some((b, b_exact))) => {
+                    Some((format!("({a}) and ({b})"), a_exact && b_exact))
+                }
+                // Dropping a side of an `and` only matches more packets
+                (Some((expr, _)), None) | (None, Some((expr, _))) => Some((expr, false)),
+                (None, None) => None,
+            },
+            Self::Or(a, b) => {
+                let ((a, a_exact), (b, b_exact)) = (a.to_bpf()?, b.to_bpf()?);
+                Some((format!("({a}) or ({b})"), a_exact && b_exact))
+            }
+            Self::Not(a) => match a.to_bpf()? {
+                (expr, true) => Some((format!("not ({expr})"), true)),
+                (_, false) => None,
+            },
+            Self::Exists(name) => bpf_any(name, BpfField::exists).map(|expr| (expr, true)),
+            Self::Compare(name, CmpOp::Ne, lit) => {
+                bpf_any(name, |field| field.compare(CmpOp::Eq, lit))
+                    .map(|expr| (format!("not {expr}"), true))
+            }
+            Self::Compare(name, op, lit) => {
+                bpf_any(name, |field| field.compare(*op, lit)).map(|expr| (expr, true))
+            }
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    const SPECIAL: &[char] = &['(', ')', '!', '=', '<', '>', '&', '|', '"'];
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let mut next_is = |want: char| chars.next_if(|(_, c)| *c == want).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' if next_is('=') => Token::Op(CmpOp::Eq),
+            '!' if next_is('=') => Token::Op(CmpOp::Ne),
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Op(CmpOp::Le),
+            '<' => Token::Op(CmpOp::Lt),
+            '>' if next_is('=') => Token::Op(CmpOp::Ge),
+            '>' => Token::Op(CmpOp::Gt),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => text.push(c),
+                            None => return Err(QueryError::new(offset, "Unterminated string")),
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => return Err(QueryError::new(offset, "Unterminated string")),
+                    }
+                }
+                Token::Str(text)
+            }
+            c if SPECIAL.contains(&c) => {
+                return Err(QueryError::new(offset, "Unexpected character"));
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| !c.is_whitespace() && !SPECIAL.contains(c))
+                {
+                    word.push(c);
+                }
+                match &word[..] {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                }
+            }
+        };
+        tokens.push((offset, token));
+    }
+    Ok(tokens)
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(offset, _)| *offset)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, token)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, QueryError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, QueryError> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(QueryError::new(offset, "Unclosed parenthesis")),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let Some(&Token::Op(op)) = self.peek() else {
+                    return Ok(Expr::Exists(name));
+                };
+                self.pos += 1;
+                let offset = self.offset();
+                match self.next() {
+                    Some(Token::Word(lit)) | Some(Token::Str(lit)) => {
+                        Ok(Expr::Compare(name, op, lit))
+                    }
+                    _ => Err(QueryError::new(offset, "Expected a value")),
+                }
+            }
+            _ => Err(QueryError::new(offset, "Expected a field")),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser {
+            tokens: tokenize(query)?,
+            pos: 0,
+            end: query.len(),
+        };
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(QueryError::new(parser.offset(), "Unexpected token"));
+        }
+        Ok(Self { expr })
+    }
+
+    pub fn matches(&self, packet: &Packet) -> bool {
+        self.matches_entries(&DiffCollector::collect_packet(packet)[..])
+    }
+
+    pub(crate) fn matches_entries(&self, entries: &[Entry]) -> bool {
+        self.expr.eval(entries)
+    }
+
+    /// Translates the query to a BPF filter expression, for the parts that
+    /// map cleanly: IPv4 and Ethernet addresses, TCP and UDP ports, and the
+    /// presence of IPv4, TCP, and UDP. A live capture can then filter in the
+    /// kernel, and only test the packets that pass against the full query.
+    ///
+    /// Parts of an `and` that can't be translated are left out, which
+    /// matches more packets than the query, and the filter is then not
+    /// exact. Returns `None` if nothing could be translated. Note that BPF
+    /// only looks at the outermost headers, not those in tunnels, so even
+    /// an exact filter can differ from the query for tunneled packets.
+    ///
+    /// ```ignore
+    /// let query = Query::parse("tcp.port == 443 && http.method == GET")?;
+    /// let bpf = query.to_bpf().unwrap();
+    /// assert_eq!(bpf.expr(), "(tcp src port 443 or tcp dst port 443)");
+    /// assert!(!bpf.is_exact());
+    /// ```
+    pub fn to_bpf(&self) -> Option<BpfFilter> {
+        let (expr, exact) = self.expr.to_bpf()?;
+        Some(BpfFilter { expr, exact })
+    }
+}
+
+impl BpfFilter {
+    /// The filter expression, in pcap filter syntax.
+    pub fn expr(&self) -> &str {
+        &self.expr[..]
+    }
+
+    /// True if the filter matches exactly the same packets as the query,
+    /// so the query doesn't need to be tested again after dissection.
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+}
+
+impl std::fmt::Display for BpfFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expr[..])
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, QueryError> {
+        Self::parse(s)
+    }
+}
+
+impl QueryError {
+    fn new(offset: usize, msg: &'static str) -> Self {
+        Self { offset, msg }
+    }
+
+    /// Byte offset of the error in the query.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+pub(crate) fn get(packet: &Packet, name: &str) -> Option<Field> {
+    find_field(&DiffCollector::collect_packet(packet)[..], name)
+}
+
+pub(crate) fn find_field(entries: &[Entry], name: &str) -> Option<Field> {
+    let entry = find(entries, name).find(|entry| entry.field.is_some())?;
+    Some(Field {
+        path: entry.path.clone(),
+        value: entry.field.clone()?,
+        descr: entry.descr.clone(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PduExt, RawPdu};
+
+    #[test]
+    fn query_fields() {
+        _register_field("raw.data", "Raw Bytes.Data");
+        let mut pdu = RawPdu::new(vec![0xab, 0xcd]);
+        pdu.set_inner_pdu(RawPdu::new(vec![7]));
+        let packet = Packet::new(SystemTime::UNIX_EPOCH, pdu, None, None, None);
+
+        let field = packet.get("raw_bytes.data").unwrap();
+        assert_eq!(field.path(), "Raw Bytes.Data");
+        assert_eq!(field.value(), &FieldValue::Bytes(vec![0xab, 0xcd]));
+        assert_eq!(packet.get("Raw Bytes.Data"), Some(field));
+        assert!(packet.get("raw_bytes.bogus").is_none());
+
+        let matches = |query: &str| packet.matches(query).unwrap();
+        assert!(matches("raw.data"));
+        assert!(matches("raw.data == abcd"));
+        assert!(matches("raw.data == \"07\" && !bogus"));
+        assert!(matches("raw.data != 01 and (bogus or raw.data > 0000)"));
+        assert!(!matches("raw.data != 07"));
+        assert!(!matches("not raw.data || bogus"));
+
+        assert_eq!(Query::parse("raw.data ==").unwrap_err().offset(), 11);
+        assert_eq!(Query::parse("(raw.data").unwrap_err().offset(), 0);
+        assert_eq!(Query::parse("a b").unwrap_err().offset(), 2);
+        assert!(Query::parse("a = b").is_err());
+    }
+
+    #[test]
+    fn bpf_translation() {
+        _register_field("bpf_test.port", "TCP.Source Port");
+        _register_field("bpf_test.port", "TCP.Destination Port");
+        let bpf = |query: &str| {
+            Query::parse(query)
+                .unwrap()
+                .to_bpf()
+                .map(|bpf| (bpf.to_string(), bpf.is_exact()))
+        };
+        let exact = |expr: &str| Some((String::from(expr), true));
+
+        assert_eq!(bpf("tcp"), exact("tcp"));
+        assert_eq!(
+            bpf("ipv4.source_address == 10.0.0.1"),
+            exact("ip src host 10.0.0.1")
+        );
+        assert_eq!(
+            bpf("bpf_test.port == 443"),
+            exact("(tcp src port 443 or tcp dst port 443)")
+        );
+        assert_eq!(
+            bpf("udp.destination_port < 1024 or ipv4.protocol == 1"),
+            exact("(udp dst portrange 0-1023) or (ip proto 1)")
+        );
+        assert_eq!(
+            bpf("not ethernet_ii.src_address == AA-BB-CC-DD-EE-FF"),
+            exact("not (ether src aa:bb:cc:dd:ee:ff)")
+        );
+        assert_eq!(bpf("tcp.source_port != 22"), exact("not tcp src port 22"));
+        assert_eq!(
+            bpf("udp and raw.data == 00"),
+            Some((String::from("udp"), false))
+        );
+        assert_eq!(bpf("udp or raw.data == 00"), None);
+        assert_eq!(bpf("not (udp and raw.data == 00)"), None);
+        assert_eq!(bpf("ipv4.source_address == host.example"), None);
+        assert_eq!(bpf("tcp.source_port < 0"), None);
+    }
+}
@@ -1,35 +1,81 @@
-use super::{BasePdu, DResult, Dissect, Dump, DumpValue, NodeDumper, Pdu, Session, TempPdu};
+use super::{BasePdu, Bytes, DResult, Dissect, Dump, DumpValue, NodeDumper, Pdu, Session, TempPdu};
 use sniffle_ende::decode::Decode;
 use sniffle_ende::encode::Encoder;
 use sniffle_ende::nom::combinator::{map, rest};
+use std::ops::{Deref, DerefMut};
 
+/// Bytes that were not dissected as any protocol.
+///
+/// When dissected, the data references the buffer of the packet being
+/// dissected (see `Session::packet_bytes`), rather than being copied.
 #[derive(Debug)]
 pub struct RawPdu {
     base: BasePdu,
-    data: Vec<u8>,
+    data: Bytes,
+}
+
+/// Mutable access to the data of a `RawPdu`, returned by `RawPdu::data_mut`.
+/// The edited data is stored back in the PDU when this is dropped.
+pub struct RawData<'a> {
+    data: &'a mut Bytes,
+    buf: Vec<u8>,
 }
 
 impl RawPdu {
-    pub fn new(data: Vec<u8>) -> Self {
+    pub fn new<B: Into<Bytes>>(data: B) -> Self {
         Self {
             base: BasePdu::default(),
-            data,
+            data: data.into(),
         }
     }
 
-    pub fn data(&self) -> &Vec<u8> {
+    pub fn data(&self) -> &Bytes {
         &self.data
     }
 
-    pub fn data_mut(&mut self) -> &mut Vec<u8> {
-        &mut self.data
+    pub fn set_data<B: Into<Bytes>>(&mut self, data: B) {
+        self.base.mark_dirty();
+        self.data = data.into();
+    }
+
+    /// Returns the data for editing in place. The data is copied out of the
+    /// packet buffer only if the buffer is still shared.
+    pub fn data_mut(&mut self) -> RawData<'_> {
+        self.base.mark_dirty();
+        let buf = Vec::from(std::mem::take(&mut self.data));
+        RawData {
+            data: &mut self.data,
+            buf,
+        }
+    }
+}
+
+impl<'a> Deref for RawData<'a> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl<'a> DerefMut for RawData<'a> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl<'a> Drop for RawData<'a> {
+    fn drop(&mut self) {
+        *self.data = Bytes::from(std::mem::take(&mut self.buf));
     }
 }
 
 impl Clone for RawPdu {
     fn clone(&self) -> Self {
+        let mut base = BasePdu::default();
+        base.set_truncated_len(self.base.truncated_len());
         Self {
-            base: BasePdu::default(),
+            base,
             data: self.data.clone(),
         }
     }
@@ -37,20 +83,17 @@ impl Clone for RawPdu {
 
 impl Decode for RawPdu {
     fn decode(buf: &[u8]) -> DResult<'_, Self> {
-        map(rest, |buf| Self {
-            base: BasePdu::default(),
-            data: Vec::from(buf),
-        })(buf)
+        map(rest, |buf| Self::new(Bytes::copy_from_slice(buf)))(buf)
     }
 }
 
 impl Dissect for RawPdu {
     fn dissect<'a>(
         buf: &'a [u8],
-        _session: &Session,
+        session: &Session,
         _parent: Option<TempPdu<'_>>,
     ) -> DResult<'a, Self> {
-        Self::decode(buf)
+        map(rest, |buf| Self::new(session.packet_bytes(buf)))(buf)
     }
 }
 
@@ -76,6 +119,33 @@ impl Pdu for RawPdu {
 
     fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<'_, D>) -> Result<(), D::Error> {
         let mut node = dumper.add_node("Raw Bytes", None)?;
-        node.add_field("Data", DumpValue::Bytes(&self.data[..]), None)
+        node.byte_range(0, self.data.len()).add_field(
+            "Data",
+            DumpValue::Bytes(&self.data[..]),
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PduExt;
+
+    #[test]
+    fn data_mut() {
+        let buffer = Bytes::from(vec![1u8, 2, 3, 4]);
+        let mut pdu = RawPdu::new(buffer.slice(1..3));
+        pdu.data_mut().push(5);
+        assert_eq!(&pdu.data()[..], &[2, 3, 5][..]);
+        assert!(pdu.is_modified());
+        // The shared buffer was copied, not edited
+        assert_eq!(&buffer[..], &[1, 2, 3, 4][..]);
+
+        // An unshared buffer is edited without a copy
+        let ptr = pdu.data().as_ptr();
+        pdu.data_mut()[0] = 9;
+        assert_eq!(&pdu.data()[..], &[9, 3, 5][..]);
+        assert_eq!(pdu.data().as_ptr(), ptr);
     }
 }
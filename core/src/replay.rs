@@ -0,0 +1,218 @@
+use super::{Error, SniffRaw, Transmit};
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+/// Replays captured packets from a raw sniffer (typically a capture file)
+/// out of a transmitter (typically a `DeviceInjector`), reproducing the
+/// original inter-packet gaps.
+///
+/// Sleeping is done with tokio timers, so the runtime must have time
+/// enabled.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    speed: Option<f64>,
+    max_burst: Option<usize>,
+    limit: Option<u64>,
+}
+
+/// Results of a completed replay
+#[derive(Debug, Clone, Default)]
+pub struct ReplaySummary {
+    /// Number of packets successfully transmitted
+    pub sent: u64,
+    /// Number of packets that failed to transmit
+    pub dropped: u64,
+    /// Number of bytes successfully transmitted
+    pub bytes: u64,
+    /// Wall clock time spent replaying
+    pub elapsed: Duration,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self {
+            speed: Some(1.0),
+            max_burst: None,
+            limit: None,
+        }
+    }
+
+    /// Scales the original timing, e.g. `2.0` replays twice as fast.
+    /// Values that are not positive and finite replay as fast as possible.
+    pub fn speed(self, multiplier: f64) -> Self {
+        let mut replay = self;
+        replay.speed = if multiplier.is_finite() && multiplier > 0.0 {
+            Some(multiplier)
+        } else {
+            None
+        };
+        replay
+    }
+
+    /// Ignores the original timing and transmits as fast as possible.
+    pub fn top_speed(self) -> Self {
+        let mut replay = self;
+        replay.speed = None;
+        replay
+    }
+
+    /// Limits how many packets may be sent back to back when the replay
+    /// falls behind schedule. Once the limit is reached, the schedule is
+    /// reset relative to the current packet instead of trying to catch up.
+    pub fn max_burst(self, packets: usize) -> Self {
+        let mut replay = self;
+        replay.max_burst = Some(packets);
+        replay
+    }
+
+    /// Stops after the given number of packets.
+    pub fn limit(self, packets: u64) -> Self {
+        let mut replay = self;
+        replay.limit = Some(packets);
+        replay
+    }
+
+    pub async fn run<S, T>(
+        &self,
+        sniffer: &mut S,
+        transmitter: &mut T,
+    ) -> Result<ReplaySummary, Error>
+    where
+        S: SniffRaw + ?Sized,
+        T: Transmit + ?Sized,
+    {
+        let mut summary = ReplaySummary::default();
+        let started = Instant::now();
+        let mut anchor: Option<(SystemTime, Instant)> = None;
+        let mut burst = 0usize;
+
+        while self
+            .limit
+            .map(|limit| summary.sent + summary.dropped < limit)
+            .unwrap_or(true)
+        {
+            let pkt = match sniffer.sniff_raw().await? {
+                Some(pkt) => pkt,
+                None => break,
+            };
+
+            if let Some(speed) = self.speed {
+                let ts = pkt.timestamp();
+                let (first_ts, first_inst) = *anchor.get_or_insert((ts, Instant::now()));
+                let offset = ts
+                    .duration_since(first_ts)
+                    .unwrap_or_default()
+                    .div_f64(speed);
+                let target = first_inst + offset;
+                let now = Instant::now();
+                if target > now {
+                    burst = 0;
+                    tokio::time::sleep_until(target).await;
+                } else {
+                    burst += 1;
+                    if let Some(max_burst) = self.max_burst {
+                        if burst > max_burst {
+                            burst = 0;
+                            anchor = Some((ts, now));
+                        }
+                    }
+                }
+            }
+
+            let len = pkt.data().len() as u64;
+            match transmitter.transmit_raw(pkt).await {
+                Ok(()) => {
+                    summary.sent += 1;
+                    summary.bytes += len;
+                }
+                Err(_) => {
+                    summary.dropped += 1;
+                }
+            }
+        }
+
+        summary.elapsed = started.elapsed();
+        Ok(summary)
+    }
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LinkType, RawPacket};
+    use async_trait::async_trait;
+
+    struct Source {
+        pkts: Vec<(SystemTime, Vec<u8>)>,
+        curr: Option<Vec<u8>>,
+    }
+
+    #[async_trait]
+    impl SniffRaw for Source {
+        async fn sniff_raw(&mut self) -> Result<Option<RawPacket<'_>>, Error> {
+            if self.pkts.is_empty() {
+                return Ok(None);
+            }
+            let (ts, data) = self.pkts.remove(0);
+            let len = data.len();
+            let data = self.curr.insert(data);
+            Ok(Some(RawPacket::new(
+                LinkType::ETHERNET,
+                ts,
+                len,
+                None,
+                &data[..],
+                None,
+            )))
+        }
+    }
+
+    struct Sink(Vec<Instant>);
+
+    #[async_trait]
+    impl Transmit for Sink {
+        async fn transmit_raw(&mut self, packet: RawPacket<'_>) -> Result<(), Error> {
+            if packet.data().is_empty() {
+                return Err(Error::MalformedCapture);
+            }
+            self.0.push(Instant::now());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replay_timing() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let t0 = std::time::UNIX_EPOCH;
+            let mut src = Source {
+                pkts: vec![
+                    (t0, vec![1, 2]),
+                    (t0 + Duration::from_millis(100), vec![]),
+                    (t0 + Duration::from_millis(300), vec![3]),
+                ],
+                curr: None,
+            };
+            let mut sink = Sink(Vec::new());
+            let summary = Replay::new()
+                .speed(2.0)
+                .run(&mut src, &mut sink)
+                .await
+                .unwrap();
+            assert_eq!(summary.sent, 2);
+            assert_eq!(summary.dropped, 1);
+            assert_eq!(summary.bytes, 3);
+            assert_eq!(sink.0[1] - sink.0[0], Duration::from_millis(150));
+        });
+    }
+}
@@ -0,0 +1,363 @@
+use super::{Annotation, Dump, DumpValue, Error};
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::io::AsyncBufReadExt;
+
+/// Where a host name came from. When an address has names from more than
+/// one source, the name from the highest source is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NameSource {
+    /// A reverse DNS lookup.
+    Dns,
+    /// The capture file, such as a pcapng Name Resolution Block.
+    Capture,
+    /// A hosts table provided by the user.
+    Hosts,
+}
+
+/// Looks up the host name of an address, such as with reverse DNS.
+#[async_trait]
+pub trait ReverseLookup: Send + Sync {
+    async fn reverse_lookup(&self, addr: IpAddr) -> Option<String>;
+}
+
+/// Reverse DNS using the system resolver.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemReverseLookup;
+
+/// Maps IP addresses to host names.
+///
+/// Register a `NameResolver` in a `Session` to have capture files add the
+/// names they record, such as pcapng Name Resolution Blocks. Wrap a dumper
+/// in a `ResolvingDumper` to show names beside addresses in dumps. Clones
+/// share the same table of names.
+///
+/// Dumps only use names that are already known. Live reverse lookups are
+/// done by `resolve`, which caches the result, if a `ReverseLookup` is set.
+#[derive(Clone, Default)]
+pub struct NameResolver {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    names: RwLock<HashMap<IpAddr, (NameSource, Option<String>)>>,
+    reverse: RwLock<Option<Arc<dyn ReverseLookup>>>,
+}
+
+/// A dumper that shows the names known to a `NameResolver` beside
+/// addresses. See `Dump::resolve_name`.
+pub struct ResolvingDumper<D: Dump> {
+    dumper: D,
+    resolver: NameResolver,
+}
+
+impl NameResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables live lookups of unknown addresses by `resolve`.
+    pub fn reverse_lookup<L: ReverseLookup + 'static>(self, lookup: L) -> Self {
+        *self.inner.reverse.write() = Some(Arc::new(lookup));
+        self
+    }
+
+    /// Adds a name for `addr`. The name replaces any existing name from the
+    /// same or a lower source.
+    pub fn insert<A: Into<IpAddr>, S: Into<String>>(&self, addr: A, name: S, source: NameSource) {
+        let mut names = self.inner.names.write();
+        let entry = names.entry(addr.into()).or_insert((source, None));
+        if entry.1.is_none() || entry.0 <= source {
+            *entry = (source, Some(name.into()));
+        }
+    }
+
+    /// Adds the entries of a hosts file, in the format of `/etc/hosts`: an
+    /// address followed by one or more names, with `#` starting a comment.
+    /// The first name of each line is used. Returns the number of entries
+    /// added.
+    pub async fn load_hosts<R: tokio::io::AsyncBufRead + Unpin>(
+        &self,
+        mut reader: R,
+    ) -> Result<usize, Error> {
+        let mut line = String::new();
+        let mut count = 0;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(count);
+            }
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            let addr = match fields.next().map(str::parse::<IpAddr>) {
+                Some(Ok(addr)) => addr,
+                _ => continue,
+            };
+            if let Some(name) = fields.next() {
+                self.insert(addr, name, NameSource::Hosts);
+                count += 1;
+            }
+        }
+    }
+
+    /// Returns the known name of `addr`, without doing any lookups.
+    pub fn lookup<A: Into<IpAddr>>(&self, addr: A) -> Option<String> {
+        self.inner
+            .names
+            .read()
+            .get(&addr.into())
+            .and_then(|(_, name)| name.clone())
+    }
+
+    /// Returns the name of `addr`, doing a reverse lookup if the name isn't
+    /// known and a `ReverseLookup` is set. Failed lookups are remembered and
+    /// not retried.
+    pub async fn resolve<A: Into<IpAddr>>(&self, addr: A) -> Option<String> {
+        let addr = addr.into();
+        if let Some((_, name)) = self.inner.names.read().get(&addr) {
+            return name.clone();
+        }
+        let lookup = self.inner.reverse.read().clone()?;
+        let name = lookup.reverse_lookup(addr).await;
+        let mut names = self.inner.names.write();
+        let entry = names.entry(addr).or_insert((NameSource::Dns, None));
+        if entry.1.is_none() {
+            entry.1 = name;
+        }
+        entry.1.clone()
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl ReverseLookup for SystemReverseLookup {
+    async fn reverse_lookup(&self, addr: IpAddr) -> Option<String> {
+        tokio::task::spawn_blocking(move || getnameinfo(addr))
+            .await
+            .ok()
+            .flatten()
+    }
+}
+
+#[cfg(unix)]
+fn getnameinfo(addr: IpAddr) -> Option<String> {
+    use std::mem::{size_of, zeroed};
+
+    let mut host = [0 as libc::c_char; 1025];
+    // SAFETY: The socket addresses are fully initialized, and their sizes
+    // and the size of `host` are passed along with them.
+    let ret = unsafe {
+        match addr {
+            IpAddr::V4(addr) => {
+                let mut sa: libc::sockaddr_in = zeroed();
+                sa.sin_family = libc::AF_INET as libc::sa_family_t;
+                sa.sin_addr.s_addr = u32::from_ne_bytes(addr.octets());
+                libc::getnameinfo(
+                    &sa as *const libc::sockaddr_in as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+            IpAddr::V6(addr) => {
+                let mut sa: libc::sockaddr_in6 = zeroed();
+                sa.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sa.sin6_addr.s6_addr = addr.octets();
+                libc::getnameinfo(
+                    &sa as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+    };
+    if ret != 0 {
+        return None;
+    }
+    // SAFETY: getnameinfo succeeded, so `host` holds a nul terminated string.
+    let name = unsafe { std::ffi::CStr::from_ptr(host.as_ptr()) };
+    name.to_str().ok().map(String::from)
+}
+
+impl<D: Dump> ResolvingDumper<D> {
+    pub fn new(dumper: D, resolver: NameResolver) -> Self {
+        Self { dumper, resolver }
+    }
+
+    pub fn resolver(&self) -> &NameResolver {
+        &self.resolver
+    }
+
+    pub fn into_inner(self) -> D {
+        self.dumper
+    }
+}
+
+impl<D: Dump> std::ops::Deref for ResolvingDumper<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        &self.dumper
+    }
+}
+
+impl<D: Dump> std::ops::DerefMut for ResolvingDumper<D> {
+    fn deref_mut(&mut self) -> &mut D {
+        &mut self.dumper
+    }
+}
+
+impl<D: Dump> Dump for ResolvingDumper<D> {
+    type Error = D::Error;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        self.dumper.start_packet()
+    }
+
+    fn end_packet(&mut self) {
+        self.dumper.end_packet()
+    }
+
+    fn start_node(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_node(name, descr)
+    }
+
+    fn end_node(&mut self) {
+        self.dumper.end_node()
+    }
+
+    fn add_field(
+        &mut self,
+        name: &str,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        self.dumper.add_field(name, value, descr)
+    }
+
+    fn add_info(&mut self, name: &str, descr: &str) -> Result<(), Self::Error> {
+        self.dumper.add_info(name, descr)
+    }
+
+    fn start_list(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_list(name, descr)
+    }
+
+    fn end_list(&mut self) {
+        self.dumper.end_list()
+    }
+
+    fn add_list_item(
+        &mut self,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        self.dumper.add_list_item(value, descr)
+    }
+
+    fn start_list_node(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_list_node(descr)
+    }
+
+    fn end_list_node(&mut self) {
+        self.dumper.end_list_node()
+    }
+
+    fn start_list_sublist(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.dumper.start_list_sublist(descr)
+    }
+
+    fn end_list_sublist(&mut self) {
+        self.dumper.end_list_sublist()
+    }
+
+    fn set_byte_range(&mut self, offset: usize, len: usize) {
+        self.dumper.set_byte_range(offset, len)
+    }
+
+    fn resolve_name(&self, addr: IpAddr) -> Option<String> {
+        self.resolver.lookup(addr)
+    }
+
+    fn annotate_addr(&self, addr: IpAddr) -> Option<Annotation> {
+        self.dumper.annotate_addr(addr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    struct Fixed;
+
+    #[async_trait]
+    impl ReverseLookup for Fixed {
+        async fn reverse_lookup(&self, addr: IpAddr) -> Option<String> {
+            match addr {
+                IpAddr::V4(addr) if addr.octets()[3] == 3 => Some("dns.example".into()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_names() {
+        let hosts = "\
+# comment
+10.0.0.1  router gw # trailing comment
+fe80::1   link-local
+bogus     line
+";
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let resolver = NameResolver::new().reverse_lookup(Fixed);
+            let shared = resolver.clone();
+            assert_eq!(resolver.load_hosts(hosts.as_bytes()).await.unwrap(), 2);
+            shared.insert(Ipv4Addr::new(10, 0, 0, 1), "nrb", NameSource::Capture);
+            shared.insert(Ipv4Addr::new(10, 0, 0, 2), "nrb", NameSource::Capture);
+
+            assert_eq!(
+                resolver.lookup(Ipv4Addr::new(10, 0, 0, 1)).as_deref(),
+                Some("router")
+            );
+            assert_eq!(
+                resolver
+                    .lookup("fe80::1".parse::<IpAddr>().unwrap())
+                    .as_deref(),
+                Some("link-local")
+            );
+            assert_eq!(
+                resolver.lookup(Ipv4Addr::new(10, 0, 0, 2)).as_deref(),
+                Some("nrb")
+            );
+            assert_eq!(resolver.lookup(Ipv4Addr::new(10, 0, 0, 3)), None);
+            assert_eq!(
+                resolver
+                    .resolve(Ipv4Addr::new(10, 0, 0, 3))
+                    .await
+                    .as_deref(),
+                Some("dns.example")
+            );
+            assert_eq!(
+                shared.lookup(Ipv4Addr::new(10, 0, 0, 3)).as_deref(),
+                Some("dns.example")
+            );
+            assert_eq!(resolver.resolve(Ipv4Addr::new(10, 0, 0, 4)).await, None);
+        });
+    }
+}
@@ -0,0 +1,311 @@
+use super::{ByteDumpFormatter, Dump, DumpValue, Dumper, Packet};
+use chrono::{offset::Utc, DateTime, SecondsFormat};
+use serde_json::{Map, Number, Value};
+use std::convert::Infallible;
+use tokio::io::AsyncWriteExt;
+
+/// Dumps packets as `serde_json::Value` trees.
+///
+/// Each packet becomes an object. Nodes become nested objects keyed by
+/// name, fields become values keyed by name, and lists become arrays.
+/// Fields with the same name within a node are collected into an array.
+pub struct SerdeDumper {
+    stack: Vec<Value>,
+    names: Vec<Option<String>>,
+    packets: Vec<Value>,
+    descriptions: bool,
+}
+
+/// Dumps packets as newline delimited JSON, one object per packet.
+pub struct JsonDumper<W: tokio::io::AsyncWrite + Send + Unpin> {
+    writer: W,
+    inner: SerdeDumper,
+    count: u64,
+    pretty: bool,
+    buf: Vec<u8>,
+}
+
+pub(crate) fn to_value(value: DumpValue<'_>) -> Value {
+    match value {
+        DumpValue::Bool(val) => Value::Bool(val),
+        DumpValue::Int(val) => Value::from(val),
+        DumpValue::UInt(val) => Value::from(val),
+        DumpValue::Float(val) => Number::from_f64(val)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        DumpValue::Text(val) => Value::from(val),
+        DumpValue::Bytes(val) => Value::from(ByteDumpFormatter(val).to_string()),
+        DumpValue::Time(val) => {
+            let ts: DateTime<Utc> = val.into();
+            Value::from(ts.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        }
+        DumpValue::Duration(val) => Number::from_f64(val.as_secs_f64())
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+    }
+}
+
+fn insert(obj: &mut Map<String, Value>, name: &str, value: Value) {
+    match obj.get_mut(name) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(prev) => {
+            let prev = prev.take();
+            let _ = obj.insert(String::from(name), Value::Array(vec![prev, value]));
+        }
+        None => {
+            let _ = obj.insert(String::from(name), value);
+        }
+    }
+}
+
+impl SerdeDumper {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            names: Vec::new(),
+            packets: Vec::new(),
+            descriptions: false,
+        }
+    }
+
+    /// When enabled, fields and list items that have a description are
+    /// emitted as `{"value": ..., "descr": ...}` objects, and node and list
+    /// descriptions are included under a `"descr"` key.
+    pub fn descriptions(self, enable: bool) -> Self {
+        let mut dumper = self;
+        dumper.descriptions = enable;
+        dumper
+    }
+
+    /// Dumps a packet, returning its value.
+    pub fn dump(&mut self, pkt: &Packet) -> Value {
+        let mut dumper = &mut *self;
+        match pkt.dump(&mut Dumper::new(&mut dumper)) {
+            Ok(()) => {}
+            Err(e) => match e {},
+        }
+        self.packets.pop().unwrap_or(Value::Null)
+    }
+
+    /// Returns the packets dumped through the `Dump` interface so far.
+    pub fn packets(&self) -> &[Value] {
+        &self.packets[..]
+    }
+
+    pub fn take_packets(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.packets)
+    }
+
+    fn field_value(&self, value: DumpValue<'_>, descr: Option<&str>) -> Value {
+        let value = to_value(value);
+        match descr {
+            Some(descr) if self.descriptions => {
+                let mut obj = Map::new();
+                let _ = obj.insert(String::from("value"), value);
+                let _ = obj.insert(String::from("descr"), Value::from(descr));
+                Value::Object(obj)
+            }
+            _ => value,
+        }
+    }
+
+    fn push(&mut self, name: Option<&str>, mut value: Value, descr: Option<&str>) {
+        if let (Some(descr), true) = (descr, self.descriptions) {
+            if let Value::Object(obj) = &mut value {
+                let _ = obj.insert(String::from("descr"), Value::from(descr));
+            }
+        }
+        self.stack.push(value);
+        self.names.push(name.map(String::from));
+    }
+
+    fn pop(&mut self) {
+        let (value, name) = match (self.stack.pop(), self.names.pop()) {
+            (Some(value), Some(name)) => (value, name),
+            _ => return,
+        };
+        if self.stack.is_empty() {
+            self.packets.push(value);
+        } else {
+            self.add_to_top(name.as_deref(), value);
+        }
+    }
+
+    fn add_to_top(&mut self, name: Option<&str>, value: Value) {
+        if let Some(parent) = self.stack.last_mut() {
+            match (parent, name) {
+                (Value::Object(obj), Some(name)) => insert(obj, name, value),
+                (Value::Array(items), _) => items.push(value),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Default for SerdeDumper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dump for SerdeDumper {
+    type Error = Infallible;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        self.stack.clear();
+        self.names.clear();
+        self.push(None, Value::Object(Map::new()), None);
+        Ok(())
+    }
+
+    fn end_packet(&mut self) {
+        self.pop();
+    }
+
+    fn start_node(&mut self, name: &str, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.push(Some(name), Value::Object(Map::new()), descr);
+        Ok(())
+    }
+
+    fn end_node(&mut self) {
+        self.pop();
+    }
+
+    fn add_field(
+        &mut self,
+        name: &str,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let value = self.field_value(value, descr);
+        self.add_to_top(Some(name), value);
+        Ok(())
+    }
+
+    fn add_info(&mut self, name: &str, descr: &str) -> Result<(), Self::Error> {
+        self.add_to_top(Some(name), Value::from(descr));
+        Ok(())
+    }
+
+    fn start_list(&mut self, name: &str, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.push(Some(name), Value::Array(Vec::new()), None);
+        Ok(())
+    }
+
+    fn end_list(&mut self) {
+        self.pop();
+    }
+
+    fn add_list_item(
+        &mut self,
+        value: DumpValue<'_>,
+        descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        let value = self.field_value(value, descr);
+        self.add_to_top(None, value);
+        Ok(())
+    }
+
+    fn start_list_node(&mut self, descr: Option<&str>) -> Result<(), Self::Error> {
+        self.push(None, Value::Object(Map::new()), descr);
+        Ok(())
+    }
+
+    fn end_list_node(&mut self) {
+        self.pop();
+    }
+
+    fn start_list_sublist(&mut self, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.push(None, Value::Array(Vec::new()), None);
+        Ok(())
+    }
+
+    fn end_list_sublist(&mut self) {
+        self.pop();
+    }
+}
+
+impl<W: tokio::io::AsyncWrite + Send + Unpin> JsonDumper<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            inner: SerdeDumper::new(),
+            count: 0,
+            pretty: false,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Writes each packet as indented, multi-line JSON instead of one
+    /// packet per line.
+    pub fn pretty(self, enable: bool) -> Self {
+        let mut dumper = self;
+        dumper.pretty = enable;
+        dumper
+    }
+
+    /// See `SerdeDumper::descriptions`.
+    pub fn descriptions(self, enable: bool) -> Self {
+        let mut dumper = self;
+        dumper.inner = dumper.inner.descriptions(enable);
+        dumper
+    }
+
+    pub fn as_inner(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn packet_count(&self) -> u64 {
+        self.count
+    }
+
+    pub async fn dump(&mut self, pkt: &Packet) -> std::io::Result<()> {
+        let value = self.inner.dump(pkt);
+        self.count += 1;
+        self.buf.clear();
+        if self.pretty {
+            serde_json::to_writer_pretty(&mut self.buf, &value)?;
+        } else {
+            serde_json::to_writer(&mut self.buf, &value)?;
+        }
+        self.buf.push(b'\n');
+        self.writer.write_all(&self.buf).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serde_dump() {
+        let mut dumper = SerdeDumper::new().descriptions(true);
+        {
+            let mut dumper = Dumper::new(&mut dumper);
+            let mut pkt = dumper.add_packet().unwrap();
+            let mut node = pkt.add_node("eth", Some("Ethernet")).unwrap();
+            node.add_field("type", DumpValue::UInt(0x800), None)
+                .unwrap();
+            node.add_field("src", DumpValue::Bytes(&[0xab, 0x01]), Some("ab:01"))
+                .unwrap();
+            let mut list = node.add_list("opts", None).unwrap();
+            list.add_item(DumpValue::Int(-1), None).unwrap();
+            list.add_item(DumpValue::Bool(true), None).unwrap();
+        }
+        assert_eq!(
+            dumper.take_packets(),
+            [serde_json::json!({
+                "eth": {
+                    "descr": "Ethernet",
+                    "type": 2048,
+                    "src": { "value": "AB01", "descr": "ab:01" },
+                    "opts": [-1, true],
+                }
+            })]
+        );
+    }
+}
@@ -1,9 +1,9 @@
 use super::{
-    AnyPdu, BasePdu, DResult, Device, Dissector, DissectorTable, DissectorTableParser, Dump,
-    NodeDumper, Pdu, PduExt, Priority, RawPdu, TempPdu,
+    AnyPdu, BasePdu, Bytes, DResult, Device, Dissect, DissectMode, Dissector, DissectorTable,
+    DissectorTableParser, Dump, MemoryBudget, NodeDumper, Pdu, PduExt, Pool, Priority, RawPdu,
+    StrDissectorTable, TempPdu,
 };
 use lazy_static::*;
-use sniffle_ende::decode::Decode;
 use sniffle_ende::encode::Encoder;
 use sniffle_ende::nom::{combinator::map, Parser};
 use std::{
@@ -13,18 +13,51 @@ use std::{
 };
 use tokio::sync::{Mutex, RwLock};
 
+thread_local! {
+    /// The buffer of the packet being dissected on this thread, set by
+    /// `Session::with_packet_buffer`.
+    static PACKET_BUFFER: std::cell::RefCell<Option<Bytes>> = const { std::cell::RefCell::new(None) };
+}
+
 pub(crate) struct LastInfo {
-    pub(crate) ts: std::time::SystemTime,
+    pub(crate) ts: super::Timestamp,
     pub(crate) dev: Option<Arc<Device>>,
     pub(crate) snaplen: usize,
+    pub(crate) truncated: usize,
 }
 
 pub struct Session {
     state: HashMap<TypeId, Box<dyn Any + Send + Sync + 'static>>,
     virt_packets: Mutex<VecDeque<Virtual>>,
     last_info: RwLock<LastInfo>,
+    pool: Pool,
+    memory: MemoryBudget,
+}
+
+/// Builds a `Session` with a chosen set of dissector tables, dissectors,
+/// and state.
+///
+/// By default, the tables and dissectors registered with
+/// `register_dissector_table!` and `register_dissector!` are loaded, just as
+/// with `Session::new()`. Embedders that want a minimal session, or that
+/// load dissectors from plugins at runtime, can opt out of either and add
+/// their own.
+///
+/// ```ignore
+/// let session = Session::builder()
+///     .default_dissectors(false)
+///     .dissector::<LinkTypeTable, _>(LinkType::ETHERNET, Priority(0), my_dissector)
+///     .build();
+/// ```
+pub struct SessionBuilder {
+    default_tables: bool,
+    default_dissectors: bool,
+    tables: Vec<SessionSetup>,
+    setup: Vec<SessionSetup>,
 }
 
+type SessionSetup = Box<dyn FnOnce(&mut Session)>;
+
 #[derive(Debug)]
 pub struct Virtual {
     base: BasePdu,
@@ -33,9 +66,10 @@ pub struct Virtual {
 impl Default for LastInfo {
     fn default() -> Self {
         Self {
-            ts: std::time::SystemTime::UNIX_EPOCH,
+            ts: super::Timestamp::UNIX_EPOCH,
             dev: None,
             snaplen: 0xFFFF,
+            truncated: 0,
         }
     }
 }
@@ -51,6 +85,8 @@ impl Session {
             state: HashMap::new(),
             virt_packets: Mutex::new(VecDeque::new()),
             last_info: RwLock::new(LastInfo::default()),
+            pool: Pool::new(),
+            memory: MemoryBudget::new(),
         }
     }
 
@@ -77,6 +113,10 @@ impl Session {
         session
     }
 
+    pub fn builder() -> SessionBuilder {
+        SessionBuilder::new()
+    }
+
     pub fn register<S: Any + Send + Sync + 'static>(&mut self, state: S) {
         let _ = self
             .state
@@ -114,6 +154,39 @@ impl Session {
             .expect("Requested dissector table is not loaded");
     }
 
+    /// Adds a dissector table, replacing any existing table of the same
+    /// type along with its dissectors.
+    pub fn register_dissector_table<T: DissectorTable + Send + Sync + 'static>(
+        &mut self,
+        table: T,
+    ) {
+        self.state.insert(TypeId::of::<T>(), Box::new(table));
+    }
+
+    /// Loads a dissector into the dissector table `T`. Unlike
+    /// `load_dissector`, an empty table is added first if the session does
+    /// not have one.
+    pub fn register_dissector<
+        T: DissectorTable + Send + Sync + 'static,
+        D: Dissector + Send + Sync + 'static,
+    >(
+        &mut self,
+        param: T::Param,
+        priority: Priority,
+        dissector: D,
+    ) {
+        if self.get::<T>().is_none() {
+            self.register(T::default());
+        }
+        self.load_dissector::<T, D>(param, priority, dissector);
+    }
+
+    /// The `DissectMode` registered in the session, or `DissectMode::Strict`
+    /// if none is.
+    pub fn dissect_mode(&self) -> DissectMode {
+        self.get::<DissectMode>().copied().unwrap_or_default()
+    }
+
     pub fn table_dissector<'a, T: DissectorTable + Send + Sync + 'static>(
         &'a self,
         param: &'a T::Param,
@@ -141,10 +214,52 @@ impl Session {
         parent: Option<TempPdu<'_>>,
     ) -> DResult<'a, AnyPdu> {
         self.table_dissector::<T>(param, parent)
-            .or(map(RawPdu::decode, AnyPdu::new))
+            .or(map(RawPdu::dissector(self, None), AnyPdu::new))
             .parse(buffer)
     }
 
+    /// Like `table_dissector`, but looks up a borrowed name in a
+    /// string-keyed table declared with `dissector_table!(Name, str)`.
+    pub fn table_str_dissector<'a, T: StrDissectorTable + Send + Sync + 'static>(
+        &'a self,
+        name: &str,
+        parent: Option<TempPdu<'a>>,
+    ) -> DissectorTableParser<'a, T> {
+        match self.get::<T>() {
+            Some(table) => table.str_dissector(name, self, parent),
+            None => DissectorTableParser::empty(self, parent),
+        }
+    }
+
+    pub fn table_dissect_str<'a, T: StrDissectorTable + Send + Sync + 'static>(
+        &self,
+        name: &str,
+        buffer: &'a [u8],
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, AnyPdu> {
+        self.table_str_dissector::<T>(name, parent).parse(buffer)
+    }
+
+    /// Allocation pool that dissectors can use to reuse buffers across
+    /// packets.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    pub fn pool_mut(&mut self) -> &mut Pool {
+        &mut self.pool
+    }
+
+    /// Memory budget of the stateful subsystems, such as reassembly and
+    /// conversation tracking, that keep data across packets.
+    pub fn memory(&self) -> &MemoryBudget {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut MemoryBudget {
+        &mut self.memory
+    }
+
     pub async fn enqueue_virtual_packet<P: Pdu + Send + Sync + 'static>(&self, packet: P) {
         let mut virt = Virtual {
             base: Default::default(),
@@ -157,6 +272,78 @@ impl Session {
         self.virt_packets.lock().await.pop_front()
     }
 
+    /// The device that captured the packet currently being dissected, if
+    /// known.
+    pub fn device(&self) -> Option<Arc<Device>> {
+        self.last_info
+            .try_read()
+            .ok()
+            .and_then(|info| info.dev.clone())
+    }
+
+    /// Number of bytes of the packet currently being dissected that were
+    /// not captured, because the packet was longer than the snaplen.
+    ///
+    /// Dissectors can use this to tell a packet cut short by the capture
+    /// from a malformed one, and mark the missing bytes with
+    /// `BasePdu::set_truncated_len`.
+    pub fn truncated_len(&self) -> usize {
+        self.last_info
+            .try_read()
+            .map(|info| info.truncated)
+            .unwrap_or(0)
+    }
+
+    /// Calls `f`, which dissects `buffer`, with `buffer` as the packet
+    /// buffer referenced by `packet_bytes`. Sniffers dissect each packet
+    /// this way; it is only needed when calling dissectors directly.
+    ///
+    /// The buffer is only visible to dissectors called by `f` on the
+    /// current thread, and the previous buffer, if any, is restored
+    /// afterwards.
+    pub fn with_packet_buffer<R, F: FnOnce() -> R>(&self, buffer: &Bytes, f: F) -> R {
+        struct Restore(Option<Bytes>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let prev = self.0.take();
+                PACKET_BUFFER.with(|buffer| *buffer.borrow_mut() = prev);
+            }
+        }
+
+        let prev = PACKET_BUFFER.with(|prev| prev.replace(Some(buffer.clone())));
+        let _restore = Restore(prev);
+        f()
+    }
+
+    /// Returns `data` as `Bytes`. If `data` is part of the buffer of the
+    /// packet currently being dissected, the returned `Bytes` references
+    /// that buffer instead of copying the data.
+    ///
+    /// Dissectors should use this for payloads and other byte strings they
+    /// keep in their Pdus, so that reading a capture doesn't need an
+    /// allocation for each of them.
+    pub fn packet_bytes(&self, data: &[u8]) -> Bytes {
+        self.shared_packet_bytes(data)
+            .unwrap_or_else(|| Bytes::copy_from_slice(data))
+    }
+
+    /// Like `packet_bytes`, but returns `None` instead of copying `data`
+    /// when it isn't part of the packet buffer.
+    pub fn shared_packet_bytes(&self, data: &[u8]) -> Option<Bytes> {
+        PACKET_BUFFER.with(|buffer| {
+            let buffer = buffer.borrow();
+            let buffer = buffer.as_ref()?;
+            let start = buffer.as_ptr() as usize;
+            let addr = data.as_ptr() as usize;
+            if addr >= start && addr + data.len() <= start + buffer.len() {
+                Some(buffer.slice_ref(data))
+            } else {
+                None
+            }
+        })
+    }
+
     pub(crate) async fn last_info<R, F: FnOnce(&LastInfo) -> R>(&self, f: F) -> R {
         let guard = self.last_info.read().await;
         f(&guard)
@@ -174,6 +361,118 @@ impl Default for Session {
     }
 }
 
+impl SessionBuilder {
+    pub fn new() -> Self {
+        Self {
+            default_tables: true,
+            default_dissectors: true,
+            tables: Vec::new(),
+            setup: Vec::new(),
+        }
+    }
+
+    /// Whether to load the tables registered with
+    /// `register_dissector_table!`. Defaults to true.
+    pub fn default_tables(mut self, load: bool) -> Self {
+        self.default_tables = load;
+        self
+    }
+
+    /// Whether to load the dissectors registered with `register_dissector!`.
+    /// Defaults to true. The default dissectors are loaded into the default
+    /// tables, so this also loads the default tables.
+    pub fn default_dissectors(mut self, load: bool) -> Self {
+        self.default_dissectors = load;
+        self
+    }
+
+    /// Adds a dissector table, replacing a default table of the same type.
+    pub fn table<T: DissectorTable + Send + Sync + 'static>(mut self, table: T) -> Self {
+        self.tables.push(Box::new(move |session| {
+            session.register_dissector_table(table)
+        }));
+        self
+    }
+
+    /// Adds a dissector. See `Session::register_dissector`.
+    pub fn dissector<
+        T: DissectorTable + Send + Sync + 'static,
+        D: Dissector + Send + Sync + 'static,
+    >(
+        mut self,
+        param: T::Param,
+        priority: Priority,
+        dissector: D,
+    ) -> Self
+    where
+        T::Param: 'static,
+    {
+        self.setup.push(Box::new(move |session| {
+            session.register_dissector::<T, D>(param, priority, dissector)
+        }));
+        self
+    }
+
+    /// Adds session state. See `Session::register`.
+    pub fn state<S: Any + Send + Sync + 'static>(mut self, state: S) -> Self {
+        self.setup
+            .push(Box::new(move |session| session.register(state)));
+        self
+    }
+
+    /// Sets the `DissectMode` of the session.
+    pub fn dissect_mode(self, mode: DissectMode) -> Self {
+        self.state(mode)
+    }
+
+    /// Limits the memory kept across packets by all stateful subsystems
+    /// together. See `MemoryBudget`.
+    pub fn memory_limit(self, bytes: usize) -> Self {
+        self.plugin(move |session| session.memory_mut().set_limit(Some(bytes)))
+    }
+
+    /// Limits the memory kept across packets by the subsystem `name`, such
+    /// as `MemoryBudget::REASSEMBLY`.
+    pub fn subsystem_memory_limit(self, name: &'static str, bytes: usize) -> Self {
+        self.plugin(move |session| session.memory_mut().set_subsystem_limit(name, Some(bytes)))
+    }
+
+    /// Runs `plugin` on the session when it is built, after all tables and
+    /// default dissectors are loaded. This is how dissectors loaded at
+    /// runtime, such as from a dynamic library, are added.
+    pub fn plugin<P: FnOnce(&mut Session) + 'static>(mut self, plugin: P) -> Self {
+        self.setup.push(Box::new(plugin));
+        self
+    }
+
+    pub fn build(self) -> Session {
+        let mut session = Session::new_from_scratch();
+        if self.default_tables || self.default_dissectors {
+            for setup in TABLE_SETUP.read().iter() {
+                setup(&mut session);
+            }
+        }
+        for setup in self.tables {
+            setup(&mut session);
+        }
+        if self.default_dissectors {
+            for setup in DISSECT_SETUP.read().iter() {
+                setup(&mut session);
+            }
+        }
+        for setup in self.setup {
+            setup(&mut session);
+        }
+        session
+    }
+}
+
+impl Default for SessionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Pdu for Virtual {
     fn base_pdu(&self) -> &BasePdu {
         &self.base
@@ -270,3 +569,84 @@ macro_rules! register_dissector {
         }
     };
 }
+
+/// Loads a set of dissectors into a dissector table of a `Session`.
+///
+/// ```ignore
+/// load_dissectors!(&mut session, LinkTypeTable {
+///     LinkType::ETHERNET => Priority(0), ethernet_ii_dissector;
+///     LinkType::RAW => Priority(0), raw_ip_dissector;
+/// });
+/// ```
+#[macro_export]
+macro_rules! load_dissectors {
+    ($session:expr, $table:ty { $($param:expr => $pri:expr, $dissector:expr);* $(;)? }) => {{
+        let session: &mut $crate::Session = $session;
+        $(session.load_dissector::<$table, _>($param, $pri, $dissector);)*
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LinkType, LinkTypeTable};
+    use sniffle_ende::decode::Decode;
+
+    fn raw_dissector<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, RawPdu> {
+        RawPdu::decode(buf)
+    }
+
+    #[test]
+    fn build_session() {
+        let session = Session::builder()
+            .default_tables(false)
+            .default_dissectors(false)
+            .state(7u32)
+            .dissector::<LinkTypeTable, _>(LinkType::ETHERNET, Priority(0), raw_dissector)
+            .plugin(|session| {
+                session.register_dissector::<LinkTypeTable, _>(
+                    LinkType::RAW,
+                    Priority(0),
+                    raw_dissector,
+                )
+            })
+            .build();
+        assert_eq!(session.get::<u32>(), Some(&7));
+        let data = [1u8, 2, 3];
+        for link in [LinkType::ETHERNET, LinkType::RAW] {
+            let (rem, pdu) = session
+                .table_dissect::<LinkTypeTable>(&link, &data[..], None)
+                .unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(pdu.total_len(), 3);
+        }
+        assert!(session
+            .table_dissect::<LinkTypeTable>(&LinkType::IPV4, &data[..], None)
+            .is_err());
+    }
+
+    #[test]
+    fn packet_bytes() {
+        let session = Session::new_from_scratch();
+        let buffer = Bytes::from(vec![1u8, 2, 3, 4]);
+        let shared = session.with_packet_buffer(&buffer, || {
+            let shared = session.packet_bytes(&buffer[1..3]);
+            assert_eq!(shared.as_ptr(), buffer[1..].as_ptr());
+
+            let other = [2u8, 3];
+            let copied = session.packet_bytes(&other[..]);
+            assert_ne!(copied.as_ptr(), other.as_ptr());
+            assert_eq!(copied, shared);
+            shared
+        });
+
+        // The buffer is only used while dissecting it
+        let copied = session.packet_bytes(&buffer[1..3]);
+        assert_ne!(copied.as_ptr(), buffer[1..].as_ptr());
+        assert_eq!(copied, shared);
+    }
+}
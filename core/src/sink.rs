@@ -0,0 +1,321 @@
+use super::{Error, Packet, Timestamp};
+use async_trait::async_trait;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// A destination for packets outside of the application, such as a file,
+/// pipe, socket, or message queue producer.
+#[async_trait]
+pub trait PacketSink: Send {
+    async fn send(&mut self, packet: &Packet) -> Result<(), Error>;
+
+    /// Flushes any packets buffered by the sink.
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Sends each packet in order, stopping at the first error.
+    async fn send_iter<'p, I>(&mut self, packets: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = &'p Packet> + Send,
+        I::IntoIter: Send,
+    {
+        for packet in packets {
+            self.send(packet).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Encoding of the packet summaries written by `SummarySink`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SinkFormat {
+    /// Newline delimited JSON, one object per packet:
+    ///
+    /// ```text
+    /// {"timestamp":"2023-01-01T00:00:00.5Z","len":60,"captured_len":60,
+    ///  "fields":{"ipv4.src":"10.0.0.1","tcp.dstport":443},"data":"0011..."}
+    /// ```
+    ///
+    /// Fields are keyed by the names they were selected with. A field with a
+    /// description, such as an address, is its description, and other
+    /// fields are their value. The raw bytes are upper case hex.
+    #[cfg(feature = "json")]
+    Json,
+    /// Protobuf messages, each preceded by its length as a varint, as read
+    /// by `parseDelimitedFrom` and similar, with the schema:
+    ///
+    /// ```text
+    /// message Packet {
+    ///   int64 timestamp_ns = 1;
+    ///   uint64 len = 2;
+    ///   uint64 captured_len = 3;
+    ///   repeated Field fields = 4;
+    ///   bytes data = 5;
+    /// }
+    ///
+    /// message Field {
+    ///   string name = 1;
+    ///   string value = 2;
+    /// }
+    /// ```
+    ///
+    /// Field values are formatted as text, using the description if the
+    /// field has one.
+    Protobuf,
+}
+
+/// Writes a summary of each packet to an `AsyncWrite`, made of selected
+/// fields and the raw bytes of the packet. See `SinkFormat` for the
+/// encodings.
+///
+/// Fields are selected by name, as in a `Query`, such as `"ipv4.src"`.
+/// Fields the packet does not have are left out of its summary, and if a
+/// packet has a field more than once, the first is used.
+///
+/// ```ignore
+/// let mut sink = SummarySink::new(stdout(), SinkFormat::Json)
+///     .fields(["ipv4.src", "ipv4.dst", "tcp.port"])
+///     .raw_bytes(false);
+/// while let Some(packet) = sniffer.sniff().await? {
+///     sink.send(&packet).await?;
+/// }
+/// sink.flush().await?;
+/// ```
+pub struct SummarySink<W: AsyncWrite + Send + Unpin> {
+    writer: W,
+    format: SinkFormat,
+    fields: Vec<String>,
+    raw_bytes: bool,
+    count: u64,
+    buf: Vec<u8>,
+    data: Vec<u8>,
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn encode_tag(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+    encode_varint(buf, ((field as u64) << 3) | wire_type as u64);
+}
+
+fn encode_bytes(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    encode_tag(buf, field, 2);
+    encode_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+fn encode_uint(buf: &mut Vec<u8>, field: u32, value: u64) {
+    if value != 0 {
+        encode_tag(buf, field, 0);
+        encode_varint(buf, value);
+    }
+}
+
+impl<W: AsyncWrite + Send + Unpin> SummarySink<W> {
+    /// Creates a sink that selects no fields and includes the raw bytes of
+    /// each packet.
+    pub fn new(writer: W, format: SinkFormat) -> Self {
+        Self {
+            writer,
+            format,
+            fields: Vec::new(),
+            raw_bytes: true,
+            count: 0,
+            buf: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Selects the fields included in each summary, in order.
+    pub fn fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether the raw bytes of each packet are included in its
+    /// summary.
+    pub fn raw_bytes(mut self, enable: bool) -> Self {
+        self.raw_bytes = enable;
+        self
+    }
+
+    pub fn format(&self) -> SinkFormat {
+        self.format
+    }
+
+    pub fn packet_count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn encode(&mut self, packet: &Packet) -> Result<(), Error> {
+        self.buf.clear();
+        self.data.clear();
+        if self.raw_bytes {
+            match packet.buffer() {
+                Some(buf) => self.data.extend_from_slice(&buf[..]),
+                None => packet.serialize(&mut self.data)?,
+            }
+        }
+        match self.format {
+            #[cfg(feature = "json")]
+            SinkFormat::Json => self.encode_json(packet)?,
+            SinkFormat::Protobuf => self.encode_protobuf(packet),
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    fn encode_json(&mut self, packet: &Packet) -> Result<(), Error> {
+        use super::{ByteDumpFormatter, DumpValue};
+        use serde_json::{Map, Value};
+
+        let mut fields = Map::new();
+        for name in self.fields.iter() {
+            if let Some(field) = packet.get(&name[..]) {
+                let value = match field.descr() {
+                    Some(descr) => Value::from(descr),
+                    None => super::serde_dump::to_value(field.value().as_dump_value()),
+                };
+                let _ = fields.insert(name.clone(), value);
+            }
+        }
+        let mut obj = Map::new();
+        let ts = super::serde_dump::to_value(DumpValue::Time(packet.timestamp()));
+        let _ = obj.insert(String::from("timestamp"), ts);
+        let _ = obj.insert(String::from("len"), Value::from(packet.len()));
+        let _ = obj.insert(
+            String::from("captured_len"),
+            Value::from(packet.captured_len()),
+        );
+        let _ = obj.insert(String::from("fields"), Value::Object(fields));
+        if self.raw_bytes {
+            let data = ByteDumpFormatter(&self.data[..]).to_string();
+            let _ = obj.insert(String::from("data"), Value::from(data));
+        }
+        serde_json::to_writer(&mut self.buf, &Value::Object(obj)).map_err(std::io::Error::from)?;
+        self.buf.push(b'\n');
+        Ok(())
+    }
+
+    fn encode_protobuf(&mut self, packet: &Packet) {
+        let ts: Timestamp = packet.precise_timestamp();
+        let mut msg = Vec::new();
+        let nanos = ts.as_nanos().clamp(i64::MIN.into(), i64::MAX.into()) as i64;
+        encode_uint(&mut msg, 1, nanos as u64);
+        encode_uint(&mut msg, 2, packet.len() as u64);
+        encode_uint(&mut msg, 3, packet.captured_len() as u64);
+        let mut field_msg = Vec::new();
+        for name in self.fields.iter() {
+            if let Some(field) = packet.get(&name[..]) {
+                field_msg.clear();
+                encode_bytes(&mut field_msg, 1, name.as_bytes());
+                encode_bytes(&mut field_msg, 2, field.to_string().as_bytes());
+                encode_bytes(&mut msg, 4, &field_msg[..]);
+            }
+        }
+        if self.raw_bytes && !self.data.is_empty() {
+            encode_bytes(&mut msg, 5, &self.data[..]);
+        }
+        encode_varint(&mut self.buf, msg.len() as u64);
+        self.buf.extend_from_slice(&msg[..]);
+    }
+}
+
+#[async_trait]
+impl<W: AsyncWrite + Send + Unpin> PacketSink for SummarySink<W> {
+    async fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        self.encode(packet)?;
+        self.writer.write_all(&self.buf[..]).await?;
+        self.count += 1;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.writer.flush().await?)
+    }
+}
+
+#[cfg(feature = "json")]
+#[async_trait]
+impl<W: AsyncWrite + Send + Unpin> PacketSink for super::JsonDumper<W> {
+    async fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        Ok(self.dump(packet).await?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{RawPdu, Timestamp};
+
+    fn packet() -> Packet {
+        Packet::new(
+            Timestamp::new(1, 500),
+            RawPdu::new(vec![0xde, 0xad, 0xbe, 0xef]),
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
+    #[test]
+    fn summary_sink_protobuf() {
+        let mut sink = SummarySink::new(Vec::new(), SinkFormat::Protobuf);
+        block_on(sink.send(&packet())).unwrap();
+        block_on(sink.send(&packet())).unwrap();
+        assert_eq!(sink.packet_count(), 2);
+        let out = sink.into_inner();
+        let msg = [
+            0x08, 0xf4, 0x97, 0xeb, 0xdc, 0x03, // timestamp_ns = 1_000_000_500
+            0x10, 0x04, // len = 4
+            0x18, 0x04, // captured_len = 4
+            0x2a, 0x04, 0xde, 0xad, 0xbe, 0xef, // data
+        ];
+        assert_eq!(out[0] as usize, msg.len());
+        assert_eq!(out[1..=msg.len()], msg);
+        assert_eq!(out[msg.len() + 1..], out[..=msg.len()]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn summary_sink_json() {
+        let mut sink =
+            SummarySink::new(Vec::new(), SinkFormat::Json).fields(["raw_bytes.data", "ipv4.src"]);
+        block_on(sink.send(&packet())).unwrap();
+        let out = sink.into_inner();
+        assert_eq!(out.last(), Some(&b'\n'));
+        let value: serde_json::Value = serde_json::from_slice(&out[..]).unwrap();
+        assert_eq!(value["len"], 4);
+        assert_eq!(value["data"], "DEADBEEF");
+        assert_eq!(
+            value["fields"],
+            serde_json::json!({ "raw_bytes.data": "DEADBEEF" })
+        );
+        assert_eq!(value["timestamp"], "1970-01-01T00:00:01.000000500Z");
+    }
+}
@@ -1,20 +1,35 @@
-use super::{AnyPdu, Device, Error, LinkType, LinkTypeTable, Packet, RawPdu, Session};
+use super::{
+    AnyPdu, Bytes, Device, Error, LinkType, LinkTypeTable, Packet, PacketAnnotator, PacketMeta,
+    RawPdu, Session, Timestamp, Validation,
+};
 use async_trait::async_trait;
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::time::SystemTime;
 
 pub struct RawPacket<'a> {
     datalink: LinkType,
-    ts: SystemTime,
+    ts: Timestamp,
     snaplen: usize,
     len: usize,
-    data: &'a [u8],
+    data: RawData<'a>,
     device: Option<std::sync::Arc<Device>>,
+    meta: Option<Box<PacketMeta>>,
+}
+
+/// The bytes of a `RawPacket`, either borrowed from the sniffer, or handed
+/// over in a `Bytes` buffer that the dissected packet can keep.
+enum RawData<'a> {
+    Borrowed(&'a [u8]),
+    Shared(Bytes),
 }
 
 impl<'a> RawPacket<'a> {
-    pub fn new(
+    pub fn new<T: Into<Timestamp>>(
         datalink: LinkType,
-        timestamp: SystemTime,
+        timestamp: T,
         orig_len: usize,
         snaplen: Option<usize>,
         data: &'a [u8],
@@ -22,19 +37,54 @@ impl<'a> RawPacket<'a> {
     ) -> Self {
         Self {
             datalink,
-            ts: timestamp,
+            ts: timestamp.into(),
             snaplen: snaplen.unwrap_or(65535),
             len: orig_len,
-            data,
+            data: RawData::Borrowed(data),
+            device,
+            meta: None,
+        }
+    }
+
+    /// Creates a packet from a buffer owned by the sniffer. The buffer is
+    /// handed over to the dissected `Packet` as is, so capture readers that
+    /// read each packet into its own buffer should use this rather than
+    /// `new`, which copies the data.
+    pub fn from_bytes<T: Into<Timestamp>>(
+        datalink: LinkType,
+        timestamp: T,
+        orig_len: usize,
+        snaplen: Option<usize>,
+        data: Bytes,
+        device: Option<std::sync::Arc<Device>>,
+    ) -> Self {
+        Self {
+            datalink,
+            ts: timestamp.into(),
+            snaplen: snaplen.unwrap_or(65535),
+            len: orig_len,
+            data: RawData::Shared(data),
             device,
+            meta: None,
         }
     }
 
+    /// Attaches capture metadata, such as pcapng packet options.
+    pub fn with_meta(mut self, meta: PacketMeta) -> Self {
+        self.meta = Some(Box::new(meta));
+        self
+    }
+
     pub fn datalink(&self) -> LinkType {
         self.datalink
     }
 
     pub fn timestamp(&self) -> SystemTime {
+        self.ts.to_system_time()
+    }
+
+    /// The timestamp, along with its capture precision and UTC offset.
+    pub fn precise_timestamp(&self) -> Timestamp {
         self.ts
     }
 
@@ -46,8 +96,28 @@ impl<'a> RawPacket<'a> {
         self.len
     }
 
-    pub fn data(&self) -> &'a [u8] {
-        self.data
+    pub fn data(&self) -> &[u8] {
+        match &self.data {
+            RawData::Borrowed(data) => data,
+            RawData::Shared(data) => &data[..],
+        }
+    }
+
+    /// The buffer holding the data, if it was created with `from_bytes`.
+    pub fn bytes(&self) -> Option<&Bytes> {
+        match &self.data {
+            RawData::Borrowed(_) => None,
+            RawData::Shared(data) => Some(data),
+        }
+    }
+
+    /// The data as `Bytes`, which is copied unless the packet was created
+    /// with `from_bytes`.
+    pub fn into_bytes(self) -> Bytes {
+        match self.data {
+            RawData::Borrowed(data) => Bytes::copy_from_slice(data),
+            RawData::Shared(data) => data,
+        }
     }
 
     pub fn device(&self) -> Option<&Device> {
@@ -57,6 +127,10 @@ impl<'a> RawPacket<'a> {
     pub fn share_device(&self) -> Option<std::sync::Arc<Device>> {
         self.device.clone()
     }
+
+    pub fn meta(&self) -> Option<&PacketMeta> {
+        self.meta.as_deref()
+    }
 }
 
 #[async_trait]
@@ -69,45 +143,71 @@ pub trait Sniff: Send {
     async fn sniff(&mut self) -> Result<Option<Packet>, Error>;
 }
 
+/// Dissects the packets of a `SniffRaw` implementation with a `Session`.
+///
+/// Packets are read with `Sniff::sniff`, or through `Stream`. While a
+/// packet read by `Stream::poll_next` is pending, the raw sniffer and the
+/// session are owned by that read, so `raw`, `raw_mut`, `into_raw`,
+/// `session`, and `session_mut` panic until the read completes. `sniff`
+/// completes a pending read first and returns its packet.
 pub struct Sniffer<S: SniffRaw> {
-    raw_sniffer: S,
+    raw_sniffer: Option<S>,
     session: Session,
+    pending: Option<PendingSniff<S>>,
+    stream_done: bool,
 }
 
+type PendingSniff<S> = Pin<Box<dyn Future<Output = (S, Session, SniffResult)> + Send>>;
+
+type SniffResult = Result<Option<Packet>, Error>;
+
+const READ_PENDING: &str = "Sniffer is in the middle of a Stream read";
+
 impl<S: SniffRaw> Sniffer<S> {
     pub fn new(raw_sniffer: S) -> Self {
-        Self {
-            raw_sniffer,
-            session: Session::default(),
-        }
+        Self::with_session(raw_sniffer, Session::default())
     }
 
     pub fn with_session(raw_sniffer: S, session: Session) -> Self {
         Self {
-            raw_sniffer,
+            raw_sniffer: Some(raw_sniffer),
             session,
+            pending: None,
+            stream_done: false,
         }
     }
 
     pub fn into_raw(self) -> S {
-        self.raw_sniffer
+        self.raw_sniffer.expect(READ_PENDING)
+    }
+
+    pub fn raw(&self) -> &S {
+        self.raw_sniffer.as_ref().expect(READ_PENDING)
+    }
+
+    pub fn raw_mut(&mut self) -> &mut S {
+        self.raw_sniffer.as_mut().expect(READ_PENDING)
     }
 
     pub fn session(&self) -> &Session {
+        assert!(self.pending.is_none(), "{}", READ_PENDING);
         &self.session
     }
 
     pub fn session_mut(&mut self) -> &mut Session {
+        assert!(self.pending.is_none(), "{}", READ_PENDING);
         &mut self.session
     }
+
+    /// Gives a packet that is no longer needed back to the session's pool,
+    /// so the boxes holding its layers can be reused for the next packets.
+    pub fn recycle(&self, packet: Packet) {
+        self.session.pool().recycle_pdu(packet.into_pdu());
+    }
 }
 
-async fn sniff_impl<S: SniffRaw>(
-    sniffer: &mut Sniffer<S>,
-    session: &Session,
-    last_info: &mut super::session::LastInfo,
-) -> Result<Option<Packet>, Error> {
-    if let Some(pkt) = sniffer.raw_sniffer.sniff_raw().await? {
+async fn sniff_impl<S: SniffRaw>(raw_sniffer: &mut S, session: &Session) -> SniffResult {
+    if let Some(pkt) = raw_sniffer.sniff_raw().await? {
         let RawPacket {
             datalink,
             ts,
@@ -115,54 +215,128 @@ async fn sniff_impl<S: SniffRaw>(
             snaplen,
             data,
             device,
+            ..
         } = pkt;
-        last_info.ts = ts;
-        last_info.dev = device.clone();
-        last_info.snaplen = snaplen;
-        match session.table_dissect::<LinkTypeTable>(&datalink, data, None) {
-            Ok((_rem, pdu)) => Ok(Some(Packet::new(ts, pdu, Some(len), Some(snaplen), device))),
-            _ => Ok(Some(Packet::new(
+        // Dissectors can reference the buffer with `Session::packet_bytes`.
+        // It is handed over by sniffers that own it, and copied otherwise.
+        let buffer = match data {
+            RawData::Borrowed(data) => Bytes::copy_from_slice(data),
+            RawData::Shared(data) => data,
+        };
+        // Recorded before dissecting, so dissectors can look up the device
+        // that captured the packet.
+        let dev = device.clone();
+        let truncated = len.saturating_sub(buffer.len());
+        session
+            .last_info_mut(move |info| {
+                info.ts = ts;
+                info.dev = dev;
+                info.snaplen = snaplen;
+                info.truncated = truncated;
+            })
+            .await;
+        let dissected = session.with_packet_buffer(&buffer, || {
+            session.table_dissect::<LinkTypeTable>(&datalink, &buffer[..], None)
+        });
+        let mut pkt = match dissected {
+            Ok((_rem, pdu)) => Packet::new(ts, pdu, Some(len), Some(snaplen), device),
+            _ => Packet::new(
                 ts,
-                AnyPdu::new(RawPdu::new(Vec::from(data))),
+                session.pool().pdu(RawPdu::new(buffer.clone())),
                 Some(len),
                 Some(snaplen),
                 device,
-            ))),
+            ),
+        };
+        pkt.set_buffer(Some(buffer));
+        if let Some(options) = session.get::<Validation>() {
+            pkt.validate(options);
         }
+        if let Some(annotator) = session.get::<std::sync::Arc<dyn PacketAnnotator>>() {
+            pkt.set_annotator(Some(annotator.clone()));
+        }
+        Ok(Some(pkt))
     } else {
         Ok(None)
     }
 }
 
+async fn sniff_session<S: SniffRaw>(raw_sniffer: &mut S, session: &Session) -> SniffResult {
+    if let Some(pdu) = session.next_virtual_packet().await {
+        return Ok(Some(
+            session
+                .last_info(move |info| {
+                    Packet::new(info.ts, pdu, None, Some(info.snaplen), info.dev.clone())
+                })
+                .await,
+        ));
+    }
+    sniff_impl(raw_sniffer, session).await
+}
+
+impl<S: SniffRaw> Sniffer<S> {
+    /// Gives the raw sniffer and session back from a completed read.
+    fn finish_read(&mut self, raw_sniffer: S, session: Session) {
+        self.pending = None;
+        self.raw_sniffer = Some(raw_sniffer);
+        self.session = session;
+    }
+}
+
 #[async_trait]
 impl<S: SniffRaw> Sniff for Sniffer<S> {
     async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
-        let session = std::mem::replace(&mut self.session, Session::new_from_scratch());
-        if let Some(pdu) = session.next_virtual_packet().await {
-            let ret = Ok(Some(
-                session
-                    .last_info(move |info| {
-                        Packet::new(info.ts, pdu, None, Some(info.snaplen), info.dev.clone())
-                    })
-                    .await,
-            ));
-            let _ = std::mem::replace(self.session_mut(), session);
-            return ret;
+        if let Some(pending) = self.pending.as_mut() {
+            let (raw_sniffer, session, res) = pending.await;
+            self.finish_read(raw_sniffer, session);
+            return res;
         }
+        let raw_sniffer = self.raw_sniffer.as_mut().expect(READ_PENDING);
+        sniff_session(raw_sniffer, &self.session).await
+    }
+}
+
+// The raw sniffer and session are only ever moved, never pinned in place.
+impl<S: SniffRaw> Unpin for Sniffer<S> {}
 
-        let mut last_info = super::session::LastInfo {
-            ts: SystemTime::UNIX_EPOCH,
-            dev: None,
-            snaplen: 0,
+/// Yields the packets of the sniffer until it returns `None` or an error.
+/// The stream ends after an error, since most sniffers can't continue
+/// after one.
+impl<S: SniffRaw + 'static> Stream for Sniffer<S> {
+    type Item = Result<Packet, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.stream_done {
+            return Poll::Ready(None);
+        }
+        let this = &mut *self;
+        let pending = match this.pending.as_mut() {
+            Some(pending) => pending,
+            None => {
+                let mut raw_sniffer = this.raw_sniffer.take().expect(READ_PENDING);
+                let session = std::mem::replace(&mut this.session, Session::new_from_scratch());
+                this.pending.insert(Box::pin(async move {
+                    let res = sniff_session(&mut raw_sniffer, &session).await;
+                    (raw_sniffer, session, res)
+                }))
+            }
         };
-        let ret = sniff_impl(self, &session, &mut last_info).await;
-        session
-            .last_info_mut(move |info| {
-                *info = last_info;
-            })
-            .await;
-        let _ = std::mem::replace(self.session_mut(), session);
-        ret
+        let (raw_sniffer, session, res) = match pending.as_mut().poll(cx) {
+            Poll::Ready(ret) => ret,
+            Poll::Pending => return Poll::Pending,
+        };
+        this.finish_read(raw_sniffer, session);
+        match res {
+            Ok(Some(pkt)) => Poll::Ready(Some(Ok(pkt))),
+            Ok(None) => {
+                this.stream_done = true;
+                Poll::Ready(None)
+            }
+            Err(e) => {
+                this.stream_done = true;
+                Poll::Ready(Some(Err(e)))
+            }
+        }
     }
 }
 
@@ -170,12 +344,14 @@ impl<S: SniffRaw> Sniff for Sniffer<S> {
 impl<S: SniffRaw> Sniff for S {
     async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
         Ok(self.sniff_raw().await?.map(|pkt| {
+            let (ts, len, snaplen) = (pkt.ts, pkt.len, pkt.snaplen);
+            let device = pkt.device.clone();
             Packet::new(
-                pkt.ts,
-                AnyPdu::new(RawPdu::new(Vec::from(pkt.data))),
-                Some(pkt.len),
-                Some(pkt.snaplen),
-                pkt.device,
+                ts,
+                AnyPdu::new(RawPdu::new(pkt.into_bytes())),
+                Some(len),
+                Some(snaplen),
+                device,
             )
         }))
     }
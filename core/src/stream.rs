@@ -0,0 +1,270 @@
+use super::{AnyPdu, Error, Session};
+
+/// Events emitted by a `StreamDissect` implementation.
+#[derive(Debug)]
+pub enum StreamEvent<'a> {
+    /// A fully dissected Pdu, such as a message header.
+    Pdu(AnyPdu),
+    /// A piece of a message body, starting at `offset` bytes into the body.
+    Chunk { offset: u64, data: &'a [u8] },
+    /// The end of a message body of `len` bytes.
+    End { len: u64 },
+}
+
+/// Incremental dissection for protocols that carry messages too large to
+/// materialize in a single Pdu.
+///
+/// Rather than producing a Pdu that owns the whole payload, the dissector
+/// consumes data as it arrives and emits `StreamEvent`s. Any bytes left
+/// unconsumed are buffered by `StreamDissector` and presented again,
+/// followed by new data, on the next call.
+pub trait StreamDissect {
+    /// Consumes data from the front of `data`, returning the number of bytes
+    /// consumed. Returning 0 indicates that more data is needed.
+    fn feed(
+        &mut self,
+        data: &[u8],
+        session: &Session,
+        events: &mut dyn FnMut(StreamEvent<'_>),
+    ) -> Result<usize, Error>;
+
+    /// Called when no more data will arrive. `data` holds any bytes that
+    /// were never consumed.
+    fn finish(
+        &mut self,
+        data: &[u8],
+        session: &Session,
+        events: &mut dyn FnMut(StreamEvent<'_>),
+    ) -> Result<(), Error> {
+        let _ = (session, events);
+        if data.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MalformedStream)
+        }
+    }
+}
+
+/// Drives a `StreamDissect`, buffering at most `max_buffer` unconsumed
+/// bytes between calls.
+pub struct StreamDissector<D: StreamDissect> {
+    dissector: D,
+    buf: Vec<u8>,
+    max_buffer: usize,
+}
+
+/// Tracks the progress of a message body of known length, splitting
+/// incoming data into body chunks and trailing data.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyTracker {
+    len: u64,
+    offset: u64,
+}
+
+impl<D: StreamDissect> StreamDissector<D> {
+    pub fn new(dissector: D) -> Self {
+        Self::with_max_buffer(dissector, 0x10000)
+    }
+
+    pub fn with_max_buffer(dissector: D, max_buffer: usize) -> Self {
+        Self {
+            dissector,
+            buf: Vec::new(),
+            max_buffer,
+        }
+    }
+
+    pub fn dissector(&self) -> &D {
+        &self.dissector
+    }
+
+    pub fn dissector_mut(&mut self) -> &mut D {
+        &mut self.dissector
+    }
+
+    pub fn into_inner(self) -> D {
+        self.dissector
+    }
+
+    /// Number of bytes currently buffered
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Feeds new data to the dissector, calling `events` for each event
+    /// emitted.
+    pub fn push<F>(&mut self, data: &[u8], session: &Session, events: F) -> Result<(), Error>
+    where
+        F: FnMut(StreamEvent<'_>),
+    {
+        let mut events = events;
+        let mut data = data;
+
+        // Finish off any previously buffered data first
+        while !self.buf.is_empty() && !data.is_empty() {
+            let take = data
+                .len()
+                .min(self.max_buffer.saturating_sub(self.buf.len()).max(1));
+            self.buf.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            let consumed = self.feed_buffered(session, &mut events)?;
+            if consumed == 0 && self.buf.len() >= self.max_buffer {
+                return Err(Error::StreamBufferFull);
+            }
+        }
+
+        while !data.is_empty() {
+            let consumed = self.dissector.feed(data, session, &mut events)?;
+            if consumed == 0 {
+                if data.len() > self.max_buffer {
+                    return Err(Error::StreamBufferFull);
+                }
+                self.buf.extend_from_slice(data);
+                break;
+            }
+            data = &data[consumed.min(data.len())..];
+        }
+
+        Ok(())
+    }
+
+    /// Signals the end of the stream.
+    pub fn finish<F>(&mut self, session: &Session, events: F) -> Result<(), Error>
+    where
+        F: FnMut(StreamEvent<'_>),
+    {
+        let mut events = events;
+        let buf = std::mem::take(&mut self.buf);
+        self.dissector.finish(&buf[..], session, &mut events)
+    }
+
+    fn feed_buffered(
+        &mut self,
+        session: &Session,
+        events: &mut dyn FnMut(StreamEvent<'_>),
+    ) -> Result<usize, Error> {
+        let mut total = 0;
+        loop {
+            let consumed = self.dissector.feed(&self.buf[total..], session, events)?;
+            if consumed == 0 {
+                break;
+            }
+            total = (total + consumed).min(self.buf.len());
+            if total == self.buf.len() {
+                break;
+            }
+        }
+        let _ = self.buf.drain(..total);
+        Ok(total)
+    }
+}
+
+impl BodyTracker {
+    pub fn new(len: u64) -> Self {
+        Self { len, offset: 0 }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.len - self.offset
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.offset == self.len
+    }
+
+    /// Takes the part of `data` belonging to the body, emitting it as a
+    /// chunk, and an `End` event once the body is complete. Returns the
+    /// number of bytes of `data` consumed.
+    pub fn feed(&mut self, data: &[u8], events: &mut dyn FnMut(StreamEvent<'_>)) -> usize {
+        let take = usize::try_from(self.remaining())
+            .unwrap_or(usize::MAX)
+            .min(data.len());
+        if take > 0 {
+            events(StreamEvent::Chunk {
+                offset: self.offset,
+                data: &data[..take],
+            });
+            self.offset += take as u64;
+            if self.is_done() {
+                events(StreamEvent::End { len: self.len });
+            }
+        }
+        take
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RawPdu;
+
+    // 4 byte big endian length header, followed by the body
+    struct LengthPrefixed(Option<BodyTracker>);
+
+    impl StreamDissect for LengthPrefixed {
+        fn feed(
+            &mut self,
+            data: &[u8],
+            _session: &Session,
+            events: &mut dyn FnMut(StreamEvent<'_>),
+        ) -> Result<usize, Error> {
+            match &mut self.0 {
+                Some(body) => {
+                    let consumed = body.feed(data, events);
+                    if body.is_done() {
+                        self.0 = None;
+                    }
+                    Ok(consumed)
+                }
+                None => {
+                    if data.len() < 4 {
+                        return Ok(0);
+                    }
+                    events(StreamEvent::Pdu(AnyPdu::new(RawPdu::new(
+                        data[..4].to_vec(),
+                    ))));
+                    let len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                    self.0 = Some(BodyTracker::new(len as u64));
+                    Ok(4)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stream_dissect() {
+        let session = Session::new_from_scratch();
+        let mut stream = StreamDissector::with_max_buffer(LengthPrefixed(None), 8);
+        let mut chunks = Vec::new();
+        let mut headers = 0;
+        let mut ends = Vec::new();
+        let mut on_event = |event: StreamEvent<'_>| match event {
+            StreamEvent::Pdu(_) => headers += 1,
+            StreamEvent::Chunk { offset, data } => chunks.push((offset, data.to_vec())),
+            StreamEvent::End { len } => ends.push(len),
+        };
+
+        stream.push(&[0, 0], &session, &mut on_event).unwrap();
+        assert_eq!(stream.buffered(), 2);
+        stream.push(&[0, 5, 1, 2], &session, &mut on_event).unwrap();
+        stream.push(&[3, 4, 5, 0], &session, &mut on_event).unwrap();
+        stream.push(&[0, 0, 1, 9], &session, &mut on_event).unwrap();
+        stream.finish(&session, &mut on_event).unwrap();
+
+        assert_eq!(headers, 2);
+        assert_eq!(chunks, [(0, vec![1, 2]), (2, vec![3, 4, 5]), (0, vec![9]),]);
+        assert_eq!(ends, [5, 1]);
+    }
+}
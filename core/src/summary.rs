@@ -0,0 +1,612 @@
+use super::diff::DiffCollector;
+use super::query::find_field;
+use super::{
+    Dump, DumpValue, Dumper, Error, Field, FieldValue, Ipv4Address, Ipv6Address, LruMap,
+    MacAddress, MemoryAccount, MemoryBudget, Packet, Pdu, PduExt, Session, Sniff, Timestamp,
+};
+use lazy_static::*;
+use std::convert::Infallible;
+use std::time::Duration;
+
+/// Statistics of a stream of packets: a protocol hierarchy, the traffic of
+/// each endpoint, and the conversations between endpoints.
+///
+/// Endpoints and conversations are found with the kinds registered with
+/// `register_conversation!`, such as `"IPv4"` or `"TCP"`. Endpoints and
+/// conversations can be limited to a memory budget, in which case the
+/// least recently active ones are dropped to make room for new ones.
+#[derive(Debug, Clone, Default)]
+pub struct TrafficSummary {
+    packets: u64,
+    bytes: u64,
+    first: Option<Timestamp>,
+    last: Option<Timestamp>,
+    hierarchy: ProtocolNode,
+    endpoints: LruMap<(&'static str, String), Endpoint>,
+    conversations: LruMap<(&'static str, EndpointKey, EndpointKey), Conversation>,
+}
+
+/// A protocol in the protocol hierarchy of a `TrafficSummary`. The bytes of
+/// a protocol are the bytes of its PDUs, including the PDUs inside them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtocolNode {
+    /// Name of the protocol, as dumped. Empty for the root of the hierarchy.
+    pub name: String,
+    pub packets: u64,
+    pub bytes: u64,
+    /// Protocols found directly inside this one
+    pub children: Vec<ProtocolNode>,
+}
+
+/// The traffic sent and received by one address, such as an IPv4 address
+/// or an IPv4 address and TCP port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub kind: &'static str,
+    pub address: String,
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+}
+
+/// The address of one end of a conversation, as found in the fields of a
+/// conversation kind.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EndpointAddress {
+    Ipv4(Ipv4Address),
+    Ipv6(Ipv6Address),
+    Mac(MacAddress),
+    /// Any other address, as the values of its fields separated by `:`
+    Other(String),
+}
+
+/// One end of a conversation: an address, and a port for kinds such as
+/// `"TCP"` whose last field is a 16 bit number following the address.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EndpointKey {
+    pub address: EndpointAddress,
+    pub port: Option<u16>,
+}
+
+/// The traffic between two endpoints, in both directions. `a` is the source
+/// of the first packet seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversation {
+    pub kind: &'static str,
+    pub a: EndpointKey,
+    pub b: EndpointKey,
+    pub a_to_b_packets: u64,
+    pub a_to_b_bytes: u64,
+    pub b_to_a_packets: u64,
+    pub b_to_a_bytes: u64,
+    pub first: Timestamp,
+    pub last: Timestamp,
+}
+
+struct ConversationKind {
+    name: &'static str,
+    src: &'static [&'static str],
+    dst: &'static [&'static str],
+}
+
+/// Records the name of the first node of a dumped PDU.
+#[derive(Default)]
+pub(crate) struct LayerName(Option<String>);
+
+lazy_static! {
+    static ref CONVERSATION_KINDS: parking_lot::RwLock<Vec<ConversationKind>> =
+        parking_lot::RwLock::new(Vec::new());
+}
+
+pub fn _register_conversation(
+    name: &'static str,
+    src: &'static [&'static str],
+    dst: &'static [&'static str],
+) {
+    CONVERSATION_KINDS
+        .write()
+        .push(ConversationKind { name, src, dst });
+}
+
+/// Registers a kind of endpoint for `TrafficSummary`. An endpoint address
+/// is made of the values of one or more fields, as named for `Query`,
+/// separated by `:`. Packets without all of the fields are not counted.
+///
+/// ```ignore
+/// register_conversation!(tcp, "TCP", ["ipv4.src_addr", "tcp.srcport"] => ["ipv4.dst_addr", "tcp.dstport"]);
+/// ```
+#[macro_export]
+macro_rules! register_conversation {
+    ($id:ident, $name:expr, [$($src:expr),+ $(,)?] => [$($dst:expr),+ $(,)?]) => {
+        $crate::paste::paste! {
+            #[$crate::ctor::ctor]
+            #[allow(non_snake_case)]
+            fn [<__sniffle_conversation_ $id>]() {
+                $crate::_register_conversation($name, &[$($src),+], &[$($dst),+]);
+            }
+        }
+    };
+}
+
+impl TrafficSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a summary that charges its endpoints and conversations to
+    /// `account`.
+    pub fn with_memory(account: MemoryAccount) -> Self {
+        Self {
+            endpoints: LruMap::new(account.clone()),
+            conversations: LruMap::new(account),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a summary limited by the `MemoryBudget::CONVERSATIONS`
+    /// budget of `session`.
+    pub fn with_session(session: &Session) -> Self {
+        Self::with_memory(session.memory().account(MemoryBudget::CONVERSATIONS))
+    }
+
+    /// Summarizes all packets from `sniffer`.
+    pub async fn from_sniffer<S: Sniff + ?Sized>(sniffer: &mut S) -> Result<Self, Error> {
+        let mut summary = Self::new();
+        while let Some(packet) = sniffer.sniff().await? {
+            summary.add(&packet);
+        }
+        Ok(summary)
+    }
+
+    pub fn add(&mut self, packet: &Packet) {
+        let len = packet.len() as u64;
+        let ts = packet.precise_timestamp();
+        self.packets += 1;
+        self.bytes += len;
+        self.first = Some(self.first.map_or(ts, |first| first.min(ts)));
+        self.last = Some(self.last.map_or(ts, |last| last.max(ts)));
+
+        let mut node = &mut self.hierarchy;
+        node.packets += 1;
+        node.bytes += len;
+        let mut layer = Some(packet.pdu());
+        while let Some(pdu) = layer {
+            if let Some(name) = LayerName::of(pdu) {
+                let idx = match node.children.iter().position(|child| child.name == name) {
+                    Some(idx) => idx,
+                    None => {
+                        node.children.push(ProtocolNode {
+                            name,
+                            ..Default::default()
+                        });
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[idx];
+                node.packets += 1;
+                node.bytes += pdu.total_len() as u64;
+            }
+            layer = pdu.inner_pdu();
+        }
+
+        let entries = DiffCollector::collect_packet(packet);
+        let endpoint = |names: &[&str]| -> Option<EndpointKey> {
+            let fields: Option<Vec<_>> = names
+                .iter()
+                .map(|name| find_field(&entries[..], name))
+                .collect();
+            fields.map(EndpointKey::from_fields)
+        };
+        for kind in CONVERSATION_KINDS.read().iter() {
+            let (src, dst) = match (endpoint(kind.src), endpoint(kind.dst)) {
+                (Some(src), Some(dst)) => (src, dst),
+                _ => continue,
+            };
+            if let Some(tx) = self.endpoint(kind.name, &src.to_string()) {
+                tx.tx_packets += 1;
+                tx.tx_bytes += len;
+            }
+            if let Some(rx) = self.endpoint(kind.name, &dst.to_string()) {
+                rx.rx_packets += 1;
+                rx.rx_bytes += len;
+            }
+
+            let reverse = (kind.name, dst.clone(), src.clone());
+            if let Some(conv) = self.conversations.get_mut(&reverse) {
+                conv.b_to_a_packets += 1;
+                conv.b_to_a_bytes += len;
+                conv.first = conv.first.min(ts);
+                conv.last = conv.last.max(ts);
+                continue;
+            }
+            let key = (kind.name, src, dst);
+            if !self.conversations.contains_key(&key) {
+                let size =
+                    std::mem::size_of::<Conversation>() + 2 * (key.1.heap_len() + key.2.heap_len());
+                let conv = Conversation {
+                    kind: kind.name,
+                    a: key.1.clone(),
+                    b: key.2.clone(),
+                    a_to_b_packets: 0,
+                    a_to_b_bytes: 0,
+                    b_to_a_packets: 0,
+                    b_to_a_bytes: 0,
+                    first: ts,
+                    last: ts,
+                };
+                self.conversations.insert(key.clone(), conv, size);
+            }
+            if let Some(conv) = self.conversations.get_mut(&key) {
+                conv.a_to_b_packets += 1;
+                conv.a_to_b_bytes += len;
+                conv.first = conv.first.min(ts);
+                conv.last = conv.last.max(ts);
+            }
+        }
+    }
+
+    /// The endpoint `address`, which is added if it is new. Returns `None`
+    /// if there is no room for a new endpoint.
+    fn endpoint(&mut self, kind: &'static str, address: &str) -> Option<&mut Endpoint> {
+        let key = (kind, String::from(address));
+        if !self.endpoints.contains_key(&key) {
+            let size = std::mem::size_of::<Endpoint>() + 2 * address.len();
+            let endpoint = Endpoint {
+                kind,
+                address: address.into(),
+                tx_packets: 0,
+                tx_bytes: 0,
+                rx_packets: 0,
+                rx_bytes: 0,
+            };
+            self.endpoints.insert(key.clone(), endpoint, size);
+        }
+        self.endpoints.get_mut(&key)
+    }
+
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn first_timestamp(&self) -> Option<Timestamp> {
+        self.first
+    }
+
+    pub fn last_timestamp(&self) -> Option<Timestamp> {
+        self.last
+    }
+
+    /// Time between the first and last packets
+    pub fn duration(&self) -> Duration {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last.duration_since(first).unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The root of the protocol hierarchy, which counts every packet. Its
+    /// children are the outermost protocols, in the order first seen.
+    pub fn hierarchy(&self) -> &ProtocolNode {
+        &self.hierarchy
+    }
+
+    /// Endpoints of the kind `kind`, sorted by total bytes, largest first.
+    pub fn endpoints(&self, kind: &str) -> Vec<&Endpoint> {
+        let mut endpoints: Vec<_> = self
+            .endpoints
+            .values()
+            .filter(|endpoint| endpoint.kind == kind)
+            .collect();
+        endpoints.sort_by(|a, b| {
+            b.bytes()
+                .cmp(&a.bytes())
+                .then_with(|| a.address.cmp(&b.address))
+        });
+        endpoints
+    }
+
+    /// The `count` endpoints of the kind `kind` with the most bytes.
+    pub fn top_talkers(&self, kind: &str, count: usize) -> Vec<&Endpoint> {
+        let mut endpoints = self.endpoints(kind);
+        endpoints.truncate(count);
+        endpoints
+    }
+
+    /// Conversations of the kind `kind`, sorted by total bytes, largest
+    /// first.
+    pub fn conversations(&self, kind: &str) -> Vec<&Conversation> {
+        let mut conversations: Vec<_> = self
+            .conversations
+            .values()
+            .filter(|conv| conv.kind == kind)
+            .collect();
+        conversations.sort_by(|a, b| {
+            b.bytes()
+                .cmp(&a.bytes())
+                .then_with(|| (&a.a, &a.b).cmp(&(&b.a, &b.b)))
+        });
+        conversations
+    }
+}
+
+impl ProtocolNode {
+    /// Finds a descendant by the names of the protocols leading to it, such
+    /// as `["Ethernet", "Ipv4", "TCP"]`.
+    pub fn find(&self, path: &[&str]) -> Option<&ProtocolNode> {
+        path.iter().try_fold(self, |node, name| {
+            node.children.iter().find(|child| child.name == *name)
+        })
+    }
+}
+
+impl Endpoint {
+    pub fn packets(&self) -> u64 {
+        self.tx_packets + self.rx_packets
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.tx_bytes + self.rx_bytes
+    }
+}
+
+impl EndpointAddress {
+    fn from_field(field: &Field) -> Self {
+        if let FieldValue::Bytes(bytes) = field.value() {
+            if let Ok(addr) = <[u8; 4]>::try_from(&bytes[..]) {
+                return Self::Ipv4(addr.into());
+            }
+            if let Ok(addr) = <[u8; 16]>::try_from(&bytes[..]) {
+                return Self::Ipv6(addr.into());
+            }
+            if let Ok(addr) = <[u8; 6]>::try_from(&bytes[..]) {
+                return Self::Mac(addr.into());
+            }
+        }
+        Self::Other(field.to_string())
+    }
+}
+
+impl std::fmt::Display for EndpointAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ipv4(addr) => addr.fmt(f),
+            Self::Ipv6(addr) => addr.fmt(f),
+            Self::Mac(addr) => addr.fmt(f),
+            Self::Other(addr) => f.write_str(&addr[..]),
+        }
+    }
+}
+
+impl EndpointKey {
+    fn from_fields(mut fields: Vec<Field>) -> Self {
+        let port = match (fields.len(), fields.last().map(Field::value)) {
+            (2.., Some(FieldValue::UInt(port))) => u16::try_from(*port).ok(),
+            _ => None,
+        };
+        if port.is_some() {
+            fields.pop();
+        }
+        let address = match &fields[..] {
+            [field] => EndpointAddress::from_field(field),
+            fields => EndpointAddress::Other(
+                fields
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(":"),
+            ),
+        };
+        Self { address, port }
+    }
+
+    fn heap_len(&self) -> usize {
+        match &self.address {
+            EndpointAddress::Other(addr) => addr.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// Formats the address, followed by `:` and the port if there is one.
+/// IPv6 addresses are put in brackets when followed by a port.
+impl std::fmt::Display for EndpointKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.address, self.port) {
+            (EndpointAddress::Ipv6(addr), Some(port)) => write!(f, "[{}]:{}", addr, port),
+            (addr, Some(port)) => write!(f, "{}:{}", addr, port),
+            (addr, None) => addr.fmt(f),
+        }
+    }
+}
+
+impl Conversation {
+    pub fn packets(&self) -> u64 {
+        self.a_to_b_packets + self.b_to_a_packets
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.a_to_b_bytes + self.b_to_a_bytes
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.last.duration_since(self.first).unwrap_or_default()
+    }
+}
+
+impl LayerName {
+    pub(crate) fn of<P: Pdu>(pdu: &P) -> Option<String> {
+        let mut dumper = Dumper::new(Self::default());
+        match dumper.add_packet().and_then(|mut node| pdu.dump(&mut node)) {
+            Ok(()) => {}
+            Err(e) => match e {},
+        }
+        let Self(name) = std::mem::take(&mut *dumper);
+        name
+    }
+
+    fn visit(&mut self, name: &str) {
+        if self.0.is_none() {
+            self.0 = Some(name.into());
+        }
+    }
+}
+
+impl Dump for LayerName {
+    type Error = Infallible;
+
+    fn start_packet(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn end_packet(&mut self) {}
+
+    fn start_node(&mut self, name: &str, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.visit(name);
+        Ok(())
+    }
+
+    fn end_node(&mut self) {}
+
+    fn add_field(
+        &mut self,
+        _name: &str,
+        _value: DumpValue<'_>,
+        _descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn add_info(&mut self, _name: &str, _descr: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn start_list(&mut self, name: &str, _descr: Option<&str>) -> Result<(), Self::Error> {
+        self.visit(name);
+        Ok(())
+    }
+
+    fn end_list(&mut self) {}
+
+    fn add_list_item(
+        &mut self,
+        _value: DumpValue<'_>,
+        _descr: Option<&str>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn start_list_node(&mut self, _descr: Option<&str>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn end_list_node(&mut self) {}
+
+    fn start_list_sublist(&mut self, _descr: Option<&str>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn end_list_sublist(&mut self) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LinkType, LinkTypeTable, PluginContext, PluginDissector, PluginGuest};
+    use crate::{PluginValue, Priority, Session};
+
+    /// A header of a source and a destination byte.
+    struct Addrs;
+
+    impl PluginGuest for Addrs {
+        fn dissect(&self, ctx: &mut PluginContext<'_>) -> Option<usize> {
+            let mut hdr = [0u8; 2];
+            if ctx.read(0, &mut hdr[..]) != 2 {
+                return None;
+            }
+            ctx.emit_field("Src", 0, 1, PluginValue::UInt(hdr[0].into()));
+            ctx.emit_field("Dst", 1, 1, PluginValue::UInt(hdr[1].into()));
+            Some(2)
+        }
+    }
+
+    fn summarize(session: Session, mut summary: TrafficSummary) -> TrafficSummary {
+        // Conversation kinds are global, so only register once for all tests
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        REGISTER.call_once(|| _register_conversation("Addrs", &["addrs.src"], &["addrs.dst"]));
+        for (secs, data) in [(1, [1u8, 2, 0]), (2, [2, 1, 0]), (4, [1, 3, 0])] {
+            let (_, pdu) = session
+                .table_dissect::<LinkTypeTable>(&LinkType::USER0, &data[..], None)
+                .unwrap();
+            summary.add(&Packet::new(Timestamp::new(secs, 0), pdu, None, None, None));
+        }
+        summary
+    }
+
+    fn session() -> crate::SessionBuilder {
+        Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(
+                LinkType::USER0,
+                Priority(0),
+                PluginDissector::new("Addrs", Addrs),
+            )
+    }
+
+    #[test]
+    fn summarize_packets() {
+        let summary = summarize(session().build(), TrafficSummary::new());
+
+        assert_eq!(summary.packets(), 3);
+        assert_eq!(summary.bytes(), 9);
+        assert_eq!(summary.duration(), Duration::from_secs(3));
+        let addrs = summary.hierarchy().find(&["Addrs"]).unwrap();
+        assert_eq!((addrs.packets, addrs.bytes), (3, 9));
+        let raw = addrs.find(&["Raw Bytes"]).unwrap();
+        assert_eq!((raw.packets, raw.bytes), (3, 3));
+        assert!(raw.children.is_empty());
+
+        let talkers = summary.top_talkers("Addrs", 1);
+        assert_eq!(talkers.len(), 1);
+        assert_eq!(talkers[0].address, "1");
+        assert_eq!((talkers[0].tx_packets, talkers[0].rx_packets), (2, 1));
+
+        let convs = summary.conversations("Addrs");
+        assert_eq!(convs.len(), 2);
+        let other = |addr: &str| EndpointKey {
+            address: EndpointAddress::Other(addr.into()),
+            port: None,
+        };
+        assert_eq!((&convs[0].a, &convs[0].b), (&other("1"), &other("2")));
+        assert_eq!((convs[0].a_to_b_packets, convs[0].b_to_a_packets), (1, 1));
+        assert_eq!(convs[0].duration(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn bounded_conversations() {
+        // Room for the three endpoints, but only one conversation
+        let limit =
+            std::mem::size_of::<Conversation>() + 4 + 3 * (std::mem::size_of::<Endpoint>() + 2);
+        let session = session()
+            .subsystem_memory_limit(MemoryBudget::CONVERSATIONS, limit)
+            .build();
+        let summary = TrafficSummary::with_session(&session);
+        let summary = summarize(session, summary);
+
+        assert_eq!(summary.packets(), 3);
+        assert_eq!(summary.endpoints("Addrs").len(), 3);
+        let convs = summary.conversations("Addrs");
+        assert_eq!(convs.len(), 1);
+        assert_eq!(
+            (convs[0].a.to_string(), convs[0].b.to_string()),
+            (String::from("1"), String::from("3"))
+        );
+        let stats = summary.conversations.account().stats();
+        assert_eq!((stats.evictions, stats.rejected), (1, 0));
+        assert_eq!(stats.used, limit);
+    }
+}
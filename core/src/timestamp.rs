@@ -0,0 +1,299 @@
+use chrono::{offset::Utc, DateTime, FixedOffset, SecondsFormat};
+use std::time::{Duration, SystemTime};
+
+/// The resolution a timestamp was captured with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimestampPrecision {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+/// A packet timestamp with nanosecond resolution.
+///
+/// Besides the point in time, which is always UTC, a `Timestamp` remembers
+/// the precision it was captured with and, when the capture recorded one,
+/// the offset of the capture clock's time zone from UTC (pcap `thiszone`,
+/// pcapng `if_tsoffset`). Neither affects comparison or arithmetic.
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamp {
+    secs: i64,
+    nanos: u32,
+    precision: TimestampPrecision,
+    utc_offset: Option<i64>,
+}
+
+impl TimestampPrecision {
+    /// Number of digits after the decimal point.
+    pub fn digits(self) -> u32 {
+        match self {
+            Self::Seconds => 0,
+            Self::Millis => 3,
+            Self::Micros => 6,
+            Self::Nanos => 9,
+        }
+    }
+
+    /// The coarsest precision that can represent `digits` digits after the
+    /// decimal point.
+    pub fn from_digits(digits: u32) -> Self {
+        match digits {
+            0 => Self::Seconds,
+            1..=3 => Self::Millis,
+            4..=6 => Self::Micros,
+            _ => Self::Nanos,
+        }
+    }
+
+    fn nanos_per_tick(self) -> u32 {
+        10u32.pow(9 - self.digits())
+    }
+}
+
+impl Timestamp {
+    pub const UNIX_EPOCH: Self = Self {
+        secs: 0,
+        nanos: 0,
+        precision: TimestampPrecision::Nanos,
+        utc_offset: None,
+    };
+
+    /// Creates a timestamp `secs` seconds and `nanos` nanoseconds after the
+    /// Unix epoch. `nanos` may exceed one second.
+    pub fn new(secs: i64, nanos: u32) -> Self {
+        Self::from_nanos(i128::from(secs) * 1_000_000_000 + i128::from(nanos))
+    }
+
+    /// Creates a timestamp from nanoseconds since the Unix epoch, which is
+    /// negative for times before it. Saturates outside the `i64` range of
+    /// seconds.
+    pub fn from_nanos(nanos: i128) -> Self {
+        let secs = nanos.div_euclid(1_000_000_000);
+        let nanos = nanos.rem_euclid(1_000_000_000) as u32;
+        let (secs, nanos) = match i64::try_from(secs) {
+            Ok(secs) => (secs, nanos),
+            Err(_) if secs < 0 => (i64::MIN, 0),
+            Err(_) => (i64::MAX, 999_999_999),
+        };
+        Self {
+            secs,
+            nanos,
+            precision: TimestampPrecision::Nanos,
+            utc_offset: None,
+        }
+    }
+
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    /// Sets the capture precision. The time itself is not rounded.
+    pub fn with_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Sets the offset of the capture clock's time zone from UTC, in seconds.
+    pub fn with_utc_offset(mut self, offset: Option<i64>) -> Self {
+        self.utc_offset = offset;
+        self
+    }
+
+    /// Whole seconds since the Unix epoch. Negative for earlier times.
+    pub fn secs(&self) -> i64 {
+        self.secs
+    }
+
+    /// Nanoseconds past `secs`, always less than one second.
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    pub fn as_nanos(&self) -> i128 {
+        i128::from(self.secs) * 1_000_000_000 + i128::from(self.nanos)
+    }
+
+    pub fn precision(&self) -> TimestampPrecision {
+        self.precision
+    }
+
+    pub fn utc_offset(&self) -> Option<i64> {
+        self.utc_offset
+    }
+
+    /// Truncates the time to the capture precision.
+    pub fn truncated(&self) -> Self {
+        let per_tick = self.precision.nanos_per_tick();
+        Self {
+            nanos: self.nanos - self.nanos % per_tick,
+            ..*self
+        }
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        if self.secs >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(Duration::new(self.secs as u64, self.nanos))
+        } else {
+            SystemTime::UNIX_EPOCH
+                .checked_sub(Duration::from_secs(self.secs.unsigned_abs()))
+                .and_then(|ts| ts.checked_add(Duration::from_nanos(self.nanos.into())))
+        }
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+
+    /// The time elapsed since `earlier`, or `None` if `earlier` is later.
+    pub fn duration_since(&self, earlier: Timestamp) -> Option<Duration> {
+        let diff = self.as_nanos().checked_sub(earlier.as_nanos())?;
+        let secs = u64::try_from(diff / 1_000_000_000).ok()?;
+        Some(Duration::new(secs, (diff % 1_000_000_000) as u32))
+    }
+
+    pub fn checked_add(&self, dur: Duration) -> Option<Self> {
+        let nanos = self.as_nanos().checked_add(dur.as_nanos() as i128)?;
+        Some(self.with_nanos(nanos))
+    }
+
+    pub fn checked_sub(&self, dur: Duration) -> Option<Self> {
+        let nanos = self.as_nanos().checked_sub(dur.as_nanos() as i128)?;
+        Some(self.with_nanos(nanos))
+    }
+
+    fn with_nanos(&self, nanos: i128) -> Self {
+        Self {
+            precision: self.precision,
+            utc_offset: self.utc_offset,
+            ..Self::from_nanos(nanos)
+        }
+    }
+
+    /// Formats the timestamp as RFC 3339 with as many fractional digits as
+    /// the capture precision, in UTC.
+    pub fn to_rfc3339(&self) -> String {
+        self.format(None)
+    }
+
+    /// Formats the timestamp as RFC 3339 in the capture clock's time zone,
+    /// if known, and UTC otherwise.
+    pub fn to_local_rfc3339(&self) -> String {
+        self.format(self.utc_offset)
+    }
+
+    fn format(&self, offset: Option<i64>) -> String {
+        let fmt = match self.precision {
+            TimestampPrecision::Seconds => SecondsFormat::Secs,
+            TimestampPrecision::Millis => SecondsFormat::Millis,
+            TimestampPrecision::Micros => SecondsFormat::Micros,
+            TimestampPrecision::Nanos => SecondsFormat::Nanos,
+        };
+        let utc = DateTime::<Utc>::from(self.truncated().to_system_time());
+        match offset
+            .and_then(|offset| i32::try_from(offset).ok())
+            .and_then(FixedOffset::east_opt)
+        {
+            Some(offset) => utc.with_timezone(&offset).to_rfc3339_opts(fmt, true),
+            None => utc.to_rfc3339_opts(fmt, true),
+        }
+    }
+}
+
+impl Default for Timestamp {
+    fn default() -> Self {
+        Self::UNIX_EPOCH
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        (self.secs, self.nanos) == (other.secs, other.nanos)
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.secs, self.nanos).cmp(&(other.secs, other.nanos))
+    }
+}
+
+impl std::hash::Hash for Timestamp {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (self.secs, self.nanos).hash(state);
+    }
+}
+
+impl std::ops::Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, dur: Duration) -> Timestamp {
+        self.checked_add(dur)
+            .expect("overflow when adding duration to timestamp")
+    }
+}
+
+impl std::ops::Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, dur: Duration) -> Timestamp {
+        self.checked_sub(dur)
+            .expect("overflow when subtracting duration from timestamp")
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(ts: SystemTime) -> Self {
+        match ts.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(dur) => Self::from_nanos(dur.as_nanos() as i128),
+            Err(e) => Self::from_nanos(-(e.duration().as_nanos() as i128)),
+        }
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(ts: Timestamp) -> Self {
+        ts.to_system_time()
+    }
+}
+
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_local_rfc3339()[..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timestamp_precision_and_offset() {
+        let ts = Timestamp::new(1_700_000_000, 123_456_789)
+            .with_precision(TimestampPrecision::Micros)
+            .with_utc_offset(Some(3600));
+        assert_eq!(ts.to_rfc3339(), "2023-11-14T22:13:20.123456Z");
+        assert_eq!(ts.to_string(), "2023-11-14T23:13:20.123456+01:00");
+        assert_eq!(ts.truncated().subsec_nanos(), 123_456_000);
+
+        let later = ts + Duration::from_millis(1_900);
+        assert_eq!(later.secs(), 1_700_000_002);
+        assert_eq!(later.subsec_nanos(), 23_456_789);
+        assert_eq!(later.precision(), TimestampPrecision::Micros);
+        assert_eq!(later.duration_since(ts), Some(Duration::from_millis(1_900)));
+        assert_eq!(ts.duration_since(later), None);
+
+        let before = Timestamp::from_nanos(-1_500_000_000);
+        assert_eq!((before.secs(), before.subsec_nanos()), (-2, 500_000_000));
+        assert_eq!(Timestamp::from(before.to_system_time()), before);
+        assert_eq!(
+            Timestamp::from(SystemTime::UNIX_EPOCH + Duration::from_nanos(5)),
+            Timestamp::new(0, 5)
+        );
+    }
+}
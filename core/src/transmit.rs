@@ -1,5 +1,24 @@
-use super::{Error, LinkType, Packet, RawPacket};
+use super::{Error, LinkType, Packet, Pdu, PduExt, RawPacket};
 use async_trait::async_trait;
+use std::time::SystemTime;
+
+fn take_buffer<T: Transmit + ?Sized>(tx: &mut T) -> (Vec<u8>, bool) {
+    match tx.transmission_buffer() {
+        Some(buf) => {
+            buf.clear();
+            (std::mem::take(buf), true)
+        }
+        None => (Vec::new(), false),
+    }
+}
+
+fn restore_buffer<T: Transmit + ?Sized>(tx: &mut T, buf: Vec<u8>, has_buffer: bool) {
+    if has_buffer {
+        if let Some(tbuf) = tx.transmission_buffer() {
+            *tbuf = buf;
+        }
+    }
+}
 
 #[async_trait]
 pub trait Transmit: Send {
@@ -10,23 +29,12 @@ pub trait Transmit: Send {
     }
 
     async fn transmit_as(&mut self, packet: &Packet, datalink: LinkType) -> Result<(), Error> {
-        let mut has_buffer = false;
-        let mut buf = match self.transmission_buffer() {
-            Some(buf) => {
-                has_buffer = true;
-                buf.clear();
-                std::mem::take(buf)
-            }
-            None => Vec::new(),
+        let (mut buf, has_buffer) = take_buffer(self);
+        let res = match packet.make_raw_with_datalink(&mut buf, datalink) {
+            Ok(raw) => self.transmit_raw(raw).await,
+            Err(e) => Err(e),
         };
-        let res = self
-            .transmit_raw(packet.make_raw_with_datalink(&mut buf, datalink)?)
-            .await;
-        if has_buffer {
-            if let Some(tbuf) = self.transmission_buffer() {
-                *tbuf = buf;
-            }
-        }
+        restore_buffer(self, buf, has_buffer);
         res
     }
 
@@ -36,4 +44,50 @@ pub trait Transmit: Send {
         }
         Err(Error::UnknownLinkType)
     }
+
+    /// Transmits a PDU chain, timestamped with the current time.
+    ///
+    /// The PDU is copied and made canonical before serialization, so lengths,
+    /// checksums, etc. are filled in without modifying `pdu`.
+    async fn transmit_pdu<P: Pdu>(&mut self, pdu: &P) -> Result<(), Error> {
+        let datalink = LinkType::from_pdu(pdu).ok_or(Error::UnknownLinkType)?;
+        let mut pdu = pdu.clone();
+        pdu.make_all_canonical();
+        let (mut buf, has_buffer) = take_buffer(self);
+        let res = match pdu.serialize(&mut buf) {
+            Ok(()) => {
+                let len = buf.len();
+                self.transmit_raw(RawPacket::new(
+                    datalink,
+                    SystemTime::now(),
+                    len,
+                    None,
+                    &buf[..],
+                    None,
+                ))
+                .await
+            }
+            Err(e) => Err(e.into()),
+        };
+        restore_buffer(self, buf, has_buffer);
+        res
+    }
+
+    /// Transmits each packet in order with `transmit`, stopping at the
+    /// first error.
+    ///
+    /// This makes one `transmit` call per packet, so it saves no system
+    /// calls over a loop. For batched transmission on npcap, queue the
+    /// packets in a `pcaprs::SendQueue` and send them with
+    /// `pcaprs::Injector::transmit_queue`.
+    async fn transmit_all<'p, I>(&mut self, packets: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = &'p Packet> + Send,
+        I::IntoIter: Send,
+    {
+        for packet in packets {
+            self.transmit(packet).await?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,206 @@
+/// A problem found while validating a packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Issue {
+    /// A checksum does not match the data it covers.
+    BadChecksum { protocol: &'static str },
+    /// A checksum was zero and assumed to be left for the NIC to fill in.
+    OffloadedChecksum { protocol: &'static str },
+    /// Fewer bytes were captured than were on the wire.
+    Truncated { captured: usize, original: usize },
+    /// A length or other field is inconsistent with the packet.
+    Malformed {
+        protocol: &'static str,
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadChecksum { protocol } => write!(f, "Bad {} checksum", protocol),
+            Self::OffloadedChecksum { protocol } => {
+                write!(f, "{} checksum offloaded", protocol)
+            }
+            Self::Truncated { captured, original } => {
+                write!(f, "Truncated ({} of {} bytes captured)", captured, original)
+            }
+            Self::Malformed { protocol, reason } => write!(f, "Malformed {}: {}", protocol, reason),
+        }
+    }
+}
+
+/// Session option that enables validation of each sniffed packet. Register
+/// it with `Session::register` and read the results with
+/// `Packet::validation`.
+///
+/// Captures taken on the sending host often have zero checksums, because
+/// checksum computation is offloaded to the NIC. `offload_zero_checksums`
+/// reports these as `Issue::OffloadedChecksum` instead of
+/// `Issue::BadChecksum`.
+#[derive(Debug, Clone, Default)]
+pub struct Validation {
+    offload_zero_checksums: bool,
+}
+
+/// The issues found in a packet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    issues: Vec<Issue>,
+}
+
+/// Collects issues while the PDUs of a packet are validated. Passed to
+/// `Pdu::validate`.
+pub struct Validator<'a> {
+    options: &'a Validation,
+    report: &'a mut ValidationReport,
+}
+
+impl Validation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn offload_zero_checksums(mut self, offload: bool) -> Self {
+        self.offload_zero_checksums = offload;
+        self
+    }
+
+    pub fn offloads_zero_checksums(&self) -> bool {
+        self.offload_zero_checksums
+    }
+}
+
+impl ValidationReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues[..]
+    }
+
+    /// Returns true if no issues were found. Offloaded checksums are not
+    /// counted as issues here.
+    pub fn is_valid(&self) -> bool {
+        self.issues
+            .iter()
+            .all(|issue| matches!(issue, Issue::OffloadedChecksum { .. }))
+    }
+
+    pub fn push(&mut self, issue: Issue) {
+        self.issues.push(issue);
+    }
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(options: &'a Validation, report: &'a mut ValidationReport) -> Self {
+        Self { options, report }
+    }
+
+    pub fn options(&self) -> &Validation {
+        self.options
+    }
+
+    /// Records the result of checking a checksum field.
+    pub fn checksum(&mut self, protocol: &'static str, checksum: u16, valid: bool) {
+        if valid {
+            return;
+        }
+        if checksum == 0 && self.options.offload_zero_checksums {
+            self.report.push(Issue::OffloadedChecksum { protocol });
+        } else {
+            self.report.push(Issue::BadChecksum { protocol });
+        }
+    }
+
+    pub fn issue(&mut self, issue: Issue) {
+        self.report.push(issue);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(options: &Validation, checksum: u16, valid: bool) -> ValidationReport {
+        let mut report = ValidationReport::new();
+        Validator::new(options, &mut report).checksum("UDP", checksum, valid);
+        report
+    }
+
+    #[test]
+    fn valid_checksum() {
+        let report = check(&Validation::new(), 0x1234, true);
+        assert!(report.issues().is_empty());
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let report = check(&Validation::new(), 0x1234, false);
+        assert_eq!(report.issues(), &[Issue::BadChecksum { protocol: "UDP" }]);
+        assert!(!report.is_valid());
+
+        // Only zero checksums are taken to be offloaded
+        let options = Validation::new().offload_zero_checksums(true);
+        let report = check(&options, 0x1234, false);
+        assert_eq!(report.issues(), &[Issue::BadChecksum { protocol: "UDP" }]);
+    }
+
+    #[test]
+    fn zero_checksum() {
+        let report = check(&Validation::new(), 0, false);
+        assert_eq!(report.issues(), &[Issue::BadChecksum { protocol: "UDP" }]);
+
+        let options = Validation::new().offload_zero_checksums(true);
+        assert!(options.offloads_zero_checksums());
+        let report = check(&options, 0, false);
+        assert_eq!(
+            report.issues(),
+            &[Issue::OffloadedChecksum { protocol: "UDP" }]
+        );
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn other_issues() {
+        let options = Validation::new().offload_zero_checksums(true);
+        let mut report = ValidationReport::new();
+        let mut validator = Validator::new(&options, &mut report);
+        assert!(validator.options().offloads_zero_checksums());
+        validator.checksum("TCP", 0, false);
+        validator.issue(Issue::Truncated {
+            captured: 60,
+            original: 1514,
+        });
+        assert_eq!(report.issues().len(), 2);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn display() {
+        let issues = [
+            Issue::BadChecksum { protocol: "IPv4" },
+            Issue::OffloadedChecksum { protocol: "TCP" },
+            Issue::Truncated {
+                captured: 60,
+                original: 1514,
+            },
+            Issue::Malformed {
+                protocol: "UDP",
+                reason: "length too short",
+            },
+        ];
+        let text: Vec<_> = issues.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            text,
+            [
+                "Bad IPv4 checksum",
+                "TCP checksum offloaded",
+                "Truncated (60 of 1514 bytes captured)",
+                "Malformed UDP: length too short",
+            ]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use super::{Error, PluginContext, PluginDissector, PluginGuest, PluginValue, PLUGIN_ABI_VERSION};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use wasmtime::{
+    Caller, Config, Engine, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+/// A dissector plugin compiled to WebAssembly, run with wasmtime.
+///
+/// The module implements the API described in the `plugin` module
+/// documentation, and exports its linear memory as `memory`. Each call to
+/// `dissect` runs in a new instance, with the guest's fuel and memory
+/// limited, so a guest that traps, loops, or grows its memory without end
+/// only fails to dissect the buffer.
+///
+/// ```ignore
+/// let session = Session::builder()
+///     .dissector::<LinkTypeTable, _>(
+///         LinkType::USER0,
+///         Priority(0),
+///         WasmPlugin::from_file("my_proto.wasm")?.dissector("My Protocol"),
+///     )
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct WasmPlugin {
+    engine: Engine,
+    instance: InstancePre<Host>,
+    fuel: u64,
+    memory_limit: usize,
+}
+
+/// The state of a guest instance during one call to `dissect`.
+struct Host {
+    buf: Vec<u8>,
+    name: Option<String>,
+    fields: Vec<(String, usize, usize, PluginValue)>,
+    limits: StoreLimits,
+}
+
+impl WasmPlugin {
+    /// Fuel of a call to `dissect`, roughly the number of instructions the
+    /// guest may run
+    pub const DEFAULT_FUEL: u64 = 10_000_000;
+
+    /// Bytes of linear memory a guest may use
+    pub const DEFAULT_MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+    /// Loads a WebAssembly module, checking that it implements the version
+    /// of the plugin API in `PLUGIN_ABI_VERSION`.
+    pub fn new(wasm: &[u8]) -> Result<Self, Error> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(Error::Wasm)?;
+        let module = Module::from_binary(&engine, wasm).map_err(Error::Wasm)?;
+        let mut linker = Linker::new(&engine);
+        link(&mut linker).map_err(Error::Wasm)?;
+        let plugin = Self {
+            instance: linker.instantiate_pre(&module).map_err(Error::Wasm)?,
+            engine,
+            fuel: Self::DEFAULT_FUEL,
+            memory_limit: Self::DEFAULT_MEMORY_LIMIT,
+        };
+        plugin.check_abi_version().map_err(Error::Wasm)?;
+        Ok(plugin)
+    }
+
+    /// Loads a WebAssembly module from a `.wasm` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::new(&std::fs::read(path)?[..])
+    }
+
+    /// Sets the fuel of each call to `dissect`, instead of `DEFAULT_FUEL`.
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Sets the memory limit of the guest, instead of
+    /// `DEFAULT_MEMORY_LIMIT`.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Makes a dissector of the plugin, which names its PDUs `name` unless
+    /// the guest sets another name.
+    pub fn dissector<N: Into<Arc<str>>>(self, name: N) -> PluginDissector {
+        PluginDissector::new(name, self)
+    }
+
+    fn store(&self, buf: Vec<u8>) -> wasmtime::Result<Store<Host>> {
+        let host = Host {
+            buf,
+            name: None,
+            fields: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.memory_limit)
+                .instances(1)
+                .build(),
+        };
+        let mut store = Store::new(&self.engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(self.fuel)?;
+        Ok(store)
+    }
+
+    fn check_abi_version(&self) -> wasmtime::Result<()> {
+        let mut store = self.store(Vec::new())?;
+        let instance = self.instance.instantiate(&mut store)?;
+        instance.get_typed_func::<(), i32>(&mut store, "sniffle_dissect")?;
+        let version = instance
+            .get_typed_func::<(), i32>(&mut store, "sniffle_abi_version")?
+            .call(&mut store, ())?;
+        if version as u32 != PLUGIN_ABI_VERSION {
+            return Err(wasmtime::Error::msg(format!(
+                "plugin ABI version {} is not supported, expected {}",
+                version, PLUGIN_ABI_VERSION
+            )));
+        }
+        Ok(())
+    }
+
+    fn run(&self, buf: Vec<u8>) -> wasmtime::Result<(i32, Host)> {
+        let mut store = self.store(buf)?;
+        let instance = self.instance.instantiate(&mut store)?;
+        let len = instance
+            .get_typed_func::<(), i32>(&mut store, "sniffle_dissect")?
+            .call(&mut store, ())?;
+        Ok((len, store.into_data()))
+    }
+}
+
+impl PluginGuest for WasmPlugin {
+    fn dissect(&self, ctx: &mut PluginContext<'_>) -> Option<usize> {
+        let mut buf = vec![0u8; ctx.len()];
+        ctx.read(0, &mut buf[..]);
+        let (len, host) = self.run(buf).ok()?;
+        if let Some(name) = host.name {
+            ctx.set_name(&name[..]);
+        }
+        for (name, offset, len, value) in host.fields {
+            ctx.emit_field(&name[..], offset, len, value);
+        }
+        usize::try_from(len).ok()
+    }
+}
+
+/// The range of guest memory at `ptr` of `len` bytes, if it is inside
+/// `size` bytes of memory.
+fn guest_range(ptr: i32, len: i32, size: usize) -> wasmtime::Result<Range<usize>> {
+    let start = ptr as u32 as usize;
+    match start.checked_add(len as u32 as usize) {
+        Some(end) if end <= size => Ok(start..end),
+        _ => Err(wasmtime::Error::msg("pointer outside of guest memory")),
+    }
+}
+
+fn guest_memory<'a>(
+    caller: &'a mut Caller<'_, Host>,
+) -> wasmtime::Result<(&'a mut [u8], &'a mut Host)> {
+    match caller
+        .get_export("memory")
+        .and_then(|ext| ext.into_memory())
+    {
+        Some(memory) => Ok(memory.data_and_store_mut(caller)),
+        None => Err(wasmtime::Error::msg("plugin does not export its memory")),
+    }
+}
+
+fn guest_str(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    let (mem, _) = guest_memory(caller)?;
+    let range = guest_range(ptr, len, mem.len())?;
+    Ok(String::from_utf8(Vec::from(&mem[range]))?)
+}
+
+fn emit(
+    caller: &mut Caller<'_, Host>,
+    name: (i32, i32),
+    offset: i32,
+    len: i32,
+    value: PluginValue,
+) -> wasmtime::Result<()> {
+    let name = guest_str(caller, name.0, name.1)?;
+    let field = (name, offset as u32 as usize, len as u32 as usize, value);
+    caller.data_mut().fields.push(field);
+    Ok(())
+}
+
+/// Defines the host functions imported by guests.
+fn link(linker: &mut Linker<Host>) -> wasmtime::Result<()> {
+    linker.func_wrap("sniffle", "len", |caller: Caller<'_, Host>| {
+        caller.data().buf.len() as i32
+    })?;
+    linker.func_wrap(
+        "sniffle",
+        "read",
+        |mut caller: Caller<'_, Host>, offset: i32, ptr: i32, len: i32| {
+            let (mem, host) = guest_memory(&mut caller)?;
+            let out = guest_range(ptr, len, mem.len())?;
+            let src = host.buf.get(offset as u32 as usize..).unwrap_or(&[]);
+            let len = src.len().min(out.len());
+            mem[out.start..out.start + len].copy_from_slice(&src[..len]);
+            Ok(len as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "sniffle",
+        "name",
+        |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+            let name = guest_str(&mut caller, ptr, len)?;
+            caller.data_mut().name = Some(name);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "sniffle",
+        "field_uint",
+        |mut caller: Caller<'_, Host>, np: i32, nl: i32, offset: i32, len: i32, value: i64| {
+            emit(
+                &mut caller,
+                (np, nl),
+                offset,
+                len,
+                PluginValue::UInt(value as u64),
+            )
+        },
+    )?;
+    linker.func_wrap(
+        "sniffle",
+        "field_int",
+        |mut caller: Caller<'_, Host>, np: i32, nl: i32, offset: i32, len: i32, value: i64| {
+            emit(&mut caller, (np, nl), offset, len, PluginValue::Int(value))
+        },
+    )?;
+    linker.func_wrap(
+        "sniffle",
+        "field_text",
+        |mut caller: Caller<'_, Host>,
+         np: i32,
+         nl: i32,
+         offset: i32,
+         len: i32,
+         tp: i32,
+         tl: i32| {
+            let text = guest_str(&mut caller, tp, tl)?;
+            emit(&mut caller, (np, nl), offset, len, PluginValue::Text(text))
+        },
+    )?;
+    linker.func_wrap(
+        "sniffle",
+        "field_bytes",
+        |mut caller: Caller<'_, Host>, np: i32, nl: i32, offset: i32, len: i32| {
+            emit(&mut caller, (np, nl), offset, len, PluginValue::Bytes)
+        },
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LinkType, LinkTypeTable, Pdu, PduExt, PluginPdu, Priority, RawPdu, Session};
+
+    /// A type-length-value header, named "TLV", with a field of each kind.
+    const TLV: &str = r#"
+        (module
+          (import "sniffle" "len" (func $len (result i32)))
+          (import "sniffle" "read" (func $read (param i32 i32 i32) (result i32)))
+          (import "sniffle" "name" (func $name (param i32 i32)))
+          (import "sniffle" "field_uint" (func $field_uint (param i32 i32 i32 i32 i64)))
+          (import "sniffle" "field_int" (func $field_int (param i32 i32 i32 i32 i64)))
+          (import "sniffle" "field_text" (func $field_text (param i32 i32 i32 i32 i32 i32)))
+          (import "sniffle" "field_bytes" (func $field_bytes (param i32 i32 i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 16) "TypeLengthValueTLVKindDelta")
+          (func (export "sniffle_abi_version") (result i32) (i32.const 1))
+          (func (export "sniffle_dissect") (result i32)
+            (local $vlen i32)
+            (if (i32.ne (call $read (i32.const 0) (i32.const 0) (i32.const 2)) (i32.const 2))
+              (then (return (i32.const -1))))
+            (local.set $vlen (i32.load8_u (i32.const 1)))
+            (if (i32.lt_u (call $len) (i32.add (local.get $vlen) (i32.const 2)))
+              (then (return (i32.const -1))))
+            (call $name (i32.const 31) (i32.const 3))
+            (call $field_uint (i32.const 16) (i32.const 4) (i32.const 0) (i32.const 1)
+              (i64.load8_u (i32.const 0)))
+            (call $field_uint (i32.const 20) (i32.const 6) (i32.const 1) (i32.const 1)
+              (i64.load8_u (i32.const 1)))
+            (call $field_bytes (i32.const 26) (i32.const 5) (i32.const 2) (local.get $vlen))
+            (call $field_text (i32.const 34) (i32.const 4) (i32.const 0) (i32.const 1)
+              (i32.const 31) (i32.const 3))
+            (call $field_int (i32.const 38) (i32.const 5) (i32.const 1) (i32.const 1)
+              (i64.sub (i64.load8_u (i32.const 1)) (i64.const 4)))
+            (i32.add (local.get $vlen) (i32.const 2))))
+    "#;
+
+    /// A plugin with `body` as its `sniffle_dissect`.
+    fn guest(body: &str) -> String {
+        format!(
+            r#"(module
+                 (import "sniffle" "read" (func $read (param i32 i32 i32) (result i32)))
+                 (memory (export "memory") 1)
+                 (func (export "sniffle_abi_version") (result i32) (i32.const 1))
+                 (func (export "sniffle_dissect") (result i32) {}))"#,
+            body
+        )
+    }
+
+    fn plugin(wat: &str) -> Result<WasmPlugin, Error> {
+        WasmPlugin::new(&wat::parse_str(wat).unwrap()[..])
+    }
+
+    fn session(plugin: WasmPlugin) -> Session {
+        Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(LinkType::USER0, Priority(0), plugin.dissector("Wasm"))
+            .build()
+    }
+
+    #[test]
+    fn wasm_plugin_dissect() {
+        let session = session(plugin(TLV).unwrap());
+        let data = [7u8, 3, 1, 2, 3, 9];
+        let (_, pdu) = session
+            .table_dissect::<LinkTypeTable>(&LinkType::USER0, &data[..], None)
+            .unwrap();
+        let inner = pdu.inner_pdu().unwrap().downcast_ref::<RawPdu>().unwrap();
+        assert_eq!(&inner.data()[..], &[9]);
+        let pdu = pdu.downcast_ref::<PluginPdu>().unwrap();
+        assert_eq!(pdu.name(), "TLV");
+        assert_eq!(pdu.header_len(), 5);
+        assert_eq!(pdu.fields().len(), 5);
+        assert_eq!(pdu.field("Type").unwrap().value(), &PluginValue::UInt(7));
+        assert_eq!(pdu.field("Length").unwrap().value(), &PluginValue::UInt(3));
+        let value = pdu.field("Value").unwrap();
+        assert_eq!((value.offset(), value.len()), (2, 3));
+        assert_eq!(value.value(), &PluginValue::Bytes);
+        let kind = pdu.field("Kind").unwrap().value();
+        assert_eq!(kind, &PluginValue::Text("TLV".into()));
+        assert_eq!(pdu.field("Delta").unwrap().value(), &PluginValue::Int(-1));
+
+        for data in [&[7u8, 3, 1][..], &[7]] {
+            assert!(session
+                .table_dissect::<LinkTypeTable>(&LinkType::USER0, data, None)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn bad_modules() {
+        assert!(WasmPlugin::new(b"not wasm").is_err());
+        let version = guest("(i32.const 0)").replace("(i32.const 1))", "(i32.const 2))");
+        let err = plugin(&version).err().unwrap();
+        assert!(err.to_string().contains("ABI version 2"), "{}", err);
+        let missing =
+            r#"(module (func (export "sniffle_abi_version") (result i32) (i32.const 1)))"#;
+        assert!(plugin(missing).is_err());
+        let unknown_import = r#"(module (import "sniffle" "bogus" (func)))"#;
+        assert!(plugin(unknown_import).is_err());
+    }
+
+    #[test]
+    fn misbehaving_guests() {
+        let (_, pdu) = session(plugin(&guest("(i32.const 2)")).unwrap())
+            .table_dissect::<LinkTypeTable>(&LinkType::USER0, &[1, 2, 3], None)
+            .unwrap();
+        assert_eq!(pdu.header_len(), 2);
+
+        for body in [
+            // Runs out of fuel
+            "(loop $spin (br $spin)) (i32.const 0)",
+            // Reads into memory past the end of the guest's memory
+            "(drop (call $read (i32.const 0) (i32.const 65535) (i32.const 2))) (i32.const 0)",
+            // Traps when its memory can't grow past the limit
+            "(drop (memory.grow (i32.const 1))) (if (i32.eq (memory.size) (i32.const 1)) (then (unreachable))) (i32.const 0)",
+            // Claims more bytes than the buffer holds
+            "(i32.const 4)",
+        ] {
+            let plugin = plugin(&guest(body)).unwrap().memory_limit(64 * 1024);
+            let session = session(plugin);
+            assert!(session
+                .table_dissect::<LinkTypeTable>(&LinkType::USER0, &[1, 2, 3], None)
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Type-length-value elements of the Basic Encoding Rules (X.690), as used
+//! by SNMP and LDAP.
+//!
+//! Only definite length elements are supported. Encoding always produces
+//! the shortest form, as DER requires.
+
+use crate::decode::{DResult, Decode, DecodeError};
+use crate::encode::{Encode, Encoder};
+use crate::varint::BerLength;
+use std::io::Result;
+
+/// Universal class tag numbers
+pub mod tag {
+    pub const BOOLEAN: u32 = 1;
+    pub const INTEGER: u32 = 2;
+    pub const BIT_STRING: u32 = 3;
+    pub const OCTET_STRING: u32 = 4;
+    pub const NULL: u32 = 5;
+    pub const OBJECT_IDENTIFIER: u32 = 6;
+    pub const ENUMERATED: u32 = 10;
+    pub const UTF8_STRING: u32 = 12;
+    pub const SEQUENCE: u32 = 16;
+    pub const SET: u32 = 17;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    Universal,
+    Application,
+    ContextSpecific,
+    Private,
+}
+
+/// The identifier octets of an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Identifier {
+    pub class: Class,
+    /// Whether the contents are a sequence of elements.
+    pub constructed: bool,
+    pub tag: u32,
+}
+
+/// An element with definite length contents.
+///
+/// ```
+/// # use sniffle_ende::ber::{self, tag, Identifier};
+/// let (rem, elem) = ber::tlv(&[0x02, 0x02, 0x01, 0x00, 0xff]).unwrap();
+/// assert_eq!(rem, &[0xff]);
+/// assert_eq!(elem.id, Identifier::universal(tag::INTEGER));
+/// assert_eq!(ber::decode_integer(elem.value), Some(256));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tlv<'a> {
+    pub id: Identifier,
+    pub value: &'a [u8],
+}
+
+fn malformed<T>() -> DResult<'static, T> {
+    Err(nom::Err::Error(DecodeError::Malformed))
+}
+
+impl Identifier {
+    pub const fn new(class: Class, constructed: bool, tag: u32) -> Self {
+        Self {
+            class,
+            constructed,
+            tag,
+        }
+    }
+
+    /// A universal class identifier, which is constructed for SEQUENCE and
+    /// SET.
+    pub const fn universal(tag: u32) -> Self {
+        Self::new(
+            Class::Universal,
+            tag == tag::SEQUENCE || tag == tag::SET,
+            tag,
+        )
+    }
+
+    pub const SEQUENCE: Self = Self::universal(tag::SEQUENCE);
+
+    /// Number of bytes in the encoding of the identifier.
+    pub fn encoded_len(&self) -> usize {
+        if self.tag < 0x1f {
+            1
+        } else {
+            1 + (32 - self.tag.leading_zeros() as usize).div_ceil(7)
+        }
+    }
+}
+
+impl Decode for Identifier {
+    fn decode(buf: &[u8]) -> DResult<'_, Self> {
+        let (mut buf, first) = u8::decode(buf)?;
+        let class = match first >> 6 {
+            0 => Class::Universal,
+            1 => Class::Application,
+            2 => Class::ContextSpecific,
+            _ => Class::Private,
+        };
+        let constructed = first & 0x20 != 0;
+        let mut tag = (first & 0x1f) as u32;
+        if tag == 0x1f {
+            tag = 0;
+            loop {
+                let (rem, byte) = u8::decode(buf)?;
+                buf = rem;
+                if tag > u32::MAX >> 7 {
+                    return malformed();
+                }
+                tag = (tag << 7) | (byte & 0x7f) as u32;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+        Ok((buf, Self::new(class, constructed, tag)))
+    }
+}
+
+impl Encode for Identifier {
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
+        let mut first = match self.class {
+            Class::Universal => 0x00,
+            Class::Application => 0x40,
+            Class::ContextSpecific => 0x80,
+            Class::Private => 0xc0,
+        };
+        if self.constructed {
+            first |= 0x20;
+        }
+        let len = self.encoded_len();
+        if len == 1 {
+            return encoder.write_all(&[first | self.tag as u8]);
+        }
+        let mut buf = [0u8; 6];
+        buf[0] = first | 0x1f;
+        for (i, byte) in buf[1..len].iter_mut().enumerate() {
+            let shift = 7 * (len - 2 - i);
+            *byte = ((self.tag >> shift) as u8 & 0x7f) | 0x80;
+        }
+        buf[len - 1] &= 0x7f;
+        encoder.write_all(&buf[..len])
+    }
+}
+
+/// Decodes an element with definite length contents. Indefinite lengths are
+/// malformed.
+pub fn tlv(buf: &[u8]) -> DResult<'_, Tlv<'_>> {
+    let (buf, id) = Identifier::decode(buf)?;
+    let (buf, len) = BerLength::decode(buf)?;
+    let len = match len {
+        BerLength::Definite(len) => match usize::try_from(len) {
+            Ok(len) => len,
+            Err(_) => return malformed(),
+        },
+        BerLength::Indefinite => return malformed(),
+    };
+    if buf.len() < len {
+        return Err(nom::Err::Incomplete(nom::Needed::new(len - buf.len())));
+    }
+    Ok((
+        &buf[len..],
+        Tlv {
+            id,
+            value: &buf[..len],
+        },
+    ))
+}
+
+impl<'a> Tlv<'a> {
+    pub fn new(id: Identifier, value: &'a [u8]) -> Self {
+        Self { id, value }
+    }
+
+    /// Number of bytes in the identifier and length octets.
+    pub fn header_len(&self) -> usize {
+        self.id.encoded_len() + BerLength::Definite(self.value.len() as u64).encoded_len()
+    }
+
+    /// Number of bytes in the encoding of the element.
+    pub fn encoded_len(&self) -> usize {
+        self.header_len() + self.value.len()
+    }
+
+    /// Decodes the elements of constructed contents.
+    pub fn children(&self) -> DResult<'a, Vec<Tlv<'a>>> {
+        let mut buf = self.value;
+        let mut children = Vec::new();
+        while !buf.is_empty() {
+            let (rem, child) = tlv(buf)?;
+            buf = rem;
+            children.push(child);
+        }
+        Ok((buf, children))
+    }
+}
+
+impl Encode for Tlv<'_> {
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
+        self.id.encode(encoder)?;
+        BerLength::Definite(self.value.len() as u64).encode(encoder)?;
+        encoder.write_all(self.value)
+    }
+}
+
+/// Decodes the contents of an INTEGER, or returns `None` if it is empty or
+/// does not fit in an `i64`.
+pub fn decode_integer(value: &[u8]) -> Option<i64> {
+    if value.is_empty() || value.len() > 8 {
+        return None;
+    }
+    let init = if value[0] & 0x80 != 0 { -1i64 } else { 0 };
+    Some(
+        value
+            .iter()
+            .fold(init, |acc, byte| (acc << 8) | *byte as i64),
+    )
+}
+
+/// Decodes the contents of a non-negative INTEGER, such as an SNMP counter,
+/// or returns `None` if it is empty, negative, or does not fit in a `u64`.
+pub fn decode_unsigned(value: &[u8]) -> Option<u64> {
+    let value = match value {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        [first, ..] if first & 0x80 == 0 => value,
+        _ => return None,
+    };
+    if value.len() > 8 {
+        return None;
+    }
+    Some(
+        value
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64),
+    )
+}
+
+/// Encodes the contents of an INTEGER.
+pub fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7 {
+        let redundant = match bytes[start] {
+            0x00 => bytes[start + 1] & 0x80 == 0,
+            0xff => bytes[start + 1] & 0x80 != 0,
+            _ => false,
+        };
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    Vec::from(&bytes[start..])
+}
+
+/// Encodes the contents of a non-negative INTEGER.
+pub fn encode_unsigned(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = (value.leading_zeros() as usize / 8).min(7);
+    let mut out = Vec::with_capacity(9);
+    if bytes[start] & 0x80 != 0 {
+        out.push(0);
+    }
+    out.extend_from_slice(&bytes[start..]);
+    out
+}
+
+/// Decodes the contents of an OBJECT IDENTIFIER into its arcs, or returns
+/// `None` if it is malformed.
+///
+/// ```
+/// # use sniffle_ende::ber;
+/// let oid = [0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x05, 0x00];
+/// assert_eq!(ber::decode_oid(&oid), Some(vec![1, 3, 6, 1, 2, 1, 1, 5, 0]));
+/// assert_eq!(ber::encode_oid(&[1, 3, 6, 1, 2, 1, 1, 5, 0]), Some(Vec::from(&oid[..])));
+/// ```
+pub fn decode_oid(value: &[u8]) -> Option<Vec<u32>> {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for (i, byte) in value.iter().enumerate() {
+        arc = (arc << 7) | (byte & 0x7f) as u64;
+        if arc > u32::MAX as u64 + 80 {
+            return None;
+        }
+        if byte & 0x80 != 0 {
+            if i + 1 == value.len() {
+                return None;
+            }
+            continue;
+        }
+        if arcs.is_empty() {
+            let first = (arc / 40).min(2);
+            arcs.push(first as u32);
+            arcs.push(u32::try_from(arc - first * 40).ok()?);
+        } else {
+            arcs.push(u32::try_from(arc).ok()?);
+        }
+        arc = 0;
+    }
+    if arcs.is_empty() {
+        None
+    } else {
+        Some(arcs)
+    }
+}
+
+/// Encodes the contents of an OBJECT IDENTIFIER. Returns `None` if there
+/// are fewer than two arcs or the first two arcs are out of range.
+pub fn encode_oid(arcs: &[u32]) -> Option<Vec<u8>> {
+    let (first, second, rest) = match arcs {
+        [first @ 0..=2, second, rest @ ..] if *first == 2 || *second < 40 => {
+            (*first, *second, rest)
+        }
+        _ => return None,
+    };
+    let mut out = Vec::new();
+    let first = first as u64 * 40 + second as u64;
+    for arc in std::iter::once(first).chain(rest.iter().map(|arc| *arc as u64)) {
+        let len = (64 - (arc | 1).leading_zeros() as usize).div_ceil(7);
+        for i in (0..len).rev() {
+            let byte = (arc >> (7 * i)) as u8 & 0x7f;
+            out.push(if i == 0 { byte } else { byte | 0x80 });
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ber_elements() {
+        for (value, bytes) in [
+            (0i64, &[0x00][..]),
+            (127, &[0x7f]),
+            (128, &[0x00, 0x80]),
+            (-1, &[0xff]),
+            (-129, &[0xff, 0x7f]),
+            (i64::MIN, &[0x80, 0, 0, 0, 0, 0, 0, 0]),
+        ] {
+            assert_eq!(&encode_integer(value)[..], bytes);
+            assert_eq!(decode_integer(bytes), Some(value));
+        }
+        assert_eq!(
+            &encode_unsigned(u64::MAX)[..],
+            &[0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(decode_unsigned(&encode_unsigned(u64::MAX)), Some(u64::MAX));
+        assert_eq!(decode_unsigned(&[0x80]), None);
+        assert_eq!(decode_integer(&[]), None);
+
+        let id = Identifier::new(Class::ContextSpecific, true, 0x1234);
+        let mut buf = Vec::new();
+        Tlv::new(id, &[1, 2, 3]).encode(&mut buf).unwrap();
+        assert_eq!(&buf[..], &[0xbf, 0xa4, 0x34, 0x03, 1, 2, 3]);
+        assert_eq!(tlv(&buf[..]), Ok((&[][..], Tlv::new(id, &[1, 2, 3]))));
+        assert!(matches!(tlv(&buf[..5]), Err(nom::Err::Incomplete(_))));
+        assert!(matches!(
+            tlv(&[0x30, 0x80, 0x00, 0x00]),
+            Err(nom::Err::Error(DecodeError::Malformed))
+        ));
+
+        let seq = [0x30, 0x06, 0x02, 0x01, 0x05, 0x04, 0x01, b'a'];
+        let (_, elem) = tlv(&seq[..]).unwrap();
+        assert_eq!(elem.id, Identifier::SEQUENCE);
+        let (_, children) = elem.children().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[1].id, Identifier::universal(tag::OCTET_STRING));
+        assert_eq!(children[1].value, b"a");
+
+        assert_eq!(decode_oid(&[0x88, 0x37, 0x03]), Some(vec![2, 999, 3]));
+        assert_eq!(encode_oid(&[2, 999, 3]), Some(vec![0x88, 0x37, 0x03]));
+        assert_eq!(decode_oid(&[0x2b, 0x86]), None);
+        assert_eq!(encode_oid(&[1, 40]), None);
+    }
+}
@@ -113,6 +113,130 @@ pub fn decode_le<D: DecodeLe>(buf: &[u8]) -> DResult<'_, D> {
     D::decode_le(buf)
 }
 
+/// A type that decodes the same byte region as one of several layouts, where
+/// the layout is chosen by a previously decoded selector value (e.g. ICMP
+/// bodies keyed by type, or DNS RDATA keyed by RR type).
+///
+/// Usually implemented with the [`decode_overlay!`](crate::decode_overlay)
+/// macro.
+pub trait DecodeOverlay: Sized {
+    type Selector;
+
+    fn decode_overlay(selector: Self::Selector, buf: &[u8]) -> DResult<'_, Self>;
+}
+
+/// Returns a parser that decodes an overlay using `selector` to choose the
+/// layout.
+pub fn overlay<'a, O>(selector: O::Selector) -> impl FnMut(&'a [u8]) -> DResult<'a, O>
+where
+    O: DecodeOverlay,
+    O::Selector: Clone,
+{
+    move |buf| O::decode_overlay(selector.clone(), buf)
+}
+
+/// Returns a parser that decodes an overlay from a fixed size region of `len`
+/// bytes. See [`scoped`] for how the region is handled.
+pub fn overlay_region<'a, O>(
+    selector: O::Selector,
+    len: usize,
+) -> impl FnMut(&'a [u8]) -> DResult<'a, O>
+where
+    O: DecodeOverlay,
+    O::Selector: Clone,
+{
+    scoped(len, overlay(selector))
+}
+
+/// Returns a parser that restricts `parser` to the next `len` bytes.
+///
+/// The full `len` bytes are always consumed, even if `parser` doesn't use
+/// all of them. If `parser` runs out of data within the region, the
+/// result is `DecodeError::Malformed` rather than `Incomplete`, since the
+/// region is known to be complete.
+pub fn scoped<'a, O, F>(len: usize, mut parser: F) -> impl FnMut(&'a [u8]) -> DResult<'a, O>
+where
+    F: nom::Parser<&'a [u8], O, DecodeError<'a>>,
+{
+    move |buf: &'a [u8]| {
+        if buf.len() < len {
+            return Err(nom::Err::Incomplete(nom::Needed::new(len - buf.len())));
+        }
+        let (region, rem) = buf.split_at(len);
+        match parser.parse(region) {
+            Ok((_, val)) => Ok((rem, val)),
+            Err(nom::Err::Incomplete(_)) => Err(nom::Err::Error(DecodeError::Malformed)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Defines an enum implementing [`DecodeOverlay`](crate::decode::DecodeOverlay),
+/// where each variant wraps a type implementing
+/// [`Decode`](crate::decode::Decode) and is selected by matching a pattern
+/// against the selector. If no pattern matches, decoding fails with
+/// `DecodeError::Malformed`, so include a `_` arm for a catch-all layout.
+///
+/// ```
+/// use sniffle_ende::decode::{overlay, DResult, Decode, DecodeBe};
+/// use sniffle_ende::decode_overlay;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Echo(u16, u16);
+///
+/// impl Decode for Echo {
+///     fn decode(buf: &[u8]) -> DResult<'_, Self> {
+///         let (buf, id) = u16::decode_be(buf)?;
+///         let (buf, seq) = u16::decode_be(buf)?;
+///         Ok((buf, Echo(id, seq)))
+///     }
+/// }
+///
+/// decode_overlay! {
+///     #[derive(Debug, PartialEq)]
+///     enum Body: u8 {
+///         0 | 8 => Echo(Echo),
+///         _ => Other([u8; 4]),
+///     }
+/// }
+///
+/// let buf = [0, 1, 0, 2];
+/// assert_eq!(overlay::<Body>(8)(&buf[..]).unwrap().1, Body::Echo(Echo(1, 2)));
+/// assert_eq!(overlay::<Body>(3)(&buf[..]).unwrap().1, Body::Other([0, 1, 0, 2]));
+/// ```
+#[macro_export]
+macro_rules! decode_overlay {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident: $sel:ty {
+            $($pat:pat => $variant:ident($ty:ty)),+ $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $($variant($ty),)+
+        }
+
+        impl $crate::decode::DecodeOverlay for $name {
+            type Selector = $sel;
+
+            fn decode_overlay(
+                selector: $sel,
+                buf: &[u8],
+            ) -> $crate::decode::DResult<'_, Self> {
+                #[allow(unreachable_patterns)]
+                match selector {
+                    $($pat => {
+                        let (rem, val) = <$ty as $crate::decode::Decode>::decode(buf)?;
+                        Ok((rem, Self::$variant(val)))
+                    })+
+                    _ => Err($crate::nom::Err::Error($crate::decode::DecodeError::Malformed)),
+                }
+            }
+        }
+    };
+}
+
 impl<'a> ParseError<&'a [u8]> for DecodeError<'a> {
     fn from_error_kind(input: &'a [u8], kind: nom::error::ErrorKind) -> Self {
         Self::Nom(nom::error::Error::from_error_kind(input, kind))
@@ -259,7 +383,9 @@ macro_rules! make_decode {
 }
 
 unsafe fn transmute<T, U>(x: T) -> U {
-    std::ptr::read(std::mem::transmute::<_, *const U>(std::ptr::addr_of!(x)))
+    std::ptr::read(std::mem::transmute::<*const T, *const U>(
+        std::ptr::addr_of!(x),
+    ))
 }
 
 make_decode!(u16, be_u16, le_u16);
@@ -859,4 +985,53 @@ mod test {
         assert_eq!(<[i16; 2]>::decode_le(&buf[5..]), Err(incomplete!(3)));
         assert_eq!(<[i16; 2]>::decode_le(&buf[6..]), Err(incomplete!(4)));
     }
+
+    crate::decode_overlay! {
+        #[derive(Debug, PartialEq)]
+        enum Body: u8 {
+            0 => Word(u8),
+            1 | 2 => Pair([u8; 2]),
+        }
+    }
+
+    #[test]
+    fn overlay_decode() {
+        let buf = &[1, 2, 3, 4][..];
+        assert_eq!(overlay::<Body>(0)(buf), Ok((&[2, 3, 4][..], Body::Word(1))));
+        assert_eq!(
+            overlay::<Body>(2)(buf),
+            Ok((&[3, 4][..], Body::Pair([1, 2])))
+        );
+        assert_eq!(
+            overlay::<Body>(3)(buf),
+            Err(nom::Err::Error(DecodeError::Malformed))
+        );
+        assert_eq!(overlay::<Body>(1)(&buf[3..]), Err(incomplete!(1)));
+    }
+
+    #[test]
+    fn overlay_region_decode() {
+        let buf = &[1, 2, 3, 4][..];
+        assert_eq!(
+            overlay_region::<Body>(0, 3)(buf),
+            Ok((&[4][..], Body::Word(1)))
+        );
+        assert_eq!(
+            overlay_region::<Body>(1, 1)(buf),
+            Err(nom::Err::Error(DecodeError::Malformed))
+        );
+        assert_eq!(overlay_region::<Body>(0, 6)(buf), Err(incomplete!(2)));
+    }
+
+    #[test]
+    fn scoped_decode() {
+        let buf = &[1, 2, 3, 4][..];
+        assert_eq!(scoped(3, u16::decode_be)(buf), Ok((&[4][..], 0x0102)));
+        assert_eq!(scoped(2, u16::decode_be)(buf), Ok((&[3, 4][..], 0x0102)));
+        assert_eq!(
+            scoped(1, u16::decode_be)(buf),
+            Err(nom::Err::Error(DecodeError::Malformed))
+        );
+        assert_eq!(scoped(5, u16::decode_be)(buf), Err(incomplete!(1)));
+    }
 }
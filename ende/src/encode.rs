@@ -1,4 +1,12 @@
-use std::io::{Result, Write};
+use bytes::{Bytes, BytesMut};
+use std::cell::Cell;
+use std::io::{ErrorKind, IoSlice, Result, Write};
+
+thread_local! {
+    /// The chunk currently being written by `Encoder::encode_bytes`, so a
+    /// `BytesEncoder` can reference it instead of copying it.
+    static PENDING: Cell<Option<Bytes>> = const { Cell::new(None) };
+}
 
 pub trait Encoder<'a>: Write + 'a {
     fn encode<E: BasicEncode + ?Sized>(&mut self, data: &E) -> Result<&mut Self> {
@@ -16,9 +24,141 @@ pub trait Encoder<'a>: Write + 'a {
         Ok(self)
     }
 
+    /// Writes all of `bufs`, using vectored writes where the underlying
+    /// writer supports them.
+    fn encode_vectored(&mut self, mut bufs: &mut [IoSlice<'_>]) -> Result<&mut Self> {
+        IoSlice::advance_slices(&mut bufs, 0);
+        while !bufs.is_empty() {
+            match self.write_vectored(bufs) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self)
+    }
+
+    /// Writes a shared chunk of bytes. A `BytesEncoder` keeps a reference
+    /// to the chunk rather than copying it; other writers copy as usual.
+    fn encode_bytes(&mut self, bytes: &Bytes) -> Result<&mut Self> {
+        let prev = PENDING.replace(Some(bytes.clone()));
+        let res = self.write_all(&bytes[..]);
+        PENDING.set(prev);
+        res?;
+        Ok(self)
+    }
+
     fn as_dyn_mut(&mut self) -> &mut DynEncoder<'a>;
 }
 
+/// An encoder that collects its output as a list of `Bytes` chunks.
+///
+/// Chunks written with `Encoder::encode_bytes` are stored by reference, so
+/// large payloads are not copied. All other writes are buffered into
+/// chunks of their own.
+#[derive(Debug, Default, Clone)]
+pub struct BytesEncoder {
+    chunks: Vec<Bytes>,
+    buf: BytesMut,
+    len: usize,
+}
+
+impl BytesEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total number of bytes written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the written chunks, in order.
+    pub fn into_chunks(mut self) -> Vec<Bytes> {
+        self.flush_buf();
+        self.chunks
+    }
+
+    /// Returns the written bytes as a single chunk. This only copies when
+    /// more than one chunk was written.
+    pub fn freeze(self) -> Bytes {
+        let mut chunks = self.into_chunks();
+        match chunks.len() {
+            0 => Bytes::new(),
+            1 => chunks.pop().unwrap_or_default(),
+            _ => {
+                let mut buf = BytesMut::with_capacity(chunks.iter().map(Bytes::len).sum());
+                for chunk in chunks {
+                    buf.extend_from_slice(&chunk[..]);
+                }
+                buf.freeze()
+            }
+        }
+    }
+
+    /// Returns the written chunks as `IoSlice`s, such as for passing to a
+    /// vectored write.
+    pub fn io_slices(&mut self) -> Vec<IoSlice<'_>> {
+        self.flush_buf();
+        self.chunks
+            .iter()
+            .map(|chunk| IoSlice::new(chunk))
+            .collect()
+    }
+
+    fn flush_buf(&mut self) {
+        if !self.buf.is_empty() {
+            self.chunks.push(self.buf.split().freeze());
+        }
+    }
+
+    fn pending_chunk(buf: &[u8]) -> Option<Bytes> {
+        let pending = PENDING.take();
+        let chunk = pending.as_ref().and_then(|pending| {
+            let start = pending.as_ptr() as usize;
+            let ptr = buf.as_ptr() as usize;
+            (ptr >= start && ptr + buf.len() <= start + pending.len())
+                .then(|| pending.slice_ref(buf))
+        });
+        PENDING.set(pending);
+        chunk
+    }
+}
+
+impl Write for BytesEncoder {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match Self::pending_chunk(buf) {
+            Some(chunk) => {
+                self.flush_buf();
+                self.chunks.push(chunk);
+            }
+            None => self.buf.extend_from_slice(buf),
+        }
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        let mut len = 0;
+        for buf in bufs {
+            len += self.write(buf)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
 pub type DynEncoder<'a> = dyn Write + 'a;
 
 pub trait Encode: Sized {
@@ -154,13 +294,20 @@ impl Encode for u8 {
     }
 }
 
+impl Encode for Bytes {
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
+        encoder.encode_bytes(self)?;
+        Ok(())
+    }
+}
+
 impl Encode for i8 {
     fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
         encoder.write_all(&self.to_ne_bytes())
     }
 
     fn encode_many<'a, W: Encoder<'a> + ?Sized>(slice: &[Self], encoder: &mut W) -> Result<()> {
-        unsafe { encoder.write_all(std::mem::transmute(slice)) }
+        unsafe { encoder.write_all(std::mem::transmute::<&[i8], &[u8]>(slice)) }
     }
 }
 
@@ -228,3 +375,30 @@ make_encode!(i64);
 make_encode!(i128);
 make_encode!(f32);
 make_encode!(f64);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bytes_encoder() {
+        let payload = Bytes::from(vec![7u8; 1024]);
+        let mut enc = BytesEncoder::new();
+        enc.encode_be(&0x0102u16)
+            .unwrap()
+            .encode(&payload)
+            .unwrap()
+            .encode_vectored(&mut [IoSlice::new(&[3, 4]), IoSlice::new(&[5])])
+            .unwrap();
+        assert_eq!(enc.len(), 1029);
+        let chunks = enc.clone().into_chunks();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].as_ptr(), payload.as_ptr());
+        assert_eq!(&chunks[2][..], &[3, 4, 5]);
+
+        let mut vec = Vec::new();
+        vec.encode_be(&0x0102u16).unwrap().encode(&payload).unwrap();
+        vec.extend_from_slice(&[3, 4, 5]);
+        assert_eq!(&enc.freeze()[..], &vec[..]);
+    }
+}
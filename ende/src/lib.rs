@@ -1,6 +1,9 @@
 #![doc = include_str!("../README.md")]
 
+pub mod ber;
 pub mod decode;
 pub mod encode;
+pub mod varint;
 
+pub use bytes;
 pub use nom;
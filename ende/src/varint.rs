@@ -0,0 +1,296 @@
+//! Variable length integer encodings.
+//!
+//! Each encoding is a newtype implementing `Decode` and `Encode`. Encoding
+//! always produces the shortest form, while decoding accepts any valid form.
+
+use crate::decode::{DResult, Decode, DecodeError};
+use crate::encode::{Encode, Encoder};
+use std::io::{Error, ErrorKind, Result};
+
+/// An unsigned LEB128 integer, as used for protobuf varints.
+///
+/// ```
+/// # use sniffle_ende::varint::VarInt;
+/// # use sniffle_ende::decode::Decode;
+/// assert_eq!(VarInt::decode(&[0xac, 0x02]), Ok((&[][..], VarInt(300))));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarInt(pub u64);
+
+/// A signed integer, zigzag encoded and then LEB128 encoded, as used for
+/// protobuf `sint32` and `sint64`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZigZag(pub i64);
+
+/// A QUIC variable length integer (RFC 9000, section 16), which is at most
+/// `QuicVarInt::MAX`.
+///
+/// ```
+/// # use sniffle_ende::varint::QuicVarInt;
+/// # use sniffle_ende::decode::Decode;
+/// assert_eq!(QuicVarInt::decode(&[0x7b, 0xbd]), Ok((&[][..], QuicVarInt(15293))));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QuicVarInt(pub u64);
+
+/// The length octets of a BER or DER encoded value (X.690, section 8.1.3),
+/// as used by SNMP and LDAP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BerLength {
+    Definite(u64),
+    /// The contents are terminated by an end-of-contents value. Not
+    /// allowed in DER.
+    Indefinite,
+}
+
+fn incomplete<T>() -> DResult<'static, T> {
+    Err(nom::Err::Incomplete(nom::Needed::new(1)))
+}
+
+fn malformed<T>() -> DResult<'static, T> {
+    Err(nom::Err::Error(DecodeError::Malformed))
+}
+
+impl VarInt {
+    /// Number of bytes in the encoding of the value.
+    pub fn encoded_len(&self) -> usize {
+        (64 - (self.0 | 1).leading_zeros() as usize).div_ceil(7)
+    }
+}
+
+impl Decode for VarInt {
+    fn decode(buf: &[u8]) -> DResult<'_, Self> {
+        let mut value = 0u64;
+        for (i, byte) in buf.iter().enumerate() {
+            // The 10th byte may only hold the top bit of a u64
+            if i == 9 && *byte > 1 {
+                return malformed();
+            }
+            value |= ((byte & 0x7f) as u64) << (i * 7);
+            if byte & 0x80 == 0 {
+                return Ok((&buf[i + 1..], Self(value)));
+            }
+        }
+        if buf.len() >= 10 {
+            malformed()
+        } else {
+            incomplete()
+        }
+    }
+}
+
+impl Encode for VarInt {
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
+        let mut buf = [0u8; 10];
+        let mut value = self.0;
+        let len = self.encoded_len();
+        for byte in buf[..len - 1].iter_mut() {
+            *byte = (value as u8 & 0x7f) | 0x80;
+            value >>= 7;
+        }
+        buf[len - 1] = value as u8;
+        encoder.write_all(&buf[..len])
+    }
+}
+
+impl ZigZag {
+    pub fn encoded_len(&self) -> usize {
+        VarInt::from(*self).encoded_len()
+    }
+}
+
+impl From<ZigZag> for VarInt {
+    fn from(value: ZigZag) -> Self {
+        Self(((value.0 << 1) ^ (value.0 >> 63)) as u64)
+    }
+}
+
+impl From<VarInt> for ZigZag {
+    fn from(value: VarInt) -> Self {
+        Self((value.0 >> 1) as i64 ^ -((value.0 & 1) as i64))
+    }
+}
+
+impl Decode for ZigZag {
+    fn decode(buf: &[u8]) -> DResult<'_, Self> {
+        let (rem, value) = VarInt::decode(buf)?;
+        Ok((rem, value.into()))
+    }
+}
+
+impl Encode for ZigZag {
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
+        VarInt::from(*self).encode(encoder)
+    }
+}
+
+impl QuicVarInt {
+    /// The largest value that can be encoded, 2^62 - 1.
+    pub const MAX: u64 = (1 << 62) - 1;
+
+    /// Number of bytes in the encoding of the value: 1, 2, 4, or 8.
+    pub fn encoded_len(&self) -> usize {
+        match self.0 {
+            0..=0x3f => 1,
+            0x40..=0x3fff => 2,
+            0x4000..=0x3fff_ffff => 4,
+            _ => 8,
+        }
+    }
+}
+
+impl Decode for QuicVarInt {
+    fn decode(buf: &[u8]) -> DResult<'_, Self> {
+        let first = match buf.first() {
+            Some(first) => *first,
+            None => return incomplete(),
+        };
+        let len = 1 << (first >> 6);
+        if buf.len() < len {
+            return Err(nom::Err::Incomplete(nom::Needed::new(len - buf.len())));
+        }
+        let value = buf[1..len]
+            .iter()
+            .fold((first & 0x3f) as u64, |acc, byte| (acc << 8) | *byte as u64);
+        Ok((&buf[len..], Self(value)))
+    }
+}
+
+impl Encode for QuicVarInt {
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
+        if self.0 > Self::MAX {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "QUIC variable length integer out of range",
+            ));
+        }
+        let len = self.encoded_len();
+        let mut buf = self.0.to_be_bytes();
+        buf[8 - len] |= (len.trailing_zeros() as u8) << 6;
+        encoder.write_all(&buf[8 - len..])
+    }
+}
+
+impl BerLength {
+    /// Number of bytes in the encoding of the length.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Definite(0..=0x7f) => 1,
+            Self::Definite(len) => 1 + (64 - len.leading_zeros() as usize).div_ceil(8),
+            Self::Indefinite => 1,
+        }
+    }
+}
+
+impl Decode for BerLength {
+    fn decode(buf: &[u8]) -> DResult<'_, Self> {
+        let first = match buf.first() {
+            Some(first) => *first,
+            None => return incomplete(),
+        };
+        match first {
+            0..=0x7f => Ok((&buf[1..], Self::Definite(first as u64))),
+            0x80 => Ok((&buf[1..], Self::Indefinite)),
+            // Reserved
+            0xff => malformed(),
+            _ => {
+                let len = (first & 0x7f) as usize;
+                if buf.len() < len + 1 {
+                    return Err(nom::Err::Incomplete(nom::Needed::new(len + 1 - buf.len())));
+                }
+                let bytes = &buf[1..len + 1];
+                let leading = bytes.iter().take_while(|byte| **byte == 0).count();
+                if len - leading > 8 {
+                    return malformed();
+                }
+                let value = bytes
+                    .iter()
+                    .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+                Ok((&buf[len + 1..], Self::Definite(value)))
+            }
+        }
+    }
+}
+
+impl Encode for BerLength {
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> Result<()> {
+        match self {
+            Self::Definite(len @ 0..=0x7f) => encoder.write_all(&[*len as u8]),
+            Self::Definite(len) => {
+                let bytes = len.to_be_bytes();
+                let count = self.encoded_len() - 1;
+                encoder.write_all(&[0x80 | count as u8])?;
+                encoder.write_all(&bytes[8 - count..])
+            }
+            Self::Indefinite => encoder.write_all(&[0x80]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<T: Decode + Encode + PartialEq + std::fmt::Debug>(value: T, bytes: &[u8]) {
+        let mut buf = Vec::new();
+        value.encode(&mut buf).unwrap();
+        assert_eq!(&buf[..], bytes, "{:?}", value);
+        assert_eq!(T::decode(bytes), Ok((&[][..], value)));
+    }
+
+    #[test]
+    fn varints() {
+        round_trip(VarInt(0), &[0]);
+        round_trip(VarInt(1), &[1]);
+        round_trip(VarInt(300), &[0xac, 0x02]);
+        let mut max = vec![0xff; 9];
+        max.push(1);
+        round_trip(VarInt(u64::MAX), &max[..]);
+        assert_eq!(VarInt(u64::MAX).encoded_len(), 10);
+        assert!(matches!(
+            VarInt::decode(&[0xff; 10]),
+            Err(nom::Err::Error(DecodeError::Malformed))
+        ));
+        assert!(matches!(
+            VarInt::decode(&[0x80]),
+            Err(nom::Err::Incomplete(_))
+        ));
+
+        round_trip(ZigZag(0), &[0]);
+        round_trip(ZigZag(-1), &[1]);
+        round_trip(ZigZag(1), &[2]);
+        round_trip(ZigZag(i64::MIN), &max[..]);
+
+        round_trip(QuicVarInt(37), &[0x25]);
+        round_trip(QuicVarInt(15293), &[0x7b, 0xbd]);
+        round_trip(QuicVarInt(494878333), &[0x9d, 0x7f, 0x3e, 0x7d]);
+        round_trip(
+            QuicVarInt(151288809941952652),
+            &[0xc2, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c],
+        );
+        assert_eq!(
+            QuicVarInt::decode(&[0x40, 0x25]),
+            Ok((&[][..], QuicVarInt(37)))
+        );
+        assert!(QuicVarInt(QuicVarInt::MAX + 1)
+            .encode(&mut Vec::new())
+            .is_err());
+        assert!(matches!(
+            QuicVarInt::decode(&[0x80, 0]),
+            Err(nom::Err::Incomplete(_))
+        ));
+
+        round_trip(BerLength::Definite(0x7f), &[0x7f]);
+        round_trip(BerLength::Definite(0x80), &[0x81, 0x80]);
+        round_trip(BerLength::Definite(0x1234), &[0x82, 0x12, 0x34]);
+        round_trip(BerLength::Indefinite, &[0x80]);
+        assert_eq!(
+            BerLength::decode(&[0x82, 0x00, 0x05]),
+            Ok((&[][..], BerLength::Definite(5)))
+        );
+        assert!(matches!(
+            BerLength::decode(&[0xff]),
+            Err(nom::Err::Error(DecodeError::Malformed))
+        ));
+    }
+}
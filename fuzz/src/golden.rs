@@ -0,0 +1,301 @@
+//! Golden file round-trip checks.
+//!
+//! Every packet in a capture is dissected and serialized again, and the
+//! result must match the captured bytes exactly. This catches dissectors
+//! that drop or rewrite data they don't understand.
+
+use sniffle::capfile::Sniffer;
+use sniffle::dissect::Session;
+use sniffle::pdu::{AnyPdu, Bytes, Pdu, PduExt};
+use sniffle::sniff::{LinkType, LinkTypeTable, SniffRaw};
+use sniffle::utils::{diff, FieldDiff};
+use sniffle::Error;
+use std::fmt;
+use std::path::Path;
+
+/// The first packet in a capture that did not survive a round trip.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    index: usize,
+    link_type: LinkType,
+    original: Vec<u8>,
+    serialized: Option<Vec<u8>>,
+    fields: Vec<FieldDiff>,
+}
+
+impl Divergence {
+    /// Zero based index of the packet in the capture.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    /// The captured packet bytes.
+    pub fn original(&self) -> &[u8] {
+        &self.original[..]
+    }
+
+    /// The re-serialized packet bytes, or `None` if the packet could not be
+    /// dissected.
+    pub fn serialized(&self) -> Option<&[u8]> {
+        self.serialized.as_deref()
+    }
+
+    /// Offset of the first byte that differs. If one buffer is a prefix of
+    /// the other, this is the length of the shorter one.
+    pub fn offset(&self) -> Option<usize> {
+        let serialized = self.serialized.as_ref()?;
+        Some(
+            self.original
+                .iter()
+                .zip(serialized.iter())
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| self.original.len().min(serialized.len())),
+        )
+    }
+
+    /// Fields that differ between the original packet and the re-serialized
+    /// packet when dissected again. This is empty when the fields match but
+    /// the bytes do not, such as when data is silently dropped.
+    pub fn fields(&self) -> &[FieldDiff] {
+        &self.fields[..]
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "packet {} (link type {}): ",
+            self.index, self.link_type.0
+        )?;
+        let Some(serialized) = &self.serialized else {
+            return write!(f, "failed to dissect {} bytes", self.original.len());
+        };
+        write!(
+            f,
+            "serialized {} bytes, captured {} bytes, first difference at offset {}",
+            serialized.len(),
+            self.original.len(),
+            self.offset().unwrap_or_default()
+        )?;
+        if let Some(field) = self.fields.first() {
+            write!(
+                f,
+                "; field {}: {} != {}",
+                field.path(),
+                field.left().unwrap_or("<missing>"),
+                field.right().unwrap_or("<missing>")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Checks every packet in a pcap or pcapng file, returning the first
+/// divergence. Packets truncated by the capture's snap length are skipped.
+pub fn check_file<P: AsRef<Path>>(path: P) -> Result<Option<Divergence>, Error> {
+    check_bytes(&std::fs::read(path)?[..])
+}
+
+/// Like `check_file`, for a capture already in memory.
+pub fn check_bytes(capture: &[u8]) -> Result<Option<Divergence>, Error> {
+    check_bytes_with_session(capture, &Session::new())
+}
+
+/// Like `check_bytes`, dissecting with a custom session.
+pub fn check_bytes_with_session(
+    capture: &[u8],
+    session: &Session,
+) -> Result<Option<Divergence>, Error> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()?
+        .block_on(async {
+            let mut sniffer = Sniffer::new_raw(std::io::Cursor::new(capture)).await?;
+            let mut index = 0;
+            while let Some(pkt) = sniffer.sniff_raw().await? {
+                if pkt.data().len() >= pkt.orig_len() {
+                    if let Some(div) = check_packet(session, index, pkt.datalink(), pkt.data()) {
+                        return Ok(Some(div));
+                    }
+                }
+                index += 1;
+            }
+            Ok(None)
+        })
+}
+
+/// Panics with a description of the first packet in the capture at `path`
+/// that does not round trip. Intended for use in a `#[test]`.
+pub fn assert_round_trip<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+    match check_file(path) {
+        Ok(None) => {}
+        Ok(Some(div)) => panic!("{}: {div}", path.display()),
+        Err(e) => panic!("failed to read capture {}: {e}", path.display()),
+    }
+}
+
+/// Dissects `data` from a packet buffer, as sniffed packets are, then
+/// forgets the captured bytes, so serializing the PDUs encodes their
+/// fields rather than copying `data`.
+fn dissect(session: &Session, link_type: LinkType, data: &[u8]) -> Option<AnyPdu> {
+    let buffer = Bytes::copy_from_slice(data);
+    let (_, mut pdu) = session
+        .with_packet_buffer(&buffer, || {
+            session.table_dissect::<LinkTypeTable>(&link_type, &buffer[..], None)
+        })
+        .ok()?;
+    pdu.forget_dissected_bytes();
+    Some(pdu)
+}
+
+fn check_packet(
+    session: &Session,
+    index: usize,
+    link_type: LinkType,
+    data: &[u8],
+) -> Option<Divergence> {
+    let mut div = Divergence {
+        index,
+        link_type,
+        original: data.to_vec(),
+        serialized: None,
+        fields: Vec::new(),
+    };
+    let Some(pdu) = dissect(session, link_type, data) else {
+        return Some(div);
+    };
+    let mut serialized = Vec::with_capacity(pdu.total_len());
+    if pdu.serialize(&mut serialized).is_err() || serialized[..] != *data {
+        if let Some(reparsed) = dissect(session, link_type, &serialized[..]) {
+            div.fields = diff(&pdu, &reparsed);
+        }
+        div.serialized = Some(serialized);
+        return Some(div);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle::dissect::{DResult, Priority};
+    use sniffle::dump::{Dump, NodeDumper};
+    use sniffle::encode::Encoder;
+    use sniffle::pdu::{BasePdu, RawPdu, TempPdu};
+    use sniffle::protos::PacketBuilderExt;
+    use sniffle::PacketBuilder;
+
+    fn pcap(link_type: LinkType, packets: &[&[u8]]) -> Vec<u8> {
+        let mut buf = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        buf.extend_from_slice(&[0; 8]);
+        buf.extend_from_slice(&65535u32.to_le_bytes());
+        buf.extend_from_slice(&u32::from(link_type.0).to_le_bytes());
+        for pkt in packets {
+            buf.extend_from_slice(&[0; 8]);
+            buf.extend_from_slice(&(pkt.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&(pkt.len() as u32).to_le_bytes());
+            buf.extend_from_slice(pkt);
+        }
+        buf
+    }
+
+    fn lossy<'a>(buf: &'a [u8], _: &Session, _: Option<TempPdu<'_>>) -> DResult<'a, RawPdu> {
+        let len = buf.len().min(2);
+        Ok((&buf[len..], RawPdu::new(buf[..len].to_vec())))
+    }
+
+    /// A one byte header whose value is encoded inverted, as a field
+    /// encoding bug would.
+    #[derive(Debug, Clone, Default)]
+    struct Inverted {
+        base: BasePdu,
+        value: u8,
+    }
+
+    impl Pdu for Inverted {
+        fn base_pdu(&self) -> &BasePdu {
+            &self.base
+        }
+
+        fn base_pdu_mut(&mut self) -> &mut BasePdu {
+            &mut self.base
+        }
+
+        fn header_len(&self) -> usize {
+            1
+        }
+
+        fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+            &self,
+            encoder: &mut W,
+        ) -> std::io::Result<()> {
+            encoder.encode(&!self.value).map(|_| ())
+        }
+
+        fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<'_, D>) -> Result<(), D::Error> {
+            dumper.add_node("Inverted", None).map(|_| ())
+        }
+    }
+
+    fn inverted<'a>(buf: &'a [u8], _: &Session, _: Option<TempPdu<'_>>) -> DResult<'a, Inverted> {
+        match buf.split_first() {
+            Some((&value, rest)) => Ok((
+                rest,
+                Inverted {
+                    base: BasePdu::default(),
+                    value,
+                },
+            )),
+            None => Err(sniffle::nom::Err::Error(
+                sniffle::dissect::DissectError::Malformed,
+            )),
+        }
+    }
+
+    #[test]
+    fn field_encoding_divergence() {
+        // The captured bytes are not reused, so the bad encoding is caught
+        let session = Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(LinkType::USER0, Priority(0), inverted)
+            .build();
+        let capture = pcap(LinkType::USER0, &[&[0x0f]]);
+        let div = check_bytes_with_session(&capture[..], &session)
+            .unwrap()
+            .unwrap();
+        assert_eq!(div.original(), &[0x0f]);
+        assert_eq!(div.serialized(), Some(&[0xf0u8][..]));
+    }
+
+    #[test]
+    fn golden_round_trip() {
+        let pkt = PacketBuilder::new()
+            .ethernet([0xff; 6].into(), [0x02, 0, 0, 0, 0, 1].into())
+            .ipv4([192, 168, 0, 1].into(), [192, 168, 0, 255].into())
+            .udp(5000, 5001)
+            .payload(vec![1, 2, 3, 4, 5])
+            .to_bytes();
+        let capture = pcap(LinkType::ETHERNET, &[&pkt[..], &pkt[..]]);
+        assert!(check_bytes(&capture[..]).unwrap().is_none());
+
+        let session = Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(LinkType::USER0, Priority(0), lossy)
+            .build();
+        let capture = pcap(LinkType::USER0, &[&[1, 2], &[1, 2, 3, 4]]);
+        let div = check_bytes_with_session(&capture[..], &session)
+            .unwrap()
+            .unwrap();
+        assert_eq!(div.index(), 1);
+        assert_eq!(div.original(), &[1, 2, 3, 4]);
+        assert_eq!(div.serialized(), Some(&[1u8, 2][..]));
+        assert_eq!(div.offset(), Some(2));
+    }
+}
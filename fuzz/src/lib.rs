@@ -0,0 +1,249 @@
+//! Fuzzing harness for sniffle dissectors.
+//!
+//! [`fuzz`] is intended to be called directly from a fuzz target, and
+//! [`assert_corpus`] replays saved crash inputs from a unit test. The
+//! [`golden`] module checks that real captures survive a round trip.
+
+pub mod golden;
+
+use sniffle::dissect::Session;
+use sniffle::pdu::{FieldMap, Pdu, PduExt};
+use sniffle::sniff::{LinkType, LinkTypeTable};
+use std::any::Any;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// An input that caused a dissector to panic.
+#[derive(Debug, Clone)]
+pub struct Crash {
+    link_type: LinkType,
+    input: Vec<u8>,
+    message: String,
+    path: Option<PathBuf>,
+}
+
+/// Feeds arbitrary bytes to link layer dissectors.
+pub struct Fuzzer {
+    session: Session,
+    link_types: Vec<LinkType>,
+}
+
+impl Crash {
+    /// The link type the input was dissected as.
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    /// The input that caused the panic.
+    pub fn input(&self) -> &[u8] {
+        &self.input[..]
+    }
+
+    /// The panic message.
+    pub fn message(&self) -> &str {
+        &self.message[..]
+    }
+
+    /// The corpus file the input was read from, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path.display())?;
+        }
+        write!(
+            f,
+            "link type {} panicked on {} byte input: {}",
+            self.link_type.0,
+            self.input.len(),
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for Crash {}
+
+impl Fuzzer {
+    /// Creates a fuzzer for every registered link layer Pdu, using a
+    /// session with the default dissectors.
+    pub fn new() -> Self {
+        let mut link_types: Vec<_> = LinkType::registered_pdus()
+            .into_iter()
+            .map(|(_, link_type)| link_type)
+            .collect();
+        link_types.sort_by_key(|link_type| link_type.0);
+        link_types.dedup();
+        Self::with_session(Session::new(), link_types)
+    }
+
+    /// Creates a fuzzer for the given link types using a custom session.
+    pub fn with_session(session: Session, link_types: Vec<LinkType>) -> Self {
+        Self {
+            session,
+            link_types,
+        }
+    }
+
+    /// The link types each input is dissected as.
+    pub fn link_types(&self) -> &[LinkType] {
+        &self.link_types[..]
+    }
+
+    /// Dissects `data` as each link type, stopping at the first crash.
+    pub fn run(&self, data: &[u8]) -> Result<(), Crash> {
+        for link_type in self.link_types.iter() {
+            self.run_link_type(*link_type, data)?;
+        }
+        Ok(())
+    }
+
+    /// Dissects `data` as a packet of `link_type`. A successfully dissected
+    /// Pdu is also dumped, serialized, and serialized again after being made
+    /// canonical, since those paths work from dissected field values.
+    pub fn run_link_type(&self, link_type: LinkType, data: &[u8]) -> Result<(), Crash> {
+        catch_unwind(AssertUnwindSafe(|| {
+            let Ok((_, mut pdu)) = self
+                .session
+                .table_dissect::<LinkTypeTable>(&link_type, data, None)
+            else {
+                return;
+            };
+            let _ = FieldMap::new(&pdu);
+            pdu.forget_dissected_bytes();
+            let mut out = Vec::with_capacity(pdu.total_len());
+            let _ = pdu.serialize(&mut out);
+            pdu.make_canonical();
+            out.clear();
+            let _ = pdu.serialize(&mut out);
+        }))
+        .map_err(|payload| Crash {
+            link_type,
+            input: data.to_vec(),
+            message: panic_message(payload),
+            path: None,
+        })
+    }
+
+    /// Runs every file in `dir` as an input, returning all crashes found.
+    pub fn replay_corpus<P: AsRef<Path>>(&self, dir: P) -> std::io::Result<Vec<Crash>> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+
+        let mut crashes = Vec::new();
+        for path in paths {
+            let data = std::fs::read(&path)?;
+            for link_type in self.link_types.iter() {
+                if let Err(mut crash) = self.run_link_type(*link_type, &data[..]) {
+                    crash.path = Some(path.clone());
+                    crashes.push(crash);
+                }
+            }
+        }
+        Ok(crashes)
+    }
+}
+
+impl Default for Fuzzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(msg) => String::from(*msg),
+            Err(_) => String::from("<non-string panic payload>"),
+        },
+    }
+}
+
+fn shared() -> &'static Fuzzer {
+    static FUZZER: OnceLock<Fuzzer> = OnceLock::new();
+    FUZZER.get_or_init(Fuzzer::new)
+}
+
+/// Fuzz target entry point. Dissects `data` as every registered link type
+/// and panics with a description of the first crash, so the fuzzing engine
+/// records the input.
+pub fn fuzz(data: &[u8]) {
+    if let Err(crash) = shared().run(data) {
+        panic!("{crash}");
+    }
+}
+
+/// Replays every file in `dir` and panics listing any crashes. Intended
+/// for use in a `#[test]` to keep fixed crash inputs from regressing.
+pub fn assert_corpus<P: AsRef<Path>>(dir: P) {
+    let dir = dir.as_ref();
+    let crashes = match shared().replay_corpus(dir) {
+        Ok(crashes) => crashes,
+        Err(e) => panic!("failed to read corpus {}: {e}", dir.display()),
+    };
+    if !crashes.is_empty() {
+        let list: Vec<_> = crashes.iter().map(|crash| crash.to_string()).collect();
+        panic!("{} crashes in corpus:\n{}", crashes.len(), list.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle::dissect::{DResult, Priority};
+    use sniffle::pdu::{RawPdu, TempPdu};
+
+    fn boom<'a>(buf: &'a [u8], _: &Session, _: Option<TempPdu<'_>>) -> DResult<'a, RawPdu> {
+        if buf.first() == Some(&0xff) {
+            panic!("boom");
+        }
+        Ok((&buf[buf.len()..], RawPdu::new(buf.to_vec())))
+    }
+
+    #[test]
+    fn corpus() {
+        assert_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/corpus"));
+    }
+
+    #[test]
+    fn fuzz_harness() {
+        let fuzzer = Fuzzer::new();
+        assert!(fuzzer.link_types().contains(&LinkType::ETHERNET));
+        for len in 0..64u8 {
+            let data: Vec<u8> = (0..len).map(|i| i.wrapping_mul(37) ^ len).collect();
+            fuzzer.run(&data[..]).unwrap();
+        }
+
+        let session = Session::builder()
+            .default_dissectors(false)
+            .dissector::<LinkTypeTable, _>(LinkType::USER0, Priority(0), boom)
+            .build();
+        let fuzzer = Fuzzer::with_session(session, vec![LinkType::USER0]);
+        fuzzer.run(&[1, 2, 3]).unwrap();
+        let crash = fuzzer.run(&[0xff, 0]).unwrap_err();
+        assert_eq!(crash.link_type(), LinkType::USER0);
+        assert_eq!(crash.input(), &[0xff, 0]);
+        assert_eq!(crash.message(), "boom");
+
+        let dir = std::env::temp_dir().join(format!("sniffle-fuzz-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ok"), [1u8, 2]).unwrap();
+        std::fs::write(dir.join("crash"), [0xffu8]).unwrap();
+        let crashes = fuzzer.replay_corpus(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].path(), Some(dir.join("crash").as_path()));
+    }
+}
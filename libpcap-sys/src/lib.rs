@@ -420,7 +420,7 @@ extern "C" {
     pub fn pcap_freealldevs(alldevs: *mut pcap_if_t);
     pub fn pcap_lib_version() -> *const libc::c_char;
 
-    #[cfg(all(not(target_os = "netbsd"), not(taget_os = "qnx")))]
+    #[cfg(all(not(target_os = "netbsd"), not(target_os = "nto")))]
     pub fn bpf_filter(
         pc: *const bpf_insn,
         pkt: *const libc::c_uchar,
@@ -24,7 +24,7 @@ fn get_ts_prec<C: Capture>(cap: &C) -> TsPrecision {
 }
 
 #[cfg(not(feature = "npcap"))]
-fn get_ts_prec<C: Capture>(cap: &C) -> TsPrecision {
+fn get_ts_prec<C: Capture>(_cap: &C) -> TsPrecision {
     TsPrecision::Micro
 }
 
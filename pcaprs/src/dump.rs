@@ -115,7 +115,7 @@ impl DumpFile {
             pcap_dump(
                 std::mem::transmute::<*mut pcap_dumper_t, *mut libc::c_uchar>(self.dumper.as_ptr()),
                 (&mut hdr) as *mut pcap_pkthdr,
-                std::mem::transmute::<_, *mut libc::c_uchar>(ptr),
+                ptr as *mut libc::c_uchar,
             );
         }
         Ok(())
@@ -18,6 +18,9 @@ impl OfflineFilter {
         )
     }
 
+    /// Compiles `filter` for the given link type and snapshot length using
+    /// a dead libpcap handle, so no device or capture file is required.
+    /// Compilation errors carry libpcap's description of the problem.
     pub fn with_netmask(
         datalink: LinkType,
         snaplen: u32,
@@ -25,28 +28,43 @@ impl OfflineFilter {
         optimize: bool,
         mask: Ipv4Address,
     ) -> Result<Self> {
+        let pcap = Pcap::open_dead(datalink, snaplen, None)?;
+        let filter = match CString::new(filter) {
+            Ok(filter) => filter,
+            Err(e) => {
+                return Err(PcapError::General(format!("{}", e)));
+            }
+        };
         let mut prog = bpf_program::default();
-        let prog_ptr = (&mut prog) as *mut bpf_program;
-        let datalink = datalink.0 as i32;
-        let snaplen = snaplen as i32;
         let optimize = if optimize { 1 } else { 0 };
-        let mask = u32::from_be_bytes(mask);
         unsafe {
-            let filter = match CString::new(filter) {
-                Ok(filter) => filter,
-                Err(e) => {
-                    return Err(PcapError::General(format!("{}", e)));
-                }
-            };
-            let c_filter =
-                std::mem::transmute::<*const u8, *const i8>(filter.as_bytes_with_nul().as_ptr());
-            if pcap_compile_nopcap(snaplen, datalink, prog_ptr, c_filter, optimize, mask) != 0 {
-                return Err(PcapError::General(String::from("invalid filter")));
+            if pcap_compile(
+                pcap.raw_handle().as_ptr(),
+                (&mut prog) as *mut bpf_program,
+                filter.as_ptr(),
+                optimize,
+                u32::from_be_bytes(mask),
+            ) != 0
+            {
+                return Err(PcapError::General(make_string(pcap_geterr(
+                    pcap.raw_handle().as_ptr(),
+                ))));
             }
         }
         Ok(Self(prog))
     }
 
+    /// Checks that `filter` is a valid filter expression for the given link
+    /// type and snapshot length.
+    pub fn validate(datalink: LinkType, snaplen: u32, filter: &str) -> Result<()> {
+        Self::new(datalink, snaplen, filter, false).map(|_| ())
+    }
+
+    /// Returns the number of BPF instructions in the compiled program.
+    pub fn instruction_count(&self) -> usize {
+        self.0.bf_len as usize
+    }
+
     pub fn filter(&mut self, packet: &[u8]) -> bool {
         self.filter_partial(packet, packet.len() as u32)
     }
@@ -70,3 +88,11 @@ impl OfflineFilter {
         }
     }
 }
+
+impl Drop for OfflineFilter {
+    fn drop(&mut self) {
+        unsafe {
+            pcap_freecode((&mut self.0) as *mut bpf_program);
+        }
+    }
+}
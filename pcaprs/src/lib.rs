@@ -16,6 +16,10 @@ mod filter;
 mod injector;
 mod link_type;
 mod pcap;
+#[cfg(feature = "npcap")]
+mod remote;
+#[cfg(windows)]
+mod send_queue;
 mod tstype;
 mod utils;
 
@@ -32,6 +36,10 @@ pub use filter::*;
 pub use injector::*;
 pub use link_type::*;
 pub use pcap::*;
+#[cfg(feature = "npcap")]
+pub use remote::*;
+#[cfg(windows)]
+pub use send_queue::*;
 pub use tstype::*;
 use utils::*;
 
@@ -61,12 +69,13 @@ pub enum PcapError {
     PromiscNotSupported(String),
     #[cfg(feature = "npcap")]
     TsTypeNotSupported,
+    InvalidConfig(String),
     IO(std::io::Error),
 }
 
 pub type Result<T> = std::result::Result<T, PcapError>;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     In,
     Out,
@@ -158,6 +167,7 @@ impl fmt::Display for PcapError {
                         .to_string_lossy()
                 )
             },
+            Self::InvalidConfig(ref msg) => write!(f, "{}", msg),
             Self::IO(ref err) => write!(f, "{}", err),
         }
     }
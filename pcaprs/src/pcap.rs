@@ -22,6 +22,7 @@ pub struct PcapConfig {
     immediate: Option<bool>,
     bufsize: Option<u32>,
     ts_prec: Option<TsPrecision>,
+    direction: Option<Direction>,
 }
 
 unsafe impl Send for Pcap {}
@@ -65,20 +66,28 @@ impl Pcap {
             let c_name =
                 std::mem::transmute::<*const u8, *const i8>(name.as_bytes_with_nul().as_ptr());
 
+            config.validate()?;
+
             let hndl = NonNull::new(pcap_create(c_name, errbuf_ptr))
                 .ok_or_else(|| PcapError::General(make_string(errbuf_ptr)))?;
 
             if let Some(snaplen) = config.snaplen {
                 if pcap_set_snaplen(hndl.as_ptr(), snaplen as i32) != 0 {
                     pcap_close(hndl.as_ptr());
-                    return Err(PcapError::Activated);
+                    return Err(PcapError::InvalidConfig(format!(
+                        "failed to set snaplen of {} on {}",
+                        snaplen, config.source
+                    )));
                 }
             }
 
             if let Some(bufsize) = config.bufsize {
                 if pcap_set_buffer_size(hndl.as_ptr(), bufsize as i32) != 0 {
                     pcap_close(hndl.as_ptr());
-                    return Err(PcapError::Activated);
+                    return Err(PcapError::InvalidConfig(format!(
+                        "failed to set buffer size of {} bytes on {}",
+                        bufsize, config.source
+                    )));
                 }
             }
 
@@ -90,7 +99,7 @@ impl Pcap {
             }
 
             if let Some(rfmon) = config.rfmon {
-                if pcap_can_set_rfmon(hndl.as_ptr()) != 0 {
+                if pcap_can_set_rfmon(hndl.as_ptr()) > 0 {
                     if pcap_set_rfmon(hndl.as_ptr(), if rfmon { 1 } else { 0 }) != 0 {
                         pcap_close(hndl.as_ptr());
                         return Err(PcapError::Activated);
@@ -139,7 +148,10 @@ impl Pcap {
             if let Some(immediate) = config.immediate {
                 if pcap_set_immediate_mode(hndl.as_ptr(), if immediate { 1 } else { 0 }) != 0 {
                     pcap_close(hndl.as_ptr());
-                    return Err(PcapError::Activated);
+                    return Err(PcapError::InvalidConfig(format!(
+                        "failed to set immediate mode on {}",
+                        config.source
+                    )));
                 }
             }
 
@@ -164,7 +176,11 @@ impl Pcap {
 
             match pcap_activate(hndl.as_ptr()) {
                 PCAP_WARNING | PCAP_ERROR => {
-                    let err = PcapError::General(make_string(pcap_geterr(hndl.as_ptr())));
+                    let err = PcapError::General(format!(
+                        "failed to activate {}: {}",
+                        config.source,
+                        make_string(pcap_geterr(hndl.as_ptr()))
+                    ));
                     pcap_close(hndl.as_ptr());
                     return Err(err);
                 }
@@ -214,10 +230,58 @@ impl Pcap {
                 }
             }
 
+            if let Some(direction) = config.direction {
+                let direction = match direction {
+                    Direction::In => PCAP_D_IN,
+                    Direction::Out => PCAP_D_OUT,
+                    Direction::InOut => PCAP_D_INOUT,
+                };
+                if pcap_setdirection(hndl.as_ptr(), direction) != 0 {
+                    let err = PcapError::General(make_string(pcap_geterr(hndl.as_ptr())));
+                    pcap_close(hndl.as_ptr());
+                    return Err(err);
+                }
+            }
+
             Ok(Pcap(hndl))
         }
     }
 
+    /// Checks whether the source in `config` can be put into monitor mode,
+    /// without activating a capture.
+    pub fn can_set_rfmon(config: &PcapConfig) -> Result<bool> {
+        unsafe {
+            let mut errbuf: [libc::c_char; PCAP_ERRBUF_SIZE] = [0; PCAP_ERRBUF_SIZE];
+            let errbuf_ptr = errbuf.as_mut_ptr();
+            let name = match CString::new(&config.source[..]) {
+                Ok(name) => name,
+                Err(e) => {
+                    return Err(PcapError::NoSuchDevice(format!("{}", e)));
+                }
+            };
+            let c_name =
+                std::mem::transmute::<*const u8, *const i8>(name.as_bytes_with_nul().as_ptr());
+
+            let hndl = NonNull::new(pcap_create(c_name, errbuf_ptr))
+                .ok_or_else(|| PcapError::General(make_string(errbuf_ptr)))?;
+
+            let res = match pcap_can_set_rfmon(hndl.as_ptr()) {
+                0 => Ok(false),
+                1 => Ok(true),
+                PCAP_ERROR_NO_SUCH_DEVICE => Err(PcapError::NoSuchDevice(make_string(
+                    pcap_geterr(hndl.as_ptr()),
+                ))),
+                PCAP_ERROR_PERM_DENIED => Err(PcapError::PermDenied(make_string(pcap_geterr(
+                    hndl.as_ptr(),
+                )))),
+                PCAP_ERROR_ACTIVATED => Err(PcapError::Activated),
+                _ => Err(PcapError::General(make_string(pcap_geterr(hndl.as_ptr())))),
+            };
+            pcap_close(hndl.as_ptr());
+            res
+        }
+    }
+
     pub fn open_live<D: AsDeviceName>(
         device: D,
         snaplen: u32,
@@ -364,6 +428,7 @@ impl Pcap {
         Ok(filt)
     }
 
+    #[cfg(feature = "tokio")]
     pub(crate) fn set_nonblocking(&mut self, enable: bool) -> Result<()> {
         let enable = if enable { 1 } else { 0 };
         unsafe {
@@ -426,6 +491,7 @@ impl PcapConfig {
             immediate: None,
             bufsize: None,
             ts_prec: None,
+            direction: None,
         }
     }
 
@@ -433,6 +499,47 @@ impl PcapConfig {
         Pcap::activate(self)
     }
 
+    /// Checks the configured values for problems that libpcap would either
+    /// reject or silently ignore, without creating a capture handle.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(snaplen) = self.snaplen {
+            if snaplen == 0 || snaplen > i32::MAX as u32 {
+                return Err(PcapError::InvalidConfig(format!(
+                    "snaplen of {} is out of range for {}",
+                    snaplen, self.source
+                )));
+            }
+        }
+
+        if let Some(bufsize) = self.bufsize {
+            if bufsize == 0 || bufsize > i32::MAX as u32 {
+                return Err(PcapError::InvalidConfig(format!(
+                    "buffer size of {} bytes is out of range for {}",
+                    bufsize, self.source
+                )));
+            }
+        }
+
+        if let Some(timeout) = self.timeout {
+            if timeout.as_millis() > libc::c_int::MAX as u128 {
+                return Err(PcapError::InvalidConfig(format!(
+                    "timeout of {}ms is out of range for {}",
+                    timeout.as_millis(),
+                    self.source
+                )));
+            }
+        }
+
+        #[cfg(not(feature = "npcap"))]
+        if self.immediate == Some(true) {
+            return Err(PcapError::InvalidConfig(String::from(
+                "immediate mode requires the npcap feature",
+            )));
+        }
+
+        Ok(())
+    }
+
     pub fn snaplen(&mut self, snaplen: u32) -> &mut Self {
         self.snaplen = Some(snaplen);
         self
@@ -472,6 +579,17 @@ impl PcapConfig {
         self.ts_prec = Some(prec);
         self
     }
+
+    /// Restricts the capture to packets received, sent, or both. The
+    /// direction is applied once the capture is activated.
+    pub fn direction(&mut self, direction: Direction) -> &mut Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    pub fn can_set_rfmon(&self) -> Result<bool> {
+        Pcap::can_set_rfmon(self)
+    }
 }
 
 impl<P: AsRef<Path>> From<P> for PcapConfig {
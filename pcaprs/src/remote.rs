@@ -0,0 +1,378 @@
+use super::*;
+use std::ptr::NonNull;
+
+#[derive(Debug, Clone, Default)]
+pub enum RemoteAuth {
+    #[default]
+    Null,
+    Password {
+        username: String,
+        password: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Sampling {
+    /// Capture every packet
+    None,
+    /// Capture one out of every N packets
+    OneEveryN(u32),
+    /// Capture the first packet after each N milliseconds
+    FirstAfterMs(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteDevice {
+    device: Device,
+    auth: RemoteAuth,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    source: String,
+    snaplen: u32,
+    flags: libc::c_int,
+    timeout: Duration,
+    auth: RemoteAuth,
+    sampling: Option<Sampling>,
+}
+
+// Keeps the C strings referenced by a pcap_rmtauth alive.
+struct RawAuth {
+    auth: pcap_rmtauth,
+    _username: Option<CString>,
+    _password: Option<CString>,
+}
+
+/// Builds an rpcap source, such as `rpcap://host:2002/eth0`.
+///
+/// IPv6 addresses are put in brackets. Without a port, libpcap uses the
+/// default rpcap port. Without a device, the source names only the host, as
+/// used to list its devices with `RemoteDevice::all`.
+pub fn rpcap_source(host: &str, port: Option<u16>, device: Option<&str>) -> String {
+    let mut source = String::from("rpcap://");
+    if host.contains(':') && !host.starts_with('[') {
+        source.push('[');
+        source.push_str(host);
+        source.push(']');
+    } else {
+        source.push_str(host);
+    }
+    if let Some(port) = port {
+        source.push_str(&format!(":{}", port));
+    }
+    source.push('/');
+    if let Some(device) = device {
+        source.push_str(device);
+    }
+    source
+}
+
+impl RemoteAuth {
+    fn to_raw(&self) -> Result<RawAuth> {
+        match self {
+            Self::Null => Ok(RawAuth {
+                auth: pcap_rmtauth {
+                    type_: RPCAP_RMTAUTH_NULL,
+                    ..Default::default()
+                },
+                _username: None,
+                _password: None,
+            }),
+            Self::Password { username, password } => {
+                let username = CString::new(&username[..])
+                    .map_err(|e| PcapError::General(format!("{}", e)))?;
+                let password = CString::new(&password[..])
+                    .map_err(|e| PcapError::General(format!("{}", e)))?;
+                Ok(RawAuth {
+                    auth: pcap_rmtauth {
+                        type_: RPCAP_RMTAUTH_PWD,
+                        username: username.as_ptr(),
+                        password: password.as_ptr(),
+                    },
+                    _username: Some(username),
+                    _password: Some(password),
+                })
+            }
+        }
+    }
+}
+
+impl RemoteDevice {
+    /// Lists the devices available from an rpcap source such as
+    /// `rpcap://host:2002/`.
+    pub fn all(source: &str, auth: RemoteAuth) -> Result<Vec<RemoteDevice>> {
+        let c_source = CString::new(source).map_err(|e| PcapError::General(format!("{}", e)))?;
+        let mut raw_auth = auth.to_raw()?;
+        let mut ret = Vec::new();
+        unsafe {
+            let mut errbuf: [libc::c_char; PCAP_ERRBUF_SIZE] = [0; PCAP_ERRBUF_SIZE];
+            let errbuf_ptr = errbuf.as_mut_ptr();
+            let mut devs: *mut pcap_if_t = std::ptr::null_mut();
+            if pcap_findalldevs_ex(
+                c_source.as_ptr(),
+                (&mut raw_auth.auth) as *mut pcap_rmtauth,
+                (&mut devs) as *mut *mut pcap_if_t,
+                errbuf_ptr,
+            ) != 0
+            {
+                return Err(PcapError::General(make_string(errbuf_ptr)));
+            }
+            let free_ptr = devs;
+            while !devs.is_null() {
+                let dev = &*devs;
+                ret.push(RemoteDevice {
+                    device: Device::from(dev),
+                    auth: auth.clone(),
+                });
+                devs = dev.next;
+            }
+            if !free_ptr.is_null() {
+                pcap_freealldevs(free_ptr);
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn into_device(self) -> Device {
+        self.device
+    }
+
+    pub fn auth(&self) -> &RemoteAuth {
+        &self.auth
+    }
+
+    /// Creates a capture configuration for this device using the same
+    /// authentication used to list it.
+    pub fn config(&self) -> RemoteConfig {
+        let mut config = RemoteConfig::create(self.device.name());
+        config.auth(self.auth.clone());
+        config
+    }
+}
+
+impl AsDeviceName for RemoteDevice {
+    fn as_device_name(&self) -> &str {
+        self.device.name()
+    }
+}
+
+impl RemoteConfig {
+    pub fn create(source: &str) -> Self {
+        Self {
+            source: String::from(source),
+            snaplen: 65535,
+            flags: 0,
+            timeout: Duration::from_millis(1000),
+            auth: RemoteAuth::Null,
+            sampling: None,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn open(&self) -> Result<Pcap> {
+        Pcap::open_remote(self)
+    }
+
+    pub fn snaplen(&mut self, snaplen: u32) -> &mut Self {
+        self.snaplen = snaplen;
+        self
+    }
+
+    pub fn promiscuous_mode(&mut self, enable: bool) -> &mut Self {
+        self.set_flag(PCAP_OPENFLAG_PROMISCUOUS, enable)
+    }
+
+    /// Transfer captured packets over UDP instead of TCP
+    pub fn udp_transfer(&mut self, enable: bool) -> &mut Self {
+        self.set_flag(PCAP_OPENFLAG_DATATX_UDP, enable)
+    }
+
+    /// Excludes the rpcap traffic itself from the remote capture
+    pub fn no_capture_rpcap(&mut self, enable: bool) -> &mut Self {
+        self.set_flag(PCAP_OPENFLAG_NOCAPTURE_RPCAP, enable)
+    }
+
+    pub fn max_responsiveness(&mut self, enable: bool) -> &mut Self {
+        self.set_flag(PCAP_OPENFLAG_MAX_RESPONSIVENESS, enable)
+    }
+
+    pub fn timeout(&mut self, dur: Duration) -> &mut Self {
+        self.timeout = dur;
+        self
+    }
+
+    pub fn auth(&mut self, auth: RemoteAuth) -> &mut Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn sampling(&mut self, sampling: Sampling) -> &mut Self {
+        self.sampling = Some(sampling);
+        self
+    }
+
+    fn set_flag(&mut self, flag: libc::c_int, enable: bool) -> &mut Self {
+        if enable {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+        self
+    }
+}
+
+impl Pcap {
+    pub fn open_remote(config: &RemoteConfig) -> Result<Pcap> {
+        let source = CString::new(&config.source[..])
+            .map_err(|e| PcapError::NoSuchDevice(format!("{}", e)))?;
+        let mut raw_auth = config.auth.to_raw()?;
+        unsafe {
+            let mut errbuf: [libc::c_char; PCAP_ERRBUF_SIZE] = [0; PCAP_ERRBUF_SIZE];
+            let errbuf_ptr = errbuf.as_mut_ptr();
+            let hndl = NonNull::new(pcap_open(
+                source.as_ptr(),
+                config.snaplen as libc::c_int,
+                config.flags,
+                config.timeout.as_millis() as libc::c_int,
+                (&mut raw_auth.auth) as *mut pcap_rmtauth,
+                errbuf_ptr,
+            ))
+            .ok_or_else(|| PcapError::General(make_string(errbuf_ptr)))?;
+            let pcap = Pcap::from_raw(hndl);
+
+            if let Some(sampling) = config.sampling {
+                let samp = pcap_setsampling(hndl.as_ptr());
+                if samp.is_null() {
+                    return Err(PcapError::General(String::from(
+                        "sampling is not supported for this source",
+                    )));
+                }
+                let (method, value) = match sampling {
+                    Sampling::None => (PCAP_SAMP_NOSAMP, 0),
+                    Sampling::OneEveryN(n) => (PCAP_SAMP_1_EVERY_N, n as libc::c_int),
+                    Sampling::FirstAfterMs(ms) => (PCAP_SAMP_FIRST_AFTER_N_MS, ms as libc::c_int),
+                };
+                (*samp).method = method;
+                (*samp).value = value;
+            }
+
+            Ok(pcap)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn source() {
+        assert_eq!(rpcap_source("host", None, None), "rpcap://host/");
+        assert_eq!(
+            rpcap_source("10.0.0.1", Some(2002), Some("eth0")),
+            "rpcap://10.0.0.1:2002/eth0"
+        );
+        assert_eq!(
+            rpcap_source("fe80::1", Some(2003), Some("eth0")),
+            "rpcap://[fe80::1]:2003/eth0"
+        );
+        assert_eq!(rpcap_source("[::1]", None, None), "rpcap://[::1]/");
+        assert_eq!(
+            rpcap_source("host", None, Some(r"\Device\NPF_Loopback")),
+            r"rpcap://host/\Device\NPF_Loopback"
+        );
+    }
+
+    #[test]
+    fn config() {
+        let mut config = RemoteConfig::create(&rpcap_source("host", None, Some("eth0")));
+        assert_eq!(config.source(), "rpcap://host/eth0");
+        assert_eq!(config.snaplen, 65535);
+        assert_eq!(config.flags, 0);
+        assert_eq!(config.timeout, Duration::from_millis(1000));
+        assert!(matches!(config.auth, RemoteAuth::Null));
+        assert!(config.sampling.is_none());
+
+        config
+            .snaplen(128)
+            .promiscuous_mode(true)
+            .udp_transfer(true)
+            .no_capture_rpcap(true)
+            .max_responsiveness(true)
+            .timeout(Duration::from_millis(10))
+            .sampling(Sampling::OneEveryN(10));
+        assert_eq!(config.snaplen, 128);
+        assert_eq!(
+            config.flags,
+            PCAP_OPENFLAG_PROMISCUOUS
+                | PCAP_OPENFLAG_DATATX_UDP
+                | PCAP_OPENFLAG_NOCAPTURE_RPCAP
+                | PCAP_OPENFLAG_MAX_RESPONSIVENESS
+        );
+        assert_eq!(config.timeout, Duration::from_millis(10));
+        assert!(matches!(config.sampling, Some(Sampling::OneEveryN(10))));
+
+        config.promiscuous_mode(false).udp_transfer(false);
+        assert_eq!(
+            config.flags,
+            PCAP_OPENFLAG_NOCAPTURE_RPCAP | PCAP_OPENFLAG_MAX_RESPONSIVENESS
+        );
+    }
+
+    #[test]
+    fn device_config_keeps_auth() {
+        let mut device = DeviceBuilder::new();
+        device.name(rpcap_source("host", None, Some("eth0")));
+        let device = RemoteDevice {
+            device: device.into_device(),
+            auth: RemoteAuth::Password {
+                username: String::from("user"),
+                password: String::from("secret"),
+            },
+        };
+        let config = device.config();
+        assert_eq!(config.source(), "rpcap://host/eth0");
+        assert!(matches!(
+            config.auth,
+            RemoteAuth::Password { ref username, ref password }
+                if username == "user" && password == "secret"
+        ));
+    }
+
+    #[test]
+    fn null_auth() {
+        let raw = RemoteAuth::default().to_raw().unwrap();
+        assert_eq!(raw.auth.type_, RPCAP_RMTAUTH_NULL);
+        assert!(raw.auth.username.is_null());
+        assert!(raw.auth.password.is_null());
+    }
+
+    #[test]
+    fn password_auth() {
+        let auth = RemoteAuth::Password {
+            username: String::from("user"),
+            password: String::from("secret"),
+        };
+        let raw = auth.to_raw().unwrap();
+        assert_eq!(raw.auth.type_, RPCAP_RMTAUTH_PWD);
+        unsafe {
+            assert_eq!(CStr::from_ptr(raw.auth.username).to_str(), Ok("user"));
+            assert_eq!(CStr::from_ptr(raw.auth.password).to_str(), Ok("secret"));
+        }
+
+        let auth = RemoteAuth::Password {
+            username: String::from("user"),
+            password: String::from("sec\0ret"),
+        };
+        assert!(matches!(auth.to_raw(), Err(PcapError::General(_))));
+    }
+}
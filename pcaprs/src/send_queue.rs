@@ -0,0 +1,172 @@
+use super::*;
+use std::ptr::NonNull;
+
+/// A queue of raw packets to be transmitted with a single call into npcap.
+///
+/// Queued packets are sent with [`Injector::transmit_queue`], which avoids
+/// a system call per packet when replaying at high rates.
+#[derive(Debug)]
+pub struct SendQueue(NonNull<pcap_send_queue>);
+
+unsafe impl Send for SendQueue {}
+
+unsafe impl Sync for SendQueue {}
+
+impl SendQueue {
+    /// Allocates a queue with `memsize` bytes of storage. Each queued packet
+    /// uses `size_of::<pcap_pkthdr>()` bytes in addition to its data.
+    pub fn new(memsize: u32) -> Result<Self> {
+        unsafe {
+            NonNull::new(pcap_sendqueue_alloc(memsize as libc::c_uint))
+                .map(Self)
+                .ok_or_else(|| PcapError::General(String::from("failed to allocate send queue")))
+        }
+    }
+
+    /// Queues a packet with the given timestamp. The timestamps are used to
+    /// space out packets when the queue is transmitted synchronized.
+    pub fn queue(&mut self, timestamp: SystemTime, pkt: &[u8]) -> Result<()> {
+        self.queue_partial(timestamp, pkt, pkt.len() as u32)
+    }
+
+    pub fn queue_partial(
+        &mut self,
+        timestamp: SystemTime,
+        pkt: &[u8],
+        orig_len: u32,
+    ) -> Result<()> {
+        let mut hdr = pcap_pkthdr::default();
+        match timestamp.duration_since(std::time::UNIX_EPOCH) {
+            Ok(dur) => {
+                hdr.ts.tv_sec = dur.as_secs() as _;
+                hdr.ts.tv_usec = dur.subsec_micros() as _;
+            }
+            Err(e) => {
+                return Err(PcapError::General(format!("{}", e)));
+            }
+        }
+        hdr.caplen = pkt.len() as u32;
+        hdr.len = orig_len;
+        unsafe {
+            if pcap_sendqueue_queue(self.0.as_ptr(), (&hdr) as *const pcap_pkthdr, pkt.as_ptr())
+                != 0
+            {
+                return Err(PcapError::General(String::from("send queue is full")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bytes of the queue's storage currently in use
+    pub fn len(&self) -> usize {
+        unsafe { self.0.as_ref().len as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total number of bytes of storage in the queue
+    pub fn capacity(&self) -> usize {
+        unsafe { self.0.as_ref().maxlen as usize }
+    }
+
+    /// Removes all queued packets, keeping the allocated storage.
+    pub fn clear(&mut self) {
+        unsafe {
+            self.0.as_mut().len = 0;
+        }
+    }
+}
+
+impl Drop for SendQueue {
+    fn drop(&mut self) {
+        unsafe {
+            pcap_sendqueue_destroy(self.0.as_ptr());
+        }
+    }
+}
+
+impl Injector {
+    /// Transmits all packets in `queue`, returning the number of bytes sent.
+    ///
+    /// If `sync` is true, packets are spaced according to their timestamps.
+    /// Otherwise they are sent as fast as possible.
+    pub fn transmit_queue(&mut self, queue: &SendQueue, sync: bool) -> Result<usize> {
+        unsafe {
+            let sent = pcap_sendqueue_transmit(
+                self.0.raw_handle().as_ptr(),
+                queue.0.as_ptr(),
+                if sync { 1 } else { 0 },
+            ) as usize;
+            if sent < queue.len() {
+                Err(PcapError::General(make_string(pcap_geterr(
+                    self.0.raw_handle().as_ptr(),
+                ))))
+            } else {
+                Ok(sent)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HDR_LEN: usize = std::mem::size_of::<pcap_pkthdr>();
+
+    #[test]
+    fn capacity() {
+        let queue = SendQueue::new(1024).unwrap();
+        assert_eq!(queue.capacity(), 1024);
+        assert_eq!(queue.len(), 0);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn len() {
+        let mut queue = SendQueue::new(1024).unwrap();
+        queue.queue(SystemTime::now(), &[0; 60]).unwrap();
+        assert_eq!(queue.len(), HDR_LEN + 60);
+        // Only the captured bytes are queued, not the original length
+        queue
+            .queue_partial(SystemTime::now(), &[0; 20], 1500)
+            .unwrap();
+        assert_eq!(queue.len(), 2 * HDR_LEN + 80);
+        assert!(!queue.is_empty());
+        assert_eq!(queue.capacity(), 1024);
+    }
+
+    #[test]
+    fn full() {
+        let memsize = (HDR_LEN + 100) as u32;
+        let mut queue = SendQueue::new(memsize).unwrap();
+        queue.queue(SystemTime::now(), &[0; 60]).unwrap();
+        // The header and data must both fit in the remaining storage
+        assert!(queue.queue(SystemTime::now(), &[0; 40]).is_err());
+        assert_eq!(queue.len(), HDR_LEN + 60);
+        // A packet that exactly fills the rest
+        queue.queue(SystemTime::now(), &[0; 40 - HDR_LEN]).unwrap();
+        assert_eq!(queue.len(), queue.capacity());
+    }
+
+    #[test]
+    fn clear() {
+        let mut queue = SendQueue::new(1024).unwrap();
+        queue.queue(SystemTime::now(), &[0; 60]).unwrap();
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.capacity(), 1024);
+        queue.queue(SystemTime::now(), &[0; 60]).unwrap();
+        assert_eq!(queue.len(), HDR_LEN + 60);
+    }
+
+    #[test]
+    fn timestamp_before_epoch() {
+        let mut queue = SendQueue::new(1024).unwrap();
+        let ts = std::time::UNIX_EPOCH - Duration::from_secs(1);
+        assert!(queue.queue(ts, &[0; 60]).is_err());
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,839 @@
+//! Border Gateway Protocol version 4 (BGP-4), RFC 4271.
+//!
+//! `Bgp` dissects the messages found in a single TCP segment. BGP messages
+//! are often split across segments, such as large updates during the
+//! initial table exchange, so `BgpStream` reassembles them from the TCP
+//! stream with a `StreamDissector`.
+
+use super::tcp::TcpPortDissectorTable;
+use crate::prelude::*;
+use sniffle_core::{Error, Ipv4Address, Ipv4Subnet, StreamDissect, StreamEvent};
+
+/// TCP port BGP speakers listen on
+pub const BGP_TCP_PORT: u16 = 179;
+
+const MARKER: [u8; 16] = [0xff; 16];
+const HEADER_LEN: u16 = 19;
+const MAX_MESSAGE_LEN: u16 = 4096;
+
+/// One or more BGP messages, as found in a single TCP segment.
+///
+/// A message split across segments is not dissected; see `BgpStream`.
+#[derive(Debug, Clone)]
+pub struct Bgp {
+    base: BasePdu,
+    messages: Vec<Message>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Open(Open),
+    Update(Update),
+    Notification(Notification),
+    Keepalive,
+    /// Route refresh, unknown message types, or messages that could not be
+    /// parsed
+    Other {
+        msg_type: MessageType,
+        body: Bytes,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageType(pub u8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Open {
+    pub version: u8,
+    /// The sender's AS number, or `AS_TRANS` (23456) if it needs four
+    /// octets. See `Open::four_octet_as`.
+    pub my_as: u16,
+    pub hold_time: u16,
+    pub bgp_id: Ipv4Address,
+    /// Optional parameters, such as capabilities, as encoded
+    pub opt_params: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub withdrawn: Vec<Ipv4Subnet>,
+    pub attributes: Vec<PathAttribute>,
+    pub nlri: Vec<Ipv4Subnet>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub code: u8,
+    pub subcode: u8,
+    pub data: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathAttribute {
+    pub flags: u8,
+    pub type_code: AttrType,
+    pub data: Bytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AttrType(pub u8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsPathSegment {
+    Set(Vec<u32>),
+    Sequence(Vec<u32>),
+}
+
+/// Path attribute flags
+pub mod attr_flags {
+    pub const OPTIONAL: u8 = 0x80;
+    pub const TRANSITIVE: u8 = 0x40;
+    pub const PARTIAL: u8 = 0x20;
+    pub const EXTENDED_LENGTH: u8 = 0x10;
+}
+
+/// Values of the ORIGIN path attribute
+pub mod origin {
+    pub const IGP: u8 = 0;
+    pub const EGP: u8 = 1;
+    pub const INCOMPLETE: u8 = 2;
+}
+
+/// Reassembles BGP messages from one direction of a TCP stream.
+///
+/// Each complete message is emitted as a `StreamEvent::Pdu` holding a
+/// `Bgp` with that one message. Data that doesn't start with a BGP header
+/// is a `Error::MalformedStream`.
+#[derive(Debug, Default)]
+pub struct BgpStream;
+
+impl MessageType {
+    pub const OPEN: Self = Self(1);
+    pub const UPDATE: Self = Self(2);
+    pub const NOTIFICATION: Self = Self(3);
+    pub const KEEPALIVE: Self = Self(4);
+    pub const ROUTE_REFRESH: Self = Self(5);
+}
+
+impl std::fmt::Display for MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::OPEN => "Open",
+            Self::UPDATE => "Update",
+            Self::NOTIFICATION => "Notification",
+            Self::KEEPALIVE => "Keepalive",
+            Self::ROUTE_REFRESH => "Route Refresh",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl AttrType {
+    pub const ORIGIN: Self = Self(1);
+    pub const AS_PATH: Self = Self(2);
+    pub const NEXT_HOP: Self = Self(3);
+    pub const MULTI_EXIT_DISC: Self = Self(4);
+    pub const LOCAL_PREF: Self = Self(5);
+    pub const ATOMIC_AGGREGATE: Self = Self(6);
+    pub const AGGREGATOR: Self = Self(7);
+    pub const COMMUNITIES: Self = Self(8);
+    pub const MP_REACH_NLRI: Self = Self(14);
+    pub const MP_UNREACH_NLRI: Self = Self(15);
+    pub const AS4_PATH: Self = Self(17);
+}
+
+impl std::fmt::Display for AttrType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::ORIGIN => "Origin",
+            Self::AS_PATH => "AS Path",
+            Self::NEXT_HOP => "Next Hop",
+            Self::MULTI_EXIT_DISC => "Multi Exit Discriminator",
+            Self::LOCAL_PREF => "Local Preference",
+            Self::ATOMIC_AGGREGATE => "Atomic Aggregate",
+            Self::AGGREGATOR => "Aggregator",
+            Self::COMMUNITIES => "Communities",
+            Self::MP_REACH_NLRI => "Multiprotocol Reachable NLRI",
+            Self::MP_UNREACH_NLRI => "Multiprotocol Unreachable NLRI",
+            Self::AS4_PATH => "AS4 Path",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+fn decode_prefix<'a>(cur: &mut DecodeCursor<'a>) -> DecodeResult<'a, Ipv4Subnet> {
+    let prefix_len: u8 = cur.decode()?;
+    if prefix_len > 32 {
+        return Err(nom::Err::Error(DissectError::Malformed));
+    }
+    let mut addr = [0u8; 4];
+    let bytes = cur.take((prefix_len as usize).div_ceil(8))?;
+    addr[..bytes.len()].copy_from_slice(bytes);
+    Ok(Ipv4Subnet::new(addr.into(), prefix_len.into()))
+}
+
+fn decode_prefixes(mut cur: DecodeCursor<'_>) -> DecodeResult<'_, Vec<Ipv4Subnet>> {
+    let mut prefixes = Vec::new();
+    while !cur.is_empty() {
+        // The prefixes are bounded by a length field, so running out of
+        // bytes is malformed rather than incomplete
+        match decode_prefix(&mut cur) {
+            Err(nom::Err::Incomplete(_)) => {
+                return Err(nom::Err::Error(DissectError::Malformed));
+            }
+            res => prefixes.push(res?),
+        }
+    }
+    Ok(prefixes)
+}
+
+fn encode_prefixes(prefixes: &[Ipv4Subnet], out: &mut Vec<u8>) {
+    for prefix in prefixes.iter() {
+        let prefix_len = prefix.prefix_len().min(32);
+        let addr = <[u8; 4]>::from(prefix.base_addr());
+        out.push(prefix_len as u8);
+        out.extend_from_slice(&addr[..(prefix_len as usize).div_ceil(8)]);
+    }
+}
+
+impl Open {
+    /// The sender's four-octet AS number, from the capability advertising
+    /// support for them (RFC 6793).
+    pub fn four_octet_as(&self) -> Option<u32> {
+        let mut params = &self.opt_params[..];
+        while let [param_type, len, rest @ ..] = params {
+            let param = rest.get(..*len as usize)?;
+            params = &rest[*len as usize..];
+            if *param_type != 2 {
+                continue;
+            }
+            let mut caps = param;
+            while let [code, len, rest @ ..] = caps {
+                let cap = rest.get(..*len as usize)?;
+                caps = &rest[*len as usize..];
+                if *code == 65 {
+                    return Some(u32::from_be_bytes(cap.try_into().ok()?));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl PathAttribute {
+    /// Creates an attribute, setting the extended length flag if the data
+    /// needs it.
+    pub fn new<B: Into<Bytes>>(type_code: AttrType, flags: u8, data: B) -> Self {
+        let data = data.into();
+        let flags = if data.len() > 0xFF {
+            flags | attr_flags::EXTENDED_LENGTH
+        } else {
+            flags
+        };
+        Self {
+            flags,
+            type_code,
+            data,
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        if self.flags & attr_flags::EXTENDED_LENGTH != 0 {
+            4 + self.data.len()
+        } else {
+            3 + self.data.len()
+        }
+    }
+
+    fn decode<'a>(cur: &mut DecodeCursor<'a>, session: &Session) -> DecodeResult<'a, Self> {
+        let flags: u8 = cur.decode()?;
+        let type_code = AttrType(cur.decode()?);
+        let len = if flags & attr_flags::EXTENDED_LENGTH != 0 {
+            cur.decode_be::<u16>()? as usize
+        } else {
+            cur.decode::<u8>()? as usize
+        };
+        Ok(Self {
+            flags,
+            type_code,
+            data: session.packet_bytes(cur.take(len)?),
+        })
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(self.flags);
+        out.push(self.type_code.0);
+        if self.flags & attr_flags::EXTENDED_LENGTH != 0 {
+            out.extend_from_slice(&(self.data.len() as u16).to_be_bytes()[..]);
+        } else {
+            out.push(self.data.len() as u8);
+        }
+        out.extend_from_slice(&self.data[..]);
+    }
+
+    fn u32_value(&self, type_code: AttrType) -> Option<u32> {
+        if self.type_code != type_code {
+            return None;
+        }
+        Some(u32::from_be_bytes(self.data[..].try_into().ok()?))
+    }
+
+    /// The value of an ORIGIN attribute. See the `origin` module.
+    pub fn origin(&self) -> Option<u8> {
+        match (self.type_code, &self.data[..]) {
+            (AttrType::ORIGIN, [origin]) => Some(*origin),
+            _ => None,
+        }
+    }
+
+    /// The segments of an AS_PATH or AS4_PATH attribute.
+    ///
+    /// AS4_PATH attributes always hold four-octet AS numbers. AS_PATH
+    /// attributes hold four-octet AS numbers only if both speakers
+    /// advertised support for them in their OPEN messages, which is given
+    /// by `four_octet`.
+    pub fn as_path(&self, four_octet: bool) -> Option<Vec<AsPathSegment>> {
+        let as_len = match self.type_code {
+            AttrType::AS_PATH if !four_octet => 2,
+            AttrType::AS_PATH | AttrType::AS4_PATH => 4,
+            _ => return None,
+        };
+        let mut data = &self.data[..];
+        let mut segments = Vec::new();
+        while let [seg_type, count, rest @ ..] = data {
+            let len = *count as usize * as_len;
+            let ases = rest
+                .get(..len)?
+                .chunks(as_len)
+                .map(|n| n.iter().fold(0u32, |n, b| (n << 8) | *b as u32))
+                .collect();
+            segments.push(match seg_type {
+                1 => AsPathSegment::Set(ases),
+                2 => AsPathSegment::Sequence(ases),
+                _ => return None,
+            });
+            data = &rest[len..];
+        }
+        if data.is_empty() {
+            Some(segments)
+        } else {
+            None
+        }
+    }
+
+    pub fn next_hop(&self) -> Option<Ipv4Address> {
+        match self.type_code {
+            AttrType::NEXT_HOP => {
+                let addr: [u8; 4] = self.data[..].try_into().ok()?;
+                Some(addr.into())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn multi_exit_disc(&self) -> Option<u32> {
+        self.u32_value(AttrType::MULTI_EXIT_DISC)
+    }
+
+    pub fn local_pref(&self) -> Option<u32> {
+        self.u32_value(AttrType::LOCAL_PREF)
+    }
+
+    /// The communities of a COMMUNITIES attribute (RFC 1997), each usually
+    /// an AS number in the upper 16 bits and a value in the lower 16 bits.
+    pub fn communities(&self) -> Option<Vec<u32>> {
+        if self.type_code != AttrType::COMMUNITIES || !self.data.len().is_multiple_of(4) {
+            return None;
+        }
+        Some(
+            self.data
+                .chunks(4)
+                .map(|n| u32::from_be_bytes([n[0], n[1], n[2], n[3]]))
+                .collect(),
+        )
+    }
+}
+
+impl Update {
+    pub fn attribute(&self, type_code: AttrType) -> Option<&PathAttribute> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.type_code == type_code)
+    }
+}
+
+impl Message {
+    pub fn msg_type(&self) -> MessageType {
+        match self {
+            Self::Open(_) => MessageType::OPEN,
+            Self::Update(_) => MessageType::UPDATE,
+            Self::Notification(_) => MessageType::NOTIFICATION,
+            Self::Keepalive => MessageType::KEEPALIVE,
+            Self::Other { msg_type, .. } => *msg_type,
+        }
+    }
+
+    /// Dissects one message, header included.
+    pub fn dissect<'a>(buf: &'a [u8], session: &Session) -> DResult<'a, Self> {
+        decode_with(|cur| {
+            if cur.take(16)? != MARKER {
+                return Err(nom::Err::Error(DissectError::Malformed));
+            }
+            let len: u16 = cur.decode_be()?;
+            if !(HEADER_LEN..=MAX_MESSAGE_LEN).contains(&len) {
+                return Err(nom::Err::Error(DissectError::Malformed));
+            }
+            let msg_type = MessageType(cur.decode()?);
+            let mut body = cur.sub((len - HEADER_LEN).into())?;
+            let raw = body.remaining();
+            match Self::decode_body(msg_type, &mut body, session)
+                .and_then(|msg| body.finish().map(|_| msg))
+            {
+                Ok(msg) => Ok(msg),
+                Err(nom::Err::Failure(e)) => Err(nom::Err::Failure(e)),
+                Err(_) => Ok(Self::Other {
+                    msg_type,
+                    body: session.packet_bytes(raw),
+                }),
+            }
+        })(buf)
+    }
+
+    fn decode_body<'a>(
+        msg_type: MessageType,
+        body: &mut DecodeCursor<'a>,
+        session: &Session,
+    ) -> DecodeResult<'a, Self> {
+        Ok(match msg_type {
+            MessageType::OPEN => {
+                let version = body.decode()?;
+                let my_as = body.decode_be()?;
+                let hold_time = body.decode_be()?;
+                let bgp_id = body.decode()?;
+                let opt_len: u8 = body.decode()?;
+                Self::Open(Open {
+                    version,
+                    my_as,
+                    hold_time,
+                    bgp_id,
+                    opt_params: session.packet_bytes(body.take(opt_len.into())?),
+                })
+            }
+            MessageType::UPDATE => {
+                let withdrawn_len: u16 = body.decode_be()?;
+                let withdrawn = decode_prefixes(body.sub(withdrawn_len.into())?)?;
+                let attrs_len: u16 = body.decode_be()?;
+                let mut attrs = body.sub(attrs_len.into())?;
+                let mut attributes = Vec::new();
+                while !attrs.is_empty() {
+                    attributes.push(PathAttribute::decode(&mut attrs, session)?);
+                }
+                let nlri = decode_prefixes(DecodeCursor::new(body.rest()))?;
+                Self::Update(Update {
+                    withdrawn,
+                    attributes,
+                    nlri,
+                })
+            }
+            MessageType::NOTIFICATION => Self::Notification(Notification {
+                code: body.decode()?,
+                subcode: body.decode()?,
+                data: session.packet_bytes(body.rest()),
+            }),
+            MessageType::KEEPALIVE => Self::Keepalive,
+            msg_type => Self::Other {
+                msg_type,
+                body: session.packet_bytes(body.rest()),
+            },
+        })
+    }
+
+    fn encode_body(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Self::Open(open) => {
+                out.push(open.version);
+                out.extend_from_slice(&open.my_as.to_be_bytes()[..]);
+                out.extend_from_slice(&open.hold_time.to_be_bytes()[..]);
+                out.extend_from_slice(&<[u8; 4]>::from(open.bgp_id)[..]);
+                out.push(open.opt_params.len() as u8);
+                out.extend_from_slice(&open.opt_params[..]);
+            }
+            Self::Update(update) => {
+                let mut withdrawn = Vec::new();
+                encode_prefixes(&update.withdrawn[..], &mut withdrawn);
+                out.extend_from_slice(&(withdrawn.len() as u16).to_be_bytes()[..]);
+                out.extend_from_slice(&withdrawn[..]);
+                let attrs_len: usize = update
+                    .attributes
+                    .iter()
+                    .map(|attr| attr.encoded_len())
+                    .sum();
+                out.extend_from_slice(&(attrs_len as u16).to_be_bytes()[..]);
+                for attr in update.attributes.iter() {
+                    attr.encode(&mut out);
+                }
+                encode_prefixes(&update.nlri[..], &mut out);
+            }
+            Self::Notification(notif) => {
+                out.push(notif.code);
+                out.push(notif.subcode);
+                out.extend_from_slice(&notif.data[..]);
+            }
+            Self::Keepalive => {}
+            Self::Other { body, .. } => out.extend_from_slice(&body[..]),
+        }
+        out
+    }
+}
+
+impl Bgp {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn with_message(msg: Message) -> Self {
+        Self {
+            base: BasePdu::default(),
+            messages: vec![msg],
+        }
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages[..]
+    }
+
+    pub fn messages_mut(&mut self) -> &mut Vec<Message> {
+        self.base.mark_dirty();
+        &mut self.messages
+    }
+
+    pub fn open(&self) -> Option<&Open> {
+        self.messages.iter().find_map(|msg| match msg {
+            Message::Open(open) => Some(open),
+            _ => None,
+        })
+    }
+
+    pub fn updates(&self) -> impl Iterator<Item = &Update> + '_ {
+        self.messages.iter().filter_map(|msg| match msg {
+            Message::Update(update) => Some(update),
+            _ => None,
+        })
+    }
+}
+
+impl Default for Bgp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dissect for Bgp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (mut buf, first) = Message::dissect(buf, session)?;
+        let mut messages = vec![first];
+        while let Ok((rem, msg)) = Message::dissect(buf, session) {
+            messages.push(msg);
+            buf = rem;
+        }
+        Ok((
+            buf,
+            Self {
+                base: BasePdu::default(),
+                messages,
+            },
+        ))
+    }
+}
+
+impl Pdu for Bgp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|msg| HEADER_LEN as usize + msg.encode_body().len())
+            .sum()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        for msg in self.messages.iter() {
+            let body = msg.encode_body();
+            encoder
+                .encode(&MARKER[..])?
+                .encode_be(&(HEADER_LEN + body.len() as u16))?
+                .encode(&msg.msg_type().0)?
+                .encode(&body[..])?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("BGP", None)?;
+        let mut offset = 0;
+        for msg in self.messages.iter() {
+            let body = msg.encode_body();
+            let len = HEADER_LEN as usize + body.len();
+            let msg_type = msg.msg_type().to_string();
+            let mut msg_node = node
+                .byte_range(offset, len)
+                .add_node("Message", Some(&msg_type[..]))?;
+            msg_node.byte_range(offset + 16, 2).add_field(
+                "Length",
+                DumpValue::UInt(len as u64),
+                None,
+            )?;
+            msg_node.byte_range(offset + 18, 1).add_field(
+                "Type",
+                DumpValue::UInt(msg.msg_type().0.into()),
+                Some(&msg_type[..]),
+            )?;
+            match msg {
+                Message::Open(open) => {
+                    msg_node.add_field("Version", DumpValue::UInt(open.version.into()), None)?;
+                    msg_node.add_field("My AS", DumpValue::UInt(open.my_as.into()), None)?;
+                    msg_node.add_field(
+                        "Hold Time",
+                        DumpValue::UInt(open.hold_time.into()),
+                        None,
+                    )?;
+                    msg_node.add_field(
+                        "BGP Identifier",
+                        DumpValue::Bytes(&open.bgp_id[..]),
+                        Some(&open.bgp_id.to_string()[..]),
+                    )?;
+                    if let Some(asn) = open.four_octet_as() {
+                        msg_node.add_field("Four-Octet AS", DumpValue::UInt(asn.into()), None)?;
+                    }
+                    msg_node.add_field(
+                        "Optional Parameters",
+                        DumpValue::Bytes(&open.opt_params[..]),
+                        None,
+                    )?;
+                }
+                Message::Update(update) => {
+                    for prefix in update.withdrawn.iter() {
+                        msg_node.add_field(
+                            "Withdrawn Route",
+                            DumpValue::Bytes(&prefix.base_addr()[..]),
+                            Some(&prefix.to_string()[..]),
+                        )?;
+                    }
+                    for attr in update.attributes.iter() {
+                        let descr = attr.type_code.to_string();
+                        let mut attr_node =
+                            msg_node.add_node("Path Attribute", Some(&descr[..]))?;
+                        attr_node.add_field("Flags", DumpValue::UInt(attr.flags.into()), None)?;
+                        attr_node.add_field(
+                            "Type Code",
+                            DumpValue::UInt(attr.type_code.0.into()),
+                            Some(&descr[..]),
+                        )?;
+                        attr_node.add_field("Value", DumpValue::Bytes(&attr.data[..]), None)?;
+                    }
+                    for prefix in update.nlri.iter() {
+                        msg_node.add_field(
+                            "NLRI",
+                            DumpValue::Bytes(&prefix.base_addr()[..]),
+                            Some(&prefix.to_string()[..]),
+                        )?;
+                    }
+                }
+                Message::Notification(notif) => {
+                    msg_node.add_field("Error Code", DumpValue::UInt(notif.code.into()), None)?;
+                    msg_node.add_field(
+                        "Error Subcode",
+                        DumpValue::UInt(notif.subcode.into()),
+                        None,
+                    )?;
+                    msg_node.add_field("Data", DumpValue::Bytes(&notif.data[..]), None)?;
+                }
+                Message::Keepalive => {}
+                Message::Other { body, .. } => {
+                    msg_node.add_field("Body", DumpValue::Bytes(&body[..]), None)?;
+                }
+            }
+            offset += len;
+        }
+        Ok(())
+    }
+}
+
+impl BgpStream {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StreamDissect for BgpStream {
+    fn feed(
+        &mut self,
+        data: &[u8],
+        session: &Session,
+        events: &mut dyn FnMut(StreamEvent<'_>),
+    ) -> Result<usize, Error> {
+        if data.len() < HEADER_LEN as usize {
+            return Ok(0);
+        }
+        let len = u16::from_be_bytes([data[16], data[17]]);
+        if data[..16] != MARKER || !(HEADER_LEN..=MAX_MESSAGE_LEN).contains(&len) {
+            return Err(Error::MalformedStream);
+        }
+        let len = len as usize;
+        if data.len() < len {
+            return Ok(0);
+        }
+        let (_, msg) =
+            Message::dissect(&data[..len], session).map_err(|_| Error::MalformedStream)?;
+        events(StreamEvent::Pdu(AnyPdu::new(Bgp::with_message(msg))));
+        Ok(len)
+    }
+}
+
+register_dissector!(
+    bgp,
+    TcpPortDissectorTable,
+    BGP_TCP_PORT,
+    Priority(0),
+    Bgp::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tcp::Tcp;
+    use sniffle_core::StreamDissector;
+
+    fn update() -> Update {
+        Update {
+            withdrawn: vec![Ipv4Subnet::new([192, 0, 2, 0].into(), 24)],
+            attributes: vec![
+                PathAttribute::new(AttrType::ORIGIN, attr_flags::TRANSITIVE, vec![origin::IGP]),
+                PathAttribute::new(
+                    AttrType::AS_PATH,
+                    attr_flags::TRANSITIVE,
+                    vec![2, 2, 0, 0, 0xfd, 0xe8, 0, 1, 0, 0],
+                ),
+                PathAttribute::new(
+                    AttrType::NEXT_HOP,
+                    attr_flags::TRANSITIVE,
+                    vec![10, 0, 0, 1],
+                ),
+                PathAttribute::new(
+                    AttrType::COMMUNITIES,
+                    attr_flags::OPTIONAL | attr_flags::TRANSITIVE,
+                    vec![0xfd, 0xe8, 0, 100],
+                ),
+            ],
+            nlri: vec![
+                Ipv4Subnet::new([198, 51, 100, 0].into(), 24),
+                Ipv4Subnet::new([10, 128, 0, 0].into(), 9),
+            ],
+        }
+    }
+
+    #[test]
+    fn bgp_messages() {
+        let open = Open {
+            version: 4,
+            my_as: 23456,
+            hold_time: 90,
+            bgp_id: [1, 1, 1, 1].into(),
+            opt_params: Bytes::from_static(&[2, 6, 65, 4, 0, 1, 0x11, 0x70]),
+        };
+        let mut bgp = Bgp::new();
+        bgp.messages_mut().push(Message::Open(open.clone()));
+        bgp.messages_mut().push(Message::Keepalive);
+        bgp.messages_mut().push(Message::Update(update()));
+
+        let mut tcp = Tcp::with_ports(50000, BGP_TCP_PORT);
+        tcp.set_inner_pdu(bgp);
+        tcp.make_canonical();
+        let mut buf = Vec::new();
+        tcp.serialize(&mut buf).unwrap();
+
+        let session = Session::new();
+        let (_, tcp) = Tcp::dissect(&buf[..], &session, None).unwrap();
+        let bgp = tcp.find::<Bgp>().unwrap();
+        assert_eq!(bgp.messages().len(), 3);
+        assert_eq!(bgp.open(), Some(&open));
+        assert_eq!(open.four_octet_as(), Some(70000));
+
+        let update = bgp.updates().next().unwrap();
+        assert_eq!(update, &self::update());
+        assert_eq!(update.nlri[1].to_string(), "10.128.0.0/9");
+        let origin = update.attribute(AttrType::ORIGIN).unwrap();
+        assert_eq!(origin.origin(), Some(origin::IGP));
+        let as_path = update.attribute(AttrType::AS_PATH).unwrap();
+        assert_eq!(
+            as_path.as_path(true),
+            Some(vec![AsPathSegment::Sequence(vec![65000, 65536])])
+        );
+        // The same bytes don't parse with two-octet AS numbers
+        assert_eq!(as_path.as_path(false), None);
+        let next_hop = update.attribute(AttrType::NEXT_HOP).unwrap();
+        assert_eq!(next_hop.next_hop(), Some([10, 0, 0, 1].into()));
+        let communities = update.attribute(AttrType::COMMUNITIES).unwrap();
+        assert_eq!(communities.communities(), Some(vec![0xfde8_0064]));
+
+        let mut out = Vec::new();
+        tcp.serialize(&mut out).unwrap();
+        assert_eq!(out, buf);
+    }
+
+    #[test]
+    fn bgp_stream() {
+        let mut bgp = Bgp::new();
+        bgp.messages_mut().push(Message::Update(update()));
+        bgp.messages_mut().push(Message::Notification(Notification {
+            code: 6,
+            subcode: 2,
+            data: Bytes::new(),
+        }));
+        let mut buf = Vec::new();
+        bgp.serialize(&mut buf).unwrap();
+
+        // A message split across segments needs reassembly
+        let session = Session::new();
+        assert!(Bgp::dissect(&buf[..30], &session, None).is_err());
+
+        let mut stream = StreamDissector::new(BgpStream::new());
+        let mut messages = Vec::new();
+        for segment in buf.chunks(7) {
+            stream
+                .push(segment, &session, |event| {
+                    if let StreamEvent::Pdu(pdu) = event {
+                        let bgp = pdu.downcast_ref::<Bgp>().unwrap();
+                        messages.extend(bgp.messages().iter().cloned());
+                    }
+                })
+                .unwrap();
+        }
+        stream.finish(&session, |_| ()).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], Message::Update(update()));
+        assert_eq!(messages[1].msg_type(), MessageType::NOTIFICATION);
+
+        let mut stream = StreamDissector::new(BgpStream::new());
+        assert!(stream.push(&[0u8; 19][..], &session, |_| ()).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Protocol shorthands for `PacketBuilder`.
+
+use sniffle_core::PacketBuilder;
+
+#[cfg(feature = "ethernet_ii")]
+use super::ethernet_ii::EthernetII;
+#[cfg(feature = "ipv4")]
+use super::ipv4::Ipv4;
+#[cfg(feature = "tcp")]
+use super::tcp::Tcp;
+#[cfg(feature = "udp")]
+use super::udp::Udp;
+#[cfg(feature = "ipv4")]
+use sniffle_core::Ipv4Address;
+#[cfg(feature = "ethernet_ii")]
+use sniffle_core::MacAddress;
+
+/// Adds a layer for each protocol to `PacketBuilder`.
+///
+/// ```
+/// # use sniffle_core::{Packet, PduExt};
+/// # use sniffle_protos::{builder::PacketBuilderExt, udp::Udp};
+/// let pkt = Packet::builder()
+///     .ethernet([0xff; 6].into(), [0x02, 0, 0, 0, 0, 1].into())
+///     .ipv4([192, 168, 0, 1].into(), [192, 168, 0, 255].into())
+///     .udp(5000, 5001)
+///     .payload(&b"hello"[..])
+///     .build();
+/// assert_eq!(pkt.find::<Udp>().unwrap().length(), 13);
+/// ```
+pub trait PacketBuilderExt: Sized {
+    #[cfg(feature = "ethernet_ii")]
+    fn ethernet(self, dst: MacAddress, src: MacAddress) -> Self;
+
+    #[cfg(feature = "ipv4")]
+    fn ipv4(self, src: Ipv4Address, dst: Ipv4Address) -> Self;
+
+    #[cfg(feature = "udp")]
+    fn udp(self, src_port: u16, dst_port: u16) -> Self;
+
+    #[cfg(feature = "tcp")]
+    fn tcp(self, src_port: u16, dst_port: u16) -> Self;
+}
+
+impl PacketBuilderExt for PacketBuilder {
+    #[cfg(feature = "ethernet_ii")]
+    fn ethernet(self, dst: MacAddress, src: MacAddress) -> Self {
+        self.push(EthernetII::with_addresses(dst, src))
+    }
+
+    #[cfg(feature = "ipv4")]
+    fn ipv4(self, src: Ipv4Address, dst: Ipv4Address) -> Self {
+        self.push(Ipv4::with_addresses(src, dst))
+    }
+
+    #[cfg(feature = "udp")]
+    fn udp(self, src_port: u16, dst_port: u16) -> Self {
+        self.push(Udp::with_ports(src_port, dst_port))
+    }
+
+    #[cfg(feature = "tcp")]
+    fn tcp(self, src_port: u16, dst_port: u16) -> Self {
+        self.push(Tcp::with_ports(src_port, dst_port))
+    }
+}
+
+#[cfg(all(test, feature = "udp"))]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+    use crate::{ethernet_ii::EthernetII, ipv4::Ipv4, udp::Udp};
+
+    #[test]
+    fn build_udp() {
+        let buf = PacketBuilder::new()
+            .ethernet([0xff; 6].into(), [0x02, 0, 0, 0, 0, 1].into())
+            .ipv4([192, 168, 0, 1].into(), [192, 168, 0, 255].into())
+            .udp(5000, 5001)
+            .payload(vec![1, 2, 3, 4, 5])
+            .to_bytes();
+        assert_eq!(buf.len(), 60);
+
+        let session = Session::new();
+        let (_, eth) = EthernetII::dissect(&buf[..], &session, None).unwrap();
+        let ipv4 = eth.find::<Ipv4>().unwrap();
+        assert_eq!(u8::from(ipv4.version()), 4);
+        assert_eq!(ipv4.totlen(), 33);
+        let udp = eth.find::<Udp>().unwrap();
+        assert_eq!(udp.length(), 13);
+        assert_eq!(udp.checksum_valid(&ipv4.pseudo_header()), Some(true));
+        assert_eq!(udp.inner_pdu().unwrap().total_len(), 5);
+    }
+}
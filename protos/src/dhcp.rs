@@ -0,0 +1,852 @@
+use super::udp::UdpPortDissectorTable;
+use crate::prelude::*;
+use nom::{
+    bytes::complete::take,
+    combinator::{map, rest},
+    sequence::tuple,
+};
+use sniffle_core::{Ipv4Address, MacAddress};
+
+/// UDP port DHCP servers listen on
+pub const DHCP_SERVER_PORT: u16 = 67;
+/// UDP port DHCP clients listen on
+pub const DHCP_CLIENT_PORT: u16 = 68;
+
+/// Value of the magic cookie preceding the options
+pub const MAGIC_COOKIE: u32 = 0x63825363;
+
+/// Dynamic Host Configuration Protocol message, as defined by RFC 2131
+#[derive(Debug, Clone)]
+pub struct Dhcp {
+    base: BasePdu,
+    op: u8,
+    htype: u8,
+    hlen: u8,
+    hops: u8,
+    xid: u32,
+    secs: u16,
+    flags: u16,
+    ciaddr: Ipv4Address,
+    yiaddr: Ipv4Address,
+    siaddr: Ipv4Address,
+    giaddr: Ipv4Address,
+    chaddr: [u8; 16],
+    sname: [u8; 64],
+    file: [u8; 128],
+    magic: u32,
+    opts: Vec<DhcpOption>,
+    padding: Bytes,
+}
+
+/// A DHCP option. The pad and end options are encoded without a length or
+/// data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpOption {
+    code: u8,
+    data: Bytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageType(pub u8);
+
+/// DHCP option codes
+pub mod opt_code {
+    pub const PAD: u8 = 0;
+    pub const SUBNET_MASK: u8 = 1;
+    pub const ROUTER: u8 = 3;
+    pub const DOMAIN_NAME_SERVER: u8 = 6;
+    pub const HOST_NAME: u8 = 12;
+    pub const DOMAIN_NAME: u8 = 15;
+    pub const REQUESTED_IP_ADDRESS: u8 = 50;
+    pub const IP_ADDRESS_LEASE_TIME: u8 = 51;
+    pub const MESSAGE_TYPE: u8 = 53;
+    pub const SERVER_IDENTIFIER: u8 = 54;
+    pub const PARAMETER_REQUEST_LIST: u8 = 55;
+    pub const MESSAGE: u8 = 56;
+    pub const MAXIMUM_MESSAGE_SIZE: u8 = 57;
+    pub const RENEWAL_TIME: u8 = 58;
+    pub const REBINDING_TIME: u8 = 59;
+    pub const VENDOR_CLASS_IDENTIFIER: u8 = 60;
+    pub const CLIENT_IDENTIFIER: u8 = 61;
+    pub const RELAY_AGENT_INFORMATION: u8 = 82;
+    pub const END: u8 = 255;
+}
+
+/// Relay agent information sub-option codes, as defined by RFC 3046
+pub mod relay_sub_opt {
+    pub const CIRCUIT_ID: u8 = 1;
+    pub const REMOTE_ID: u8 = 2;
+}
+
+const OP_REQUEST: u8 = 1;
+const OP_REPLY: u8 = 2;
+const FLAG_BROADCAST: u16 = 0x8000;
+
+impl MessageType {
+    pub const DISCOVER: Self = Self(1);
+    pub const OFFER: Self = Self(2);
+    pub const REQUEST: Self = Self(3);
+    pub const DECLINE: Self = Self(4);
+    pub const ACK: Self = Self(5);
+    pub const NAK: Self = Self(6);
+    pub const RELEASE: Self = Self(7);
+    pub const INFORM: Self = Self(8);
+}
+
+impl std::fmt::Display for MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::DISCOVER => "Discover",
+            Self::OFFER => "Offer",
+            Self::REQUEST => "Request",
+            Self::DECLINE => "Decline",
+            Self::ACK => "ACK",
+            Self::NAK => "NAK",
+            Self::RELEASE => "Release",
+            Self::INFORM => "Inform",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl DhcpOption {
+    pub fn new<B: Into<Bytes>>(code: u8, data: B) -> Self {
+        Self {
+            code,
+            data: data.into(),
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    pub fn code_mut(&mut self) -> &mut u8 {
+        &mut self.code
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    pub fn set_data<B: Into<Bytes>>(&mut self, data: B) {
+        self.data = data.into();
+    }
+
+    fn has_len(&self) -> bool {
+        self.code != opt_code::PAD && self.code != opt_code::END
+    }
+
+    fn encoded_len(&self) -> usize {
+        if self.has_len() {
+            2 + self.data.len()
+        } else {
+            1
+        }
+    }
+
+    fn decode<'a>(buf: &'a [u8], session: &Session) -> DResult<'a, Self> {
+        let (buf, code) = u8::decode(buf)?;
+        if code == opt_code::PAD || code == opt_code::END {
+            return Ok((
+                buf,
+                Self {
+                    code,
+                    data: Bytes::new(),
+                },
+            ));
+        }
+        let (buf, len) = u8::decode(buf)?;
+        let (buf, data) = take(len as usize)(buf)?;
+        Ok((
+            buf,
+            Self {
+                code,
+                data: session.packet_bytes(data),
+            },
+        ))
+    }
+}
+
+/// Splits a buffer of type-length-value encoded sub-options, each with a one
+/// byte code and length. Stops at the first truncated sub-option.
+fn sub_options(mut buf: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut subs = Vec::new();
+    while buf.len() >= 2 {
+        let len = buf[1] as usize;
+        if buf.len() < 2 + len {
+            break;
+        }
+        subs.push((buf[0], &buf[2..2 + len]));
+        buf = &buf[2 + len..];
+    }
+    subs
+}
+
+fn ipv4_list(data: &[u8]) -> Vec<Ipv4Address> {
+    data.chunks_exact(4)
+        .map(|addr| Ipv4Address::from([addr[0], addr[1], addr[2], addr[3]]))
+        .collect()
+}
+
+impl Dhcp {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            op: OP_REQUEST,
+            htype: 1,
+            hlen: 6,
+            hops: 0,
+            xid: 0,
+            secs: 0,
+            flags: 0,
+            ciaddr: Ipv4Address::default(),
+            yiaddr: Ipv4Address::default(),
+            siaddr: Ipv4Address::default(),
+            giaddr: Ipv4Address::default(),
+            chaddr: [0u8; 16],
+            sname: [0u8; 64],
+            file: [0u8; 128],
+            magic: MAGIC_COOKIE,
+            opts: Vec::new(),
+            padding: Bytes::new(),
+        }
+    }
+
+    /// Operation code. 1 for requests (client to server) and 2 for replies.
+    pub fn op(&self) -> u8 {
+        self.op
+    }
+
+    pub fn op_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.op
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.op == OP_REQUEST
+    }
+
+    pub fn is_reply(&self) -> bool {
+        self.op == OP_REPLY
+    }
+
+    /// Hardware address type, using ARP hardware type values
+    pub fn htype(&self) -> u8 {
+        self.htype
+    }
+
+    pub fn htype_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.htype
+    }
+
+    /// Hardware address length
+    pub fn hlen(&self) -> u8 {
+        self.hlen
+    }
+
+    pub fn hlen_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.hlen
+    }
+
+    pub fn hops(&self) -> u8 {
+        self.hops
+    }
+
+    pub fn hops_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.hops
+    }
+
+    /// Transaction ID
+    pub fn xid(&self) -> u32 {
+        self.xid
+    }
+
+    pub fn xid_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.xid
+    }
+
+    pub fn secs(&self) -> u16 {
+        self.secs
+    }
+
+    pub fn secs_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.secs
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn flags_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.flags
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        (self.flags & FLAG_BROADCAST) != 0
+    }
+
+    /// Client IP address
+    pub fn ciaddr(&self) -> Ipv4Address {
+        self.ciaddr
+    }
+
+    pub fn ciaddr_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
+        &mut self.ciaddr
+    }
+
+    /// "Your" (client) IP address, as assigned by the server
+    pub fn yiaddr(&self) -> Ipv4Address {
+        self.yiaddr
+    }
+
+    pub fn yiaddr_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
+        &mut self.yiaddr
+    }
+
+    /// IP address of the next server to use in bootstrap
+    pub fn siaddr(&self) -> Ipv4Address {
+        self.siaddr
+    }
+
+    pub fn siaddr_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
+        &mut self.siaddr
+    }
+
+    /// Relay agent IP address
+    pub fn giaddr(&self) -> Ipv4Address {
+        self.giaddr
+    }
+
+    pub fn giaddr_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
+        &mut self.giaddr
+    }
+
+    /// Client hardware address field, including any zero padding
+    pub fn chaddr_raw(&self) -> &[u8; 16] {
+        &self.chaddr
+    }
+
+    pub fn chaddr_raw_mut(&mut self) -> &mut [u8; 16] {
+        self.base.mark_dirty();
+        &mut self.chaddr
+    }
+
+    /// Client hardware address, truncated to `hlen` bytes
+    pub fn chaddr(&self) -> &[u8] {
+        &self.chaddr[..std::cmp::min(self.hlen as usize, 16)]
+    }
+
+    /// Client MAC address, if the hardware type is Ethernet
+    pub fn client_mac(&self) -> Option<MacAddress> {
+        if self.htype == 1 && self.hlen == 6 {
+            let mut mac = [0u8; 6];
+            mac.copy_from_slice(&self.chaddr[..6]);
+            Some(MacAddress::from(mac))
+        } else {
+            None
+        }
+    }
+
+    /// Server host name field, including any zero padding
+    pub fn sname(&self) -> &[u8; 64] {
+        &self.sname
+    }
+
+    pub fn sname_mut(&mut self) -> &mut [u8; 64] {
+        self.base.mark_dirty();
+        &mut self.sname
+    }
+
+    /// Boot file name field, including any zero padding
+    pub fn file(&self) -> &[u8; 128] {
+        &self.file
+    }
+
+    pub fn file_mut(&mut self) -> &mut [u8; 128] {
+        self.base.mark_dirty();
+        &mut self.file
+    }
+
+    pub fn magic_cookie(&self) -> u32 {
+        self.magic
+    }
+
+    pub fn magic_cookie_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.magic
+    }
+
+    pub fn options(&self) -> &[DhcpOption] {
+        &self.opts[..]
+    }
+
+    pub fn options_mut(&mut self) -> &mut Vec<DhcpOption> {
+        self.base.mark_dirty();
+        &mut self.opts
+    }
+
+    /// Returns the first option with the specified code
+    pub fn option(&self, code: u8) -> Option<&DhcpOption> {
+        self.opts.iter().find(|opt| opt.code == code)
+    }
+
+    /// Replaces the first option with the specified code, or inserts it
+    /// before the end option if there is none.
+    pub fn set_option<B: Into<Bytes>>(&mut self, code: u8, data: B) {
+        self.base.mark_dirty();
+        let data = data.into();
+        match self.opts.iter_mut().find(|opt| opt.code == code) {
+            Some(opt) => opt.data = data,
+            None => {
+                let pos = self
+                    .opts
+                    .iter()
+                    .position(|opt| opt.code == opt_code::END)
+                    .unwrap_or(self.opts.len());
+                self.opts.insert(pos, DhcpOption::new(code, data));
+            }
+        }
+    }
+
+    /// Bytes following the end option
+    pub fn padding(&self) -> &[u8] {
+        &self.padding[..]
+    }
+
+    pub fn set_padding<B: Into<Bytes>>(&mut self, padding: B) {
+        self.base.mark_dirty();
+        self.padding = padding.into();
+    }
+
+    fn option_data(&self, code: u8) -> Option<&[u8]> {
+        self.option(code).map(|opt| &opt.data[..])
+    }
+
+    fn option_addr(&self, code: u8) -> Option<Ipv4Address> {
+        self.option_data(code)
+            .filter(|data| data.len() == 4)
+            .map(|data| Ipv4Address::from([data[0], data[1], data[2], data[3]]))
+    }
+
+    fn option_u32(&self, code: u8) -> Option<u32> {
+        self.option_data(code)
+            .filter(|data| data.len() == 4)
+            .map(|data| u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
+    pub fn message_type(&self) -> Option<MessageType> {
+        self.option_data(opt_code::MESSAGE_TYPE)
+            .filter(|data| data.len() == 1)
+            .map(|data| MessageType(data[0]))
+    }
+
+    /// Option codes listed in the parameter request list option
+    pub fn requested_options(&self) -> Option<&[u8]> {
+        self.option_data(opt_code::PARAMETER_REQUEST_LIST)
+    }
+
+    /// Client identifier option data, including the type byte
+    pub fn client_id(&self) -> Option<&[u8]> {
+        self.option_data(opt_code::CLIENT_IDENTIFIER)
+    }
+
+    pub fn requested_ip(&self) -> Option<Ipv4Address> {
+        self.option_addr(opt_code::REQUESTED_IP_ADDRESS)
+    }
+
+    pub fn server_id(&self) -> Option<Ipv4Address> {
+        self.option_addr(opt_code::SERVER_IDENTIFIER)
+    }
+
+    pub fn subnet_mask(&self) -> Option<Ipv4Address> {
+        self.option_addr(opt_code::SUBNET_MASK)
+    }
+
+    pub fn routers(&self) -> Vec<Ipv4Address> {
+        self.option_data(opt_code::ROUTER)
+            .map(ipv4_list)
+            .unwrap_or_default()
+    }
+
+    pub fn dns_servers(&self) -> Vec<Ipv4Address> {
+        self.option_data(opt_code::DOMAIN_NAME_SERVER)
+            .map(ipv4_list)
+            .unwrap_or_default()
+    }
+
+    /// Lease time in seconds
+    pub fn lease_time(&self) -> Option<u32> {
+        self.option_u32(opt_code::IP_ADDRESS_LEASE_TIME)
+    }
+
+    pub fn host_name(&self) -> Option<&str> {
+        self.option_data(opt_code::HOST_NAME)
+            .and_then(|data| std::str::from_utf8(data).ok())
+    }
+
+    pub fn domain_name(&self) -> Option<&str> {
+        self.option_data(opt_code::DOMAIN_NAME)
+            .and_then(|data| std::str::from_utf8(data).ok())
+    }
+
+    /// Sub-options of the relay agent information option, as `(code, data)`
+    /// pairs. See `relay_sub_opt` for common codes.
+    pub fn relay_agent_info(&self) -> Option<Vec<(u8, &[u8])>> {
+        self.option_data(opt_code::RELAY_AGENT_INFORMATION)
+            .map(sub_options)
+    }
+
+    pub fn circuit_id(&self) -> Option<&[u8]> {
+        self.relay_agent_info()?
+            .into_iter()
+            .find(|(code, _)| *code == relay_sub_opt::CIRCUIT_ID)
+            .map(|(_, data)| data)
+    }
+
+    pub fn remote_id(&self) -> Option<&[u8]> {
+        self.relay_agent_info()?
+            .into_iter()
+            .find(|(code, _)| *code == relay_sub_opt::REMOTE_ID)
+            .map(|(_, data)| data)
+    }
+
+    /// Appends an end option if the options are not already terminated
+    pub fn update_end(&mut self) {
+        self.base.mark_dirty();
+        if self.opts.last().map(|opt| opt.code) != Some(opt_code::END) {
+            self.opts.push(DhcpOption::new(opt_code::END, Bytes::new()));
+        }
+    }
+}
+
+impl Dissect for Dhcp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (buf, (op, htype, hlen, hops, xid, secs, flags)) = tuple((
+            u8::decode,
+            u8::decode,
+            u8::decode,
+            u8::decode,
+            u32::decode_be,
+            u16::decode_be,
+            u16::decode_be,
+        ))(buf)?;
+        if op != OP_REQUEST && op != OP_REPLY {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let (buf, (ciaddr, yiaddr, siaddr, giaddr, chaddr, sname, file, magic)) = tuple((
+            Ipv4Address::decode,
+            Ipv4Address::decode,
+            Ipv4Address::decode,
+            Ipv4Address::decode,
+            <[u8; 16]>::decode,
+            <[u8; 64]>::decode,
+            <[u8; 128]>::decode,
+            u32::decode_be,
+        ))(buf)?;
+        if magic != MAGIC_COOKIE {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+
+        let mut opts = Vec::new();
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let (rem, opt) = DhcpOption::decode(buf, session)?;
+            buf = rem;
+            let end = opt.code == opt_code::END;
+            opts.push(opt);
+            if end {
+                break;
+            }
+        }
+        let (buf, padding) = map(rest, |rest| session.packet_bytes(rest))(buf)?;
+
+        Ok((
+            buf,
+            Self {
+                base: BasePdu::default(),
+                op,
+                htype,
+                hlen,
+                hops,
+                xid,
+                secs,
+                flags,
+                ciaddr,
+                yiaddr,
+                siaddr,
+                giaddr,
+                chaddr,
+                sname,
+                file,
+                magic,
+                opts,
+                padding,
+            },
+        ))
+    }
+}
+
+impl Pdu for Dhcp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        240 + self.opts.iter().map(|opt| opt.encoded_len()).sum::<usize>() + self.padding.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.op)?
+            .encode(&self.htype)?
+            .encode(&self.hlen)?
+            .encode(&self.hops)?
+            .encode_be(&self.xid)?
+            .encode_be(&self.secs)?
+            .encode_be(&self.flags)?
+            .encode(&self.ciaddr)?
+            .encode(&self.yiaddr)?
+            .encode(&self.siaddr)?
+            .encode(&self.giaddr)?
+            .encode(&self.chaddr[..])?
+            .encode(&self.sname[..])?
+            .encode(&self.file[..])?
+            .encode_be(&self.magic)?;
+        for opt in self.opts.iter() {
+            encoder.encode(&opt.code)?;
+            if opt.has_len() {
+                encoder
+                    .encode(&(opt.data.len() as u8))?
+                    .encode(&opt.data[..])?;
+            }
+        }
+        encoder.encode(&self.padding[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let descr = self.message_type().map(|msg_type| msg_type.to_string());
+        let mut node = dumper.add_node("DHCP", descr.as_deref())?;
+        node.byte_range(0, 1).add_field(
+            "Operation",
+            DumpValue::UInt(self.op.into()),
+            Some(match self.op {
+                OP_REQUEST => "Request",
+                OP_REPLY => "Reply",
+                _ => "Unknown",
+            }),
+        )?;
+        node.byte_range(1, 1).add_field(
+            "Hardware Type",
+            DumpValue::UInt(self.htype.into()),
+            None,
+        )?;
+        node.byte_range(2, 1).add_field(
+            "Hardware Address Length",
+            DumpValue::UInt(self.hlen.into()),
+            None,
+        )?;
+        node.byte_range(3, 1)
+            .add_field("Hops", DumpValue::UInt(self.hops.into()), None)?;
+        node.byte_range(4, 4).add_field(
+            "Transaction ID",
+            DumpValue::UInt(self.xid.into()),
+            Some(&format!("0x{:08x}", self.xid)[..]),
+        )?;
+        node.byte_range(8, 2).add_field(
+            "Seconds Elapsed",
+            DumpValue::UInt(self.secs.into()),
+            None,
+        )?;
+        {
+            let mut node = node
+                .byte_range(10, 2)
+                .add_node("Flags", Some(&format!("0x{:04x}", self.flags)[..]))?;
+            node.add_field("Broadcast", DumpValue::Bool(self.is_broadcast()), None)?;
+        }
+        for (offset, name, addr) in [
+            (12, "Client IP Address", self.ciaddr),
+            (16, "Your IP Address", self.yiaddr),
+            (20, "Next Server IP Address", self.siaddr),
+            (24, "Relay Agent IP Address", self.giaddr),
+        ] {
+            node.byte_range(offset, 4).add_field(
+                name,
+                DumpValue::Bytes(&addr[..]),
+                Some(&addr.to_string()[..]),
+            )?;
+        }
+        let chaddr_descr = self.client_mac().map(|mac| mac.to_string());
+        node.byte_range(28, 16).add_field(
+            "Client Hardware Address",
+            DumpValue::Bytes(self.chaddr()),
+            chaddr_descr.as_deref(),
+        )?;
+        node.byte_range(44, 64).add_field(
+            "Server Host Name",
+            DumpValue::Bytes(&self.sname[..]),
+            None,
+        )?;
+        node.byte_range(108, 128).add_field(
+            "Boot File Name",
+            DumpValue::Bytes(&self.file[..]),
+            None,
+        )?;
+        node.byte_range(236, 4).add_field(
+            "Magic Cookie",
+            DumpValue::UInt(self.magic.into()),
+            Some(&format!("0x{:08x}", self.magic)[..]),
+        )?;
+
+        let mut offset = 240;
+        for opt in self.opts.iter() {
+            let len = opt.encoded_len();
+            let mut opt_node = node
+                .byte_range(offset, len)
+                .add_node("Option", Some(&format!("({})", opt.code)[..]))?;
+            opt_node.add_field("Code", DumpValue::UInt(opt.code.into()), None)?;
+            if opt.has_len() {
+                opt_node.add_field("Length", DumpValue::UInt(opt.data.len() as u64), None)?;
+                let descr = match opt.code {
+                    opt_code::MESSAGE_TYPE if opt.data.len() == 1 => {
+                        Some(MessageType(opt.data[0]).to_string())
+                    }
+                    opt_code::SUBNET_MASK
+                    | opt_code::REQUESTED_IP_ADDRESS
+                    | opt_code::SERVER_IDENTIFIER
+                    | opt_code::ROUTER
+                    | opt_code::DOMAIN_NAME_SERVER => Some(
+                        ipv4_list(&opt.data[..])
+                            .iter()
+                            .map(|addr| addr.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                    opt_code::HOST_NAME | opt_code::DOMAIN_NAME => {
+                        std::str::from_utf8(&opt.data[..]).ok().map(String::from)
+                    }
+                    _ => None,
+                };
+                opt_node.add_field("Data", DumpValue::Bytes(&opt.data[..]), descr.as_deref())?;
+            }
+            offset += len;
+        }
+        if !self.padding.is_empty() {
+            node.byte_range(offset, self.padding.len()).add_field(
+                "Padding",
+                DumpValue::Bytes(&self.padding[..]),
+                None,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.magic = MAGIC_COOKIE;
+        self.update_end();
+    }
+}
+
+impl Default for Dhcp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    dhcp_server,
+    UdpPortDissectorTable,
+    DHCP_SERVER_PORT,
+    Priority(0),
+    Dhcp::dissect
+);
+
+register_dissector!(
+    dhcp_client,
+    UdpPortDissectorTable,
+    DHCP_CLIENT_PORT,
+    Priority(0),
+    Dhcp::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::udp::Udp;
+
+    #[test]
+    fn dhcp_discover() {
+        let mut dhcp = Dhcp::new();
+        *dhcp.xid_mut() = 0x3903f326;
+        *dhcp.flags_mut() = FLAG_BROADCAST;
+        dhcp.chaddr_raw_mut()[..6].copy_from_slice(&[0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]);
+        dhcp.set_option(opt_code::MESSAGE_TYPE, vec![MessageType::DISCOVER.0]);
+        dhcp.set_option(
+            opt_code::CLIENT_IDENTIFIER,
+            vec![1, 0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42],
+        );
+        dhcp.set_option(opt_code::REQUESTED_IP_ADDRESS, vec![192, 168, 0, 10]);
+        dhcp.set_option(opt_code::PARAMETER_REQUEST_LIST, vec![1, 3, 6, 42]);
+        dhcp.set_option(
+            opt_code::RELAY_AGENT_INFORMATION,
+            vec![1, 2, 0xab, 0xcd, 2, 1, 7],
+        );
+        dhcp.make_canonical();
+        assert_eq!(dhcp.options().last().unwrap().code(), opt_code::END);
+
+        let mut udp = Udp::with_ports(DHCP_CLIENT_PORT, DHCP_SERVER_PORT);
+        udp.set_inner_pdu(dhcp);
+        udp.make_canonical();
+        let mut buf = Vec::new();
+        udp.serialize(&mut buf).unwrap();
+
+        let session = Session::new();
+        let (rem, udp) = Udp::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        let dhcp = udp.find::<Dhcp>().unwrap();
+        assert!(dhcp.is_request());
+        assert!(dhcp.is_broadcast());
+        assert_eq!(dhcp.xid(), 0x3903f326);
+        assert_eq!(dhcp.message_type(), Some(MessageType::DISCOVER));
+        assert_eq!(
+            dhcp.client_mac(),
+            Some(MacAddress::from([0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]))
+        );
+        assert_eq!(dhcp.client_id().unwrap().len(), 7);
+        assert_eq!(
+            dhcp.requested_ip(),
+            Some(Ipv4Address::from([192, 168, 0, 10]))
+        );
+        assert_eq!(dhcp.requested_options(), Some(&[1, 3, 6, 42][..]));
+        assert_eq!(dhcp.circuit_id(), Some(&[0xab, 0xcd][..]));
+        assert_eq!(dhcp.remote_id(), Some(&[7][..]));
+        assert_eq!(dhcp.lease_time(), None);
+
+        let mut out = Vec::new();
+        udp.serialize(&mut out).unwrap();
+        assert_eq!(out, buf);
+    }
+}
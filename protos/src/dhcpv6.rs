@@ -0,0 +1,579 @@
+use super::udp::UdpPortDissectorTable;
+use crate::prelude::*;
+use nom::{bytes::complete::take, combinator::cond, sequence::tuple};
+use sniffle_core::Ipv6Address;
+
+/// UDP port DHCPv6 clients listen on
+pub const DHCPV6_CLIENT_PORT: u16 = 546;
+/// UDP port DHCPv6 servers and relay agents listen on
+pub const DHCPV6_SERVER_PORT: u16 = 547;
+
+/// Dynamic Host Configuration Protocol for IPv6 message, as defined by
+/// RFC 8415
+///
+/// Relay-forward and relay-reply messages additionally carry a hop count,
+/// link address, and peer address. The relayed message is contained in the
+/// relay message option.
+#[derive(Debug, Clone)]
+pub struct Dhcpv6 {
+    base: BasePdu,
+    msg_type: MessageType,
+    xid: u32,
+    relay: Option<RelayHeader>,
+    opts: Vec<Dhcpv6Option>,
+}
+
+/// Header fields specific to relay-forward and relay-reply messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayHeader {
+    pub hop_count: u8,
+    pub link_address: Ipv6Address,
+    pub peer_address: Ipv6Address,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcpv6Option {
+    code: u16,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageType(pub u8);
+
+/// DHCPv6 option codes
+pub mod opt_code {
+    pub const CLIENTID: u16 = 1;
+    pub const SERVERID: u16 = 2;
+    pub const IA_NA: u16 = 3;
+    pub const IA_TA: u16 = 4;
+    pub const IAADDR: u16 = 5;
+    pub const ORO: u16 = 6;
+    pub const PREFERENCE: u16 = 7;
+    pub const ELAPSED_TIME: u16 = 8;
+    pub const RELAY_MSG: u16 = 9;
+    pub const AUTH: u16 = 11;
+    pub const UNICAST: u16 = 12;
+    pub const STATUS_CODE: u16 = 13;
+    pub const RAPID_COMMIT: u16 = 14;
+    pub const USER_CLASS: u16 = 15;
+    pub const VENDOR_CLASS: u16 = 16;
+    pub const VENDOR_OPTS: u16 = 17;
+    pub const INTERFACE_ID: u16 = 18;
+    pub const RECONF_MSG: u16 = 19;
+    pub const RECONF_ACCEPT: u16 = 20;
+    pub const DNS_SERVERS: u16 = 23;
+    pub const DOMAIN_LIST: u16 = 24;
+    pub const IA_PD: u16 = 25;
+    pub const IAPREFIX: u16 = 26;
+    pub const REMOTE_ID: u16 = 37;
+}
+
+impl MessageType {
+    pub const SOLICIT: Self = Self(1);
+    pub const ADVERTISE: Self = Self(2);
+    pub const REQUEST: Self = Self(3);
+    pub const CONFIRM: Self = Self(4);
+    pub const RENEW: Self = Self(5);
+    pub const REBIND: Self = Self(6);
+    pub const REPLY: Self = Self(7);
+    pub const RELEASE: Self = Self(8);
+    pub const DECLINE: Self = Self(9);
+    pub const RECONFIGURE: Self = Self(10);
+    pub const INFORMATION_REQUEST: Self = Self(11);
+    pub const RELAY_FORW: Self = Self(12);
+    pub const RELAY_REPL: Self = Self(13);
+
+    pub fn is_relay(&self) -> bool {
+        *self == Self::RELAY_FORW || *self == Self::RELAY_REPL
+    }
+}
+
+impl std::fmt::Display for MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::SOLICIT => "Solicit",
+            Self::ADVERTISE => "Advertise",
+            Self::REQUEST => "Request",
+            Self::CONFIRM => "Confirm",
+            Self::RENEW => "Renew",
+            Self::REBIND => "Rebind",
+            Self::REPLY => "Reply",
+            Self::RELEASE => "Release",
+            Self::DECLINE => "Decline",
+            Self::RECONFIGURE => "Reconfigure",
+            Self::INFORMATION_REQUEST => "Information-request",
+            Self::RELAY_FORW => "Relay-forward",
+            Self::RELAY_REPL => "Relay-reply",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl Dhcpv6Option {
+    pub fn new(code: u16, data: Vec<u8>) -> Self {
+        Self { code, data }
+    }
+
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn code_mut(&mut self) -> &mut u16 {
+        &mut self.code
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+
+    /// Nested options contained in the option data, for options such as
+    /// IA_NA that carry a fixed size prefix followed by options.
+    pub fn sub_options(&self, offset: usize) -> Vec<Dhcpv6Option> {
+        self.data
+            .get(offset..)
+            .and_then(|buf| decode_options(buf).ok())
+            .map(|(_, opts)| opts)
+            .unwrap_or_default()
+    }
+
+    fn decode(buf: &[u8]) -> DResult<'_, Self> {
+        let (buf, (code, len)) = tuple((u16::decode_be, u16::decode_be))(buf)?;
+        let (buf, data) = take(len as usize)(buf)?;
+        Ok((
+            buf,
+            Self {
+                code,
+                data: Vec::from(data),
+            },
+        ))
+    }
+}
+
+fn decode_options(mut buf: &[u8]) -> DResult<'_, Vec<Dhcpv6Option>> {
+    let mut opts = Vec::new();
+    while !buf.is_empty() {
+        let (rem, opt) = Dhcpv6Option::decode(buf)?;
+        buf = rem;
+        opts.push(opt);
+    }
+    Ok((buf, opts))
+}
+
+impl Dhcpv6 {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            msg_type: MessageType::SOLICIT,
+            xid: 0,
+            relay: None,
+            opts: Vec::new(),
+        }
+    }
+
+    pub fn message_type(&self) -> MessageType {
+        self.msg_type
+    }
+
+    pub fn message_type_mut(&mut self) -> &mut MessageType {
+        self.base.mark_dirty();
+        &mut self.msg_type
+    }
+
+    /// Transaction ID. Only the lower 24 bits are used, and it is not present
+    /// in relay messages.
+    pub fn xid(&self) -> u32 {
+        self.xid
+    }
+
+    pub fn xid_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.xid
+    }
+
+    /// Relay header fields, if this is a relay message
+    pub fn relay(&self) -> Option<&RelayHeader> {
+        self.relay.as_ref()
+    }
+
+    pub fn relay_mut(&mut self) -> &mut Option<RelayHeader> {
+        self.base.mark_dirty();
+        &mut self.relay
+    }
+
+    pub fn options(&self) -> &[Dhcpv6Option] {
+        &self.opts[..]
+    }
+
+    pub fn options_mut(&mut self) -> &mut Vec<Dhcpv6Option> {
+        self.base.mark_dirty();
+        &mut self.opts
+    }
+
+    /// Returns the first option with the specified code
+    pub fn option(&self, code: u16) -> Option<&Dhcpv6Option> {
+        self.opts.iter().find(|opt| opt.code == code)
+    }
+
+    /// Replaces the first option with the specified code, or appends it if
+    /// there is none.
+    pub fn set_option(&mut self, code: u16, data: Vec<u8>) {
+        self.base.mark_dirty();
+        match self.opts.iter_mut().find(|opt| opt.code == code) {
+            Some(opt) => opt.data = data,
+            None => self.opts.push(Dhcpv6Option::new(code, data)),
+        }
+    }
+
+    fn option_data(&self, code: u16) -> Option<&[u8]> {
+        self.option(code).map(|opt| &opt.data[..])
+    }
+
+    /// Client DUID
+    pub fn client_id(&self) -> Option<&[u8]> {
+        self.option_data(opt_code::CLIENTID)
+    }
+
+    /// Server DUID
+    pub fn server_id(&self) -> Option<&[u8]> {
+        self.option_data(opt_code::SERVERID)
+    }
+
+    /// Option codes listed in the option request option
+    pub fn requested_options(&self) -> Option<Vec<u16>> {
+        self.option_data(opt_code::ORO).map(|data| {
+            data.chunks_exact(2)
+                .map(|code| u16::from_be_bytes([code[0], code[1]]))
+                .collect()
+        })
+    }
+
+    /// Elapsed time, in hundredths of a second
+    pub fn elapsed_time(&self) -> Option<u16> {
+        self.option_data(opt_code::ELAPSED_TIME)
+            .filter(|data| data.len() == 2)
+            .map(|data| u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    pub fn preference(&self) -> Option<u8> {
+        self.option_data(opt_code::PREFERENCE)
+            .filter(|data| data.len() == 1)
+            .map(|data| data[0])
+    }
+
+    /// Status code and message
+    pub fn status(&self) -> Option<(u16, &str)> {
+        self.option_data(opt_code::STATUS_CODE)
+            .filter(|data| data.len() >= 2)
+            .map(|data| {
+                (
+                    u16::from_be_bytes([data[0], data[1]]),
+                    std::str::from_utf8(&data[2..]).unwrap_or(""),
+                )
+            })
+    }
+
+    pub fn dns_servers(&self) -> Vec<Ipv6Address> {
+        self.option_data(opt_code::DNS_SERVERS)
+            .map(|data| {
+                data.chunks_exact(16)
+                    .map(|addr| {
+                        let mut raw = [0u8; 16];
+                        raw.copy_from_slice(addr);
+                        Ipv6Address::from(raw)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn interface_id(&self) -> Option<&[u8]> {
+        self.option_data(opt_code::INTERFACE_ID)
+    }
+
+    /// Relay agent remote ID, including the enterprise number
+    pub fn remote_id(&self) -> Option<&[u8]> {
+        self.option_data(opt_code::REMOTE_ID)
+    }
+
+    /// The raw relayed message
+    pub fn relay_message(&self) -> Option<&[u8]> {
+        self.option_data(opt_code::RELAY_MSG)
+    }
+
+    /// Decodes the relayed message, if this is a relay message
+    pub fn relayed(&self) -> Option<Dhcpv6> {
+        self.relay_message()
+            .and_then(|msg| Self::parse(msg).ok())
+            .map(|(_, msg)| msg)
+    }
+
+    /// Identity association ID of the first IA_NA option
+    pub fn ia_na_iaid(&self) -> Option<u32> {
+        self.option_data(opt_code::IA_NA)
+            .filter(|data| data.len() >= 12)
+            .map(|data| u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
+    /// Addresses in the IA Address options of the first IA_NA option
+    pub fn ia_na_addresses(&self) -> Vec<Ipv6Address> {
+        self.option(opt_code::IA_NA)
+            .map(|opt| {
+                opt.sub_options(12)
+                    .into_iter()
+                    .filter(|sub| sub.code == opt_code::IAADDR && sub.data.len() >= 16)
+                    .map(|sub| {
+                        let mut raw = [0u8; 16];
+                        raw.copy_from_slice(&sub.data[..16]);
+                        Ipv6Address::from(raw)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn parse(buf: &[u8]) -> DResult<'_, Self> {
+        let (buf, msg_type) = u8::decode(buf)?;
+        let msg_type = MessageType(msg_type);
+        if msg_type.0 == 0 {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let (buf, (relay, xid)) = tuple((
+            cond(
+                msg_type.is_relay(),
+                tuple((u8::decode, Ipv6Address::decode, Ipv6Address::decode)),
+            ),
+            cond(!msg_type.is_relay(), <[u8; 3]>::decode),
+        ))(buf)?;
+        let (buf, opts) = decode_options(buf)?;
+        Ok((
+            buf,
+            Self {
+                base: BasePdu::default(),
+                msg_type,
+                xid: xid
+                    .map(|xid| u32::from_be_bytes([0, xid[0], xid[1], xid[2]]))
+                    .unwrap_or(0),
+                relay: relay.map(|(hop_count, link_address, peer_address)| RelayHeader {
+                    hop_count,
+                    link_address,
+                    peer_address,
+                }),
+                opts,
+            },
+        ))
+    }
+
+    fn fixed_len(&self) -> usize {
+        if self.relay.is_some() {
+            34
+        } else {
+            4
+        }
+    }
+
+    fn dump_fields<D: Dump + ?Sized>(
+        &self,
+        node: &mut NodeDumper<D>,
+        base: usize,
+    ) -> Result<(), D::Error> {
+        node.byte_range(base, 1).add_field(
+            "Message Type",
+            DumpValue::UInt(self.msg_type.0.into()),
+            Some(&self.msg_type.to_string()[..]),
+        )?;
+        match self.relay {
+            Some(relay) => {
+                node.byte_range(base + 1, 1).add_field(
+                    "Hop Count",
+                    DumpValue::UInt(relay.hop_count.into()),
+                    None,
+                )?;
+                node.byte_range(base + 2, 16).add_field(
+                    "Link Address",
+                    DumpValue::Bytes(&relay.link_address[..]),
+                    Some(&relay.link_address.to_string()[..]),
+                )?;
+                node.byte_range(base + 18, 16).add_field(
+                    "Peer Address",
+                    DumpValue::Bytes(&relay.peer_address[..]),
+                    Some(&relay.peer_address.to_string()[..]),
+                )?;
+            }
+            None => {
+                node.byte_range(base + 1, 3).add_field(
+                    "Transaction ID",
+                    DumpValue::UInt(self.xid.into()),
+                    Some(&format!("0x{:06x}", self.xid)[..]),
+                )?;
+            }
+        }
+        let mut offset = base + self.fixed_len();
+        for opt in self.opts.iter() {
+            let len = 4 + opt.data.len();
+            let mut opt_node = node
+                .byte_range(offset, len)
+                .add_node("Option", Some(&format!("({})", opt.code)[..]))?;
+            opt_node.add_field("Code", DumpValue::UInt(opt.code.into()), None)?;
+            opt_node.add_field("Length", DumpValue::UInt(opt.data.len() as u64), None)?;
+            let relayed = if opt.code == opt_code::RELAY_MSG {
+                Self::parse(&opt.data[..]).ok().map(|(_, msg)| msg)
+            } else {
+                None
+            };
+            match relayed {
+                Some(msg) => {
+                    let mut msg_node = opt_node
+                        .byte_range(offset + 4, opt.data.len())
+                        .add_node("Relayed Message", Some(&msg.msg_type.to_string()[..]))?;
+                    msg.dump_fields(&mut msg_node, offset + 4)?;
+                }
+                None => {
+                    opt_node.add_field("Data", DumpValue::Bytes(&opt.data[..]), None)?;
+                }
+            }
+            offset += len;
+        }
+        Ok(())
+    }
+}
+
+impl Dissect for Dhcpv6 {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        Self::parse(buf)
+    }
+}
+
+impl Pdu for Dhcpv6 {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.fixed_len()
+            + self
+                .opts
+                .iter()
+                .map(|opt| 4 + opt.data.len())
+                .sum::<usize>()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.msg_type.0)?;
+        match self.relay {
+            Some(relay) => {
+                encoder
+                    .encode(&relay.hop_count)?
+                    .encode(&relay.link_address)?
+                    .encode(&relay.peer_address)?;
+            }
+            None => {
+                encoder.encode(&self.xid.to_be_bytes()[1..])?;
+            }
+        }
+        for opt in self.opts.iter() {
+            encoder
+                .encode_be(&opt.code)?
+                .encode_be(&(opt.data.len() as u16))?
+                .encode(&opt.data[..])?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("DHCPv6", Some(&self.msg_type.to_string()[..]))?;
+        self.dump_fields(&mut node, 0)
+    }
+
+    fn make_canonical(&mut self) {
+        self.xid &= 0x00FF_FFFF;
+        if self.msg_type.is_relay() {
+            if self.relay.is_none() {
+                self.relay = Some(RelayHeader {
+                    hop_count: 0,
+                    link_address: Ipv6Address::default(),
+                    peer_address: Ipv6Address::default(),
+                });
+            }
+        } else {
+            self.relay = None;
+        }
+    }
+}
+
+impl Default for Dhcpv6 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    dhcpv6_client,
+    UdpPortDissectorTable,
+    DHCPV6_CLIENT_PORT,
+    Priority(0),
+    Dhcpv6::dissect
+);
+
+register_dissector!(
+    dhcpv6_server,
+    UdpPortDissectorTable,
+    DHCPV6_SERVER_PORT,
+    Priority(0),
+    Dhcpv6::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dhcpv6_relay_solicit() {
+        let data = [
+            0x0c, 0x00, // Relay-forward, hop count
+            0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x01, // Link address
+            0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x02, // Peer address
+            0x00, 0x12, 0x00, 0x02, 0x00, 0x05, // Interface ID
+            0x00, 0x09, 0x00, 0x20, // Relay message
+            0x01, 0x10, 0x08, 0x74, // Solicit
+            0x00, 0x01, 0x00, 0x0a, 0x00, 0x03, 0x00, 0x01, 0x00, 0x0b, 0x82, 0x01, 0xfc,
+            0x42, // Client ID
+            0x00, 0x06, 0x00, 0x04, 0x00, 0x17, 0x00, 0x18, // ORO
+            0x00, 0x08, 0x00, 0x02, 0x00, 0x00, // Elapsed time
+        ];
+        let session = Session::new();
+        let (rem, relay) = Dhcpv6::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(relay.message_type(), MessageType::RELAY_FORW);
+        assert_eq!(relay.relay().unwrap().hop_count, 0);
+        assert_eq!(relay.interface_id(), Some(&[0x00, 0x05][..]));
+
+        let solicit = relay.relayed().unwrap();
+        assert_eq!(solicit.message_type(), MessageType::SOLICIT);
+        assert_eq!(solicit.xid(), 0x100874);
+        assert_eq!(solicit.client_id().unwrap().len(), 10);
+        assert_eq!(solicit.requested_options(), Some(vec![23, 24]));
+        assert_eq!(solicit.elapsed_time(), Some(0));
+
+        let mut out = Vec::new();
+        relay.serialize(&mut out).unwrap();
+        assert_eq!(&out[..], &data[..]);
+    }
+}
@@ -1,10 +1,12 @@
 use super::ethertype::Ethertype;
 use crate::prelude::*;
+use checksum::Crc32;
 use nom::{
     combinator::{flat_map, map, rest},
     sequence::tuple,
 };
 use sniffle_core::MacAddress;
+use std::io::Write;
 use utils::CountingEncoder;
 
 #[derive(Debug, Clone)]
@@ -14,20 +16,45 @@ pub struct EthernetII {
     src_addr: MacAddress,
     ethertype: Ethertype,
     trailer: Trailer,
+    fcs: Option<u32>,
+}
+
+/// Whether captured frames end with a 4 byte frame check sequence (FCS).
+///
+/// Register a value with `Session::register` to override the default,
+/// `EthernetFcs::Auto`. Only applies to Ethernet II at the link layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EthernetFcs {
+    /// Frames never include an FCS
+    Absent,
+    /// Frames always include an FCS
+    Present,
+    /// Frames include an FCS when the capturing device reports a 4 byte FCS
+    /// length (see `Device::fcs_length`)
+    #[default]
+    Auto,
+    /// Like `Auto`, but when the device doesn't report an FCS length, the
+    /// last 4 bytes of a frame are taken as an FCS if they are a valid CRC
+    /// of the rest of the frame
+    Heuristic,
 }
 
 #[derive(Debug, Clone)]
 enum Trailer {
     Auto,
     Zeros(usize),
-    Manual(Vec<u8>),
+    Manual(Bytes),
 }
 
 dissector_table!(pub EthertypeDissectorTable, Ethertype);
 dissector_table!(pub HeurDissectorTable);
+// Dissectors for the payload of IEEE 802.3 frames, which hold the length
+// of the payload in place of an ethertype.
+dissector_table!(pub Ieee8023DissectorTable);
 
 register_dissector_table!(EthertypeDissectorTable);
 register_dissector_table!(HeurDissectorTable);
+register_dissector_table!(Ieee8023DissectorTable);
 
 impl EthernetII {
     pub fn new() -> Self {
@@ -37,6 +64,7 @@ impl EthernetII {
             src_addr: Default::default(),
             ethertype: Ethertype(0),
             trailer: Trailer::Auto,
+            fcs: None,
         }
     }
 
@@ -47,6 +75,7 @@ impl EthernetII {
             src_addr,
             ethertype: Ethertype(0),
             trailer: Trailer::Auto,
+            fcs: None,
         }
     }
 
@@ -55,6 +84,7 @@ impl EthernetII {
     }
 
     pub fn dst_address_mut(&mut self) -> &mut MacAddress {
+        self.base.mark_dirty();
         &mut self.dst_addr
     }
 
@@ -63,6 +93,7 @@ impl EthernetII {
     }
 
     pub fn src_address_mut(&mut self) -> &mut MacAddress {
+        self.base.mark_dirty();
         &mut self.src_addr
     }
 
@@ -71,24 +102,34 @@ impl EthernetII {
     }
 
     pub fn ethertype_mut(&mut self) -> &mut Ethertype {
+        self.base.mark_dirty();
         &mut self.ethertype
     }
 
+    /// Whether this is an IEEE 802.3 frame, where the ethertype field holds
+    /// the length of the payload.
+    pub fn is_length_framed(&self) -> bool {
+        Ethertype::IEEE_802_3_LENGTH.contains(self.ethertype)
+    }
+
     pub fn update_ethertype(&mut self) {
+        self.base.mark_dirty();
         let ethertype = self
             .inner_pdu()
-            .map(|inner| Ethertype::from_pdu(inner).unwrap_or(self.ethertype))
+            .map(|inner| {
+                if Ethertype::is_length_framed(inner) {
+                    Ethertype(inner.total_len().min(0x05dc) as u16)
+                } else {
+                    Ethertype::from_pdu(inner).unwrap_or(self.ethertype)
+                }
+            })
             .unwrap_or(self.ethertype);
         self.ethertype = ethertype;
     }
 
     fn auto_trailer_len(&self) -> usize {
         let inner_len = self.inner_pdu().map(|inner| inner.total_len()).unwrap_or(0);
-        if inner_len < 46 {
-            46 - inner_len
-        } else {
-            0
-        }
+        46_usize.saturating_sub(inner_len)
     }
 
     pub fn trailer(&self) -> &[u8] {
@@ -99,22 +140,89 @@ impl EthernetII {
         }
     }
 
-    pub fn trailer_mut(&mut self) -> &mut Vec<u8> {
-        let trailer = match &mut self.trailer {
-            Trailer::Auto => vec![0u8; self.auto_trailer_len()],
-            Trailer::Zeros(len) => vec![0u8; *len],
-            Trailer::Manual(trailer) => std::mem::take(trailer),
-        };
-        self.trailer = Trailer::Manual(trailer);
-        match &mut self.trailer {
-            Trailer::Manual(trailer) => trailer,
-            _ => unreachable!(),
-        }
+    pub fn set_trailer<B: Into<Bytes>>(&mut self, trailer: B) {
+        self.base.mark_dirty();
+        self.trailer = Trailer::Manual(trailer.into());
     }
 
     pub fn update_trailer(&mut self) {
+        self.base.mark_dirty();
         self.trailer = Trailer::Auto;
     }
+
+    /// The frame check sequence, if the frame has one
+    pub fn fcs(&self) -> Option<u32> {
+        self.fcs
+    }
+
+    pub fn fcs_mut(&mut self) -> &mut Option<u32> {
+        self.base.mark_dirty();
+        &mut self.fcs
+    }
+
+    /// Computes the FCS over the header, payload, and trailer.
+    pub fn calc_fcs(&self) -> u32 {
+        let mut crc = Crc32::new();
+        let _ = self.serialize_frame(&mut crc);
+        crc.checksum()
+    }
+
+    /// Checks the FCS against the frame contents, or returns `None` if the
+    /// frame has no FCS.
+    pub fn fcs_valid(&self) -> Option<bool> {
+        self.fcs.map(|fcs| fcs == self.calc_fcs())
+    }
+
+    /// Recomputes the FCS, if the frame has one.
+    pub fn update_fcs(&mut self) {
+        self.base.mark_dirty();
+        if self.fcs.is_some() {
+            self.fcs = Some(self.calc_fcs());
+        }
+    }
+
+    fn serialize_frame<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> std::io::Result<()> {
+        self.serialize_header(encoder)?;
+        let mut writer = CountingEncoder::new(encoder);
+        self.inner_pdu()
+            .map(|inner| inner.serialize(&mut writer))
+            .unwrap_or(Ok(()))?;
+        let inner_len = writer.bytes_written();
+        let encoder = writer.into_inner();
+        match &self.trailer {
+            Trailer::Auto => {
+                encoder.encode(&PADDING[..46_usize.saturating_sub(inner_len)])?;
+            }
+            Trailer::Zeros(len) => {
+                encoder.encode(&PADDING[..*len])?;
+            }
+            Trailer::Manual(trailer) => {
+                encoder.encode(&trailer[..])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn has_fcs(buf: &[u8], session: &Session) -> bool {
+    if buf.len() < 18 {
+        return false;
+    }
+    let device_fcs = || session.device().and_then(|dev| dev.fcs_length());
+    match session.get::<EthernetFcs>().copied().unwrap_or_default() {
+        EthernetFcs::Absent => false,
+        EthernetFcs::Present => true,
+        EthernetFcs::Auto => device_fcs() == Some(4),
+        EthernetFcs::Heuristic => match device_fcs() {
+            Some(len) => len == 4,
+            None => {
+                let (frame, fcs) = buf.split_at(buf.len() - 4);
+                let mut crc = Crc32::new();
+                let _ = crc.write_all(frame);
+                crc.checksum().to_le_bytes()[..] == *fcs
+            }
+        },
+    }
 }
 
 const PADDING: [u8; 46] = [0u8; 46];
@@ -125,7 +233,13 @@ impl Dissect for EthernetII {
         session: &Session,
         parent: Option<TempPdu<'_>>,
     ) -> DResult<'a, Self> {
-        flat_map(
+        let fcs_len = if parent.is_none() && has_fcs(buf, session) {
+            4
+        } else {
+            0
+        };
+        let (frame, fcs) = buf.split_at(buf.len() - fcs_len);
+        let (rem, mut eth) = flat_map(
             tuple((<[MacAddress; 2]>::decode, map(u16::decode_be, Ethertype))),
             |([dst_addr, src_addr], ethertype)| {
                 let parent = parent.clone();
@@ -136,43 +250,57 @@ impl Dissect for EthernetII {
                         src_addr,
                         ethertype,
                         trailer: Trailer::Auto,
+                        fcs: None,
                     };
                     let before = buf.len();
-                    let (buf, (inner, trailer)) = session
-                        .table_dissector::<EthertypeDissectorTable>(
-                            &eth.ethertype,
-                            Some(TempPdu::new(&eth, &parent)),
-                        )
-                        .or(session.table_dissector::<HeurDissectorTable>(
-                            &(),
-                            Some(TempPdu::new(&eth, &parent)),
-                        ))
-                        .or(map(RawPdu::decode, AnyPdu::new))
-                        .and(map(rest, |trailer: &'a [u8]| {
-                            let inner_len = before - trailer.len();
-                            let trailer_len = if inner_len < 46 { 46 - inner_len } else { 0 };
-                            let mut zeros = 0usize;
-                            for byte in buf {
-                                if *byte != 0 {
-                                    break;
-                                }
-                                zeros += 1;
-                            }
-                            if zeros != trailer.len() {
-                                Trailer::Manual(Vec::from(trailer))
-                            } else if trailer_len != trailer.len() {
-                                Trailer::Zeros(zeros)
-                            } else {
-                                Trailer::Auto
-                            }
-                        }))
-                        .parse(buf)?;
+                    let (buf, inner) = if eth.is_length_framed() {
+                        // Anything past the length is trailer, even if the
+                        // payload isn't fully dissected
+                        let len = usize::from(eth.ethertype.0).min(buf.len());
+                        let (rem, inner) = session
+                            .table_dissector::<Ieee8023DissectorTable>(
+                                &(),
+                                Some(TempPdu::new(&eth, &parent)),
+                            )
+                            .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                            .parse(&buf[..len])?;
+                        (&buf[len - rem.len()..], inner)
+                    } else {
+                        session
+                            .table_dissector::<EthertypeDissectorTable>(
+                                &eth.ethertype,
+                                Some(TempPdu::new(&eth, &parent)),
+                            )
+                            .or(session.table_dissector::<HeurDissectorTable>(
+                                &(),
+                                Some(TempPdu::new(&eth, &parent)),
+                            ))
+                            .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                            .parse(buf)?
+                    };
+                    let (buf, trailer) = map(rest, |trailer: &'a [u8]| {
+                        let inner_len = before - trailer.len();
+                        let trailer_len = 46_usize.saturating_sub(inner_len);
+                        let zeros = trailer.iter().take_while(|byte| **byte == 0).count();
+                        if zeros != trailer.len() {
+                            Trailer::Manual(session.packet_bytes(trailer))
+                        } else if trailer_len != trailer.len() {
+                            Trailer::Zeros(zeros)
+                        } else {
+                            Trailer::Auto
+                        }
+                    })(buf)?;
                     eth.trailer = trailer;
                     eth.set_inner_pdu(inner);
                     Ok((buf, eth))
                 }
             },
-        )(buf)
+        )(frame)?;
+        if fcs_len == 0 {
+            return Ok((rem, eth));
+        }
+        eth.fcs = Some(u32::from_le_bytes([fcs[0], fcs[1], fcs[2], fcs[3]]));
+        Ok((&fcs[4..], eth))
     }
 }
 
@@ -190,7 +318,7 @@ impl Pdu for EthernetII {
     }
 
     fn trailer_len(&self) -> usize {
-        self.trailer().len()
+        self.trailer().len() + self.fcs.map(|_| 4).unwrap_or(0)
     }
 
     fn total_len(&self) -> usize {
@@ -198,16 +326,11 @@ impl Pdu for EthernetII {
         self.header_len()
             + inner_len
             + match &self.trailer {
-                Trailer::Auto => {
-                    if inner_len < 46 {
-                        46 - inner_len
-                    } else {
-                        0
-                    }
-                }
+                Trailer::Auto => 46_usize.saturating_sub(inner_len),
                 Trailer::Zeros(len) => *len,
                 Trailer::Manual(trailer) => trailer.len(),
             }
+            + self.fcs.map(|_| 4).unwrap_or(0)
     }
 
     fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
@@ -226,27 +349,16 @@ impl Pdu for EthernetII {
         encoder: &mut W,
     ) -> std::io::Result<()> {
         encoder.encode(self.trailer())?;
+        if let Some(fcs) = self.fcs {
+            encoder.encode_le(&fcs)?;
+        }
         Ok(())
     }
 
     fn serialize<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> std::io::Result<()> {
-        self.serialize_header(encoder)?;
-        let mut writer = CountingEncoder::new(encoder);
-        self.inner_pdu()
-            .map(|inner| inner.serialize(&mut writer))
-            .unwrap_or(Ok(()))?;
-        let inner_len = writer.bytes_written();
-        let encoder = writer.into_inner();
-        match &self.trailer {
-            Trailer::Auto => {
-                encoder.encode(&PADDING[..(46 - inner_len)])?;
-            }
-            Trailer::Zeros(len) => {
-                encoder.encode(&PADDING[..*len])?;
-            }
-            Trailer::Manual(trailer) => {
-                encoder.encode(&trailer[..])?;
-            }
+        self.serialize_frame(encoder)?;
+        if let Some(fcs) = self.fcs {
+            encoder.encode_le(&fcs)?;
         }
         Ok(())
     }
@@ -256,26 +368,62 @@ impl Pdu for EthernetII {
             "Ethernet II",
             Some(&format!("{}->{}", self.src_addr, self.dst_addr)[..]),
         )?;
-        node.add_field(
+        node.byte_range(0, 6).add_field(
             "Dst Address",
             DumpValue::Bytes(&self.dst_addr[..]),
             Some(&self.dst_addr.to_string()[..]),
         )?;
-        node.add_field(
+        node.byte_range(6, 6).add_field(
             "Src Address",
             DumpValue::Bytes(&self.src_addr[..]),
             Some(&self.src_addr.to_string()[..]),
         )?;
-        node.add_field(
-            "Ethertype",
-            DumpValue::UInt(self.ethertype.0.into()),
-            Some(&format!("0x{:04x}", self.ethertype.0)[..]),
-        )
+        if self.is_length_framed() {
+            node.byte_range(12, 2).add_field(
+                "Length",
+                DumpValue::UInt(self.ethertype.0.into()),
+                None,
+            )?;
+        } else {
+            node.byte_range(12, 2).add_field(
+                "Ethertype",
+                DumpValue::UInt(self.ethertype.0.into()),
+                Some(&format!("0x{:04x}", self.ethertype.0)[..]),
+            )?;
+        }
+        let trailer = self.trailer();
+        if !trailer.is_empty() {
+            let start = self.total_len() - trailer.len() - self.fcs.map(|_| 4).unwrap_or(0);
+            let descr = if trailer.iter().all(|byte| *byte == 0) {
+                Some("Padding")
+            } else {
+                None
+            };
+            node.byte_range(start, trailer.len()).add_field(
+                "Trailer",
+                DumpValue::Bytes(trailer),
+                descr,
+            )?;
+        }
+        if let Some(fcs) = self.fcs {
+            let valid = if fcs == self.calc_fcs() {
+                "valid"
+            } else {
+                "invalid"
+            };
+            node.byte_range(self.total_len() - 4, 4).add_field(
+                "FCS",
+                DumpValue::UInt(fcs.into()),
+                Some(&format!("0x{:08x} ({})", fcs, valid)[..]),
+            )?;
+        }
+        Ok(())
     }
 
     fn make_canonical(&mut self) {
         self.update_ethertype();
         self.update_trailer();
+        self.update_fcs();
     }
 }
 
@@ -293,3 +441,94 @@ register_dissector!(
     Priority(0),
     EthernetII::dissect
 );
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ethernet_fcs() {
+        let mut eth = EthernetII::new();
+        // Local experimental ethertype, so the payload isn't length framed
+        *eth.ethertype_mut() = Ethertype(0x88b5);
+        eth.set_inner_pdu(RawPdu::new(vec![0xab; 50]));
+        *eth.fcs_mut() = Some(0);
+        assert_eq!(eth.fcs_valid(), Some(false));
+        eth.make_canonical();
+        assert_eq!(eth.fcs_valid(), Some(true));
+
+        let mut buf = Vec::new();
+        eth.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 68);
+        assert_eq!(buf[64..], eth.calc_fcs().to_le_bytes());
+
+        let session = Session::new();
+        let (_, dissected) = EthernetII::dissect(&buf[..], &session, None).unwrap();
+        assert_eq!(dissected.fcs(), None);
+        assert_eq!(dissected.inner_pdu().unwrap().total_len(), 54);
+
+        for mode in [EthernetFcs::Present, EthernetFcs::Heuristic] {
+            let mut session = Session::new();
+            session.register(mode);
+            let (rem, dissected) = EthernetII::dissect(&buf[..], &session, None).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(dissected.fcs(), eth.fcs());
+            assert_eq!(dissected.fcs_valid(), Some(true));
+            assert_eq!(dissected.total_len(), 68);
+        }
+
+        let mut session = Session::new();
+        session.register(EthernetFcs::Heuristic);
+        buf[20] ^= 1;
+        let (_, dissected) = EthernetII::dissect(&buf[..], &session, None).unwrap();
+        assert_eq!(dissected.fcs(), None);
+    }
+
+    #[test]
+    fn ethernet_padding() {
+        let mut frame = vec![
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x08, 0x00,
+            // IPv4, total length of 24, experimental protocol
+            0x45, 0x00, 0x00, 0x18, 0x00, 0x00, 0x40, 0x00, 0x40, 0xfd, 0x00, 0x00, 0xc0, 0x00,
+            0x02, 0x01, 0xc0, 0x00, 0x02, 0x02, // Payload, starting with zeros
+            0x00, 0x00, 0xab, 0xcd,
+        ];
+        frame.resize(60, 0);
+        let mut session = Session::new();
+        session.register(EthernetFcs::Heuristic);
+        let mut crc = Crc32::new();
+        crc.write_all(&frame[..]).unwrap();
+        frame.extend_from_slice(&crc.checksum().to_le_bytes());
+
+        let (rem, eth) = EthernetII::dissect(&frame[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(eth.inner_pdu().unwrap().total_len(), 24);
+        assert_eq!(eth.inner_pdu().unwrap().inner_pdu().unwrap().total_len(), 4);
+        assert_eq!(eth.trailer(), &[0u8; 22][..]);
+        assert_eq!(eth.fcs_valid(), Some(true));
+        assert_eq!(eth.total_len(), 64);
+
+        let mut out = Vec::new();
+        eth.serialize(&mut out).unwrap();
+        assert_eq!(out, frame);
+
+        frame[59] = 0xff;
+        let (_, eth) = EthernetII::dissect(&frame[..60], &Session::new(), None).unwrap();
+        assert_eq!(eth.trailer().len(), 22);
+        assert_eq!(eth.trailer()[21], 0xff);
+
+        // A non-zero trailer references the packet buffer
+        let session = Session::new();
+        let buffer = Bytes::copy_from_slice(&frame[..60]);
+        let (_, mut eth) = session
+            .with_packet_buffer(&buffer, || EthernetII::dissect(&buffer[..], &session, None))
+            .unwrap();
+        assert_eq!(eth.trailer().as_ptr(), buffer[38..].as_ptr());
+
+        eth.set_trailer(vec![0xee; 4]);
+        assert_eq!(eth.trailer(), &[0xee; 4][..]);
+        let mut out = Vec::new();
+        eth.serialize(&mut out).unwrap();
+        assert_eq!(&out[..], [&buffer[..38], &[0xee; 4][..]].concat());
+    }
+}
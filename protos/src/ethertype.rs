@@ -1,7 +1,7 @@
 use super::prelude::*;
 use lazy_static::*;
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct Ethertype(pub u16);
@@ -21,6 +21,7 @@ pub struct EthertypeIter {
 
 lazy_static! {
     static ref ETHERTYPE_PDUS: RwLock<HashMap<PduType, Ethertype>> = RwLock::new(HashMap::new());
+    static ref IEEE_802_3_PDUS: RwLock<HashSet<PduType>> = RwLock::new(HashSet::new());
 }
 
 macro_rules! count {
@@ -298,6 +299,12 @@ impl Ethertype {
     pub fn from_pdu<P: Pdu>(pdu: &P) -> Option<Self> {
         ETHERTYPE_PDUS.read().get(&pdu.pdu_type()).copied()
     }
+
+    /// Whether `pdu` is carried in IEEE 802.3 frames, which hold the length
+    /// of the payload in place of an ethertype, such as LLC.
+    pub fn is_length_framed<P: Pdu>(pdu: &P) -> bool {
+        IEEE_802_3_PDUS.read().contains(&pdu.pdu_type())
+    }
 }
 
 impl<const N: usize> EthertypeSet<N> {
@@ -362,6 +369,11 @@ pub fn _register_ethertype_pdu<P: Pdu>(ethertype: Ethertype) {
     }
 }
 
+#[doc(hidden)]
+pub fn _register_ieee_802_3_pdu<P: Pdu>() {
+    IEEE_802_3_PDUS.write().insert(PduType::of::<P>());
+}
+
 #[macro_export]
 macro_rules! register_ethertype_pdu {
     ($pdu:ty, $ethertype:expr) => {
@@ -374,3 +386,16 @@ macro_rules! register_ethertype_pdu {
         }
     };
 }
+
+#[macro_export]
+macro_rules! register_ieee_802_3_pdu {
+    ($pdu:ty) => {
+        $crate::paste::paste! {
+            #[$crate::ctor::ctor]
+            #[allow(non_snake_case)]
+            fn [<__sniffle_registry_ieee_802_3_pdu_ $pdu>]() {
+                $crate::ethertype::_register_ieee_802_3_pdu::<$pdu>();
+            }
+        }
+    };
+}
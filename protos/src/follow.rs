@@ -0,0 +1,966 @@
+//! Following the payload of a TCP or UDP conversation, like Wireshark's
+//! "Follow Stream".
+
+use crate::ipv4::Ipv4;
+use crate::prelude::*;
+use crate::tcp::{flags, Tcp};
+use crate::udp::Udp;
+use futures_core::Stream;
+use sniffle_core::{Error, Ipv4Address, MemoryAccount, Packet, Sniff, Timestamp};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The transport protocol of a followed conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FollowProtocol {
+    Tcp,
+    Udp,
+}
+
+/// The direction payload was sent in. The client is the endpoint that
+/// opened a TCP connection, or that sent the first packet seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FollowDirection {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// Contiguous payload sent in one direction of a followed conversation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowChunk {
+    pub direction: FollowDirection,
+    /// Timestamp of the packet that completed the chunk
+    pub timestamp: Timestamp,
+    pub data: Vec<u8>,
+}
+
+/// Performance statistics of a followed conversation. See `Follow::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlowStats {
+    /// Time from the client's SYN to its ACK of the server's SYN-ACK, if
+    /// the handshake was seen
+    pub handshake_rtt: Option<Duration>,
+    pub client_to_server: FlowDirectionStats,
+    pub server_to_client: FlowDirectionStats,
+}
+
+/// Statistics of the traffic sent in one direction of a followed
+/// conversation.
+///
+/// Round trip times are measured from a TCP segment to the ACK that covers
+/// it, as seen at the point of capture. In the client to server direction,
+/// that is the round trip from the capture point to the server and back.
+/// Retransmitted segments aren't measured, since their ACK can't be matched
+/// to one transmission.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlowDirectionStats {
+    pub packets: u64,
+    /// Payload delivered in order, without retransmitted bytes
+    pub payload_bytes: u64,
+    pub first: Option<Timestamp>,
+    pub last: Option<Timestamp>,
+    /// Smoothed round trip time, as in RFC 6298
+    pub srtt: Option<Duration>,
+    pub rtt_samples: u64,
+    /// Bytes sent that have not been acknowledged yet
+    pub bytes_in_flight: u32,
+    pub max_bytes_in_flight: u32,
+}
+
+/// Sequence number tracking of one direction, for `FlowStats`
+#[derive(Default)]
+struct Timing {
+    /// End of the highest segment sent
+    high_seq: Option<u32>,
+    /// Highest sequence number acknowledged by the other side
+    acked: Option<u32>,
+    /// End, time sent, and whether it was retransmitted, of segments that
+    /// haven't been acknowledged yet
+    unacked: VecDeque<(u32, Timestamp, bool)>,
+}
+
+/// Maximum number of unacknowledged segments timed per direction
+const MAX_UNACKED: usize = 256;
+
+#[derive(Default)]
+struct Reassembly {
+    next_seq: Option<u32>,
+    pending: Vec<(u32, Timestamp, Vec<u8>)>,
+    buffered: usize,
+    fin: bool,
+}
+
+/// Reassembles the payload of one TCP or UDP conversation over IPv4 from
+/// the packets fed to it.
+///
+/// TCP segments are put back in sequence order, with retransmitted bytes
+/// dropped. Segments that arrive ahead of a gap are buffered, up to
+/// `max_buffer` bytes per direction, until the gap is filled or `finish`
+/// is called. UDP datagrams are passed through in the order they arrive.
+pub struct Follow {
+    protocol: FollowProtocol,
+    client: (Ipv4Address, u16),
+    server: (Ipv4Address, u16),
+    dirs: [Reassembly; 2],
+    reset: bool,
+    max_buffer: usize,
+    account: Option<MemoryAccount>,
+    stats: FlowStats,
+    timing: [Timing; 2],
+    syn_time: Option<Timestamp>,
+    syn_ack_seen: bool,
+}
+
+struct Segment<'a> {
+    protocol: FollowProtocol,
+    src: (Ipv4Address, u16),
+    dst: (Ipv4Address, u16),
+    tcp: Option<&'a Tcp>,
+    payload: Option<&'a AnyPdu>,
+}
+
+impl<'a> Segment<'a> {
+    fn of(packet: &'a Packet) -> Option<Self> {
+        let mut ipv4 = None;
+        let mut layer = Some(packet.pdu());
+        while let Some(pdu) = layer {
+            if let Some(ip) = pdu.downcast_ref::<Ipv4>() {
+                ipv4 = Some(ip);
+            } else if let Some(tcp) = pdu.downcast_ref::<Tcp>() {
+                let ip = ipv4?;
+                return Some(Self {
+                    protocol: FollowProtocol::Tcp,
+                    src: (ip.src_address(), tcp.src_port()),
+                    dst: (ip.dst_address(), tcp.dst_port()),
+                    tcp: Some(tcp),
+                    payload: tcp.inner_pdu(),
+                });
+            } else if let Some(udp) = pdu.downcast_ref::<Udp>() {
+                let ip = ipv4?;
+                return Some(Self {
+                    protocol: FollowProtocol::Udp,
+                    src: (ip.src_address(), udp.src_port()),
+                    dst: (ip.dst_address(), udp.dst_port()),
+                    tcp: None,
+                    payload: udp.inner_pdu(),
+                });
+            }
+            layer = pdu.inner_pdu();
+        }
+        None
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        if let Some(payload) = self.payload {
+            // Serializing into a Vec can't fail
+            let _ = payload.serialize(&mut data);
+        }
+        data
+    }
+}
+
+impl Reassembly {
+    /// Adds a segment with sequence number `seq`, returning the data that
+    /// became contiguous.
+    fn push(
+        &mut self,
+        seq: u32,
+        ts: Timestamp,
+        data: Vec<u8>,
+        max_buffer: usize,
+        account: Option<&MemoryAccount>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let next = *self.next_seq.get_or_insert(seq);
+        let ahead = seq.wrapping_sub(next) as i32;
+        if ahead > 0 {
+            if !data.is_empty() {
+                if self.buffered + data.len() > max_buffer {
+                    return Err(Error::StreamBufferFull);
+                }
+                if let Some(account) = account {
+                    if !account.try_reserve(data.len()) {
+                        account.record_rejection();
+                        return Err(Error::StreamBufferFull);
+                    }
+                }
+                self.buffered += data.len();
+                self.pending.push((seq, ts, data));
+            }
+            return Ok(None);
+        }
+
+        let mut out = trim(next, seq, data);
+        let mut next = next.wrapping_add(out.len() as u32);
+        while let Some(idx) = self
+            .pending
+            .iter()
+            .position(|(seq, _, _)| seq.wrapping_sub(next) as i32 <= 0)
+        {
+            let (seq, _, data) = self.pending.swap_remove(idx);
+            self.buffered -= data.len();
+            if let Some(account) = account {
+                account.release(data.len());
+            }
+            let data = trim(next, seq, data);
+            next = next.wrapping_add(data.len() as u32);
+            out.extend_from_slice(&data[..]);
+        }
+        self.next_seq = Some(next);
+        Ok(if out.is_empty() { None } else { Some(out) })
+    }
+
+    /// Removes all buffered segments in sequence order, skipping gaps.
+    fn drain(&mut self, account: Option<&MemoryAccount>) -> Vec<(Timestamp, Vec<u8>)> {
+        let base = self.next_seq.unwrap_or(0);
+        let mut pending = std::mem::take(&mut self.pending);
+        if let Some(account) = account {
+            account.release(self.buffered);
+        }
+        self.buffered = 0;
+        pending.sort_by_key(|(seq, _, _)| seq.wrapping_sub(base));
+        let mut out = Vec::new();
+        let mut next = base;
+        for (seq, ts, data) in pending {
+            if seq.wrapping_sub(next) as i32 > 0 {
+                next = seq;
+            }
+            let data = trim(next, seq, data);
+            next = next.wrapping_add(data.len() as u32);
+            if !data.is_empty() {
+                out.push((ts, data));
+            }
+        }
+        self.next_seq = Some(next);
+        out
+    }
+}
+
+/// True if sequence number `a` comes before `b`
+fn seq_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+impl Timing {
+    fn bytes_in_flight(&self) -> u32 {
+        match (self.high_seq, self.acked) {
+            (Some(high), Some(acked)) if seq_before(acked, high) => high.wrapping_sub(acked),
+            _ => 0,
+        }
+    }
+}
+
+impl FlowDirectionStats {
+    /// Time between the first and last packets
+    pub fn duration(&self) -> Duration {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last.duration_since(first).unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Payload delivered per second, or `None` if the packets span no time.
+    pub fn goodput(&self) -> Option<f64> {
+        let secs = self.duration().as_secs_f64();
+        if secs > 0.0 {
+            Some(self.payload_bytes as f64 / secs)
+        } else {
+            None
+        }
+    }
+
+    fn add_packet(&mut self, ts: Timestamp) {
+        self.packets += 1;
+        self.first = Some(self.first.map_or(ts, |first| first.min(ts)));
+        self.last = Some(self.last.map_or(ts, |last| last.max(ts)));
+    }
+
+    fn add_rtt(&mut self, rtt: Duration) {
+        self.rtt_samples += 1;
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => srtt * 7 / 8 + rtt / 8,
+            None => rtt,
+        });
+    }
+}
+
+impl FlowStats {
+    pub fn direction(&self, direction: FollowDirection) -> &FlowDirectionStats {
+        match direction {
+            FollowDirection::ClientToServer => &self.client_to_server,
+            FollowDirection::ServerToClient => &self.server_to_client,
+        }
+    }
+
+    fn direction_mut(&mut self, direction: FollowDirection) -> &mut FlowDirectionStats {
+        match direction {
+            FollowDirection::ClientToServer => &mut self.client_to_server,
+            FollowDirection::ServerToClient => &mut self.server_to_client,
+        }
+    }
+}
+
+impl FollowDirection {
+    fn reverse(self) -> Self {
+        match self {
+            Self::ClientToServer => Self::ServerToClient,
+            Self::ServerToClient => Self::ClientToServer,
+        }
+    }
+}
+
+/// Removes the bytes of `data`, starting at `seq`, that come before `next`.
+fn trim(next: u32, seq: u32, mut data: Vec<u8>) -> Vec<u8> {
+    let behind = next.wrapping_sub(seq) as usize;
+    if behind >= data.len() {
+        data.clear();
+    } else {
+        data.drain(..behind);
+    }
+    data
+}
+
+impl Follow {
+    /// Starts following the conversation `packet` belongs to. Returns
+    /// `None` if the packet isn't TCP or UDP over IPv4. The packet itself
+    /// isn't consumed; pass it to `push` to include its payload.
+    pub fn new(packet: &Packet) -> Option<Self> {
+        Self::with_max_buffer(packet, 0x100000)
+    }
+
+    pub fn with_max_buffer(packet: &Packet, max_buffer: usize) -> Option<Self> {
+        let seg = Segment::of(packet)?;
+        let syn_ack = seg
+            .tcp
+            .map(|tcp| tcp.has_flags(flags::SYN | flags::ACK))
+            .unwrap_or(false);
+        let (client, server) = if syn_ack {
+            (seg.dst, seg.src)
+        } else {
+            (seg.src, seg.dst)
+        };
+        Some(Self {
+            protocol: seg.protocol,
+            client,
+            server,
+            dirs: Default::default(),
+            reset: false,
+            max_buffer,
+            account: None,
+            stats: FlowStats::default(),
+            timing: Default::default(),
+            syn_time: None,
+            syn_ack_seen: false,
+        })
+    }
+
+    /// Charges segments buffered ahead of gaps to `account`, usually the
+    /// `MemoryBudget::REASSEMBLY` account of a session, in addition to the
+    /// `max_buffer` limit. A segment that doesn't fit in either fails with
+    /// `Error::StreamBufferFull`.
+    pub fn with_memory(mut self, account: MemoryAccount) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    pub fn protocol(&self) -> FollowProtocol {
+        self.protocol
+    }
+
+    /// The address and port of the client.
+    pub fn client(&self) -> (Ipv4Address, u16) {
+        self.client
+    }
+
+    /// The address and port of the server.
+    pub fn server(&self) -> (Ipv4Address, u16) {
+        self.server
+    }
+
+    /// The direction `packet` travels in, or `None` if it isn't part of
+    /// the conversation.
+    pub fn direction(&self, packet: &Packet) -> Option<FollowDirection> {
+        Segment::of(packet).and_then(|seg| self.segment_direction(&seg))
+    }
+
+    fn segment_direction(&self, seg: &Segment<'_>) -> Option<FollowDirection> {
+        if seg.protocol != self.protocol {
+            None
+        } else if seg.src == self.client && seg.dst == self.server {
+            Some(FollowDirection::ClientToServer)
+        } else if seg.src == self.server && seg.dst == self.client {
+            Some(FollowDirection::ServerToClient)
+        } else {
+            None
+        }
+    }
+
+    /// Round trip time, bytes in flight, and goodput of the packets pushed
+    /// so far.
+    pub fn stats(&self) -> FlowStats {
+        self.stats
+    }
+
+    /// Updates the statistics with the segment `tcp`, sent in `direction`
+    /// with `seg_len` bytes of payload, SYN, and FIN.
+    fn track(&mut self, direction: FollowDirection, tcp: &Tcp, seg_len: u32, ts: Timestamp) {
+        let reverse = direction.reverse();
+        let syn = tcp.has_flags(flags::SYN);
+        let ack = tcp.has_flags(flags::ACK);
+        if direction == FollowDirection::ClientToServer {
+            if syn && !ack {
+                self.syn_time = Some(ts);
+            } else if !syn && ack && self.syn_ack_seen && self.stats.handshake_rtt.is_none() {
+                self.stats.handshake_rtt = self.syn_time.and_then(|syn| ts.duration_since(syn));
+            }
+        } else if syn && ack {
+            self.syn_ack_seen = true;
+        }
+
+        let timing = &mut self.timing[direction as usize];
+        let seq = tcp.seq();
+        let end = seq.wrapping_add(seg_len);
+        if timing.acked.is_none() {
+            timing.acked = Some(seq);
+        }
+        if seg_len > 0 {
+            match timing.high_seq {
+                Some(high) if !seq_before(high, end) => {
+                    for (unacked_end, _, retransmitted) in timing.unacked.iter_mut() {
+                        if seq_before(seq, *unacked_end) {
+                            *retransmitted = true;
+                        }
+                    }
+                }
+                _ => {
+                    timing.high_seq = Some(end);
+                    if timing.unacked.len() < MAX_UNACKED {
+                        timing.unacked.push_back((end, ts, false));
+                    }
+                }
+            }
+        }
+
+        if ack {
+            let ack = tcp.ack();
+            let timing = &mut self.timing[reverse as usize];
+            if timing.acked.is_none_or(|acked| seq_before(acked, ack)) {
+                timing.acked = Some(ack);
+            }
+            let mut rtt = None;
+            while let Some(&(end, sent, retransmitted)) = timing.unacked.front() {
+                if seq_before(ack, end) {
+                    break;
+                }
+                let _ = timing.unacked.pop_front();
+                rtt = if retransmitted {
+                    None
+                } else {
+                    ts.duration_since(sent)
+                };
+            }
+            if let Some(rtt) = rtt {
+                self.stats.direction_mut(reverse).add_rtt(rtt);
+            }
+        }
+
+        for direction in [direction, reverse] {
+            let in_flight = self.timing[direction as usize].bytes_in_flight();
+            let stats = self.stats.direction_mut(direction);
+            stats.bytes_in_flight = in_flight;
+            stats.max_bytes_in_flight = stats.max_bytes_in_flight.max(in_flight);
+        }
+    }
+
+    /// True once a TCP conversation has been reset, or closed in both
+    /// directions. UDP conversations are never closed.
+    pub fn is_closed(&self) -> bool {
+        self.reset || (self.dirs[0].fin && self.dirs[1].fin)
+    }
+
+    /// Feeds a packet, calling `chunks` with any payload that became
+    /// contiguous. Packets from other conversations are ignored.
+    pub fn push<F>(&mut self, packet: &Packet, chunks: F) -> Result<(), Error>
+    where
+        F: FnMut(FollowChunk),
+    {
+        let mut chunks = chunks;
+        let seg = match Segment::of(packet) {
+            Some(seg) => seg,
+            None => return Ok(()),
+        };
+        let direction = match self.segment_direction(&seg) {
+            Some(direction) => direction,
+            None => return Ok(()),
+        };
+        let timestamp = packet.precise_timestamp();
+        let data = seg.payload();
+        self.stats.direction_mut(direction).add_packet(timestamp);
+
+        let tcp = match seg.tcp {
+            Some(tcp) => tcp,
+            None => {
+                if !data.is_empty() {
+                    self.stats.direction_mut(direction).payload_bytes += data.len() as u64;
+                    chunks(FollowChunk {
+                        direction,
+                        timestamp,
+                        data,
+                    });
+                }
+                return Ok(());
+            }
+        };
+
+        let seg_len =
+            data.len() as u32 + tcp.has_flags(flags::SYN) as u32 + tcp.has_flags(flags::FIN) as u32;
+        self.track(direction, tcp, seg_len, timestamp);
+
+        let dir = &mut self.dirs[direction as usize];
+        let mut seq = tcp.seq();
+        if tcp.has_flags(flags::SYN) {
+            // The SYN consumes one sequence number before the data
+            seq = seq.wrapping_add(1);
+            dir.next_seq = Some(seq);
+        }
+        if let Some(data) =
+            dir.push(seq, timestamp, data, self.max_buffer, self.account.as_ref())?
+        {
+            self.stats.direction_mut(direction).payload_bytes += data.len() as u64;
+            chunks(FollowChunk {
+                direction,
+                timestamp,
+                data,
+            });
+        }
+        if tcp.has_flags(flags::FIN) {
+            dir.fin = true;
+        }
+        if tcp.has_flags(flags::RST) {
+            self.reset = true;
+        }
+        Ok(())
+    }
+
+    /// Signals the end of the capture, calling `chunks` with payload still
+    /// buffered behind gaps, in sequence order.
+    pub fn finish<F>(&mut self, chunks: F)
+    where
+        F: FnMut(FollowChunk),
+    {
+        let mut chunks = chunks;
+        for direction in [
+            FollowDirection::ClientToServer,
+            FollowDirection::ServerToClient,
+        ] {
+            let dir = &mut self.dirs[direction as usize];
+            for (timestamp, data) in dir.drain(self.account.as_ref()) {
+                self.stats.direction_mut(direction).payload_bytes += data.len() as u64;
+                chunks(FollowChunk {
+                    direction,
+                    timestamp,
+                    data,
+                });
+            }
+        }
+    }
+}
+
+impl Drop for Follow {
+    fn drop(&mut self) {
+        if let Some(account) = self.account.as_ref() {
+            for dir in self.dirs.iter() {
+                account.release(dir.buffered);
+            }
+        }
+    }
+}
+
+/// Reads packets from a sniffer, returning the payload of one conversation.
+/// See `SniffFollowExt::follow`.
+///
+/// The chunks of payload are read with `next_chunk`, or as a `Stream`. The
+/// stream ends once the sniffer runs out of packets or the TCP connection
+/// is closed, or after yielding an error.
+pub struct FollowSniffer<'s, S: Sniff + ?Sized> {
+    sniffer: Option<&'s mut S>,
+    pending: Option<PendingSniff<'s, S>>,
+    follow: Follow,
+    chunks: VecDeque<FollowChunk>,
+    done: bool,
+}
+
+type PendingSniff<'s, S> =
+    Pin<Box<dyn Future<Output = (&'s mut S, Result<Option<Packet>, Error>)> + Send + 's>>;
+
+impl<'s, S: Sniff + ?Sized> FollowSniffer<'s, S> {
+    pub fn follow(&self) -> &Follow {
+        &self.follow
+    }
+
+    /// Returns the next chunk of payload, or `None` once the sniffer runs
+    /// out of packets or the TCP connection is closed.
+    pub async fn next_chunk(&mut self) -> Result<Option<FollowChunk>, Error> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+            .await
+            .transpose()
+    }
+
+    /// Feeds a packet read from the sniffer, or `None` at the end of the
+    /// packets, to the reassembly.
+    fn feed(&mut self, packet: Option<Packet>) -> Result<(), Error> {
+        let chunks = &mut self.chunks;
+        match packet {
+            Some(packet) => self.follow.push(&packet, |chunk| chunks.push_back(chunk))?,
+            None => self.done = true,
+        }
+        if self.follow.is_closed() {
+            self.done = true;
+        }
+        if self.done {
+            self.follow.finish(|chunk| chunks.push_back(chunk));
+        }
+        Ok(())
+    }
+
+    /// Reads the rest of the conversation, returning the payload sent in
+    /// each direction, concatenated.
+    pub async fn collect(mut self) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let mut client = Vec::new();
+        let mut server = Vec::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            match chunk.direction {
+                FollowDirection::ClientToServer => client.extend_from_slice(&chunk.data[..]),
+                FollowDirection::ServerToClient => server.extend_from_slice(&chunk.data[..]),
+            }
+        }
+        Ok((client, server))
+    }
+}
+
+/// Adds `follow` to sniffers, for reading the payload of one conversation
+/// from a live capture or a capture file.
+pub trait SniffFollowExt: Sniff {
+    /// Follows the conversation `packet` belongs to, starting with
+    /// `packet` itself, through the packets read from `self` afterwards.
+    /// The returned `FollowSniffer` is a `Stream` of the reassembled chunks
+    /// of payload. Returns `None` if the packet isn't TCP or UDP over IPv4.
+    ///
+    /// ```ignore
+    /// let mut chunks = sniffer.follow(&packet).unwrap();
+    /// while let Some(chunk) = chunks.next().await {
+    ///     let chunk = chunk?;
+    ///     println!("{:?}: {} bytes", chunk.direction, chunk.data.len());
+    /// }
+    /// ```
+    fn follow(&mut self, packet: &Packet) -> Option<FollowSniffer<'_, Self>> {
+        let mut follow = Follow::new(packet)?;
+        let mut chunks = VecDeque::new();
+        follow.push(packet, |chunk| chunks.push_back(chunk)).ok()?;
+        Some(FollowSniffer {
+            sniffer: Some(self),
+            pending: None,
+            follow,
+            chunks,
+            done: false,
+        })
+    }
+}
+
+impl<S: Sniff + ?Sized> SniffFollowExt for S {}
+
+impl<S: Sniff + ?Sized> Stream for FollowSniffer<'_, S> {
+    type Item = Result<FollowChunk, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(chunk) = self.chunks.pop_front() {
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+            if self.done {
+                return Poll::Ready(None);
+            }
+            // The sniffer is moved into the read while it is pending
+            let this = &mut *self;
+            let pending = match this.pending.as_mut() {
+                Some(pending) => pending,
+                None => {
+                    let sniffer = this.sniffer.take().expect("sniffer is being read");
+                    this.pending.insert(Box::pin(async move {
+                        let res = sniffer.sniff().await;
+                        (sniffer, res)
+                    }))
+                }
+            };
+            let (sniffer, res) = match pending.as_mut().poll(cx) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => return Poll::Pending,
+            };
+            self.pending = None;
+            self.sniffer = Some(sniffer);
+            if let Err(e) = res.and_then(|packet| self.feed(packet)) {
+                self.done = true;
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle_core::{MemoryBudget, Packet, RawPdu};
+
+    fn tcp(src: u16, dst: u16, seq: u32, flag: u16, data: &[u8]) -> Packet {
+        let (src_ip, dst_ip) = if src == 5000 {
+            ([10, 0, 0, 1], [10, 0, 0, 2])
+        } else {
+            ([10, 0, 0, 2], [10, 0, 0, 1])
+        };
+        let mut ipv4 = Ipv4::with_addresses(src_ip.into(), dst_ip.into());
+        let mut tcp = Tcp::with_ports(src, dst);
+        *tcp.seq_mut() = seq;
+        tcp.set_flags(flag, true);
+        if !data.is_empty() {
+            tcp.set_inner_pdu(RawPdu::new(data.to_vec()));
+        }
+        ipv4.set_inner_pdu(tcp);
+        Packet::new(Timestamp::new(seq as i64, 0), ipv4, None, None, None)
+    }
+
+    #[test]
+    fn follow_tcp_out_of_order() {
+        let packets = [
+            tcp(5000, 80, 100, flags::SYN, b""),
+            tcp(80, 5000, 900, flags::SYN | flags::ACK, b""),
+            tcp(5000, 80, 101, flags::ACK, b"GET "),
+            tcp(5000, 80, 107, flags::ACK, b"HTTP"),
+            tcp(5000, 80, 105, flags::ACK, b"/ "),
+            tcp(5000, 80, 103, flags::ACK, b"T /"), // overlapping retransmission
+            tcp(80, 5000, 901, flags::ACK, b"OK"),
+            tcp(4000, 80, 1, flags::ACK, b"other"),
+            tcp(5000, 80, 111, flags::FIN | flags::ACK, b""),
+            tcp(80, 5000, 903, flags::FIN | flags::ACK, b""),
+        ];
+        let mut follow = Follow::new(&packets[1]).unwrap();
+        assert_eq!(follow.client(), ([10, 0, 0, 1].into(), 5000));
+        let mut out = Vec::new();
+        for packet in packets.iter() {
+            follow.push(packet, |chunk| out.push(chunk)).unwrap();
+        }
+        assert!(follow.is_closed());
+        let out: Vec<_> = out.into_iter().map(|c| (c.direction, c.data)).collect();
+        assert_eq!(
+            out,
+            [
+                (FollowDirection::ClientToServer, b"GET ".to_vec()),
+                (FollowDirection::ClientToServer, b"/ HTTP".to_vec()),
+                (FollowDirection::ServerToClient, b"OK".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn follow_stats() {
+        let tcp = |ms: i64, to_server: bool, seq: u32, ack: u32, flag: u16, data: &[u8]| {
+            let (src, dst) = if to_server { (5000, 80) } else { (80, 5000) };
+            let mut packet = tcp(src, dst, seq, flag, data);
+            *packet.find_mut::<Tcp>().unwrap().ack_mut() = ack;
+            Packet::new(
+                Timestamp::from_nanos(ms as i128 * 1_000_000),
+                packet.into_pdu(),
+                None,
+                None,
+                None,
+            )
+        };
+        let packets = [
+            tcp(0, true, 100, 0, flags::SYN, b""),
+            tcp(40, false, 900, 101, flags::SYN | flags::ACK, b""),
+            tcp(50, true, 101, 901, flags::ACK, b""),
+            tcp(60, true, 101, 901, flags::ACK, b"abcd"),
+            tcp(70, true, 105, 901, flags::ACK, b"efgh"),
+            tcp(116, false, 901, 105, flags::ACK, b""),
+            tcp(120, true, 105, 901, flags::ACK, b"efgh"), // retransmission
+            tcp(160, false, 901, 109, flags::ACK, b""),
+        ];
+        let mut follow = Follow::new(&packets[0]).unwrap();
+        let ms = Duration::from_millis;
+        for (idx, packet) in packets.iter().enumerate() {
+            follow.push(packet, |_| ()).unwrap();
+            let in_flight = follow.stats().client_to_server.bytes_in_flight;
+            match idx {
+                4 => assert_eq!(in_flight, 8),
+                5 | 6 => assert_eq!(in_flight, 4),
+                7 => assert_eq!(in_flight, 0),
+                _ => {}
+            }
+        }
+
+        let stats = follow.stats();
+        assert_eq!(stats.handshake_rtt, Some(ms(50)));
+        let c2s = stats.direction(FollowDirection::ClientToServer);
+        assert_eq!((c2s.packets, c2s.payload_bytes), (5, 8));
+        // SYN to SYN-ACK, then the first data segment, but not the
+        // retransmitted one
+        assert_eq!(c2s.rtt_samples, 2);
+        assert_eq!(c2s.srtt, Some(ms(40) * 7 / 8 + ms(56) / 8));
+        assert_eq!(c2s.max_bytes_in_flight, 8);
+        assert_eq!(c2s.duration(), ms(120));
+        assert_eq!(c2s.goodput(), Some(8.0 / 0.12));
+
+        let s2c = stats.server_to_client;
+        assert_eq!((s2c.packets, s2c.payload_bytes), (3, 0));
+        assert_eq!((s2c.rtt_samples, s2c.srtt), (1, Some(ms(10))));
+    }
+
+    #[test]
+    fn follow_gap_flushed_on_finish() {
+        let mut follow = Follow::new(&tcp(5000, 80, 1, flags::ACK, b"ab")).unwrap();
+        let mut out = Vec::new();
+        follow
+            .push(&tcp(5000, 80, 1, flags::ACK, b"ab"), |c| out.push(c.data))
+            .unwrap();
+        follow
+            .push(&tcp(5000, 80, 10, flags::ACK, b"yz"), |c| out.push(c.data))
+            .unwrap();
+        assert_eq!(out, [b"ab".to_vec()]);
+        follow.finish(|c| out.push(c.data));
+        assert_eq!(out, [b"ab".to_vec(), b"yz".to_vec()]);
+
+        let mut follow = Follow::with_max_buffer(&tcp(5000, 80, 1, flags::ACK, b""), 1).unwrap();
+        follow
+            .push(&tcp(5000, 80, 1, flags::ACK, b""), |_| ())
+            .unwrap();
+        assert!(follow
+            .push(&tcp(5000, 80, 10, flags::ACK, b"yz"), |_| ())
+            .is_err());
+    }
+
+    #[test]
+    fn follow_memory_budget() {
+        let session = sniffle_core::Session::builder()
+            .default_dissectors(false)
+            .subsystem_memory_limit(MemoryBudget::REASSEMBLY, 3)
+            .build();
+        let account = session.memory().account(MemoryBudget::REASSEMBLY);
+        let mut follow = Follow::new(&tcp(5000, 80, 1, flags::ACK, b""))
+            .unwrap()
+            .with_memory(account.clone());
+        follow
+            .push(&tcp(5000, 80, 1, flags::ACK, b""), |_| ())
+            .unwrap();
+        follow
+            .push(&tcp(5000, 80, 10, flags::ACK, b"yz"), |_| ())
+            .unwrap();
+        assert!(follow
+            .push(&tcp(5000, 80, 20, flags::ACK, b"abc"), |_| ())
+            .is_err());
+        let stats = account.stats();
+        assert_eq!((stats.used, stats.rejected), (2, 1));
+        drop(follow);
+        assert_eq!(account.stats().used, 0);
+    }
+
+    #[test]
+    fn follow_udp() {
+        let udp = |src: u16, dst: u16, data: &[u8]| {
+            let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+            if src != 5000 {
+                ipv4 = Ipv4::with_addresses([10, 0, 0, 2].into(), [10, 0, 0, 1].into());
+            }
+            let mut udp = Udp::with_ports(src, dst);
+            udp.set_inner_pdu(RawPdu::new(data.to_vec()));
+            ipv4.set_inner_pdu(udp);
+            Packet::new(Timestamp::new(0, 0), ipv4, None, None, None)
+        };
+        let query = udp(5000, 53, b"query");
+        let mut follow = Follow::new(&query).unwrap();
+        assert_eq!(follow.protocol(), FollowProtocol::Udp);
+        let mut out = Vec::new();
+        for packet in [query, udp(53, 5000, b"answer"), udp(5001, 53, b"other")] {
+            follow
+                .push(&packet, |c| out.push((c.direction, c.data)))
+                .unwrap();
+        }
+        assert_eq!(
+            out,
+            [
+                (FollowDirection::ClientToServer, b"query".to_vec()),
+                (FollowDirection::ServerToClient, b"answer".to_vec()),
+            ]
+        );
+    }
+
+    struct Packets(VecDeque<Result<Packet, Error>>);
+
+    #[async_trait::async_trait]
+    impl Sniff for Packets {
+        async fn sniff(&mut self) -> Result<Option<Packet>, Error> {
+            self.0.pop_front().transpose()
+        }
+    }
+
+    async fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[test]
+    fn follow_stream() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let first = tcp(5000, 80, 1, flags::ACK, b"ab");
+            let mut sniffer = Packets(
+                [
+                    tcp(80, 5000, 50, flags::ACK, b"cd"),
+                    tcp(4000, 80, 1, flags::ACK, b"other"),
+                    tcp(5000, 80, 3, flags::FIN | flags::ACK, b"e"),
+                    tcp(80, 5000, 52, flags::FIN | flags::ACK, b""),
+                    tcp(5000, 80, 5, flags::ACK, b"after close"),
+                ]
+                .into_iter()
+                .map(Ok)
+                .collect(),
+            );
+            let mut stream = sniffer.follow(&first).unwrap();
+            let mut out = Vec::new();
+            while let Some(chunk) = next(&mut stream).await {
+                let chunk = chunk.unwrap();
+                out.push((chunk.direction, chunk.data));
+            }
+            assert_eq!(
+                out,
+                [
+                    (FollowDirection::ClientToServer, b"ab".to_vec()),
+                    (FollowDirection::ServerToClient, b"cd".to_vec()),
+                    (FollowDirection::ClientToServer, b"e".to_vec()),
+                ]
+            );
+            assert!(next(&mut stream).await.is_none());
+            drop(stream);
+            // Reading stopped once both sides sent a FIN
+            assert_eq!(sniffer.0.len(), 1);
+
+            let mut sniffer = Packets(
+                [
+                    Err(Error::MalformedCapture),
+                    Ok(tcp(5000, 80, 3, flags::ACK, b"c")),
+                ]
+                .into_iter()
+                .collect(),
+            );
+            let mut stream = sniffer.follow(&first).unwrap();
+            assert_eq!(next(&mut stream).await.unwrap().unwrap().data, b"ab");
+            assert!(matches!(
+                next(&mut stream).await,
+                Some(Err(Error::MalformedCapture))
+            ));
+            assert!(next(&mut stream).await.is_none());
+        });
+    }
+}
@@ -0,0 +1,372 @@
+use super::ethernet_ii::{EthernetII, EthertypeDissectorTable};
+use super::ethertype::Ethertype;
+use super::ip_proto::IpProto;
+use super::ipv4::IpProtoDissectorTable;
+use crate::prelude::*;
+use checksum::U16OnesComplement;
+use nom::{
+    combinator::{cond, flat_map, map},
+    sequence::tuple,
+};
+
+/// Generic Routing Encapsulation, as defined by RFC 2784 and RFC 2890, and
+/// the enhanced GRE header used by PPTP (RFC 2637, version 1).
+///
+/// The checksum, key, sequence number, and acknowledgment number fields are
+/// optional. The corresponding flag bits are set when serializing according
+/// to which fields are present.
+#[derive(Debug, Clone)]
+pub struct Gre {
+    base: BasePdu,
+    flags: u16,
+    proto: Ethertype,
+    chksum: Option<u16>,
+    reserved1: u16,
+    key: Option<u32>,
+    seq: Option<u32>,
+    ack: Option<u32>,
+}
+
+const FLAG_CHECKSUM: u16 = 0x8000;
+const FLAG_ROUTING: u16 = 0x4000;
+const FLAG_KEY: u16 = 0x2000;
+const FLAG_SEQ: u16 = 0x1000;
+const FLAG_ACK: u16 = 0x0080;
+const VERSION_MASK: u16 = 0x0007;
+const PRESENCE_MASK: u16 = FLAG_CHECKSUM | FLAG_ROUTING | FLAG_KEY | FLAG_SEQ | FLAG_ACK;
+
+impl Gre {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            flags: 0,
+            proto: Ethertype(0),
+            chksum: None,
+            reserved1: 0,
+            key: None,
+            seq: None,
+            ack: None,
+        }
+    }
+
+    /// The flags and version word, with the presence bits set according to
+    /// the optional fields.
+    pub fn flags(&self) -> u16 {
+        let mut flags = self.flags & !PRESENCE_MASK;
+        if self.chksum.is_some() {
+            flags |= FLAG_CHECKSUM;
+        }
+        if self.key.is_some() {
+            flags |= FLAG_KEY;
+        }
+        if self.seq.is_some() {
+            flags |= FLAG_SEQ;
+        }
+        if self.ack.is_some() {
+            flags |= FLAG_ACK;
+        }
+        flags
+    }
+
+    /// Flag bits other than the presence bits, including the version
+    pub fn flags_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.flags
+    }
+
+    pub fn version(&self) -> u8 {
+        (self.flags & VERSION_MASK) as u8
+    }
+
+    pub fn protocol(&self) -> Ethertype {
+        self.proto
+    }
+
+    pub fn protocol_mut(&mut self) -> &mut Ethertype {
+        self.base.mark_dirty();
+        &mut self.proto
+    }
+
+    pub fn update_protocol(&mut self) {
+        self.base.mark_dirty();
+        let proto = self
+            .inner_pdu()
+            .map(|inner| Ethertype::from_pdu(inner).unwrap_or(self.proto))
+            .unwrap_or(self.proto);
+        self.proto = proto;
+    }
+
+    pub fn checksum(&self) -> Option<u16> {
+        self.chksum
+    }
+
+    pub fn checksum_mut(&mut self) -> &mut Option<u16> {
+        self.base.mark_dirty();
+        &mut self.chksum
+    }
+
+    /// Computes the checksum over the GRE header and payload.
+    pub fn calc_checksum(&self) -> u16 {
+        let mut tmp = self.clone();
+        tmp.chksum = Some(0);
+        if let Some(inner) = self.inner_pdu() {
+            tmp.set_inner_pdu(inner.clone());
+        }
+        let mut acc = U16OnesComplement::new();
+        let _ = tmp.serialize(&mut acc);
+        acc.checksum()
+    }
+
+    /// Checks the checksum, or returns `None` if there is no checksum.
+    pub fn checksum_valid(&self) -> Option<bool> {
+        self.chksum.map(|chksum| chksum == self.calc_checksum())
+    }
+
+    /// Recomputes the checksum, if there is one.
+    pub fn update_checksum(&mut self) {
+        self.base.mark_dirty();
+        if self.chksum.is_some() {
+            self.chksum = Some(self.calc_checksum());
+        }
+    }
+
+    /// The key field. For version 1 (PPTP) headers, this holds the payload
+    /// length in the upper 16 bits and the call ID in the lower 16 bits.
+    pub fn key(&self) -> Option<u32> {
+        self.key
+    }
+
+    pub fn key_mut(&mut self) -> &mut Option<u32> {
+        self.base.mark_dirty();
+        &mut self.key
+    }
+
+    pub fn sequence(&self) -> Option<u32> {
+        self.seq
+    }
+
+    pub fn sequence_mut(&mut self) -> &mut Option<u32> {
+        self.base.mark_dirty();
+        &mut self.seq
+    }
+
+    /// The acknowledgment number, only used by version 1 (PPTP) headers
+    pub fn ack(&self) -> Option<u32> {
+        self.ack
+    }
+
+    pub fn ack_mut(&mut self) -> &mut Option<u32> {
+        self.base.mark_dirty();
+        &mut self.ack
+    }
+}
+
+impl Dissect for Gre {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            tuple((u16::decode_be, map(u16::decode_be, Ethertype))),
+            |(flags, proto)| {
+                let parent = parent.clone();
+                move |buf: &'a [u8]| {
+                    // Source routing (RFC 1701) is deprecated and not supported
+                    if (flags & FLAG_ROUTING) != 0 || (flags & VERSION_MASK) > 1 {
+                        return Err(nom::Err::Error(DissectError::Malformed));
+                    }
+                    let (buf, (chksum, key, seq, ack)) = tuple((
+                        cond(
+                            (flags & FLAG_CHECKSUM) != 0,
+                            tuple((u16::decode_be, u16::decode_be)),
+                        ),
+                        cond((flags & FLAG_KEY) != 0, u32::decode_be),
+                        cond((flags & FLAG_SEQ) != 0, u32::decode_be),
+                        cond((flags & FLAG_ACK) != 0, u32::decode_be),
+                    ))(buf)?;
+                    let mut gre = Self {
+                        base: BasePdu::default(),
+                        flags: flags & !PRESENCE_MASK,
+                        proto,
+                        chksum: chksum.map(|(chksum, _)| chksum),
+                        reserved1: chksum.map(|(_, reserved1)| reserved1).unwrap_or(0),
+                        key,
+                        seq,
+                        ack,
+                    };
+                    if buf.is_empty() {
+                        return Ok((buf, gre));
+                    }
+                    let (buf, inner) = session
+                        .table_dissector::<EthertypeDissectorTable>(
+                            &gre.proto,
+                            Some(TempPdu::new(&gre, &parent)),
+                        )
+                        .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                        .parse(buf)?;
+                    gre.set_inner_pdu(inner);
+                    Ok((buf, gre))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for Gre {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        let opt_len = |present: bool| if present { 4 } else { 0 };
+        4 + opt_len(self.chksum.is_some())
+            + opt_len(self.key.is_some())
+            + opt_len(self.seq.is_some())
+            + opt_len(self.ack.is_some())
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode_be(&self.flags())?.encode_be(&self.proto.0)?;
+        if let Some(chksum) = self.chksum {
+            encoder.encode_be(&chksum)?.encode_be(&self.reserved1)?;
+        }
+        for field in [self.key, self.seq, self.ack].into_iter().flatten() {
+            encoder.encode_be(&field)?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("GRE", None)?;
+        let flags = self.flags();
+        let mut flags_node = node
+            .byte_range(0, 2)
+            .add_node("Flags", Some(&format!("0x{:04x}", flags)[..]))?;
+        flags_node.add_field(
+            "Checksum Present",
+            DumpValue::Bool(self.chksum.is_some()),
+            None,
+        )?;
+        flags_node.add_field("Key Present", DumpValue::Bool(self.key.is_some()), None)?;
+        flags_node.add_field(
+            "Sequence Present",
+            DumpValue::Bool(self.seq.is_some()),
+            None,
+        )?;
+        flags_node.add_field("Ack Present", DumpValue::Bool(self.ack.is_some()), None)?;
+        flags_node.add_field("Version", DumpValue::UInt(self.version().into()), None)?;
+        drop(flags_node);
+        node.byte_range(2, 2).add_field(
+            "Protocol",
+            DumpValue::UInt(self.proto.0.into()),
+            Some(&format!("0x{:04x}", self.proto.0)[..]),
+        )?;
+        let mut offset = 4;
+        if let Some(chksum) = self.chksum {
+            let valid = if chksum == self.calc_checksum() {
+                "valid"
+            } else {
+                "invalid"
+            };
+            node.byte_range(offset, 2).add_field(
+                "Checksum",
+                DumpValue::UInt(chksum.into()),
+                Some(&format!("0x{:04x} ({})", chksum, valid)[..]),
+            )?;
+            node.byte_range(offset + 2, 2).add_field(
+                "Reserved",
+                DumpValue::UInt(self.reserved1.into()),
+                None,
+            )?;
+            offset += 4;
+        }
+        for (name, field) in [
+            ("Key", self.key),
+            ("Sequence Number", self.seq),
+            ("Acknowledgment Number", self.ack),
+        ] {
+            if let Some(value) = field {
+                node.byte_range(offset, 4)
+                    .add_field(name, DumpValue::UInt(value.into()), None)?;
+                offset += 4;
+            }
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_protocol();
+        self.update_checksum();
+    }
+}
+
+impl Default for Gre {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    gre,
+    IpProtoDissectorTable,
+    IpProto::GRE,
+    Priority(0),
+    Gre::dissect
+);
+crate::register_ip_proto_pdu!(Gre, IpProto::GRE);
+
+register_dissector!(
+    ethernet_ii_teb,
+    EthertypeDissectorTable,
+    Ethertype::TRANS_ETHER_BRIDGING,
+    Priority(0),
+    EthernetII::dissect
+);
+crate::register_ethertype_pdu!(EthernetII, Ethertype::TRANS_ETHER_BRIDGING);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipv4::Ipv4;
+
+    #[test]
+    fn gre_ipv4() {
+        let mut gre = Gre::new();
+        *gre.key_mut() = Some(0x1234);
+        *gre.sequence_mut() = Some(7);
+        *gre.checksum_mut() = Some(0);
+        let mut ip = Ipv4::new();
+        ip.make_canonical();
+        gre.set_inner_pdu(ip);
+        gre.make_canonical();
+        assert_eq!(gre.protocol(), Ethertype::IPV4);
+        assert_eq!(gre.flags(), 0xb000);
+        assert_eq!(gre.header_len(), 16);
+        assert_eq!(gre.checksum_valid(), Some(true));
+
+        let mut buf = Vec::new();
+        gre.serialize(&mut buf).unwrap();
+        assert_eq!(&buf[..4], &[0xb0, 0x00, 0x08, 0x00]);
+
+        let session = Session::new();
+        let (rem, dissected) = Gre::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(dissected.key(), Some(0x1234));
+        assert_eq!(dissected.sequence(), Some(7));
+        assert_eq!(dissected.ack(), None);
+        assert_eq!(dissected.checksum_valid(), Some(true));
+        assert!(dissected
+            .inner_pdu()
+            .unwrap()
+            .downcast_ref::<Ipv4>()
+            .is_some());
+    }
+}
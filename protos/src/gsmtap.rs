@@ -0,0 +1,679 @@
+use crate::prelude::*;
+use nom::{
+    bytes::complete::take,
+    combinator::{flat_map, map, rest},
+    sequence::tuple,
+};
+
+/// UDP port GSMTAP is sent to by convention
+pub const GSMTAP_UDP_PORT: u16 = 4729;
+
+#[derive(Debug, Clone)]
+pub struct Gsmtap {
+    base: BasePdu,
+    version: u8,
+    hdr_len: u8,
+    gsmtap_type: GsmtapType,
+    timeslot: u8,
+    arfcn: u16,
+    signal_dbm: i8,
+    snr_db: i8,
+    frame_number: u32,
+    sub_type: u8,
+    antenna: u8,
+    sub_slot: u8,
+    reserved: u8,
+    ext: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GsmtapType(pub u8);
+
+/// GSM Um (LAPDm) data link frame, as carried on SDCCH channels
+#[derive(Debug, Clone)]
+pub struct Lapdm {
+    base: BasePdu,
+    address: u8,
+    control: u8,
+    length: u8,
+    fill: Vec<u8>,
+}
+
+dissector_table!(pub GsmtapDissectorTable, GsmtapType);
+
+register_dissector_table!(GsmtapDissectorTable);
+
+impl GsmtapType {
+    pub const UM: Self = Self(0x01);
+    pub const ABIS: Self = Self(0x02);
+    pub const UM_BURST: Self = Self(0x03);
+    pub const SIM: Self = Self(0x04);
+    pub const TETRA_I1: Self = Self(0x05);
+    pub const TETRA_I1_BURST: Self = Self(0x06);
+    pub const WMX_BURST: Self = Self(0x07);
+    pub const GB_LLC: Self = Self(0x08);
+    pub const GB_SNDCP: Self = Self(0x09);
+    pub const GMR1_UM: Self = Self(0x0a);
+    pub const UMTS_RLC_MAC: Self = Self(0x0b);
+    pub const UMTS_RRC: Self = Self(0x0c);
+    pub const LTE_RRC: Self = Self(0x0d);
+    pub const LTE_MAC: Self = Self(0x0e);
+    pub const LTE_MAC_FRAMED: Self = Self(0x0f);
+    pub const OSMOCORE_LOG: Self = Self(0x10);
+    pub const QC_DIAG: Self = Self(0x11);
+    pub const LTE_NAS: Self = Self(0x12);
+    pub const E1_T1: Self = Self(0x13);
+}
+
+impl std::fmt::Display for GsmtapType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::UM => "GSM Um",
+            Self::ABIS => "GSM Abis",
+            Self::UM_BURST => "GSM Um Burst",
+            Self::SIM => "SIM",
+            Self::TETRA_I1 => "TETRA I1",
+            Self::TETRA_I1_BURST => "TETRA I1 Burst",
+            Self::WMX_BURST => "WiMAX Burst",
+            Self::GB_LLC => "GPRS Gb LLC",
+            Self::GB_SNDCP => "GPRS Gb SNDCP",
+            Self::GMR1_UM => "GMR-1 Um",
+            Self::UMTS_RLC_MAC => "UMTS RLC/MAC",
+            Self::UMTS_RRC => "UMTS RRC",
+            Self::LTE_RRC => "LTE RRC",
+            Self::LTE_MAC => "LTE MAC",
+            Self::LTE_MAC_FRAMED => "LTE MAC Framed",
+            Self::OSMOCORE_LOG => "Osmocore Log",
+            Self::QC_DIAG => "Qualcomm DIAG",
+            Self::LTE_NAS => "LTE NAS",
+            Self::E1_T1 => "E1/T1",
+            _ => return write!(f, "Unknown (0x{:02x})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+/// GSM Um channel sub-types
+pub mod um_channel {
+    pub const UNKNOWN: u8 = 0x00;
+    pub const BCCH: u8 = 0x01;
+    pub const CCCH: u8 = 0x02;
+    pub const RACH: u8 = 0x03;
+    pub const AGCH: u8 = 0x04;
+    pub const PCH: u8 = 0x05;
+    pub const SDCCH: u8 = 0x06;
+    pub const SDCCH4: u8 = 0x07;
+    pub const SDCCH8: u8 = 0x08;
+    pub const TCH_F: u8 = 0x09;
+    pub const TCH_H: u8 = 0x0a;
+    pub const PACCH: u8 = 0x0b;
+    pub const CBCH52: u8 = 0x0c;
+    pub const PDCH: u8 = 0x0d;
+    pub const PTCCH: u8 = 0x0e;
+    pub const CBCH51: u8 = 0x0f;
+    /// Flag set on the channel for the associated control channel (SACCH)
+    pub const ACCH: u8 = 0x80;
+}
+
+const ARFCN_PCS: u16 = 0x8000;
+const ARFCN_UPLINK: u16 = 0x4000;
+const ARFCN_MASK: u16 = 0x3fff;
+
+impl Gsmtap {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            version: 2,
+            hdr_len: 4,
+            gsmtap_type: GsmtapType(0),
+            timeslot: 0,
+            arfcn: 0,
+            signal_dbm: 0,
+            snr_db: 0,
+            frame_number: 0,
+            sub_type: 0,
+            antenna: 0,
+            sub_slot: 0,
+            reserved: 0,
+            ext: Vec::new(),
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    /// Header length in 32-bit words
+    pub fn hdr_len(&self) -> u8 {
+        self.hdr_len
+    }
+
+    pub fn hdr_len_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.hdr_len
+    }
+
+    pub fn update_hdr_len(&mut self) {
+        self.base.mark_dirty();
+        self.hdr_len = ((16 + self.ext.len()) / 4) as u8;
+    }
+
+    pub fn gsmtap_type(&self) -> GsmtapType {
+        self.gsmtap_type
+    }
+
+    pub fn gsmtap_type_mut(&mut self) -> &mut GsmtapType {
+        self.base.mark_dirty();
+        &mut self.gsmtap_type
+    }
+
+    pub fn timeslot(&self) -> u8 {
+        self.timeslot
+    }
+
+    pub fn timeslot_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.timeslot
+    }
+
+    /// Raw ARFCN field, including the PCS and uplink flags
+    pub fn arfcn_raw(&self) -> u16 {
+        self.arfcn
+    }
+
+    pub fn arfcn_raw_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.arfcn
+    }
+
+    pub fn arfcn(&self) -> u16 {
+        self.arfcn & ARFCN_MASK
+    }
+
+    pub fn is_pcs(&self) -> bool {
+        (self.arfcn & ARFCN_PCS) != 0
+    }
+
+    pub fn is_uplink(&self) -> bool {
+        (self.arfcn & ARFCN_UPLINK) != 0
+    }
+
+    pub fn signal_dbm(&self) -> i8 {
+        self.signal_dbm
+    }
+
+    pub fn signal_dbm_mut(&mut self) -> &mut i8 {
+        self.base.mark_dirty();
+        &mut self.signal_dbm
+    }
+
+    pub fn snr_db(&self) -> i8 {
+        self.snr_db
+    }
+
+    pub fn snr_db_mut(&mut self) -> &mut i8 {
+        self.base.mark_dirty();
+        &mut self.snr_db
+    }
+
+    pub fn frame_number(&self) -> u32 {
+        self.frame_number
+    }
+
+    pub fn frame_number_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.frame_number
+    }
+
+    /// Channel sub-type. For `GsmtapType::UM`, see `um_channel`.
+    pub fn sub_type(&self) -> u8 {
+        self.sub_type
+    }
+
+    pub fn sub_type_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.sub_type
+    }
+
+    pub fn antenna(&self) -> u8 {
+        self.antenna
+    }
+
+    pub fn antenna_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.antenna
+    }
+
+    pub fn sub_slot(&self) -> u8 {
+        self.sub_slot
+    }
+
+    pub fn sub_slot_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.sub_slot
+    }
+
+    /// Header bytes beyond the 16 byte version 2 header
+    pub fn extension(&self) -> &[u8] {
+        &self.ext[..]
+    }
+
+    pub fn extension_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.ext
+    }
+}
+
+impl Dissect for Gsmtap {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            tuple((
+                u8::decode,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+                u16::decode_be,
+                u8::decode,
+                u8::decode,
+                u32::decode_be,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+            )),
+            |(
+                version,
+                hdr_len,
+                gsmtap_type,
+                timeslot,
+                arfcn,
+                signal_dbm,
+                snr_db,
+                frame_number,
+                sub_type,
+                antenna,
+                sub_slot,
+                reserved,
+            )| {
+                let parent = parent.clone();
+                move |buf: &'a [u8]| {
+                    if version != 2 || hdr_len < 4 {
+                        return Err(nom::Err::Error(DissectError::Malformed));
+                    }
+                    let (buf, ext) = take((hdr_len as usize - 4) * 4)(buf)?;
+                    let mut gsmtap = Self {
+                        base: BasePdu::default(),
+                        version,
+                        hdr_len,
+                        gsmtap_type: GsmtapType(gsmtap_type),
+                        timeslot,
+                        arfcn,
+                        signal_dbm: signal_dbm as i8,
+                        snr_db: snr_db as i8,
+                        frame_number,
+                        sub_type,
+                        antenna,
+                        sub_slot,
+                        reserved,
+                        ext: Vec::from(ext),
+                    };
+                    let (buf, inner) = session
+                        .table_dissector::<GsmtapDissectorTable>(
+                            &gsmtap.gsmtap_type,
+                            Some(TempPdu::new(&gsmtap, &parent)),
+                        )
+                        .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                        .parse(buf)?;
+                    gsmtap.set_inner_pdu(inner);
+                    Ok((buf, gsmtap))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for Gsmtap {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        16 + self.ext.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.version)?
+            .encode(&self.hdr_len)?
+            .encode(&self.gsmtap_type.0)?
+            .encode(&self.timeslot)?
+            .encode_be(&self.arfcn)?
+            .encode(&(self.signal_dbm as u8))?
+            .encode(&(self.snr_db as u8))?
+            .encode_be(&self.frame_number)?
+            .encode(&self.sub_type)?
+            .encode(&self.antenna)?
+            .encode(&self.sub_slot)?
+            .encode(&self.reserved)?
+            .encode(&self.ext[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("GSMTAP", Some(&self.gsmtap_type.to_string()[..]))?;
+        node.add_field("Version", DumpValue::UInt(self.version.into()), None)?;
+        node.add_field("Header Length", DumpValue::UInt(self.hdr_len.into()), None)?;
+        node.add_field(
+            "Payload Type",
+            DumpValue::UInt(self.gsmtap_type.0.into()),
+            Some(&self.gsmtap_type.to_string()[..]),
+        )?;
+        node.add_field("Timeslot", DumpValue::UInt(self.timeslot.into()), None)?;
+        {
+            let mut node = node.add_node("ARFCN", Some(&self.arfcn().to_string()[..]))?;
+            node.add_field("PCS Band", DumpValue::Bool(self.is_pcs()), None)?;
+            node.add_field("Uplink", DumpValue::Bool(self.is_uplink()), None)?;
+            node.add_field("ARFCN", DumpValue::UInt(self.arfcn().into()), None)?;
+        }
+        node.add_field(
+            "Signal Level",
+            DumpValue::Int(self.signal_dbm.into()),
+            Some(&format!("{} dBm", self.signal_dbm)[..]),
+        )?;
+        node.add_field(
+            "Signal/Noise Ratio",
+            DumpValue::Int(self.snr_db.into()),
+            Some(&format!("{} dB", self.snr_db)[..]),
+        )?;
+        node.add_field(
+            "Frame Number",
+            DumpValue::UInt(self.frame_number.into()),
+            None,
+        )?;
+        node.add_field("Channel Type", DumpValue::UInt(self.sub_type.into()), None)?;
+        node.add_field("Antenna Number", DumpValue::UInt(self.antenna.into()), None)?;
+        node.add_field("Sub-Slot", DumpValue::UInt(self.sub_slot.into()), None)
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_hdr_len();
+    }
+}
+
+impl Default for Gsmtap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const LAPDM_FILL: u8 = 0x2b;
+
+impl Lapdm {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            address: 0x01,
+            control: 0x03,
+            length: 0x01,
+            fill: Vec::new(),
+        }
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn address_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.address
+    }
+
+    pub fn lpd(&self) -> u8 {
+        (self.address >> 5) & 0x03
+    }
+
+    pub fn sapi(&self) -> u8 {
+        (self.address >> 2) & 0x07
+    }
+
+    pub fn is_command(&self) -> bool {
+        (self.address & 0x02) != 0
+    }
+
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    pub fn control_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.control
+    }
+
+    /// Raw length indicator octet, including the M and EL bits
+    pub fn length_octet(&self) -> u8 {
+        self.length
+    }
+
+    pub fn length_octet_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.length
+    }
+
+    /// Length of the information field
+    pub fn info_len(&self) -> usize {
+        (self.length >> 2) as usize
+    }
+
+    /// More data bit
+    pub fn more(&self) -> bool {
+        (self.length & 0x02) != 0
+    }
+
+    pub fn update_length(&mut self) {
+        self.base.mark_dirty();
+        let len = self.inner_pdu().map(|inner| inner.total_len()).unwrap_or(0);
+        self.length = ((len.min(0x3f) as u8) << 2) | (self.length & 0x03) | 0x01;
+    }
+
+    /// Fill octets following the information field
+    pub fn fill(&self) -> &[u8] {
+        &self.fill[..]
+    }
+
+    pub fn fill_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.fill
+    }
+
+    /// Pads the frame with fill octets to the standard 23 byte frame size
+    pub fn update_fill(&mut self) {
+        self.base.mark_dirty();
+        let len = 3 + self.inner_pdu().map(|inner| inner.total_len()).unwrap_or(0);
+        self.fill = vec![LAPDM_FILL; 23_usize.saturating_sub(len)];
+    }
+}
+
+impl Dissect for Lapdm {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        // LAPDm is only used on dedicated control channels. Other Um
+        // channels carry layer 3 messages directly.
+        let is_dcch = parent
+            .as_ref()
+            .and_then(|parent| parent.downcast_ref::<Gsmtap>())
+            .map(|gsmtap| {
+                matches!(
+                    gsmtap.sub_type,
+                    um_channel::SDCCH | um_channel::SDCCH4 | um_channel::SDCCH8
+                )
+            })
+            .unwrap_or(false);
+        if !is_dcch {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+
+        flat_map(
+            tuple((u8::decode, u8::decode, u8::decode)),
+            |(address, control, length)| {
+                move |buf: &'a [u8]| {
+                    let (buf, info) = take((length >> 2) as usize)(buf)?;
+                    let (buf, fill) = rest(buf)?;
+                    let mut lapdm = Self {
+                        base: BasePdu::default(),
+                        address,
+                        control,
+                        length,
+                        fill: Vec::from(fill),
+                    };
+                    if !info.is_empty() {
+                        lapdm.set_inner_pdu(RawPdu::new(session.packet_bytes(info)));
+                    }
+                    Ok((buf, lapdm))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for Lapdm {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        3
+    }
+
+    fn trailer_len(&self) -> usize {
+        self.fill.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.address)?
+            .encode(&self.control)?
+            .encode(&self.length)?;
+        Ok(())
+    }
+
+    fn serialize_trailer<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.fill[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("LAPDm", None)?;
+        {
+            let mut node =
+                node.add_node("Address", Some(&format!("0x{:02x}", self.address)[..]))?;
+            node.add_field("LPD", DumpValue::UInt(self.lpd().into()), None)?;
+            node.add_field("SAPI", DumpValue::UInt(self.sapi().into()), None)?;
+            node.add_field("C/R", DumpValue::Bool(self.is_command()), None)?;
+        }
+        node.add_field(
+            "Control",
+            DumpValue::UInt(self.control.into()),
+            Some(&format!("0x{:02x}", self.control)[..]),
+        )?;
+        {
+            let mut node = node.add_node("Length", Some(&self.info_len().to_string()[..]))?;
+            node.add_field("Length", DumpValue::UInt(self.info_len() as u64), None)?;
+            node.add_field("More Data", DumpValue::Bool(self.more()), None)?;
+        }
+        if !self.fill.is_empty() {
+            node.add_field("Fill", DumpValue::Bytes(&self.fill[..]), None)?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_length();
+        self.update_fill();
+    }
+}
+
+impl Default for Lapdm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    lapdm,
+    GsmtapDissectorTable,
+    GsmtapType::UM,
+    Priority(0),
+    Lapdm::dissect
+);
+
+#[cfg(feature = "udp")]
+use crate::udp::UdpPortDissectorTable;
+#[cfg(feature = "udp")]
+register_dissector!(
+    gsmtap,
+    UdpPortDissectorTable,
+    GSMTAP_UDP_PORT,
+    Priority(0),
+    Gsmtap::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gsmtap_lapdm() {
+        let data = [
+            0x02, 0x04, 0x01, 0x00, 0x40, 0x10, 0xc0, 0x0a, 0x00, 0x01, 0x02, 0x03, 0x07, 0x00,
+            0x00, 0x00, // GSMTAP
+            0x01, 0x73, 0x09, 0x05, 0x12, // LAPDm
+            0x2b, 0x2b,
+        ];
+        let session = Session::new();
+        let (rem, gsmtap) = Gsmtap::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(gsmtap.gsmtap_type(), GsmtapType::UM);
+        assert_eq!(gsmtap.arfcn(), 16);
+        assert!(gsmtap.is_uplink());
+        assert_eq!(gsmtap.signal_dbm(), -64);
+        assert_eq!(gsmtap.frame_number(), 0x00010203);
+
+        let lapdm = gsmtap.find::<Lapdm>().unwrap();
+        assert_eq!(lapdm.sapi(), 0);
+        assert_eq!(lapdm.info_len(), 2);
+        assert_eq!(lapdm.fill(), [0x2b, 0x2b]);
+
+        let mut out = Vec::new();
+        gsmtap.serialize(&mut out).unwrap();
+        assert_eq!(&out[..], &data[..]);
+    }
+}
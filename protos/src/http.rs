@@ -0,0 +1,762 @@
+use crate::prelude::*;
+use sniffle_core::{BodyTracker, Error, StreamDissect, StreamDissector, StreamEvent};
+use std::collections::VecDeque;
+
+/// HTTP/1.x message head: the request or status line, followed by headers.
+///
+/// Message bodies are not part of the Pdu when dissecting a stream. Instead,
+/// `HttpStream` emits the head as a `StreamEvent::Pdu`, followed by
+/// `StreamEvent::Chunk`s of the decoded body and a `StreamEvent::End`. When
+/// dissected from a single buffer with `Dissect`, the body (still transfer
+/// encoded) is the inner Pdu.
+#[derive(Debug, Clone)]
+pub struct Http {
+    base: BasePdu,
+    start: StartLine,
+    version: String,
+    headers: Vec<(String, String)>,
+    transaction: Option<u64>,
+    in_response_to: Option<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartLine {
+    Request { method: String, target: String },
+    Response { status: u16, reason: String },
+}
+
+/// Incremental HTTP/1.x dissector for one direction of a reassembled stream.
+///
+/// Handles `Content-Length` and chunked bodies, and responses delimited by
+/// the end of the stream. Chunked trailers are consumed but not reported.
+#[derive(Debug)]
+pub struct HttpStream {
+    responses: bool,
+    state: State,
+    next_transaction: u64,
+    pending: VecDeque<(u64, String, String)>,
+}
+
+/// Dissects both directions of an HTTP conversation, linking each response
+/// to the request it answers.
+///
+/// Requests are assigned increasing transaction numbers, and responses are
+/// matched to requests in order, as required for pipelined requests.
+/// Request data must be pushed before the data of the corresponding
+/// response.
+pub struct HttpConversation {
+    requests: StreamDissector<HttpStream>,
+    responses: StreamDissector<HttpStream>,
+}
+
+#[derive(Debug)]
+enum State {
+    Head,
+    Body(BodyTracker),
+    Chunked { offset: u64, chunk: Chunk },
+    UntilClose { offset: u64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Chunk {
+    Size,
+    Data(u64),
+    DataEnd,
+    Trailers,
+}
+
+fn split_line(data: &[u8]) -> Option<(&[u8], usize)> {
+    let nl = data.iter().position(|b| *b == b'\n')?;
+    let line = &data[..nl];
+    Some((line.strip_suffix(b"\r").unwrap_or(line), nl + 1))
+}
+
+/// Returns the length of the message head, including the empty line
+fn head_len(data: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        let (line, len) = split_line(&data[pos..])?;
+        pos += len;
+        if line.is_empty() {
+            return Some(pos);
+        }
+    }
+}
+
+impl StartLine {
+    fn parse(line: &str) -> Option<(Self, String)> {
+        if line.starts_with("HTTP/") {
+            let (version, rest) = line.split_once(' ')?;
+            let (status, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+            let status = status.parse().ok()?;
+            Some((
+                Self::Response {
+                    status,
+                    reason: String::from(reason),
+                },
+                String::from(version),
+            ))
+        } else {
+            let mut parts = line.splitn(3, ' ');
+            let method = parts.next()?;
+            let target = parts.next()?;
+            let version = parts.next()?;
+            if method.is_empty() || !version.starts_with("HTTP/") {
+                return None;
+            }
+            Some((
+                Self::Request {
+                    method: String::from(method),
+                    target: String::from(target),
+                },
+                String::from(version),
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for StartLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request { method, target } => write!(f, "{} {}", method, target),
+            Self::Response { status, reason } => write!(f, "{} {}", status, reason),
+        }
+    }
+}
+
+impl Http {
+    pub fn new_request(method: &str, target: &str) -> Self {
+        Self {
+            base: BasePdu::default(),
+            start: StartLine::Request {
+                method: String::from(method),
+                target: String::from(target),
+            },
+            version: String::from("HTTP/1.1"),
+            headers: Vec::new(),
+            transaction: None,
+            in_response_to: None,
+        }
+    }
+
+    pub fn new_response(status: u16, reason: &str) -> Self {
+        Self {
+            base: BasePdu::default(),
+            start: StartLine::Response {
+                status,
+                reason: String::from(reason),
+            },
+            version: String::from("HTTP/1.1"),
+            headers: Vec::new(),
+            transaction: None,
+            in_response_to: None,
+        }
+    }
+
+    pub fn start_line(&self) -> &StartLine {
+        &self.start
+    }
+
+    pub fn start_line_mut(&mut self) -> &mut StartLine {
+        self.base.mark_dirty();
+        &mut self.start
+    }
+
+    pub fn is_request(&self) -> bool {
+        matches!(self.start, StartLine::Request { .. })
+    }
+
+    pub fn is_response(&self) -> bool {
+        matches!(self.start, StartLine::Response { .. })
+    }
+
+    pub fn method(&self) -> Option<&str> {
+        match &self.start {
+            StartLine::Request { method, .. } => Some(&method[..]),
+            _ => None,
+        }
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        match &self.start {
+            StartLine::Request { target, .. } => Some(&target[..]),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match &self.start {
+            StartLine::Response { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match &self.start {
+            StartLine::Response { reason, .. } => Some(&reason[..]),
+            _ => None,
+        }
+    }
+
+    /// Protocol version, such as `"HTTP/1.1"`
+    pub fn version(&self) -> &str {
+        &self.version[..]
+    }
+
+    pub fn version_mut(&mut self) -> &mut String {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers[..]
+    }
+
+    pub fn headers_mut(&mut self) -> &mut Vec<(String, String)> {
+        self.base.mark_dirty();
+        &mut self.headers
+    }
+
+    /// Returns the value of the first header with the specified name,
+    /// ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(hdr, _)| hdr.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Replaces the value of the first header with the specified name, or
+    /// appends the header if there is none.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.base.mark_dirty();
+        match self
+            .headers
+            .iter_mut()
+            .find(|(hdr, _)| hdr.eq_ignore_ascii_case(name))
+        {
+            Some((_, val)) => *val = String::from(value),
+            None => self.headers.push((String::from(name), String::from(value))),
+        }
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.header("Content-Length")
+            .and_then(|len| len.trim().parse().ok())
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        self.header("Transfer-Encoding")
+            .map(|te| {
+                te.rsplit(',')
+                    .next()
+                    .map(|last| last.trim().eq_ignore_ascii_case("chunked"))
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Number of the request/response exchange within the conversation,
+    /// starting at 0. Only set by `HttpConversation`.
+    pub fn transaction(&self) -> Option<u64> {
+        self.transaction
+    }
+
+    pub fn transaction_mut(&mut self) -> &mut Option<u64> {
+        self.base.mark_dirty();
+        &mut self.transaction
+    }
+
+    /// Method and target of the request a response answers. Only set by
+    /// `HttpConversation`.
+    pub fn in_response_to(&self) -> Option<(&str, &str)> {
+        self.in_response_to
+            .as_ref()
+            .map(|(method, target)| (&method[..], &target[..]))
+    }
+
+    fn parse_head(head: &[u8]) -> Option<Self> {
+        let head = std::str::from_utf8(head).ok()?;
+        let mut lines = head.lines();
+        let (start, version) = StartLine::parse(lines.next()?)?;
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines {
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // Obsolete line folding continues the previous value
+                let (_, value) = headers.last_mut()?;
+                value.push(' ');
+                value.push_str(line.trim());
+                continue;
+            }
+            let (name, value) = line.split_once(':')?;
+            headers.push((String::from(name), String::from(value.trim())));
+        }
+        Some(Self {
+            base: BasePdu::default(),
+            start,
+            version,
+            headers,
+            transaction: None,
+            in_response_to: None,
+        })
+    }
+
+    fn head(&self) -> String {
+        let mut head = match &self.start {
+            StartLine::Request { method, target } => {
+                format!("{} {} {}\r\n", method, target, self.version)
+            }
+            StartLine::Response { status, reason } => {
+                format!("{} {} {}\r\n", self.version, status, reason)
+            }
+        };
+        for (name, value) in self.headers.iter() {
+            head.push_str(name);
+            head.push_str(": ");
+            head.push_str(value);
+            head.push_str("\r\n");
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    fn has_body(&self, request_method: Option<&str>) -> bool {
+        match &self.start {
+            StartLine::Request { .. } => self.is_chunked() || self.content_length().is_some(),
+            StartLine::Response { status, .. } => {
+                !(*status < 200
+                    || *status == 204
+                    || *status == 304
+                    || request_method == Some("HEAD"))
+            }
+        }
+    }
+}
+
+impl Dissect for Http {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let len = head_len(buf).ok_or(nom::Err::Error(DissectError::Malformed))?;
+        let mut http =
+            Self::parse_head(&buf[..len]).ok_or(nom::Err::Error(DissectError::Malformed))?;
+        let buf = &buf[len..];
+        let body_len = if !http.has_body(None) {
+            0
+        } else if http.is_chunked() {
+            buf.len()
+        } else {
+            match http.content_length() {
+                Some(body_len) => usize::try_from(body_len)
+                    .unwrap_or(usize::MAX)
+                    .min(buf.len()),
+                None => buf.len(),
+            }
+        };
+        let (body, rem) = buf.split_at(body_len);
+        if !body.is_empty() {
+            http.set_inner_pdu(RawPdu::new(session.packet_bytes(body)));
+        }
+        Ok((rem, http))
+    }
+}
+
+impl Pdu for Http {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.head().len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(self.head().as_bytes())?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("HTTP", Some(&self.start.to_string()[..]))?;
+        let line_len = self.head().find('\n').map(|nl| nl + 1).unwrap_or(0);
+        {
+            let mut node = node.byte_range(0, line_len).add_node(
+                if self.is_request() {
+                    "Request Line"
+                } else {
+                    "Status Line"
+                },
+                None,
+            )?;
+            match &self.start {
+                StartLine::Request { method, target } => {
+                    node.add_field("Method", DumpValue::Text(method), None)?;
+                    node.add_field("Target", DumpValue::Text(target), None)?;
+                    node.add_field("Version", DumpValue::Text(&self.version), None)?;
+                }
+                StartLine::Response { status, reason } => {
+                    node.add_field("Version", DumpValue::Text(&self.version), None)?;
+                    node.add_field("Status", DumpValue::UInt((*status).into()), None)?;
+                    node.add_field("Reason", DumpValue::Text(reason), None)?;
+                }
+            }
+        }
+        let mut offset = line_len;
+        for (name, value) in self.headers.iter() {
+            let len = name.len() + value.len() + 4;
+            node.byte_range(offset, len)
+                .add_field(name, DumpValue::Text(value), None)?;
+            offset += len;
+        }
+        if let Some(transaction) = self.transaction {
+            node.add_field("Transaction", DumpValue::UInt(transaction), None)?;
+        }
+        if let Some((method, target)) = self.in_response_to() {
+            node.add_field(
+                "In Response To",
+                DumpValue::Text(&format!("{} {}", method, target)),
+                None,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        if !self.is_chunked() {
+            if let Some(len) = self.inner_pdu().map(|body| body.total_len()) {
+                self.set_header("Content-Length", &len.to_string());
+            }
+        }
+    }
+}
+
+impl HttpStream {
+    /// Dissector for the client to server direction
+    pub fn requests() -> Self {
+        Self {
+            responses: false,
+            state: State::Head,
+            next_transaction: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Dissector for the server to client direction
+    pub fn responses() -> Self {
+        Self {
+            responses: true,
+            state: State::Head,
+            next_transaction: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Registers a request that the next response will answer. This is
+    /// needed to determine whether a response has a body, such as for
+    /// responses to `HEAD` requests.
+    pub fn expect_response(&mut self, request: &Http) {
+        if let (Some(transaction), Some(method), Some(target)) =
+            (request.transaction, request.method(), request.target())
+        {
+            self.pending
+                .push_back((transaction, String::from(method), String::from(target)));
+        }
+    }
+
+    fn start_message(&mut self, mut http: Http, events: &mut dyn FnMut(StreamEvent<'_>)) {
+        let request_method = if self.responses {
+            // Interim responses do not complete the exchange
+            let interim = http.status().map(|status| status < 200).unwrap_or(false);
+            let pending = if interim {
+                self.pending.front().cloned()
+            } else {
+                self.pending.pop_front()
+            };
+            pending.map(|(transaction, method, target)| {
+                http.transaction = Some(transaction);
+                http.in_response_to = Some((method.clone(), target));
+                method
+            })
+        } else {
+            http.transaction = Some(self.next_transaction);
+            self.next_transaction += 1;
+            None
+        };
+
+        let has_body = http.has_body(request_method.as_deref());
+        let chunked = http.is_chunked();
+        let content_length = http.content_length();
+        events(StreamEvent::Pdu(AnyPdu::new(http)));
+
+        self.state = if !has_body {
+            events(StreamEvent::End { len: 0 });
+            State::Head
+        } else if chunked {
+            State::Chunked {
+                offset: 0,
+                chunk: Chunk::Size,
+            }
+        } else {
+            match content_length {
+                Some(0) => {
+                    events(StreamEvent::End { len: 0 });
+                    State::Head
+                }
+                Some(len) => State::Body(BodyTracker::new(len)),
+                None => State::UntilClose { offset: 0 },
+            }
+        };
+    }
+}
+
+impl StreamDissect for HttpStream {
+    fn feed(
+        &mut self,
+        data: &[u8],
+        _session: &Session,
+        events: &mut dyn FnMut(StreamEvent<'_>),
+    ) -> Result<usize, Error> {
+        match &mut self.state {
+            State::Head => {
+                // Empty lines between messages are ignored
+                let skip = data
+                    .iter()
+                    .take_while(|b| **b == b'\r' || **b == b'\n')
+                    .count();
+                if skip > 0 {
+                    return Ok(skip);
+                }
+                let len = match head_len(data) {
+                    Some(len) => len,
+                    None => return Ok(0),
+                };
+                let http = Http::parse_head(&data[..len]).ok_or(Error::MalformedStream)?;
+                self.start_message(http, events);
+                Ok(len)
+            }
+            State::Body(body) => {
+                let consumed = body.feed(data, events);
+                if body.is_done() {
+                    self.state = State::Head;
+                }
+                Ok(consumed)
+            }
+            State::Chunked { offset, chunk } => match *chunk {
+                Chunk::Size => {
+                    let (line, len) = match split_line(data) {
+                        Some(line) => line,
+                        None => return Ok(0),
+                    };
+                    let size = std::str::from_utf8(line)
+                        .ok()
+                        .and_then(|line| {
+                            let size = line.split(';').next().unwrap_or("").trim();
+                            u64::from_str_radix(size, 16).ok()
+                        })
+                        .ok_or(Error::MalformedStream)?;
+                    *chunk = if size == 0 {
+                        Chunk::Trailers
+                    } else {
+                        Chunk::Data(size)
+                    };
+                    Ok(len)
+                }
+                Chunk::Data(remaining) => {
+                    let take = usize::try_from(remaining)
+                        .unwrap_or(usize::MAX)
+                        .min(data.len());
+                    events(StreamEvent::Chunk {
+                        offset: *offset,
+                        data: &data[..take],
+                    });
+                    *offset += take as u64;
+                    *chunk = if remaining == take as u64 {
+                        Chunk::DataEnd
+                    } else {
+                        Chunk::Data(remaining - take as u64)
+                    };
+                    Ok(take)
+                }
+                Chunk::DataEnd => {
+                    let len = match data {
+                        [b'\r', b'\n', ..] => 2,
+                        [b'\n', ..] => 1,
+                        [b'\r'] => return Ok(0),
+                        _ => return Err(Error::MalformedStream),
+                    };
+                    *chunk = Chunk::Size;
+                    Ok(len)
+                }
+                Chunk::Trailers => {
+                    let (line, len) = match split_line(data) {
+                        Some(line) => line,
+                        None => return Ok(0),
+                    };
+                    if line.is_empty() {
+                        events(StreamEvent::End { len: *offset });
+                        self.state = State::Head;
+                    }
+                    Ok(len)
+                }
+            },
+            State::UntilClose { offset } => {
+                events(StreamEvent::Chunk {
+                    offset: *offset,
+                    data,
+                });
+                *offset += data.len() as u64;
+                Ok(data.len())
+            }
+        }
+    }
+
+    fn finish(
+        &mut self,
+        data: &[u8],
+        _session: &Session,
+        events: &mut dyn FnMut(StreamEvent<'_>),
+    ) -> Result<(), Error> {
+        match &self.state {
+            State::UntilClose { offset } => {
+                events(StreamEvent::End { len: *offset });
+                self.state = State::Head;
+                Ok(())
+            }
+            State::Head if data.iter().all(|b| *b == b'\r' || *b == b'\n') => Ok(()),
+            _ => Err(Error::MalformedStream),
+        }
+    }
+}
+
+impl HttpConversation {
+    pub fn new() -> Self {
+        Self {
+            requests: StreamDissector::new(HttpStream::requests()),
+            responses: StreamDissector::new(HttpStream::responses()),
+        }
+    }
+
+    /// Feeds client to server data
+    pub fn push_requests<F>(
+        &mut self,
+        data: &[u8],
+        session: &Session,
+        events: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(StreamEvent<'_>),
+    {
+        let mut events = events;
+        let mut requests = Vec::new();
+        let res = self.requests.push(data, session, |event| {
+            if let StreamEvent::Pdu(pdu) = &event {
+                if let Some(http) = pdu.downcast_ref::<Http>() {
+                    requests.push(http.clone());
+                }
+            }
+            events(event);
+        });
+        for request in requests.iter() {
+            self.responses.dissector_mut().expect_response(request);
+        }
+        res
+    }
+
+    /// Feeds server to client data
+    pub fn push_responses<F>(
+        &mut self,
+        data: &[u8],
+        session: &Session,
+        events: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(StreamEvent<'_>),
+    {
+        self.responses.push(data, session, events)
+    }
+
+    /// Signals the end of both directions of the conversation
+    pub fn finish<F>(&mut self, session: &Session, events: F) -> Result<(), Error>
+    where
+        F: FnMut(StreamEvent<'_>),
+    {
+        let mut events = events;
+        let requests = self.requests.finish(session, &mut events);
+        let responses = self.responses.finish(session, &mut events);
+        requests.and(responses)
+    }
+}
+
+impl Default for HttpConversation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn http_conversation() {
+        let session = Session::new_from_scratch();
+        let mut conv = HttpConversation::new();
+        let mut heads = Vec::new();
+        let mut body = Vec::new();
+        let mut ends = Vec::new();
+        let mut on_event = |event: StreamEvent<'_>| match event {
+            StreamEvent::Pdu(pdu) => heads.push(pdu.downcast_ref::<Http>().unwrap().clone()),
+            StreamEvent::Chunk { data, .. } => body.extend_from_slice(data),
+            StreamEvent::End { len } => ends.push(len),
+        };
+
+        conv.push_requests(
+            b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\nHEAD / HTTP/1.1\r\n\r\nPOST /form HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nab",
+            &session,
+            &mut on_event,
+        )
+        .unwrap();
+        conv.push_requests(b"cd\r\n0\r\n\r\n", &session, &mut on_event)
+            .unwrap();
+        conv.push_responses(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhelloHTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n",
+            &session,
+            &mut on_event,
+        )
+        .unwrap();
+        conv.push_responses(b"HTTP/1.1 201 Created\r\n\r\ndone", &session, &mut on_event)
+            .unwrap();
+        conv.finish(&session, &mut on_event).unwrap();
+
+        assert_eq!(heads.len(), 6);
+        assert_eq!(heads[0].method(), Some("GET"));
+        assert_eq!(heads[0].header("host"), Some("example.com"));
+        assert_eq!(heads[2].transaction(), Some(2));
+        assert!(heads[2].is_chunked());
+        assert_eq!(heads[3].status(), Some(200));
+        assert_eq!(heads[3].in_response_to(), Some(("GET", "/index.html")));
+        assert_eq!(heads[4].in_response_to(), Some(("HEAD", "/")));
+        assert_eq!(heads[5].transaction(), Some(2));
+        assert_eq!(heads[5].reason(), Some("Created"));
+        assert_eq!(body, b"abcdhellodone");
+        assert_eq!(ends, [0, 0, 4, 5, 0, 4]);
+
+        let mut out = Vec::new();
+        heads[0].serialize(&mut out).unwrap();
+        assert_eq!(
+            &out[..],
+            b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n"
+        );
+    }
+}
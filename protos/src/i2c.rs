@@ -0,0 +1,225 @@
+use crate::prelude::*;
+use nom::{
+    combinator::{flat_map, map},
+    sequence::tuple,
+};
+
+/// Linux I2C pseudo-header, as captured with `LinkType::IPMB_LINUX`
+#[derive(Debug, Clone)]
+pub struct I2cLinux {
+    base: BasePdu,
+    bus: u8,
+    flags: u32,
+}
+
+dissector_table!(pub I2cDissectorTable);
+
+register_dissector_table!(I2cDissectorTable);
+
+const BUS_EVENT: u8 = 0x80;
+const BUS_MASK: u8 = 0x7f;
+
+/// Flag set on messages read from the bus
+pub const I2C_FLAG_READ: u32 = 0x0000_0001;
+
+impl I2cLinux {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            bus: 0,
+            flags: 0,
+        }
+    }
+
+    /// Raw bus octet, including the event flag
+    pub fn bus_raw(&self) -> u8 {
+        self.bus
+    }
+
+    pub fn bus_raw_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.bus
+    }
+
+    pub fn bus(&self) -> u8 {
+        self.bus & BUS_MASK
+    }
+
+    /// True if the record is a bus event rather than a message
+    pub fn is_event(&self) -> bool {
+        (self.bus & BUS_EVENT) != 0
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn flags_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.flags
+    }
+
+    pub fn is_read(&self) -> bool {
+        (self.flags & I2C_FLAG_READ) != 0
+    }
+}
+
+impl Dissect for I2cLinux {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(tuple((u8::decode, u32::decode_be)), |(bus, flags)| {
+            let parent = parent.clone();
+            move |buf: &'a [u8]| {
+                let mut i2c = Self {
+                    base: BasePdu::default(),
+                    bus,
+                    flags,
+                };
+                if buf.is_empty() {
+                    return Ok((buf, i2c));
+                }
+                let (buf, inner) = if i2c.is_event() {
+                    map(RawPdu::dissector(session, None), AnyPdu::new)(buf)?
+                } else {
+                    session
+                        .table_dissector::<I2cDissectorTable>(
+                            &(),
+                            Some(TempPdu::new(&i2c, &parent)),
+                        )
+                        .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                        .parse(buf)?
+                };
+                i2c.set_inner_pdu(inner);
+                Ok((buf, i2c))
+            }
+        })(buf)
+    }
+}
+
+impl Pdu for I2cLinux {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        5
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.bus)?.encode_be(&self.flags)?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("I2C", Some(&format!("Bus {}", self.bus())[..]))?;
+        node.byte_range(0, 1)
+            .add_field("Bus", DumpValue::UInt(self.bus().into()), None)?;
+        node.byte_range(0, 1)
+            .add_field("Event", DumpValue::Bool(self.is_event()), None)?;
+        let mut flags = node
+            .byte_range(1, 4)
+            .add_node("Flags", Some(&format!("0x{:08x}", self.flags)[..]))?;
+        flags.add_field("Read", DumpValue::Bool(self.is_read()), None)
+    }
+}
+
+impl Default for I2cLinux {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_link_layer_pdu!(I2cLinux, LinkType::IPMB_LINUX);
+register_dissector!(
+    i2c_linux,
+    LinkTypeTable,
+    LinkType::IPMB_LINUX,
+    Priority(0),
+    I2cLinux::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipmb::Ipmb;
+
+    /// The IPMB request of the `ipmb` tests
+    const IPMB_REQUEST: [u8; 7] = [0x20, 0x18, 0xc8, 0x81, 0x04, 0x01, 0x7a];
+
+    fn serialize(i2c: &I2cLinux) -> Vec<u8> {
+        let mut buf = Vec::new();
+        i2c.serialize(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let mut i2c = I2cLinux::new();
+        *i2c.bus_raw_mut() = 3;
+        *i2c.flags_mut() = I2C_FLAG_READ;
+        i2c.set_inner_pdu(RawPdu::new(vec![0xde, 0xad]));
+        let buf = serialize(&i2c);
+        assert_eq!(buf, [3, 0, 0, 0, 1, 0xde, 0xad]);
+
+        let session = Session::new();
+        let (rem, i2c) = I2cLinux::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(i2c.bus(), 3);
+        assert!(!i2c.is_event());
+        assert!(i2c.is_read());
+        assert_eq!(&i2c.find::<RawPdu>().unwrap().data()[..], &[0xde, 0xad]);
+        assert_eq!(serialize(&i2c), buf);
+    }
+
+    #[test]
+    fn message_dissected_as_ipmb() {
+        let mut buf = vec![1, 0, 0, 0, 0];
+        buf.extend_from_slice(&IPMB_REQUEST[..]);
+        let session = Session::new();
+        let (_, i2c) = I2cLinux::dissect(&buf[..], &session, None).unwrap();
+        assert!(i2c.find::<Ipmb>().is_some());
+        assert_eq!(serialize(&i2c), buf);
+    }
+
+    #[test]
+    fn event_round_trip() {
+        // The data of an event is not a message, even if it looks like one
+        let mut buf = vec![BUS_EVENT | 2, 0, 0, 0, 0];
+        buf.extend_from_slice(&IPMB_REQUEST[..]);
+        let session = Session::new();
+        let (rem, i2c) = I2cLinux::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert!(i2c.is_event());
+        assert_eq!(i2c.bus(), 2);
+        assert!(i2c.find::<Ipmb>().is_none());
+        assert_eq!(&i2c.find::<RawPdu>().unwrap().data()[..], &IPMB_REQUEST[..]);
+        assert_eq!(serialize(&i2c), buf);
+    }
+
+    #[test]
+    fn header_only() {
+        let buf = [0, 0x12, 0x34, 0x56, 0x78];
+        let session = Session::new();
+        let (rem, i2c) = I2cLinux::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(i2c.flags(), 0x1234_5678);
+        assert!(i2c.inner_pdu().is_none());
+        assert_eq!(serialize(&i2c), buf);
+    }
+
+    #[test]
+    fn truncated_header() {
+        let session = Session::new();
+        assert!(I2cLinux::dissect(&[0, 0, 0, 0], &session, None).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+//! Internet Control Message Protocol (ICMP) for IPv4.
+//!
+//! Error messages, such as destination unreachable and time exceeded, carry
+//! the IP header and the first 8 bytes of the payload of the packet that
+//! caused the error. That packet is dissected as the inner PDU of the ICMP
+//! message, and `Icmp::original_flow` identifies the flow it belongs to.
+
+use super::ip_proto::IpProto;
+use super::ipv4::{IpProtoDissectorTable, Ipv4};
+use crate::prelude::*;
+use checksum::U16OnesComplement;
+use nom::{combinator::map, sequence::tuple};
+use sniffle_core::Ipv4Address;
+
+#[derive(Debug, Clone)]
+pub struct Icmp {
+    base: BasePdu,
+    msg_type: u8,
+    code: u8,
+    chksum: u16,
+    rest: [u8; 4],
+}
+
+/// The flow of the packet that caused an ICMP error message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IcmpFlow {
+    pub src_addr: Ipv4Address,
+    pub dst_addr: Ipv4Address,
+    pub proto: IpProto,
+    /// Source and destination ports, for protocols that start with them,
+    /// such as TCP and UDP.
+    pub ports: Option<(u16, u16)>,
+}
+
+pub mod msg_type {
+    pub const ECHO_REPLY: u8 = 0;
+    pub const DEST_UNREACHABLE: u8 = 3;
+    pub const SOURCE_QUENCH: u8 = 4;
+    pub const REDIRECT: u8 = 5;
+    pub const ECHO_REQUEST: u8 = 8;
+    pub const TIME_EXCEEDED: u8 = 11;
+    pub const PARAMETER_PROBLEM: u8 = 12;
+    pub const TIMESTAMP: u8 = 13;
+    pub const TIMESTAMP_REPLY: u8 = 14;
+}
+
+impl Icmp {
+    pub fn new(msg_type: u8, code: u8) -> Self {
+        Self {
+            base: BasePdu::default(),
+            msg_type,
+            code,
+            chksum: 0,
+            rest: [0; 4],
+        }
+    }
+
+    /// Creates an echo request, as sent by `ping`.
+    pub fn echo_request(ident: u16, seq: u16) -> Self {
+        let mut icmp = Self::new(msg_type::ECHO_REQUEST, 0);
+        icmp.set_echo(ident, seq);
+        icmp
+    }
+
+    pub fn msg_type(&self) -> u8 {
+        self.msg_type
+    }
+
+    pub fn msg_type_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.msg_type
+    }
+
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    pub fn code_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.code
+    }
+
+    pub fn checksum(&self) -> u16 {
+        self.chksum
+    }
+
+    pub fn checksum_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.chksum
+    }
+
+    /// The last 4 bytes of the header, whose meaning depends on the message
+    /// type.
+    pub fn rest_of_header(&self) -> [u8; 4] {
+        self.rest
+    }
+
+    pub fn rest_of_header_mut(&mut self) -> &mut [u8; 4] {
+        self.base.mark_dirty();
+        &mut self.rest
+    }
+
+    /// True for error messages, which carry the start of the packet that
+    /// caused the error.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.msg_type,
+            msg_type::DEST_UNREACHABLE
+                | msg_type::SOURCE_QUENCH
+                | msg_type::REDIRECT
+                | msg_type::TIME_EXCEEDED
+                | msg_type::PARAMETER_PROBLEM
+        )
+    }
+
+    /// The identifier and sequence number of an echo request or reply.
+    pub fn echo(&self) -> Option<(u16, u16)> {
+        match self.msg_type {
+            msg_type::ECHO_REQUEST | msg_type::ECHO_REPLY => Some((
+                u16::from_be_bytes([self.rest[0], self.rest[1]]),
+                u16::from_be_bytes([self.rest[2], self.rest[3]]),
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn set_echo(&mut self, ident: u16, seq: u16) {
+        self.base.mark_dirty();
+        self.rest[..2].copy_from_slice(&ident.to_be_bytes()[..]);
+        self.rest[2..].copy_from_slice(&seq.to_be_bytes()[..]);
+    }
+
+    /// The IPv4 header of the packet that caused an error message, which
+    /// is the inner PDU.
+    pub fn original(&self) -> Option<&Ipv4> {
+        if !self.is_error() {
+            return None;
+        }
+        self.inner_pdu()?.downcast_ref::<Ipv4>()
+    }
+
+    /// The flow of the packet that caused an error message, to correlate
+    /// the error with it.
+    pub fn original_flow(&self) -> Option<IcmpFlow> {
+        let ipv4 = self.original()?;
+        let ports = ipv4.inner_pdu().and_then(|inner| {
+            let mut buf = Vec::new();
+            inner.serialize(&mut buf).ok()?;
+            Some((
+                u16::from_be_bytes([*buf.first()?, *buf.get(1)?]),
+                u16::from_be_bytes([*buf.get(2)?, *buf.get(3)?]),
+            ))
+        });
+        Some(IcmpFlow {
+            src_addr: ipv4.src_address(),
+            dst_addr: ipv4.dst_address(),
+            proto: ipv4.proto(),
+            ports,
+        })
+    }
+
+    /// Computes the checksum from the header fields, with the checksum
+    /// field as zero, and the inner PDU.
+    pub fn calc_checksum(&self) -> u16 {
+        let mut acc = U16OnesComplement::new();
+        let _ = acc
+            .encode(&self.msg_type)
+            .and_then(|acc| acc.encode(&self.code))
+            .and_then(|acc| acc.encode_be(&0u16))
+            .and_then(|acc| acc.encode(&self.rest[..]));
+        if let Some(inner) = self.inner_pdu() {
+            let _ = inner.serialize(&mut acc);
+        }
+        acc.checksum()
+    }
+
+    pub fn checksum_valid(&self) -> bool {
+        self.chksum == self.calc_checksum()
+    }
+
+    pub fn update_checksum(&mut self) {
+        self.base.mark_dirty();
+        self.chksum = self.calc_checksum();
+    }
+}
+
+impl Dissect for Icmp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (payload, (msg_type, code, chksum, rest)) =
+            tuple((u8::decode, u8::decode, u16::decode_be, <[u8; 4]>::decode))(buf)?;
+        let mut icmp = Self {
+            base: BasePdu::default(),
+            msg_type,
+            code,
+            chksum,
+            rest,
+        };
+        if !payload.is_empty() {
+            // The embedded packet is usually cut short, so fall back to raw
+            // bytes if it can't be dissected
+            let embedded = if icmp.is_error() {
+                Ipv4::dissect(payload, session, None)
+                    .ok()
+                    .filter(|(rem, _)| rem.is_empty())
+                    .map(|(_, ipv4)| AnyPdu::new(ipv4))
+            } else {
+                None
+            };
+            let inner = match embedded {
+                Some(inner) => inner,
+                None => map(RawPdu::dissector(session, None), AnyPdu::new)(payload)?.1,
+            };
+            icmp.set_inner_pdu(inner);
+        }
+        Ok((&payload[payload.len()..], icmp))
+    }
+}
+
+impl Pdu for Icmp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        8
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.msg_type)?
+            .encode(&self.code)?
+            .encode_be(&self.chksum)?
+            .encode(&self.rest[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let descr = match self.msg_type {
+            msg_type::ECHO_REPLY => "Echo Reply",
+            msg_type::DEST_UNREACHABLE => "Destination Unreachable",
+            msg_type::SOURCE_QUENCH => "Source Quench",
+            msg_type::REDIRECT => "Redirect",
+            msg_type::ECHO_REQUEST => "Echo Request",
+            msg_type::TIME_EXCEEDED => "Time Exceeded",
+            msg_type::PARAMETER_PROBLEM => "Parameter Problem",
+            msg_type::TIMESTAMP => "Timestamp",
+            msg_type::TIMESTAMP_REPLY => "Timestamp Reply",
+            _ => "Unknown",
+        };
+        let mut node = dumper.add_node("ICMP", Some(descr))?;
+        node.byte_range(0, 1).add_field(
+            "Type",
+            DumpValue::UInt(self.msg_type.into()),
+            Some(descr),
+        )?;
+        node.byte_range(1, 1)
+            .add_field("Code", DumpValue::UInt(self.code.into()), None)?;
+        node.byte_range(2, 2).add_field(
+            "Checksum",
+            DumpValue::UInt(self.chksum.into()),
+            Some(&format!("0x{:04x}", self.chksum)[..]),
+        )?;
+        match self.echo() {
+            Some((ident, seq)) => {
+                node.byte_range(4, 2).add_field(
+                    "Identifier",
+                    DumpValue::UInt(ident.into()),
+                    None,
+                )?;
+                node.byte_range(6, 2).add_field(
+                    "Sequence Number",
+                    DumpValue::UInt(seq.into()),
+                    None,
+                )
+            }
+            None => node.byte_range(4, 4).add_field(
+                "Rest of Header",
+                DumpValue::Bytes(&self.rest[..]),
+                None,
+            ),
+        }
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_checksum();
+    }
+
+    fn validate(&self, validator: &mut Validator<'_>) {
+        validator.checksum("ICMP", self.chksum, self.checksum_valid());
+    }
+}
+
+register_dissector!(
+    icmp,
+    IpProtoDissectorTable,
+    IpProto::ICMP,
+    Priority(0),
+    Icmp::dissect
+);
+crate::register_ip_proto_pdu!(Icmp, IpProto::ICMP);
+register_field!(icmp_type, "icmp.type" => "ICMP.Type");
+register_field!(icmp_code, "icmp.code" => "ICMP.Code");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn echo() {
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        let mut icmp = Icmp::echo_request(0x1234, 7);
+        icmp.set_inner_pdu(RawPdu::new(vec![0xab; 16]));
+        ipv4.set_inner_pdu(icmp);
+        ipv4.make_all_canonical();
+
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+        let session = Session::new();
+        let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        let icmp = ipv4.find::<Icmp>().unwrap();
+        assert_eq!(icmp.echo(), Some((0x1234, 7)));
+        assert!(icmp.checksum_valid());
+        assert!(icmp.original().is_none());
+        assert_eq!(icmp.inner_pdu().unwrap().total_len(), 16);
+    }
+
+    #[test]
+    fn checksum_of_dissected_message() {
+        let mut data = [
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, // IPv4
+            0x40, 0x01, 0x66, 0xda, 0x0a, 0x00, 0x00, 0x01, //
+            0x0a, 0x00, 0x00, 0x02, //
+            0x08, 0x00, 0x33, 0x37, // Echo request, checksum
+            0x00, 0x01, 0x00, 0x01, // Identifier, sequence number
+            0x61, 0x62, 0x63, 0x64, // Payload
+        ];
+        // Dissected from a packet buffer, as sniffed packets are, so the
+        // captured bytes are kept
+        let session = Session::new();
+        let dissect = |data: &[u8]| {
+            let buffer = Bytes::copy_from_slice(data);
+            let res = session.with_packet_buffer(&buffer, || {
+                Ipv4::dissect(&buffer[..], &session, None).map(|(_, ipv4)| ipv4)
+            });
+            res.unwrap()
+        };
+        let ipv4 = dissect(&data[..]);
+        let icmp = ipv4.find::<Icmp>().unwrap();
+        assert!(icmp.base_pdu().original_bytes().is_some());
+        assert_eq!(icmp.calc_checksum(), 0x3337);
+        assert!(icmp.checksum_valid());
+
+        data[31] ^= 1;
+        let ipv4 = dissect(&data[..]);
+        assert!(!ipv4.find::<Icmp>().unwrap().checksum_valid());
+    }
+    #[test]
+    fn time_exceeded() {
+        // The original packet, a UDP datagram cut short after its header
+        let mut orig = vec![0x45, 0, 0, 48, 0, 1, 0, 0, 1, 17, 0, 0];
+        orig.extend_from_slice(&[192, 168, 1, 10, 8, 8, 8, 8][..]);
+        orig.extend_from_slice(&[0x82, 0x9b, 0, 53, 0, 28, 0, 0][..]);
+
+        let mut icmp = Icmp::new(msg_type::TIME_EXCEEDED, 0);
+        icmp.set_inner_pdu(RawPdu::new(orig));
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 254].into(), [192, 168, 1, 10].into());
+        ipv4.set_inner_pdu(icmp);
+        ipv4.make_all_canonical();
+
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+        let session = Session::new();
+        let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        let icmp = ipv4.find::<Icmp>().unwrap();
+        assert!(icmp.checksum_valid());
+        assert_eq!(icmp.original().unwrap().dst_address(), [8, 8, 8, 8].into());
+        assert_eq!(
+            icmp.original_flow(),
+            Some(IcmpFlow {
+                src_addr: [192, 168, 1, 10].into(),
+                dst_addr: [8, 8, 8, 8].into(),
+                proto: IpProto::UDP,
+                ports: Some((33435, 53)),
+            })
+        );
+
+        // A TCP segment cut short is still dissected as IPv4
+        let mut orig = vec![0x45, 0, 0, 60, 0, 2, 0, 0, 1, 6, 0, 0];
+        orig.extend_from_slice(&[192, 168, 1, 10, 1, 1, 1, 1][..]);
+        orig.extend_from_slice(&[0xc0, 0x00, 0x01, 0xbb, 0, 0, 0, 1][..]);
+        let mut tcp_icmp = Icmp::new(msg_type::DEST_UNREACHABLE, 3);
+        tcp_icmp.set_inner_pdu(RawPdu::new(orig));
+        let mut tcp_buf = Vec::new();
+        tcp_icmp.serialize(&mut tcp_buf).unwrap();
+        let (_, tcp_icmp) = Icmp::dissect(&tcp_buf[..], &session, None).unwrap();
+        let flow = tcp_icmp.original_flow().unwrap();
+        assert_eq!(
+            (flow.proto, flow.ports),
+            (IpProto::TCP, Some((0xc000, 443)))
+        );
+
+        // Serializing the dissected packet reproduces it exactly
+        let mut out = Vec::new();
+        ipv4.serialize(&mut out).unwrap();
+        assert_eq!(out, buf);
+    }
+}
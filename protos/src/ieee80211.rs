@@ -0,0 +1,785 @@
+use super::ethernet_ii::EthertypeDissectorTable;
+use super::ethertype::Ethertype;
+use crate::prelude::*;
+use nom::combinator::map;
+use sniffle_core::MacAddress;
+
+/// IEEE 802.11 MAC frame (`LinkType::IEEE802_11`).
+///
+/// Management frame bodies are parsed into fixed fields and information
+/// elements. Unprotected data frames carrying an LLC/SNAP header are
+/// dispatched by ethertype. Everything else is left as a raw payload.
+#[derive(Debug, Clone)]
+pub struct Ieee80211 {
+    base: BasePdu,
+    frame_control: u16,
+    duration: u16,
+    addr1: MacAddress,
+    addr2: Option<MacAddress>,
+    addr3: Option<MacAddress>,
+    seq_ctrl: Option<u16>,
+    addr4: Option<MacAddress>,
+    qos_ctrl: Option<u16>,
+    ht_ctrl: Option<u32>,
+    mgmt: Option<Management>,
+    snap: Option<Snap>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameType(pub u8);
+
+/// Management frame body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Management {
+    /// Fixed length fields preceding the information elements. Holds the
+    /// whole body for frames without elements, such as action frames.
+    pub fixed: Bytes,
+    pub elements: Vec<Element>,
+}
+
+/// Information element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub id: u8,
+    pub data: Bytes,
+}
+
+/// LLC/SNAP header preceding the payload of a data frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snap {
+    pub oui: [u8; 3],
+    pub ethertype: Ethertype,
+}
+
+/// Frame control flags
+pub mod fc_flags {
+    pub const TO_DS: u16 = 0x0100;
+    pub const FROM_DS: u16 = 0x0200;
+    pub const MORE_FRAGMENTS: u16 = 0x0400;
+    pub const RETRY: u16 = 0x0800;
+    pub const POWER_MGMT: u16 = 0x1000;
+    pub const MORE_DATA: u16 = 0x2000;
+    pub const PROTECTED: u16 = 0x4000;
+    pub const ORDER: u16 = 0x8000;
+}
+
+/// Management frame subtypes
+pub mod mgmt_subtype {
+    pub const ASSOC_REQ: u8 = 0;
+    pub const ASSOC_RESP: u8 = 1;
+    pub const REASSOC_REQ: u8 = 2;
+    pub const REASSOC_RESP: u8 = 3;
+    pub const PROBE_REQ: u8 = 4;
+    pub const PROBE_RESP: u8 = 5;
+    pub const BEACON: u8 = 8;
+    pub const ATIM: u8 = 9;
+    pub const DISASSOC: u8 = 10;
+    pub const AUTH: u8 = 11;
+    pub const DEAUTH: u8 = 12;
+    pub const ACTION: u8 = 13;
+}
+
+/// Control frame subtypes
+pub mod ctrl_subtype {
+    pub const BLOCK_ACK_REQ: u8 = 8;
+    pub const BLOCK_ACK: u8 = 9;
+    pub const PS_POLL: u8 = 10;
+    pub const RTS: u8 = 11;
+    pub const CTS: u8 = 12;
+    pub const ACK: u8 = 13;
+    pub const CF_END: u8 = 14;
+}
+
+/// Data frame subtypes
+pub mod data_subtype {
+    pub const DATA: u8 = 0;
+    pub const NULL: u8 = 4;
+    pub const QOS_DATA: u8 = 8;
+    pub const QOS_NULL: u8 = 12;
+}
+
+/// Information element IDs
+pub mod element_id {
+    pub const SSID: u8 = 0;
+    pub const SUPPORTED_RATES: u8 = 1;
+    pub const DS_PARAMETER_SET: u8 = 3;
+    pub const TIM: u8 = 5;
+    pub const COUNTRY: u8 = 7;
+    pub const HT_CAPABILITIES: u8 = 45;
+    pub const RSN: u8 = 48;
+    pub const EXTENDED_SUPPORTED_RATES: u8 = 50;
+    pub const HT_OPERATION: u8 = 61;
+    pub const VENDOR_SPECIFIC: u8 = 221;
+}
+
+const LLC_SNAP: [u8; 3] = [0xaa, 0xaa, 0x03];
+
+impl FrameType {
+    pub const MANAGEMENT: Self = Self(0);
+    pub const CONTROL: Self = Self(1);
+    pub const DATA: Self = Self(2);
+    pub const EXTENSION: Self = Self(3);
+}
+
+impl std::fmt::Display for FrameType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Self::MANAGEMENT => "Management",
+            Self::CONTROL => "Control",
+            Self::DATA => "Data",
+            _ => "Extension",
+        })
+    }
+}
+
+/// Length of the fixed fields in a management frame body, or `None` if the
+/// body has no information elements
+fn mgmt_fixed_len(subtype: u8) -> Option<usize> {
+    Some(match subtype {
+        mgmt_subtype::ASSOC_REQ => 4,
+        mgmt_subtype::ASSOC_RESP | mgmt_subtype::REASSOC_RESP => 6,
+        mgmt_subtype::REASSOC_REQ => 10,
+        mgmt_subtype::PROBE_REQ => 0,
+        mgmt_subtype::PROBE_RESP | mgmt_subtype::BEACON => 12,
+        mgmt_subtype::AUTH => 6,
+        mgmt_subtype::DISASSOC | mgmt_subtype::DEAUTH => 2,
+        _ => return None,
+    })
+}
+
+impl Management {
+    fn decode(subtype: u8, body: &[u8], session: &Session) -> Self {
+        let whole = || Self {
+            fixed: session.packet_bytes(body),
+            elements: Vec::new(),
+        };
+        let Some(fixed_len) = mgmt_fixed_len(subtype) else {
+            return whole();
+        };
+        let Some((fixed, mut buf)) = body.split_at_checked(fixed_len) else {
+            return whole();
+        };
+        let mut elements = Vec::new();
+        while !buf.is_empty() {
+            let Some(len) = buf.get(1) else {
+                return whole();
+            };
+            let Some(data) = buf.get(2..2 + *len as usize) else {
+                return whole();
+            };
+            elements.push(Element {
+                id: buf[0],
+                data: session.packet_bytes(data),
+            });
+            buf = &buf[2 + data.len()..];
+        }
+        Self {
+            fixed: session.packet_bytes(fixed),
+            elements,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.fixed.len()
+            + self
+                .elements
+                .iter()
+                .map(|elem| 2 + elem.data.len())
+                .sum::<usize>()
+    }
+
+    pub fn element(&self, id: u8) -> Option<&[u8]> {
+        self.elements
+            .iter()
+            .find(|elem| elem.id == id)
+            .map(|elem| &elem.data[..])
+    }
+}
+
+impl Ieee80211 {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            frame_control: 0,
+            duration: 0,
+            addr1: MacAddress::default(),
+            addr2: None,
+            addr3: None,
+            seq_ctrl: None,
+            addr4: None,
+            qos_ctrl: None,
+            ht_ctrl: None,
+            mgmt: None,
+            snap: None,
+        }
+    }
+
+    /// Creates a data frame carrying an LLC/SNAP encapsulated payload
+    pub fn new_data(receiver: MacAddress, transmitter: MacAddress, bssid: MacAddress) -> Self {
+        Self {
+            frame_control: u16::from(FrameType::DATA.0) << 2,
+            addr1: receiver,
+            addr2: Some(transmitter),
+            addr3: Some(bssid),
+            seq_ctrl: Some(0),
+            snap: Some(Snap {
+                oui: [0, 0, 0],
+                ethertype: Ethertype(0),
+            }),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a management frame with the given subtype and body
+    pub fn new_management(
+        subtype: u8,
+        dst: MacAddress,
+        src: MacAddress,
+        bssid: MacAddress,
+        body: Management,
+    ) -> Self {
+        Self {
+            frame_control: (u16::from(subtype) << 4) | (u16::from(FrameType::MANAGEMENT.0) << 2),
+            addr1: dst,
+            addr2: Some(src),
+            addr3: Some(bssid),
+            seq_ctrl: Some(0),
+            mgmt: Some(body),
+            ..Self::new()
+        }
+    }
+
+    pub fn frame_control(&self) -> u16 {
+        self.frame_control
+    }
+
+    pub fn frame_control_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.frame_control
+    }
+
+    pub fn protocol_version(&self) -> u8 {
+        (self.frame_control & 0x3) as u8
+    }
+
+    pub fn frame_type(&self) -> FrameType {
+        FrameType(((self.frame_control >> 2) & 0x3) as u8)
+    }
+
+    pub fn subtype(&self) -> u8 {
+        ((self.frame_control >> 4) & 0xf) as u8
+    }
+
+    pub fn has_flags(&self, flags: u16) -> bool {
+        self.frame_control & flags == flags
+    }
+
+    pub fn duration(&self) -> u16 {
+        self.duration
+    }
+
+    pub fn duration_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.duration
+    }
+
+    pub fn addr1(&self) -> MacAddress {
+        self.addr1
+    }
+
+    pub fn addr1_mut(&mut self) -> &mut MacAddress {
+        self.base.mark_dirty();
+        &mut self.addr1
+    }
+
+    pub fn addr2(&self) -> Option<MacAddress> {
+        self.addr2
+    }
+
+    pub fn addr2_mut(&mut self) -> &mut Option<MacAddress> {
+        self.base.mark_dirty();
+        &mut self.addr2
+    }
+
+    pub fn addr3(&self) -> Option<MacAddress> {
+        self.addr3
+    }
+
+    pub fn addr3_mut(&mut self) -> &mut Option<MacAddress> {
+        self.base.mark_dirty();
+        &mut self.addr3
+    }
+
+    pub fn addr4(&self) -> Option<MacAddress> {
+        self.addr4
+    }
+
+    pub fn addr4_mut(&mut self) -> &mut Option<MacAddress> {
+        self.base.mark_dirty();
+        &mut self.addr4
+    }
+
+    pub fn sequence_control(&self) -> Option<u16> {
+        self.seq_ctrl
+    }
+
+    pub fn sequence_control_mut(&mut self) -> &mut Option<u16> {
+        self.base.mark_dirty();
+        &mut self.seq_ctrl
+    }
+
+    pub fn sequence_number(&self) -> Option<u16> {
+        self.seq_ctrl.map(|seq| seq >> 4)
+    }
+
+    pub fn fragment_number(&self) -> Option<u8> {
+        self.seq_ctrl.map(|seq| (seq & 0xf) as u8)
+    }
+
+    pub fn qos_control(&self) -> Option<u16> {
+        self.qos_ctrl
+    }
+
+    pub fn qos_control_mut(&mut self) -> &mut Option<u16> {
+        self.base.mark_dirty();
+        &mut self.qos_ctrl
+    }
+
+    pub fn ht_control(&self) -> Option<u32> {
+        self.ht_ctrl
+    }
+
+    pub fn ht_control_mut(&mut self) -> &mut Option<u32> {
+        self.base.mark_dirty();
+        &mut self.ht_ctrl
+    }
+
+    /// The receiver address (addr1)
+    pub fn receiver(&self) -> MacAddress {
+        self.addr1
+    }
+
+    /// The transmitter address (addr2), absent in CTS and ACK frames
+    pub fn transmitter(&self) -> Option<MacAddress> {
+        self.addr2
+    }
+
+    /// The BSS ID of management and data frames, based on the DS bits
+    pub fn bssid(&self) -> Option<MacAddress> {
+        match self.frame_type() {
+            FrameType::MANAGEMENT => self.addr3,
+            FrameType::DATA => match (
+                self.has_flags(fc_flags::TO_DS),
+                self.has_flags(fc_flags::FROM_DS),
+            ) {
+                (false, false) => self.addr3,
+                (false, true) => self.addr2,
+                (true, false) => Some(self.addr1),
+                (true, true) => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn management(&self) -> Option<&Management> {
+        self.mgmt.as_ref()
+    }
+
+    pub fn management_mut(&mut self) -> &mut Option<Management> {
+        self.base.mark_dirty();
+        &mut self.mgmt
+    }
+
+    pub fn snap(&self) -> Option<&Snap> {
+        self.snap.as_ref()
+    }
+
+    pub fn snap_mut(&mut self) -> &mut Option<Snap> {
+        self.base.mark_dirty();
+        &mut self.snap
+    }
+
+    /// The SSID element of a management frame
+    pub fn ssid(&self) -> Option<&[u8]> {
+        self.mgmt.as_ref()?.element(element_id::SSID)
+    }
+
+    /// Beacon interval of a beacon or probe response, in time units
+    pub fn beacon_interval(&self) -> Option<u16> {
+        match self.subtype() {
+            mgmt_subtype::BEACON | mgmt_subtype::PROBE_RESP => {
+                let fixed = &self.mgmt.as_ref()?.fixed;
+                Some(u16::from_le_bytes([*fixed.get(8)?, *fixed.get(9)?]))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn update_ethertype(&mut self) {
+        self.base.mark_dirty();
+        let ethertype = self.inner_pdu().and_then(Ethertype::from_pdu);
+        if let (Some(snap), Some(ethertype)) = (self.snap.as_mut(), ethertype) {
+            snap.ethertype = ethertype;
+        }
+    }
+
+    fn mac_header_len(&self) -> usize {
+        4 + 6
+            + self.addr2.map(|_| 6).unwrap_or(0)
+            + self.addr3.map(|_| 6).unwrap_or(0)
+            + self.seq_ctrl.map(|_| 2).unwrap_or(0)
+            + self.addr4.map(|_| 6).unwrap_or(0)
+            + self.qos_ctrl.map(|_| 2).unwrap_or(0)
+            + self.ht_ctrl.map(|_| 4).unwrap_or(0)
+    }
+}
+
+fn mac(buf: &[u8]) -> Option<(MacAddress, &[u8])> {
+    let addr: [u8; 6] = buf.get(..6)?.try_into().ok()?;
+    Some((MacAddress::from(addr), &buf[6..]))
+}
+
+fn le_u16(buf: &[u8]) -> Option<(u16, &[u8])> {
+    Some((u16::from_le_bytes([*buf.first()?, *buf.get(1)?]), &buf[2..]))
+}
+
+impl Ieee80211 {
+    /// Parses the MAC header, leaving the frame body
+    fn parse_header(buf: &[u8]) -> Option<(Self, &[u8])> {
+        let mut frame = Self::new();
+        let (frame_control, buf) = le_u16(buf)?;
+        let (duration, buf) = le_u16(buf)?;
+        let (addr1, mut buf) = mac(buf)?;
+        frame.frame_control = frame_control;
+        frame.duration = duration;
+        frame.addr1 = addr1;
+        let subtype = frame.subtype();
+        match frame.frame_type() {
+            FrameType::CONTROL if !matches!(subtype, ctrl_subtype::CTS | ctrl_subtype::ACK) => {
+                let (addr2, rest) = mac(buf)?;
+                frame.addr2 = Some(addr2);
+                buf = rest;
+            }
+            FrameType::MANAGEMENT | FrameType::DATA => {
+                let (addr2, rest) = mac(buf)?;
+                let (addr3, rest) = mac(rest)?;
+                let (seq_ctrl, rest) = le_u16(rest)?;
+                frame.addr2 = Some(addr2);
+                frame.addr3 = Some(addr3);
+                frame.seq_ctrl = Some(seq_ctrl);
+                buf = rest;
+                let is_data = frame.frame_type() == FrameType::DATA;
+                if is_data && frame.has_flags(fc_flags::TO_DS | fc_flags::FROM_DS) {
+                    let (addr4, rest) = mac(buf)?;
+                    frame.addr4 = Some(addr4);
+                    buf = rest;
+                }
+                let is_qos = is_data && subtype & 0x8 != 0;
+                if is_qos {
+                    let (qos_ctrl, rest) = le_u16(buf)?;
+                    frame.qos_ctrl = Some(qos_ctrl);
+                    buf = rest;
+                }
+                if (is_qos || !is_data) && frame.has_flags(fc_flags::ORDER) {
+                    let ht_ctrl: [u8; 4] = buf.get(..4)?.try_into().ok()?;
+                    frame.ht_ctrl = Some(u32::from_le_bytes(ht_ctrl));
+                    buf = &buf[4..];
+                }
+            }
+            _ => {}
+        }
+        Some((frame, buf))
+    }
+}
+
+impl Dissect for Ieee80211 {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (mut frame, mut body) =
+            Self::parse_header(buf).ok_or(nom::Err::Error(DissectError::Malformed))?;
+        let protected = frame.has_flags(fc_flags::PROTECTED);
+        match frame.frame_type() {
+            FrameType::MANAGEMENT if !protected => {
+                frame.mgmt = Some(Management::decode(frame.subtype(), body, session));
+                body = &body[body.len()..];
+            }
+            FrameType::DATA if !protected && body.len() >= 8 && body[..3] == LLC_SNAP => {
+                frame.snap = Some(Snap {
+                    oui: [body[3], body[4], body[5]],
+                    ethertype: Ethertype(u16::from_be_bytes([body[6], body[7]])),
+                });
+                body = &body[8..];
+            }
+            _ => {}
+        }
+        if body.is_empty() {
+            return Ok((body, frame));
+        }
+        let (rem, inner) = match frame.snap {
+            Some(snap) => session
+                .table_dissector::<EthertypeDissectorTable>(
+                    &snap.ethertype,
+                    Some(TempPdu::new(&frame, &parent)),
+                )
+                .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                .parse(body)?,
+            None => map(RawPdu::dissector(session, None), AnyPdu::new).parse(body)?,
+        };
+        frame.set_inner_pdu(inner);
+        Ok((rem, frame))
+    }
+}
+
+impl Pdu for Ieee80211 {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.mac_header_len()
+            + self.mgmt.as_ref().map(|mgmt| mgmt.len()).unwrap_or(0)
+            + self.snap.map(|_| 8).unwrap_or(0)
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode_le(&self.frame_control)?
+            .encode_le(&self.duration)?
+            .encode(&self.addr1)?;
+        if let Some(addr2) = &self.addr2 {
+            encoder.encode(addr2)?;
+        }
+        if let Some(addr3) = &self.addr3 {
+            encoder.encode(addr3)?;
+        }
+        if let Some(seq_ctrl) = &self.seq_ctrl {
+            encoder.encode_le(seq_ctrl)?;
+        }
+        if let Some(addr4) = &self.addr4 {
+            encoder.encode(addr4)?;
+        }
+        if let Some(qos_ctrl) = &self.qos_ctrl {
+            encoder.encode_le(qos_ctrl)?;
+        }
+        if let Some(ht_ctrl) = &self.ht_ctrl {
+            encoder.encode_le(ht_ctrl)?;
+        }
+        if let Some(mgmt) = &self.mgmt {
+            encoder.encode(&mgmt.fixed[..])?;
+            for elem in mgmt.elements.iter() {
+                encoder
+                    .encode(&elem.id)?
+                    .encode(&(elem.data.len() as u8))?
+                    .encode(&elem.data[..])?;
+            }
+        }
+        if let Some(snap) = &self.snap {
+            encoder
+                .encode(&LLC_SNAP[..])?
+                .encode(&snap.oui[..])?
+                .encode_be(&snap.ethertype.0)?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("IEEE 802.11", Some(&self.frame_type().to_string()[..]))?;
+        node.byte_range(0, 2).add_field(
+            "Frame Control",
+            DumpValue::UInt(self.frame_control.into()),
+            Some(&format!("0x{:04x}", self.frame_control)[..]),
+        )?;
+        node.add_field(
+            "Type",
+            DumpValue::UInt(self.frame_type().0.into()),
+            Some(&self.frame_type().to_string()[..]),
+        )?;
+        node.add_field("Subtype", DumpValue::UInt(self.subtype().into()), None)?;
+        node.byte_range(2, 2)
+            .add_field("Duration", DumpValue::UInt(self.duration.into()), None)?;
+        let mut offset = 4;
+        for (name, addr) in [
+            ("Address 1", Some(self.addr1)),
+            ("Address 2", self.addr2),
+            ("Address 3", self.addr3),
+        ] {
+            if let Some(addr) = addr {
+                node.byte_range(offset, 6).add_field(
+                    name,
+                    DumpValue::Bytes(&addr[..]),
+                    Some(&addr.to_string()[..]),
+                )?;
+                offset += 6;
+            }
+        }
+        if let Some(seq_ctrl) = self.seq_ctrl {
+            node.byte_range(offset, 2).add_field(
+                "Sequence Number",
+                DumpValue::UInt((seq_ctrl >> 4).into()),
+                None,
+            )?;
+            node.byte_range(offset, 2).add_field(
+                "Fragment Number",
+                DumpValue::UInt((seq_ctrl & 0xf).into()),
+                None,
+            )?;
+            offset += 2;
+        }
+        if let Some(addr4) = self.addr4 {
+            node.byte_range(offset, 6).add_field(
+                "Address 4",
+                DumpValue::Bytes(&addr4[..]),
+                Some(&addr4.to_string()[..]),
+            )?;
+            offset += 6;
+        }
+        if let Some(qos_ctrl) = self.qos_ctrl {
+            node.byte_range(offset, 2).add_field(
+                "QoS Control",
+                DumpValue::UInt(qos_ctrl.into()),
+                Some(&format!("TID {}", qos_ctrl & 0xf)[..]),
+            )?;
+            offset += 2;
+        }
+        if let Some(ht_ctrl) = self.ht_ctrl {
+            node.byte_range(offset, 4).add_field(
+                "HT Control",
+                DumpValue::UInt(ht_ctrl.into()),
+                None,
+            )?;
+            offset += 4;
+        }
+        if let Some(mgmt) = &self.mgmt {
+            if !mgmt.fixed.is_empty() {
+                node.byte_range(offset, mgmt.fixed.len()).add_field(
+                    "Fixed Parameters",
+                    DumpValue::Bytes(&mgmt.fixed[..]),
+                    None,
+                )?;
+                offset += mgmt.fixed.len();
+            }
+            for elem in mgmt.elements.iter() {
+                let descr = match elem.id {
+                    element_id::SSID => String::from_utf8_lossy(&elem.data[..]).into_owned(),
+                    _ => format!("ID {}", elem.id),
+                };
+                node.byte_range(offset, 2 + elem.data.len()).add_field(
+                    "Element",
+                    DumpValue::Bytes(&elem.data[..]),
+                    Some(&descr[..]),
+                )?;
+                offset += 2 + elem.data.len();
+            }
+        }
+        if let Some(snap) = &self.snap {
+            node.byte_range(offset + 6, 2).add_field(
+                "Ethertype",
+                DumpValue::UInt(snap.ethertype.0.into()),
+                Some(&format!("0x{:04x}", snap.ethertype.0)[..]),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_ethertype();
+    }
+}
+
+impl Default for Ieee80211 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_link_layer_pdu!(Ieee80211, LinkType::IEEE802_11);
+register_dissector!(
+    ieee80211,
+    LinkTypeTable,
+    LinkType::IEEE802_11,
+    Priority(0),
+    Ieee80211::dissect
+);
+
+#[cfg(all(test, feature = "radiotap"))]
+mod test {
+    use super::*;
+    use crate::ipv4::Ipv4;
+    use crate::radiotap::{flags, FieldKind, Radiotap};
+
+    #[test]
+    fn radiotap_ieee80211() {
+        let ap = MacAddress::from([0x02, 0, 0, 0, 0, 0x01]);
+        let sta = MacAddress::from([0x02, 0, 0, 0, 0, 0x02]);
+        let session = Session::new();
+
+        let mut radiotap = Radiotap::new();
+        radiotap.set_field(FieldKind::ANTENNA_SIGNAL, vec![-42i8 as u8]);
+        radiotap.set_field(
+            FieldKind::TSFT,
+            0x0102030405060708u64.to_le_bytes().to_vec(),
+        );
+        radiotap.set_field(FieldKind::FLAGS, vec![flags::FCS]);
+        radiotap.set_field(FieldKind::CHANNEL, vec![0x85, 0x09, 0xa0, 0x00]);
+        let beacon = Ieee80211::new_management(
+            mgmt_subtype::BEACON,
+            MacAddress::from([0xff; 6]),
+            ap,
+            ap,
+            Management {
+                fixed: Bytes::from_static(&[0, 0, 0, 0, 0, 0, 0, 0, 0x64, 0x00, 0x01, 0x04]),
+                elements: vec![Element {
+                    id: element_id::SSID,
+                    data: Bytes::from_static(b"sniffle"),
+                }],
+            },
+        );
+        radiotap.set_inner_pdu(beacon);
+        *radiotap.fcs_mut() = Some(0);
+        radiotap.make_canonical();
+
+        let mut buf = Vec::new();
+        radiotap.serialize(&mut buf).unwrap();
+        // header and present bitmap, tsft, flags, padding, channel, signal
+        assert_eq!(radiotap.length(), 8 + 8 + 1 + 1 + 4 + 1);
+        assert_eq!(radiotap.present(), [0x2b]);
+
+        let (rem, dissected) = Radiotap::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(dissected.tsft(), Some(0x0102030405060708));
+        assert_eq!(dissected.channel(), Some((2437, 0xa0)));
+        assert_eq!(dissected.antenna_signal(), Some(-42));
+        assert_eq!(dissected.fcs_valid(), Some(true));
+        let frame = dissected.find::<Ieee80211>().unwrap();
+        assert_eq!(frame.frame_type(), FrameType::MANAGEMENT);
+        assert_eq!(frame.ssid(), Some(&b"sniffle"[..]));
+        assert_eq!(frame.beacon_interval(), Some(100));
+        assert_eq!(frame.bssid(), Some(ap));
+
+        let mut data = Ieee80211::new_data(ap, sta, ap);
+        *data.frame_control_mut() |= fc_flags::TO_DS;
+        let mut ipv4 = Ipv4::new();
+        ipv4.set_inner_pdu(RawPdu::new(vec![0xab; 8]));
+        ipv4.make_canonical();
+        data.set_inner_pdu(ipv4);
+        data.make_canonical();
+        let mut buf = Vec::new();
+        data.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 24 + 8 + 28);
+
+        let (_, dissected) = Ieee80211::dissect(&buf[..], &session, None).unwrap();
+        assert_eq!(dissected.snap().unwrap().ethertype, Ethertype::IPV4);
+        assert_eq!(dissected.bssid(), Some(ap));
+        assert!(dissected.find::<Ipv4>().is_some());
+    }
+}
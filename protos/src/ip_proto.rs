@@ -1,4 +1,5 @@
 use super::prelude::*;
+use checksum::IpPseudoHeader;
 use lazy_static::*;
 use parking_lot::RwLock;
 use std::collections::HashMap;
@@ -6,8 +7,12 @@ use std::collections::HashMap;
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct IpProto(pub u8);
 
+type PseudoHeaderFn = fn(&AnyPdu) -> Option<IpPseudoHeader>;
+
 lazy_static! {
     static ref IP_PROTO_PDUS: RwLock<HashMap<PduType, IpProto>> = RwLock::new(HashMap::new());
+    static ref PSEUDO_HEADER_PDUS: RwLock<HashMap<PduType, PseudoHeaderFn>> =
+        RwLock::new(HashMap::new());
 }
 
 macro_rules! ip_proto {
@@ -207,3 +212,81 @@ macro_rules! register_ip_proto_pdu {
         }
     };
 }
+
+/// Builds the checksum pseudo-header from the closest IP layer containing
+/// `pdu`, or returns `None` if there isn't one.
+///
+/// This relies on `parent_pdu`, so it's meant to be used from
+/// `Pdu::make_canonical` and `Pdu::validate` after the links have been set
+/// by `PduExt::link_parent_pdus`, as `make_all_canonical` and
+/// `Packet::validate` do.
+pub fn pseudo_header<P: Pdu>(pdu: &P) -> Option<IpPseudoHeader> {
+    let mut parent = pdu.parent_pdu();
+    while let Some(pdu) = parent {
+        let func = PSEUDO_HEADER_PDUS.read().get(&pdu.pdu_type()).copied();
+        if let Some(func) = func {
+            return func(pdu);
+        }
+        parent = pdu.parent_pdu();
+    }
+    None
+}
+
+/// Returns true if bytes of `pdu`, or of the IP payload containing it, were
+/// not captured, in which case its checksum can't be verified.
+///
+/// Like `pseudo_header`, this relies on `parent_pdu`.
+pub fn payload_truncated<P: Pdu>(pdu: &P) -> bool {
+    if pdu.is_truncated() {
+        return true;
+    }
+    let mut parent = pdu.parent_pdu();
+    while let Some(pdu) = parent {
+        // Parent links have no inner PDU, so this only counts bytes missing
+        // from the payload of the parent itself
+        if pdu.is_truncated() {
+            return true;
+        }
+        if PSEUDO_HEADER_PDUS.read().contains_key(&pdu.pdu_type()) {
+            break;
+        }
+        parent = pdu.parent_pdu();
+    }
+    false
+}
+
+#[doc(hidden)]
+pub fn _register_pseudo_header_pdu<P: Pdu>(func: PseudoHeaderFn) {
+    if PSEUDO_HEADER_PDUS
+        .write()
+        .insert(PduType::of::<P>(), func)
+        .is_some()
+    {
+        panic!("A Pdu can only register one pseudo-header");
+    }
+}
+
+#[doc(hidden)]
+pub fn _downcast_pseudo_header<P: Pdu, H: Into<IpPseudoHeader>>(
+    pdu: &AnyPdu,
+    func: fn(&P) -> H,
+) -> Option<IpPseudoHeader> {
+    pdu.downcast_ref::<P>().map(|pdu| func(pdu).into())
+}
+
+/// Registers a function building the checksum pseudo-header of an IP layer
+/// PDU, for use by `pseudo_header`.
+#[macro_export]
+macro_rules! register_pseudo_header_pdu {
+    ($pdu:ty, $func:expr) => {
+        $crate::paste::paste! {
+            #[$crate::ctor::ctor]
+            #[allow(non_snake_case)]
+            fn [<__sniffle_registry_pseudo_header_pdu_ $pdu>]() {
+                $crate::ip_proto::_register_pseudo_header_pdu::<$pdu>(|pdu| {
+                    $crate::ip_proto::_downcast_pseudo_header::<$pdu, _>(pdu, $func)
+                });
+            }
+        }
+    };
+}
@@ -0,0 +1,510 @@
+use crate::i2c::I2cDissectorTable;
+use crate::prelude::*;
+use nom::{
+    bytes::complete::take,
+    combinator::{flat_map, verify},
+    sequence::tuple,
+};
+
+/// Intelligent Platform Management Bus message, carrying an IPMI request
+/// or response
+#[derive(Debug, Clone)]
+pub struct Ipmb {
+    base: BasePdu,
+    dst_addr: u8,
+    netfn_lun: u8,
+    hdr_checksum: u8,
+    src_addr: u8,
+    seq_lun: u8,
+    cmd: u8,
+    data: Vec<u8>,
+    checksum: u8,
+}
+
+/// IPMI network function. Odd values are responses to the request with
+/// the preceding even value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetFn(pub u8);
+
+impl NetFn {
+    pub const CHASSIS: Self = Self(0x00);
+    pub const BRIDGE: Self = Self(0x02);
+    pub const SENSOR_EVENT: Self = Self(0x04);
+    pub const APP: Self = Self(0x06);
+    pub const FIRMWARE: Self = Self(0x08);
+    pub const STORAGE: Self = Self(0x0a);
+    pub const TRANSPORT: Self = Self(0x0c);
+    pub const GROUP_EXT: Self = Self(0x2c);
+    pub const OEM_GROUP: Self = Self(0x2e);
+
+    pub fn is_response(&self) -> bool {
+        (self.0 & 0x01) != 0
+    }
+
+    /// The request network function of a request/response pair
+    pub fn request(&self) -> Self {
+        Self(self.0 & !0x01)
+    }
+
+    pub fn response(&self) -> Self {
+        Self(self.0 | 0x01)
+    }
+}
+
+impl std::fmt::Display for NetFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.request() {
+            Self::CHASSIS => "Chassis",
+            Self::BRIDGE => "Bridge",
+            Self::SENSOR_EVENT => "Sensor/Event",
+            Self::APP => "Application",
+            Self::FIRMWARE => "Firmware",
+            Self::STORAGE => "Storage",
+            Self::TRANSPORT => "Transport",
+            Self::GROUP_EXT => "Group Extension",
+            Self::OEM_GROUP => "OEM/Group",
+            _ if self.0 >= 0x30 => "OEM",
+            _ => "Reserved",
+        };
+        if self.is_response() {
+            write!(f, "{} Response", name)
+        } else {
+            write!(f, "{} Request", name)
+        }
+    }
+}
+
+/// Returns the name of a standard IPMI command
+pub fn command_name(netfn: NetFn, cmd: u8) -> Option<&'static str> {
+    Some(match (netfn.request(), cmd) {
+        (NetFn::CHASSIS, 0x00) => "Get Chassis Capabilities",
+        (NetFn::CHASSIS, 0x01) => "Get Chassis Status",
+        (NetFn::CHASSIS, 0x02) => "Chassis Control",
+        (NetFn::CHASSIS, 0x04) => "Chassis Identify",
+        (NetFn::CHASSIS, 0x08) => "Set System Boot Options",
+        (NetFn::CHASSIS, 0x09) => "Get System Boot Options",
+        (NetFn::SENSOR_EVENT, 0x02) => "Platform Event",
+        (NetFn::SENSOR_EVENT, 0x20) => "Get Device SDR Info",
+        (NetFn::SENSOR_EVENT, 0x21) => "Get Device SDR",
+        (NetFn::SENSOR_EVENT, 0x22) => "Reserve Device SDR Repository",
+        (NetFn::SENSOR_EVENT, 0x2d) => "Get Sensor Reading",
+        (NetFn::APP, 0x01) => "Get Device ID",
+        (NetFn::APP, 0x02) => "Cold Reset",
+        (NetFn::APP, 0x03) => "Warm Reset",
+        (NetFn::APP, 0x04) => "Get Self Test Results",
+        (NetFn::APP, 0x22) => "Reset Watchdog Timer",
+        (NetFn::APP, 0x24) => "Set Watchdog Timer",
+        (NetFn::APP, 0x25) => "Get Watchdog Timer",
+        (NetFn::APP, 0x2e) => "Set BMC Global Enables",
+        (NetFn::APP, 0x2f) => "Get BMC Global Enables",
+        (NetFn::APP, 0x33) => "Get Message",
+        (NetFn::APP, 0x34) => "Send Message",
+        (NetFn::APP, 0x37) => "Get System GUID",
+        (NetFn::APP, 0x38) => "Get Channel Authentication Capabilities",
+        (NetFn::STORAGE, 0x10) => "Get FRU Inventory Area Info",
+        (NetFn::STORAGE, 0x11) => "Read FRU Data",
+        (NetFn::STORAGE, 0x12) => "Write FRU Data",
+        (NetFn::STORAGE, 0x20) => "Get SDR Repository Info",
+        (NetFn::STORAGE, 0x22) => "Reserve SDR Repository",
+        (NetFn::STORAGE, 0x23) => "Get SDR",
+        (NetFn::STORAGE, 0x40) => "Get SEL Info",
+        (NetFn::STORAGE, 0x42) => "Reserve SEL",
+        (NetFn::STORAGE, 0x43) => "Get SEL Entry",
+        (NetFn::STORAGE, 0x44) => "Add SEL Entry",
+        (NetFn::STORAGE, 0x47) => "Clear SEL",
+        (NetFn::STORAGE, 0x48) => "Get SEL Time",
+        (NetFn::STORAGE, 0x49) => "Set SEL Time",
+        (NetFn::TRANSPORT, 0x01) => "Set LAN Configuration Parameters",
+        (NetFn::TRANSPORT, 0x02) => "Get LAN Configuration Parameters",
+        _ => return None,
+    })
+}
+
+/// Returns the description of an IPMI completion code
+pub fn completion_code_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0x00 => "Command Completed Normally",
+        0xc0 => "Node Busy",
+        0xc1 => "Invalid Command",
+        0xc2 => "Command Invalid for Given LUN",
+        0xc3 => "Timeout",
+        0xc4 => "Out of Space",
+        0xc5 => "Reservation Canceled or Invalid",
+        0xc6 => "Request Data Truncated",
+        0xc7 => "Request Data Length Invalid",
+        0xc8 => "Request Data Field Length Limit Exceeded",
+        0xc9 => "Parameter Out of Range",
+        0xca => "Cannot Return Number of Requested Data Bytes",
+        0xcb => "Requested Sensor, Data, or Record Not Present",
+        0xcc => "Invalid Data Field in Request",
+        0xcd => "Command Illegal for Specified Sensor or Record Type",
+        0xce => "Command Response Could Not Be Provided",
+        0xcf => "Cannot Execute Duplicated Request",
+        0xd0 => "SDR Repository in Update Mode",
+        0xd1 => "Device in Firmware Update Mode",
+        0xd2 => "BMC Initialization in Progress",
+        0xd3 => "Destination Unavailable",
+        0xd4 => "Insufficient Privilege Level",
+        0xd5 => "Command Not Supported in Present State",
+        0xd6 => "Command Sub-function Disabled or Unavailable",
+        0xff => "Unspecified Error",
+        _ => return None,
+    })
+}
+
+/// IPMB two's complement checksum, such that the sum of the checksummed
+/// bytes and the checksum is zero.
+pub fn ipmb_checksum(data: &[u8]) -> u8 {
+    0u8.wrapping_sub(data.iter().fold(0u8, |acc, byte| acc.wrapping_add(*byte)))
+}
+
+impl Ipmb {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            dst_addr: 0x20,
+            netfn_lun: 0,
+            hdr_checksum: 0,
+            src_addr: 0x81,
+            seq_lun: 0,
+            cmd: 0,
+            data: Vec::new(),
+            checksum: 0,
+        }
+    }
+
+    /// Slave address of the destination. This is the responder for
+    /// requests, and the requester for responses.
+    pub fn dst_addr(&self) -> u8 {
+        self.dst_addr
+    }
+
+    pub fn dst_addr_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.dst_addr
+    }
+
+    pub fn netfn(&self) -> NetFn {
+        NetFn(self.netfn_lun >> 2)
+    }
+
+    pub fn set_netfn(&mut self, netfn: NetFn) {
+        self.base.mark_dirty();
+        self.netfn_lun = (netfn.0 << 2) | (self.netfn_lun & 0x03);
+    }
+
+    pub fn dst_lun(&self) -> u8 {
+        self.netfn_lun & 0x03
+    }
+
+    pub fn set_dst_lun(&mut self, lun: u8) {
+        self.base.mark_dirty();
+        self.netfn_lun = (self.netfn_lun & 0xfc) | (lun & 0x03);
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.netfn().is_response()
+    }
+
+    pub fn hdr_checksum(&self) -> u8 {
+        self.hdr_checksum
+    }
+
+    pub fn hdr_checksum_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.hdr_checksum
+    }
+
+    pub fn calc_hdr_checksum(&self) -> u8 {
+        ipmb_checksum(&[self.dst_addr, self.netfn_lun])
+    }
+
+    pub fn update_hdr_checksum(&mut self) {
+        self.base.mark_dirty();
+        self.hdr_checksum = self.calc_hdr_checksum();
+    }
+
+    pub fn src_addr(&self) -> u8 {
+        self.src_addr
+    }
+
+    pub fn src_addr_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.src_addr
+    }
+
+    /// Request sequence number
+    pub fn seq(&self) -> u8 {
+        self.seq_lun >> 2
+    }
+
+    pub fn set_seq(&mut self, seq: u8) {
+        self.base.mark_dirty();
+        self.seq_lun = (seq << 2) | (self.seq_lun & 0x03);
+    }
+
+    pub fn src_lun(&self) -> u8 {
+        self.seq_lun & 0x03
+    }
+
+    pub fn set_src_lun(&mut self, lun: u8) {
+        self.base.mark_dirty();
+        self.seq_lun = (self.seq_lun & 0xfc) | (lun & 0x03);
+    }
+
+    pub fn cmd(&self) -> u8 {
+        self.cmd
+    }
+
+    pub fn cmd_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.cmd
+    }
+
+    pub fn cmd_name(&self) -> Option<&'static str> {
+        command_name(self.netfn(), self.cmd)
+    }
+
+    /// Completion code of a response, which is the first data byte
+    pub fn completion_code(&self) -> Option<u8> {
+        if self.is_response() {
+            self.data.first().copied()
+        } else {
+            None
+        }
+    }
+
+    /// Command data, including the completion code of a response
+    pub fn data(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.data
+    }
+
+    pub fn checksum(&self) -> u8 {
+        self.checksum
+    }
+
+    pub fn checksum_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.checksum
+    }
+
+    pub fn calc_checksum(&self) -> u8 {
+        ipmb_checksum(&[self.src_addr, self.seq_lun, self.cmd]).wrapping_sub(
+            self.data
+                .iter()
+                .fold(0u8, |acc, byte| acc.wrapping_add(*byte)),
+        )
+    }
+
+    pub fn update_checksum(&mut self) {
+        self.base.mark_dirty();
+        self.checksum = self.calc_checksum();
+    }
+}
+
+impl Dissect for Ipmb {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            verify(
+                tuple((
+                    u8::decode,
+                    u8::decode,
+                    u8::decode,
+                    u8::decode,
+                    u8::decode,
+                    u8::decode,
+                )),
+                |(dst_addr, netfn_lun, hdr_checksum, _, _, _)| {
+                    ipmb_checksum(&[*dst_addr, *netfn_lun]) == *hdr_checksum
+                },
+            ),
+            |(dst_addr, netfn_lun, hdr_checksum, src_addr, seq_lun, cmd)| {
+                move |buf: &'a [u8]| {
+                    if buf.is_empty() {
+                        return Err(nom::Err::Error(DissectError::Malformed));
+                    }
+                    let (buf, data) = take(buf.len() - 1)(buf)?;
+                    let (buf, checksum) = u8::decode(buf)?;
+                    let ipmb = Self {
+                        base: BasePdu::default(),
+                        dst_addr,
+                        netfn_lun,
+                        hdr_checksum,
+                        src_addr,
+                        seq_lun,
+                        cmd,
+                        data: Vec::from(data),
+                        checksum,
+                    };
+                    if ipmb.calc_checksum() != checksum {
+                        return Err(nom::Err::Error(DissectError::Malformed));
+                    }
+                    Ok((buf, ipmb))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for Ipmb {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        7 + self.data.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.dst_addr)?
+            .encode(&self.netfn_lun)?
+            .encode(&self.hdr_checksum)?
+            .encode(&self.src_addr)?
+            .encode(&self.seq_lun)?
+            .encode(&self.cmd)?
+            .encode(&self.data[..])?
+            .encode(&self.checksum)?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let netfn = self.netfn();
+        let cmd_name = self.cmd_name().unwrap_or("Unknown Command");
+        let mut node = dumper.add_node(
+            "IPMB",
+            Some(
+                &format!(
+                    "{} {}",
+                    cmd_name,
+                    if netfn.is_response() {
+                        "Response"
+                    } else {
+                        "Request"
+                    }
+                )[..],
+            ),
+        )?;
+        node.byte_range(0, 1).add_field(
+            "Destination Address",
+            DumpValue::UInt(self.dst_addr.into()),
+            Some(&format!("0x{:02x}", self.dst_addr)[..]),
+        )?;
+        node.byte_range(1, 1).add_field(
+            "Network Function",
+            DumpValue::UInt(netfn.0.into()),
+            Some(&netfn.to_string()[..]),
+        )?;
+        node.byte_range(1, 1).add_field(
+            "Destination LUN",
+            DumpValue::UInt(self.dst_lun().into()),
+            None,
+        )?;
+        node.byte_range(2, 1).add_field(
+            "Header Checksum",
+            DumpValue::UInt(self.hdr_checksum.into()),
+            Some(&format!("0x{:02x}", self.hdr_checksum)[..]),
+        )?;
+        node.byte_range(3, 1).add_field(
+            "Source Address",
+            DumpValue::UInt(self.src_addr.into()),
+            Some(&format!("0x{:02x}", self.src_addr)[..]),
+        )?;
+        node.byte_range(4, 1)
+            .add_field("Sequence", DumpValue::UInt(self.seq().into()), None)?;
+        node.byte_range(4, 1).add_field(
+            "Source LUN",
+            DumpValue::UInt(self.src_lun().into()),
+            None,
+        )?;
+        node.byte_range(5, 1).add_field(
+            "Command",
+            DumpValue::UInt(self.cmd.into()),
+            Some(cmd_name),
+        )?;
+        let mut data = &self.data[..];
+        if let Some(code) = self.completion_code() {
+            node.byte_range(6, 1).add_field(
+                "Completion Code",
+                DumpValue::UInt(code.into()),
+                completion_code_name(code),
+            )?;
+            data = &data[1..];
+        }
+        if !data.is_empty() {
+            node.byte_range(6 + self.data.len() - data.len(), data.len())
+                .add_field("Data", DumpValue::Bytes(data), None)?;
+        }
+        node.byte_range(6 + self.data.len(), 1).add_field(
+            "Checksum",
+            DumpValue::UInt(self.checksum.into()),
+            Some(&format!("0x{:02x}", self.checksum)[..]),
+        )
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_hdr_checksum();
+        self.update_checksum();
+    }
+}
+
+impl Default for Ipmb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(ipmb, I2cDissectorTable, (), Priority(0), Ipmb::dissect);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::i2c::I2cLinux;
+
+    #[test]
+    fn ipmb_get_device_id() {
+        let data = [
+            0x00, 0x00, 0x00, 0x00, 0x00, // I2C pseudo-header
+            0x20, 0x18, 0xc8, 0x81, 0x04, 0x01, 0x7a, // IPMB request
+        ];
+        let session = Session::new();
+        let (rem, i2c) = I2cLinux::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        let ipmb = i2c.find::<Ipmb>().unwrap();
+        assert_eq!(ipmb.netfn(), NetFn::APP);
+        assert_eq!(ipmb.seq(), 1);
+        assert_eq!(ipmb.cmd_name(), Some("Get Device ID"));
+        assert_eq!(ipmb.completion_code(), None);
+
+        let mut rsp = Ipmb::new();
+        *rsp.dst_addr_mut() = 0x81;
+        rsp.set_netfn(NetFn::APP.response());
+        *rsp.src_addr_mut() = 0x20;
+        rsp.set_seq(1);
+        *rsp.cmd_mut() = 0x01;
+        rsp.data_mut().extend_from_slice(&[0xc1]);
+        rsp.make_canonical();
+        let mut out = Vec::new();
+        rsp.serialize(&mut out).unwrap();
+        let (_, rsp) = Ipmb::dissect(&out[..], &session, None).unwrap();
+        assert!(rsp.is_response());
+        assert_eq!(rsp.completion_code(), Some(0xc1));
+        assert_eq!(completion_code_name(0xc1), Some("Invalid Command"));
+    }
+}
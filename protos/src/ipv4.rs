@@ -1,10 +1,10 @@
 use super::ip_proto::IpProto;
 use crate::prelude::*;
-use checksum::U16OnesComplement;
+use checksum::{Ipv4PseudoHeader, U16OnesComplement};
 use chrono::{offset::Utc, DateTime};
 use nom::{
-    combinator::{all_consuming, consumed, flat_map, map, rest},
-    multi::{fold_many0, length_value, many0},
+    combinator::{consumed, flat_map, map, rest},
+    multi::fold_many0,
     sequence::tuple,
     Parser,
 };
@@ -78,7 +78,7 @@ pub enum OptionClass {
 pub struct RawOption {
     pub opt_type: OptionType,
     pub len: Option<u8>,
-    pub data: Vec<u8>,
+    pub data: Bytes,
 }
 
 #[derive(Clone, Debug)]
@@ -128,13 +128,13 @@ pub enum Classification {
 #[derive(Clone, Debug)]
 pub struct BasicSecurity {
     pub classification: Classification,
-    pub authority: Vec<u8>,
+    pub authority: Bytes,
 }
 
 #[derive(Clone, Debug)]
 pub struct ExtendedSecurity {
     pub format: u8,
-    pub sec_info: Vec<u8>,
+    pub sec_info: Bytes,
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -168,24 +168,24 @@ pub enum Opt {
     Lsrr(RouteRecord),
     Ts(Timestamp),
     ESec(ExtendedSecurity),
-    Cipso(Vec<u8>),
+    Cipso(Bytes),
     Rr(RouteRecord),
     Sid(StreamId),
     Ssrr(RouteRecord),
-    Zsu(Vec<u8>),
+    Zsu(Bytes),
     Mtup(Mtu),
     Mtur(Mtu),
-    Finn(Vec<u8>),
-    Visa(Vec<u8>),
-    Encode(Vec<u8>),
-    Imitd(Vec<u8>),
-    Eip(Vec<u8>),
+    Finn(Bytes),
+    Visa(Bytes),
+    Encode(Bytes),
+    Imitd(Bytes),
+    Eip(Bytes),
     Tr(Traceroute),
-    AddExt(Vec<u8>),
+    AddExt(Bytes),
     RtrAlt(RouterAlert),
-    Sdb(Vec<u8>),
-    Dps(Vec<u8>),
-    Ump(Vec<u8>),
+    Sdb(Bytes),
+    Dps(Bytes),
+    Ump(Bytes),
     Qs(QuickStart),
     Raw(RawOption),
 }
@@ -482,301 +482,188 @@ fn serialize_quick_start<'a, E: Encoder<'a> + ?Sized>(
     Ok(())
 }
 
-fn dissect_body<F: for<'a> FnMut(&'a [u8]) -> DResult<'a, Opt>>(
-    buf: &[u8],
+/// Dissects the length and body of an option with `f`, which must decode
+/// the whole body. The option is kept as a `RawOption` if it doesn't.
+fn dissect_body<'b, F>(
+    buf: &'b [u8],
+    session: &Session,
     opt_type: OptionType,
-    f: F,
-) -> DResult<'_, Opt> {
-    length_value(u8::decode, all_consuming(f))
-        .or(move |buf| dissect_raw(buf, opt_type))
-        .parse(buf)
-}
-
-fn dissect_sec(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Sec, |buf| {
-        u8::decode
-            .map(Classification::from)
-            .and(rest.map(Vec::from))
-            .map(|(classification, authority)| {
-                Opt::Sec(BasicSecurity {
-                    classification,
-                    authority,
-                })
-            })
-            .parse(buf)
-    })
-}
-
-fn dissect_lsrr(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Lsrr, |buf| {
-        u8::decode
-            .and(many0(Ipv4Address::decode))
-            .map(|(pointer, routes)| Opt::Lsrr(RouteRecord { pointer, routes }))
-            .parse(buf)
-    })
-}
-
-fn dissect_ts(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Ts, |buf| {
-        flat_map(tuple((u8::decode, u8::decode)), |(pointer, of)| {
-            let (overflow, flag): (uint::U4, uint::U4) = uint::unpack!(of);
-            let flag = TimestampFlag::from(flag);
-            all_consuming(move |buf| match flag {
-                TimestampFlag::TsOnly | TimestampFlag::Unknown(_) => fold_many0(
-                    u32::decode_be.map(|ts| {
-                        SystemTime::UNIX_EPOCH
-                            .checked_add(Duration::from_millis(ts as u64))
-                            .unwrap()
-                    }),
-                    Vec::new,
-                    |mut acc: Vec<_>, ts| {
-                        acc.push(TimestampEntry::Ts(ts));
-                        acc
-                    },
-                )(buf),
-                TimestampFlag::AddrAndTs | TimestampFlag::PrespecifiedAddrs => {
-                    fold_many0(
-                        (Ipv4Address::decode).and(u32::decode_be.map(|ts| {
-                            SystemTime::UNIX_EPOCH
-                                .checked_add(Duration::from_millis(ts as u64))
-                                .unwrap()
-                        })),
-                        Vec::new,
-                        |mut acc: Vec<_>, (addr, ts)| {
-                            acc.push(TimestampEntry::Addr(addr));
-                            acc.push(TimestampEntry::Ts(ts));
-                            acc
-                        },
-                    )(buf)
-                }
-            })
-            .map(move |entries| {
-                Opt::Ts(Timestamp {
-                    pointer,
-                    overflow,
-                    flag,
-                    entries,
-                })
-            })
-        })(buf)
-    })
-}
-
-fn dissect_esec(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::ESec, |buf| {
-        map(
-            tuple((u8::decode, map(rest, Vec::from))),
-            |(format, sec_info)| Opt::ESec(ExtendedSecurity { format, sec_info }),
-        )(buf)
-    })
-}
-
-fn dissect_cipso(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Cipso, |buf| {
-        map(rest, |data| Opt::Cipso(Vec::from(data)))(buf)
-    })
-}
-
-fn dissect_rr(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Rr, |buf| {
-        u8::decode
-            .and(many0(Ipv4Address::decode))
-            .map(|(pointer, routes)| Opt::Rr(RouteRecord { pointer, routes }))
-            .parse(buf)
-    })
-}
-
-fn dissect_sid(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Sid, |buf| {
-        map(u16::decode_be, |sid| Opt::Sid(StreamId(sid)))(buf)
-    })
-}
-
-fn dissect_ssrr(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Ssrr, |buf| {
-        u8::decode
-            .and(many0(Ipv4Address::decode))
-            .map(|(pointer, routes)| Opt::Ssrr(RouteRecord { pointer, routes }))
-            .parse(buf)
-    })
-}
-
-fn dissect_zsu(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Zsu, |buf| {
-        map(rest, |data| Opt::Zsu(Vec::from(data)))(buf)
-    })
-}
-
-fn dissect_mtup(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Mtup, |buf| {
-        map(u16::decode_be, |sid| Opt::Mtup(Mtu(sid)))(buf)
-    })
-}
-
-fn dissect_mtur(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Mtur, |buf| {
-        map(u16::decode_be, |sid| Opt::Mtur(Mtu(sid)))(buf)
-    })
-}
-
-fn dissect_finn(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Finn, |buf| {
-        map(rest, |data| Opt::Finn(Vec::from(data)))(buf)
-    })
-}
-
-fn dissect_visa(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Visa, |buf| {
-        map(rest, |data| Opt::Visa(Vec::from(data)))(buf)
-    })
-}
-
-fn dissect_encode(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Encode, |buf| {
-        map(rest, |data| Opt::Encode(Vec::from(data)))(buf)
-    })
-}
-
-fn dissect_imitd(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Imitd, |buf| {
-        map(rest, |data| Opt::Imitd(Vec::from(data)))(buf)
-    })
-}
-
-fn dissect_eip(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Eip, |buf| {
-        map(rest, |data| Opt::Eip(Vec::from(data)))(buf)
-    })
-}
-
-fn dissect_tr(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Tr, |buf| {
-        map(
-            tuple((
-                u16::decode_be,
-                u16::decode_be,
-                u16::decode_be,
-                Ipv4Address::decode,
-            )),
-            |(id, out_hops, return_hops, orig_addr)| {
-                Opt::Tr(Traceroute {
-                    id,
-                    out_hops,
-                    return_hops,
-                    orig_addr,
-                })
-            },
-        )(buf)
-    })
-}
-
-fn dissect_addext(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::AddExt, |buf| {
-        map(rest, |data| Opt::AddExt(Vec::from(data)))(buf)
-    })
+    mut f: F,
+) -> DResult<'b, Opt>
+where
+    F: for<'a> FnMut(&mut DecodeCursor<'a>) -> DecodeResult<'a, Opt>,
+{
+    let mut cur = DecodeCursor::new(buf);
+    let len: u8 = cur.decode()?;
+    let mut body = cur.sub(len.into())?;
+    match f(&mut body).and_then(|opt| body.finish().map(|_| opt)) {
+        Ok(opt) => Ok((cur.remaining(), opt)),
+        Err(nom::Err::Error(_)) => dissect_raw(buf, session, opt_type),
+        Err(e) => Err(e),
+    }
 }
 
-fn dissect_rtralt(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::RtrAlt, |buf| {
-        map(u16::decode_be, |ra| Opt::RtrAlt(RouterAlert(ra)))(buf)
-    })
+fn dissect_route_record<'a>(body: &mut DecodeCursor<'a>) -> DecodeResult<'a, RouteRecord> {
+    let pointer = body.decode()?;
+    let mut routes = Vec::new();
+    while !body.is_empty() {
+        routes.push(body.decode::<Ipv4Address>()?);
+    }
+    Ok(RouteRecord { pointer, routes })
 }
 
-fn dissect_sdb(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Sdb, |buf| {
-        map(rest, |data| Opt::Sdb(Vec::from(data)))(buf)
+fn dissect_timestamp<'a>(body: &mut DecodeCursor<'a>) -> DecodeResult<'a, Timestamp> {
+    fn decode_ts<'a>(body: &mut DecodeCursor<'a>) -> DecodeResult<'a, SystemTime> {
+        let ts: u32 = body.decode_be()?;
+        Ok(SystemTime::UNIX_EPOCH
+            .checked_add(Duration::from_millis(ts as u64))
+            .unwrap())
+    }
+
+    let pointer = body.decode()?;
+    let (overflow, flag): (uint::U4, uint::U4) = uint::unpack!(body.decode::<u8>()?);
+    let flag = TimestampFlag::from(flag);
+    let mut entries = Vec::new();
+    while !body.is_empty() {
+        match flag {
+            TimestampFlag::TsOnly | TimestampFlag::Unknown(_) => {
+                entries.push(TimestampEntry::Ts(decode_ts(body)?));
+            }
+            TimestampFlag::AddrAndTs | TimestampFlag::PrespecifiedAddrs => {
+                let addr = body.decode::<Ipv4Address>()?;
+                let ts = decode_ts(body)?;
+                entries.push(TimestampEntry::Addr(addr));
+                entries.push(TimestampEntry::Ts(ts));
+            }
+        }
+    }
+    Ok(Timestamp {
+        pointer,
+        overflow,
+        flag,
+        entries,
     })
 }
 
-fn dissect_dps(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Dps, |buf| {
-        map(rest, |data| Opt::Dps(Vec::from(data)))(buf)
-    })
+fn dissect_raw<'a>(buf: &'a [u8], session: &Session, opt_type: OptionType) -> DResult<'a, Opt> {
+    decode_with(move |cur| {
+        let len = if cur.is_empty() {
+            None
+        } else {
+            Some(cur.decode()?)
+        };
+        Ok(Opt::Raw(RawOption {
+            opt_type,
+            len,
+            data: session.packet_bytes(cur.rest()),
+        }))
+    })(buf)
 }
 
-fn dissect_ump(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Ump, |buf| {
-        map(rest, |data| Opt::Ump(Vec::from(data)))(buf)
-    })
-}
+impl Opt {
+    /// Dissects an option. Opaque option data references the packet buffer,
+    /// as with `Session::packet_bytes`.
+    pub fn dissect<'a>(buf: &'a [u8], session: &Session) -> DResult<'a, Self> {
+        use OptionType::*;
 
-fn dissect_qs(buf: &[u8]) -> DResult<'_, Opt> {
-    dissect_body(buf, OptionType::Qs, |buf| {
-        map(
-            tuple((u8::decode, u8::decode, u32::decode_be)),
-            |(frr, ttl, nr)| {
-                let (func, rate_req): (uint::U4, uint::U4) = uint::unpack!(frr);
-                let (nonce, reserved): (uint::U30, uint::U2) = uint::unpack!(nr);
-                Opt::Qs(QuickStart {
+        let mut cur = DecodeCursor::new(buf);
+        let opt_type = OptionType::from(cur.decode::<u8>()?);
+        let buf = cur.remaining();
+        match opt_type {
+            Eool => Ok((buf, Opt::Eool)),
+            Nop => Ok((buf, Opt::Nop)),
+            Sec => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Sec(BasicSecurity {
+                    classification: Classification::from(body.decode::<u8>()?),
+                    authority: session.packet_bytes(body.rest()),
+                }))
+            }),
+            Lsrr => dissect_body(buf, session, opt_type, |body| {
+                dissect_route_record(body).map(Opt::Lsrr)
+            }),
+            Ts => dissect_body(buf, session, opt_type, |body| {
+                dissect_timestamp(body).map(Opt::Ts)
+            }),
+            ESec => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::ESec(ExtendedSecurity {
+                    format: body.decode()?,
+                    sec_info: session.packet_bytes(body.rest()),
+                }))
+            }),
+            Cipso => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Cipso(session.packet_bytes(body.rest())))
+            }),
+            Rr => dissect_body(buf, session, opt_type, |body| {
+                dissect_route_record(body).map(Opt::Rr)
+            }),
+            Sid => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Sid(StreamId(body.decode_be()?)))
+            }),
+            Ssrr => dissect_body(buf, session, opt_type, |body| {
+                dissect_route_record(body).map(Opt::Ssrr)
+            }),
+            Zsu => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Zsu(session.packet_bytes(body.rest())))
+            }),
+            Mtup => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Mtup(Mtu(body.decode_be()?)))
+            }),
+            Mtur => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Mtur(Mtu(body.decode_be()?)))
+            }),
+            Finn => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Finn(session.packet_bytes(body.rest())))
+            }),
+            Visa => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Visa(session.packet_bytes(body.rest())))
+            }),
+            Encode => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Encode(session.packet_bytes(body.rest())))
+            }),
+            Imitd => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Imitd(session.packet_bytes(body.rest())))
+            }),
+            Eip => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Eip(session.packet_bytes(body.rest())))
+            }),
+            Tr => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Tr(Traceroute {
+                    id: body.decode_be()?,
+                    out_hops: body.decode_be()?,
+                    return_hops: body.decode_be()?,
+                    orig_addr: body.decode()?,
+                }))
+            }),
+            AddExt => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::AddExt(session.packet_bytes(body.rest())))
+            }),
+            RtrAlt => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::RtrAlt(RouterAlert(body.decode_be()?)))
+            }),
+            Sdb => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Sdb(session.packet_bytes(body.rest())))
+            }),
+            Dps => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Dps(session.packet_bytes(body.rest())))
+            }),
+            Ump => dissect_body(buf, session, opt_type, |body| {
+                Ok(Opt::Ump(session.packet_bytes(body.rest())))
+            }),
+            Qs => dissect_body(buf, session, opt_type, |body| {
+                let (func, rate_req): (uint::U4, uint::U4) = uint::unpack!(body.decode::<u8>()?);
+                let ttl = body.decode()?;
+                let (nonce, reserved): (uint::U30, uint::U2) =
+                    uint::unpack!(body.decode_be::<u32>()?);
+                Ok(Opt::Qs(QuickStart {
                     func,
                     rate_req,
                     ttl,
                     nonce,
                     reserved,
-                })
-            },
-        )(buf)
-    })
-}
-
-fn dissect_raw(buf: &[u8], opt_type: OptionType) -> DResult<'_, Opt> {
-    if buf.is_empty() {
-        Ok((
-            buf,
-            Opt::Raw(RawOption {
-                opt_type,
-                len: None,
-                data: Vec::new(),
+                }))
             }),
-        ))
-    } else {
-        u8::decode
-            .and(rest)
-            .map(move |(len, data)| {
-                Opt::Raw(RawOption {
-                    opt_type,
-                    len: Some(len),
-                    data: Vec::from(data),
-                })
-            })
-            .parse(buf)
-    }
-}
-
-impl Opt {
-    pub fn dissect(buf: &[u8]) -> DResult<'_, Self> {
-        flat_map(map(u8::decode, OptionType::from), |opt_type| {
-            move |buf| match opt_type {
-                OptionType::Eool => Ok((buf, Opt::Eool)),
-                OptionType::Nop => Ok((buf, Opt::Nop)),
-                OptionType::Sec => dissect_sec(buf),
-                OptionType::Lsrr => dissect_lsrr(buf),
-                OptionType::Ts => dissect_ts(buf),
-                OptionType::ESec => dissect_esec(buf),
-                OptionType::Cipso => dissect_cipso(buf),
-                OptionType::Rr => dissect_rr(buf),
-                OptionType::Sid => dissect_sid(buf),
-                OptionType::Ssrr => dissect_ssrr(buf),
-                OptionType::Zsu => dissect_zsu(buf),
-                OptionType::Mtup => dissect_mtup(buf),
-                OptionType::Mtur => dissect_mtur(buf),
-                OptionType::Finn => dissect_finn(buf),
-                OptionType::Visa => dissect_visa(buf),
-                OptionType::Encode => dissect_encode(buf),
-                OptionType::Imitd => dissect_imitd(buf),
-                OptionType::Eip => dissect_eip(buf),
-                OptionType::Tr => dissect_tr(buf),
-                OptionType::AddExt => dissect_addext(buf),
-                OptionType::RtrAlt => dissect_rtralt(buf),
-                OptionType::Sdb => dissect_sdb(buf),
-                OptionType::Dps => dissect_dps(buf),
-                OptionType::Ump => dissect_ump(buf),
-                OptionType::Qs => dissect_qs(buf),
-                OptionType::Unspecified(opt_type) => {
-                    dissect_raw(buf, OptionType::Unspecified(opt_type))
-                }
-            }
-        })(buf)
+            Unspecified(_) => dissect_raw(buf, session, opt_type),
+        }
     }
 
     fn serialize_data<'a, E: Encoder<'a> + ?Sized>(&self, encoder: &mut E) -> std::io::Result<()> {
@@ -866,13 +753,13 @@ impl Opt {
             RawOption {
                 opt_type: self.option_type(),
                 len: Some(len),
-                data,
+                data: data.into(),
             }
         } else {
             RawOption {
                 opt_type: self.option_type(),
                 len: None,
-                data: Vec::new(),
+                data: Bytes::new(),
             }
         }
     }
@@ -986,6 +873,7 @@ impl Opt {
 }
 
 dissector_table!(pub IpProtoDissectorTable, IpProto);
+register_dissector_table!(IpProtoDissectorTable);
 dissector_table!(pub HeurDissectorTable);
 
 const PADDING: [u8; 3] = [0u8; 3];
@@ -1038,6 +926,7 @@ impl Ipv4 {
     }
 
     pub fn version_mut(&mut self) -> &mut uint::U4 {
+        self.base.mark_dirty();
         &mut self.version
     }
 
@@ -1046,10 +935,12 @@ impl Ipv4 {
     }
 
     pub fn ihl_mut(&mut self) -> &mut uint::U4 {
+        self.base.mark_dirty();
         &mut self.ihl
     }
 
     pub fn update_ihl(&mut self) {
+        self.base.mark_dirty();
         self.ihl = match (self.header_len() as u64 / 4).try_into() {
             Ok(val) => val,
             _ => 0xFu8.into_masked(),
@@ -1061,6 +952,7 @@ impl Ipv4 {
     }
 
     pub fn dscp_mut(&mut self) -> &mut uint::U6 {
+        self.base.mark_dirty();
         &mut self.dscp
     }
 
@@ -1069,6 +961,7 @@ impl Ipv4 {
     }
 
     pub fn ecn_mut(&mut self) -> &mut uint::U2 {
+        self.base.mark_dirty();
         &mut self.ecn
     }
 
@@ -1077,15 +970,16 @@ impl Ipv4 {
     }
 
     pub fn totlen_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
         &mut self.totlen
     }
 
     pub fn update_totlen(&mut self) {
+        self.base.mark_dirty();
         let inner_len = self.inner_pdu().map(|pdu| pdu.total_len()).unwrap_or(0);
-        self.totlen = match (self.header_len() + inner_len).try_into() {
-            Ok(val) => val,
-            _ => 0xFFFFu16,
-        };
+        self.totlen = (self.header_len() + inner_len)
+            .try_into()
+            .unwrap_or(0xFFFFu16);
     }
 
     pub fn identifier(&self) -> u16 {
@@ -1093,6 +987,7 @@ impl Ipv4 {
     }
 
     pub fn identifier_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
         &mut self.ident
     }
 
@@ -1101,6 +996,7 @@ impl Ipv4 {
     }
 
     pub fn flags_mut(&mut self) -> &mut uint::U3 {
+        self.base.mark_dirty();
         &mut self.flags
     }
 
@@ -1109,6 +1005,7 @@ impl Ipv4 {
     }
 
     pub fn fragment_offset_mut(&mut self) -> &mut uint::U13 {
+        self.base.mark_dirty();
         &mut self.frag_offset
     }
 
@@ -1117,6 +1014,7 @@ impl Ipv4 {
     }
 
     pub fn ttl_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
         &mut self.ttl
     }
 
@@ -1125,10 +1023,12 @@ impl Ipv4 {
     }
 
     pub fn proto_mut(&mut self) -> &mut IpProto {
+        self.base.mark_dirty();
         &mut self.proto
     }
 
     pub fn update_proto(&mut self) {
+        self.base.mark_dirty();
         let proto = self
             .inner_pdu()
             .map(|inner| IpProto::from_pdu(inner).unwrap_or(self.proto))
@@ -1141,21 +1041,30 @@ impl Ipv4 {
     }
 
     pub fn checksum_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
         &mut self.chksum
     }
 
     pub fn update_checksum(&mut self) {
+        self.base.mark_dirty();
         let mut acc = U16OnesComplement::new();
         self.chksum = 0;
         let _ = self.serialize_header(&mut acc);
         self.chksum = acc.checksum();
     }
 
+    pub fn checksum_valid(&self) -> bool {
+        let mut acc = U16OnesComplement::new();
+        let _ = self.serialize_header(&mut acc);
+        acc.checksum() == 0
+    }
+
     pub fn src_address(&self) -> Ipv4Address {
         self.src_addr
     }
 
     pub fn src_address_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
         &mut self.src_addr
     }
 
@@ -1164,14 +1073,25 @@ impl Ipv4 {
     }
 
     pub fn dst_address_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
         &mut self.dst_addr
     }
 
+    /// Returns the pseudo-header used by upper layer protocols to compute
+    /// checksums.
+    pub fn pseudo_header(&self) -> Ipv4PseudoHeader {
+        Ipv4PseudoHeader {
+            src: self.src_addr,
+            dst: self.dst_addr,
+        }
+    }
+
     pub fn options(&self) -> &[Opt] {
         &self.opts[..]
     }
 
     pub fn options_mut(&mut self) -> &mut Vec<Opt> {
+        self.base.mark_dirty();
         &mut self.opts
     }
 
@@ -1201,6 +1121,7 @@ impl Ipv4 {
     }
 
     pub fn padding_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
         let padding = match &mut self.padding {
             Padding::Auto => vec![0u8; self.auto_padding_len()],
             Padding::Manual(padding) => std::mem::take(padding),
@@ -1213,6 +1134,7 @@ impl Ipv4 {
     }
 
     pub fn update_padding(&mut self) {
+        self.base.mark_dirty();
         self.padding = Padding::Auto;
     }
 }
@@ -1267,7 +1189,7 @@ impl Dissect for Ipv4 {
                                                     DissectError::Malformed,
                                                 ));
                                             }
-                                            let (tmp_buf, opt) = Opt::dissect(tmp_buf)?;
+                                            let (tmp_buf, opt) = Opt::dissect(tmp_buf, session)?;
                                             done = opt.option_type() == OptionType::Eool;
                                             Ok((tmp_buf, opt))
                                         },
@@ -1278,7 +1200,7 @@ impl Dissect for Ipv4 {
                                         },
                                     ),
                                     map(rest, move |padding: &'a [u8]| {
-                                        if padding.len() < 4 && len % 4 == 0 {
+                                        if padding.len() < 4 && len.is_multiple_of(4) {
                                             Padding::Auto
                                         } else {
                                             Padding::Manual(Vec::from(padding))
@@ -1320,7 +1242,7 @@ impl Dissect for Ipv4 {
                 let (payload, rem) = if buf.len() + hdr_data.len() <= ipv4.totlen as usize {
                     (buf, &buf[buf.len()..])
                 } else {
-                    let payload_len = ipv4.totlen as usize - hdr_data.len();
+                    let payload_len = (ipv4.totlen as usize).saturating_sub(hdr_data.len());
                     (&buf[..payload_len], &buf[payload_len..])
                 };
                 if !payload.is_empty() {
@@ -1333,14 +1255,21 @@ impl Dissect for Ipv4 {
                             &(),
                             Some(TempPdu::new(&ipv4, &parent)),
                         ))
-                        .or(map(RawPdu::decode, AnyPdu::new))
+                        .or(map(RawPdu::dissector(session, None), AnyPdu::new))
                         .parse(payload)?;
                     if !rem.is_empty() {
                         get_inner_most(&mut inner)
-                            .set_inner_pdu(AnyPdu::new(RawPdu::new(Vec::from(rem))));
+                            .set_inner_pdu(AnyPdu::new(RawPdu::new(session.packet_bytes(rem))));
                     }
                     ipv4.set_inner_pdu(inner);
                 }
+                let missing = (ipv4.totlen as usize).saturating_sub(hdr_data.len() + payload.len());
+                if missing > 0 && session.truncated_len() > 0 {
+                    // Bytes already marked by the inner PDUs are not counted twice
+                    let marked = ipv4.wire_len() - ipv4.total_len();
+                    ipv4.base_pdu_mut()
+                        .set_truncated_len(missing.saturating_sub(marked));
+                }
                 Ok((rem, ipv4))
             },
         )(buf)?
@@ -1348,7 +1277,7 @@ impl Dissect for Ipv4 {
     }
 }
 
-fn get_inner_most(pdu: &mut AnyPdu) -> &mut AnyPdu {
+pub(crate) fn get_inner_most(pdu: &mut AnyPdu) -> &mut AnyPdu {
     let has_inner = pdu.inner_pdu().is_some();
     if !has_inner {
         pdu
@@ -1401,18 +1330,30 @@ impl Pdu for Ipv4 {
             "Ipv4",
             Some(&format!("{}->{}", self.src_addr, self.dst_addr)[..]),
         )?;
-        node.add_field("Version", DumpValue::UInt(self.version.into()), None)?;
-        node.add_field("IHL", DumpValue::UInt(self.ihl.into()), None)?;
-        node.add_field("DSCP", DumpValue::UInt(self.dscp.into()), None)?;
-        node.add_field("ECN", DumpValue::UInt(self.ecn.into()), None)?;
-        node.add_field("Total Length", DumpValue::UInt(self.totlen.into()), None)?;
-        node.add_field("Identification", DumpValue::UInt(self.ident.into()), None)?;
+        node.byte_range(0, 1)
+            .add_field("Version", DumpValue::UInt(self.version.into()), None)?;
+        node.byte_range(0, 1)
+            .add_field("IHL", DumpValue::UInt(self.ihl.into()), None)?;
+        node.byte_range(1, 1)
+            .add_field("DSCP", DumpValue::UInt(self.dscp.into()), None)?;
+        node.byte_range(1, 1)
+            .add_field("ECN", DumpValue::UInt(self.ecn.into()), None)?;
+        node.byte_range(2, 2).add_field(
+            "Total Length",
+            DumpValue::UInt(self.totlen.into()),
+            None,
+        )?;
+        node.byte_range(4, 2).add_field(
+            "Identification",
+            DumpValue::UInt(self.ident.into()),
+            None,
+        )?;
         {
             let flags: u8 = self.flags.into();
             let reserved = (flags & 0b100) > 0;
             let dont_frag = (flags & 0b010) > 0;
             let more_frags = (flags & 0b001) > 0;
-            let mut node = node.add_node(
+            let mut node = node.byte_range(6, 1).add_node(
                 "Flags",
                 Some(
                     &format!(
@@ -1426,26 +1367,39 @@ impl Pdu for Ipv4 {
             node.add_field("Don't Fragment", DumpValue::Bool(dont_frag), None)?;
             node.add_field("More Fragments", DumpValue::Bool(more_frags), None)?;
         }
-        node.add_field(
+        node.byte_range(6, 2).add_field(
             "Fragment Offset",
             DumpValue::UInt(self.frag_offset.into()),
             None,
         )?;
-        node.add_field("Time to Live", DumpValue::UInt(self.ttl.into()), None)?;
-        node.add_field("Protocol", DumpValue::UInt(self.proto.0.into()), None)?;
-        node.add_field("Checksum", DumpValue::UInt(self.chksum.into()), None)?;
-        node.add_field(
+        node.byte_range(8, 1)
+            .add_field("Time to Live", DumpValue::UInt(self.ttl.into()), None)?;
+        node.byte_range(9, 1)
+            .add_field("Protocol", DumpValue::UInt(self.proto.0.into()), None)?;
+        node.byte_range(10, 2)
+            .add_field("Checksum", DumpValue::UInt(self.chksum.into()), None)?;
+        let src_addr = std::net::Ipv4Addr::from(self.src_addr).into();
+        let src = node.describe_addr(src_addr);
+        node.byte_range(12, 4).add_field(
             "Source Address",
             DumpValue::Bytes(&self.src_addr[..]),
-            Some(&format!("{}", self.src_addr)),
+            Some(&src[..]),
         )?;
-        node.add_field(
+        node.byte_range(12, 4)
+            .add_annotation("Source Annotation", src_addr)?;
+        let dst_addr = std::net::Ipv4Addr::from(self.dst_addr).into();
+        let dst = node.describe_addr(dst_addr);
+        node.byte_range(16, 4).add_field(
             "Destination Address",
             DumpValue::Bytes(&self.dst_addr[..]),
-            Some(&format!("{}", self.dst_addr)),
+            Some(&dst[..]),
         )?;
+        node.byte_range(16, 4)
+            .add_annotation("Destination Annotation", dst_addr)?;
         if !self.opts.is_empty() {
-            let mut node = node.add_node("Options", None)?;
+            let mut node = node
+                .byte_range(20, self.header_len() - 20)
+                .add_node("Options", None)?;
             for opt in self.opts.iter() {
                 match opt {
                     Opt::Eool => node.add_info("End of Options List", "")?,
@@ -1708,13 +1662,14 @@ impl Pdu for Ipv4 {
             Ok(val) => val,
             _ => 0xFu8.into_masked(),
         };
-        self.totlen = match (header_len + inner_len).try_into() {
-            Ok(val) => val,
-            _ => 0xFFFFu16,
-        };
+        self.totlen = (header_len + inner_len).try_into().unwrap_or(0xFFFFu16);
         self.update_proto();
         self.update_checksum();
     }
+
+    fn validate(&self, validator: &mut Validator<'_>) {
+        validator.checksum("IPv4", self.chksum, self.checksum_valid());
+    }
 }
 
 impl Default for Ipv4 {
@@ -1733,3 +1688,101 @@ register_dissector!(
     Ipv4::dissect
 );
 crate::register_ethertype_pdu!(Ipv4, Ethertype::IPV4);
+crate::register_pseudo_header_pdu!(Ipv4, Ipv4::pseudo_header);
+register_field!(ipv4_src_addr, "ipv4.src_addr" => "Ipv4.Source Address");
+register_field!(ipv4_dst_addr, "ipv4.dst_addr" => "Ipv4.Destination Address");
+register_field!(ipv4_addr, "ipv4.addr" => "Ipv4.Source Address", "Ipv4.Destination Address");
+register_field!(ipv4_ttl, "ipv4.ttl" => "Ipv4.Time to Live");
+register_field!(ipv4_proto, "ipv4.proto" => "Ipv4.Protocol");
+register_field!(ipv4_src_country, "ipv4.src_country" => "Ipv4.Source Annotation.Country");
+register_field!(ipv4_dst_country, "ipv4.dst_country" => "Ipv4.Destination Annotation.Country");
+register_field!(
+    ipv4_country,
+    "ipv4.country" => "Ipv4.Source Annotation.Country", "Ipv4.Destination Annotation.Country"
+);
+register_field!(ipv4_src_asn, "ipv4.src_asn" => "Ipv4.Source Annotation.ASN");
+register_field!(ipv4_dst_asn, "ipv4.dst_asn" => "Ipv4.Destination Annotation.ASN");
+register_field!(
+    ipv4_asn,
+    "ipv4.asn" => "Ipv4.Source Annotation.ASN", "Ipv4.Destination Annotation.ASN"
+);
+register_conversation!(ipv4, "IPv4", ["ipv4.src_addr"] => ["ipv4.dst_addr"]);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sniffle_core::{Annotation, Packet, PacketAnnotator};
+
+    struct Private;
+
+    impl PacketAnnotator for Private {
+        fn annotate(&self, addr: std::net::IpAddr) -> Option<Annotation> {
+            match addr {
+                std::net::IpAddr::V4(addr) if addr.is_private() => None,
+                _ => Some(Annotation {
+                    country: Some("NZ".into()),
+                    asn: Some(64512),
+                    as_org: None,
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn total_length_shorter_than_header() {
+        let session = Session::new();
+        let mut buf = vec![
+            0x45, 0, 0, 8, 0, 1, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+        ];
+        buf.extend_from_slice(&[0xaa; 8][..]);
+        let (rem, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        assert_eq!(ipv4.totlen(), 8);
+        assert!(ipv4.inner_pdu().is_none());
+        assert_eq!(rem, &[0xaa; 8][..]);
+    }
+
+    #[test]
+    fn option_dissection() {
+        let session = Session::new_from_scratch();
+        let buf = [7, 5, 4, 10, 0, 0, 1, 136, 3, 0, 1, 2, 1, 0];
+        let (rem, rr) = Opt::dissect(&buf[..], &session).unwrap();
+        match rr {
+            Opt::Rr(rr) => {
+                assert_eq!(rr.pointer, 4);
+                assert_eq!(rr.routes, [Ipv4Address::from([10, 0, 0, 1])]);
+            }
+            opt => panic!("unexpected option {:?}", opt),
+        }
+
+        // The body has a byte left over, so the option is kept raw
+        let (rem, sid) = Opt::dissect(rem, &session).unwrap();
+        match sid {
+            Opt::Raw(raw) => {
+                assert_eq!(raw.opt_type, OptionType::Sid);
+                assert_eq!(raw.len, Some(3));
+                assert_eq!(raw.data, [0, 1, 2, 1, 0][..]);
+            }
+            opt => panic!("unexpected option {:?}", opt),
+        }
+        assert!(rem.is_empty());
+
+        assert!(matches!(
+            Opt::dissect(&[7, 8, 4][..], &session),
+            Err(nom::Err::Incomplete(_))
+        ));
+    }
+
+    #[test]
+    fn query_annotations() {
+        let ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [203, 0, 113, 9].into());
+        let mut packet = Packet::new(SystemTime::UNIX_EPOCH, ipv4, None, None, None);
+        assert!(packet.get("ipv4.country").is_none());
+
+        packet.set_annotator(Some(std::sync::Arc::new(Private)));
+        assert!(packet.get("ipv4.src_country").is_none());
+        assert_eq!(packet.get("ipv4.dst_country").unwrap().to_string(), "NZ");
+        assert!(packet
+            .matches("ipv4.country == NZ && ipv4.asn == 64512")
+            .unwrap());
+    }
+}
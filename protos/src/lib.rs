@@ -6,10 +6,64 @@ pub use ::ctor;
 
 pub mod prelude;
 
+#[cfg(feature = "bgp")]
+pub mod bgp;
+pub mod builder;
+#[cfg(feature = "dhcp")]
+pub mod dhcp;
+#[cfg(feature = "dhcpv6")]
+pub mod dhcpv6;
+#[cfg(feature = "ethernet_ii")]
 pub mod ethernet_ii;
 pub mod ethertype;
+#[cfg(all(feature = "tcp", feature = "udp"))]
+pub mod follow;
+#[cfg(feature = "gre")]
+pub mod gre;
+#[cfg(feature = "gsmtap")]
+pub mod gsmtap;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "i2c")]
+pub mod i2c;
+#[cfg(feature = "icmp")]
+pub mod icmp;
+#[cfg(feature = "ieee80211")]
+pub mod ieee80211;
 pub mod ip_proto;
+#[cfg(feature = "ipmb")]
+pub mod ipmb;
+#[cfg(feature = "ipv4")]
 pub mod ipv4;
+#[cfg(feature = "llc")]
+pub mod llc;
+#[cfg(feature = "loratap")]
+pub mod loratap;
+#[cfg(feature = "mctp")]
+pub mod mctp;
+#[cfg(feature = "ntp")]
+pub mod ntp;
+#[cfg(feature = "ospf")]
+pub mod ospf;
+#[cfg(all(feature = "tcp", feature = "udp"))]
+pub mod payload;
+#[cfg(feature = "radiotap")]
+pub mod radiotap;
+#[cfg(feature = "sll")]
+pub mod sll;
+#[cfg(feature = "sll2")]
+pub mod sll2;
+#[cfg(feature = "snmp")]
+pub mod snmp;
+#[cfg(feature = "stp")]
+pub mod stp;
+#[cfg(feature = "tcp")]
+pub mod tcp;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "udp")]
+pub mod udp;
 
+pub use sniffle_core::ErrorPdu;
 pub use sniffle_core::RawPdu;
 pub use sniffle_core::Virtual;
@@ -0,0 +1,335 @@
+//! IEEE 802.2 Logical Link Control (LLC), and the Subnetwork Access
+//! Protocol (SNAP) extension.
+//!
+//! LLC is the payload of IEEE 802.3 frames, which hold the length of the
+//! payload where Ethernet II frames hold an ethertype. The payload of an
+//! LLC PDU is dissected by its destination SAP with `LlcSapDissectorTable`,
+//! except for SNAP, which identifies the payload by an OUI and protocol ID.
+//! SNAP with a zero OUI carries an ethertype, and its payload is dissected
+//! with the `EthertypeDissectorTable`.
+
+use super::ethernet_ii::{EthertypeDissectorTable, Ieee8023DissectorTable};
+use super::ethertype::Ethertype;
+use crate::prelude::*;
+use nom::combinator::map;
+
+/// SAP used for SNAP
+pub const SNAP_SAP: u8 = 0xaa;
+
+#[derive(Debug, Clone)]
+pub struct Llc {
+    base: BasePdu,
+    dsap: u8,
+    ssap: u8,
+    control: Control,
+    snap: Option<Snap>,
+}
+
+/// The control field, which is one byte for unnumbered (U-format) PDUs and
+/// two bytes for information (I-format) and supervisory (S-format) PDUs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Unnumbered(u8),
+    Numbered(u16),
+}
+
+/// SNAP header following an LLC header with the SNAP SAPs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Snap {
+    pub oui: [u8; 3],
+    pub protocol_id: u16,
+}
+
+dissector_table!(pub LlcSapDissectorTable, u8);
+dissector_table!(pub SnapDissectorTable, Snap);
+
+register_dissector_table!(LlcSapDissectorTable);
+register_dissector_table!(SnapDissectorTable);
+
+/// Unnumbered control field values, without the poll/final bit
+pub mod control {
+    pub const UI: u8 = 0x03;
+    pub const XID: u8 = 0xaf;
+    pub const TEST: u8 = 0xe3;
+}
+
+impl Control {
+    fn len(&self) -> usize {
+        match self {
+            Self::Unnumbered(_) => 1,
+            Self::Numbered(_) => 2,
+        }
+    }
+}
+
+impl Snap {
+    /// The ethertype of the payload, if the OUI is zero
+    pub fn ethertype(&self) -> Option<Ethertype> {
+        if self.oui == [0; 3] {
+            Some(Ethertype(self.protocol_id))
+        } else {
+            None
+        }
+    }
+}
+
+impl Llc {
+    /// Creates an unnumbered information (UI) PDU.
+    pub fn new(dsap: u8, ssap: u8) -> Self {
+        Self {
+            base: BasePdu::default(),
+            dsap,
+            ssap,
+            control: Control::Unnumbered(control::UI),
+            snap: None,
+        }
+    }
+
+    /// Creates an unnumbered information (UI) PDU with a SNAP header.
+    pub fn with_snap(oui: [u8; 3], protocol_id: u16) -> Self {
+        let mut llc = Self::new(SNAP_SAP, SNAP_SAP);
+        llc.snap = Some(Snap { oui, protocol_id });
+        llc
+    }
+
+    pub fn dsap(&self) -> u8 {
+        self.dsap
+    }
+
+    pub fn dsap_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.dsap
+    }
+
+    pub fn ssap(&self) -> u8 {
+        self.ssap
+    }
+
+    pub fn ssap_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.ssap
+    }
+
+    pub fn control(&self) -> Control {
+        self.control
+    }
+
+    pub fn control_mut(&mut self) -> &mut Control {
+        self.base.mark_dirty();
+        &mut self.control
+    }
+
+    pub fn snap(&self) -> Option<&Snap> {
+        self.snap.as_ref()
+    }
+
+    pub fn snap_mut(&mut self) -> &mut Option<Snap> {
+        self.base.mark_dirty();
+        &mut self.snap
+    }
+
+    /// Sets the protocol ID of a SNAP header with a zero OUI to the
+    /// ethertype of the payload.
+    pub fn update_ethertype(&mut self) {
+        self.base.mark_dirty();
+        let ethertype = self.inner_pdu().and_then(Ethertype::from_pdu);
+        if let (Some(snap), Some(ethertype)) = (self.snap.as_mut(), ethertype) {
+            if snap.oui == [0; 3] {
+                snap.protocol_id = ethertype.0;
+            }
+        }
+    }
+}
+
+impl Dissect for Llc {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let mut cur = DecodeCursor::new(buf);
+        let dsap: u8 = cur.decode()?;
+        let ssap: u8 = cur.decode()?;
+        let ctrl: u8 = cur.decode()?;
+        // U-format PDUs have both low bits of the control field set
+        let control = if ctrl & 0x03 == 0x03 {
+            Control::Unnumbered(ctrl)
+        } else {
+            Control::Numbered(u16::from_be_bytes([ctrl, cur.decode()?]))
+        };
+        let snap = if dsap & 0xfe == SNAP_SAP && matches!(control, Control::Unnumbered(_)) {
+            Some(Snap {
+                oui: cur.decode()?,
+                protocol_id: cur.decode_be()?,
+            })
+        } else {
+            None
+        };
+        let mut llc = Self {
+            base: BasePdu::default(),
+            dsap,
+            ssap,
+            control,
+            snap,
+        };
+        let buf = cur.remaining();
+        if buf.is_empty() {
+            return Ok((buf, llc));
+        }
+        let (rem, inner) = match (llc.snap, llc.snap.and_then(|snap| snap.ethertype())) {
+            (_, Some(ethertype)) => session
+                .table_dissector::<EthertypeDissectorTable>(
+                    &ethertype,
+                    Some(TempPdu::new(&llc, &parent)),
+                )
+                .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                .parse(buf)?,
+            (Some(snap), None) => session
+                .table_dissector::<SnapDissectorTable>(&snap, Some(TempPdu::new(&llc, &parent)))
+                .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                .parse(buf)?,
+            (None, _) => session
+                .table_dissector::<LlcSapDissectorTable>(
+                    &llc.dsap,
+                    Some(TempPdu::new(&llc, &parent)),
+                )
+                .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                .parse(buf)?,
+        };
+        llc.set_inner_pdu(inner);
+        Ok((rem, llc))
+    }
+}
+
+impl Pdu for Llc {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        2 + self.control.len() + self.snap.map(|_| 5).unwrap_or(0)
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.dsap)?.encode(&self.ssap)?;
+        match self.control {
+            Control::Unnumbered(ctrl) => encoder.encode(&ctrl)?,
+            Control::Numbered(ctrl) => encoder.encode_be(&ctrl)?,
+        };
+        if let Some(snap) = &self.snap {
+            encoder
+                .encode(&snap.oui[..])?
+                .encode_be(&snap.protocol_id)?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("LLC", None)?;
+        node.byte_range(0, 1).add_field(
+            "DSAP",
+            DumpValue::UInt(self.dsap.into()),
+            Some(&format!("0x{:02x}", self.dsap)[..]),
+        )?;
+        node.byte_range(1, 1).add_field(
+            "SSAP",
+            DumpValue::UInt(self.ssap.into()),
+            Some(&format!("0x{:02x}", self.ssap)[..]),
+        )?;
+        let ctrl = match self.control {
+            Control::Unnumbered(ctrl) => ctrl.into(),
+            Control::Numbered(ctrl) => ctrl,
+        };
+        node.byte_range(2, self.control.len()).add_field(
+            "Control",
+            DumpValue::UInt(ctrl.into()),
+            Some(&format!("0x{:02x}", ctrl)[..]),
+        )?;
+        if let Some(snap) = &self.snap {
+            let offset = 2 + self.control.len();
+            node.byte_range(offset, 3)
+                .add_field("OUI", DumpValue::Bytes(&snap.oui[..]), None)?;
+            let descr = match snap.ethertype() {
+                Some(ethertype) => format!("Ethertype 0x{:04x}", ethertype.0),
+                None => format!("0x{:04x}", snap.protocol_id),
+            };
+            node.byte_range(offset + 3, 2).add_field(
+                "Protocol ID",
+                DumpValue::UInt(snap.protocol_id.into()),
+                Some(&descr[..]),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_ethertype();
+    }
+}
+
+crate::register_ieee_802_3_pdu!(Llc);
+register_dissector!(llc, Ieee8023DissectorTable, (), Priority(0), Llc::dissect);
+register_field!(llc_dsap, "llc.dsap" => "LLC.DSAP");
+register_field!(llc_ssap, "llc.ssap" => "LLC.SSAP");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ethernet_ii::EthernetII;
+    use crate::ipv4::Ipv4;
+
+    #[test]
+    fn llc_snap() {
+        let mut llc = Llc::with_snap([0; 3], 0);
+        llc.set_inner_pdu(Ipv4::with_addresses(
+            [10, 0, 0, 1].into(),
+            [10, 0, 0, 2].into(),
+        ));
+        let mut eth = EthernetII::new();
+        eth.set_inner_pdu(llc);
+        eth.make_all_canonical();
+        assert!(eth.is_length_framed());
+        assert_eq!(eth.ethertype(), Ethertype(28));
+
+        let mut buf = Vec::new();
+        eth.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 60);
+        let session = Session::new();
+        let (rem, eth) = EthernetII::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        let llc = eth.find::<Llc>().unwrap();
+        assert_eq!(
+            llc.snap().and_then(|snap| snap.ethertype()),
+            Some(Ethertype::IPV4)
+        );
+        assert_eq!(
+            llc.find::<Ipv4>().unwrap().dst_address(),
+            [10, 0, 0, 2].into()
+        );
+        assert_eq!(eth.trailer().len(), 18);
+
+        let mut out = Vec::new();
+        eth.serialize(&mut out).unwrap();
+        assert_eq!(out, buf);
+    }
+
+    #[test]
+    fn llc_numbered() {
+        // An I-format PDU has a two byte control field
+        let buf = [0xf0, 0xf0, 0x0a, 0x0c, 0xde, 0xad];
+        let session = Session::new();
+        let (rem, llc) = Llc::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(llc.control(), Control::Numbered(0x0a0c));
+        assert!(llc.snap().is_none());
+        assert_eq!(llc.header_len(), 4);
+        assert_eq!(llc.inner_pdu().unwrap().total_len(), 2);
+    }
+}
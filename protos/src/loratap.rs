@@ -0,0 +1,741 @@
+use crate::prelude::*;
+use nom::{
+    bytes::complete::take,
+    combinator::{flat_map, map, rest},
+    sequence::tuple,
+};
+
+/// LoRaTap radio header (`LinkType::LORATAP`)
+#[derive(Debug, Clone)]
+pub struct LoraTap {
+    base: BasePdu,
+    version: u8,
+    padding: u8,
+    length: u16,
+    frequency: u32,
+    bandwidth: u8,
+    spreading_factor: u8,
+    packet_rssi: u8,
+    max_rssi: u8,
+    current_rssi: u8,
+    snr: u8,
+    sync_word: u8,
+    ext: Vec<u8>,
+}
+
+/// LoRaWAN MAC layer frame
+#[derive(Debug, Clone)]
+pub struct LoraWan {
+    base: BasePdu,
+    mhdr: u8,
+    payload: LoraWanPayload,
+    mic: [u8; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MType {
+    JoinRequest,
+    JoinAccept,
+    UnconfirmedDataUp,
+    UnconfirmedDataDown,
+    ConfirmedDataUp,
+    ConfirmedDataDown,
+    RejoinRequest,
+    Proprietary,
+}
+
+#[derive(Debug, Clone)]
+pub enum LoraWanPayload {
+    Data(DataFrame),
+    JoinRequest(JoinRequest),
+    /// Encrypted join accept, rejoin, or proprietary payload
+    Other(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub struct DataFrame {
+    pub dev_addr: u32,
+    pub fctrl: u8,
+    pub fcnt: u16,
+    pub fopts: Vec<u8>,
+    pub fport: Option<u8>,
+    /// Encrypted application or MAC command payload
+    pub frm_payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinRequest {
+    pub join_eui: u64,
+    pub dev_eui: u64,
+    pub dev_nonce: u16,
+}
+
+dissector_table!(pub LoraTapDissectorTable);
+
+register_dissector_table!(LoraTapDissectorTable);
+
+impl LoraTap {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            version: 0,
+            padding: 0,
+            length: 15,
+            frequency: 0,
+            bandwidth: 0,
+            spreading_factor: 0,
+            packet_rssi: 0,
+            max_rssi: 0,
+            current_rssi: 0,
+            snr: 0,
+            sync_word: 0x34,
+            ext: Vec::new(),
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    /// Header length in bytes
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn length_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.length
+    }
+
+    pub fn update_length(&mut self) {
+        self.base.mark_dirty();
+        self.length = (15 + self.ext.len()) as u16;
+    }
+
+    /// Frequency in Hz
+    pub fn frequency(&self) -> u32 {
+        self.frequency
+    }
+
+    pub fn frequency_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.frequency
+    }
+
+    /// Bandwidth in units of 125 kHz
+    pub fn bandwidth(&self) -> u8 {
+        self.bandwidth
+    }
+
+    pub fn bandwidth_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.bandwidth
+    }
+
+    pub fn spreading_factor(&self) -> u8 {
+        self.spreading_factor
+    }
+
+    pub fn spreading_factor_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.spreading_factor
+    }
+
+    pub fn packet_rssi(&self) -> u8 {
+        self.packet_rssi
+    }
+
+    pub fn packet_rssi_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.packet_rssi
+    }
+
+    pub fn max_rssi(&self) -> u8 {
+        self.max_rssi
+    }
+
+    pub fn max_rssi_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.max_rssi
+    }
+
+    pub fn current_rssi(&self) -> u8 {
+        self.current_rssi
+    }
+
+    pub fn current_rssi_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.current_rssi
+    }
+
+    /// Raw SNR field, in units of 0.25 dB
+    pub fn snr(&self) -> u8 {
+        self.snr
+    }
+
+    pub fn snr_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.snr
+    }
+
+    pub fn sync_word(&self) -> u8 {
+        self.sync_word
+    }
+
+    pub fn sync_word_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.sync_word
+    }
+
+    /// Header bytes beyond the version 0 header
+    pub fn extension(&self) -> &[u8] {
+        &self.ext[..]
+    }
+
+    pub fn extension_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.ext
+    }
+}
+
+impl Dissect for LoraTap {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            tuple((
+                u8::decode,
+                u8::decode,
+                u16::decode_be,
+                u32::decode_be,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+                u8::decode,
+            )),
+            |(
+                version,
+                padding,
+                length,
+                frequency,
+                bandwidth,
+                spreading_factor,
+                packet_rssi,
+                max_rssi,
+                current_rssi,
+                snr,
+                sync_word,
+            )| {
+                let parent = parent.clone();
+                move |buf: &'a [u8]| {
+                    if length < 15 {
+                        return Err(nom::Err::Error(DissectError::Malformed));
+                    }
+                    let (buf, ext) = take(length as usize - 15)(buf)?;
+                    let mut tap = Self {
+                        base: BasePdu::default(),
+                        version,
+                        padding,
+                        length,
+                        frequency,
+                        bandwidth,
+                        spreading_factor,
+                        packet_rssi,
+                        max_rssi,
+                        current_rssi,
+                        snr,
+                        sync_word,
+                        ext: Vec::from(ext),
+                    };
+                    let (buf, inner) = session
+                        .table_dissector::<LoraTapDissectorTable>(
+                            &(),
+                            Some(TempPdu::new(&tap, &parent)),
+                        )
+                        .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                        .parse(buf)?;
+                    tap.set_inner_pdu(inner);
+                    Ok((buf, tap))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for LoraTap {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        15 + self.ext.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.version)?
+            .encode(&self.padding)?
+            .encode_be(&self.length)?
+            .encode_be(&self.frequency)?
+            .encode(&self.bandwidth)?
+            .encode(&self.spreading_factor)?
+            .encode(&self.packet_rssi)?
+            .encode(&self.max_rssi)?
+            .encode(&self.current_rssi)?
+            .encode(&self.snr)?
+            .encode(&self.sync_word)?
+            .encode(&self.ext[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node(
+            "LoRaTap",
+            Some(&format!("{} Hz, SF{}", self.frequency, self.spreading_factor)[..]),
+        )?;
+        node.add_field("Version", DumpValue::UInt(self.version.into()), None)?;
+        node.add_field("Header Length", DumpValue::UInt(self.length.into()), None)?;
+        node.add_field("Frequency", DumpValue::UInt(self.frequency.into()), None)?;
+        node.add_field(
+            "Bandwidth",
+            DumpValue::UInt(self.bandwidth.into()),
+            Some(&format!("{} kHz", self.bandwidth as u32 * 125)[..]),
+        )?;
+        node.add_field(
+            "Spreading Factor",
+            DumpValue::UInt(self.spreading_factor.into()),
+            None,
+        )?;
+        node.add_field(
+            "Packet RSSI",
+            DumpValue::Int(self.packet_rssi as i64 - 139),
+            Some(&format!("{} dBm", self.packet_rssi as i64 - 139)[..]),
+        )?;
+        node.add_field(
+            "Max RSSI",
+            DumpValue::Int(self.max_rssi as i64 - 139),
+            Some(&format!("{} dBm", self.max_rssi as i64 - 139)[..]),
+        )?;
+        node.add_field(
+            "Current RSSI",
+            DumpValue::Int(self.current_rssi as i64 - 139),
+            Some(&format!("{} dBm", self.current_rssi as i64 - 139)[..]),
+        )?;
+        node.add_field(
+            "SNR",
+            DumpValue::Float((self.snr as i8) as f64 / 4.0),
+            Some(&format!("{} dB", (self.snr as i8) as f64 / 4.0)[..]),
+        )?;
+        node.add_field(
+            "Sync Word",
+            DumpValue::UInt(self.sync_word.into()),
+            Some(&format!("0x{:02x}", self.sync_word)[..]),
+        )
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_length();
+    }
+}
+
+impl Default for LoraTap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MType {
+    pub fn is_data(self) -> bool {
+        matches!(
+            self,
+            Self::UnconfirmedDataUp
+                | Self::UnconfirmedDataDown
+                | Self::ConfirmedDataUp
+                | Self::ConfirmedDataDown
+        )
+    }
+}
+
+impl From<u8> for MType {
+    fn from(mhdr: u8) -> Self {
+        match mhdr >> 5 {
+            0 => Self::JoinRequest,
+            1 => Self::JoinAccept,
+            2 => Self::UnconfirmedDataUp,
+            3 => Self::UnconfirmedDataDown,
+            4 => Self::ConfirmedDataUp,
+            5 => Self::ConfirmedDataDown,
+            6 => Self::RejoinRequest,
+            _ => Self::Proprietary,
+        }
+    }
+}
+
+impl From<MType> for u8 {
+    fn from(mtype: MType) -> u8 {
+        let val = match mtype {
+            MType::JoinRequest => 0,
+            MType::JoinAccept => 1,
+            MType::UnconfirmedDataUp => 2,
+            MType::UnconfirmedDataDown => 3,
+            MType::ConfirmedDataUp => 4,
+            MType::ConfirmedDataDown => 5,
+            MType::RejoinRequest => 6,
+            MType::Proprietary => 7,
+        };
+        val << 5
+    }
+}
+
+impl std::fmt::Display for MType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::JoinRequest => "Join Request",
+            Self::JoinAccept => "Join Accept",
+            Self::UnconfirmedDataUp => "Unconfirmed Data Up",
+            Self::UnconfirmedDataDown => "Unconfirmed Data Down",
+            Self::ConfirmedDataUp => "Confirmed Data Up",
+            Self::ConfirmedDataDown => "Confirmed Data Down",
+            Self::RejoinRequest => "Rejoin Request",
+            Self::Proprietary => "Proprietary",
+        })
+    }
+}
+
+impl DataFrame {
+    /// Adaptive data rate bit
+    pub fn adr(&self) -> bool {
+        (self.fctrl & 0x80) != 0
+    }
+
+    pub fn ack(&self) -> bool {
+        (self.fctrl & 0x20) != 0
+    }
+
+    pub fn fopts_len(&self) -> usize {
+        (self.fctrl & 0x0f) as usize
+    }
+
+    fn len(&self) -> usize {
+        7 + self.fopts.len() + self.fport.map(|_| 1).unwrap_or(0) + self.frm_payload.len()
+    }
+
+    fn dissect<'a>(buf: &'a [u8]) -> DResult<'a, Self> {
+        flat_map(
+            tuple((u32::decode_le, u8::decode, u16::decode_le)),
+            |(dev_addr, fctrl, fcnt)| {
+                move |buf: &'a [u8]| {
+                    let (buf, fopts) = take((fctrl & 0x0f) as usize)(buf)?;
+                    let (buf, fport) = if buf.is_empty() {
+                        (buf, None)
+                    } else {
+                        map(u8::decode, Some)(buf)?
+                    };
+                    let (buf, frm_payload) = rest(buf)?;
+                    Ok((
+                        buf,
+                        DataFrame {
+                            dev_addr,
+                            fctrl,
+                            fcnt,
+                            fopts: Vec::from(fopts),
+                            fport,
+                            frm_payload: Vec::from(frm_payload),
+                        },
+                    ))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl LoraWan {
+    pub fn new(mtype: MType, payload: LoraWanPayload) -> Self {
+        Self {
+            base: BasePdu::default(),
+            mhdr: u8::from(mtype),
+            payload,
+            mic: [0u8; 4],
+        }
+    }
+
+    pub fn mhdr(&self) -> u8 {
+        self.mhdr
+    }
+
+    pub fn mhdr_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.mhdr
+    }
+
+    pub fn mtype(&self) -> MType {
+        MType::from(self.mhdr)
+    }
+
+    pub fn major(&self) -> u8 {
+        self.mhdr & 0x03
+    }
+
+    pub fn payload(&self) -> &LoraWanPayload {
+        &self.payload
+    }
+
+    pub fn payload_mut(&mut self) -> &mut LoraWanPayload {
+        self.base.mark_dirty();
+        &mut self.payload
+    }
+
+    /// Returns the device address of a data frame
+    pub fn dev_addr(&self) -> Option<u32> {
+        match &self.payload {
+            LoraWanPayload::Data(frame) => Some(frame.dev_addr),
+            _ => None,
+        }
+    }
+
+    /// Message integrity code
+    pub fn mic(&self) -> [u8; 4] {
+        self.mic
+    }
+
+    pub fn mic_mut(&mut self) -> &mut [u8; 4] {
+        self.base.mark_dirty();
+        &mut self.mic
+    }
+
+    fn payload_len(&self) -> usize {
+        match &self.payload {
+            LoraWanPayload::Data(frame) => frame.len(),
+            LoraWanPayload::JoinRequest(_) => 18,
+            LoraWanPayload::Other(data) => data.len(),
+        }
+    }
+}
+
+impl Dissect for LoraWan {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        if buf.len() < 5 {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let (body, mic) = buf.split_at(buf.len() - 4);
+        let (body, mhdr) = u8::decode(body)?;
+        let mtype = MType::from(mhdr);
+        let payload = if mtype.is_data() {
+            let (_, frame) = DataFrame::dissect(body)?;
+            LoraWanPayload::Data(frame)
+        } else if mtype == MType::JoinRequest && body.len() == 18 {
+            let (_, (join_eui, dev_eui, dev_nonce)) =
+                tuple((u64::decode_le, u64::decode_le, u16::decode_le))(body)?;
+            LoraWanPayload::JoinRequest(JoinRequest {
+                join_eui,
+                dev_eui,
+                dev_nonce,
+            })
+        } else {
+            LoraWanPayload::Other(Vec::from(body))
+        };
+        Ok((
+            &buf[buf.len()..],
+            Self {
+                base: BasePdu::default(),
+                mhdr,
+                payload,
+                mic: [mic[0], mic[1], mic[2], mic[3]],
+            },
+        ))
+    }
+}
+
+impl Pdu for LoraWan {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        1 + self.payload_len()
+    }
+
+    fn trailer_len(&self) -> usize {
+        4
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.mhdr)?;
+        match &self.payload {
+            LoraWanPayload::Data(frame) => {
+                encoder
+                    .encode_le(&frame.dev_addr)?
+                    .encode(&frame.fctrl)?
+                    .encode_le(&frame.fcnt)?
+                    .encode(&frame.fopts[..])?;
+                if let Some(fport) = frame.fport {
+                    encoder.encode(&fport)?;
+                }
+                encoder.encode(&frame.frm_payload[..])?;
+            }
+            LoraWanPayload::JoinRequest(req) => {
+                encoder
+                    .encode_le(&req.join_eui)?
+                    .encode_le(&req.dev_eui)?
+                    .encode_le(&req.dev_nonce)?;
+            }
+            LoraWanPayload::Other(data) => {
+                encoder.encode(&data[..])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn serialize_trailer<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.mic[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("LoRaWAN", Some(&self.mtype().to_string()[..]))?;
+        {
+            let mut node = node.add_node("MHDR", Some(&format!("0x{:02x}", self.mhdr)[..]))?;
+            node.add_field(
+                "Message Type",
+                DumpValue::UInt((self.mhdr >> 5).into()),
+                Some(&self.mtype().to_string()[..]),
+            )?;
+            node.add_field("Major Version", DumpValue::UInt(self.major().into()), None)?;
+        }
+        match &self.payload {
+            LoraWanPayload::Data(frame) => {
+                node.add_field(
+                    "Device Address",
+                    DumpValue::UInt(frame.dev_addr.into()),
+                    Some(&format!("{:08x}", frame.dev_addr)[..]),
+                )?;
+                {
+                    let mut node =
+                        node.add_node("FCtrl", Some(&format!("0x{:02x}", frame.fctrl)[..]))?;
+                    node.add_field("ADR", DumpValue::Bool(frame.adr()), None)?;
+                    node.add_field("ACK", DumpValue::Bool(frame.ack()), None)?;
+                    node.add_field(
+                        "FOpts Length",
+                        DumpValue::UInt(frame.fopts_len() as u64),
+                        None,
+                    )?;
+                }
+                node.add_field("Frame Counter", DumpValue::UInt(frame.fcnt.into()), None)?;
+                if !frame.fopts.is_empty() {
+                    node.add_field("FOpts", DumpValue::Bytes(&frame.fopts[..]), None)?;
+                }
+                if let Some(fport) = frame.fport {
+                    node.add_field("FPort", DumpValue::UInt(fport.into()), None)?;
+                }
+                if !frame.frm_payload.is_empty() {
+                    node.add_field("FRMPayload", DumpValue::Bytes(&frame.frm_payload[..]), None)?;
+                }
+            }
+            LoraWanPayload::JoinRequest(req) => {
+                node.add_field(
+                    "JoinEUI",
+                    DumpValue::UInt(req.join_eui),
+                    Some(&format!("{:016x}", req.join_eui)[..]),
+                )?;
+                node.add_field(
+                    "DevEUI",
+                    DumpValue::UInt(req.dev_eui),
+                    Some(&format!("{:016x}", req.dev_eui)[..]),
+                )?;
+                node.add_field("DevNonce", DumpValue::UInt(req.dev_nonce.into()), None)?;
+            }
+            LoraWanPayload::Other(data) => {
+                node.add_field("Payload", DumpValue::Bytes(&data[..]), None)?;
+            }
+        }
+        node.add_field("MIC", DumpValue::Bytes(&self.mic[..]), None)
+    }
+}
+
+register_link_layer_pdu!(LoraTap, LinkType::LORATAP);
+register_dissector!(
+    loratap,
+    LinkTypeTable,
+    LinkType::LORATAP,
+    Priority(0),
+    LoraTap::dissect
+);
+register_dissector!(
+    lorawan,
+    LoraTapDissectorTable,
+    (),
+    Priority(0),
+    LoraWan::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loratap_lorawan() {
+        let data = [
+            0x00, 0x00, 0x00, 0x0f, 0x33, 0xbe, 0x27, 0xa0, 0x01, 0x07, 0x8b, 0x8d, 0x80, 0x28,
+            0x34, // LoRaTap
+            0x40, // MHDR: unconfirmed data up
+            0x04, 0x03, 0x02, 0x01, // DevAddr
+            0x80, 0x05, 0x00, // FCtrl, FCnt
+            0x0a, 0xde, 0xad, // FPort, FRMPayload
+            0x11, 0x22, 0x33, 0x44, // MIC
+        ];
+        let session = Session::new();
+        let (rem, tap) = LoraTap::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(tap.frequency(), 868_100_000);
+        assert_eq!(tap.spreading_factor(), 7);
+
+        let lorawan = tap.find::<LoraWan>().unwrap();
+        assert_eq!(lorawan.mtype(), MType::UnconfirmedDataUp);
+        assert_eq!(lorawan.dev_addr(), Some(0x01020304));
+        assert_eq!(lorawan.mic(), [0x11, 0x22, 0x33, 0x44]);
+        match lorawan.payload() {
+            LoraWanPayload::Data(frame) => {
+                assert!(frame.adr());
+                assert_eq!(frame.fcnt, 5);
+                assert_eq!(frame.fport, Some(10));
+                assert_eq!(frame.frm_payload, [0xde, 0xad]);
+            }
+            _ => panic!("expected a data frame"),
+        }
+
+        let mut out = Vec::new();
+        tap.serialize(&mut out).unwrap();
+        assert_eq!(&out[..], &data[..]);
+    }
+}
@@ -0,0 +1,580 @@
+use crate::i2c::I2cDissectorTable;
+use crate::prelude::*;
+use nom::{
+    bytes::complete::take,
+    combinator::{flat_map, map, verify},
+    sequence::tuple,
+};
+
+/// SMBus command code identifying MCTP packets
+pub const MCTP_SMBUS_COMMAND: u8 = 0x0f;
+
+/// MCTP over SMBus/I2C framing (DMTF DSP0237)
+#[derive(Debug, Clone)]
+pub struct MctpSmbus {
+    base: BasePdu,
+    dst_addr: u8,
+    command: u8,
+    byte_count: u8,
+    src_addr: u8,
+    pec: Option<u8>,
+}
+
+/// Management Component Transport Protocol packet (DMTF DSP0236)
+#[derive(Debug, Clone)]
+pub struct Mctp {
+    base: BasePdu,
+    version: u8,
+    dst_eid: u8,
+    src_eid: u8,
+    flags: u8,
+    msg_type: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MctpMessageType(pub u8);
+
+dissector_table!(pub MctpDissectorTable, MctpMessageType);
+
+register_dissector_table!(MctpDissectorTable);
+
+impl MctpMessageType {
+    pub const CONTROL: Self = Self(0x00);
+    pub const PLDM: Self = Self(0x01);
+    pub const NCSI: Self = Self(0x02);
+    pub const ETHERNET: Self = Self(0x03);
+    pub const NVME_MI: Self = Self(0x04);
+    pub const SPDM: Self = Self(0x05);
+    pub const SECURED: Self = Self(0x06);
+    pub const CXL_FM_API: Self = Self(0x07);
+    pub const CXL_CCI: Self = Self(0x08);
+    pub const VENDOR_PCI: Self = Self(0x7e);
+    pub const VENDOR_IANA: Self = Self(0x7f);
+}
+
+impl std::fmt::Display for MctpMessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::CONTROL => "MCTP Control",
+            Self::PLDM => "PLDM",
+            Self::NCSI => "NC-SI",
+            Self::ETHERNET => "Ethernet",
+            Self::NVME_MI => "NVMe-MI",
+            Self::SPDM => "SPDM",
+            Self::SECURED => "Secured Messages",
+            Self::CXL_FM_API => "CXL FM API",
+            Self::CXL_CCI => "CXL CCI",
+            Self::VENDOR_PCI => "Vendor Defined (PCI)",
+            Self::VENDOR_IANA => "Vendor Defined (IANA)",
+            _ => return write!(f, "Unknown (0x{:02x})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+/// SMBus packet error code, a CRC-8 with polynomial 0x07
+pub fn smbus_pec(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, byte| {
+        let mut crc = crc ^ byte;
+        for _ in 0..8 {
+            crc = if (crc & 0x80) != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+impl MctpSmbus {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            dst_addr: 0,
+            command: MCTP_SMBUS_COMMAND,
+            byte_count: 1,
+            src_addr: 0x01,
+            pec: Some(0),
+        }
+    }
+
+    /// Destination slave address octet, including the R/W bit
+    pub fn dst_addr(&self) -> u8 {
+        self.dst_addr
+    }
+
+    pub fn dst_addr_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.dst_addr
+    }
+
+    pub fn command(&self) -> u8 {
+        self.command
+    }
+
+    pub fn command_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.command
+    }
+
+    /// Number of bytes following the byte count, excluding the PEC
+    pub fn byte_count(&self) -> u8 {
+        self.byte_count
+    }
+
+    pub fn byte_count_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.byte_count
+    }
+
+    pub fn update_byte_count(&mut self) {
+        self.base.mark_dirty();
+        let len = 1 + self.inner_pdu().map(|inner| inner.total_len()).unwrap_or(0);
+        self.byte_count = len.min(0xff) as u8;
+    }
+
+    /// Source slave address octet. The low bit is always set.
+    pub fn src_addr(&self) -> u8 {
+        self.src_addr
+    }
+
+    pub fn src_addr_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.src_addr
+    }
+
+    /// Packet error code, if present in the capture
+    pub fn pec(&self) -> Option<u8> {
+        self.pec
+    }
+
+    pub fn pec_mut(&mut self) -> &mut Option<u8> {
+        self.base.mark_dirty();
+        &mut self.pec
+    }
+
+    pub fn calc_pec(&self) -> u8 {
+        let mut data = vec![self.dst_addr, self.command, self.byte_count, self.src_addr];
+        if let Some(inner) = self.inner_pdu() {
+            let _ = inner.serialize(&mut data);
+        }
+        smbus_pec(&data[..])
+    }
+
+    /// Recalculates the PEC, if present
+    pub fn update_pec(&mut self) {
+        self.base.mark_dirty();
+        if self.pec.is_some() {
+            self.pec = Some(self.calc_pec());
+        }
+    }
+}
+
+impl Dissect for MctpSmbus {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            verify(
+                tuple((u8::decode, u8::decode, u8::decode, u8::decode)),
+                |(_, command, byte_count, src_addr)| {
+                    *command == MCTP_SMBUS_COMMAND && *byte_count >= 5 && (src_addr & 0x01) != 0
+                },
+            ),
+            |(dst_addr, command, byte_count, src_addr)| {
+                let parent = parent.clone();
+                move |buf: &'a [u8]| {
+                    let (buf, payload) = take(byte_count as usize - 1)(buf)?;
+                    let (buf, pec) = match buf.split_first() {
+                        Some((pec, rem)) => (rem, Some(*pec)),
+                        None => (buf, None),
+                    };
+                    let mut smbus = Self {
+                        base: BasePdu::default(),
+                        dst_addr,
+                        command,
+                        byte_count,
+                        src_addr,
+                        pec,
+                    };
+                    let (_, inner) = map(
+                        Mctp::dissector(session, Some(TempPdu::new(&smbus, &parent))),
+                        AnyPdu::new,
+                    )
+                    .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                    .parse(payload)?;
+                    smbus.set_inner_pdu(inner);
+                    Ok((buf, smbus))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for MctpSmbus {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        4
+    }
+
+    fn trailer_len(&self) -> usize {
+        if self.pec.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.dst_addr)?
+            .encode(&self.command)?
+            .encode(&self.byte_count)?
+            .encode(&self.src_addr)?;
+        Ok(())
+    }
+
+    fn serialize_trailer<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        if let Some(pec) = self.pec {
+            encoder.encode(&pec)?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("MCTP SMBus", None)?;
+        node.byte_range(0, 1).add_field(
+            "Destination Address",
+            DumpValue::UInt((self.dst_addr >> 1).into()),
+            Some(&format!("0x{:02x}", self.dst_addr >> 1)[..]),
+        )?;
+        node.byte_range(1, 1).add_field(
+            "Command Code",
+            DumpValue::UInt(self.command.into()),
+            Some(&format!("0x{:02x}", self.command)[..]),
+        )?;
+        node.byte_range(2, 1).add_field(
+            "Byte Count",
+            DumpValue::UInt(self.byte_count.into()),
+            None,
+        )?;
+        node.byte_range(3, 1).add_field(
+            "Source Address",
+            DumpValue::UInt((self.src_addr >> 1).into()),
+            Some(&format!("0x{:02x}", self.src_addr >> 1)[..]),
+        )?;
+        if let Some(pec) = self.pec {
+            let offset = self.total_len() - 1;
+            node.byte_range(offset, 1).add_field(
+                "PEC",
+                DumpValue::UInt(pec.into()),
+                Some(&format!("0x{:02x}", pec)[..]),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_byte_count();
+        self.update_pec();
+    }
+}
+
+impl Default for MctpSmbus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const FLAG_SOM: u8 = 0x80;
+const FLAG_EOM: u8 = 0x40;
+const FLAG_TO: u8 = 0x08;
+const MSG_TYPE_IC: u8 = 0x80;
+
+impl Mctp {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            version: 0x01,
+            dst_eid: 0,
+            src_eid: 0,
+            flags: FLAG_SOM | FLAG_EOM | FLAG_TO,
+            msg_type: Some(0),
+        }
+    }
+
+    /// Header version octet, including the reserved bits
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    pub fn dst_eid(&self) -> u8 {
+        self.dst_eid
+    }
+
+    pub fn dst_eid_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.dst_eid
+    }
+
+    pub fn src_eid(&self) -> u8 {
+        self.src_eid
+    }
+
+    pub fn src_eid_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.src_eid
+    }
+
+    /// Raw flags octet, containing SOM, EOM, sequence, TO, and tag
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn flags_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.flags
+    }
+
+    /// Start of message
+    pub fn som(&self) -> bool {
+        (self.flags & FLAG_SOM) != 0
+    }
+
+    /// End of message
+    pub fn eom(&self) -> bool {
+        (self.flags & FLAG_EOM) != 0
+    }
+
+    /// Packet sequence number
+    pub fn seq(&self) -> u8 {
+        (self.flags >> 4) & 0x03
+    }
+
+    /// Tag owner
+    pub fn tag_owner(&self) -> bool {
+        (self.flags & FLAG_TO) != 0
+    }
+
+    pub fn tag(&self) -> u8 {
+        self.flags & 0x07
+    }
+
+    /// Message type octet, including the integrity check bit. Only present
+    /// in the first packet of a message.
+    pub fn msg_type_raw(&self) -> Option<u8> {
+        self.msg_type
+    }
+
+    pub fn msg_type_raw_mut(&mut self) -> &mut Option<u8> {
+        self.base.mark_dirty();
+        &mut self.msg_type
+    }
+
+    pub fn msg_type(&self) -> Option<MctpMessageType> {
+        self.msg_type.map(|ty| MctpMessageType(ty & !MSG_TYPE_IC))
+    }
+
+    /// True if the message ends with a message integrity check
+    pub fn integrity_check(&self) -> bool {
+        self.msg_type
+            .map(|ty| (ty & MSG_TYPE_IC) != 0)
+            .unwrap_or(false)
+    }
+}
+
+impl Dissect for Mctp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            verify(
+                tuple((u8::decode, u8::decode, u8::decode, u8::decode)),
+                |(version, _, _, _)| (version & 0x0f) == 0x01,
+            ),
+            |(version, dst_eid, src_eid, flags)| {
+                let parent = parent.clone();
+                move |buf: &'a [u8]| {
+                    let (buf, msg_type) = if (flags & FLAG_SOM) != 0 {
+                        let (buf, msg_type) = u8::decode(buf)?;
+                        (buf, Some(msg_type))
+                    } else {
+                        (buf, None)
+                    };
+                    let mut mctp = Self {
+                        base: BasePdu::default(),
+                        version,
+                        dst_eid,
+                        src_eid,
+                        flags,
+                        msg_type,
+                    };
+                    if buf.is_empty() {
+                        return Ok((buf, mctp));
+                    }
+                    let (buf, inner) = match mctp.msg_type() {
+                        Some(msg_type) => session
+                            .table_dissector::<MctpDissectorTable>(
+                                &msg_type,
+                                Some(TempPdu::new(&mctp, &parent)),
+                            )
+                            .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                            .parse(buf)?,
+                        None => map(RawPdu::dissector(session, None), AnyPdu::new)(buf)?,
+                    };
+                    mctp.set_inner_pdu(inner);
+                    Ok((buf, mctp))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for Mctp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        if self.msg_type.is_some() {
+            5
+        } else {
+            4
+        }
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.version)?
+            .encode(&self.dst_eid)?
+            .encode(&self.src_eid)?
+            .encode(&self.flags)?;
+        if let Some(msg_type) = self.msg_type {
+            encoder.encode(&msg_type)?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node(
+            "MCTP",
+            Some(&format!("{}->{}", self.src_eid, self.dst_eid)[..]),
+        )?;
+        node.byte_range(0, 1).add_field(
+            "Header Version",
+            DumpValue::UInt((self.version & 0x0f).into()),
+            None,
+        )?;
+        node.byte_range(1, 1).add_field(
+            "Destination EID",
+            DumpValue::UInt(self.dst_eid.into()),
+            None,
+        )?;
+        node.byte_range(2, 1).add_field(
+            "Source EID",
+            DumpValue::UInt(self.src_eid.into()),
+            None,
+        )?;
+        {
+            let mut node = node
+                .byte_range(3, 1)
+                .add_node("Flags", Some(&format!("0x{:02x}", self.flags)[..]))?;
+            node.add_field("Start of Message", DumpValue::Bool(self.som()), None)?;
+            node.add_field("End of Message", DumpValue::Bool(self.eom()), None)?;
+            node.add_field("Sequence", DumpValue::UInt(self.seq().into()), None)?;
+            node.add_field("Tag Owner", DumpValue::Bool(self.tag_owner()), None)?;
+            node.add_field("Tag", DumpValue::UInt(self.tag().into()), None)?;
+        }
+        if let Some(msg_type) = self.msg_type() {
+            let mut node = node
+                .byte_range(4, 1)
+                .add_node("Message Type", Some(&msg_type.to_string()[..]))?;
+            node.add_field(
+                "Integrity Check",
+                DumpValue::Bool(self.integrity_check()),
+                None,
+            )?;
+            node.add_field("Type", DumpValue::UInt(msg_type.0.into()), None)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Mctp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    mctp_smbus,
+    I2cDissectorTable,
+    (),
+    Priority(1),
+    MctpSmbus::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::i2c::I2cLinux;
+
+    #[test]
+    fn mctp_smbus() {
+        let mut data = vec![
+            0x00, 0x00, 0x00, 0x00, 0x00, // I2C pseudo-header
+            0x20, 0x0f, 0x08, 0x21, // SMBus
+            0x01, 0x09, 0x08, 0xc8, 0x00, // MCTP
+            0x81, 0x02, // Get EID request
+        ];
+        data.push(smbus_pec(&data[5..]));
+        let session = Session::new();
+        let (rem, i2c) = I2cLinux::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+
+        let smbus = i2c.find::<MctpSmbus>().unwrap();
+        assert_eq!(smbus.src_addr() >> 1, 0x10);
+        assert_eq!(smbus.pec(), Some(smbus.calc_pec()));
+
+        let mctp = smbus.find::<Mctp>().unwrap();
+        assert_eq!(mctp.dst_eid(), 9);
+        assert_eq!(mctp.src_eid(), 8);
+        assert!(mctp.som() && mctp.eom() && mctp.tag_owner());
+        assert_eq!(mctp.msg_type(), Some(MctpMessageType::CONTROL));
+        assert_eq!(mctp.inner_pdu().unwrap().total_len(), 2);
+
+        let mut out = Vec::new();
+        i2c.serialize(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+}
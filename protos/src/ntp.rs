@@ -0,0 +1,588 @@
+use super::udp::UdpPortDissectorTable;
+use crate::prelude::*;
+use nom::{combinator::rest, sequence::tuple};
+use sniffle_core::Ipv4Address;
+use std::time::{Duration, SystemTime};
+
+/// UDP port NTP servers and peers listen on
+pub const NTP_PORT: u16 = 123;
+
+/// Seconds from the NTP prime epoch, 1900-01-01, to the Unix epoch
+const UNIX_OFFSET: i64 = 2_208_988_800;
+const ERA_SECS: i64 = 1 << 32;
+
+/// Network Time Protocol message, as defined by RFC 5905
+///
+/// Control (mode 6) and private (mode 7) messages have a different format
+/// and are not dissected. Extension fields and the message authentication
+/// code, if any, are kept as raw bytes in the trailer.
+#[derive(Debug, Clone)]
+pub struct Ntp {
+    base: BasePdu,
+    leap: LeapIndicator,
+    version: u8,
+    mode: Mode,
+    stratum: u8,
+    poll: i8,
+    precision: i8,
+    root_delay: u32,
+    root_dispersion: u32,
+    reference_id: [u8; 4],
+    reference_ts: NtpTimestamp,
+    origin_ts: NtpTimestamp,
+    receive_ts: NtpTimestamp,
+    transmit_ts: NtpTimestamp,
+    trailer: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LeapIndicator(pub u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mode(pub u8);
+
+/// A 64 bit NTP timestamp: seconds since the start of the timestamp's era in
+/// the upper 32 bits, and fractional seconds in the lower 32 bits.
+///
+/// NTP era 0 started at 1900-01-01 and era 1 starts in 2036, so a timestamp
+/// alone is ambiguous. `to_system_time` resolves it to the era within 68
+/// years of a pivot time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NtpTimestamp(pub u64);
+
+impl LeapIndicator {
+    pub const NO_WARNING: Self = Self(0);
+    pub const LAST_MINUTE_61: Self = Self(1);
+    pub const LAST_MINUTE_59: Self = Self(2);
+    pub const UNSYNCHRONIZED: Self = Self(3);
+}
+
+impl std::fmt::Display for LeapIndicator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::NO_WARNING => "No warning",
+            Self::LAST_MINUTE_61 => "Last minute has 61 seconds",
+            Self::LAST_MINUTE_59 => "Last minute has 59 seconds",
+            Self::UNSYNCHRONIZED => "Unsynchronized",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl Mode {
+    pub const RESERVED: Self = Self(0);
+    pub const SYMMETRIC_ACTIVE: Self = Self(1);
+    pub const SYMMETRIC_PASSIVE: Self = Self(2);
+    pub const CLIENT: Self = Self(3);
+    pub const SERVER: Self = Self(4);
+    pub const BROADCAST: Self = Self(5);
+    pub const CONTROL: Self = Self(6);
+    pub const PRIVATE: Self = Self(7);
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::RESERVED => "Reserved",
+            Self::SYMMETRIC_ACTIVE => "Symmetric active",
+            Self::SYMMETRIC_PASSIVE => "Symmetric passive",
+            Self::CLIENT => "Client",
+            Self::SERVER => "Server",
+            Self::BROADCAST => "Broadcast",
+            Self::CONTROL => "Control",
+            Self::PRIVATE => "Private",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl NtpTimestamp {
+    pub fn new(seconds: u32, fraction: u32) -> Self {
+        Self(((seconds as u64) << 32) | fraction as u64)
+    }
+
+    pub fn seconds(&self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    /// Fractional seconds, in units of 2^-32 seconds
+    pub fn fraction(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// A zero timestamp means the time is unknown or not set
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Converts the timestamp to a system time, assuming it is within 68
+    /// years of `pivot`.
+    pub fn to_system_time_near(&self, pivot: SystemTime) -> SystemTime {
+        let pivot = match pivot.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        } + UNIX_OFFSET;
+        let secs = self.seconds() as i64;
+        let era = (pivot - secs + ERA_SECS / 2).div_euclid(ERA_SECS);
+        let unix = secs + era * ERA_SECS - UNIX_OFFSET;
+        let nanos = ((self.fraction() as u64 * 1_000_000_000) >> 32) as u32;
+        if unix >= 0 {
+            SystemTime::UNIX_EPOCH + Duration::new(unix as u64, nanos)
+        } else {
+            SystemTime::UNIX_EPOCH - Duration::from_secs(unix.unsigned_abs())
+                + Duration::new(0, nanos)
+        }
+    }
+
+    /// Converts the timestamp to a system time between 1968 and 2104, as
+    /// recommended by RFC 4330. Returns `None` for a zero timestamp.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        if self.is_zero() {
+            return None;
+        }
+        // 2036-02-07, the start of era 1
+        let pivot = SystemTime::UNIX_EPOCH + Duration::from_secs((ERA_SECS - UNIX_OFFSET) as u64);
+        Some(self.to_system_time_near(pivot))
+    }
+
+    /// Converts a system time to a timestamp in whichever era contains it
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let (secs, nanos) = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+            Err(before) => {
+                let before = before.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i64), 0),
+                    nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+                }
+            }
+        };
+        let seconds = (secs + UNIX_OFFSET).rem_euclid(ERA_SECS) as u32;
+        let fraction = (((nanos as u64) << 32) / 1_000_000_000) as u32;
+        Self::new(seconds, fraction)
+    }
+}
+
+/// Converts an NTP short format value, 16.16 fixed point seconds
+fn short_duration(value: u32) -> Duration {
+    Duration::from_nanos(((value as u64) * 1_000_000_000) >> 16)
+}
+
+impl Ntp {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            leap: LeapIndicator::NO_WARNING,
+            version: 4,
+            mode: Mode::CLIENT,
+            stratum: 0,
+            poll: 0,
+            precision: 0,
+            root_delay: 0,
+            root_dispersion: 0,
+            reference_id: [0; 4],
+            reference_ts: NtpTimestamp::default(),
+            origin_ts: NtpTimestamp::default(),
+            receive_ts: NtpTimestamp::default(),
+            transmit_ts: NtpTimestamp::default(),
+            trailer: Vec::new(),
+        }
+    }
+
+    pub fn leap(&self) -> LeapIndicator {
+        self.leap
+    }
+
+    pub fn leap_mut(&mut self) -> &mut LeapIndicator {
+        self.base.mark_dirty();
+        &mut self.leap
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn mode_mut(&mut self) -> &mut Mode {
+        self.base.mark_dirty();
+        &mut self.mode
+    }
+
+    /// Stratum of the server's clock. 0 is unspecified or a kiss-o'-death
+    /// message, 1 is a primary server, and 2-15 are secondary servers.
+    pub fn stratum(&self) -> u8 {
+        self.stratum
+    }
+
+    pub fn stratum_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.stratum
+    }
+
+    /// Maximum interval between messages, in log2 seconds
+    pub fn poll(&self) -> i8 {
+        self.poll
+    }
+
+    pub fn poll_mut(&mut self) -> &mut i8 {
+        self.base.mark_dirty();
+        &mut self.poll
+    }
+
+    /// Precision of the system clock, in log2 seconds
+    pub fn precision(&self) -> i8 {
+        self.precision
+    }
+
+    pub fn precision_mut(&mut self) -> &mut i8 {
+        self.base.mark_dirty();
+        &mut self.precision
+    }
+
+    /// Round trip delay to the reference clock, in NTP short format
+    pub fn root_delay(&self) -> u32 {
+        self.root_delay
+    }
+
+    pub fn root_delay_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.root_delay
+    }
+
+    /// Total dispersion to the reference clock, in NTP short format
+    pub fn root_dispersion(&self) -> u32 {
+        self.root_dispersion
+    }
+
+    pub fn root_dispersion_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.root_dispersion
+    }
+
+    pub fn reference_id(&self) -> [u8; 4] {
+        self.reference_id
+    }
+
+    pub fn reference_id_mut(&mut self) -> &mut [u8; 4] {
+        self.base.mark_dirty();
+        &mut self.reference_id
+    }
+
+    /// The kiss code of a kiss-o'-death message, or the reference clock
+    /// identifier of a primary server, such as `"GPS"`.
+    pub fn reference_code(&self) -> Option<&str> {
+        if self.stratum > 1 {
+            return None;
+        }
+        let len = self.reference_id.iter().position(|c| *c == 0).unwrap_or(4);
+        std::str::from_utf8(&self.reference_id[..len])
+            .ok()
+            .filter(|code| code.chars().all(|c| c.is_ascii_graphic()))
+    }
+
+    /// The IPv4 address of the upstream server of a secondary server. For
+    /// IPv6 upstream servers, this is instead the first four bytes of the MD5
+    /// hash of the address.
+    pub fn reference_addr(&self) -> Option<Ipv4Address> {
+        if self.stratum > 1 {
+            Some(self.reference_id.into())
+        } else {
+            None
+        }
+    }
+
+    pub fn reference_timestamp(&self) -> NtpTimestamp {
+        self.reference_ts
+    }
+
+    pub fn reference_timestamp_mut(&mut self) -> &mut NtpTimestamp {
+        self.base.mark_dirty();
+        &mut self.reference_ts
+    }
+
+    pub fn origin_timestamp(&self) -> NtpTimestamp {
+        self.origin_ts
+    }
+
+    pub fn origin_timestamp_mut(&mut self) -> &mut NtpTimestamp {
+        self.base.mark_dirty();
+        &mut self.origin_ts
+    }
+
+    pub fn receive_timestamp(&self) -> NtpTimestamp {
+        self.receive_ts
+    }
+
+    pub fn receive_timestamp_mut(&mut self) -> &mut NtpTimestamp {
+        self.base.mark_dirty();
+        &mut self.receive_ts
+    }
+
+    pub fn transmit_timestamp(&self) -> NtpTimestamp {
+        self.transmit_ts
+    }
+
+    pub fn transmit_timestamp_mut(&mut self) -> &mut NtpTimestamp {
+        self.base.mark_dirty();
+        &mut self.transmit_ts
+    }
+
+    /// Extension fields and message authentication code
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer[..]
+    }
+
+    pub fn trailer_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.trailer
+    }
+
+    fn parse(buf: &[u8]) -> DResult<'_, Self> {
+        let (
+            buf,
+            (
+                flags,
+                stratum,
+                poll,
+                precision,
+                root_delay,
+                root_dispersion,
+                reference_id,
+                timestamps,
+                trailer,
+            ),
+        ) = tuple((
+            u8::decode,
+            u8::decode,
+            i8::decode,
+            i8::decode,
+            u32::decode_be,
+            u32::decode_be,
+            <[u8; 4]>::decode,
+            <[u64; 4]>::decode_be,
+            rest,
+        ))(buf)?;
+        let version = (flags >> 3) & 0x7;
+        let mode = Mode(flags & 0x7);
+        if version == 0 || version > 4 || mode == Mode::CONTROL || mode == Mode::PRIVATE {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        Ok((
+            buf,
+            Self {
+                base: BasePdu::default(),
+                leap: LeapIndicator(flags >> 6),
+                version,
+                mode,
+                stratum,
+                poll,
+                precision,
+                root_delay,
+                root_dispersion,
+                reference_id,
+                reference_ts: NtpTimestamp(timestamps[0]),
+                origin_ts: NtpTimestamp(timestamps[1]),
+                receive_ts: NtpTimestamp(timestamps[2]),
+                transmit_ts: NtpTimestamp(timestamps[3]),
+                trailer: Vec::from(trailer),
+            },
+        ))
+    }
+}
+
+impl Dissect for Ntp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        Self::parse(buf)
+    }
+}
+
+impl Pdu for Ntp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        48 + self.trailer.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        let flags = (self.leap.0 << 6) | ((self.version & 0x7) << 3) | (self.mode.0 & 0x7);
+        encoder
+            .encode(&flags)?
+            .encode(&self.stratum)?
+            .encode(&self.poll)?
+            .encode(&self.precision)?
+            .encode_be(&self.root_delay)?
+            .encode_be(&self.root_dispersion)?
+            .encode(&self.reference_id[..])?
+            .encode_be(&self.reference_ts.0)?
+            .encode_be(&self.origin_ts.0)?
+            .encode_be(&self.receive_ts.0)?
+            .encode_be(&self.transmit_ts.0)?
+            .encode(&self.trailer[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("NTP", Some(&self.mode.to_string()[..]))?;
+        node.byte_range(0, 1).add_field(
+            "Leap Indicator",
+            DumpValue::UInt(self.leap.0.into()),
+            Some(&self.leap.to_string()[..]),
+        )?;
+        node.byte_range(0, 1)
+            .add_field("Version", DumpValue::UInt(self.version.into()), None)?;
+        node.byte_range(0, 1).add_field(
+            "Mode",
+            DumpValue::UInt(self.mode.0.into()),
+            Some(&self.mode.to_string()[..]),
+        )?;
+        node.byte_range(1, 1)
+            .add_field("Stratum", DumpValue::UInt(self.stratum.into()), None)?;
+        node.byte_range(2, 1)
+            .add_field("Poll", DumpValue::Int(self.poll.into()), None)?;
+        node.byte_range(3, 1).add_field(
+            "Precision",
+            DumpValue::Int(self.precision.into()),
+            None,
+        )?;
+        node.byte_range(4, 4).add_field(
+            "Root Delay",
+            DumpValue::Duration(short_duration(self.root_delay)),
+            None,
+        )?;
+        node.byte_range(8, 4).add_field(
+            "Root Dispersion",
+            DumpValue::Duration(short_duration(self.root_dispersion)),
+            None,
+        )?;
+        let descr = match (self.reference_code(), self.reference_addr()) {
+            (Some(code), _) => Some(String::from(code)),
+            (None, Some(addr)) => Some(addr.to_string()),
+            (None, None) => None,
+        };
+        node.byte_range(12, 4).add_field(
+            "Reference ID",
+            DumpValue::Bytes(&self.reference_id[..]),
+            descr.as_deref(),
+        )?;
+        for (i, (name, ts)) in [
+            ("Reference Timestamp", self.reference_ts),
+            ("Origin Timestamp", self.origin_ts),
+            ("Receive Timestamp", self.receive_ts),
+            ("Transmit Timestamp", self.transmit_ts),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let value = match ts.to_system_time() {
+                Some(time) => DumpValue::Time(time),
+                None => DumpValue::UInt(0),
+            };
+            node.byte_range(16 + i * 8, 8)
+                .add_field(name, value, None)?;
+        }
+        if !self.trailer.is_empty() {
+            node.byte_range(48, self.trailer.len()).add_field(
+                "Trailer",
+                DumpValue::Bytes(&self.trailer[..]),
+                None,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.leap.0 &= 0x3;
+        self.version &= 0x7;
+        self.mode.0 &= 0x7;
+    }
+}
+
+impl Default for Ntp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    ntp,
+    UdpPortDissectorTable,
+    NTP_PORT,
+    Priority(0),
+    Ntp::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ntp_server_reply() {
+        let data = [
+            0x24, 0x02, 0x03, 0xe8, // LI, VN, mode, stratum, poll, precision
+            0x00, 0x00, 0x00, 0x10, // Root delay
+            0x00, 0x00, 0x00, 0x20, // Root dispersion
+            0xc0, 0x00, 0x02, 0x01, // Reference ID
+            0xe9, 0x3c, 0x7f, 0x00, 0x00, 0x00, 0x00, 0x00, // Reference
+            0xe9, 0x3c, 0x7f, 0x10, 0x80, 0x00, 0x00, 0x00, // Origin
+            0xe9, 0x3c, 0x7f, 0x10, 0x80, 0x00, 0x00, 0x00, // Receive
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // Transmit, era 1
+        ];
+        let session = Session::new();
+        let (rem, ntp) = Ntp::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(ntp.leap(), LeapIndicator::NO_WARNING);
+        assert_eq!(ntp.version(), 4);
+        assert_eq!(ntp.mode(), Mode::SERVER);
+        assert_eq!(ntp.stratum(), 2);
+        assert_eq!(ntp.precision(), -24);
+        assert_eq!(ntp.reference_code(), None);
+        assert_eq!(
+            ntp.reference_addr(),
+            Some(Ipv4Address::from([192, 0, 2, 1]))
+        );
+
+        // 2024-01-01T00:00:16.5Z
+        let origin = SystemTime::UNIX_EPOCH + Duration::from_millis(1_704_067_216_500);
+        assert_eq!(ntp.origin_timestamp().to_system_time(), Some(origin));
+        assert_eq!(
+            NtpTimestamp::from_system_time(origin),
+            ntp.origin_timestamp()
+        );
+        // One second into era 1, 2036-02-07T06:28:17Z
+        let transmit = SystemTime::UNIX_EPOCH + Duration::from_secs(2_085_978_497);
+        assert_eq!(ntp.transmit_timestamp().to_system_time(), Some(transmit));
+        // The same timestamp in era 0 is 1900-01-01T00:00:01Z
+        let pivot = SystemTime::UNIX_EPOCH - Duration::from_secs(2_000_000_000);
+        assert_eq!(
+            ntp.transmit_timestamp().to_system_time_near(pivot),
+            SystemTime::UNIX_EPOCH - Duration::from_secs(2_208_988_799)
+        );
+
+        let mut out = Vec::new();
+        ntp.serialize(&mut out).unwrap();
+        assert_eq!(&out[..], &data[..]);
+    }
+}
@@ -0,0 +1,713 @@
+//! Open Shortest Path First version 2 (OSPFv2), RFC 2328.
+
+use super::ip_proto::IpProto;
+use super::ipv4::IpProtoDissectorTable;
+use crate::prelude::*;
+use checksum::U16OnesComplement;
+use sniffle_core::Ipv4Address;
+
+/// An OSPFv2 packet.
+///
+/// Hello and database description packets, and the LSA headers of link
+/// state acknowledgments, are dissected. The bodies of link state requests
+/// and updates are kept as raw bytes.
+#[derive(Debug, Clone)]
+pub struct Ospf {
+    base: BasePdu,
+    version: u8,
+    length: u16,
+    router_id: Ipv4Address,
+    area_id: Ipv4Address,
+    chksum: u16,
+    auth_type: u16,
+    auth: [u8; 8],
+    body: Body,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PacketType(pub u8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+    Hello(Hello),
+    DbDescription(DbDescription),
+    LsAck(Vec<LsaHeader>),
+    /// Link state requests, updates, and unknown packet types
+    Other {
+        packet_type: PacketType,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub network_mask: Ipv4Address,
+    pub hello_interval: u16,
+    pub options: u8,
+    pub priority: u8,
+    pub dead_interval: u32,
+    pub designated_router: Ipv4Address,
+    pub backup_designated_router: Ipv4Address,
+    pub neighbors: Vec<Ipv4Address>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbDescription {
+    pub mtu: u16,
+    pub options: u8,
+    /// The I, M, and MS bits
+    pub flags: u8,
+    pub seq: u32,
+    pub lsa_headers: Vec<LsaHeader>,
+}
+
+/// The header of a link state advertisement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LsaHeader {
+    pub age: u16,
+    pub options: u8,
+    pub ls_type: u8,
+    pub ls_id: Ipv4Address,
+    pub advertising_router: Ipv4Address,
+    pub seq: u32,
+    pub checksum: u16,
+    pub length: u16,
+}
+
+impl PacketType {
+    pub const HELLO: Self = Self(1);
+    pub const DB_DESCRIPTION: Self = Self(2);
+    pub const LS_REQUEST: Self = Self(3);
+    pub const LS_UPDATE: Self = Self(4);
+    pub const LS_ACK: Self = Self(5);
+}
+
+impl std::fmt::Display for PacketType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::HELLO => f.write_str("Hello"),
+            Self::DB_DESCRIPTION => f.write_str("Database Description"),
+            Self::LS_REQUEST => f.write_str("Link State Request"),
+            Self::LS_UPDATE => f.write_str("Link State Update"),
+            Self::LS_ACK => f.write_str("Link State Acknowledgment"),
+            Self(val) => write!(f, "Unknown ({val})"),
+        }
+    }
+}
+
+impl LsaHeader {
+    fn decode<'a>(cur: &mut DecodeCursor<'a>) -> DecodeResult<'a, Self> {
+        Ok(Self {
+            age: cur.decode_be()?,
+            options: cur.decode()?,
+            ls_type: cur.decode()?,
+            ls_id: cur.decode()?,
+            advertising_router: cur.decode()?,
+            seq: cur.decode_be()?,
+            checksum: cur.decode_be()?,
+            length: cur.decode_be()?,
+        })
+    }
+
+    fn decode_all(mut cur: DecodeCursor<'_>) -> DecodeResult<'_, Vec<Self>> {
+        // The body length comes from the OSPF header, so a partial LSA
+        // header is malformed rather than incomplete
+        if !cur.len().is_multiple_of(20) {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let mut hdrs = Vec::new();
+        while !cur.is_empty() {
+            hdrs.push(Self::decode(&mut cur)?);
+        }
+        Ok(hdrs)
+    }
+
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> std::io::Result<()> {
+        encoder
+            .encode_be(&self.age)?
+            .encode(&self.options)?
+            .encode(&self.ls_type)?
+            .encode(&self.ls_id)?
+            .encode(&self.advertising_router)?
+            .encode_be(&self.seq)?
+            .encode_be(&self.checksum)?
+            .encode_be(&self.length)?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(
+        &self,
+        node: &mut NodeDumper<D>,
+        offset: usize,
+    ) -> Result<(), D::Error> {
+        let mut node = node
+            .byte_range(offset, 20)
+            .add_node("LSA Header", Some(&self.ls_id.to_string()[..]))?;
+        node.byte_range(offset, 2)
+            .add_field("Age", DumpValue::UInt(self.age.into()), None)?;
+        node.byte_range(offset + 2, 1).add_field(
+            "Options",
+            DumpValue::UInt(self.options.into()),
+            None,
+        )?;
+        node.byte_range(offset + 3, 1).add_field(
+            "Type",
+            DumpValue::UInt(self.ls_type.into()),
+            None,
+        )?;
+        node.byte_range(offset + 4, 4).add_field(
+            "Link State ID",
+            DumpValue::Bytes(&self.ls_id[..]),
+            Some(&self.ls_id.to_string()[..]),
+        )?;
+        node.byte_range(offset + 8, 4).add_field(
+            "Advertising Router",
+            DumpValue::Bytes(&self.advertising_router[..]),
+            Some(&self.advertising_router.to_string()[..]),
+        )?;
+        node.byte_range(offset + 12, 4).add_field(
+            "Sequence Number",
+            DumpValue::UInt(self.seq.into()),
+            None,
+        )?;
+        node.byte_range(offset + 16, 2).add_field(
+            "Checksum",
+            DumpValue::UInt(self.checksum.into()),
+            Some(&format!("0x{:04x}", self.checksum)[..]),
+        )?;
+        node.byte_range(offset + 18, 2).add_field(
+            "Length",
+            DumpValue::UInt(self.length.into()),
+            None,
+        )
+    }
+}
+
+impl Body {
+    pub fn packet_type(&self) -> PacketType {
+        match self {
+            Self::Hello(_) => PacketType::HELLO,
+            Self::DbDescription(_) => PacketType::DB_DESCRIPTION,
+            Self::LsAck(_) => PacketType::LS_ACK,
+            Self::Other { packet_type, .. } => *packet_type,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Hello(hello) => 20 + 4 * hello.neighbors.len(),
+            Self::DbDescription(dd) => 8 + 20 * dd.lsa_headers.len(),
+            Self::LsAck(hdrs) => 20 * hdrs.len(),
+            Self::Other { data, .. } => data.len(),
+        }
+    }
+
+    fn decode(packet_type: PacketType, mut cur: DecodeCursor<'_>) -> DecodeResult<'_, Self> {
+        Ok(match packet_type {
+            PacketType::HELLO => {
+                let mut hello = Hello {
+                    network_mask: cur.decode()?,
+                    hello_interval: cur.decode_be()?,
+                    options: cur.decode()?,
+                    priority: cur.decode()?,
+                    dead_interval: cur.decode_be()?,
+                    designated_router: cur.decode()?,
+                    backup_designated_router: cur.decode()?,
+                    neighbors: Vec::new(),
+                };
+                if !cur.len().is_multiple_of(4) {
+                    return Err(nom::Err::Error(DissectError::Malformed));
+                }
+                while !cur.is_empty() {
+                    hello.neighbors.push(cur.decode()?);
+                }
+                Self::Hello(hello)
+            }
+            PacketType::DB_DESCRIPTION => Self::DbDescription(DbDescription {
+                mtu: cur.decode_be()?,
+                options: cur.decode()?,
+                flags: cur.decode()?,
+                seq: cur.decode_be()?,
+                lsa_headers: LsaHeader::decode_all(cur)?,
+            }),
+            PacketType::LS_ACK => Self::LsAck(LsaHeader::decode_all(cur)?),
+            packet_type => Self::Other {
+                packet_type,
+                data: Vec::from(cur.rest()),
+            },
+        })
+    }
+
+    fn encode<'a, W: Encoder<'a> + ?Sized>(&self, encoder: &mut W) -> std::io::Result<()> {
+        match self {
+            Self::Hello(hello) => {
+                encoder
+                    .encode(&hello.network_mask)?
+                    .encode_be(&hello.hello_interval)?
+                    .encode(&hello.options)?
+                    .encode(&hello.priority)?
+                    .encode_be(&hello.dead_interval)?
+                    .encode(&hello.designated_router)?
+                    .encode(&hello.backup_designated_router)?;
+                for neighbor in hello.neighbors.iter() {
+                    encoder.encode(neighbor)?;
+                }
+            }
+            Self::DbDescription(dd) => {
+                encoder
+                    .encode_be(&dd.mtu)?
+                    .encode(&dd.options)?
+                    .encode(&dd.flags)?
+                    .encode_be(&dd.seq)?;
+                for hdr in dd.lsa_headers.iter() {
+                    hdr.encode(encoder)?;
+                }
+            }
+            Self::LsAck(hdrs) => {
+                for hdr in hdrs.iter() {
+                    hdr.encode(encoder)?;
+                }
+            }
+            Self::Other { data, .. } => {
+                encoder.encode(&data[..])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Ospf {
+    pub fn new(router_id: Ipv4Address, area_id: Ipv4Address, body: Body) -> Self {
+        let mut ospf = Self {
+            base: BasePdu::default(),
+            version: 2,
+            length: 0,
+            router_id,
+            area_id,
+            chksum: 0,
+            auth_type: 0,
+            auth: [0; 8],
+            body,
+        };
+        ospf.update_length();
+        ospf
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    pub fn packet_type(&self) -> PacketType {
+        self.body.packet_type()
+    }
+
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn length_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.length
+    }
+
+    pub fn update_length(&mut self) {
+        self.base.mark_dirty();
+        self.length = (24 + self.body.len()).try_into().unwrap_or(0xFFFF);
+    }
+
+    pub fn router_id(&self) -> Ipv4Address {
+        self.router_id
+    }
+
+    pub fn router_id_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
+        &mut self.router_id
+    }
+
+    pub fn area_id(&self) -> Ipv4Address {
+        self.area_id
+    }
+
+    pub fn area_id_mut(&mut self) -> &mut Ipv4Address {
+        self.base.mark_dirty();
+        &mut self.area_id
+    }
+
+    pub fn checksum(&self) -> u16 {
+        self.chksum
+    }
+
+    pub fn checksum_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.chksum
+    }
+
+    pub fn auth_type(&self) -> u16 {
+        self.auth_type
+    }
+
+    pub fn auth_type_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.auth_type
+    }
+
+    pub fn auth(&self) -> [u8; 8] {
+        self.auth
+    }
+
+    pub fn auth_mut(&mut self) -> &mut [u8; 8] {
+        self.base.mark_dirty();
+        &mut self.auth
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Body {
+        self.base.mark_dirty();
+        &mut self.body
+    }
+
+    /// The LSA headers of a database description or link state
+    /// acknowledgment.
+    pub fn lsa_headers(&self) -> &[LsaHeader] {
+        match &self.body {
+            Body::DbDescription(dd) => &dd.lsa_headers[..],
+            Body::LsAck(hdrs) => &hdrs[..],
+            _ => &[],
+        }
+    }
+
+    /// Computes the checksum, which covers the whole packet except the
+    /// authentication data.
+    pub fn calc_checksum(&self) -> u16 {
+        let mut tmp = self.clone();
+        tmp.chksum = 0;
+        let mut buf = Vec::new();
+        let _ = tmp.serialize_header(&mut buf);
+        let mut acc = U16OnesComplement::new();
+        let _ = acc
+            .encode(&buf[..16])
+            .and_then(|acc| acc.encode(&buf[24..]));
+        acc.checksum()
+    }
+
+    /// Checks the checksum, or returns `None` if cryptographic
+    /// authentication is used, which replaces the checksum.
+    pub fn checksum_valid(&self) -> Option<bool> {
+        if self.auth_type == 2 {
+            None
+        } else {
+            Some(self.chksum == self.calc_checksum())
+        }
+    }
+
+    pub fn update_checksum(&mut self) {
+        self.base.mark_dirty();
+        self.chksum = self.calc_checksum();
+    }
+}
+
+impl Dissect for Ospf {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let mut cur = DecodeCursor::new(buf);
+        let version: u8 = cur.decode()?;
+        let packet_type = PacketType(cur.decode()?);
+        let length: u16 = cur.decode_be()?;
+        if version != 2 || length < 24 {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let router_id = cur.decode()?;
+        let area_id = cur.decode()?;
+        let chksum = cur.decode_be()?;
+        let auth_type = cur.decode_be()?;
+        let auth = cur.decode()?;
+        let body = cur.sub(length as usize - 24)?;
+        let body = Body::decode(packet_type, body)?;
+        Ok((
+            cur.remaining(),
+            Self {
+                base: BasePdu::default(),
+                version,
+                length,
+                router_id,
+                area_id,
+                chksum,
+                auth_type,
+                auth,
+                body,
+            },
+        ))
+    }
+}
+
+impl Pdu for Ospf {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        24 + self.body.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode(&self.version)?
+            .encode(&self.packet_type().0)?
+            .encode_be(&self.length)?
+            .encode(&self.router_id)?
+            .encode(&self.area_id)?
+            .encode_be(&self.chksum)?
+            .encode_be(&self.auth_type)?
+            .encode(&self.auth[..])?;
+        self.body.encode(encoder)
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let packet_type = self.packet_type().to_string();
+        let mut node = dumper.add_node("OSPF", Some(&packet_type[..]))?;
+        node.byte_range(0, 1)
+            .add_field("Version", DumpValue::UInt(self.version.into()), None)?;
+        node.byte_range(1, 1).add_field(
+            "Packet Type",
+            DumpValue::UInt(self.packet_type().0.into()),
+            Some(&packet_type[..]),
+        )?;
+        node.byte_range(2, 2)
+            .add_field("Length", DumpValue::UInt(self.length.into()), None)?;
+        node.byte_range(4, 4).add_field(
+            "Router ID",
+            DumpValue::Bytes(&self.router_id[..]),
+            Some(&self.router_id.to_string()[..]),
+        )?;
+        node.byte_range(8, 4).add_field(
+            "Area ID",
+            DumpValue::Bytes(&self.area_id[..]),
+            Some(&self.area_id.to_string()[..]),
+        )?;
+        node.byte_range(12, 2).add_field(
+            "Checksum",
+            DumpValue::UInt(self.chksum.into()),
+            Some(&format!("0x{:04x}", self.chksum)[..]),
+        )?;
+        node.byte_range(14, 2).add_field(
+            "Authentication Type",
+            DumpValue::UInt(self.auth_type.into()),
+            None,
+        )?;
+        node.byte_range(16, 8).add_field(
+            "Authentication",
+            DumpValue::Bytes(&self.auth[..]),
+            None,
+        )?;
+        match &self.body {
+            Body::Hello(hello) => {
+                let mut hello_node = node
+                    .byte_range(24, self.body.len())
+                    .add_node("Hello", None)?;
+                hello_node.byte_range(24, 4).add_field(
+                    "Network Mask",
+                    DumpValue::Bytes(&hello.network_mask[..]),
+                    Some(&hello.network_mask.to_string()[..]),
+                )?;
+                hello_node.byte_range(28, 2).add_field(
+                    "Hello Interval",
+                    DumpValue::UInt(hello.hello_interval.into()),
+                    None,
+                )?;
+                hello_node.byte_range(30, 1).add_field(
+                    "Options",
+                    DumpValue::UInt(hello.options.into()),
+                    None,
+                )?;
+                hello_node.byte_range(31, 1).add_field(
+                    "Router Priority",
+                    DumpValue::UInt(hello.priority.into()),
+                    None,
+                )?;
+                hello_node.byte_range(32, 4).add_field(
+                    "Dead Interval",
+                    DumpValue::UInt(hello.dead_interval.into()),
+                    None,
+                )?;
+                hello_node.byte_range(36, 4).add_field(
+                    "Designated Router",
+                    DumpValue::Bytes(&hello.designated_router[..]),
+                    Some(&hello.designated_router.to_string()[..]),
+                )?;
+                hello_node.byte_range(40, 4).add_field(
+                    "Backup Designated Router",
+                    DumpValue::Bytes(&hello.backup_designated_router[..]),
+                    Some(&hello.backup_designated_router.to_string()[..]),
+                )?;
+                for (i, neighbor) in hello.neighbors.iter().enumerate() {
+                    hello_node.byte_range(44 + 4 * i, 4).add_field(
+                        "Neighbor",
+                        DumpValue::Bytes(&neighbor[..]),
+                        Some(&neighbor.to_string()[..]),
+                    )?;
+                }
+            }
+            Body::DbDescription(dd) => {
+                let mut dd_node = node
+                    .byte_range(24, self.body.len())
+                    .add_node("Database Description", None)?;
+                dd_node.byte_range(24, 2).add_field(
+                    "Interface MTU",
+                    DumpValue::UInt(dd.mtu.into()),
+                    None,
+                )?;
+                dd_node.byte_range(26, 1).add_field(
+                    "Options",
+                    DumpValue::UInt(dd.options.into()),
+                    None,
+                )?;
+                dd_node.byte_range(27, 1).add_field(
+                    "Flags",
+                    DumpValue::UInt(dd.flags.into()),
+                    None,
+                )?;
+                dd_node.byte_range(28, 4).add_field(
+                    "Sequence Number",
+                    DumpValue::UInt(dd.seq.into()),
+                    None,
+                )?;
+                for (i, hdr) in dd.lsa_headers.iter().enumerate() {
+                    hdr.dump(&mut dd_node, 32 + 20 * i)?;
+                }
+            }
+            Body::LsAck(hdrs) => {
+                for (i, hdr) in hdrs.iter().enumerate() {
+                    hdr.dump(&mut node, 24 + 20 * i)?;
+                }
+            }
+            Body::Other { data, .. } => {
+                node.byte_range(24, data.len()).add_field(
+                    "Data",
+                    DumpValue::Bytes(&data[..]),
+                    None,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_length();
+        if self.auth_type != 2 {
+            self.update_checksum();
+        }
+    }
+
+    fn validate(&self, validator: &mut Validator<'_>) {
+        if let Some(valid) = self.checksum_valid() {
+            validator.checksum("OSPF", self.chksum, valid);
+        }
+    }
+}
+
+register_dissector!(
+    ospf,
+    IpProtoDissectorTable,
+    IpProto::OSPFIGP,
+    Priority(0),
+    Ospf::dissect
+);
+crate::register_ip_proto_pdu!(Ospf, IpProto::OSPFIGP);
+register_field!(ospf_router_id, "ospf.router_id" => "OSPF.Router ID");
+register_field!(ospf_area_id, "ospf.area_id" => "OSPF.Area ID");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipv4::Ipv4;
+
+    #[test]
+    fn ospf_hello() {
+        let hello = Hello {
+            network_mask: [255, 255, 255, 0].into(),
+            hello_interval: 10,
+            options: 0x02,
+            priority: 1,
+            dead_interval: 40,
+            designated_router: [10, 0, 0, 1].into(),
+            backup_designated_router: [0, 0, 0, 0].into(),
+            neighbors: vec![[2, 2, 2, 2].into()],
+        };
+        let ospf = Ospf::new(
+            [1, 1, 1, 1].into(),
+            [0, 0, 0, 0].into(),
+            Body::Hello(hello.clone()),
+        );
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [224, 0, 0, 5].into());
+        ipv4.set_inner_pdu(ospf);
+        ipv4.make_all_canonical();
+
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+        let session = Session::new();
+        let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        let ospf = ipv4.find::<Ospf>().unwrap();
+        assert_eq!(ospf.length(), 48);
+        assert_eq!(ospf.router_id(), [1, 1, 1, 1].into());
+        assert_eq!(ospf.checksum_valid(), Some(true));
+        assert_eq!(ospf.body(), &Body::Hello(hello));
+    }
+
+    #[test]
+    fn ospf_db_description() {
+        let lsa = LsaHeader {
+            age: 1,
+            options: 0x22,
+            ls_type: 1,
+            ls_id: [1, 1, 1, 1].into(),
+            advertising_router: [1, 1, 1, 1].into(),
+            seq: 0x80000001,
+            checksum: 0xabcd,
+            length: 36,
+        };
+        let mut ospf = Ospf::new(
+            [2, 2, 2, 2].into(),
+            [0, 0, 0, 0].into(),
+            Body::DbDescription(DbDescription {
+                mtu: 1500,
+                options: 0x42,
+                flags: 0x03,
+                seq: 1234,
+                lsa_headers: vec![lsa, lsa],
+            }),
+        );
+        ospf.update_checksum();
+
+        let mut buf = Vec::new();
+        ospf.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 72);
+        let session = Session::new();
+        let (rem, ospf) = Ospf::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(ospf.packet_type(), PacketType::DB_DESCRIPTION);
+        assert_eq!(ospf.lsa_headers(), &[lsa, lsa][..]);
+        assert_eq!(ospf.checksum_valid(), Some(true));
+
+        // A truncated LSA header is malformed
+        buf[3] -= 4;
+        assert!(Ospf::dissect(&buf[..68], &session, None).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Tags TCP and UDP payloads that no other dissector recognized with a
+//! coarse content class (text, binary, compressed or encrypted) and their
+//! entropy.
+//!
+//! Classification is off by default, since it replaces the `RawPdu` those
+//! payloads would otherwise be dissected as. Enable it by registering
+//! `ClassifyPayloads` with the session.
+
+use crate::prelude::*;
+use crate::tcp::HeurDissectorTable as TcpHeurDissectorTable;
+use crate::udp::HeurDissectorTable as UdpHeurDissectorTable;
+use nom::combinator::rest;
+use utils::entropy::{self, ContentClass};
+
+/// Session option that enables the payload classification heuristic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClassifyPayloads;
+
+/// A TCP or UDP payload that was not recognized by any dissector.
+#[derive(Debug, Clone)]
+pub struct ClassifiedPayload {
+    base: BasePdu,
+    data: Bytes,
+    class: ContentClass,
+    entropy: f64,
+}
+
+impl ClassifiedPayload {
+    pub fn new<B: Into<Bytes>>(data: B) -> Self {
+        let data = data.into();
+        let class = entropy::classify(&data[..]);
+        let entropy = entropy::entropy(&data[..]);
+        Self {
+            base: BasePdu::default(),
+            data,
+            class,
+            entropy,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    pub fn class(&self) -> ContentClass {
+        self.class
+    }
+
+    /// Shannon entropy of the payload in bits per byte.
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    fn dissect_heuristic<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        if session.get::<ClassifyPayloads>().is_none() {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        Self::dissect(buf, session, None)
+    }
+}
+
+impl Dissect for ClassifiedPayload {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (rem, data) = rest(buf)?;
+        Ok((rem, Self::new(session.packet_bytes(data))))
+    }
+}
+
+impl Pdu for ClassifiedPayload {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.data[..]).map(|_| ())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("Payload", Some(self.class.name()))?;
+        node.add_field("Class", DumpValue::Text(self.class.name()), None)?;
+        node.add_field(
+            "Entropy",
+            DumpValue::Float(self.entropy),
+            Some(&format!("{:.2} bits/byte", self.entropy)[..]),
+        )?;
+        node.byte_range(0, self.data.len()).add_field(
+            "Data",
+            DumpValue::Bytes(&self.data[..]),
+            None,
+        )
+    }
+}
+
+// Lowest priority, so that every other heuristic gets a chance first
+register_dissector!(
+    classified_payload_tcp,
+    TcpHeurDissectorTable,
+    (),
+    Priority(i32::MIN),
+    ClassifiedPayload::dissect_heuristic
+);
+
+register_dissector!(
+    classified_payload_udp,
+    UdpHeurDissectorTable,
+    (),
+    Priority(i32::MIN),
+    ClassifiedPayload::dissect_heuristic
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::udp::Udp;
+
+    fn dissect_udp(session: &Session, payload: &[u8]) -> Udp {
+        let mut data = vec![0xc3, 0x50, 0xc3, 0x51, 0x00, 0x00, 0x00, 0x00];
+        data[5] = (8 + payload.len()) as u8;
+        data.extend_from_slice(payload);
+        Udp::dissect(&data[..], session, None).unwrap().1
+    }
+
+    #[test]
+    fn classify_udp_payload() {
+        let payload = b"hello, unrecognized protocol\n";
+
+        let session = Session::new();
+        let udp = dissect_udp(&session, payload);
+        assert!(udp.find::<ClassifiedPayload>().is_none());
+        assert!(udp.find::<RawPdu>().is_some());
+
+        let session = Session::builder().state(ClassifyPayloads).build();
+        let udp = dissect_udp(&session, payload);
+        let classified = udp.find::<ClassifiedPayload>().unwrap();
+        assert_eq!(classified.class(), ContentClass::Text);
+        assert_eq!(classified.data(), &payload[..]);
+        assert!(classified.entropy() > 3.0);
+        assert_eq!(udp.total_len(), 8 + payload.len());
+    }
+}
@@ -2,9 +2,11 @@
 
 pub use nom::{self, Parser};
 pub use sniffle_core::{
-    dissector_table, register_dissector, register_dissector_table, register_link_layer_pdu, AnyPdu,
-    BasePdu, DResult, Dissect, DissectError, Dump, DumpValue, LinkType, LinkTypeTable, ListDumper,
-    NodeDumper, Pdu, PduExt, PduType, Priority, RawPdu, Session, TempPdu,
+    decode_with, dissector_table, register_conversation, register_dissector,
+    register_dissector_table, register_field, register_link_layer_pdu, AnyPdu, BasePdu, Bytes,
+    DResult, DecodeCursor, DecodeResult, Dissect, DissectError, Dump, DumpValue, LinkType,
+    LinkTypeTable, ListDumper, NodeDumper, Pdu, PduExt, PduType, Priority, RawPdu, Session,
+    TempPdu, Validator,
 };
 pub use sniffle_ende::{
     decode::{Decode, DecodeBe, DecodeLe},
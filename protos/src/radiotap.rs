@@ -0,0 +1,743 @@
+use crate::prelude::*;
+use checksum::Crc32;
+use nom::combinator::map;
+
+/// Radiotap capture header (`LinkType::IEEE802_11_RADIOTAP`).
+///
+/// Fields are described by the present bitmaps and follow them in bit
+/// order, each aligned to its natural alignment relative to the start of the
+/// header. Fields in the default radiotap namespace and the TLV list are
+/// parsed. Anything following a vendor namespace or a field of unknown size
+/// is kept as unparsed bytes.
+#[derive(Debug, Clone)]
+pub struct Radiotap {
+    base: BasePdu,
+    version: u8,
+    pad: u8,
+    length: u16,
+    present: Vec<u32>,
+    fields: Vec<Field>,
+    tlvs: Vec<Tlv>,
+    unparsed: Bytes,
+    fcs: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// Which repetition of the radiotap namespace the field belongs to.
+    /// Fields in the first present bitmap are in namespace 0.
+    pub namespace: usize,
+    pub kind: FieldKind,
+    pub data: Bytes,
+}
+
+/// A field's bit index in the present bitmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FieldKind(pub u8);
+
+/// An entry in the TLV list that follows the fields when the `TLV` bit is
+/// present
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tlv {
+    pub tlv_type: u16,
+    pub data: Bytes,
+}
+
+/// Values of the `FLAGS` field
+pub mod flags {
+    pub const CFP: u8 = 0x01;
+    pub const SHORT_PREAMBLE: u8 = 0x02;
+    pub const WEP: u8 = 0x04;
+    pub const FRAGMENTATION: u8 = 0x08;
+    /// The frame ends with a 4 byte FCS
+    pub const FCS: u8 = 0x10;
+    pub const DATA_PAD: u8 = 0x20;
+    pub const BAD_FCS: u8 = 0x40;
+    pub const SHORT_GI: u8 = 0x80;
+}
+
+const RADIOTAP_NAMESPACE: u32 = 1 << 29;
+const VENDOR_NAMESPACE: u32 = 1 << 30;
+const EXT: u32 = 1 << 31;
+
+impl FieldKind {
+    pub const TSFT: Self = Self(0);
+    pub const FLAGS: Self = Self(1);
+    pub const RATE: Self = Self(2);
+    pub const CHANNEL: Self = Self(3);
+    pub const FHSS: Self = Self(4);
+    pub const ANTENNA_SIGNAL: Self = Self(5);
+    pub const ANTENNA_NOISE: Self = Self(6);
+    pub const LOCK_QUALITY: Self = Self(7);
+    pub const TX_ATTENUATION: Self = Self(8);
+    pub const DB_TX_ATTENUATION: Self = Self(9);
+    pub const DBM_TX_POWER: Self = Self(10);
+    pub const ANTENNA: Self = Self(11);
+    pub const DB_ANTENNA_SIGNAL: Self = Self(12);
+    pub const DB_ANTENNA_NOISE: Self = Self(13);
+    pub const RX_FLAGS: Self = Self(14);
+    pub const TX_FLAGS: Self = Self(15);
+    pub const RTS_RETRIES: Self = Self(16);
+    pub const DATA_RETRIES: Self = Self(17);
+    pub const XCHANNEL: Self = Self(18);
+    pub const MCS: Self = Self(19);
+    pub const AMPDU_STATUS: Self = Self(20);
+    pub const VHT: Self = Self(21);
+    pub const TIMESTAMP: Self = Self(22);
+    pub const HE: Self = Self(23);
+    pub const HE_MU: Self = Self(24);
+    pub const HE_MU_OTHER_USER: Self = Self(25);
+    pub const ZERO_LEN_PSDU: Self = Self(26);
+    pub const L_SIG: Self = Self(27);
+    pub const TLV: Self = Self(28);
+
+    /// Alignment and size of the field, if known
+    pub fn layout(self) -> Option<(usize, usize)> {
+        Some(match self.0 {
+            0 => (8, 8),
+            1 | 2 | 5 | 6 | 10 | 11 | 12 | 13 | 16 | 17 | 26 => (1, 1),
+            3 | 27 => (2, 4),
+            4 | 7 | 8 | 9 | 14 | 15 => (2, 2),
+            18 | 20 => (4, 8),
+            19 => (1, 3),
+            21 | 23 | 24 => (2, 12),
+            22 => (8, 12),
+            25 => (2, 6),
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Self::TSFT => "TSFT",
+            Self::FLAGS => "Flags",
+            Self::RATE => "Rate",
+            Self::CHANNEL => "Channel",
+            Self::FHSS => "FHSS",
+            Self::ANTENNA_SIGNAL => "Antenna Signal",
+            Self::ANTENNA_NOISE => "Antenna Noise",
+            Self::LOCK_QUALITY => "Lock Quality",
+            Self::TX_ATTENUATION => "TX Attenuation",
+            Self::DB_TX_ATTENUATION => "dB TX Attenuation",
+            Self::DBM_TX_POWER => "dBm TX Power",
+            Self::ANTENNA => "Antenna",
+            Self::DB_ANTENNA_SIGNAL => "dB Antenna Signal",
+            Self::DB_ANTENNA_NOISE => "dB Antenna Noise",
+            Self::RX_FLAGS => "RX Flags",
+            Self::TX_FLAGS => "TX Flags",
+            Self::RTS_RETRIES => "RTS Retries",
+            Self::DATA_RETRIES => "Data Retries",
+            Self::XCHANNEL => "XChannel",
+            Self::MCS => "MCS",
+            Self::AMPDU_STATUS => "A-MPDU Status",
+            Self::VHT => "VHT",
+            Self::TIMESTAMP => "Timestamp",
+            Self::HE => "HE",
+            Self::HE_MU => "HE-MU",
+            Self::HE_MU_OTHER_USER => "HE-MU Other User",
+            Self::ZERO_LEN_PSDU => "0-Length PSDU",
+            Self::L_SIG => "L-SIG",
+            Self::TLV => "TLV",
+            _ => return None,
+        })
+    }
+}
+
+fn align(off: usize, align: usize) -> usize {
+    off.div_ceil(align) * align
+}
+
+fn le_u16(buf: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(buf.get(off..off + 2)?.try_into().ok()?))
+}
+
+fn le_u32(buf: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(buf.get(off..off + 4)?.try_into().ok()?))
+}
+
+impl Radiotap {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            version: 0,
+            pad: 0,
+            length: 8,
+            present: vec![0],
+            fields: Vec::new(),
+            tlvs: Vec::new(),
+            unparsed: Bytes::new(),
+            fcs: None,
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn length_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.length
+    }
+
+    pub fn update_length(&mut self) {
+        self.base.mark_dirty();
+        self.length = self.encode_header().len() as u16;
+    }
+
+    pub fn present(&self) -> &[u32] {
+        &self.present[..]
+    }
+
+    pub fn present_mut(&mut self) -> &mut Vec<u32> {
+        self.base.mark_dirty();
+        &mut self.present
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields[..]
+    }
+
+    pub fn fields_mut(&mut self) -> &mut Vec<Field> {
+        self.base.mark_dirty();
+        &mut self.fields
+    }
+
+    pub fn tlvs(&self) -> &[Tlv] {
+        &self.tlvs[..]
+    }
+
+    pub fn tlvs_mut(&mut self) -> &mut Vec<Tlv> {
+        self.base.mark_dirty();
+        &mut self.tlvs
+    }
+
+    /// Header bytes that could not be parsed into fields or TLVs
+    pub fn unparsed(&self) -> &[u8] {
+        &self.unparsed[..]
+    }
+
+    /// Returns the data of a field in the first radiotap namespace
+    pub fn field(&self, kind: FieldKind) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .find(|field| field.namespace == 0 && field.kind == kind)
+            .map(|field| &field.data[..])
+    }
+
+    /// Adds or replaces a field in the first radiotap namespace and sets its
+    /// present bit. `data` is expected to be the field's size.
+    pub fn set_field<B: Into<Bytes>>(&mut self, kind: FieldKind, data: B) {
+        self.base.mark_dirty();
+        let data = data.into();
+        self.present[0] |= 1 << kind.0;
+        let pos = self
+            .fields
+            .iter()
+            .position(|field| field.namespace > 0 || field.kind >= kind)
+            .unwrap_or(self.fields.len());
+        match self.fields.get_mut(pos) {
+            Some(field) if field.namespace == 0 && field.kind == kind => field.data = data,
+            _ => self.fields.insert(
+                pos,
+                Field {
+                    namespace: 0,
+                    kind,
+                    data,
+                },
+            ),
+        }
+    }
+
+    /// Removes a field from the first radiotap namespace
+    pub fn remove_field(&mut self, kind: FieldKind) {
+        self.base.mark_dirty();
+        self.present[0] &= !(1 << kind.0);
+        self.fields
+            .retain(|field| field.namespace != 0 || field.kind != kind);
+    }
+
+    pub fn tsft(&self) -> Option<u64> {
+        Some(u64::from_le_bytes(
+            self.field(FieldKind::TSFT)?.try_into().ok()?,
+        ))
+    }
+
+    pub fn flags(&self) -> Option<u8> {
+        self.field(FieldKind::FLAGS)?.first().copied()
+    }
+
+    /// Data rate in units of 500 kbps
+    pub fn rate(&self) -> Option<u8> {
+        self.field(FieldKind::RATE)?.first().copied()
+    }
+
+    /// Channel frequency in MHz and channel flags
+    pub fn channel(&self) -> Option<(u16, u16)> {
+        let data = self.field(FieldKind::CHANNEL)?;
+        Some((le_u16(data, 0)?, le_u16(data, 2)?))
+    }
+
+    /// Antenna signal in dBm
+    pub fn antenna_signal(&self) -> Option<i8> {
+        self.field(FieldKind::ANTENNA_SIGNAL)?
+            .first()
+            .map(|val| *val as i8)
+    }
+
+    /// Antenna noise in dBm
+    pub fn antenna_noise(&self) -> Option<i8> {
+        self.field(FieldKind::ANTENNA_NOISE)?
+            .first()
+            .map(|val| *val as i8)
+    }
+
+    pub fn antenna(&self) -> Option<u8> {
+        self.field(FieldKind::ANTENNA)?.first().copied()
+    }
+
+    /// The frame check sequence following the 802.11 frame, if the `FCS`
+    /// flag is set
+    pub fn fcs(&self) -> Option<u32> {
+        self.fcs
+    }
+
+    pub fn fcs_mut(&mut self) -> &mut Option<u32> {
+        self.base.mark_dirty();
+        &mut self.fcs
+    }
+
+    /// Computes the FCS over the 802.11 frame.
+    pub fn calc_fcs(&self) -> u32 {
+        let mut crc = Crc32::new();
+        if let Some(inner) = self.inner_pdu() {
+            let _ = inner.serialize(&mut crc);
+        }
+        crc.checksum()
+    }
+
+    /// Checks the FCS against the frame contents, or returns `None` if there
+    /// is no FCS.
+    pub fn fcs_valid(&self) -> Option<bool> {
+        self.fcs.map(|fcs| fcs == self.calc_fcs())
+    }
+
+    /// Recomputes the FCS, if there is one.
+    pub fn update_fcs(&mut self) {
+        self.base.mark_dirty();
+        if self.fcs.is_some() {
+            self.fcs = Some(self.calc_fcs());
+        }
+    }
+
+    fn parse_header(&mut self, hdr: &[u8], session: &Session) -> Option<()> {
+        let mut off = 4;
+        loop {
+            let word = le_u32(hdr, off)?;
+            off += 4;
+            self.present.push(word);
+            if word & EXT == 0 {
+                break;
+            }
+        }
+
+        let mut namespace = 0;
+        let mut word_idx = 0;
+        let mut has_tlvs = false;
+        'words: for word in self.present.iter().copied() {
+            for bit in 0..29u8 {
+                if word & (1 << bit) == 0 {
+                    continue;
+                }
+                let kind = FieldKind(bit);
+                let layout = if word_idx == 0 { kind.layout() } else { None };
+                if kind == FieldKind::TLV && word_idx == 0 {
+                    has_tlvs = true;
+                    continue;
+                }
+                let Some((field_align, size)) = layout else {
+                    break 'words;
+                };
+                let start = align(off, field_align);
+                let Some(data) = hdr.get(start..start + size) else {
+                    break 'words;
+                };
+                self.fields.push(Field {
+                    namespace,
+                    kind,
+                    data: session.packet_bytes(data),
+                });
+                off = start + size;
+            }
+            if word & VENDOR_NAMESPACE != 0 {
+                break;
+            } else if word & RADIOTAP_NAMESPACE != 0 {
+                namespace += 1;
+                word_idx = 0;
+            } else {
+                word_idx += 1;
+            }
+        }
+
+        if has_tlvs && off < hdr.len() {
+            off = align(off, 4);
+            while off + 4 <= hdr.len() {
+                let tlv_type = le_u16(hdr, off)?;
+                let len = le_u16(hdr, off + 2)? as usize;
+                let Some(data) = hdr.get(off + 4..off + 4 + len) else {
+                    break;
+                };
+                self.tlvs.push(Tlv {
+                    tlv_type,
+                    data: session.packet_bytes(data),
+                });
+                off = align(off + 4 + len, 4).min(hdr.len());
+            }
+        }
+
+        self.unparsed = session.packet_bytes(hdr.get(off..).unwrap_or(&[]));
+        Some(())
+    }
+
+    fn encode_header(&self) -> Vec<u8> {
+        let mut hdr = vec![self.version, self.pad];
+        hdr.extend_from_slice(&self.length.to_le_bytes());
+        for word in self.present.iter() {
+            hdr.extend_from_slice(&word.to_le_bytes());
+        }
+        for field in self.fields.iter() {
+            let field_align = field.kind.layout().map(|(align, _)| align).unwrap_or(1);
+            hdr.resize(align(hdr.len(), field_align), 0);
+            hdr.extend_from_slice(&field.data[..]);
+        }
+        if !self.tlvs.is_empty() {
+            hdr.resize(align(hdr.len(), 4), 0);
+            for tlv in self.tlvs.iter() {
+                hdr.extend_from_slice(&tlv.tlv_type.to_le_bytes());
+                hdr.extend_from_slice(&(tlv.data.len() as u16).to_le_bytes());
+                hdr.extend_from_slice(&tlv.data[..]);
+                hdr.resize(align(hdr.len(), 4), 0);
+            }
+        }
+        hdr.extend_from_slice(&self.unparsed[..]);
+        hdr
+    }
+}
+
+impl Dissect for Radiotap {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        if buf.len() < 8 {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let length = u16::from_le_bytes([buf[2], buf[3]]);
+        if (length as usize) < 8 || length as usize > buf.len() {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let (hdr, payload) = buf.split_at(length as usize);
+        let mut radiotap = Self {
+            base: BasePdu::default(),
+            version: buf[0],
+            pad: buf[1],
+            length,
+            present: Vec::new(),
+            fields: Vec::new(),
+            tlvs: Vec::new(),
+            unparsed: Bytes::new(),
+            fcs: None,
+        };
+        radiotap
+            .parse_header(hdr, session)
+            .ok_or(nom::Err::Error(DissectError::Malformed))?;
+
+        let has_fcs = radiotap
+            .flags()
+            .map(|val| val & flags::FCS != 0)
+            .unwrap_or(false)
+            && payload.len() >= 4;
+        let (frame, fcs) = payload.split_at(payload.len() - if has_fcs { 4 } else { 0 });
+        let (_, inner) = session
+            .table_dissector::<LinkTypeTable>(
+                &LinkType::IEEE802_11,
+                Some(TempPdu::new(&radiotap, &parent)),
+            )
+            .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+            .parse(frame)?;
+        radiotap.set_inner_pdu(inner);
+        if has_fcs {
+            radiotap.fcs = Some(u32::from_le_bytes([fcs[0], fcs[1], fcs[2], fcs[3]]));
+        }
+        Ok((&fcs[fcs.len()..], radiotap))
+    }
+}
+
+impl Pdu for Radiotap {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.encode_header().len()
+    }
+
+    fn trailer_len(&self) -> usize {
+        self.fcs.map(|_| 4).unwrap_or(0)
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.encode_header()[..])?;
+        Ok(())
+    }
+
+    fn serialize_trailer<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        if let Some(fcs) = self.fcs {
+            encoder.encode_le(&fcs)?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("Radiotap", None)?;
+        node.byte_range(0, 1)
+            .add_field("Version", DumpValue::UInt(self.version.into()), None)?;
+        node.byte_range(2, 2)
+            .add_field("Length", DumpValue::UInt(self.length.into()), None)?;
+        for (i, word) in self.present.iter().enumerate() {
+            node.byte_range(4 + i * 4, 4).add_field(
+                "Present",
+                DumpValue::UInt((*word).into()),
+                Some(&format!("0x{:08x}", word)[..]),
+            )?;
+        }
+        for field in self.fields.iter() {
+            let name = field
+                .kind
+                .name()
+                .map(String::from)
+                .unwrap_or_else(|| format!("Field {}", field.kind.0));
+            let descr = match field.kind {
+                FieldKind::RATE if field.namespace == 0 => {
+                    Some(format!("{} Mbps", f64::from(field.data[0]) / 2.0))
+                }
+                FieldKind::CHANNEL if field.namespace == 0 => {
+                    le_u16(&field.data[..], 0).map(|freq| format!("{} MHz", freq))
+                }
+                FieldKind::ANTENNA_SIGNAL | FieldKind::ANTENNA_NOISE => {
+                    Some(format!("{} dBm", field.data[0] as i8))
+                }
+                _ => None,
+            };
+            node.add_field(&name, DumpValue::Bytes(&field.data[..]), descr.as_deref())?;
+        }
+        for tlv in self.tlvs.iter() {
+            node.add_field(
+                "TLV",
+                DumpValue::Bytes(&tlv.data[..]),
+                Some(&format!("type {}", tlv.tlv_type)[..]),
+            )?;
+        }
+        if !self.unparsed.is_empty() {
+            node.add_field("Unparsed", DumpValue::Bytes(&self.unparsed[..]), None)?;
+        }
+        if let Some(fcs) = self.fcs {
+            let valid = if fcs == self.calc_fcs() {
+                "valid"
+            } else {
+                "invalid"
+            };
+            node.byte_range(self.total_len() - 4, 4).add_field(
+                "FCS",
+                DumpValue::UInt(fcs.into()),
+                Some(&format!("0x{:08x} ({})", fcs, valid)[..]),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_length();
+        self.update_fcs();
+    }
+}
+
+impl Default for Radiotap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_link_layer_pdu!(Radiotap, LinkType::IEEE802_11_RADIOTAP);
+register_dissector!(
+    radiotap,
+    LinkTypeTable,
+    LinkType::IEEE802_11_RADIOTAP,
+    Priority(0),
+    Radiotap::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dissect(buf: &[u8]) -> Option<Radiotap> {
+        let session = Session::new();
+        let (rem, radiotap) = Radiotap::dissect(buf, &session, None).ok()?;
+        assert!(rem.is_empty());
+        Some(radiotap)
+    }
+
+    fn serialize(radiotap: &Radiotap) -> Vec<u8> {
+        let mut buf = Vec::new();
+        radiotap.serialize(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn alignment_padding() {
+        #[rustfmt::skip]
+        let buf = [
+            0, 0, 24, 0,
+            // Flags, Channel, Antenna Signal, XChannel
+            0x2a, 0, 0x04, 0,
+            // Flags, then a byte of padding before the channel
+            flags::SHORT_PREAMBLE, 0,
+            0x6c, 0x09, 0xa0, 0x00,
+            // Antenna signal, then a byte of padding before the 4 byte
+            // aligned XChannel
+            0xd8, 0,
+            0x00, 0x00, 0x00, 0x00, 0x6c, 0x09, 0x01, 0x00,
+            // The frame
+            0x80, 0x00,
+        ];
+        let radiotap = dissect(&buf[..]).unwrap();
+        assert_eq!(radiotap.flags(), Some(flags::SHORT_PREAMBLE));
+        assert_eq!(radiotap.channel(), Some((2412, 0x00a0)));
+        assert_eq!(radiotap.antenna_signal(), Some(-40));
+        assert_eq!(
+            radiotap.field(FieldKind::XCHANNEL),
+            Some(&[0x00, 0x00, 0x00, 0x00, 0x6c, 0x09, 0x01, 0x00][..])
+        );
+        assert!(radiotap.unparsed().is_empty());
+        assert_eq!(radiotap.fcs(), None);
+        assert_eq!(serialize(&radiotap), &buf[..]);
+
+        // Adding a field puts it in bit order, with the padding it needs
+        let mut radiotap = Radiotap::new();
+        radiotap.set_field(FieldKind::TSFT, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        radiotap.set_field(FieldKind::FLAGS, vec![flags::SHORT_PREAMBLE]);
+        radiotap.set_field(FieldKind::RX_FLAGS, vec![0, 0]);
+        radiotap.make_canonical();
+        assert_eq!(radiotap.present(), &[0x4003]);
+        assert_eq!(radiotap.length(), 20);
+        let buf = serialize(&radiotap);
+        assert_eq!(&buf[8..], &[1, 2, 3, 4, 5, 6, 7, 8, 0x02, 0, 0, 0]);
+        assert_eq!(
+            dissect(&buf[..]).unwrap().field(FieldKind::RX_FLAGS),
+            Some(&[0, 0][..])
+        );
+    }
+
+    #[test]
+    fn extended_present() {
+        #[rustfmt::skip]
+        let buf = [
+            0, 0, 24, 0,
+            // Antenna Signal, then another radiotap namespace
+            0x20, 0, 0, 0xa0,
+            // Antenna Signal and Antenna, then a vendor namespace
+            0x20, 0x08, 0, 0xc0,
+            // The vendor namespace bitmap
+            0, 0, 0, 0,
+            // The fields of both radiotap namespaces, then padding
+            0xd8, 0xd0, 1, 0,
+            // Vendor namespace data, left unparsed
+            0x00, 0x11, 0x22, 0x01,
+        ];
+        let radiotap = dissect(&buf[..]).unwrap();
+        assert_eq!(radiotap.present(), &[0xa000_0020, 0xc000_0820, 0]);
+        assert_eq!(
+            radiotap.fields(),
+            &[
+                Field {
+                    namespace: 0,
+                    kind: FieldKind::ANTENNA_SIGNAL,
+                    data: Bytes::from_static(&[0xd8]),
+                },
+                Field {
+                    namespace: 1,
+                    kind: FieldKind::ANTENNA_SIGNAL,
+                    data: Bytes::from_static(&[0xd0]),
+                },
+                Field {
+                    namespace: 1,
+                    kind: FieldKind::ANTENNA,
+                    data: Bytes::from_static(&[1]),
+                },
+            ]
+        );
+        // Only the first namespace is read by the accessors
+        assert_eq!(radiotap.antenna_signal(), Some(-40));
+        assert_eq!(radiotap.antenna(), None);
+        assert_eq!(radiotap.unparsed(), &[0, 0x00, 0x11, 0x22, 0x01]);
+        assert_eq!(serialize(&radiotap), &buf[..]);
+    }
+
+    #[test]
+    fn truncated_header() {
+        // Shorter than the fixed header, or than the length it claims
+        assert!(dissect(&[0, 0, 8, 0, 0, 0]).is_none());
+        assert!(dissect(&[0, 0, 6, 0, 0, 0, 0, 0]).is_none());
+        assert!(dissect(&[0, 0, 12, 0, 0, 0, 0, 0]).is_none());
+        // An extended present bitmap without the next word
+        assert!(dissect(&[0, 0, 10, 0, 0, 0, 0, 0x80, 0, 0]).is_none());
+
+        // A field past the end of the header is left unparsed
+        let radiotap = dissect(&[0, 0, 10, 0, 0x08, 0, 0, 0, 0x6c, 0x09]).unwrap();
+        assert!(radiotap.fields().is_empty());
+        assert_eq!(radiotap.unparsed(), &[0x6c, 0x09]);
+
+        // So is a TLV longer than the header
+        #[rustfmt::skip]
+        let buf = [
+            0, 0, 24, 0,
+            // Antenna and TLVs
+            0x00, 0x08, 0x00, 0x10,
+            1, 0, 0, 0,
+            5, 0, 1, 0, 0xaa, 0, 0, 0,
+            // A TLV of 8 bytes, past the end of the header
+            6, 0, 8, 0,
+        ];
+        let radiotap = dissect(&buf[..]).unwrap();
+        assert_eq!(radiotap.antenna(), Some(1));
+        assert_eq!(
+            radiotap.tlvs(),
+            &[Tlv {
+                tlv_type: 5,
+                data: Bytes::from_static(&[0xaa]),
+            }]
+        );
+        assert_eq!(radiotap.unparsed(), &[6, 0, 8, 0]);
+        assert_eq!(serialize(&radiotap), &buf[..]);
+    }
+}
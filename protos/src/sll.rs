@@ -0,0 +1,307 @@
+use super::ethernet_ii::EthertypeDissectorTable;
+use super::ethertype::Ethertype;
+use crate::prelude::*;
+use nom::{
+    combinator::{flat_map, map},
+    sequence::tuple,
+};
+
+/// Linux cooked capture (`LINKTYPE_LINUX_SLL`) header, as produced when
+/// capturing on the "any" device.
+#[derive(Debug, Clone)]
+pub struct Sll {
+    base: BasePdu,
+    packet_type: PacketType,
+    arphrd_type: u16,
+    addr_len: u16,
+    addr: [u8; 8],
+    protocol: Ethertype,
+    trailer: Vec<u8>,
+}
+
+/// Direction of a packet relative to the capturing host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PacketType(pub u16);
+
+impl PacketType {
+    pub const HOST: Self = Self(0);
+    pub const BROADCAST: Self = Self(1);
+    pub const MULTICAST: Self = Self(2);
+    pub const OTHER_HOST: Self = Self(3);
+    pub const OUTGOING: Self = Self(4);
+}
+
+impl std::fmt::Display for PacketType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::HOST => "Unicast to us",
+            Self::BROADCAST => "Broadcast",
+            Self::MULTICAST => "Multicast",
+            Self::OTHER_HOST => "Unicast to another host",
+            Self::OUTGOING => "Sent by us",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+/// `ARPHRD_ETHER`, the hardware type of Ethernet devices
+pub const ARPHRD_ETHER: u16 = 1;
+
+impl Sll {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            packet_type: PacketType::HOST,
+            arphrd_type: ARPHRD_ETHER,
+            addr_len: 0,
+            addr: [0u8; 8],
+            protocol: Ethertype(0),
+            trailer: Vec::new(),
+        }
+    }
+
+    pub fn packet_type(&self) -> PacketType {
+        self.packet_type
+    }
+
+    pub fn packet_type_mut(&mut self) -> &mut PacketType {
+        self.base.mark_dirty();
+        &mut self.packet_type
+    }
+
+    pub fn arphrd_type(&self) -> u16 {
+        self.arphrd_type
+    }
+
+    pub fn arphrd_type_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.arphrd_type
+    }
+
+    /// The link-layer source address. At most 8 bytes are captured, even if
+    /// the address length field is larger.
+    pub fn address(&self) -> &[u8] {
+        &self.addr[..usize::from(self.addr_len).min(8)]
+    }
+
+    pub fn address_len(&self) -> u16 {
+        self.addr_len
+    }
+
+    pub fn address_len_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.addr_len
+    }
+
+    /// Sets the address and address length. Addresses longer than 8 bytes
+    /// are truncated.
+    pub fn set_address(&mut self, addr: &[u8]) {
+        self.base.mark_dirty();
+        let len = addr.len().min(8);
+        self.addr = [0u8; 8];
+        self.addr[..len].copy_from_slice(&addr[..len]);
+        self.addr_len = addr.len() as u16;
+    }
+
+    /// Bytes following the inner PDU, such as Ethernet padding
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer[..]
+    }
+
+    pub fn trailer_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.trailer
+    }
+
+    pub fn protocol(&self) -> Ethertype {
+        self.protocol
+    }
+
+    pub fn protocol_mut(&mut self) -> &mut Ethertype {
+        self.base.mark_dirty();
+        &mut self.protocol
+    }
+
+    pub fn update_protocol(&mut self) {
+        self.base.mark_dirty();
+        let protocol = self
+            .inner_pdu()
+            .map(|inner| Ethertype::from_pdu(inner).unwrap_or(self.protocol))
+            .unwrap_or(self.protocol);
+        self.protocol = protocol;
+    }
+}
+
+impl Dissect for Sll {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            tuple((
+                map(u16::decode_be, PacketType),
+                u16::decode_be,
+                u16::decode_be,
+                <[u8; 8]>::decode,
+                map(u16::decode_be, Ethertype),
+            )),
+            |(packet_type, arphrd_type, addr_len, addr, protocol)| {
+                let parent = parent.clone();
+                move |buf: &'a [u8]| {
+                    let mut sll = Self {
+                        base: BasePdu::default(),
+                        packet_type,
+                        arphrd_type,
+                        addr_len,
+                        addr,
+                        protocol,
+                        trailer: Vec::new(),
+                    };
+                    let (buf, inner) = session
+                        .table_dissector::<EthertypeDissectorTable>(
+                            &sll.protocol,
+                            Some(TempPdu::new(&sll, &parent)),
+                        )
+                        .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                        .parse(buf)?;
+                    // Bytes beyond the end of the inner PDU, such as the
+                    // padding of short Ethernet frames
+                    sll.trailer = Vec::from(buf);
+                    sll.set_inner_pdu(inner);
+                    Ok((&buf[buf.len()..], sll))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for Sll {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        16
+    }
+
+    fn trailer_len(&self) -> usize {
+        self.trailer.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode_be(&self.packet_type.0)?
+            .encode_be(&self.arphrd_type)?
+            .encode_be(&self.addr_len)?
+            .encode(&self.addr[..])?
+            .encode_be(&self.protocol.0)?;
+        Ok(())
+    }
+
+    fn serialize_trailer<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.trailer[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("Linux Cooked Capture", None)?;
+        node.byte_range(0, 2).add_field(
+            "Packet Type",
+            DumpValue::UInt(self.packet_type.0.into()),
+            Some(&self.packet_type.to_string()[..]),
+        )?;
+        node.byte_range(2, 2).add_field(
+            "ARPHRD Type",
+            DumpValue::UInt(self.arphrd_type.into()),
+            None,
+        )?;
+        node.byte_range(4, 2).add_field(
+            "Address Length",
+            DumpValue::UInt(self.addr_len.into()),
+            None,
+        )?;
+        node.byte_range(6, 8)
+            .add_field("Address", DumpValue::Bytes(self.address()), None)?;
+        node.byte_range(14, 2).add_field(
+            "Protocol",
+            DumpValue::UInt(self.protocol.0.into()),
+            Some(&format!("0x{:04x}", self.protocol.0)[..]),
+        )?;
+        if !self.trailer.is_empty() {
+            node.byte_range(self.total_len() - self.trailer.len(), self.trailer.len())
+                .add_field("Trailer", DumpValue::Bytes(&self.trailer[..]), None)?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_protocol();
+    }
+}
+
+impl Default for Sll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_link_layer_pdu!(Sll, LinkType::LINUX_SLL);
+register_dissector!(
+    sll,
+    LinkTypeTable,
+    LinkType::LINUX_SLL,
+    Priority(0),
+    Sll::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipv4::Ipv4;
+
+    #[test]
+    fn sll_ipv4() {
+        let mut sll = Sll::new();
+        *sll.packet_type_mut() = PacketType::OUTGOING;
+        sll.set_address(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        let mut ipv4 = Ipv4::new();
+        ipv4.set_inner_pdu(RawPdu::new(vec![0xab; 8]));
+        ipv4.make_canonical();
+        sll.set_inner_pdu(ipv4);
+        sll.make_canonical();
+        assert_eq!(sll.protocol(), Ethertype::IPV4);
+
+        let mut buf = Vec::new();
+        sll.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 16 + 20 + 8);
+        assert_eq!(buf[..6], [0x00, 0x04, 0x00, 0x01, 0x00, 0x06]);
+
+        let session = Session::new();
+        let (rem, dissected) = Sll::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(dissected.packet_type(), PacketType::OUTGOING);
+        assert_eq!(dissected.address(), [0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert!(dissected.find::<Ipv4>().is_some());
+
+        buf.extend_from_slice(&[0u8; 6]);
+        let (rem, dissected) = Sll::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(dissected.trailer(), &[0u8; 6][..]);
+        assert_eq!(dissected.find::<Ipv4>().unwrap().total_len(), 28);
+        let mut out = Vec::new();
+        dissected.serialize(&mut out).unwrap();
+        assert_eq!(out, buf);
+    }
+}
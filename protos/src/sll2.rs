@@ -0,0 +1,309 @@
+use super::ethernet_ii::EthertypeDissectorTable;
+use super::ethertype::Ethertype;
+use super::sll::{PacketType, ARPHRD_ETHER};
+use crate::prelude::*;
+use nom::{
+    combinator::{flat_map, map},
+    sequence::tuple,
+};
+
+/// Linux cooked capture v2 (`LINKTYPE_LINUX_SLL2`) header. Unlike v1, it
+/// records the interface the packet was captured on.
+#[derive(Debug, Clone)]
+pub struct Sll2 {
+    base: BasePdu,
+    protocol: Ethertype,
+    reserved: u16,
+    if_index: u32,
+    arphrd_type: u16,
+    packet_type: PacketType,
+    addr_len: u8,
+    addr: [u8; 8],
+    trailer: Vec<u8>,
+}
+
+impl Sll2 {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            protocol: Ethertype(0),
+            reserved: 0,
+            if_index: 0,
+            arphrd_type: ARPHRD_ETHER,
+            packet_type: PacketType::HOST,
+            addr_len: 0,
+            addr: [0u8; 8],
+            trailer: Vec::new(),
+        }
+    }
+
+    pub fn protocol(&self) -> Ethertype {
+        self.protocol
+    }
+
+    pub fn protocol_mut(&mut self) -> &mut Ethertype {
+        self.base.mark_dirty();
+        &mut self.protocol
+    }
+
+    pub fn update_protocol(&mut self) {
+        self.base.mark_dirty();
+        let protocol = self
+            .inner_pdu()
+            .map(|inner| Ethertype::from_pdu(inner).unwrap_or(self.protocol))
+            .unwrap_or(self.protocol);
+        self.protocol = protocol;
+    }
+
+    pub fn reserved(&self) -> u16 {
+        self.reserved
+    }
+
+    pub fn reserved_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.reserved
+    }
+
+    pub fn interface_index(&self) -> u32 {
+        self.if_index
+    }
+
+    pub fn interface_index_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.if_index
+    }
+
+    pub fn arphrd_type(&self) -> u16 {
+        self.arphrd_type
+    }
+
+    pub fn arphrd_type_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.arphrd_type
+    }
+
+    pub fn packet_type(&self) -> PacketType {
+        self.packet_type
+    }
+
+    pub fn packet_type_mut(&mut self) -> &mut PacketType {
+        self.base.mark_dirty();
+        &mut self.packet_type
+    }
+
+    /// The link-layer source address. At most 8 bytes are captured, even if
+    /// the address length field is larger.
+    pub fn address(&self) -> &[u8] {
+        &self.addr[..usize::from(self.addr_len).min(8)]
+    }
+
+    pub fn address_len(&self) -> u8 {
+        self.addr_len
+    }
+
+    pub fn address_len_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.addr_len
+    }
+
+    /// Sets the address and address length. Addresses longer than 8 bytes
+    /// are truncated.
+    pub fn set_address(&mut self, addr: &[u8]) {
+        self.base.mark_dirty();
+        let len = addr.len().min(8);
+        self.addr = [0u8; 8];
+        self.addr[..len].copy_from_slice(&addr[..len]);
+        self.addr_len = addr.len() as u8;
+    }
+
+    /// Bytes following the inner PDU, such as Ethernet padding
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer[..]
+    }
+
+    pub fn trailer_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.trailer
+    }
+}
+
+impl Dissect for Sll2 {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        flat_map(
+            tuple((
+                map(u16::decode_be, Ethertype),
+                u16::decode_be,
+                u32::decode_be,
+                u16::decode_be,
+                map(u8::decode, |val| PacketType(val.into())),
+                u8::decode,
+                <[u8; 8]>::decode,
+            )),
+            |(protocol, reserved, if_index, arphrd_type, packet_type, addr_len, addr)| {
+                let parent = parent.clone();
+                move |buf: &'a [u8]| {
+                    let mut sll = Self {
+                        base: BasePdu::default(),
+                        protocol,
+                        reserved,
+                        if_index,
+                        arphrd_type,
+                        packet_type,
+                        addr_len,
+                        addr,
+                        trailer: Vec::new(),
+                    };
+                    let (buf, inner) = session
+                        .table_dissector::<EthertypeDissectorTable>(
+                            &sll.protocol,
+                            Some(TempPdu::new(&sll, &parent)),
+                        )
+                        .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                        .parse(buf)?;
+                    // Bytes beyond the end of the inner PDU, such as the
+                    // padding of short Ethernet frames
+                    sll.trailer = Vec::from(buf);
+                    sll.set_inner_pdu(inner);
+                    Ok((&buf[buf.len()..], sll))
+                }
+            },
+        )(buf)
+    }
+}
+
+impl Pdu for Sll2 {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        20
+    }
+
+    fn trailer_len(&self) -> usize {
+        self.trailer.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode_be(&self.protocol.0)?
+            .encode_be(&self.reserved)?
+            .encode_be(&self.if_index)?
+            .encode_be(&self.arphrd_type)?
+            .encode(&(self.packet_type.0 as u8))?
+            .encode(&self.addr_len)?
+            .encode(&self.addr[..])?;
+        Ok(())
+    }
+
+    fn serialize_trailer<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder.encode(&self.trailer[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("Linux Cooked Capture v2", None)?;
+        node.byte_range(0, 2).add_field(
+            "Protocol",
+            DumpValue::UInt(self.protocol.0.into()),
+            Some(&format!("0x{:04x}", self.protocol.0)[..]),
+        )?;
+        node.byte_range(2, 2)
+            .add_field("Reserved", DumpValue::UInt(self.reserved.into()), None)?;
+        node.byte_range(4, 4).add_field(
+            "Interface Index",
+            DumpValue::UInt(self.if_index.into()),
+            None,
+        )?;
+        node.byte_range(8, 2).add_field(
+            "ARPHRD Type",
+            DumpValue::UInt(self.arphrd_type.into()),
+            None,
+        )?;
+        node.byte_range(10, 1).add_field(
+            "Packet Type",
+            DumpValue::UInt(self.packet_type.0.into()),
+            Some(&self.packet_type.to_string()[..]),
+        )?;
+        node.byte_range(11, 1).add_field(
+            "Address Length",
+            DumpValue::UInt(self.addr_len.into()),
+            None,
+        )?;
+        node.byte_range(12, 8)
+            .add_field("Address", DumpValue::Bytes(self.address()), None)?;
+        if !self.trailer.is_empty() {
+            node.byte_range(self.total_len() - self.trailer.len(), self.trailer.len())
+                .add_field("Trailer", DumpValue::Bytes(&self.trailer[..]), None)?;
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_protocol();
+    }
+}
+
+impl Default for Sll2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_link_layer_pdu!(Sll2, LinkType::LINUX_SLL2);
+register_dissector!(
+    sll2,
+    LinkTypeTable,
+    LinkType::LINUX_SLL2,
+    Priority(0),
+    Sll2::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipv4::Ipv4;
+
+    #[test]
+    fn sll2_ipv4() {
+        let mut sll = Sll2::new();
+        *sll.interface_index_mut() = 3;
+        *sll.packet_type_mut() = PacketType::BROADCAST;
+        sll.set_address(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        let mut ipv4 = Ipv4::new();
+        ipv4.set_inner_pdu(RawPdu::new(vec![0xab; 8]));
+        ipv4.make_canonical();
+        sll.set_inner_pdu(ipv4);
+        sll.make_canonical();
+
+        let mut buf = Vec::new();
+        sll.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 20 + 20 + 8);
+        assert_eq!(
+            buf[..12],
+            [0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x01, 0x06]
+        );
+
+        let session = Session::new();
+        let (rem, dissected) = Sll2::dissect(&buf[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(dissected.interface_index(), 3);
+        assert_eq!(dissected.packet_type(), PacketType::BROADCAST);
+        assert_eq!(dissected.address(), [0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert!(dissected.find::<Ipv4>().is_some());
+    }
+}
@@ -0,0 +1,809 @@
+use super::udp::UdpPortDissectorTable;
+use crate::prelude::*;
+use sniffle_core::Ipv4Address;
+use sniffle_ende::ber::{self, tag, Class, Identifier, Tlv};
+use sniffle_ende::encode::Encode;
+use sniffle_ende::varint::BerLength;
+
+/// UDP port SNMP agents listen on for requests
+pub const SNMP_PORT: u16 = 161;
+/// UDP port SNMP managers listen on for traps and informs
+pub const SNMP_TRAP_PORT: u16 = 162;
+
+/// Simple Network Management Protocol message, version 1 (RFC 1157) or
+/// version 2c (RFC 1901 and RFC 3416)
+///
+/// Version 3 messages are not dissected. Serializing always produces the
+/// shortest BER encoding, which may differ from the dissected bytes.
+#[derive(Debug, Clone)]
+pub struct Snmp {
+    base: BasePdu,
+    version: Version,
+    community: Vec<u8>,
+    pdu: SnmpPdu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Version(pub i64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PduType(pub u8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnmpPdu {
+    /// Every PDU other than a version 1 trap
+    Request(RequestPdu),
+    Trap(TrapPdu),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestPdu {
+    pub pdu_type: PduType,
+    pub request_id: i32,
+    /// The number of non-repeaters in a GetBulkRequest
+    pub error_status: i32,
+    /// The number of max-repetitions in a GetBulkRequest
+    pub error_index: i32,
+    pub varbinds: Vec<VarBind>,
+}
+
+/// A version 1 Trap-PDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrapPdu {
+    pub enterprise: Oid,
+    pub agent_addr: Ipv4Address,
+    pub generic_trap: i32,
+    pub specific_trap: i32,
+    /// Time since the agent was initialized, in hundredths of a second
+    pub time_stamp: u32,
+    pub varbinds: Vec<VarBind>,
+}
+
+/// An object identifier, such as 1.3.6.1.2.1.1.5.0
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Oid(pub Vec<u32>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarBind {
+    pub name: Oid,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    ObjectId(Oid),
+    IpAddress(Ipv4Address),
+    Counter32(u32),
+    Gauge32(u32),
+    /// Hundredths of a second
+    TimeTicks(u32),
+    Opaque(Vec<u8>),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+    Other(Identifier, Vec<u8>),
+}
+
+/// Generic trap types of a version 1 trap
+pub mod generic_trap {
+    pub const COLD_START: i32 = 0;
+    pub const WARM_START: i32 = 1;
+    pub const LINK_DOWN: i32 = 2;
+    pub const LINK_UP: i32 = 3;
+    pub const AUTHENTICATION_FAILURE: i32 = 4;
+    pub const EGP_NEIGHBOR_LOSS: i32 = 5;
+    pub const ENTERPRISE_SPECIFIC: i32 = 6;
+}
+
+impl Version {
+    pub const V1: Self = Self(0);
+    pub const V2C: Self = Self(1);
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::V1 => f.write_str("v1"),
+            Self::V2C => f.write_str("v2c"),
+            _ => write!(f, "Unknown ({})", self.0),
+        }
+    }
+}
+
+impl PduType {
+    pub const GET_REQUEST: Self = Self(0xa0);
+    pub const GET_NEXT_REQUEST: Self = Self(0xa1);
+    pub const RESPONSE: Self = Self(0xa2);
+    pub const SET_REQUEST: Self = Self(0xa3);
+    pub const TRAP: Self = Self(0xa4);
+    pub const GET_BULK_REQUEST: Self = Self(0xa5);
+    pub const INFORM_REQUEST: Self = Self(0xa6);
+    pub const SNMPV2_TRAP: Self = Self(0xa7);
+    pub const REPORT: Self = Self(0xa8);
+
+    fn identifier(&self) -> Identifier {
+        Identifier::new(Class::ContextSpecific, true, (self.0 & 0x1f) as u32)
+    }
+}
+
+impl std::fmt::Display for PduType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::GET_REQUEST => "GetRequest",
+            Self::GET_NEXT_REQUEST => "GetNextRequest",
+            Self::RESPONSE => "Response",
+            Self::SET_REQUEST => "SetRequest",
+            Self::TRAP => "Trap",
+            Self::GET_BULK_REQUEST => "GetBulkRequest",
+            Self::INFORM_REQUEST => "InformRequest",
+            Self::SNMPV2_TRAP => "SNMPv2-Trap",
+            Self::REPORT => "Report",
+            _ => return write!(f, "Unknown (0x{:02x})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::fmt::Display for Oid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, arc) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", arc)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<u32>> for Oid {
+    fn from(arcs: Vec<u32>) -> Self {
+        Self(arcs)
+    }
+}
+
+fn malformed<'a, T>() -> Result<T, nom::Err<DissectError<'a>>> {
+    Err(nom::Err::Error(DissectError::Malformed))
+}
+
+/// Decodes constructed contents. The contents are complete, so running out
+/// of bytes is malformed rather than incomplete.
+fn children<'a, 'b>(
+    elem: &Tlv<'a>,
+    id: Identifier,
+) -> Result<Vec<Tlv<'a>>, nom::Err<DissectError<'b>>> {
+    if elem.id != id {
+        return malformed();
+    }
+    match elem.children() {
+        Ok((_, children)) => Ok(children),
+        Err(_) => malformed(),
+    }
+}
+
+fn integer<'a>(elem: &Tlv<'_>) -> Result<i64, nom::Err<DissectError<'a>>> {
+    match ber::decode_integer(elem.value) {
+        Some(value) if elem.id == Identifier::universal(tag::INTEGER) => Ok(value),
+        _ => malformed(),
+    }
+}
+
+fn integer32<'a>(elem: &Tlv<'_>) -> Result<i32, nom::Err<DissectError<'a>>> {
+    i32::try_from(integer(elem)?).or_else(|_| malformed())
+}
+
+fn oid<'a>(elem: &Tlv<'_>) -> Result<Oid, nom::Err<DissectError<'a>>> {
+    match ber::decode_oid(elem.value) {
+        Some(arcs) if elem.id == Identifier::universal(tag::OBJECT_IDENTIFIER) => Ok(Oid(arcs)),
+        _ => malformed(),
+    }
+}
+
+/// Appends a definite length element to `out`
+fn push_tlv(out: &mut Vec<u8>, id: Identifier, value: &[u8]) {
+    // Encoding to a Vec cannot fail
+    let _ = Tlv::new(id, value).encode(out);
+}
+
+/// The length of an element with `len` bytes of contents
+fn tlv_len(id: Identifier, len: usize) -> usize {
+    id.encoded_len() + BerLength::Definite(len as u64).encoded_len() + len
+}
+
+fn push_integer(out: &mut Vec<u8>, value: i64) {
+    push_tlv(
+        out,
+        Identifier::universal(tag::INTEGER),
+        &ber::encode_integer(value),
+    );
+}
+
+fn push_oid(out: &mut Vec<u8>, oid: &Oid) {
+    let value = ber::encode_oid(&oid.0[..]).unwrap_or_default();
+    push_tlv(out, Identifier::universal(tag::OBJECT_IDENTIFIER), &value);
+}
+
+const fn application(tag: u32) -> Identifier {
+    Identifier::new(Class::Application, false, tag)
+}
+
+const fn context(tag: u32) -> Identifier {
+    Identifier::new(Class::ContextSpecific, false, tag)
+}
+
+impl Value {
+    fn decode<'a>(elem: &Tlv<'_>) -> Result<Self, nom::Err<DissectError<'a>>> {
+        let unsigned = || match ber::decode_unsigned(elem.value) {
+            Some(value) => Ok(value),
+            None => malformed(),
+        };
+        let unsigned32 = || u32::try_from(unsigned()?).or_else(|_| malformed());
+        let id = elem.id;
+        Ok(match (id.class, id.constructed, id.tag) {
+            (Class::Universal, false, tag::INTEGER) => Self::Integer(integer(elem)?),
+            (Class::Universal, false, tag::OCTET_STRING) => Self::OctetString(elem.value.into()),
+            (Class::Universal, false, tag::NULL) => Self::Null,
+            (Class::Universal, false, tag::OBJECT_IDENTIFIER) => Self::ObjectId(oid(elem)?),
+            (Class::Application, false, 0) => match <[u8; 4]>::try_from(elem.value) {
+                Ok(addr) => Self::IpAddress(addr.into()),
+                Err(_) => return malformed(),
+            },
+            (Class::Application, false, 1) => Self::Counter32(unsigned32()?),
+            (Class::Application, false, 2) => Self::Gauge32(unsigned32()?),
+            (Class::Application, false, 3) => Self::TimeTicks(unsigned32()?),
+            (Class::Application, false, 4) => Self::Opaque(elem.value.into()),
+            (Class::Application, false, 6) => Self::Counter64(unsigned()?),
+            (Class::ContextSpecific, false, 0) => Self::NoSuchObject,
+            (Class::ContextSpecific, false, 1) => Self::NoSuchInstance,
+            (Class::ContextSpecific, false, 2) => Self::EndOfMibView,
+            _ => Self::Other(id, elem.value.into()),
+        })
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Integer(value) => push_integer(out, *value),
+            Self::OctetString(value) => {
+                push_tlv(out, Identifier::universal(tag::OCTET_STRING), value)
+            }
+            Self::Null => push_tlv(out, Identifier::universal(tag::NULL), &[]),
+            Self::ObjectId(oid) => push_oid(out, oid),
+            Self::IpAddress(addr) => push_tlv(out, application(0), &addr[..]),
+            Self::Counter32(value) => {
+                push_tlv(out, application(1), &ber::encode_unsigned(*value as u64))
+            }
+            Self::Gauge32(value) => {
+                push_tlv(out, application(2), &ber::encode_unsigned(*value as u64))
+            }
+            Self::TimeTicks(value) => {
+                push_tlv(out, application(3), &ber::encode_unsigned(*value as u64))
+            }
+            Self::Opaque(value) => push_tlv(out, application(4), value),
+            Self::Counter64(value) => push_tlv(out, application(6), &ber::encode_unsigned(*value)),
+            Self::NoSuchObject => push_tlv(out, context(0), &[]),
+            Self::NoSuchInstance => push_tlv(out, context(1), &[]),
+            Self::EndOfMibView => push_tlv(out, context(2), &[]),
+            Self::Other(id, value) => push_tlv(out, *id, value),
+        }
+    }
+
+    fn dump<D: Dump + ?Sized>(
+        &self,
+        node: &mut NodeDumper<D>,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), D::Error> {
+        let field = node.byte_range(offset, len);
+        match self {
+            Self::Integer(value) => field.add_field("Value", DumpValue::Int(*value), None),
+            Self::OctetString(value) | Self::Opaque(value) | Self::Other(_, value) => {
+                let text = std::str::from_utf8(value)
+                    .ok()
+                    .filter(|text| text.chars().all(|c| !c.is_control()));
+                field.add_field("Value", DumpValue::Bytes(value), text)
+            }
+            Self::Null => field.add_field("Value", DumpValue::Text("NULL"), None),
+            Self::ObjectId(oid) => {
+                field.add_field("Value", DumpValue::Text(&oid.to_string()[..]), None)
+            }
+            Self::IpAddress(addr) => field.add_field(
+                "Value",
+                DumpValue::Bytes(&addr[..]),
+                Some(&addr.to_string()[..]),
+            ),
+            Self::Counter32(value) | Self::Gauge32(value) => {
+                field.add_field("Value", DumpValue::UInt((*value).into()), None)
+            }
+            Self::TimeTicks(value) => field.add_field(
+                "Value",
+                DumpValue::Duration(std::time::Duration::from_millis(*value as u64 * 10)),
+                None,
+            ),
+            Self::Counter64(value) => field.add_field("Value", DumpValue::UInt(*value), None),
+            Self::NoSuchObject => field.add_field("Value", DumpValue::Text("noSuchObject"), None),
+            Self::NoSuchInstance => {
+                field.add_field("Value", DumpValue::Text("noSuchInstance"), None)
+            }
+            Self::EndOfMibView => field.add_field("Value", DumpValue::Text("endOfMibView"), None),
+        }
+    }
+}
+
+impl VarBind {
+    pub fn new(name: Oid, value: Value) -> Self {
+        Self { name, value }
+    }
+
+    fn decode<'a>(elem: &Tlv<'_>) -> Result<Self, nom::Err<DissectError<'a>>> {
+        match &children(elem, Identifier::SEQUENCE)?[..] {
+            [name, value] => Ok(Self {
+                name: oid(name)?,
+                value: Value::decode(value)?,
+            }),
+            _ => malformed(),
+        }
+    }
+
+    fn content(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        push_oid(&mut out, &self.name);
+        self.value.encode(&mut out);
+        out
+    }
+}
+
+fn decode_varbinds<'a>(elem: &Tlv<'_>) -> Result<Vec<VarBind>, nom::Err<DissectError<'a>>> {
+    children(elem, Identifier::SEQUENCE)?
+        .iter()
+        .map(VarBind::decode)
+        .collect()
+}
+
+fn varbinds_content(varbinds: &[VarBind]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for varbind in varbinds {
+        push_tlv(&mut out, Identifier::SEQUENCE, &varbind.content());
+    }
+    out
+}
+
+fn dump_varbinds<D: Dump + ?Sized>(
+    node: &mut NodeDumper<D>,
+    varbinds: &[VarBind],
+    mut offset: usize,
+) -> Result<(), D::Error> {
+    let content = varbinds_content(varbinds);
+    let mut list = node
+        .byte_range(offset, tlv_len(Identifier::SEQUENCE, content.len()))
+        .add_node("Variable Bindings", None)?;
+    offset += tlv_len(Identifier::SEQUENCE, content.len()) - content.len();
+    for varbind in varbinds {
+        let len = varbind.content().len();
+        let name = varbind.name.to_string();
+        let mut vb_node = list
+            .byte_range(offset, tlv_len(Identifier::SEQUENCE, len))
+            .add_node("Variable Binding", Some(&name[..]))?;
+        offset += tlv_len(Identifier::SEQUENCE, len) - len;
+        let mut name_bytes = Vec::new();
+        push_oid(&mut name_bytes, &varbind.name);
+        vb_node.byte_range(offset, name_bytes.len()).add_field(
+            "Name",
+            DumpValue::Text(&name[..]),
+            None,
+        )?;
+        offset += name_bytes.len();
+        varbind
+            .value
+            .dump(&mut vb_node, offset, len - name_bytes.len())?;
+        offset += len - name_bytes.len();
+    }
+    Ok(())
+}
+
+fn dump_integer<D: Dump + ?Sized>(
+    node: &mut NodeDumper<D>,
+    offset: &mut usize,
+    name: &str,
+    value: i64,
+) -> Result<(), D::Error> {
+    let len = tlv_len(
+        Identifier::universal(tag::INTEGER),
+        ber::encode_integer(value).len(),
+    );
+    node.byte_range(*offset, len)
+        .add_field(name, DumpValue::Int(value), None)?;
+    *offset += len;
+    Ok(())
+}
+
+impl SnmpPdu {
+    pub fn pdu_type(&self) -> PduType {
+        match self {
+            Self::Request(pdu) => pdu.pdu_type,
+            Self::Trap(_) => PduType::TRAP,
+        }
+    }
+
+    pub fn varbinds(&self) -> &[VarBind] {
+        match self {
+            Self::Request(pdu) => &pdu.varbinds[..],
+            Self::Trap(pdu) => &pdu.varbinds[..],
+        }
+    }
+
+    pub fn varbinds_mut(&mut self) -> &mut Vec<VarBind> {
+        match self {
+            Self::Request(pdu) => &mut pdu.varbinds,
+            Self::Trap(pdu) => &mut pdu.varbinds,
+        }
+    }
+
+    fn decode<'a>(elem: &Tlv<'_>) -> Result<Self, nom::Err<DissectError<'a>>> {
+        let pdu_type = match elem.id {
+            Identifier {
+                class: Class::ContextSpecific,
+                constructed: true,
+                tag: tag @ 0..=8,
+            } => PduType(0xa0 | tag as u8),
+            _ => return malformed(),
+        };
+        let fields = children(elem, elem.id)?;
+        if pdu_type == PduType::TRAP {
+            return match &fields[..] {
+                [enterprise, agent_addr, generic_trap, specific_trap, time_stamp, varbinds] => {
+                    let agent_addr = match Value::decode(agent_addr)? {
+                        Value::IpAddress(addr) => addr,
+                        _ => return malformed(),
+                    };
+                    let time_stamp = match Value::decode(time_stamp)? {
+                        Value::TimeTicks(ticks) => ticks,
+                        _ => return malformed(),
+                    };
+                    Ok(Self::Trap(TrapPdu {
+                        enterprise: oid(enterprise)?,
+                        agent_addr,
+                        generic_trap: integer32(generic_trap)?,
+                        specific_trap: integer32(specific_trap)?,
+                        time_stamp,
+                        varbinds: decode_varbinds(varbinds)?,
+                    }))
+                }
+                _ => malformed(),
+            };
+        }
+        match &fields[..] {
+            [request_id, error_status, error_index, varbinds] => Ok(Self::Request(RequestPdu {
+                pdu_type,
+                request_id: integer32(request_id)?,
+                error_status: integer32(error_status)?,
+                error_index: integer32(error_index)?,
+                varbinds: decode_varbinds(varbinds)?,
+            })),
+            _ => malformed(),
+        }
+    }
+
+    fn content(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Self::Request(pdu) => {
+                push_integer(&mut out, pdu.request_id.into());
+                push_integer(&mut out, pdu.error_status.into());
+                push_integer(&mut out, pdu.error_index.into());
+            }
+            Self::Trap(pdu) => {
+                push_oid(&mut out, &pdu.enterprise);
+                Value::IpAddress(pdu.agent_addr).encode(&mut out);
+                push_integer(&mut out, pdu.generic_trap.into());
+                push_integer(&mut out, pdu.specific_trap.into());
+                Value::TimeTicks(pdu.time_stamp).encode(&mut out);
+            }
+        }
+        push_tlv(
+            &mut out,
+            Identifier::SEQUENCE,
+            &varbinds_content(self.varbinds()),
+        );
+        out
+    }
+}
+
+impl Snmp {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            version: Version::V2C,
+            community: Vec::from(&b"public"[..]),
+            pdu: SnmpPdu::Request(RequestPdu {
+                pdu_type: PduType::GET_REQUEST,
+                request_id: 0,
+                error_status: 0,
+                error_index: 0,
+                varbinds: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut Version {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    pub fn community(&self) -> &[u8] {
+        &self.community[..]
+    }
+
+    pub fn community_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.community
+    }
+
+    pub fn pdu(&self) -> &SnmpPdu {
+        &self.pdu
+    }
+
+    pub fn pdu_mut(&mut self) -> &mut SnmpPdu {
+        self.base.mark_dirty();
+        &mut self.pdu
+    }
+
+    pub fn pdu_type(&self) -> PduType {
+        self.pdu.pdu_type()
+    }
+
+    pub fn varbinds(&self) -> &[VarBind] {
+        self.pdu.varbinds()
+    }
+
+    fn parse(buf: &[u8]) -> DResult<'_, Self> {
+        let (rem, msg) = ber::tlv(buf)?;
+        match &children(&msg, Identifier::SEQUENCE)?[..] {
+            [version, community, pdu] => {
+                let version = Version(integer(version)?);
+                if version != Version::V1 && version != Version::V2C {
+                    return malformed();
+                }
+                if community.id != Identifier::universal(tag::OCTET_STRING) {
+                    return malformed();
+                }
+                Ok((
+                    rem,
+                    Self {
+                        base: BasePdu::default(),
+                        version,
+                        community: community.value.into(),
+                        pdu: SnmpPdu::decode(pdu)?,
+                    },
+                ))
+            }
+            _ => malformed(),
+        }
+    }
+
+    fn content(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        push_integer(&mut out, self.version.0);
+        push_tlv(
+            &mut out,
+            Identifier::universal(tag::OCTET_STRING),
+            &self.community,
+        );
+        let pdu_id = match self.pdu {
+            SnmpPdu::Request(ref pdu) => pdu.pdu_type.identifier(),
+            SnmpPdu::Trap(_) => PduType::TRAP.identifier(),
+        };
+        push_tlv(&mut out, pdu_id, &self.pdu.content());
+        out
+    }
+}
+
+impl Dissect for Snmp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        Self::parse(buf)
+    }
+}
+
+impl Pdu for Snmp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        tlv_len(Identifier::SEQUENCE, self.content().len())
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        Tlv::new(Identifier::SEQUENCE, &self.content()[..]).encode(encoder)
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let pdu_type = self.pdu_type().to_string();
+        let mut node = dumper.add_node("SNMP", Some(&pdu_type[..]))?;
+        let content = self.content();
+        let mut offset = tlv_len(Identifier::SEQUENCE, content.len()) - content.len();
+
+        let mut field = Vec::new();
+        push_integer(&mut field, self.version.0);
+        node.byte_range(offset, field.len()).add_field(
+            "Version",
+            DumpValue::Int(self.version.0),
+            Some(&self.version.to_string()[..]),
+        )?;
+        offset += field.len();
+
+        let len = tlv_len(
+            Identifier::universal(tag::OCTET_STRING),
+            self.community.len(),
+        );
+        node.byte_range(offset, len).add_field(
+            "Community",
+            DumpValue::Bytes(&self.community[..]),
+            std::str::from_utf8(&self.community[..]).ok(),
+        )?;
+        offset += len;
+
+        let pdu_content = self.pdu.content();
+        let pdu_id = self.pdu_type().identifier();
+        let mut pdu_node = node
+            .byte_range(offset, tlv_len(pdu_id, pdu_content.len()))
+            .add_node("PDU", Some(&pdu_type[..]))?;
+        offset += tlv_len(pdu_id, pdu_content.len()) - pdu_content.len();
+
+        match self.pdu {
+            SnmpPdu::Request(ref pdu) => {
+                let (status, index) = if pdu.pdu_type == PduType::GET_BULK_REQUEST {
+                    ("Non-repeaters", "Max-repetitions")
+                } else {
+                    ("Error Status", "Error Index")
+                };
+                dump_integer(
+                    &mut pdu_node,
+                    &mut offset,
+                    "Request ID",
+                    pdu.request_id.into(),
+                )?;
+                dump_integer(&mut pdu_node, &mut offset, status, pdu.error_status.into())?;
+                dump_integer(&mut pdu_node, &mut offset, index, pdu.error_index.into())?;
+            }
+            SnmpPdu::Trap(ref pdu) => {
+                let mut field = Vec::new();
+                push_oid(&mut field, &pdu.enterprise);
+                pdu_node.byte_range(offset, field.len()).add_field(
+                    "Enterprise",
+                    DumpValue::Text(&pdu.enterprise.to_string()[..]),
+                    None,
+                )?;
+                offset += field.len();
+                pdu_node.byte_range(offset, 6).add_field(
+                    "Agent Address",
+                    DumpValue::Bytes(&pdu.agent_addr[..]),
+                    Some(&pdu.agent_addr.to_string()[..]),
+                )?;
+                offset += 6;
+                dump_integer(
+                    &mut pdu_node,
+                    &mut offset,
+                    "Generic Trap",
+                    pdu.generic_trap.into(),
+                )?;
+                dump_integer(
+                    &mut pdu_node,
+                    &mut offset,
+                    "Specific Trap",
+                    pdu.specific_trap.into(),
+                )?;
+                let mut field = Vec::new();
+                Value::TimeTicks(pdu.time_stamp).encode(&mut field);
+                pdu_node.byte_range(offset, field.len()).add_field(
+                    "Time Stamp",
+                    DumpValue::Duration(std::time::Duration::from_millis(
+                        pdu.time_stamp as u64 * 10,
+                    )),
+                    None,
+                )?;
+                offset += field.len();
+            }
+        }
+        dump_varbinds(&mut pdu_node, self.varbinds(), offset)
+    }
+}
+
+impl Default for Snmp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    snmp,
+    UdpPortDissectorTable,
+    SNMP_PORT,
+    Priority(0),
+    Snmp::dissect
+);
+
+register_dissector!(
+    snmp_trap,
+    UdpPortDissectorTable,
+    SNMP_TRAP_PORT,
+    Priority(0),
+    Snmp::dissect
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn snmp_get_response() {
+        let data = [
+            0x30, 0x36, // Message
+            0x02, 0x01, 0x01, // Version
+            0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', // Community
+            0xa2, 0x29, // Response
+            0x02, 0x02, 0x30, 0x39, // Request ID
+            0x02, 0x01, 0x00, // Error status
+            0x02, 0x01, 0x00, // Error index
+            0x30, 0x1d, // Variable bindings
+            0x30, 0x0d, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x03,
+            0x00, // sysUpTime.0
+            0x43, 0x01, 0x64, // 1 second
+            0x30, 0x0c, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x05,
+            0x00, // sysName.0
+            0x05, 0x00, // NULL
+        ];
+        let session = Session::new();
+        let (rem, snmp) = Snmp::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(snmp.version(), Version::V2C);
+        assert_eq!(snmp.community(), b"public");
+        assert_eq!(snmp.pdu_type(), PduType::RESPONSE);
+        let pdu = match snmp.pdu() {
+            SnmpPdu::Request(pdu) => pdu,
+            SnmpPdu::Trap(_) => panic!("expected a response"),
+        };
+        assert_eq!(pdu.request_id, 12345);
+        assert_eq!(
+            snmp.varbinds(),
+            &[
+                VarBind::new(Oid(vec![1, 3, 6, 1, 2, 1, 1, 3, 0]), Value::TimeTicks(100)),
+                VarBind::new(Oid(vec![1, 3, 6, 1, 2, 1, 1, 5, 0]), Value::Null),
+            ][..]
+        );
+        assert_eq!(snmp.varbinds()[1].name.to_string(), "1.3.6.1.2.1.1.5.0");
+
+        assert_eq!(snmp.header_len(), data.len());
+        let mut out = Vec::new();
+        snmp.serialize(&mut out).unwrap();
+        assert_eq!(&out[..], &data[..]);
+
+        // Truncated within the message is incomplete, but truncated within
+        // an element is malformed.
+        assert!(matches!(
+            Snmp::dissect(&data[..20], &session, None),
+            Err(nom::Err::Incomplete(_))
+        ));
+        let mut bad = data;
+        bad[14] = 0x2a;
+        assert!(Snmp::dissect(&bad[..], &session, None).is_err());
+    }
+}
@@ -0,0 +1,479 @@
+//! Spanning Tree Protocol (STP) bridge protocol data units (BPDUs), from
+//! IEEE 802.1D, including the Rapid Spanning Tree Protocol (RSTP).
+//!
+//! BPDUs are carried by LLC with the SAP 0x42. The MSTP fields following
+//! an RST BPDU are kept as raw bytes.
+
+use super::llc::LlcSapDissectorTable;
+use crate::prelude::*;
+use sniffle_core::MacAddress;
+use std::time::Duration;
+
+/// LLC SAP of STP
+pub const STP_SAP: u8 = 0x42;
+
+#[derive(Debug, Clone)]
+pub struct Stp {
+    base: BasePdu,
+    protocol_id: u16,
+    version: u8,
+    bpdu_type: BpduType,
+    config: Option<BpduConfig>,
+    extra: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BpduType(pub u8);
+
+/// The fields of configuration and RST BPDUs. Times are in units of 1/256
+/// of a second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BpduConfig {
+    pub flags: u8,
+    pub root_id: BridgeId,
+    pub root_path_cost: u32,
+    pub bridge_id: BridgeId,
+    pub port_id: u16,
+    pub message_age: u16,
+    pub max_age: u16,
+    pub hello_time: u16,
+    pub forward_delay: u16,
+}
+
+/// A bridge identifier: a priority, which includes the system ID extension
+/// (usually the VLAN), followed by a MAC address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BridgeId {
+    pub priority: u16,
+    pub mac: MacAddress,
+}
+
+/// BPDU flags
+pub mod flags {
+    pub const TOPOLOGY_CHANGE: u8 = 0x01;
+    pub const PROPOSAL: u8 = 0x02;
+    /// Port role of RST BPDUs, see `Stp::port_role`
+    pub const PORT_ROLE: u8 = 0x0c;
+    pub const LEARNING: u8 = 0x10;
+    pub const FORWARDING: u8 = 0x20;
+    pub const AGREEMENT: u8 = 0x40;
+    pub const TOPOLOGY_CHANGE_ACK: u8 = 0x80;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortRole {
+    Unknown,
+    AlternateOrBackup,
+    Root,
+    Designated,
+}
+
+impl BpduType {
+    pub const CONFIG: Self = Self(0x00);
+    pub const RST: Self = Self(0x02);
+    pub const TCN: Self = Self(0x80);
+}
+
+impl std::fmt::Display for BpduType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::CONFIG => "Configuration",
+            Self::RST => "Rapid/Multiple Spanning Tree",
+            Self::TCN => "Topology Change Notification",
+            _ => return write!(f, "Unknown (0x{:02x})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::fmt::Display for BridgeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.priority, self.mac)
+    }
+}
+
+impl BridgeId {
+    fn decode<'a>(cur: &mut DecodeCursor<'a>) -> DecodeResult<'a, Self> {
+        Ok(Self {
+            priority: cur.decode_be()?,
+            mac: cur.decode()?,
+        })
+    }
+}
+
+impl BpduConfig {
+    fn decode<'a>(cur: &mut DecodeCursor<'a>) -> DecodeResult<'a, Self> {
+        Ok(Self {
+            flags: cur.decode()?,
+            root_id: BridgeId::decode(cur)?,
+            root_path_cost: cur.decode_be()?,
+            bridge_id: BridgeId::decode(cur)?,
+            port_id: cur.decode_be()?,
+            message_age: cur.decode_be()?,
+            max_age: cur.decode_be()?,
+            hello_time: cur.decode_be()?,
+            forward_delay: cur.decode_be()?,
+        })
+    }
+
+    pub fn message_age(&self) -> Duration {
+        stp_time(self.message_age)
+    }
+
+    pub fn max_age(&self) -> Duration {
+        stp_time(self.max_age)
+    }
+
+    pub fn hello_time(&self) -> Duration {
+        stp_time(self.hello_time)
+    }
+
+    pub fn forward_delay(&self) -> Duration {
+        stp_time(self.forward_delay)
+    }
+}
+
+fn stp_time(val: u16) -> Duration {
+    Duration::from_micros(val as u64 * 1_000_000 / 256)
+}
+
+impl Stp {
+    /// Creates a configuration BPDU.
+    pub fn config(config: BpduConfig) -> Self {
+        Self {
+            base: BasePdu::default(),
+            protocol_id: 0,
+            version: 0,
+            bpdu_type: BpduType::CONFIG,
+            config: Some(config),
+            extra: Vec::new(),
+        }
+    }
+
+    /// Creates an RST BPDU.
+    pub fn rst(config: BpduConfig) -> Self {
+        Self {
+            base: BasePdu::default(),
+            protocol_id: 0,
+            version: 2,
+            bpdu_type: BpduType::RST,
+            config: Some(config),
+            // Version 1 length
+            extra: vec![0],
+        }
+    }
+
+    /// Creates a topology change notification BPDU.
+    pub fn tcn() -> Self {
+        Self {
+            base: BasePdu::default(),
+            protocol_id: 0,
+            version: 0,
+            bpdu_type: BpduType::TCN,
+            config: None,
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn protocol_id(&self) -> u16 {
+        self.protocol_id
+    }
+
+    pub fn protocol_id_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.protocol_id
+    }
+
+    /// 0 for STP, 2 for RSTP, and 3 for MSTP
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn version_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.version
+    }
+
+    pub fn bpdu_type(&self) -> BpduType {
+        self.bpdu_type
+    }
+
+    pub fn bpdu_type_mut(&mut self) -> &mut BpduType {
+        self.base.mark_dirty();
+        &mut self.bpdu_type
+    }
+
+    pub fn bpdu_config(&self) -> Option<&BpduConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn bpdu_config_mut(&mut self) -> &mut Option<BpduConfig> {
+        self.base.mark_dirty();
+        &mut self.config
+    }
+
+    /// The bytes following the configuration, such as the version 1 length
+    /// of RST BPDUs and the MSTP fields.
+    pub fn extra(&self) -> &[u8] {
+        &self.extra[..]
+    }
+
+    pub fn extra_mut(&mut self) -> &mut Vec<u8> {
+        self.base.mark_dirty();
+        &mut self.extra
+    }
+
+    pub fn root_id(&self) -> Option<BridgeId> {
+        self.config.map(|config| config.root_id)
+    }
+
+    pub fn bridge_id(&self) -> Option<BridgeId> {
+        self.config.map(|config| config.bridge_id)
+    }
+
+    /// The role of the sending port, for RST BPDUs
+    pub fn port_role(&self) -> Option<PortRole> {
+        if self.bpdu_type != BpduType::RST {
+            return None;
+        }
+        Some(match (self.config?.flags & flags::PORT_ROLE) >> 2 {
+            1 => PortRole::AlternateOrBackup,
+            2 => PortRole::Root,
+            3 => PortRole::Designated,
+            _ => PortRole::Unknown,
+        })
+    }
+}
+
+impl Dissect for Stp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        _session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        decode_with(|cur| {
+            let protocol_id = cur.decode_be()?;
+            if protocol_id != 0 {
+                return Err(nom::Err::Error(DissectError::Malformed));
+            }
+            let version = cur.decode()?;
+            let bpdu_type = BpduType(cur.decode()?);
+            let config = match bpdu_type {
+                BpduType::TCN => None,
+                _ => Some(BpduConfig::decode(cur)?),
+            };
+            Ok(Self {
+                base: BasePdu::default(),
+                protocol_id,
+                version,
+                bpdu_type,
+                config,
+                extra: Vec::from(cur.rest()),
+            })
+        })(buf)
+    }
+}
+
+impl Pdu for Stp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        4 + self.config.map(|_| 31).unwrap_or(0) + self.extra.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode_be(&self.protocol_id)?
+            .encode(&self.version)?
+            .encode(&self.bpdu_type.0)?;
+        if let Some(config) = &self.config {
+            encoder
+                .encode(&config.flags)?
+                .encode_be(&config.root_id.priority)?
+                .encode(&config.root_id.mac)?
+                .encode_be(&config.root_path_cost)?
+                .encode_be(&config.bridge_id.priority)?
+                .encode(&config.bridge_id.mac)?
+                .encode_be(&config.port_id)?
+                .encode_be(&config.message_age)?
+                .encode_be(&config.max_age)?
+                .encode_be(&config.hello_time)?
+                .encode_be(&config.forward_delay)?;
+        }
+        encoder.encode(&self.extra[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let bpdu_type = self.bpdu_type.to_string();
+        let mut node = dumper.add_node("STP", Some(&bpdu_type[..]))?;
+        node.byte_range(0, 2).add_field(
+            "Protocol ID",
+            DumpValue::UInt(self.protocol_id.into()),
+            None,
+        )?;
+        node.byte_range(2, 1)
+            .add_field("Version", DumpValue::UInt(self.version.into()), None)?;
+        node.byte_range(3, 1).add_field(
+            "BPDU Type",
+            DumpValue::UInt(self.bpdu_type.0.into()),
+            Some(&bpdu_type[..]),
+        )?;
+        if let Some(config) = &self.config {
+            let role = match self.port_role() {
+                Some(PortRole::AlternateOrBackup) => Some("Alternate/Backup"),
+                Some(PortRole::Root) => Some("Root"),
+                Some(PortRole::Designated) => Some("Designated"),
+                Some(PortRole::Unknown) | None => None,
+            };
+            node.byte_range(4, 1)
+                .add_field("Flags", DumpValue::UInt(config.flags.into()), role)?;
+            node.byte_range(5, 8).add_field(
+                "Root Identifier",
+                DumpValue::UInt(config.root_id.priority.into()),
+                Some(&config.root_id.to_string()[..]),
+            )?;
+            node.byte_range(13, 4).add_field(
+                "Root Path Cost",
+                DumpValue::UInt(config.root_path_cost.into()),
+                None,
+            )?;
+            node.byte_range(17, 8).add_field(
+                "Bridge Identifier",
+                DumpValue::UInt(config.bridge_id.priority.into()),
+                Some(&config.bridge_id.to_string()[..]),
+            )?;
+            node.byte_range(25, 2).add_field(
+                "Port Identifier",
+                DumpValue::UInt(config.port_id.into()),
+                Some(&format!("0x{:04x}", config.port_id)[..]),
+            )?;
+            let times = [
+                ("Message Age", config.message_age),
+                ("Max Age", config.max_age),
+                ("Hello Time", config.hello_time),
+                ("Forward Delay", config.forward_delay),
+            ];
+            for (i, (name, val)) in times.into_iter().enumerate() {
+                node.byte_range(27 + 2 * i, 2).add_field(
+                    name,
+                    DumpValue::UInt(val.into()),
+                    Some(&format!("{:?}", stp_time(val))[..]),
+                )?;
+            }
+        }
+        if !self.extra.is_empty() {
+            let offset = self.header_len() - self.extra.len();
+            node.byte_range(offset, self.extra.len()).add_field(
+                "Extra",
+                DumpValue::Bytes(&self.extra[..]),
+                None,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+register_dissector!(
+    stp,
+    LlcSapDissectorTable,
+    STP_SAP,
+    Priority(0),
+    Stp::dissect
+);
+register_field!(stp_root_id, "stp.root_id" => "STP.Root Identifier");
+register_field!(stp_bridge_id, "stp.bridge_id" => "STP.Bridge Identifier");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ethernet_ii::EthernetII;
+    use crate::llc::Llc;
+
+    #[test]
+    fn stp_config() {
+        // A configuration BPDU, as sent to the bridge group address
+        let mut frame = vec![
+            0x01, 0x80, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x26,
+            0x42, 0x42, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x11, 0x22, 0x33,
+            0x44, 0x55, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55,
+            0x80, 0x01, 0x00, 0x00, 0x14, 0x00, 0x02, 0x00, 0x0f, 0x00,
+        ];
+        frame.resize(60, 0);
+        let session = Session::new();
+        let (rem, eth) = EthernetII::dissect(&frame[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert!(eth.is_length_framed());
+        assert_eq!(eth.find::<Llc>().unwrap().dsap(), STP_SAP);
+
+        let stp = eth.find::<Stp>().unwrap();
+        assert_eq!(stp.bpdu_type(), BpduType::CONFIG);
+        let config = stp.bpdu_config().unwrap();
+        assert_eq!(
+            stp.root_id().unwrap().to_string(),
+            "32768/00:11:22:33:44:55"
+        );
+        assert_eq!(config.root_path_cost, 0);
+        assert_eq!(config.port_id, 0x8001);
+        assert_eq!(config.max_age(), Duration::from_secs(20));
+        assert_eq!(config.hello_time(), Duration::from_secs(2));
+        assert_eq!(config.forward_delay(), Duration::from_secs(15));
+        assert!(stp.port_role().is_none());
+        assert_eq!(eth.trailer().len(), 8);
+
+        let mut out = Vec::new();
+        eth.serialize(&mut out).unwrap();
+        assert_eq!(out, frame);
+    }
+
+    #[test]
+    fn stp_rst() {
+        let bridge = BridgeId {
+            priority: 0x1000 + 10,
+            mac: [0x02, 0, 0, 0, 0, 1].into(),
+        };
+        let config = BpduConfig {
+            flags: flags::FORWARDING | flags::LEARNING | flags::PORT_ROLE | flags::AGREEMENT,
+            root_id: bridge,
+            root_path_cost: 20000,
+            bridge_id: bridge,
+            port_id: 0x8002,
+            message_age: 0,
+            max_age: 20 * 256,
+            hello_time: 2 * 256,
+            forward_delay: 15 * 256,
+        };
+        let mut llc = Llc::new(STP_SAP, STP_SAP);
+        llc.set_inner_pdu(Stp::rst(config));
+        let mut eth = EthernetII::new();
+        eth.set_inner_pdu(llc);
+        eth.make_all_canonical();
+        assert_eq!(eth.ethertype().0, 39);
+
+        let mut buf = Vec::new();
+        eth.serialize(&mut buf).unwrap();
+        let session = Session::new();
+        let (_, eth) = EthernetII::dissect(&buf[..], &session, None).unwrap();
+        let stp = eth.find::<Stp>().unwrap();
+        assert_eq!(stp.version(), 2);
+        assert_eq!(stp.bpdu_config(), Some(&config));
+        assert_eq!(stp.port_role(), Some(PortRole::Designated));
+        assert_eq!(stp.extra(), &[0][..]);
+
+        let mut tcn = Stp::tcn();
+        let mut buf = Vec::new();
+        tcn.serialize(&mut buf).unwrap();
+        assert_eq!(buf, [0, 0, 0, 0x80]);
+        tcn = Stp::dissect(&buf[..], &session, None).unwrap().1;
+        assert!(tcn.bpdu_config().is_none());
+    }
+}
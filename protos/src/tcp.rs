@@ -0,0 +1,1119 @@
+use super::ip_proto::{payload_truncated, pseudo_header, IpProto};
+use super::ipv4::{get_inner_most, IpProtoDissectorTable, Ipv4};
+use crate::prelude::*;
+use checksum::{PseudoHeader, U16OnesComplement};
+use nom::{bytes::complete::take, combinator::map, sequence::tuple};
+use parking_lot::Mutex;
+use sniffle_core::{Ipv4Address, LruMap, MemoryAccount};
+
+#[derive(Debug, Clone)]
+pub struct Tcp {
+    base: BasePdu,
+    src_port: u16,
+    dst_port: u16,
+    seq: u32,
+    ack: u32,
+    data_offset: u8,
+    flags: u16,
+    window: u16,
+    chksum: u16,
+    urgent: u16,
+    opts: Vec<TcpOption>,
+    padding: Bytes,
+    analysis: Option<TcpAnalysis>,
+}
+
+/// A TCP option. The end of option list and no-operation options are encoded
+/// without a length or data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpOption {
+    kind: u8,
+    data: Bytes,
+}
+
+/// Expert analysis of a TCP segment within its connection, like
+/// Wireshark's `tcp.analysis` fields. Set during dissection when the
+/// session has a `TcpAnalyzer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpAnalysis {
+    /// The segment resends data that was already seen
+    pub retransmission: bool,
+    /// A retransmission of the data the receiver asked for with duplicate
+    /// ACKs. Also sets `retransmission`.
+    pub fast_retransmission: bool,
+    /// The segment fills a gap left by later data that was already seen
+    pub out_of_order: bool,
+    /// For a duplicate ACK, how many duplicates of the ACK have been seen,
+    /// including this one
+    pub duplicate_ack: Option<u32>,
+    /// The sender's receive window is full
+    pub zero_window: bool,
+    /// The segment only probes whether the connection is still alive
+    pub keep_alive: bool,
+}
+
+/// Session state that enables `TcpAnalysis` of the TCP segments over IPv4
+/// dissected by the session. The analysis depends on the segments seen
+/// before, so packets should be dissected in the order they were captured.
+///
+/// The analyzer tracks the state of each direction of each connection. It
+/// can be limited to a memory budget, in which case the least recently
+/// active connections are forgotten to make room for new ones.
+///
+/// ```ignore
+/// let mut session = Session::new();
+/// let account = session.memory().account(MemoryBudget::CONVERSATIONS);
+/// session.register(TcpAnalyzer::with_memory(account));
+/// ```
+#[derive(Debug, Default)]
+pub struct TcpAnalyzer {
+    flows: Mutex<LruMap<TcpFlowKey, TcpFlowState>>,
+}
+
+type TcpFlowKey = (Ipv4Address, u16, Ipv4Address, u16);
+
+/// The state of one direction of a connection
+#[derive(Debug, Clone)]
+struct TcpFlowState {
+    next_seq: u32,
+    /// Sequence ranges skipped by later data, which haven't been seen yet
+    gaps: Vec<(u32, u32)>,
+    last_ack: Option<u32>,
+    last_window: u16,
+    dup_acks: u32,
+}
+
+/// Maximum number of gaps tracked per direction
+const MAX_GAPS: usize = 16;
+
+/// True if sequence number `a` comes before `b`
+fn seq_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+dissector_table!(pub TcpPortDissectorTable, u16);
+dissector_table!(pub HeurDissectorTable);
+
+register_dissector_table!(TcpPortDissectorTable);
+register_dissector_table!(HeurDissectorTable);
+
+/// TCP flag bits
+pub mod flags {
+    pub const FIN: u16 = 0x001;
+    pub const SYN: u16 = 0x002;
+    pub const RST: u16 = 0x004;
+    pub const PSH: u16 = 0x008;
+    pub const ACK: u16 = 0x010;
+    pub const URG: u16 = 0x020;
+    pub const ECE: u16 = 0x040;
+    pub const CWR: u16 = 0x080;
+    pub const NS: u16 = 0x100;
+}
+
+/// TCP option kinds
+pub mod opt_kind {
+    pub const END: u8 = 0;
+    pub const NOP: u8 = 1;
+    pub const MSS: u8 = 2;
+    pub const WINDOW_SCALE: u8 = 3;
+    pub const SACK_PERMITTED: u8 = 4;
+    pub const SACK: u8 = 5;
+    pub const TIMESTAMPS: u8 = 8;
+}
+
+const FLAGS_MASK: u16 = 0x0FFF;
+
+impl TcpOption {
+    pub fn new<B: Into<Bytes>>(kind: u8, data: B) -> Self {
+        Self {
+            kind,
+            data: data.into(),
+        }
+    }
+
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+
+    pub fn kind_mut(&mut self) -> &mut u8 {
+        &mut self.kind
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    pub fn set_data<B: Into<Bytes>>(&mut self, data: B) {
+        self.data = data.into();
+    }
+
+    fn has_len(&self) -> bool {
+        self.kind != opt_kind::END && self.kind != opt_kind::NOP
+    }
+
+    fn encoded_len(&self) -> usize {
+        if self.has_len() {
+            2 + self.data.len()
+        } else {
+            1
+        }
+    }
+}
+
+fn decode_options(mut buf: &[u8], session: &Session) -> Option<(Vec<TcpOption>, Bytes)> {
+    let mut opts = Vec::new();
+    while !buf.is_empty() {
+        let kind = buf[0];
+        if kind == opt_kind::END {
+            opts.push(TcpOption::new(kind, Bytes::new()));
+            return Some((opts, session.packet_bytes(&buf[1..])));
+        } else if kind == opt_kind::NOP {
+            opts.push(TcpOption::new(kind, Bytes::new()));
+            buf = &buf[1..];
+        } else {
+            let len = *buf.get(1)? as usize;
+            if len < 2 || len > buf.len() {
+                return None;
+            }
+            opts.push(TcpOption::new(kind, session.packet_bytes(&buf[2..len])));
+            buf = &buf[len..];
+        }
+    }
+    Some((opts, Bytes::new()))
+}
+
+impl Tcp {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            src_port: 0,
+            dst_port: 0,
+            seq: 0,
+            ack: 0,
+            data_offset: 5,
+            flags: 0,
+            window: 0,
+            chksum: 0,
+            urgent: 0,
+            opts: Vec::new(),
+            padding: Bytes::new(),
+            analysis: None,
+        }
+    }
+
+    pub fn with_ports(src_port: u16, dst_port: u16) -> Self {
+        let mut tcp = Self::new();
+        tcp.src_port = src_port;
+        tcp.dst_port = dst_port;
+        tcp
+    }
+
+    pub fn src_port(&self) -> u16 {
+        self.src_port
+    }
+
+    pub fn src_port_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.src_port
+    }
+
+    pub fn dst_port(&self) -> u16 {
+        self.dst_port
+    }
+
+    pub fn dst_port_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.dst_port
+    }
+
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    pub fn seq_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.seq
+    }
+
+    pub fn ack(&self) -> u32 {
+        self.ack
+    }
+
+    pub fn ack_mut(&mut self) -> &mut u32 {
+        self.base.mark_dirty();
+        &mut self.ack
+    }
+
+    /// Header length in 32-bit words
+    pub fn data_offset(&self) -> u8 {
+        self.data_offset
+    }
+
+    pub fn data_offset_mut(&mut self) -> &mut u8 {
+        self.base.mark_dirty();
+        &mut self.data_offset
+    }
+
+    pub fn update_data_offset(&mut self) {
+        self.base.mark_dirty();
+        self.data_offset = std::cmp::min(self.header_len() / 4, 15) as u8;
+    }
+
+    /// Flag bits, including the reserved bits. See `flags`.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn flags_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.flags
+    }
+
+    pub fn has_flags(&self, flags: u16) -> bool {
+        (self.flags & flags) == flags
+    }
+
+    pub fn set_flags(&mut self, flags: u16, value: bool) {
+        self.base.mark_dirty();
+        if value {
+            self.flags |= flags;
+        } else {
+            self.flags &= !flags;
+        }
+    }
+
+    pub fn window(&self) -> u16 {
+        self.window
+    }
+
+    pub fn window_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.window
+    }
+
+    pub fn checksum(&self) -> u16 {
+        self.chksum
+    }
+
+    pub fn checksum_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.chksum
+    }
+
+    /// Computes the checksum using the provided IP pseudo-header, from the
+    /// header fields, with the checksum field as zero, and the payload.
+    pub fn calc_checksum<P: PseudoHeader + ?Sized>(&self, pseudo_header: &P) -> u16 {
+        let mut hdr = Vec::with_capacity(self.header_len());
+        let _ = self.serialize_header(&mut hdr);
+        if let Some(chksum) = hdr.get_mut(16..18) {
+            chksum.fill(0);
+        }
+        let mut acc =
+            U16OnesComplement::with_pseudo_header(pseudo_header, IpProto::TCP.0, self.total_len());
+        let _ = acc.encode(&hdr[..]);
+        if let Some(inner) = self.inner_pdu() {
+            let _ = inner.serialize(&mut acc);
+        }
+        acc.checksum()
+    }
+
+    pub fn checksum_valid<P: PseudoHeader + ?Sized>(&self, pseudo_header: &P) -> bool {
+        self.chksum == self.calc_checksum(pseudo_header)
+    }
+
+    pub fn update_checksum<P: PseudoHeader + ?Sized>(&mut self, pseudo_header: &P) {
+        self.base.mark_dirty();
+        self.chksum = self.calc_checksum(pseudo_header);
+    }
+
+    pub fn urgent_pointer(&self) -> u16 {
+        self.urgent
+    }
+
+    pub fn urgent_pointer_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.urgent
+    }
+
+    pub fn options(&self) -> &[TcpOption] {
+        &self.opts[..]
+    }
+
+    pub fn options_mut(&mut self) -> &mut Vec<TcpOption> {
+        self.base.mark_dirty();
+        &mut self.opts
+    }
+
+    /// Returns the first option of the specified kind
+    pub fn option(&self, kind: u8) -> Option<&TcpOption> {
+        self.opts.iter().find(|opt| opt.kind == kind)
+    }
+
+    /// Bytes following the end of option list option
+    pub fn padding(&self) -> &[u8] {
+        &self.padding[..]
+    }
+
+    pub fn set_padding<B: Into<Bytes>>(&mut self, padding: B) {
+        self.base.mark_dirty();
+        self.padding = padding.into();
+    }
+
+    /// Zero pads the options to a multiple of 4 bytes
+    pub fn update_padding(&mut self) {
+        self.base.mark_dirty();
+        let opts_len: usize = self.opts.iter().map(|opt| opt.encoded_len()).sum();
+        self.padding = Bytes::from_static(&[0u8; 3][..(4 - opts_len % 4) % 4]);
+    }
+
+    pub fn mss(&self) -> Option<u16> {
+        self.option(opt_kind::MSS)
+            .filter(|opt| opt.data.len() == 2)
+            .map(|opt| u16::from_be_bytes([opt.data[0], opt.data[1]]))
+    }
+
+    pub fn window_scale(&self) -> Option<u8> {
+        self.option(opt_kind::WINDOW_SCALE)
+            .filter(|opt| opt.data.len() == 1)
+            .map(|opt| opt.data[0])
+    }
+
+    pub fn sack_permitted(&self) -> bool {
+        self.option(opt_kind::SACK_PERMITTED).is_some()
+    }
+
+    /// Left and right edges of the selective acknowledgment blocks
+    pub fn sack_blocks(&self) -> Vec<(u32, u32)> {
+        self.option(opt_kind::SACK)
+            .map(|opt| {
+                opt.data
+                    .chunks_exact(8)
+                    .map(|block| {
+                        (
+                            u32::from_be_bytes([block[0], block[1], block[2], block[3]]),
+                            u32::from_be_bytes([block[4], block[5], block[6], block[7]]),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Expert analysis of the segment, if the session that dissected it
+    /// has a `TcpAnalyzer`
+    pub fn analysis(&self) -> Option<&TcpAnalysis> {
+        self.analysis.as_ref()
+    }
+
+    pub fn analysis_mut(&mut self) -> &mut Option<TcpAnalysis> {
+        self.base.mark_dirty();
+        &mut self.analysis
+    }
+
+    /// Timestamp value and echo reply
+    pub fn timestamps(&self) -> Option<(u32, u32)> {
+        self.option(opt_kind::TIMESTAMPS)
+            .filter(|opt| opt.data.len() == 8)
+            .map(|opt| {
+                let data = &opt.data[..];
+                (
+                    u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                    u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+                )
+            })
+    }
+}
+
+impl Dissect for Tcp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (buf, (src_port, dst_port, seq, ack, off_flags, window, chksum, urgent)) =
+            tuple((
+                u16::decode_be,
+                u16::decode_be,
+                u32::decode_be,
+                u32::decode_be,
+                u16::decode_be,
+                u16::decode_be,
+                u16::decode_be,
+                u16::decode_be,
+            ))(buf)?;
+        let data_offset = (off_flags >> 12) as u8;
+        if data_offset < 5 {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let (payload, opt_buf) = take((data_offset as usize - 5) * 4)(buf)?;
+        let (opts, padding) =
+            decode_options(opt_buf, session).ok_or(nom::Err::Error(DissectError::Malformed))?;
+        let mut tcp = Self {
+            base: BasePdu::default(),
+            src_port,
+            dst_port,
+            seq,
+            ack,
+            data_offset,
+            flags: off_flags & FLAGS_MASK,
+            window,
+            chksum,
+            urgent,
+            opts,
+            padding,
+            analysis: None,
+        };
+        if let (Some(analyzer), Some(ipv4)) = (
+            session.get::<TcpAnalyzer>(),
+            parent.as_ref().and_then(|parent| parent.find_pdu::<Ipv4>()),
+        ) {
+            tcp.analysis =
+                Some(analyzer.analyze(ipv4.src_address(), ipv4.dst_address(), &tcp, payload.len()));
+        }
+        if !payload.is_empty() {
+            // The lower port is more likely to be the well known port
+            let (lo, hi) = if src_port < dst_port {
+                (src_port, dst_port)
+            } else {
+                (dst_port, src_port)
+            };
+            let (rem, mut inner) = session
+                .table_dissector::<TcpPortDissectorTable>(&lo, Some(TempPdu::new(&tcp, &parent)))
+                .or(session.table_dissector::<TcpPortDissectorTable>(
+                    &hi,
+                    Some(TempPdu::new(&tcp, &parent)),
+                ))
+                .or(session
+                    .table_dissector::<HeurDissectorTable>(&(), Some(TempPdu::new(&tcp, &parent))))
+                .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                .parse(payload)?;
+            if !rem.is_empty() {
+                get_inner_most(&mut inner)
+                    .set_inner_pdu(AnyPdu::new(RawPdu::new(session.packet_bytes(rem))));
+            }
+            tcp.set_inner_pdu(inner);
+        }
+        Ok((&payload[payload.len()..], tcp))
+    }
+}
+
+impl Pdu for Tcp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        20 + self.opts.iter().map(|opt| opt.encoded_len()).sum::<usize>() + self.padding.len()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode_be(&self.src_port)?
+            .encode_be(&self.dst_port)?
+            .encode_be(&self.seq)?
+            .encode_be(&self.ack)?
+            .encode_be(&(((self.data_offset as u16) << 12) | (self.flags & FLAGS_MASK)))?
+            .encode_be(&self.window)?
+            .encode_be(&self.chksum)?
+            .encode_be(&self.urgent)?;
+        for opt in self.opts.iter() {
+            encoder.encode(&opt.kind)?;
+            if opt.has_len() {
+                encoder
+                    .encode(&((opt.data.len() + 2) as u8))?
+                    .encode(&opt.data[..])?;
+            }
+        }
+        encoder.encode(&self.padding[..])?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node(
+            "TCP",
+            Some(&format!("{} -> {}", self.src_port, self.dst_port)[..]),
+        )?;
+        node.byte_range(0, 2).add_field(
+            "Source Port",
+            DumpValue::UInt(self.src_port.into()),
+            None,
+        )?;
+        node.byte_range(2, 2).add_field(
+            "Destination Port",
+            DumpValue::UInt(self.dst_port.into()),
+            None,
+        )?;
+        node.byte_range(4, 4).add_field(
+            "Sequence Number",
+            DumpValue::UInt(self.seq.into()),
+            None,
+        )?;
+        node.byte_range(8, 4).add_field(
+            "Acknowledgment Number",
+            DumpValue::UInt(self.ack.into()),
+            None,
+        )?;
+        node.byte_range(12, 1).add_field(
+            "Data Offset",
+            DumpValue::UInt(self.data_offset.into()),
+            Some(&format!("{} bytes", self.data_offset as usize * 4)[..]),
+        )?;
+        {
+            let mut node = node
+                .byte_range(12, 2)
+                .add_node("Flags", Some(&format!("0x{:03x}", self.flags)[..]))?;
+            for (name, flag) in [
+                ("NS", flags::NS),
+                ("CWR", flags::CWR),
+                ("ECE", flags::ECE),
+                ("URG", flags::URG),
+                ("ACK", flags::ACK),
+                ("PSH", flags::PSH),
+                ("RST", flags::RST),
+                ("SYN", flags::SYN),
+                ("FIN", flags::FIN),
+            ] {
+                node.add_field(name, DumpValue::Bool(self.has_flags(flag)), None)?;
+            }
+        }
+        node.byte_range(14, 2)
+            .add_field("Window", DumpValue::UInt(self.window.into()), None)?;
+        node.byte_range(16, 2).add_field(
+            "Checksum",
+            DumpValue::UInt(self.chksum.into()),
+            Some(&format!("0x{:04x}", self.chksum)[..]),
+        )?;
+        node.byte_range(18, 2).add_field(
+            "Urgent Pointer",
+            DumpValue::UInt(self.urgent.into()),
+            None,
+        )?;
+        if !self.opts.is_empty() {
+            let mut node = node.add_node("Options", None)?;
+            let mut offset = 20;
+            for opt in self.opts.iter() {
+                let len = opt.encoded_len();
+                let name = match opt.kind {
+                    opt_kind::END => "End of Option List",
+                    opt_kind::NOP => "No-Operation",
+                    opt_kind::MSS => "Maximum Segment Size",
+                    opt_kind::WINDOW_SCALE => "Window Scale",
+                    opt_kind::SACK_PERMITTED => "SACK Permitted",
+                    opt_kind::SACK => "SACK",
+                    opt_kind::TIMESTAMPS => "Timestamps",
+                    _ => "Unknown",
+                };
+                let mut node = node.byte_range(offset, len).add_node(name, None)?;
+                node.add_field("Kind", DumpValue::UInt(opt.kind.into()), None)?;
+                if opt.has_len() {
+                    node.add_field("Length", DumpValue::UInt((opt.data.len() + 2) as u64), None)?;
+                    node.add_field("Data", DumpValue::Bytes(&opt.data[..]), None)?;
+                }
+                offset += len;
+            }
+        }
+        if let Some(analysis) = self.analysis.filter(|analysis| analysis.is_flagged()) {
+            let mut node = node.add_node("Analysis", Some(&analysis.to_string()[..]))?;
+            for (name, flag) in [
+                ("Retransmission", analysis.retransmission),
+                ("Fast Retransmission", analysis.fast_retransmission),
+                ("Out of Order", analysis.out_of_order),
+                ("Zero Window", analysis.zero_window),
+                ("Keep Alive", analysis.keep_alive),
+            ] {
+                if flag {
+                    node.add_field(name, DumpValue::Bool(true), None)?;
+                }
+            }
+            if let Some(count) = analysis.duplicate_ack {
+                node.add_field("Duplicate ACK", DumpValue::Bool(true), None)?;
+                node.add_field("Duplicate ACK Number", DumpValue::UInt(count.into()), None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_padding();
+        self.update_data_offset();
+        if let Some(pseudo_header) = pseudo_header(self) {
+            self.update_checksum(&pseudo_header);
+        }
+    }
+
+    fn validate(&self, validator: &mut Validator<'_>) {
+        // Packet::validate reports the truncation itself
+        if payload_truncated(self) {
+            return;
+        }
+        if let Some(pseudo_header) = pseudo_header(self) {
+            validator.checksum("TCP", self.chksum, self.checksum_valid(&pseudo_header));
+        }
+    }
+}
+
+impl Default for Tcp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TcpAnalysis {
+    /// True if any of the flags are set
+    pub fn is_flagged(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+impl std::fmt::Display for TcpAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names = Vec::new();
+        if self.fast_retransmission {
+            names.push(String::from("Fast Retransmission"));
+        } else if self.retransmission {
+            names.push(String::from("Retransmission"));
+        }
+        if self.out_of_order {
+            names.push(String::from("Out of Order"));
+        }
+        if let Some(count) = self.duplicate_ack {
+            names.push(format!("Duplicate ACK #{}", count));
+        }
+        if self.zero_window {
+            names.push(String::from("Zero Window"));
+        }
+        if self.keep_alive {
+            names.push(String::from("Keep Alive"));
+        }
+        f.write_str(&names.join(", ")[..])
+    }
+}
+
+impl TcpAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an analyzer that charges its connection state to `account`.
+    pub fn with_memory(account: MemoryAccount) -> Self {
+        Self {
+            flows: Mutex::new(LruMap::new(account)),
+        }
+    }
+
+    /// Forgets all connections.
+    pub fn clear(&self) {
+        self.flows.lock().clear();
+    }
+
+    /// Analyzes a segment from `src` to `dst` with `payload_len` bytes of
+    /// payload, and updates the state of its connection.
+    fn analyze(
+        &self,
+        src: Ipv4Address,
+        dst: Ipv4Address,
+        tcp: &Tcp,
+        payload_len: usize,
+    ) -> TcpAnalysis {
+        let control = tcp.flags & (flags::SYN | flags::FIN | flags::RST);
+        let mut analysis = TcpAnalysis {
+            zero_window: tcp.window == 0 && control == 0,
+            ..Default::default()
+        };
+        // SYN and FIN each take up a sequence number
+        let seg_len = payload_len as u32
+            + tcp.has_flags(flags::SYN) as u32
+            + tcp.has_flags(flags::FIN) as u32;
+        let seg_end = tcp.seq.wrapping_add(seg_len);
+        let ack = tcp.has_flags(flags::ACK).then_some(tcp.ack);
+
+        let mut flows = self.flows.lock();
+        let reverse = flows
+            .peek(&(dst, tcp.dst_port, src, tcp.src_port))
+            .map(|state| (state.last_ack, state.dup_acks));
+        let key = (src, tcp.src_port, dst, tcp.dst_port);
+        let state = match flows.get_mut(&key) {
+            Some(state) => state,
+            None => {
+                let state = TcpFlowState {
+                    next_seq: seg_end,
+                    gaps: Vec::new(),
+                    last_ack: ack,
+                    last_window: tcp.window,
+                    dup_acks: 0,
+                };
+                let size = std::mem::size_of::<(TcpFlowKey, TcpFlowState)>();
+                let _ = flows.insert(key, state, size);
+                return analysis;
+            }
+        };
+
+        analysis.keep_alive =
+            seg_len <= 1 && control == 0 && tcp.seq == state.next_seq.wrapping_sub(1);
+        if seg_len > 0 && !analysis.keep_alive {
+            if seq_before(tcp.seq, state.next_seq) {
+                let gap = state.gaps.iter().position(|(start, end)| {
+                    !seq_before(tcp.seq, *start) && seq_before(tcp.seq, *end)
+                });
+                if matches!(reverse, Some((Some(ack), dups)) if ack == tcp.seq && dups >= 2) {
+                    analysis.retransmission = true;
+                    analysis.fast_retransmission = true;
+                } else if let Some(idx) = gap {
+                    analysis.out_of_order = true;
+                    let (start, end) = state.gaps.remove(idx);
+                    if seq_before(start, tcp.seq) {
+                        state.gaps.push((start, tcp.seq));
+                    }
+                    if seq_before(seg_end, end) {
+                        state.gaps.push((seg_end, end));
+                    }
+                } else {
+                    analysis.retransmission = true;
+                }
+            } else if seq_before(state.next_seq, tcp.seq) && state.gaps.len() < MAX_GAPS {
+                state.gaps.push((state.next_seq, tcp.seq));
+            }
+            if seq_before(state.next_seq, seg_end) {
+                state.next_seq = seg_end;
+            }
+        }
+
+        if seg_len == 0
+            && control == 0
+            && !analysis.keep_alive
+            && ack.is_some()
+            && ack == state.last_ack
+            && tcp.window == state.last_window
+        {
+            state.dup_acks += 1;
+            analysis.duplicate_ack = Some(state.dup_acks);
+        } else if ack != state.last_ack {
+            state.dup_acks = 0;
+        }
+        if ack.is_some() {
+            state.last_ack = ack;
+        }
+        state.last_window = tcp.window;
+        analysis
+    }
+}
+
+register_dissector!(
+    tcp,
+    IpProtoDissectorTable,
+    IpProto::TCP,
+    Priority(0),
+    Tcp::dissect
+);
+crate::register_ip_proto_pdu!(Tcp, IpProto::TCP);
+register_field!(tcp_srcport, "tcp.srcport" => "TCP.Source Port");
+register_field!(tcp_dstport, "tcp.dstport" => "TCP.Destination Port");
+register_field!(tcp_port, "tcp.port" => "TCP.Source Port", "TCP.Destination Port");
+register_field!(
+    tcp_analysis_duplicate_ack_num,
+    "tcp.analysis.duplicate_ack_num" => "TCP.Analysis.Duplicate ACK Number"
+);
+register_conversation!(
+    tcp,
+    "TCP",
+    ["ipv4.src_addr", "tcp.srcport"] => ["ipv4.dst_addr", "tcp.dstport"]
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipv4::Ipv4;
+
+    #[test]
+    fn tcp_syn() {
+        let data = [
+            0xc0, 0x01, 0x00, 0x50, // Ports
+            0x00, 0x00, 0x10, 0x00, // Sequence number
+            0x00, 0x00, 0x00, 0x00, // Acknowledgment number
+            0xa0, 0x02, 0xfa, 0xf0, // Data offset, flags, window
+            0x00, 0x00, 0x00, 0x00, // Checksum, urgent pointer
+            0x02, 0x04, 0x05, 0xb4, // MSS
+            0x04, 0x02, // SACK permitted
+            0x08, 0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // Timestamps
+            0x01, // NOP
+            0x03, 0x03, 0x07, // Window scale
+        ];
+        let session = Session::new();
+        let (rem, tcp) = Tcp::dissect(&data[..], &session, None).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(tcp.dst_port(), 80);
+        assert_eq!(tcp.seq(), 0x1000);
+        assert!(tcp.has_flags(flags::SYN));
+        assert!(!tcp.has_flags(flags::ACK));
+        assert_eq!(tcp.mss(), Some(1460));
+        assert!(tcp.sack_permitted());
+        assert_eq!(tcp.timestamps(), Some((1, 0)));
+        assert_eq!(tcp.window_scale(), Some(7));
+
+        let ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        let mut tcp = tcp;
+        tcp.make_canonical();
+        tcp.update_checksum(&ipv4.pseudo_header());
+        assert!(tcp.checksum_valid(&ipv4.pseudo_header()));
+
+        let mut out = Vec::new();
+        tcp.serialize(&mut out).unwrap();
+        assert_eq!(&out[..16], &data[..16]);
+        assert_eq!(&out[20..], &data[20..]);
+    }
+
+    #[test]
+    fn checksum_of_dissected_segment() {
+        let mut data = [
+            0x45, 0x00, 0x00, 0x2a, 0x00, 0x01, 0x00, 0x00, // IPv4
+            0x40, 0x06, 0x66, 0xcb, 0x0a, 0x00, 0x00, 0x01, //
+            0x0a, 0x00, 0x00, 0x02, //
+            0x04, 0xd2, 0x00, 0x50, // Ports
+            0x00, 0x00, 0x00, 0x01, // Sequence number
+            0x00, 0x00, 0x00, 0x00, // Acknowledgment number
+            0x50, 0x18, 0x04, 0x00, // Data offset, flags, window
+            0x2a, 0x3c, 0x00, 0x00, // Checksum, urgent pointer
+            0x68, 0x69, // Payload
+        ];
+        // Dissected from a packet buffer, as sniffed packets are, so the
+        // captured bytes are kept
+        let session = Session::new();
+        let dissect = |data: &[u8]| {
+            let buffer = Bytes::copy_from_slice(data);
+            let res = session.with_packet_buffer(&buffer, || {
+                Ipv4::dissect(&buffer[..], &session, None).map(|(_, ipv4)| ipv4)
+            });
+            res.unwrap()
+        };
+        let ipv4 = dissect(&data[..]);
+        let tcp = ipv4.find::<Tcp>().unwrap();
+        assert!(tcp.base_pdu().original_bytes().is_some());
+        assert_eq!(tcp.calc_checksum(&ipv4.pseudo_header()), 0x2a3c);
+        assert!(tcp.checksum_valid(&ipv4.pseudo_header()));
+
+        data[41] ^= 1;
+        let ipv4 = dissect(&data[..]);
+        let tcp = ipv4.find::<Tcp>().unwrap();
+        assert!(!tcp.checksum_valid(&ipv4.pseudo_header()));
+    }
+
+    #[test]
+    fn query_ports() {
+        use sniffle_core::Packet;
+
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        *ipv4.ttl_mut() = 5;
+        ipv4.set_inner_pdu(Tcp::with_ports(1234, 443));
+        let packet = Packet::new(std::time::SystemTime::UNIX_EPOCH, ipv4, None, None, None);
+
+        assert_eq!(packet.get("tcp.dstport").unwrap().to_string(), "443");
+        assert_eq!(packet.get("ipv4.src_addr").unwrap().to_string(), "10.0.0.1");
+        assert!(packet.matches("tcp.port == 443 && ipv4.ttl < 10").unwrap());
+        assert!(packet
+            .matches("tcp.port == 1234 and ipv4.addr == 10.0.0.2")
+            .unwrap());
+        assert!(!packet.matches("tcp.srcport == 443 || udp").unwrap());
+    }
+
+    #[test]
+    fn make_canonical_after_dissection() {
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        ipv4.set_inner_pdu(Tcp::with_ports(1234, 80));
+        ipv4.make_all_canonical();
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+
+        let session = Session::new();
+        let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        let mut pdu = ipv4.into_any_pdu();
+        let ip = pdu.find::<Ipv4>().unwrap().pseudo_header();
+        assert!(pdu.find::<Tcp>().unwrap().checksum_valid(&ip));
+
+        // The TCP layer finds the modified IP layer through its parent link,
+        // without going through `make_all_canonical`
+        *pdu.find_mut::<Ipv4>().unwrap().src_address_mut() = [10, 0, 0, 3].into();
+        pdu.link_parent_pdus();
+        let ip = pdu.find::<Ipv4>().unwrap().pseudo_header();
+        let tcp = pdu.find_mut::<Tcp>().unwrap();
+        assert!(!tcp.checksum_valid(&ip));
+        tcp.make_canonical();
+        assert!(tcp.checksum_valid(&ip));
+    }
+
+    #[test]
+    fn validate_offloaded_checksum() {
+        use sniffle_core::{Issue, Packet, Validation};
+
+        let mut ipv4 = Ipv4::new();
+        ipv4.set_inner_pdu(Tcp::with_ports(1234, 80));
+        ipv4.make_all_canonical();
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+        buf[36] = 0;
+        buf[37] = 0;
+
+        let session = Session::new();
+        let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        let mut pkt = Packet::new(std::time::SystemTime::now(), ipv4, Some(64), None, None);
+        let report = pkt.validate(&Validation::new());
+        assert_eq!(
+            report.issues(),
+            [
+                Issue::Truncated {
+                    captured: 40,
+                    original: 64
+                },
+                Issue::BadChecksum { protocol: "TCP" }
+            ]
+        );
+        assert!(!report.is_valid());
+
+        let mut pkt = Packet::new(
+            std::time::SystemTime::now(),
+            pkt.into_pdu(),
+            None,
+            None,
+            None,
+        );
+        let report = pkt.validate(&Validation::new().offload_zero_checksums(true));
+        assert_eq!(
+            report.issues(),
+            [Issue::OffloadedChecksum { protocol: "TCP" }]
+        );
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn validate_correct_checksum() {
+        use sniffle_core::{Packet, Validation};
+
+        let mut tcp = Tcp::with_ports(1234, 80);
+        tcp.set_inner_pdu(RawPdu::new(vec![1, 2, 3, 4]));
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        ipv4.set_inner_pdu(tcp);
+        ipv4.make_all_canonical();
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+
+        let session = Session::new();
+        let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        assert_ne!(ipv4.find::<Tcp>().unwrap().checksum(), 0);
+        let mut pkt = Packet::new(std::time::SystemTime::now(), ipv4, None, None, None);
+        let report = pkt.validate(&Validation::new());
+        assert!(report.issues().is_empty());
+    }
+
+    #[test]
+    fn validate_truncated_segment() {
+        use sniffle_core::{Issue, Packet, Validation};
+
+        let mut tcp = Tcp::with_ports(1234, 80);
+        tcp.set_inner_pdu(RawPdu::new(vec![1, 2, 3, 4]));
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        ipv4.set_inner_pdu(tcp);
+        ipv4.make_all_canonical();
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+        buf.truncate(buf.len() - 2);
+
+        // Marked as the IPv4 dissector does when the capture cut the packet
+        let session = Session::new();
+        let (_, mut ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        ipv4.base_pdu_mut().set_truncated_len(2);
+        let mut pkt = Packet::new(std::time::SystemTime::now(), ipv4, Some(44), None, None);
+        let report = pkt.validate(&Validation::new());
+        assert_eq!(
+            report.issues(),
+            [Issue::Truncated {
+                captured: 42,
+                original: 44
+            }]
+        );
+    }
+
+    #[test]
+    fn tcp_analysis() {
+        use sniffle_core::{IoGraph, Packet, Query};
+
+        let client = Ipv4Address::from([10, 0, 0, 1]);
+        let server = Ipv4Address::from([10, 0, 0, 2]);
+        let mut session = Session::new();
+        session.register(TcpAnalyzer::new());
+        let segment = |to_server: bool, seq: u32, ack: u32, window: u16, len: usize| {
+            let (src, dst) = if to_server {
+                ((client, 40000), (server, 80))
+            } else {
+                ((server, 80), (client, 40000))
+            };
+            let mut tcp = Tcp::with_ports(src.1, dst.1);
+            *tcp.seq_mut() = seq;
+            *tcp.ack_mut() = ack;
+            *tcp.window_mut() = window;
+            tcp.set_flags(flags::ACK, true);
+            if len > 0 {
+                tcp.set_inner_pdu(RawPdu::new(vec![0u8; len]));
+            }
+            let mut ipv4 = Ipv4::with_addresses(src.0, dst.0);
+            ipv4.set_inner_pdu(tcp);
+            ipv4.make_all_canonical();
+            let mut buf = Vec::new();
+            ipv4.serialize(&mut buf).unwrap();
+            let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+            Packet::new(std::time::SystemTime::UNIX_EPOCH, ipv4, None, None, None)
+        };
+        let analysis = |packet: &Packet| *packet.find::<Tcp>().unwrap().analysis().unwrap();
+
+        assert!(!analysis(&segment(true, 1000, 5000, 1000, 100)).is_flagged());
+        assert!(!analysis(&segment(false, 5000, 1100, 1000, 0)).is_flagged());
+        // Skips 1100..1200
+        assert!(!analysis(&segment(true, 1200, 5000, 1000, 100)).is_flagged());
+        let dup = segment(false, 5000, 1100, 1000, 0);
+        assert_eq!(analysis(&dup).duplicate_ack, Some(1));
+        let dup = segment(false, 5000, 1100, 1000, 0);
+        assert_eq!(analysis(&dup).duplicate_ack, Some(2));
+        assert_eq!(
+            dup.get("tcp.analysis.duplicate_ack_num")
+                .unwrap()
+                .to_string(),
+            "2"
+        );
+        let fast = analysis(&segment(true, 1100, 5000, 1000, 100));
+        assert!(fast.fast_retransmission && fast.retransmission);
+        assert_eq!(fast.to_string(), "Fast Retransmission");
+
+        // Skips 1300..1400, then fills the gap
+        assert!(!analysis(&segment(true, 1400, 5000, 1000, 100)).is_flagged());
+        let ooo = analysis(&segment(true, 1300, 5000, 1000, 100));
+        assert!(ooo.out_of_order && !ooo.retransmission);
+        let retrans = segment(true, 1300, 5000, 1000, 100);
+        assert!(analysis(&retrans).retransmission);
+        assert!(retrans.matches("tcp.analysis.retransmission").unwrap());
+        assert!(!retrans.matches("tcp.analysis.out_of_order").unwrap());
+        let mut graph = IoGraph::new(std::time::Duration::from_secs(1)).filter(
+            "Retransmissions",
+            Query::parse("tcp.analysis.retransmission").unwrap(),
+        );
+        graph.add(&retrans);
+        graph.add(&segment(true, 1400, 5000, 1000, 100));
+        let buckets = graph.into_buckets();
+        assert_eq!(buckets[0].packets, 2);
+        assert_eq!(buckets[0].filter("Retransmissions").unwrap().packets, 2);
+
+        assert!(analysis(&segment(true, 1499, 5000, 1000, 0)).keep_alive);
+        let zero = analysis(&segment(false, 5000, 1500, 0, 0));
+        assert_eq!(
+            zero,
+            TcpAnalysis {
+                zero_window: true,
+                ..Default::default()
+            }
+        );
+    }
+}
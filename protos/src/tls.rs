@@ -0,0 +1,846 @@
+use super::tcp::{HeurDissectorTable, TcpPortDissectorTable};
+use crate::prelude::*;
+use nom::{bytes::complete::take, sequence::tuple};
+
+/// TCP port HTTPS is served on
+pub const HTTPS_TCP_PORT: u16 = 443;
+
+/// Maximum record length, including the expansion allowed for encrypted
+/// records
+const MAX_RECORD_LEN: u16 = 0x4000 + 2048;
+
+/// One or more TLS records, as found in a single TCP segment.
+///
+/// A record split across segments is not dissected. Records following the
+/// change cipher spec are encrypted, so handshake records that cannot be
+/// parsed are kept as opaque data.
+#[derive(Debug, Clone)]
+pub struct Tls {
+    base: BasePdu,
+    records: Vec<Record>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub content_type: ContentType,
+    pub version: u16,
+    pub content: Content,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentType(pub u8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    ChangeCipherSpec(u8),
+    Alert(Alert),
+    Handshake(Vec<Handshake>),
+    /// Application data, encrypted handshake messages, or content that could
+    /// not be parsed
+    Opaque(Bytes),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alert {
+    pub level: u8,
+    pub description: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Handshake {
+    ClientHello(ClientHello),
+    ServerHello(ServerHello),
+    Other {
+        msg_type: HandshakeType,
+        body: Bytes,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandshakeType(pub u8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHello {
+    pub version: u16,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suites: Vec<u16>,
+    pub compression_methods: Vec<u8>,
+    pub extensions: Vec<Extension>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerHello {
+    pub version: u16,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suite: u16,
+    pub compression_method: u8,
+    pub extensions: Vec<Extension>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub ext_type: u16,
+    pub data: Bytes,
+}
+
+/// Extension type values
+pub mod ext_type {
+    pub const SERVER_NAME: u16 = 0;
+    pub const SUPPORTED_GROUPS: u16 = 10;
+    pub const SIGNATURE_ALGORITHMS: u16 = 13;
+    pub const ALPN: u16 = 16;
+    pub const SUPPORTED_VERSIONS: u16 = 43;
+    pub const KEY_SHARE: u16 = 51;
+}
+
+/// Alert levels
+pub mod alert_level {
+    pub const WARNING: u8 = 1;
+    pub const FATAL: u8 = 2;
+}
+
+impl ContentType {
+    pub const CHANGE_CIPHER_SPEC: Self = Self(20);
+    pub const ALERT: Self = Self(21);
+    pub const HANDSHAKE: Self = Self(22);
+    pub const APPLICATION_DATA: Self = Self(23);
+    pub const HEARTBEAT: Self = Self(24);
+}
+
+impl std::fmt::Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::CHANGE_CIPHER_SPEC => "Change Cipher Spec",
+            Self::ALERT => "Alert",
+            Self::HANDSHAKE => "Handshake",
+            Self::APPLICATION_DATA => "Application Data",
+            Self::HEARTBEAT => "Heartbeat",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+impl HandshakeType {
+    pub const HELLO_REQUEST: Self = Self(0);
+    pub const CLIENT_HELLO: Self = Self(1);
+    pub const SERVER_HELLO: Self = Self(2);
+    pub const NEW_SESSION_TICKET: Self = Self(4);
+    pub const ENCRYPTED_EXTENSIONS: Self = Self(8);
+    pub const CERTIFICATE: Self = Self(11);
+    pub const SERVER_KEY_EXCHANGE: Self = Self(12);
+    pub const CERTIFICATE_REQUEST: Self = Self(13);
+    pub const SERVER_HELLO_DONE: Self = Self(14);
+    pub const CERTIFICATE_VERIFY: Self = Self(15);
+    pub const CLIENT_KEY_EXCHANGE: Self = Self(16);
+    pub const FINISHED: Self = Self(20);
+}
+
+impl std::fmt::Display for HandshakeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::HELLO_REQUEST => "Hello Request",
+            Self::CLIENT_HELLO => "Client Hello",
+            Self::SERVER_HELLO => "Server Hello",
+            Self::NEW_SESSION_TICKET => "New Session Ticket",
+            Self::ENCRYPTED_EXTENSIONS => "Encrypted Extensions",
+            Self::CERTIFICATE => "Certificate",
+            Self::SERVER_KEY_EXCHANGE => "Server Key Exchange",
+            Self::CERTIFICATE_REQUEST => "Certificate Request",
+            Self::SERVER_HELLO_DONE => "Server Hello Done",
+            Self::CERTIFICATE_VERIFY => "Certificate Verify",
+            Self::CLIENT_KEY_EXCHANGE => "Client Key Exchange",
+            Self::FINISHED => "Finished",
+            _ => return write!(f, "Unknown ({})", self.0),
+        };
+        f.write_str(name)
+    }
+}
+
+fn version_name(version: u16) -> String {
+    match version {
+        0x0300 => String::from("SSL 3.0"),
+        0x0301 => String::from("TLS 1.0"),
+        0x0302 => String::from("TLS 1.1"),
+        0x0303 => String::from("TLS 1.2"),
+        0x0304 => String::from("TLS 1.3"),
+        _ => format!("0x{:04x}", version),
+    }
+}
+
+fn length_prefixed(len_size: usize, buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let hdr = buf.get(..len_size)?;
+    let len = hdr.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+    let data = buf.get(len_size..len_size + len)?;
+    Some((data, &buf[len_size + len..]))
+}
+
+fn decode_extensions(buf: &[u8], session: &Session) -> Option<Vec<Extension>> {
+    if buf.is_empty() {
+        return Some(Vec::new());
+    }
+    let (mut buf, rest) = length_prefixed(2, buf)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    let mut exts = Vec::new();
+    while !buf.is_empty() {
+        let ext_type = u16::from_be_bytes([*buf.first()?, *buf.get(1)?]);
+        let (data, rest) = length_prefixed(2, &buf[2..])?;
+        exts.push(Extension {
+            ext_type,
+            data: session.packet_bytes(data),
+        });
+        buf = rest;
+    }
+    Some(exts)
+}
+
+fn encode_extensions(exts: &[Extension], out: &mut Vec<u8>) {
+    if exts.is_empty() {
+        return;
+    }
+    let len: usize = exts.iter().map(|ext| 4 + ext.data.len()).sum();
+    out.extend_from_slice(&(len as u16).to_be_bytes());
+    for ext in exts.iter() {
+        out.extend_from_slice(&ext.ext_type.to_be_bytes());
+        out.extend_from_slice(&(ext.data.len() as u16).to_be_bytes());
+        out.extend_from_slice(&ext.data[..]);
+    }
+}
+
+fn find_extension(exts: &[Extension], ext_type: u16) -> Option<&[u8]> {
+    exts.iter()
+        .find(|ext| ext.ext_type == ext_type)
+        .map(|ext| &ext.data[..])
+}
+
+fn alpn_protocols(data: &[u8]) -> Vec<String> {
+    let mut protos = Vec::new();
+    if let Some((mut list, _)) = length_prefixed(2, data) {
+        while let Some((proto, rest)) = length_prefixed(1, list) {
+            protos.push(String::from_utf8_lossy(proto).into_owned());
+            list = rest;
+        }
+    }
+    protos
+}
+
+impl ClientHello {
+    fn decode(buf: &[u8], session: &Session) -> Option<Self> {
+        let version = u16::from_be_bytes([*buf.first()?, *buf.get(1)?]);
+        let random: [u8; 32] = buf.get(2..34)?.try_into().ok()?;
+        let (session_id, buf) = length_prefixed(1, &buf[34..])?;
+        let (suites, buf) = length_prefixed(2, buf)?;
+        let (compression_methods, buf) = length_prefixed(1, buf)?;
+        Some(Self {
+            version,
+            random,
+            session_id: Vec::from(session_id),
+            cipher_suites: suites
+                .chunks_exact(2)
+                .map(|suite| u16::from_be_bytes([suite[0], suite[1]]))
+                .collect(),
+            compression_methods: Vec::from(compression_methods),
+            extensions: decode_extensions(buf, session)?,
+        })
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.version.to_be_bytes());
+        out.extend_from_slice(&self.random[..]);
+        out.push(self.session_id.len() as u8);
+        out.extend_from_slice(&self.session_id[..]);
+        out.extend_from_slice(&((self.cipher_suites.len() * 2) as u16).to_be_bytes());
+        for suite in self.cipher_suites.iter() {
+            out.extend_from_slice(&suite.to_be_bytes());
+        }
+        out.push(self.compression_methods.len() as u8);
+        out.extend_from_slice(&self.compression_methods[..]);
+        encode_extensions(&self.extensions[..], out);
+    }
+
+    pub fn extension(&self, ext_type: u16) -> Option<&[u8]> {
+        find_extension(&self.extensions[..], ext_type)
+    }
+
+    /// Host name from the server name indication extension
+    pub fn server_name(&self) -> Option<&str> {
+        let (mut list, _) = length_prefixed(2, self.extension(ext_type::SERVER_NAME)?)?;
+        while list.len() >= 3 {
+            let name_type = list[0];
+            let (name, rest) = length_prefixed(2, &list[1..])?;
+            if name_type == 0 {
+                return std::str::from_utf8(name).ok();
+            }
+            list = rest;
+        }
+        None
+    }
+
+    /// Protocols offered in the application layer protocol negotiation
+    /// extension
+    pub fn alpn_protocols(&self) -> Vec<String> {
+        self.extension(ext_type::ALPN)
+            .map(alpn_protocols)
+            .unwrap_or_default()
+    }
+
+    /// Versions offered in the supported versions extension
+    pub fn supported_versions(&self) -> Vec<u16> {
+        self.extension(ext_type::SUPPORTED_VERSIONS)
+            .and_then(|data| length_prefixed(1, data))
+            .map(|(list, _)| {
+                list.chunks_exact(2)
+                    .map(|version| u16::from_be_bytes([version[0], version[1]]))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl ServerHello {
+    fn decode(buf: &[u8], session: &Session) -> Option<Self> {
+        let version = u16::from_be_bytes([*buf.first()?, *buf.get(1)?]);
+        let random: [u8; 32] = buf.get(2..34)?.try_into().ok()?;
+        let (session_id, buf) = length_prefixed(1, &buf[34..])?;
+        let cipher_suite = u16::from_be_bytes([*buf.first()?, *buf.get(1)?]);
+        let compression_method = *buf.get(2)?;
+        Some(Self {
+            version,
+            random,
+            session_id: Vec::from(session_id),
+            cipher_suite,
+            compression_method,
+            extensions: decode_extensions(&buf[3..], session)?,
+        })
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.version.to_be_bytes());
+        out.extend_from_slice(&self.random[..]);
+        out.push(self.session_id.len() as u8);
+        out.extend_from_slice(&self.session_id[..]);
+        out.extend_from_slice(&self.cipher_suite.to_be_bytes());
+        out.push(self.compression_method);
+        encode_extensions(&self.extensions[..], out);
+    }
+
+    pub fn extension(&self, ext_type: u16) -> Option<&[u8]> {
+        find_extension(&self.extensions[..], ext_type)
+    }
+
+    /// Protocol selected in the application layer protocol negotiation
+    /// extension
+    pub fn alpn_protocol(&self) -> Option<String> {
+        self.extension(ext_type::ALPN)
+            .map(alpn_protocols)
+            .and_then(|protos| protos.into_iter().next())
+    }
+
+    /// The negotiated version, taking the supported versions extension into
+    /// account
+    pub fn selected_version(&self) -> u16 {
+        self.extension(ext_type::SUPPORTED_VERSIONS)
+            .filter(|data| data.len() == 2)
+            .map(|data| u16::from_be_bytes([data[0], data[1]]))
+            .unwrap_or(self.version)
+    }
+}
+
+impl Handshake {
+    pub fn msg_type(&self) -> HandshakeType {
+        match self {
+            Self::ClientHello(_) => HandshakeType::CLIENT_HELLO,
+            Self::ServerHello(_) => HandshakeType::SERVER_HELLO,
+            Self::Other { msg_type, .. } => *msg_type,
+        }
+    }
+
+    fn body(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        match self {
+            Self::ClientHello(hello) => hello.encode(&mut body),
+            Self::ServerHello(hello) => hello.encode(&mut body),
+            Self::Other { body: data, .. } => body.extend_from_slice(&data[..]),
+        }
+        body
+    }
+
+    fn decode_all(mut buf: &[u8], session: &Session) -> Option<Vec<Self>> {
+        let mut msgs = Vec::new();
+        while !buf.is_empty() {
+            let msg_type = HandshakeType(*buf.first()?);
+            let (body, rest) = length_prefixed(3, &buf[1..])?;
+            msgs.push(match msg_type {
+                HandshakeType::CLIENT_HELLO => {
+                    Self::ClientHello(ClientHello::decode(body, session)?)
+                }
+                HandshakeType::SERVER_HELLO => {
+                    Self::ServerHello(ServerHello::decode(body, session)?)
+                }
+                _ => Self::Other {
+                    msg_type,
+                    body: session.packet_bytes(body),
+                },
+            });
+            buf = rest;
+        }
+        Some(msgs)
+    }
+}
+
+impl Content {
+    fn decode(content_type: ContentType, data: &[u8], session: &Session) -> Self {
+        let content = match content_type {
+            ContentType::CHANGE_CIPHER_SPEC if data.len() == 1 => {
+                Some(Self::ChangeCipherSpec(data[0]))
+            }
+            ContentType::ALERT if data.len() == 2 => Some(Self::Alert(Alert {
+                level: data[0],
+                description: data[1],
+            })),
+            ContentType::HANDSHAKE => Handshake::decode_all(data, session).map(Self::Handshake),
+            _ => None,
+        };
+        content.unwrap_or_else(|| Self::Opaque(session.packet_bytes(data)))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::ChangeCipherSpec(val) => vec![*val],
+            Self::Alert(alert) => vec![alert.level, alert.description],
+            Self::Handshake(msgs) => {
+                let mut out = Vec::new();
+                for msg in msgs.iter() {
+                    let body = msg.body();
+                    out.push(msg.msg_type().0);
+                    out.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+                    out.extend_from_slice(&body[..]);
+                }
+                out
+            }
+            Self::Opaque(data) => Vec::from(&data[..]),
+        }
+    }
+}
+
+impl Record {
+    fn header_ok(content_type: u8, version: u16, len: u16) -> bool {
+        (20..=24).contains(&content_type)
+            && (version >> 8) == 3
+            && (version & 0xFF) <= 4
+            && len <= MAX_RECORD_LEN
+    }
+
+    fn dissect<'a>(buf: &'a [u8], session: &Session) -> DResult<'a, Self> {
+        let (buf, (content_type, version, len)) =
+            tuple((u8::decode, u16::decode_be, u16::decode_be))(buf)?;
+        if !Self::header_ok(content_type, version, len) {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let (buf, data) = take(len as usize)(buf)?;
+        let content_type = ContentType(content_type);
+        Ok((
+            buf,
+            Self {
+                content_type,
+                version,
+                content: Content::decode(content_type, data, session),
+            },
+        ))
+    }
+}
+
+impl Tls {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            records: Vec::new(),
+        }
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records[..]
+    }
+
+    pub fn records_mut(&mut self) -> &mut Vec<Record> {
+        self.base.mark_dirty();
+        &mut self.records
+    }
+
+    /// All handshake messages in the records
+    pub fn handshakes(&self) -> impl Iterator<Item = &Handshake> {
+        self.records
+            .iter()
+            .flat_map(|record| match &record.content {
+                Content::Handshake(msgs) => &msgs[..],
+                _ => &[],
+            })
+    }
+
+    pub fn client_hello(&self) -> Option<&ClientHello> {
+        self.handshakes().find_map(|msg| match msg {
+            Handshake::ClientHello(hello) => Some(hello),
+            _ => None,
+        })
+    }
+
+    pub fn server_hello(&self) -> Option<&ServerHello> {
+        self.handshakes().find_map(|msg| match msg {
+            Handshake::ServerHello(hello) => Some(hello),
+            _ => None,
+        })
+    }
+
+    /// Dissects TLS only if the data starts with a plausible record header.
+    /// Used for detecting TLS on non-standard ports.
+    pub fn dissect_heuristic<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        if buf.len() < 5 {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let content_type = buf[0];
+        let version = u16::from_be_bytes([buf[1], buf[2]]);
+        let len = u16::from_be_bytes([buf[3], buf[4]]);
+        if !Record::header_ok(content_type, version, len)
+            || content_type == ContentType::HEARTBEAT.0
+        {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        Self::dissect(buf, session, parent)
+    }
+}
+
+impl Dissect for Tls {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        _parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (mut buf, first) = Record::dissect(buf, session)?;
+        let mut records = vec![first];
+        while let Ok((rem, record)) = Record::dissect(buf, session) {
+            records.push(record);
+            buf = rem;
+        }
+        Ok((
+            buf,
+            Self {
+                base: BasePdu::default(),
+                records,
+            },
+        ))
+    }
+}
+
+impl Pdu for Tls {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        self.records
+            .iter()
+            .map(|record| 5 + record.content.encode().len())
+            .sum()
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        for record in self.records.iter() {
+            let content = record.content.encode();
+            encoder
+                .encode(&record.content_type.0)?
+                .encode_be(&record.version)?
+                .encode_be(&(content.len() as u16))?
+                .encode(&content[..])?;
+        }
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node("TLS", None)?;
+        let mut offset = 0;
+        for record in self.records.iter() {
+            let content = record.content.encode();
+            let mut rec_node = node
+                .byte_range(offset, 5 + content.len())
+                .add_node("Record", Some(&record.content_type.to_string()[..]))?;
+            rec_node.byte_range(offset, 1).add_field(
+                "Content Type",
+                DumpValue::UInt(record.content_type.0.into()),
+                Some(&record.content_type.to_string()[..]),
+            )?;
+            rec_node.byte_range(offset + 1, 2).add_field(
+                "Version",
+                DumpValue::UInt(record.version.into()),
+                Some(&version_name(record.version)[..]),
+            )?;
+            rec_node.byte_range(offset + 3, 2).add_field(
+                "Length",
+                DumpValue::UInt(content.len() as u64),
+                None,
+            )?;
+            match &record.content {
+                Content::ChangeCipherSpec(val) => {
+                    rec_node.add_field(
+                        "Change Cipher Spec",
+                        DumpValue::UInt((*val).into()),
+                        None,
+                    )?;
+                }
+                Content::Alert(alert) => {
+                    let level = match alert.level {
+                        alert_level::WARNING => "Warning",
+                        alert_level::FATAL => "Fatal",
+                        _ => "Unknown",
+                    };
+                    rec_node.add_field(
+                        "Alert Level",
+                        DumpValue::UInt(alert.level.into()),
+                        Some(level),
+                    )?;
+                    rec_node.add_field(
+                        "Alert Description",
+                        DumpValue::UInt(alert.description.into()),
+                        None,
+                    )?;
+                }
+                Content::Handshake(msgs) => {
+                    for msg in msgs.iter() {
+                        let mut msg_node = rec_node
+                            .add_node("Handshake", Some(&msg.msg_type().to_string()[..]))?;
+                        msg_node.add_field(
+                            "Type",
+                            DumpValue::UInt(msg.msg_type().0.into()),
+                            Some(&msg.msg_type().to_string()[..]),
+                        )?;
+                        match msg {
+                            Handshake::ClientHello(hello) => {
+                                msg_node.add_field(
+                                    "Version",
+                                    DumpValue::UInt(hello.version.into()),
+                                    Some(&version_name(hello.version)[..]),
+                                )?;
+                                msg_node.add_field(
+                                    "Random",
+                                    DumpValue::Bytes(&hello.random[..]),
+                                    None,
+                                )?;
+                                msg_node.add_field(
+                                    "Session ID",
+                                    DumpValue::Bytes(&hello.session_id[..]),
+                                    None,
+                                )?;
+                                {
+                                    let mut suites = msg_node.add_node("Cipher Suites", None)?;
+                                    for suite in hello.cipher_suites.iter() {
+                                        suites.add_field(
+                                            "Cipher Suite",
+                                            DumpValue::UInt((*suite).into()),
+                                            Some(&format!("0x{:04x}", suite)[..]),
+                                        )?;
+                                    }
+                                }
+                                if let Some(name) = hello.server_name() {
+                                    msg_node.add_field(
+                                        "Server Name",
+                                        DumpValue::Text(name),
+                                        None,
+                                    )?;
+                                }
+                                for proto in hello.alpn_protocols() {
+                                    msg_node.add_field(
+                                        "ALPN Protocol",
+                                        DumpValue::Text(&proto),
+                                        None,
+                                    )?;
+                                }
+                                dump_extensions(&mut msg_node, &hello.extensions[..])?;
+                            }
+                            Handshake::ServerHello(hello) => {
+                                msg_node.add_field(
+                                    "Version",
+                                    DumpValue::UInt(hello.selected_version().into()),
+                                    Some(&version_name(hello.selected_version())[..]),
+                                )?;
+                                msg_node.add_field(
+                                    "Random",
+                                    DumpValue::Bytes(&hello.random[..]),
+                                    None,
+                                )?;
+                                msg_node.add_field(
+                                    "Session ID",
+                                    DumpValue::Bytes(&hello.session_id[..]),
+                                    None,
+                                )?;
+                                msg_node.add_field(
+                                    "Cipher Suite",
+                                    DumpValue::UInt(hello.cipher_suite.into()),
+                                    Some(&format!("0x{:04x}", hello.cipher_suite)[..]),
+                                )?;
+                                if let Some(proto) = hello.alpn_protocol() {
+                                    msg_node.add_field(
+                                        "ALPN Protocol",
+                                        DumpValue::Text(&proto),
+                                        None,
+                                    )?;
+                                }
+                                dump_extensions(&mut msg_node, &hello.extensions[..])?;
+                            }
+                            Handshake::Other { body, .. } => {
+                                msg_node.add_field("Body", DumpValue::Bytes(&body[..]), None)?;
+                            }
+                        }
+                    }
+                }
+                Content::Opaque(data) => {
+                    rec_node.add_field("Data", DumpValue::Bytes(&data[..]), None)?;
+                }
+            }
+            offset += 5 + content.len();
+        }
+        Ok(())
+    }
+}
+
+fn dump_extensions<D: Dump + ?Sized>(
+    node: &mut NodeDumper<D>,
+    exts: &[Extension],
+) -> Result<(), D::Error> {
+    if exts.is_empty() {
+        return Ok(());
+    }
+    let mut node = node.add_node("Extensions", None)?;
+    for ext in exts.iter() {
+        node.add_field(
+            "Extension",
+            DumpValue::Bytes(&ext.data[..]),
+            Some(&format!("type {}", ext.ext_type)[..]),
+        )?;
+    }
+    Ok(())
+}
+
+impl Default for Tls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    tls,
+    TcpPortDissectorTable,
+    HTTPS_TCP_PORT,
+    Priority(0),
+    Tls::dissect
+);
+
+register_dissector!(
+    tls,
+    HeurDissectorTable,
+    (),
+    Priority(0),
+    Tls::dissect_heuristic
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tcp::Tcp;
+
+    #[test]
+    fn tls_client_hello() {
+        let mut ext_sni = vec![0x00, 0x0e, 0x00, 0x00, 0x0b];
+        ext_sni.extend_from_slice(b"example.com");
+        let ext_alpn = [0x00, 0x0c, 0x02, b'h', b'2', 0x08]
+            .iter()
+            .copied()
+            .chain(b"http/1.1".iter().copied())
+            .collect::<Vec<_>>();
+        let hello = ClientHello {
+            version: 0x0303,
+            random: [0x11; 32],
+            session_id: vec![0x22; 32],
+            cipher_suites: vec![0x1301, 0x1302, 0xc02f],
+            compression_methods: vec![0],
+            extensions: vec![
+                Extension {
+                    ext_type: ext_type::SERVER_NAME,
+                    data: ext_sni.into(),
+                },
+                Extension {
+                    ext_type: ext_type::ALPN,
+                    data: ext_alpn.into(),
+                },
+                Extension {
+                    ext_type: ext_type::SUPPORTED_VERSIONS,
+                    data: Bytes::from_static(&[0x04, 0x03, 0x04, 0x03, 0x03]),
+                },
+            ],
+        };
+        let mut tls = Tls::new();
+        tls.records_mut().push(Record {
+            content_type: ContentType::HANDSHAKE,
+            version: 0x0301,
+            content: Content::Handshake(vec![Handshake::ClientHello(hello.clone())]),
+        });
+        tls.records_mut().push(Record {
+            content_type: ContentType::ALERT,
+            version: 0x0303,
+            content: Content::Alert(Alert {
+                level: alert_level::WARNING,
+                description: 0,
+            }),
+        });
+
+        // TLS on a non-standard port is found by the heuristic dissector
+        let mut tcp = Tcp::with_ports(50000, 8443);
+        tcp.set_inner_pdu(tls);
+        tcp.make_canonical();
+        let mut buf = Vec::new();
+        tcp.serialize(&mut buf).unwrap();
+
+        let session = Session::new();
+        let (_, tcp) = Tcp::dissect(&buf[..], &session, None).unwrap();
+        let tls = tcp.find::<Tls>().unwrap();
+        assert_eq!(tls.records().len(), 2);
+        let dissected = tls.client_hello().unwrap();
+        assert_eq!(dissected, &hello);
+        assert_eq!(dissected.server_name(), Some("example.com"));
+        assert_eq!(dissected.alpn_protocols(), ["h2", "http/1.1"]);
+        assert_eq!(dissected.supported_versions(), [0x0304, 0x0303]);
+        assert_eq!(
+            tls.records()[1].content,
+            Content::Alert(Alert {
+                level: alert_level::WARNING,
+                description: 0
+            })
+        );
+
+        let mut out = Vec::new();
+        tcp.serialize(&mut out).unwrap();
+        assert_eq!(out, buf);
+
+        // Non-TLS data is not claimed by the heuristic dissector
+        let mut tcp = Tcp::with_ports(50000, 8080);
+        tcp.set_inner_pdu(RawPdu::new(Vec::from(&b"GET / HTTP/1.1\r\n\r\n"[..])));
+        tcp.make_canonical();
+        let mut buf = Vec::new();
+        tcp.serialize(&mut buf).unwrap();
+        let (_, tcp) = Tcp::dissect(&buf[..], &session, None).unwrap();
+        assert!(tcp.find::<Tls>().is_none());
+    }
+}
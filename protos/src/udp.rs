@@ -0,0 +1,416 @@
+use super::ip_proto::{payload_truncated, pseudo_header, IpProto};
+use super::ipv4::{get_inner_most, IpProtoDissectorTable};
+use crate::prelude::*;
+use checksum::{PseudoHeader, U16OnesComplement};
+use nom::{combinator::map, sequence::tuple};
+
+#[derive(Debug, Clone)]
+pub struct Udp {
+    base: BasePdu,
+    src_port: u16,
+    dst_port: u16,
+    length: u16,
+    chksum: u16,
+}
+
+dissector_table!(pub UdpPortDissectorTable, u16);
+dissector_table!(pub HeurDissectorTable);
+
+register_dissector_table!(UdpPortDissectorTable);
+register_dissector_table!(HeurDissectorTable);
+
+impl Udp {
+    pub fn new() -> Self {
+        Self {
+            base: BasePdu::default(),
+            src_port: 0,
+            dst_port: 0,
+            length: 8,
+            chksum: 0,
+        }
+    }
+
+    pub fn with_ports(src_port: u16, dst_port: u16) -> Self {
+        Self {
+            base: BasePdu::default(),
+            src_port,
+            dst_port,
+            length: 8,
+            chksum: 0,
+        }
+    }
+
+    pub fn src_port(&self) -> u16 {
+        self.src_port
+    }
+
+    pub fn src_port_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.src_port
+    }
+
+    pub fn dst_port(&self) -> u16 {
+        self.dst_port
+    }
+
+    pub fn dst_port_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.dst_port
+    }
+
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn length_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.length
+    }
+
+    pub fn update_length(&mut self) {
+        self.base.mark_dirty();
+        self.length = self.total_len().try_into().unwrap_or(0xFFFF);
+    }
+
+    /// The checksum field. A value of zero means no checksum was computed.
+    pub fn checksum(&self) -> u16 {
+        self.chksum
+    }
+
+    pub fn checksum_mut(&mut self) -> &mut u16 {
+        self.base.mark_dirty();
+        &mut self.chksum
+    }
+
+    /// Computes the checksum using the provided IP pseudo-header, from the
+    /// header fields, with the checksum field as zero, and the payload.
+    pub fn calc_checksum<P: PseudoHeader + ?Sized>(&self, pseudo_header: &P) -> u16 {
+        let mut acc =
+            U16OnesComplement::with_pseudo_header(pseudo_header, IpProto::UDP.0, self.total_len());
+        let _ = acc
+            .encode_be(&self.src_port)
+            .and_then(|acc| acc.encode_be(&self.dst_port))
+            .and_then(|acc| acc.encode_be(&self.length))
+            .and_then(|acc| acc.encode_be(&0u16));
+        if let Some(inner) = self.inner_pdu() {
+            let _ = inner.serialize(&mut acc);
+        }
+        match acc.checksum() {
+            0 => 0xFFFF,
+            chksum => chksum,
+        }
+    }
+
+    /// Checks the checksum, or returns `None` if the checksum is unused.
+    pub fn checksum_valid<P: PseudoHeader + ?Sized>(&self, pseudo_header: &P) -> Option<bool> {
+        if self.chksum == 0 {
+            None
+        } else {
+            Some(self.chksum == self.calc_checksum(pseudo_header))
+        }
+    }
+
+    pub fn update_checksum<P: PseudoHeader + ?Sized>(&mut self, pseudo_header: &P) {
+        self.base.mark_dirty();
+        self.chksum = self.calc_checksum(pseudo_header);
+    }
+}
+
+impl Dissect for Udp {
+    fn dissect<'a>(
+        buf: &'a [u8],
+        session: &Session,
+        parent: Option<TempPdu<'_>>,
+    ) -> DResult<'a, Self> {
+        let (buf, (src_port, dst_port, length, chksum)) = tuple((
+            u16::decode_be,
+            u16::decode_be,
+            u16::decode_be,
+            u16::decode_be,
+        ))(buf)?;
+        if length < 8 {
+            return Err(nom::Err::Error(DissectError::Malformed));
+        }
+        let mut udp = Self {
+            base: BasePdu::default(),
+            src_port,
+            dst_port,
+            length,
+            chksum,
+        };
+        let payload_len = std::cmp::min(length as usize - 8, buf.len());
+        let (payload, rem) = buf.split_at(payload_len);
+        if !payload.is_empty() {
+            // The lower port is more likely to be the well known port
+            let (lo, hi) = if src_port < dst_port {
+                (src_port, dst_port)
+            } else {
+                (dst_port, src_port)
+            };
+            let (payload_rem, mut inner) = session
+                .table_dissector::<UdpPortDissectorTable>(&lo, Some(TempPdu::new(&udp, &parent)))
+                .or(session.table_dissector::<UdpPortDissectorTable>(
+                    &hi,
+                    Some(TempPdu::new(&udp, &parent)),
+                ))
+                .or(session
+                    .table_dissector::<HeurDissectorTable>(&(), Some(TempPdu::new(&udp, &parent))))
+                .or(map(RawPdu::dissector(session, None), AnyPdu::new))
+                .parse(payload)?;
+            if !payload_rem.is_empty() {
+                get_inner_most(&mut inner)
+                    .set_inner_pdu(AnyPdu::new(RawPdu::new(session.packet_bytes(payload_rem))));
+            }
+            udp.set_inner_pdu(inner);
+        }
+        let missing = (length as usize - 8) - payload_len;
+        if missing > 0 && session.truncated_len() > 0 {
+            let marked = udp.wire_len() - udp.total_len();
+            udp.base_pdu_mut()
+                .set_truncated_len(missing.saturating_sub(marked));
+        }
+        Ok((rem, udp))
+    }
+}
+
+impl Pdu for Udp {
+    fn base_pdu(&self) -> &BasePdu {
+        &self.base
+    }
+
+    fn base_pdu_mut(&mut self) -> &mut BasePdu {
+        &mut self.base
+    }
+
+    fn header_len(&self) -> usize {
+        8
+    }
+
+    fn serialize_header<'a, W: Encoder<'a> + ?Sized>(
+        &self,
+        encoder: &mut W,
+    ) -> std::io::Result<()> {
+        encoder
+            .encode_be(&self.src_port)?
+            .encode_be(&self.dst_port)?
+            .encode_be(&self.length)?
+            .encode_be(&self.chksum)?;
+        Ok(())
+    }
+
+    fn dump<D: Dump + ?Sized>(&self, dumper: &mut NodeDumper<D>) -> Result<(), D::Error> {
+        let mut node = dumper.add_node(
+            "UDP",
+            Some(&format!("{} -> {}", self.src_port, self.dst_port)[..]),
+        )?;
+        node.byte_range(0, 2).add_field(
+            "Source Port",
+            DumpValue::UInt(self.src_port.into()),
+            None,
+        )?;
+        node.byte_range(2, 2).add_field(
+            "Destination Port",
+            DumpValue::UInt(self.dst_port.into()),
+            None,
+        )?;
+        node.byte_range(4, 2)
+            .add_field("Length", DumpValue::UInt(self.length.into()), None)?;
+        node.byte_range(6, 2).add_field(
+            "Checksum",
+            DumpValue::UInt(self.chksum.into()),
+            Some(&format!("0x{:04x}", self.chksum)[..]),
+        )
+    }
+
+    fn make_canonical(&mut self) {
+        self.update_length();
+        if let Some(pseudo_header) = pseudo_header(self) {
+            self.update_checksum(&pseudo_header);
+        }
+    }
+
+    fn validate(&self, validator: &mut Validator<'_>) {
+        // Packet::validate reports the truncation itself
+        if payload_truncated(self) {
+            return;
+        }
+        if let Some(pseudo_header) = pseudo_header(self) {
+            if let Some(valid) = self.checksum_valid(&pseudo_header) {
+                validator.checksum("UDP", self.chksum, valid);
+            }
+        }
+    }
+}
+
+impl Default for Udp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+register_dissector!(
+    udp,
+    IpProtoDissectorTable,
+    IpProto::UDP,
+    Priority(0),
+    Udp::dissect
+);
+crate::register_ip_proto_pdu!(Udp, IpProto::UDP);
+register_field!(udp_srcport, "udp.srcport" => "UDP.Source Port");
+register_field!(udp_dstport, "udp.dstport" => "UDP.Destination Port");
+register_field!(udp_port, "udp.port" => "UDP.Source Port", "UDP.Destination Port");
+register_conversation!(
+    udp,
+    "UDP",
+    ["ipv4.src_addr", "udp.srcport"] => ["ipv4.dst_addr", "udp.dstport"]
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ethernet_ii::EthernetII;
+    use crate::ipv4::Ipv4;
+
+    #[test]
+    fn udp_ipv4() {
+        let mut ipv4 = Ipv4::with_addresses([192, 168, 0, 1].into(), [192, 168, 0, 2].into());
+        let mut udp = Udp::with_ports(5000, 5001);
+        udp.set_inner_pdu(RawPdu::new(vec![1, 2, 3, 4, 5]));
+        udp.make_canonical();
+        udp.update_checksum(&ipv4.pseudo_header());
+        ipv4.set_inner_pdu(udp);
+        ipv4.make_canonical();
+
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+        let session = Session::new();
+        let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        let udp = ipv4.find::<Udp>().unwrap();
+        assert_eq!(udp.length(), 13);
+        assert_ne!(udp.checksum(), 0);
+        assert_eq!(udp.checksum_valid(&ipv4.pseudo_header()), Some(true));
+        assert_eq!(udp.inner_pdu().unwrap().total_len(), 5);
+    }
+
+    #[test]
+    fn checksum_of_dissected_datagram() {
+        let mut data = [
+            0x45, 0x00, 0x00, 0x1e, 0x00, 0x01, 0x00, 0x00, // IPv4
+            0x40, 0x11, 0x66, 0xcc, 0x0a, 0x00, 0x00, 0x01, //
+            0x0a, 0x00, 0x00, 0x02, //
+            0x04, 0xd2, 0x00, 0x35, // Ports
+            0x00, 0x0a, 0x7e, 0x67, // Length, checksum
+            0x68, 0x69, // Payload
+        ];
+        // Dissected from a packet buffer, as sniffed packets are, so the
+        // captured bytes are kept
+        let session = Session::new();
+        let dissect = |data: &[u8]| {
+            let buffer = Bytes::copy_from_slice(data);
+            let res = session.with_packet_buffer(&buffer, || {
+                Ipv4::dissect(&buffer[..], &session, None).map(|(_, ipv4)| ipv4)
+            });
+            res.unwrap()
+        };
+        let ipv4 = dissect(&data[..]);
+        let udp = ipv4.find::<Udp>().unwrap();
+        assert!(udp.base_pdu().original_bytes().is_some());
+        assert_eq!(udp.calc_checksum(&ipv4.pseudo_header()), 0x7e67);
+        assert_eq!(udp.checksum_valid(&ipv4.pseudo_header()), Some(true));
+
+        data[29] ^= 1;
+        let ipv4 = dissect(&data[..]);
+        let udp = ipv4.find::<Udp>().unwrap();
+        assert_eq!(udp.checksum_valid(&ipv4.pseudo_header()), Some(false));
+    }
+
+    #[test]
+    fn validate_truncated_datagram() {
+        use sniffle_core::{Issue, Packet, Validation};
+
+        let mut udp = Udp::with_ports(5000, 5001);
+        udp.set_inner_pdu(RawPdu::new(vec![1, 2, 3, 4, 5]));
+        let mut ipv4 = Ipv4::with_addresses([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        ipv4.set_inner_pdu(udp);
+        ipv4.make_all_canonical();
+        let mut buf = Vec::new();
+        ipv4.serialize(&mut buf).unwrap();
+        buf.truncate(buf.len() - 3);
+
+        // Marked as the UDP dissector does when the capture cut the packet
+        let session = Session::new();
+        let (_, mut ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+        ipv4.find_mut::<Udp>()
+            .unwrap()
+            .base_pdu_mut()
+            .set_truncated_len(3);
+        let mut pkt = Packet::new(std::time::SystemTime::now(), ipv4, Some(33), None, None);
+        let report = pkt.validate(&Validation::new());
+        assert_eq!(
+            report.issues(),
+            [Issue::Truncated {
+                captured: 30,
+                original: 33
+            }]
+        );
+    }
+
+    #[test]
+    fn udp_flow_records() {
+        use sniffle_core::{EndpointAddress, FlowRecord, Packet, Timestamp, TrafficSummary};
+
+        let session = Session::new();
+        let mut summary = TrafficSummary::new();
+        for (src, dst) in [((1, 5000), (2, 53)), ((2, 53), (1, 5000))] {
+            let mut udp = Udp::with_ports(src.1, dst.1);
+            udp.set_inner_pdu(RawPdu::new(vec![1, 2, 3, 4]));
+            let mut ipv4 = Ipv4::with_addresses([10, 0, 0, src.0].into(), [10, 0, 0, dst.0].into());
+            ipv4.set_inner_pdu(udp);
+            ipv4.make_all_canonical();
+            let mut buf = Vec::new();
+            ipv4.serialize(&mut buf).unwrap();
+            let (_, ipv4) = Ipv4::dissect(&buf[..], &session, None).unwrap();
+            summary.add(&Packet::new(Timestamp::new(1, 0), ipv4, None, None, None));
+        }
+
+        let convs = summary.conversations("UDP");
+        assert_eq!(convs.len(), 1);
+        assert_eq!(
+            convs[0].a.address,
+            EndpointAddress::Ipv4([10, 0, 0, 1].into())
+        );
+        assert_eq!((convs[0].a.port, convs[0].b.port), (Some(5000), Some(53)));
+        assert_eq!(convs[0].a.to_string(), "10.0.0.1:5000");
+
+        let records = FlowRecord::from_summary(&summary);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].src_addr, [10, 0, 0, 2].into());
+        assert_eq!((records[1].src_port, records[1].dst_port), (53, 5000));
+        assert_eq!(records[1].protocol, 17);
+    }
+
+    #[test]
+    fn udp_auto_checksum() {
+        let mut udp = Udp::with_ports(5000, 5001);
+        udp.set_inner_pdu(RawPdu::new(vec![1, 2, 3, 4, 5]));
+        let mut ipv4 = Ipv4::with_addresses([192, 168, 0, 1].into(), [192, 168, 0, 2].into());
+        ipv4.set_inner_pdu(udp);
+        let mut eth = EthernetII::new();
+        eth.set_inner_pdu(ipv4);
+        eth.make_all_canonical();
+        let ipv4 = eth.find::<Ipv4>().unwrap();
+        let udp = eth.find::<Udp>().unwrap();
+        assert_eq!(udp.checksum_valid(&ipv4.pseudo_header()), Some(true));
+
+        // Parent links of a dissected packet are refreshed before use
+        let mut buf = Vec::new();
+        eth.serialize(&mut buf).unwrap();
+        let session = Session::new();
+        let (_, mut eth) = EthernetII::dissect(&buf[..], &session, None).unwrap();
+        *eth.find_mut::<Ipv4>().unwrap().dst_address_mut() = [10, 0, 0, 1].into();
+        eth.make_all_canonical();
+        let ipv4 = eth.find::<Ipv4>().unwrap();
+        let udp = eth.find::<Udp>().unwrap();
+        assert_eq!(udp.checksum_valid(&ipv4.pseudo_header()), Some(true));
+    }
+}
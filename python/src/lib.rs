@@ -0,0 +1,629 @@
+#![doc = include_str!("../README.md")]
+// The `PyResult` returned by `#[pymethods]` is converted by the generated code
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyAttributeError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use sniffle::capfile::{pcap, pcapng, FileSniffer};
+use sniffle::dissect::{DissectMode, Session as RawSession};
+use sniffle::pdu::{Bytes, Field as RawField, FieldMap, FieldValue};
+use sniffle::sniff::{LinkType, LinkTypeTable, Sniff};
+use sniffle::transmit::Transmit;
+use sniffle::{Error, Timestamp};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::runtime::Runtime;
+
+/// A dissection session. Packets are dissected with all of the registered
+/// dissectors.
+#[pyclass(name = "Session", module = "sniffle", frozen)]
+struct PySession(RawSession);
+
+/// A capture file being read. Iterating it yields each `Packet`.
+#[pyclass(name = "Capture", module = "sniffle")]
+struct PyCapture {
+    rt: Runtime,
+    sniffer: Option<Box<dyn Sniff>>,
+}
+
+/// A dissected packet.
+///
+/// Layers are attributes named by their protocol, in lower case with spaces
+/// replaced by `_`, and their fields are attributes of the layer, such as
+/// `packet.ethernet_ii.src_address`. Fields can also be looked up by name,
+/// as in a query, with `packet["ipv4.ttl"]`.
+#[pyclass(name = "Packet", module = "sniffle", frozen)]
+struct PyPacket(sniffle::Packet);
+
+/// A layer of a `Packet`, whose fields are its attributes.
+#[pyclass(name = "Layer", module = "sniffle", frozen)]
+struct PyLayer {
+    packet: Py<PyPacket>,
+    name: String,
+}
+
+/// A field of a `Packet`, with its value and description.
+#[pyclass(name = "Field", module = "sniffle", frozen)]
+struct PyField(RawField);
+
+/// A capture file being written.
+#[pyclass(name = "Writer", module = "sniffle")]
+struct PyWriter {
+    rt: Runtime,
+    recorder: Option<Recorder>,
+}
+
+enum Recorder {
+    Pcap(pcap::FileRecorder),
+    PcapNG(pcapng::FileRecorder),
+}
+
+fn runtime() -> PyResult<Runtime> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
+
+fn py_err(err: Error) -> PyErr {
+    match err {
+        Error::Io(err) => err.into(),
+        err => PyValueError::new_err(err.to_string()),
+    }
+}
+
+fn closed() -> PyErr {
+    PyValueError::new_err("I/O operation on closed file")
+}
+
+fn default_session() -> &'static RawSession {
+    static SESSION: OnceLock<RawSession> = OnceLock::new();
+    SESSION.get_or_init(RawSession::new)
+}
+
+/// The name of a layer or field as a Python identifier, as in a query.
+fn key(name: &str) -> String {
+    name.to_lowercase().replace(' ', "_")
+}
+
+fn seconds(ts: Timestamp) -> f64 {
+    ts.secs() as f64 + f64::from(ts.subsec_nanos()) / 1e9
+}
+
+fn value(py: Python<'_>, value: &FieldValue) -> PyObject {
+    let since_epoch = |time: &SystemTime| match time.duration_since(UNIX_EPOCH) {
+        Ok(dur) => dur.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    match value {
+        FieldValue::Bool(val) => val.into_py(py),
+        FieldValue::Int(val) => val.into_py(py),
+        FieldValue::UInt(val) => val.into_py(py),
+        FieldValue::Float(val) => val.into_py(py),
+        FieldValue::Text(val) => val.into_py(py),
+        FieldValue::Bytes(val) => PyBytes::new_bound(py, &val[..]).into_py(py),
+        FieldValue::Time(val) => since_epoch(val).into_py(py),
+        FieldValue::Duration(val) => val.as_secs_f64().into_py(py),
+    }
+}
+
+fn dissect(
+    session: &RawSession,
+    link_type: u16,
+    data: &[u8],
+    timestamp: Option<f64>,
+    length: Option<usize>,
+) -> PyResult<PyPacket> {
+    let buffer = Bytes::copy_from_slice(data);
+    let pdu = session
+        .with_packet_buffer(&buffer, || {
+            session.table_dissect_or_raw::<LinkTypeTable>(&LinkType(link_type), &buffer[..], None)
+        })
+        .map(|(_, pdu)| pdu)
+        .map_err(|e| PyValueError::new_err(format!("Failed to dissect packet: {:?}", e)))?;
+    let ts = match timestamp {
+        Some(ts) => Timestamp::from_nanos((ts * 1e9) as i128),
+        None => SystemTime::now().into(),
+    };
+    let mut packet = sniffle::Packet::new(ts, pdu, length, None, None);
+    packet.set_buffer(Some(buffer));
+    Ok(PyPacket(packet))
+}
+
+#[pymethods]
+impl PySession {
+    /// Creates a session. A tolerant session keeps the outer layers of a
+    /// packet with a malformed layer, in place of failing to dissect it.
+    #[new]
+    #[pyo3(signature = (tolerant = false))]
+    fn new(tolerant: bool) -> Self {
+        let mode = if tolerant {
+            DissectMode::Tolerant
+        } else {
+            DissectMode::Strict
+        };
+        Self(RawSession::builder().dissect_mode(mode).build())
+    }
+
+    #[getter]
+    fn tolerant(&self) -> bool {
+        self.0.dissect_mode() == DissectMode::Tolerant
+    }
+
+    /// Dissects a packet of a link type, such as `sniffle.ETHERNET`. The
+    /// timestamp is in seconds since the Unix epoch, and defaults to now.
+    #[pyo3(signature = (link_type, data, timestamp = None, length = None))]
+    fn dissect(
+        &self,
+        link_type: u16,
+        data: &[u8],
+        timestamp: Option<f64>,
+        length: Option<usize>,
+    ) -> PyResult<PyPacket> {
+        dissect(&self.0, link_type, data, timestamp, length)
+    }
+}
+
+#[pymethods]
+impl PyCapture {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyPacket>> {
+        let Some(sniffer) = self.sniffer.as_mut() else {
+            return Err(closed());
+        };
+        let rt = &self.rt;
+        let packet = py.allow_threads(|| rt.block_on(sniffer.sniff()));
+        Ok(packet.map_err(py_err)?.map(PyPacket))
+    }
+
+    fn close(&mut self) {
+        self.sniffer = None;
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (_ty = None, _value = None, _traceback = None))]
+    fn __exit__(
+        &mut self,
+        _ty: Option<&Bound<'_, PyAny>>,
+        _value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.close();
+        false
+    }
+}
+
+#[pymethods]
+impl PyPacket {
+    /// Seconds since the Unix epoch
+    #[getter]
+    fn timestamp(&self) -> f64 {
+        seconds(self.0.precise_timestamp())
+    }
+
+    /// Length of the packet on the wire
+    #[getter]
+    fn length(&self) -> usize {
+        self.0.len()
+    }
+
+    #[getter]
+    fn captured_length(&self) -> usize {
+        self.0.captured_len()
+    }
+
+    #[getter]
+    fn link_type(&self) -> Option<u16> {
+        self.0.datalink().map(|link_type| link_type.0)
+    }
+
+    /// The bytes of the packet
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut data = Vec::new();
+        self.0.serialize(&mut data)?;
+        Ok(PyBytes::new_bound(py, &data[..]))
+    }
+
+    /// The names of the layers, outermost first
+    #[getter]
+    fn layers(&self) -> Vec<String> {
+        layers(&self.0)
+    }
+
+    /// Looks up a field by name, as in a query, such as `"ipv4.ttl"`.
+    fn field(&self, name: &str) -> Option<PyField> {
+        self.0.get(name).map(PyField)
+    }
+
+    /// Looks up the value of a field by name, or returns `default`.
+    #[pyo3(signature = (name, default = None))]
+    fn get(&self, py: Python<'_>, name: &str, default: Option<PyObject>) -> Option<PyObject> {
+        match self.0.get(name) {
+            Some(field) => Some(value(py, field.value())),
+            None => default,
+        }
+    }
+
+    /// Tests the packet against a query, such as `"tcp.port == 443"`.
+    fn matches(&self, query: &str) -> PyResult<bool> {
+        self.0
+            .matches(query)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn __getitem__(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        match self.0.get(name) {
+            Some(field) => Ok(value(py, field.value())),
+            None => Err(PyKeyError::new_err(name.to_string())),
+        }
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.0.get(name).is_some()
+    }
+
+    fn __getattr__(slf: &Bound<'_, Self>, name: &str) -> PyResult<PyLayer> {
+        let layer = layers(&slf.get().0)
+            .into_iter()
+            .find(|layer| key(layer) == name);
+        match layer {
+            Some(layer) => Ok(PyLayer {
+                packet: slf.clone().unbind(),
+                name: layer,
+            }),
+            None => Err(PyAttributeError::new_err(format!(
+                "Packet has no layer '{}'",
+                name
+            ))),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.captured_len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Packet {} ({} bytes)>",
+            layers(&self.0).join(" / "),
+            self.0.len()
+        )
+    }
+}
+
+fn layers(packet: &sniffle::Packet) -> Vec<String> {
+    FieldMap::new(packet.pdu())
+        .iter()
+        .filter(|field| field.depth() == 0)
+        .map(|field| String::from(field.path()))
+        .collect()
+}
+
+#[pymethods]
+impl PyLayer {
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name[..]
+    }
+
+    fn __getattr__(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        let path = format!("{}.{}", key(&self.name), name);
+        match self.packet.get().0.get(&path) {
+            Some(field) => Ok(value(py, field.value())),
+            None => Err(PyAttributeError::new_err(format!(
+                "{} has no field '{}'",
+                self.name, name
+            ))),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Layer {}>", self.name)
+    }
+}
+
+#[pymethods]
+impl PyField {
+    /// The dump path of the field, such as `"Ipv4.Time to Live"`
+    #[getter]
+    fn path(&self) -> &str {
+        self.0.path()
+    }
+
+    #[getter]
+    fn value(&self, py: Python<'_>) -> PyObject {
+        value(py, self.0.value())
+    }
+
+    /// The description of the value, such as an address in its usual
+    /// notation
+    #[getter]
+    fn description(&self) -> Option<&str> {
+        self.0.descr()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Field {}: {}>", self.0.path(), self.0)
+    }
+}
+
+#[pymethods]
+impl PyWriter {
+    /// Creates a capture file in either `"pcap"` or `"pcapng"` format.
+    #[new]
+    #[pyo3(signature = (path, format = "pcap"))]
+    fn new(py: Python<'_>, path: std::path::PathBuf, format: &str) -> PyResult<Self> {
+        if format != "pcap" && format != "pcapng" {
+            return Err(PyValueError::new_err(format!(
+                "Unknown capture format '{}'",
+                format
+            )));
+        }
+        let rt = runtime()?;
+        let recorder = py
+            .allow_threads(|| {
+                rt.block_on(async {
+                    Ok::<_, Error>(match format {
+                        "pcap" => Recorder::Pcap(pcap::FileRecorder::create(path).await?),
+                        _ => Recorder::PcapNG(pcapng::FileRecorder::create(path).await?),
+                    })
+                })
+            })
+            .map_err(py_err)?;
+        Ok(Self {
+            rt,
+            recorder: Some(recorder),
+        })
+    }
+
+    fn write(&mut self, py: Python<'_>, packet: &Bound<'_, PyPacket>) -> PyResult<()> {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return Err(closed());
+        };
+        let packet = &packet.get().0;
+        let rt = &self.rt;
+        py.allow_threads(|| {
+            rt.block_on(async {
+                match recorder {
+                    Recorder::Pcap(rec) => rec.transmit(packet).await,
+                    Recorder::PcapNG(rec) => rec.transmit(packet).await,
+                }
+            })
+        })
+        .map_err(py_err)
+    }
+
+    /// Flushes and closes the file. Does nothing if it is already closed.
+    fn close(&mut self, py: Python<'_>) -> PyResult<()> {
+        let Some(mut recorder) = self.recorder.take() else {
+            return Ok(());
+        };
+        let rt = &self.rt;
+        py.allow_threads(|| {
+            rt.block_on(async {
+                match &mut recorder {
+                    Recorder::Pcap(rec) => rec.flush().await,
+                    Recorder::PcapNG(rec) => rec.flush().await,
+                }
+            })
+        })
+        .map_err(py_err)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (_ty = None, _value = None, _traceback = None))]
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        _ty: Option<&Bound<'_, PyAny>>,
+        _value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+}
+
+/// Opens a pcap, pcapng, or other supported capture file.
+#[pyfunction]
+#[pyo3(name = "open")]
+fn open_file(py: Python<'_>, path: std::path::PathBuf) -> PyResult<PyCapture> {
+    let rt = runtime()?;
+    let sniffer = py
+        .allow_threads(|| rt.block_on(FileSniffer::open(path)))
+        .map_err(py_err)?;
+    Ok(PyCapture {
+        rt,
+        sniffer: Some(Box::new(sniffer)),
+    })
+}
+
+/// Dissects a packet with the default session. See `Session.dissect`.
+#[pyfunction]
+#[pyo3(name = "dissect", signature = (link_type, data, timestamp = None, length = None))]
+fn dissect_packet(
+    link_type: u16,
+    data: &[u8],
+    timestamp: Option<f64>,
+    length: Option<usize>,
+) -> PyResult<PyPacket> {
+    dissect(default_session(), link_type, data, timestamp, length)
+}
+
+#[pymodule]
+#[pyo3(name = "sniffle")]
+fn sniffle_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySession>()?;
+    m.add_class::<PyCapture>()?;
+    m.add_class::<PyPacket>()?;
+    m.add_class::<PyLayer>()?;
+    m.add_class::<PyField>()?;
+    m.add_class::<PyWriter>()?;
+    m.add_function(wrap_pyfunction!(open_file, m)?)?;
+    m.add_function(wrap_pyfunction!(dissect_packet, m)?)?;
+    for (name, link_type) in [
+        ("ETHERNET", LinkType::ETHERNET),
+        ("RAW", LinkType::RAW),
+        ("IPV4", LinkType::IPV4),
+        ("IPV6", LinkType::IPV6),
+        ("LINUX_SLL", LinkType::LINUX_SLL),
+        ("LINUX_SLL2", LinkType::LINUX_SLL2),
+        ("IEEE802_11", LinkType::IEEE802_11),
+        ("IEEE802_11_RADIOTAP", LinkType::IEEE802_11_RADIOTAP),
+    ] {
+        m.add(name, link_type.0)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    /// Runs `code` with the `sniffle` module, and an Ethernet, IPv4, and
+    /// UDP frame as `frame`.
+    fn run(code: &str) {
+        let frame: Vec<u8> = [
+            &[0xff; 6][..],
+            &[2, 0, 0, 0, 0, 1],
+            &[0x08, 0x00],
+            &[0x45, 0, 0, 30, 0, 1, 0, 0, 64, 17, 0, 0],
+            &[10, 0, 0, 1, 10, 0, 0, 2],
+            &[0x04, 0xd2, 0, 53, 0, 10, 0, 0],
+            &[0xde, 0xad],
+        ]
+        .concat();
+        Python::with_gil(|py| {
+            let globals = PyDict::new_bound(py);
+            let module = pyo3::wrap_pymodule!(sniffle_module)(py);
+            globals.set_item("sniffle", module).unwrap();
+            globals
+                .set_item("frame", PyBytes::new_bound(py, &frame[..]))
+                .unwrap();
+            let dir = std::env::temp_dir().join(format!("sniffle-python-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            globals.set_item("tmp", dir.clone()).unwrap();
+            let res = py.run_bound(code, Some(&globals), None);
+            std::fs::remove_dir_all(&dir).ok();
+            if let Err(e) = res {
+                e.print(py);
+                panic!("{}", e);
+            }
+        });
+    }
+
+    #[test]
+    fn packet_fields() {
+        run(r#"
+packet = sniffle.dissect(sniffle.ETHERNET, frame, timestamp=1700000000.5, length=60)
+assert packet.layers == ["Ethernet II", "Ipv4", "UDP", "Raw Bytes"], packet.layers
+assert (packet.length, packet.captured_length) == (60, len(frame))
+assert packet.timestamp == 1700000000.5
+assert packet.data == frame
+assert packet.link_type == sniffle.ETHERNET
+assert packet.ethernet_ii.src_address == bytes([2, 0, 0, 0, 0, 1])
+assert packet.ipv4.ttl == 64
+assert packet["udp.destination_port"] == 53
+assert "udp.source_port" in packet
+assert packet.get("tcp.destination_port") is None
+assert packet.get("tcp.destination_port", 0) == 0
+field = packet.field("ethernet_ii.src_address")
+assert field.description == "02:00:00:00:00:01", field.description
+assert str(field) == field.description
+for expr, err in [
+    ("packet.tcp", AttributeError),
+    ("packet.udp.bogus", AttributeError),
+    ("packet['udp.bogus']", KeyError),
+]:
+    try:
+        eval(expr)
+        assert False, expr
+    except err:
+        pass
+"#);
+    }
+
+    #[test]
+    fn session() {
+        run(r#"
+session = sniffle.Session(tolerant=True)
+assert session.tolerant and not sniffle.Session().tolerant
+packet = session.dissect(sniffle.ETHERNET, frame[:20])
+assert packet.layers == ["Ethernet II", "Raw Bytes"], packet.layers
+assert packet.length == 20
+packet = session.dissect(0xffff, frame)
+assert packet.layers == ["Raw Bytes"]
+assert packet.link_type is None
+"#);
+    }
+
+    #[test]
+    fn write_and_read() {
+        run(r#"
+import os
+for format in ["pcap", "pcapng"]:
+    path = os.path.join(tmp, "out." + format)
+    with sniffle.Writer(path, format=format) as out:
+        for i in range(3):
+            out.write(sniffle.dissect(sniffle.ETHERNET, frame, timestamp=100.25 + i))
+    with sniffle.open(path) as capture:
+        packets = list(capture)
+    assert len(packets) == 3
+    assert [p.timestamp for p in packets] == [100.25, 101.25, 102.25]
+    assert all(p.data == frame and p.udp.destination_port == 53 for p in packets)
+    assert packets[0].link_type == sniffle.ETHERNET
+    assert packets[0].matches("udp.destination_port == 53")
+    assert not packets[0].matches("tcp")
+"#);
+    }
+
+    #[test]
+    fn errors() {
+        run(r#"
+import os
+for expr, err in [
+    ("sniffle.open(os.path.join(tmp, 'missing.pcap'))", FileNotFoundError),
+    ("sniffle.Writer(os.path.join(tmp, 'out.txt'), format='txt')", ValueError),
+    ("sniffle.dissect(sniffle.ETHERNET, frame).matches('udp ==')", ValueError),
+]:
+    try:
+        eval(expr)
+        assert False, expr
+    except err:
+        pass
+
+out = sniffle.Writer(os.path.join(tmp, "out.pcap"))
+out.write(sniffle.dissect(sniffle.ETHERNET, frame))
+out.close()
+out.close()
+try:
+    out.write(sniffle.dissect(sniffle.ETHERNET, frame))
+    assert False
+except ValueError:
+    pass
+capture = sniffle.open(os.path.join(tmp, "out.pcap"))
+assert len(list(capture)) == 1
+capture.close()
+try:
+    next(capture)
+    assert False
+except ValueError:
+    pass
+"#);
+    }
+}
@@ -0,0 +1,23 @@
+#!/bin/sh
+# Checks that the crates build with each feature on its own, and with the
+# combinations of the features that select optional dependencies, so a
+# missing `#[cfg(feature = ...)]` or feature dependency is caught before a
+# user with a trimmed feature set runs into it.
+#
+# Requires cargo-hack: cargo install cargo-hack
+set -eu
+
+cd "$(dirname "$0")/.."
+
+# Code that is unused with some features, like a helper only called from
+# cfg-gated code, should be gated too
+RUSTFLAGS="${RUSTFLAGS:--D warnings}"
+export RUSTFLAGS
+
+# Every feature of each crate on its own, plus no features and all features
+cargo hack check --each-feature --no-dev-deps \
+    -p sniffle -p sniffle-core -p sniffle-capfile -p sniffle-protos
+
+# Pairs of the features that pull in optional dependencies or platform APIs
+cargo hack check --feature-powerset --depth 2 --no-dev-deps -p sniffle \
+    --include-features libpcap,npcap,fs,json,maxmind,flate2,zstd,afpacket,wasm-plugins,protos
@@ -3,10 +3,13 @@
 pub use nom;
 
 #[doc(hidden)]
-pub use sniffle_core::{_register_dissector, _register_dissector_table, _register_link_layer_pdu};
+pub use sniffle_core::{
+    _register_conversation, _register_dissector, _register_dissector_table, _register_field,
+    _register_link_layer_pdu,
+};
 
 #[doc(inline)]
-pub use sniffle_core::{Error, Packet};
+pub use sniffle_core::{Error, Extensions, Packet, PacketBuilder, Timestamp, TimestampPrecision};
 
 /// Type alias to prevent `use sniffle::prelude::*` from causing conflicts
 /// with other types or traits named `Error`.
@@ -16,51 +19,129 @@ pub mod address {
     #[doc(inline)]
     pub use sniffle_core::{
         hw, ipv4, ipv4_subnet, ipv6, ipv6_subnet, mac, oui, Address, AddressIter,
-        AddressParseError, HwAddress, Ipv4Address, Ipv4Subnet, Ipv6Address, Ipv6Subnet, MacAddress,
-        RawAddress, Subnet, SubnetParseError,
+        AddressParseError, EuiAddress, HwAddress, Ipv4Address, Ipv4Subnet, Ipv6Address, Ipv6Subnet,
+        MacAddress, RawAddress, Subnet, SubnetParseError,
     };
 }
 
 pub mod dissect {
     #[doc(inline)]
     pub use sniffle_core::{
-        dissector_table, register_dissector, register_dissector_table, AnyDissector, DResult,
-        Dissect, DissectError, Dissector, DissectorTable, Priority, Session,
+        decode_be_parser, decode_le_parser, decode_parser, decode_with, dissector_table,
+        load_dissectors, register_dissector, register_dissector_table, register_field,
+        AnyDissector, BodyTracker, DResult, DecodeCursor, DecodeResult, Dissect, DissectError,
+        DissectMode, Dissector, DissectorTable, LruMap, MemoryAccount, MemoryBudget, MemoryStats,
+        Pool, PoolStats, Poolable, Pooled, Priority, Session, SessionBuilder, StrDissectorTable,
+        StreamDissect, StreamDissector, StreamEvent,
     };
 }
 
+pub mod plugin {
+    #[doc(inline)]
+    pub use sniffle_core::{
+        PluginContext, PluginDissector, PluginField, PluginGuest, PluginPdu, PluginValue,
+        PLUGIN_ABI_VERSION,
+    };
+
+    #[cfg(feature = "wasm-plugins")]
+    #[doc(inline)]
+    pub use sniffle_core::WasmPlugin;
+}
+
 pub mod dump {
     #[doc(inline)]
-    pub use sniffle_core::{Dump, DumpValue, Dumper, ListDumper, LogDumper, NodeDumper};
+    pub use sniffle_core::{
+        Dump, DumpValue, Dumper, HexDumper, ListDumper, LogDumper, NodeDumper, PdmlDumper,
+    };
+
+    #[cfg(feature = "json")]
+    #[doc(inline)]
+    pub use sniffle_core::{JsonDumper, SerdeDumper};
+
+    #[doc(inline)]
+    pub use sniffle_core::{NameResolver, NameSource, ResolvingDumper, ReverseLookup};
+
+    #[cfg(unix)]
+    #[doc(inline)]
+    pub use sniffle_core::SystemReverseLookup;
+
+    #[doc(inline)]
+    pub use sniffle_core::{AnnotatingDumper, Annotation, PacketAnnotator};
+
+    #[cfg(feature = "maxmind")]
+    #[doc(inline)]
+    pub use sniffle_core::MaxMindDb;
 }
 
 pub mod sniff {
     #[doc(inline)]
     pub use sniffle_core::{
-        register_link_layer_pdu, Error, LinkType, LinkTypeTable, RawPacket, Sniff, Sniffer,
+        register_link_layer_pdu, Chain, ConsumerId, Direction, Error, FanoutReceiver, FanoutStats,
+        LinkType, LinkTypeTable, MergeHandle, MergePolicy, MergeSniffer, OverflowPolicy,
+        PacketFanout, PacketHash, PacketMeta, RawPacket, ReceptionType, Sniff, SniffExt, SniffRaw,
+        SniffStream, Sniffer, SourceId,
     };
 }
 
 pub mod transmit {
     #[doc(inline)]
-    pub use sniffle_core::{Error, Transmit};
+    pub use sniffle_core::{Error, Replay, ReplaySummary, Transmit};
+}
+
+pub mod sink {
+    #[doc(inline)]
+    pub use sniffle_core::{Error, PacketSink, SinkFormat, SummarySink};
+}
+
+pub mod stats {
+    #[doc(inline)]
+    pub use sniffle_core::{
+        register_conversation, Conversation, Endpoint, EndpointAddress, EndpointKey, ProtocolNode,
+        TrafficSummary,
+    };
+}
+
+pub mod analysis {
+    pub mod flow_export {
+        #[doc(inline)]
+        pub use sniffle_core::{FlowRecord, IpfixExporter};
+    }
+
+    pub mod io_graph {
+        #[doc(inline)]
+        pub use sniffle_core::{Bucket, IoCounter, IoGraph};
+    }
 }
 
 pub mod device {
     #[doc(inline)]
     pub use sniffle_core::{
-        ConnectionStatus, Device, DeviceBuilder, DeviceIpv4, DeviceIpv6, DeviceTsPrecision,
-        DeviceTsType,
+        CaptureStats, ConnectionStatus, Device, DeviceBuilder, DeviceIpv4, DeviceIpv6,
     };
 
     #[cfg(feature = "libpcap")]
     #[doc(inline)]
-    pub use sniffle_core::{AllDevicesIter, DeviceInjector, DeviceSniffer, DeviceSnifferConfig};
+    pub use sniffle_core::{
+        discover_ipv4, discover_ipv6, AllDevicesIter, DeviceDirection, DeviceInjector,
+        DeviceSniffer, DeviceSnifferConfig, DeviceTsPrecision, DeviceTsType,
+    };
+
+    #[cfg(feature = "npcap")]
+    #[doc(inline)]
+    pub use sniffle_core::{RemoteSnifferAuth, RemoteSnifferConfig, RemoteSnifferSampling};
+
+    #[cfg(all(target_os = "linux", feature = "afpacket"))]
+    #[doc(inline)]
+    pub use sniffle_core::{AfPacketConfig, AfPacketSniffer};
 }
 
 pub mod pdu {
     #[doc(inline)]
-    pub use sniffle_core::{AnyPdu, BasePdu, Pdu, PduExt, PduType, RawPdu, TempPdu};
+    pub use sniffle_core::{
+        AnyPdu, BasePdu, BpfFilter, Bytes, ErrorPdu, Field, FieldMap, FieldRange, FieldValue,
+        Issue, Pdu, PduExt, PduType, Query, QueryError, RawPdu, TempPdu, Validation,
+        ValidationReport, Validator,
+    };
 }
 
 pub mod encode {
@@ -90,25 +171,31 @@ pub mod prelude {
     pub use crate::{
         address::hw, address::ipv4, address::ipv6, address::mac, address::HwAddress,
         address::Ipv4Address, address::Ipv6Address, address::MacAddress, capfile::pcap,
-        capfile::pcapng, capfile::FileSniffer, device::ConnectionStatus, device::Device,
-        dissect::register_dissector, dissect::Priority, dissect::Session, dump::Dump,
-        dump::LogDumper, pdu::AnyPdu, pdu::Pdu, pdu::PduExt, protos, protos::RawPdu, sniff::Sniff,
-        transmit::Transmit, Packet, SniffleError,
+        capfile::pcapng, device::ConnectionStatus, device::Device, dissect::register_dissector,
+        dissect::Priority, dissect::Session, dump::Dump, dump::LogDumper, pdu::AnyPdu, pdu::Pdu,
+        pdu::PduExt, protos, protos::PacketBuilderExt, protos::RawPdu, sniff::Sniff,
+        transmit::Transmit, Packet, PacketBuilder, SniffleError,
     };
 
+    #[cfg(feature = "fs")]
+    pub use crate::capfile::FileSniffer;
+
     #[cfg(feature = "libpcap")]
     pub use crate::device::{DeviceInjector, DeviceSniffer, DeviceSnifferConfig};
 }
 
 pub mod utils {
     pub use sniffle_utils::*;
+
+    #[doc(inline)]
+    pub use sniffle_core::{diff, FieldDiff};
 }
 
 pub mod protos {
-    pub(self) use sniffle_protos as xprotos;
+    use sniffle_protos as xprotos;
 
     #[doc(inline)]
-    pub use xprotos::{RawPdu, Virtual};
+    pub use xprotos::{builder::PacketBuilderExt, ErrorPdu, RawPdu, Virtual};
 
     pub mod ethertype {
         use super::xprotos;
@@ -136,9 +223,111 @@ pub mod protos {
         pub use xprotos::register_ip_proto_pdu;
     }
 
+    #[cfg(feature = "ethernet_ii")]
     #[doc(inline)]
     pub use xprotos::ethernet_ii;
 
+    #[cfg(feature = "sll")]
+    #[doc(inline)]
+    pub use xprotos::sll;
+
+    #[cfg(feature = "sll2")]
+    #[doc(inline)]
+    pub use xprotos::sll2;
+
+    #[cfg(feature = "radiotap")]
+    #[doc(inline)]
+    pub use xprotos::radiotap;
+
+    #[cfg(feature = "ieee80211")]
+    #[doc(inline)]
+    pub use xprotos::ieee80211;
+
+    #[cfg(feature = "ipv4")]
     #[doc(inline)]
     pub use xprotos::ipv4;
+
+    #[cfg(feature = "icmp")]
+    #[doc(inline)]
+    pub use xprotos::icmp;
+
+    #[cfg(feature = "udp")]
+    #[doc(inline)]
+    pub use xprotos::udp;
+
+    #[cfg(feature = "tcp")]
+    #[doc(inline)]
+    pub use xprotos::tcp;
+
+    #[cfg(all(feature = "tcp", feature = "udp"))]
+    #[doc(inline)]
+    pub use xprotos::follow;
+
+    #[cfg(all(feature = "tcp", feature = "udp"))]
+    #[doc(inline)]
+    pub use xprotos::payload;
+
+    #[cfg(feature = "dhcp")]
+    #[doc(inline)]
+    pub use xprotos::dhcp;
+
+    #[cfg(feature = "dhcpv6")]
+    #[doc(inline)]
+    pub use xprotos::dhcpv6;
+
+    #[cfg(feature = "gre")]
+    #[doc(inline)]
+    pub use xprotos::gre;
+
+    #[cfg(feature = "http")]
+    #[doc(inline)]
+    pub use xprotos::http;
+
+    #[cfg(feature = "tls")]
+    #[doc(inline)]
+    pub use xprotos::tls;
+
+    #[cfg(feature = "gsmtap")]
+    #[doc(inline)]
+    pub use xprotos::gsmtap;
+
+    #[cfg(feature = "loratap")]
+    #[doc(inline)]
+    pub use xprotos::loratap;
+
+    #[cfg(feature = "i2c")]
+    #[doc(inline)]
+    pub use xprotos::i2c;
+
+    #[cfg(feature = "ipmb")]
+    #[doc(inline)]
+    pub use xprotos::ipmb;
+
+    #[cfg(feature = "mctp")]
+    #[doc(inline)]
+    pub use xprotos::mctp;
+
+    #[cfg(feature = "ntp")]
+    #[doc(inline)]
+    pub use xprotos::ntp;
+
+    #[cfg(feature = "snmp")]
+    #[doc(inline)]
+    pub use xprotos::snmp;
+
+    #[cfg(feature = "ospf")]
+    #[doc(inline)]
+    pub use xprotos::ospf;
+
+    #[cfg(feature = "bgp")]
+    #[doc(inline)]
+    pub use xprotos::bgp;
+
+    #[cfg(feature = "llc")]
+    #[doc(inline)]
+    pub use xprotos::llc;
+
+    #[cfg(feature = "stp")]
+    #[doc(inline)]
+    pub use xprotos::stp;
 }
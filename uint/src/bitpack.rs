@@ -252,6 +252,236 @@ bitpack_impl_all!(
     (T64, v64)
 );
 
+macro_rules! array_bitpack_impl {
+    ($n:literal; $($v:ident),+) => {
+        impl<T> BitPack for [T; $n]
+        where
+            ($(array_bitpack_impl!(__elem, $v),)+): BitPack,
+        {
+            type Packed = <($(array_bitpack_impl!(__elem, $v),)+) as BitPack>::Packed;
+
+            fn pack(self) -> Self::Packed {
+                let [$($v),+] = self;
+                ($($v,)+).pack()
+            }
+
+            fn unpack(packed: Self::Packed) -> Self {
+                let ($($v,)+) = <($(array_bitpack_impl!(__elem, $v),)+) as BitPack>::unpack(packed);
+                [$($v),+]
+            }
+        }
+    };
+    (__elem, $v:ident) => { T };
+}
+
+macro_rules! array_bitpack_impl_all {
+    ([$($v:ident),*]) => { };
+    ([$($v:ident),*] ($n:literal, $vn:ident) $(, ($ns:literal, $vns:ident))*) => {
+        array_bitpack_impl!($n; $($v,)* $vn);
+        array_bitpack_impl_all!([$($v,)* $vn] $(($ns, $vns)),*);
+    };
+}
+
+#[cfg(feature = "u128")]
+array_bitpack_impl_all!(
+    [](1, v1),
+    (2, v2),
+    (3, v3),
+    (4, v4),
+    (5, v5),
+    (6, v6),
+    (7, v7),
+    (8, v8),
+    (9, v9),
+    (10, v10),
+    (11, v11),
+    (12, v12),
+    (13, v13),
+    (14, v14),
+    (15, v15),
+    (16, v16),
+    (17, v17),
+    (18, v18),
+    (19, v19),
+    (20, v20),
+    (21, v21),
+    (22, v22),
+    (23, v23),
+    (24, v24),
+    (25, v25),
+    (26, v26),
+    (27, v27),
+    (28, v28),
+    (29, v29),
+    (30, v30),
+    (31, v31),
+    (32, v32),
+    (33, v33),
+    (34, v34),
+    (35, v35),
+    (36, v36),
+    (37, v37),
+    (38, v38),
+    (39, v39),
+    (40, v40),
+    (41, v41),
+    (42, v42),
+    (43, v43),
+    (44, v44),
+    (45, v45),
+    (46, v46),
+    (47, v47),
+    (48, v48),
+    (49, v49),
+    (50, v50),
+    (51, v51),
+    (52, v52),
+    (53, v53),
+    (54, v54),
+    (55, v55),
+    (56, v56),
+    (57, v57),
+    (58, v58),
+    (59, v59),
+    (60, v60),
+    (61, v61),
+    (62, v62),
+    (63, v63),
+    (64, v64),
+    (65, v65),
+    (66, v66),
+    (67, v67),
+    (68, v68),
+    (69, v69),
+    (70, v70),
+    (71, v71),
+    (72, v72),
+    (73, v73),
+    (74, v74),
+    (75, v75),
+    (76, v76),
+    (77, v77),
+    (78, v78),
+    (79, v79),
+    (80, v80),
+    (81, v81),
+    (82, v82),
+    (83, v83),
+    (84, v84),
+    (85, v85),
+    (86, v86),
+    (87, v87),
+    (88, v88),
+    (89, v89),
+    (90, v90),
+    (91, v91),
+    (92, v92),
+    (93, v93),
+    (94, v94),
+    (95, v95),
+    (96, v96),
+    (97, v97),
+    (98, v98),
+    (99, v99),
+    (100, v100),
+    (101, v101),
+    (102, v102),
+    (103, v103),
+    (104, v104),
+    (105, v105),
+    (106, v106),
+    (107, v107),
+    (108, v108),
+    (109, v109),
+    (110, v110),
+    (111, v111),
+    (112, v112),
+    (113, v113),
+    (114, v114),
+    (115, v115),
+    (116, v116),
+    (117, v117),
+    (118, v118),
+    (119, v119),
+    (120, v120),
+    (121, v121),
+    (122, v122),
+    (123, v123),
+    (124, v124),
+    (125, v125),
+    (126, v126),
+    (127, v127),
+    (128, v128)
+);
+
+#[cfg(not(feature = "u128"))]
+array_bitpack_impl_all!(
+    [](1, v1),
+    (2, v2),
+    (3, v3),
+    (4, v4),
+    (5, v5),
+    (6, v6),
+    (7, v7),
+    (8, v8),
+    (9, v9),
+    (10, v10),
+    (11, v11),
+    (12, v12),
+    (13, v13),
+    (14, v14),
+    (15, v15),
+    (16, v16),
+    (17, v17),
+    (18, v18),
+    (19, v19),
+    (20, v20),
+    (21, v21),
+    (22, v22),
+    (23, v23),
+    (24, v24),
+    (25, v25),
+    (26, v26),
+    (27, v27),
+    (28, v28),
+    (29, v29),
+    (30, v30),
+    (31, v31),
+    (32, v32),
+    (33, v33),
+    (34, v34),
+    (35, v35),
+    (36, v36),
+    (37, v37),
+    (38, v38),
+    (39, v39),
+    (40, v40),
+    (41, v41),
+    (42, v42),
+    (43, v43),
+    (44, v44),
+    (45, v45),
+    (46, v46),
+    (47, v47),
+    (48, v48),
+    (49, v49),
+    (50, v50),
+    (51, v51),
+    (52, v52),
+    (53, v53),
+    (54, v54),
+    (55, v55),
+    (56, v56),
+    (57, v57),
+    (58, v58),
+    (59, v59),
+    (60, v60),
+    (61, v61),
+    (62, v62),
+    (63, v63),
+    (64, v64)
+);
+
 macro_rules! impl_bitpack {
     (($l:ty, $r:ty) -> $o:ty) => {
         impl BitPack for ($l, $r) {
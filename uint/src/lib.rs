@@ -14,6 +14,11 @@ pub use uints::*;
 /// a total of 64 (or 128 with the "u128" feature enabled) bits, since there
 /// is no possible result uint beyond u64 (or u128).
 ///
+/// Any other value implementing `BitPack`, such as an array of uints or a
+/// struct of bit fields, can be nested by prefixing it with `@`, which packs
+/// it into its `BitPack::Packed` uint first. Arrays are packed with the first
+/// element in the most significant bits.
+///
 /// ## Example
 /// ```
 /// # use sniffle_uint::*;
@@ -25,16 +30,30 @@ pub use uints::*;
 /// let packed = pack!(val1, val2, val3, val4);
 ///
 /// assert_eq!(packed, U13::new(0b10_0110101_0_110).unwrap());
+///
+/// let flags = [U1::new(1).unwrap(), U1::new(0).unwrap(), U1::new(1).unwrap()];
+/// let packed = pack!(val1, @flags, @(val3, val4));
+///
+/// assert_eq!(packed, U9::new(0b10_101_0_110).unwrap());
 /// ```
 #[macro_export]
 macro_rules! pack {
-    ($term:expr) => {{ $term }};
-    ($first:expr, $($rest:expr),+) => {{
+    (__munch [$term:expr]) => {{ $term }};
+    (__munch [$($done:expr),+]) => {{
         fn pack_<T: $crate::BitPack>(vals: T) -> T::Packed {
             vals.pack()
         }
-        pack_(($first, $($rest),+))
+        pack_(($($done),+))
     }};
+    (__munch [$($done:expr),*] @ $nested:expr $(, $($rest:tt)*)?) => {
+        $crate::pack!(__munch [$($done,)* $crate::BitPack::pack($nested)] $($($rest)*)?)
+    };
+    (__munch [$($done:expr),*] $next:expr $(, $($rest:tt)*)?) => {
+        $crate::pack!(__munch [$($done,)* $next] $($($rest)*)?)
+    };
+    ($($args:tt)+) => {
+        $crate::pack!(__munch [] $($args)+)
+    };
 }
 
 /// Utility to simplify unpacking uints.
@@ -50,6 +69,11 @@ macro_rules! pack {
 /// assert_eq!(unpacked.1, U7::new(0b0110101).unwrap());
 /// assert_eq!(unpacked.2, U1::new(0b0).unwrap());
 /// assert_eq!(unpacked.3, U3::new(0b110).unwrap());
+///
+/// let (_, flags, _): (U2, U3, U4) = unpack!(U9::new(0b10_101_0110).unwrap());
+/// let flags: [U1; 3] = unpack!(flags);
+///
+/// assert_eq!(flags, [U1::new(1).unwrap(), U1::new(0).unwrap(), U1::new(1).unwrap()]);
 /// ```
 #[macro_export]
 macro_rules! unpack {
@@ -60,3 +84,62 @@ macro_rules! unpack {
         unpack_($val)
     }};
 }
+
+/// Like `pack!`, but usable in const contexts, such as when defining
+/// protocol constants. The packed type must be named, and only uints can be
+/// packed, not arrays or nested tuples.
+///
+/// ## Example
+/// ```
+/// # use sniffle_uint::*;
+/// const PACKED: U13 = const_pack!(
+///     U13;
+///     U2::new_const(0b10),
+///     U7::new_const(0b0110101),
+///     U1::new_const(0b0),
+///     U3::new_const(0b110),
+/// );
+///
+/// assert_eq!(PACKED, U13::new(0b10_0110101_0_110).unwrap());
+/// ```
+#[macro_export]
+macro_rules! const_pack {
+    ($out:ty; $($val:expr),+ $(,)?) => {{
+        let mut packed: u128 = 0;
+        let mut width: u32 = 0;
+        $(
+            let val = $val;
+            packed = (packed << val.bit_width()) | val.get() as u128;
+            width += val.bit_width();
+        )+
+        assert!(width == <$out>::BITS, "packed width does not match the output type");
+        <$out>::new_const(packed as _)
+    }};
+}
+
+/// Like `unpack!`, but usable in const contexts. The unpacked types are
+/// listed after the packed value, and a tuple of them is returned.
+///
+/// ## Example
+/// ```
+/// # use sniffle_uint::*;
+/// const UNPACKED: (U2, U7, U1, U3) = const_unpack!(U13::new_const(0b10_0110101_0_110); U2, U7, U1, U3);
+///
+/// assert_eq!(UNPACKED.0, U2::new(0b10).unwrap());
+/// assert_eq!(UNPACKED.1, U7::new(0b0110101).unwrap());
+/// assert_eq!(UNPACKED.2, U1::new(0b0).unwrap());
+/// assert_eq!(UNPACKED.3, U3::new(0b110).unwrap());
+/// ```
+#[macro_export]
+macro_rules! const_unpack {
+    ($val:expr; $($ty:ty),+ $(,)?) => {{
+        let val = $val;
+        let packed = val.get() as u128;
+        let mut shift = val.bit_width();
+        assert!(shift == 0 $(+ <$ty>::BITS)+, "unpacked widths do not match the packed type");
+        ($({
+            shift -= <$ty>::BITS;
+            <$ty>::new_masked((packed >> shift) as _)
+        },)+)
+    }};
+}
@@ -16,6 +16,45 @@ pub type U64 = u64;
 /// A 128-bit unsigned integer.
 pub type U128 = u128;
 
+/// The error returned by `TryFrom` conversions to and from uints when the
+/// value is out of range for the target type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryFromUintError {
+    source_bits: u32,
+    target_bits: u32,
+}
+
+impl TryFromUintError {
+    const fn new(source_bits: u32, target_bits: u32) -> Self {
+        Self {
+            source_bits,
+            target_bits,
+        }
+    }
+
+    /// Bit width of the type converted from.
+    pub const fn source_bits(&self) -> u32 {
+        self.source_bits
+    }
+
+    /// Bit width of the type converted to.
+    pub const fn target_bits(&self) -> u32 {
+        self.target_bits
+    }
+}
+
+impl std::fmt::Display for TryFromUintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "value of {}-bit integer out of range for {}-bit integer",
+            self.source_bits, self.target_bits
+        )
+    }
+}
+
+impl std::error::Error for TryFromUintError {}
+
 pub trait FromMasked<T> {
     fn from_masked(value: T) -> Self;
 }
@@ -36,6 +75,177 @@ impl<T: FromMasked<U31>> FromMasked<i32> for T {
     }
 }
 
+#[cfg(feature = "num-traits")]
+macro_rules! num_traits_impl {
+    ($name:ident, $repr:ty) => {
+        impl num_traits::Zero for $name {
+            fn zero() -> Self {
+                Self(0)
+            }
+
+            fn is_zero(&self) -> bool {
+                self.0 == 0
+            }
+        }
+
+        impl num_traits::One for $name {
+            fn one() -> Self {
+                Self(1)
+            }
+        }
+
+        impl num_traits::Bounded for $name {
+            fn min_value() -> Self {
+                Self::MIN
+            }
+
+            fn max_value() -> Self {
+                Self::MAX
+            }
+        }
+
+        impl num_traits::Num for $name {
+            type FromStrRadixErr = std::num::ParseIntError;
+
+            fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+                let raw = <$repr>::from_str_radix(s, radix)?;
+                match Self::new(raw) {
+                    Some(val) => Ok(val),
+                    // Produce the same error as a builtin uint would
+                    None => "256".parse::<u8>().map(|_| Self(0)),
+                }
+            }
+        }
+
+        impl num_traits::Unsigned for $name {}
+
+        impl num_traits::ToPrimitive for $name {
+            fn to_i64(&self) -> Option<i64> {
+                num_traits::ToPrimitive::to_i64(&self.0)
+            }
+
+            fn to_u64(&self) -> Option<u64> {
+                num_traits::ToPrimitive::to_u64(&self.0)
+            }
+
+            fn to_i128(&self) -> Option<i128> {
+                num_traits::ToPrimitive::to_i128(&self.0)
+            }
+
+            fn to_u128(&self) -> Option<u128> {
+                num_traits::ToPrimitive::to_u128(&self.0)
+            }
+        }
+
+        impl num_traits::FromPrimitive for $name {
+            fn from_i64(n: i64) -> Option<Self> {
+                <$repr as num_traits::FromPrimitive>::from_i64(n).and_then(Self::new)
+            }
+
+            fn from_u64(n: u64) -> Option<Self> {
+                <$repr as num_traits::FromPrimitive>::from_u64(n).and_then(Self::new)
+            }
+
+            fn from_i128(n: i128) -> Option<Self> {
+                <$repr as num_traits::FromPrimitive>::from_i128(n).and_then(Self::new)
+            }
+
+            fn from_u128(n: u128) -> Option<Self> {
+                <$repr as num_traits::FromPrimitive>::from_u128(n).and_then(Self::new)
+            }
+        }
+
+        impl num_traits::NumCast for $name {
+            fn from<T: num_traits::ToPrimitive>(n: T) -> Option<Self> {
+                <$repr as num_traits::NumCast>::from(n).and_then(Self::new)
+            }
+        }
+
+        num_traits_impl!(__binop, $name, CheckedAdd, checked_add, Option<Self>);
+        num_traits_impl!(__binop, $name, CheckedSub, checked_sub, Option<Self>);
+        num_traits_impl!(__binop, $name, CheckedMul, checked_mul, Option<Self>);
+        num_traits_impl!(__binop, $name, CheckedDiv, checked_div, Option<Self>);
+        num_traits_impl!(__binop, $name, CheckedRem, checked_rem, Option<Self>);
+        num_traits_impl!(__binop, $name, WrappingAdd, wrapping_add, Self);
+        num_traits_impl!(__binop, $name, WrappingSub, wrapping_sub, Self);
+        num_traits_impl!(__binop, $name, WrappingMul, wrapping_mul, Self);
+        num_traits_impl!(__binop, $name, SaturatingAdd, saturating_add, Self);
+        num_traits_impl!(__binop, $name, SaturatingSub, saturating_sub, Self);
+        num_traits_impl!(__binop, $name, SaturatingMul, saturating_mul, Self);
+
+        impl num_traits::CheckedNeg for $name {
+            fn checked_neg(&self) -> Option<Self> {
+                $name::checked_neg(*self)
+            }
+        }
+
+        impl num_traits::CheckedShl for $name {
+            fn checked_shl(&self, rhs: u32) -> Option<Self> {
+                $name::checked_shl(*self, rhs)
+            }
+        }
+
+        impl num_traits::CheckedShr for $name {
+            fn checked_shr(&self, rhs: u32) -> Option<Self> {
+                $name::checked_shr(*self, rhs)
+            }
+        }
+    };
+    (__binop, $name:ident, $tr:ident, $method:ident, $out:ty) => {
+        impl num_traits::$tr for $name {
+            fn $method(&self, v: &Self) -> $out {
+                $name::$method(*self, *v)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "arbitrary")]
+macro_rules! arbitrary_impl {
+    ($name:ident, $repr:ty) => {
+        impl<'a> arbitrary::Arbitrary<'a> for $name {
+            fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+                Ok(Self(u.int_in_range(0..=Self::MAX.0)?))
+            }
+
+            fn size_hint(depth: usize) -> (usize, Option<usize>) {
+                <$repr as arbitrary::Arbitrary<'a>>::size_hint(depth)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "proptest")]
+macro_rules! proptest_impl {
+    ($name:ident, $repr:ty) => {
+        impl proptest::arbitrary::Arbitrary for $name {
+            type Parameters = ();
+            type Strategy =
+                proptest::strategy::Map<std::ops::RangeInclusive<$repr>, fn($repr) -> Self>;
+
+            fn arbitrary_with(_args: ()) -> Self::Strategy {
+                proptest::strategy::Strategy::prop_map(0..=Self::MAX.0, Self)
+            }
+        }
+    };
+}
+
+macro_rules! try_from_prim_impl {
+    ($name:ident, $repr:ty; $($src:ty),+) => {
+        $(
+            impl std::convert::TryFrom<$src> for $name {
+                type Error = TryFromUintError;
+
+                fn try_from(val: $src) -> Result<Self, Self::Error> {
+                    let err = TryFromUintError::new(<$src>::BITS, Self::BITS);
+                    let raw = <$repr as std::convert::TryFrom<$src>>::try_from(val).map_err(|_| err)?;
+                    Self::new(raw).ok_or(err)
+                }
+            }
+        )+
+    };
+}
+
 macro_rules! uint {
     ($name:ident, $width:literal, $repr:ty) => {
         #[doc=concat!("A ", stringify!($width), "-bit unsigned integer.\n\nRepresented with a `", stringify!($repr), "`.")]
@@ -43,6 +253,20 @@ macro_rules! uint {
         #[repr(transparent)]
         pub struct $name($repr);
 
+        try_from_prim_impl!($name, $repr; i8, i16, i32, i64, i128, isize, usize);
+
+        #[cfg(not(feature = "u128"))]
+        try_from_prim_impl!($name, $repr; u128);
+
+        #[cfg(feature = "num-traits")]
+        num_traits_impl!($name, $repr);
+
+        #[cfg(feature = "arbitrary")]
+        arbitrary_impl!($name, $repr);
+
+        #[cfg(feature = "proptest")]
+        proptest_impl!($name, $repr);
+
         impl $name {
             pub const MIN: Self = Self(0);
 
@@ -58,10 +282,44 @@ macro_rules! uint {
                 }
             }
 
+            /// Like `new`, but panics if `n` is out of range. When used to
+            /// define a constant, this is a compile error instead.
+            pub const fn new_const(n: $repr) -> Self {
+                match Self::new(n) {
+                    Some(v) => v,
+                    None => panic!(concat!("value out of range for ", stringify!($name))),
+                }
+            }
+
+            /// Creates a uint from the low `BITS` bits of `n`, discarding
+            /// the rest.
+            pub const fn new_masked(n: $repr) -> Self {
+                Self(n & Self::MAX.0)
+            }
+
             pub const fn get(self) -> $repr {
                 self.0
             }
 
+            /// The same as `BITS`, for when only a value is at hand, such
+            /// as in `const_pack!`.
+            pub const fn bit_width(self) -> u32 {
+                $width
+            }
+
+            /// Converts to a type that can hold every value of this type,
+            /// such as a wider uint or builtin unsigned integer.
+            pub fn widen<T: From<Self>>(self) -> T {
+                T::from(self)
+            }
+
+            /// Converts to a type that may not be able to hold the value,
+            /// such as a narrower uint, failing if the value is out of
+            /// range for `T`.
+            pub fn narrow<T: std::convert::TryFrom<Self>>(self) -> Result<T, T::Error> {
+                T::try_from(self)
+            }
+
             pub const fn count_ones(self) -> u32 {
                 self.0.count_ones()
             }
@@ -727,7 +985,7 @@ macro_rules! uint {
 
         impl std::cmp::PartialOrd<$name> for $name {
             fn partial_cmp(&self, other: &$name) -> Option<std::cmp::Ordering> {
-                std::cmp::PartialOrd::<$repr>::partial_cmp(&self.0, &other.0)
+                Some(std::cmp::Ord::cmp(self, other))
             }
 
             fn lt(&self, other: &$name) -> bool {
@@ -2157,12 +2415,13 @@ macro_rules! from_impl {
         }
 
         impl std::convert::TryFrom<$tgt> for $src {
-            type Error = std::num::TryFromIntError;
+            type Error = TryFromUintError;
 
             fn try_from(val: $tgt) -> Result<Self, Self::Error> {
-                let ret = Self::from_raw(val.into_raw().try_into()?);
+                let err = TryFromUintError::new(<$tgt>::BITS, <$src>::BITS);
+                let ret = Self::from_raw(val.into_raw().try_into().map_err(|_| err)?);
                 if ret.into_raw() > Self::MAX.into_raw() {
-                    <u8 as std::convert::TryFrom<u16>>::try_from(256).map(|_| Self::from_raw(0))
+                    Err(err)
                 } else {
                     Ok(ret)
                 }
@@ -2261,6 +2520,8 @@ uint!(U63, 63, u64);
 
 #[cfg(feature = "u128")]
 mod u128_extra {
+    use super::*;
+
     uint!(U65, 65, u128);
     uint!(U66, 66, u128);
     uint!(U67, 67, u128);
@@ -0,0 +1,269 @@
+//! Reading and writing bit fields that are not aligned to bytes.
+//!
+//! Bit positions count from the start of the buffer. In `BitOrder::MsbFirst`
+//! order, as used by most network protocols, bit 0 is the most significant
+//! bit of the first byte and values are read most significant bit first. In
+//! `BitOrder::LsbFirst` order, as used by compression formats like DEFLATE,
+//! bit 0 is the least significant bit of the first byte and values are read
+//! least significant bit first.
+
+/// The order of bits within each byte.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    #[default]
+    MsbFirst,
+    LsbFirst,
+}
+
+/// A field of `LEN` bits at bit offset `OFFSET`, in `BitOrder::MsbFirst`
+/// order.
+///
+/// ```
+/// # use sniffle_utils::bits::BitField;
+/// type Version = BitField<0, 4>;
+/// type Ihl = BitField<4, 4>;
+///
+/// let mut hdr = [0x45u8, 0x00];
+/// assert_eq!(Version::get(&hdr), Some(4));
+/// assert_eq!(Ihl::get(&hdr), Some(5));
+/// assert!(Ihl::set(&mut hdr, 6));
+/// assert_eq!(hdr[0], 0x46);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BitField<const OFFSET: usize, const LEN: u32>;
+
+/// Reads bit fields from a byte slice.
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    order: BitOrder,
+}
+
+/// Writes bit fields to a byte vector. Partially written bytes are padded
+/// with zero bits.
+#[derive(Debug, Clone, Default)]
+pub struct BitWriter {
+    buf: Vec<u8>,
+    len: usize,
+    order: BitOrder,
+}
+
+impl<const OFFSET: usize, const LEN: u32> BitField<OFFSET, LEN> {
+    /// Returns the field's value, or `None` if `buf` is too short.
+    pub fn get(buf: &[u8]) -> Option<u64> {
+        get_bits(buf, OFFSET, LEN, BitOrder::MsbFirst)
+    }
+
+    /// Sets the field to the low `LEN` bits of `value`. Returns false, and
+    /// leaves `buf` unchanged, if `buf` is too short.
+    pub fn set(buf: &mut [u8], value: u64) -> bool {
+        set_bits(buf, OFFSET, LEN, value, BitOrder::MsbFirst)
+    }
+}
+
+/// Returns the `len` bits at bit offset `offset`, or `None` if `buf` is too
+/// short.
+///
+/// # Panics
+/// Panics if `len` is more than 64.
+pub fn get_bits(buf: &[u8], offset: usize, len: u32, order: BitOrder) -> Option<u64> {
+    assert!(len <= 64, "bit fields are at most 64 bits");
+    if offset.checked_add(len as usize)? > buf.len() * 8 {
+        return None;
+    }
+    let mut value = 0u64;
+    for i in 0..len {
+        let pos = offset + i as usize;
+        let bit = match order {
+            BitOrder::MsbFirst => (buf[pos / 8] >> (7 - pos % 8)) & 1,
+            BitOrder::LsbFirst => (buf[pos / 8] >> (pos % 8)) & 1,
+        } as u64;
+        value = match order {
+            BitOrder::MsbFirst => (value << 1) | bit,
+            BitOrder::LsbFirst => value | (bit << i),
+        };
+    }
+    Some(value)
+}
+
+/// Sets the `len` bits at bit offset `offset` to the low `len` bits of
+/// `value`. Returns false, and leaves `buf` unchanged, if `buf` is too short.
+///
+/// # Panics
+/// Panics if `len` is more than 64.
+pub fn set_bits(buf: &mut [u8], offset: usize, len: u32, value: u64, order: BitOrder) -> bool {
+    assert!(len <= 64, "bit fields are at most 64 bits");
+    match offset.checked_add(len as usize) {
+        Some(end) if end <= buf.len() * 8 => {}
+        _ => return false,
+    }
+    for i in 0..len {
+        let pos = offset + i as usize;
+        let (bit, shift) = match order {
+            BitOrder::MsbFirst => ((value >> (len - 1 - i)) & 1, 7 - pos % 8),
+            BitOrder::LsbFirst => ((value >> i) & 1, pos % 8),
+        };
+        let byte = &mut buf[pos / 8];
+        *byte = (*byte & !(1 << shift)) | ((bit as u8) << shift);
+    }
+    true
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(buf: &'a [u8], order: BitOrder) -> Self {
+        Self { buf, pos: 0, order }
+    }
+
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// The number of bits read so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// The number of bits left to read.
+    pub fn remaining(&self) -> usize {
+        self.buf.len() * 8 - self.pos
+    }
+
+    pub fn is_aligned(&self) -> bool {
+        self.pos.is_multiple_of(8)
+    }
+
+    /// Reads `len` bits, or returns `None`, without consuming anything, if
+    /// fewer than `len` bits are left.
+    ///
+    /// # Panics
+    /// Panics if `len` is more than 64.
+    pub fn read(&mut self, len: u32) -> Option<u64> {
+        let value = get_bits(self.buf, self.pos, len, self.order)?;
+        self.pos += len as usize;
+        Some(value)
+    }
+
+    pub fn read_bool(&mut self) -> Option<bool> {
+        self.read(1).map(|bit| bit != 0)
+    }
+
+    /// Skips `len` bits. Returns false if fewer than `len` bits are left.
+    pub fn skip(&mut self, len: usize) -> bool {
+        if len > self.remaining() {
+            return false;
+        }
+        self.pos += len;
+        true
+    }
+
+    /// Skips to the start of the next byte, if not already at one.
+    pub fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(8).min(self.buf.len() * 8);
+    }
+
+    /// The bytes after the current position, which must be aligned.
+    pub fn rest(&self) -> Option<&'a [u8]> {
+        if self.is_aligned() {
+            Some(&self.buf[self.pos / 8..])
+        } else {
+            None
+        }
+    }
+}
+
+impl BitWriter {
+    pub fn new(order: BitOrder) -> Self {
+        Self {
+            buf: Vec::new(),
+            len: 0,
+            order,
+        }
+    }
+
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// The number of bits written so far.
+    pub fn bit_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_aligned(&self) -> bool {
+        self.len.is_multiple_of(8)
+    }
+
+    /// Writes the low `len` bits of `value`.
+    ///
+    /// # Panics
+    /// Panics if `len` is more than 64.
+    pub fn write(&mut self, value: u64, len: u32) {
+        let end = self.len + len as usize;
+        self.buf.resize(end.div_ceil(8), 0);
+        set_bits(&mut self.buf, self.len, len, value, self.order);
+        self.len = end;
+    }
+
+    pub fn write_bool(&mut self, bit: bool) {
+        self.write(bit as u64, 1);
+    }
+
+    /// Pads with zero bits to the start of the next byte, if not already at
+    /// one.
+    pub fn align(&mut self) {
+        self.len = self.len.next_multiple_of(8);
+    }
+
+    /// Writes whole bytes, after aligning to the start of the next byte.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.align();
+        self.buf.extend_from_slice(bytes);
+        self.len += bytes.len() * 8;
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..]
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_write_bits() {
+        for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let mut writer = BitWriter::new(order);
+            writer.write(0b101, 3);
+            writer.write_bool(true);
+            writer.write(0x1234, 13);
+            writer.align();
+            writer.write_bytes(&[0xab]);
+            writer.write(u64::MAX, 64);
+            assert_eq!(writer.bit_len(), 96);
+
+            let bytes = writer.into_bytes();
+            let mut reader = BitReader::new(&bytes[..], order);
+            assert_eq!(reader.read(3), Some(0b101));
+            assert_eq!(reader.read_bool(), Some(true));
+            assert_eq!(reader.read(13), Some(0x1234));
+            assert_eq!(reader.rest(), None);
+            reader.align();
+            assert_eq!(reader.read(8), Some(0xab));
+            assert_eq!(reader.read(64), Some(u64::MAX));
+            assert_eq!(reader.read(1), None);
+            assert_eq!(reader.remaining(), 0);
+        }
+
+        let buf = [0b1100_0001u8, 0b1000_0000];
+        assert_eq!(get_bits(&buf, 7, 2, BitOrder::MsbFirst), Some(0b11));
+        assert_eq!(get_bits(&buf, 0, 2, BitOrder::LsbFirst), Some(0b01));
+        assert_eq!(get_bits(&buf, 15, 2, BitOrder::MsbFirst), None);
+        assert_eq!(BitField::<1, 2>::get(&buf), Some(0b10));
+    }
+}
@@ -1,11 +1,156 @@
+use sniffle_address::{Ipv4Address, Ipv6Address};
+use sniffle_ende::encode::Encoder;
 use std::io::{Result, Write};
 
+/// A checksum that is computed incrementally over the data written to it.
+pub trait Checksum: Write {
+    type Output: Copy;
+
+    /// Returns the checksum of all data written so far.
+    fn checksum(&self) -> Self::Output;
+}
+
 #[derive(Clone, Copy, Default, Debug)]
 pub struct U16OnesComplement {
     sum: u16,
     extra: Option<u8>,
 }
 
+/// Reflected CRC-32, as used for the Ethernet FCS (`Crc32::new`) and SCTP
+/// (`Crc32::castagnoli`)
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32 {
+    table: &'static [u32; 256],
+    crc: u32,
+}
+
+/// CRC-16 with the CCITT polynomial (0x1021), most significant bit first
+#[derive(Clone, Copy, Debug)]
+pub struct Crc16Ccitt {
+    crc: u16,
+}
+
+/// Fletcher-16 checksum, as defined by RFC 1146
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Fletcher16 {
+    sum1: u16,
+    sum2: u16,
+}
+
+/// Fletcher-32 checksum over big endian 16-bit words. A trailing odd byte
+/// is padded with zero.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Fletcher32 {
+    sum1: u32,
+    sum2: u32,
+    extra: Option<u8>,
+}
+
+/// Adler-32 checksum, as defined by RFC 1950
+#[derive(Clone, Copy, Debug)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+/// Pseudo-header prepended to upper layer data (TCP, UDP, etc.) when
+/// computing transport checksums.
+pub trait PseudoHeader {
+    /// Writes the pseudo-header for an upper layer protocol `proto` with
+    /// `upper_len` bytes of header and payload.
+    fn write_pseudo_header<W: Write + ?Sized>(
+        &self,
+        proto: u8,
+        upper_len: usize,
+        out: &mut W,
+    ) -> Result<()>;
+}
+
+/// IPv4 pseudo-header, as defined by RFC 793
+#[derive(Clone, Copy, Debug)]
+pub struct Ipv4PseudoHeader {
+    pub src: Ipv4Address,
+    pub dst: Ipv4Address,
+}
+
+/// IPv6 pseudo-header, as defined by RFC 8200
+#[derive(Clone, Copy, Debug)]
+pub struct Ipv6PseudoHeader {
+    pub src: Ipv6Address,
+    pub dst: Ipv6Address,
+}
+
+/// Pseudo-header of either IP version, for protocols that can be carried by
+/// both
+#[derive(Clone, Copy, Debug)]
+pub enum IpPseudoHeader {
+    V4(Ipv4PseudoHeader),
+    V6(Ipv6PseudoHeader),
+}
+
+/// Feeds the same data to two checksums, so that for example a hash and a
+/// CRC can be computed in one pass. Nest `Tee`s to combine more than two.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Tee<A, B>(pub A, pub B);
+
+/// Encoder adapter that forwards all data to an inner encoder, while also
+/// computing a checksum over it. By default, this is a ones complement
+/// checksum (plus an optional pseudo-header).
+pub struct ChecksumEncoder<'a, 'b, E: Encoder<'a> + ?Sized, C: Checksum = U16OnesComplement> {
+    encoder: &'b mut E,
+    acc: C,
+    bytes: usize,
+    _marker: std::marker::PhantomData<&'a ()>,
+}
+
+const CRC32_POLY: u32 = 0xedb88320;
+const CRC32C_POLY: u32 = 0x82f63b78;
+const CRC16_CCITT_POLY: u16 = 0x1021;
+
+const fn crc32_table(poly: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if (crc & 1) != 0 {
+                (crc >> 1) ^ poly
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const fn crc16_table(poly: u16) -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if (crc & 0x8000) != 0 {
+                (crc << 1) ^ poly
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table(CRC32_POLY);
+static CRC32C_TABLE: [u32; 256] = crc32_table(CRC32C_POLY);
+static CRC16_CCITT_TABLE: [u16; 256] = crc16_table(CRC16_CCITT_POLY);
+
 impl U16OnesComplement {
     pub fn new() -> Self {
         Self {
@@ -14,12 +159,228 @@ impl U16OnesComplement {
         }
     }
 
+    /// Creates an accumulator pre-loaded with a pseudo-header.
+    pub fn with_pseudo_header<P: PseudoHeader + ?Sized>(
+        pseudo_header: &P,
+        proto: u8,
+        upper_len: usize,
+    ) -> Self {
+        let mut acc = Self::new();
+        let _ = pseudo_header.write_pseudo_header(proto, upper_len, &mut acc);
+        acc
+    }
+
     fn add(&mut self, word: u16) {
-        self.sum = self.sum.wrapping_add(word);
+        let (sum, carry) = self.sum.overflowing_add(word);
+        self.sum = sum + carry as u16;
     }
 
     pub fn checksum(&self) -> u16 {
-        !self.sum
+        let mut tmp = *self;
+        if let Some(last) = tmp.extra.take() {
+            tmp.add(u16::from_be_bytes([last, 0]));
+        }
+        !tmp.sum
+    }
+}
+
+impl Crc32 {
+    /// Creates a CRC-32 accumulator with the IEEE 802.3 polynomial.
+    pub fn new() -> Self {
+        Self {
+            table: &CRC32_TABLE,
+            crc: !0,
+        }
+    }
+
+    /// Creates a CRC-32C accumulator with the Castagnoli polynomial.
+    pub fn castagnoli() -> Self {
+        Self {
+            table: &CRC32C_TABLE,
+            crc: !0,
+        }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Crc16Ccitt {
+    /// Creates an accumulator with an initial value of 0xffff
+    /// (CRC-16/CCITT-FALSE).
+    pub fn new() -> Self {
+        Self::with_initial(0xffff)
+    }
+
+    /// Creates an accumulator with the provided initial value, such as 0
+    /// for CRC-16/XMODEM.
+    pub fn with_initial(init: u16) -> Self {
+        Self { crc: init }
+    }
+
+    pub fn checksum(&self) -> u16 {
+        self.crc
+    }
+}
+
+impl Fletcher16 {
+    pub fn new() -> Self {
+        Self { sum1: 0, sum2: 0 }
+    }
+
+    pub fn checksum(&self) -> u16 {
+        (self.sum2 << 8) | self.sum1
+    }
+}
+
+impl Fletcher32 {
+    pub fn new() -> Self {
+        Self {
+            sum1: 0,
+            sum2: 0,
+            extra: None,
+        }
+    }
+
+    fn add(&mut self, word: u16) {
+        self.sum1 = (self.sum1 + word as u32) % 0xffff;
+        self.sum2 = (self.sum2 + self.sum1) % 0xffff;
+    }
+
+    pub fn checksum(&self) -> u32 {
+        let mut tmp = *self;
+        if let Some(last) = tmp.extra.take() {
+            tmp.add(u16::from_be_bytes([last, 0]));
+        }
+        (tmp.sum2 << 16) | tmp.sum1
+    }
+}
+
+impl Adler32 {
+    pub fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Crc16Ccitt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PseudoHeader for Ipv4PseudoHeader {
+    fn write_pseudo_header<W: Write + ?Sized>(
+        &self,
+        proto: u8,
+        upper_len: usize,
+        out: &mut W,
+    ) -> Result<()> {
+        out.write_all(&self.src[..])?;
+        out.write_all(&self.dst[..])?;
+        out.write_all(&[0, proto])?;
+        out.write_all(&(upper_len as u16).to_be_bytes()[..])
+    }
+}
+
+impl PseudoHeader for Ipv6PseudoHeader {
+    fn write_pseudo_header<W: Write + ?Sized>(
+        &self,
+        proto: u8,
+        upper_len: usize,
+        out: &mut W,
+    ) -> Result<()> {
+        out.write_all(&self.src[..])?;
+        out.write_all(&self.dst[..])?;
+        out.write_all(&(upper_len as u32).to_be_bytes()[..])?;
+        out.write_all(&[0, 0, 0, proto])
+    }
+}
+
+impl PseudoHeader for IpPseudoHeader {
+    fn write_pseudo_header<W: Write + ?Sized>(
+        &self,
+        proto: u8,
+        upper_len: usize,
+        out: &mut W,
+    ) -> Result<()> {
+        match self {
+            Self::V4(ph) => ph.write_pseudo_header(proto, upper_len, out),
+            Self::V6(ph) => ph.write_pseudo_header(proto, upper_len, out),
+        }
+    }
+}
+
+impl From<Ipv4PseudoHeader> for IpPseudoHeader {
+    fn from(ph: Ipv4PseudoHeader) -> Self {
+        Self::V4(ph)
+    }
+}
+
+impl From<Ipv6PseudoHeader> for IpPseudoHeader {
+    fn from(ph: Ipv6PseudoHeader) -> Self {
+        Self::V6(ph)
+    }
+}
+
+impl<'a, 'b, E: Encoder<'a> + ?Sized> ChecksumEncoder<'a, 'b, E, U16OnesComplement> {
+    pub fn new(encoder: &'b mut E) -> Self {
+        Self::with_accumulator(encoder, U16OnesComplement::new())
+    }
+
+    pub fn with_pseudo_header<P: PseudoHeader + ?Sized>(
+        encoder: &'b mut E,
+        pseudo_header: &P,
+        proto: u8,
+        upper_len: usize,
+    ) -> Self {
+        Self::with_accumulator(
+            encoder,
+            U16OnesComplement::with_pseudo_header(pseudo_header, proto, upper_len),
+        )
+    }
+}
+
+impl<'a, 'b, E: Encoder<'a> + ?Sized, C: Checksum> ChecksumEncoder<'a, 'b, E, C> {
+    pub fn with_accumulator(encoder: &'b mut E, acc: C) -> Self {
+        Self {
+            encoder,
+            acc,
+            bytes: 0,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    pub fn checksum(&self) -> C::Output {
+        self.acc.checksum()
+    }
+
+    pub fn bytes_written(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn accumulator(&self) -> &C {
+        &self.acc
+    }
+
+    pub fn into_inner(self) -> &'b mut E {
+        self.encoder
     }
 }
 
@@ -46,3 +407,241 @@ impl Write for U16OnesComplement {
         Ok(())
     }
 }
+
+impl Write for Crc32 {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for byte in buf.iter() {
+            self.crc = (self.crc >> 8) ^ self.table[((self.crc as u8) ^ byte) as usize];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for Crc16Ccitt {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for byte in buf.iter() {
+            self.crc =
+                (self.crc << 8) ^ CRC16_CCITT_TABLE[(((self.crc >> 8) as u8) ^ byte) as usize];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for Fletcher16 {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for byte in buf.iter() {
+            self.sum1 = (self.sum1 + *byte as u16) % 0xff;
+            self.sum2 = (self.sum2 + self.sum1) % 0xff;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for Fletcher32 {
+    fn write(&mut self, mut buf: &[u8]) -> Result<usize> {
+        let len = buf.len();
+        if !buf.is_empty() {
+            if let Some(first) = self.extra.take() {
+                self.add(u16::from_be_bytes([first, buf[0]]));
+                buf = &buf[1..];
+            }
+        }
+        while buf.len() > 1 {
+            self.add(u16::from_be_bytes([buf[0], buf[1]]));
+            buf = &buf[2..];
+        }
+        if !buf.is_empty() {
+            self.extra = Some(buf[0]);
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for Adler32 {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        const MOD: u32 = 65521;
+        // 5552 is the most bytes that can be summed before `b` overflows.
+        for chunk in buf.chunks(5552) {
+            for byte in chunk.iter() {
+                self.a += *byte as u32;
+                self.b += self.a;
+            }
+            self.a %= MOD;
+            self.b %= MOD;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_checksum {
+    ($($ty:ty => $out:ty),+ $(,)?) => {
+        $(
+            impl Checksum for $ty {
+                type Output = $out;
+
+                fn checksum(&self) -> $out {
+                    <$ty>::checksum(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_checksum! {
+    U16OnesComplement => u16,
+    Crc32 => u32,
+    Crc16Ccitt => u16,
+    Fletcher16 => u16,
+    Fletcher32 => u32,
+    Adler32 => u32,
+}
+
+impl<'a, 'b, E: Encoder<'a> + ?Sized, C: Checksum> Write for ChecksumEncoder<'a, 'b, E, C> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let bytes = self.encoder.write(buf)?;
+        self.acc.write_all(&buf[..bytes])?;
+        self.bytes += bytes;
+        Ok(bytes)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.encoder.flush()
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.encoder.write_all(buf)?;
+        self.acc.write_all(buf)?;
+        self.bytes += buf.len();
+        Ok(())
+    }
+}
+
+impl<A: Checksum, B: Checksum> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<A: Checksum, B: Checksum> Checksum for Tee<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn checksum(&self) -> Self::Output {
+        (self.0.checksum(), self.1.checksum())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn end_around_carry() {
+        let mut acc = U16OnesComplement::new();
+        acc.write_all(&[0xff, 0xff, 0x00, 0x02]).unwrap();
+        assert_eq!(acc.checksum(), !0x0002);
+    }
+
+    #[test]
+    fn odd_length() {
+        let mut acc = U16OnesComplement::new();
+        acc.write_all(&[0x12, 0x34, 0x56]).unwrap();
+        assert_eq!(acc.checksum(), !(0x1234 + 0x5600));
+    }
+
+    #[test]
+    fn udp_ipv4_pseudo_header() {
+        // UDP datagram from 192.168.0.1:1024 to 192.168.0.2:53, payload "hi"
+        let ph = Ipv4PseudoHeader {
+            src: Ipv4Address::new([192, 168, 0, 1]),
+            dst: Ipv4Address::new([192, 168, 0, 2]),
+        };
+        let udp = [0x04, 0x00, 0x00, 0x35, 0x00, 0x0a, 0x00, 0x00, b'h', b'i'];
+        let mut out = Vec::new();
+        let mut enc = ChecksumEncoder::with_pseudo_header(&mut out, &ph, 17, udp.len());
+        enc.write_all(&udp[..]).unwrap();
+        let chksum = enc.checksum();
+        assert_eq!(out, udp);
+
+        let mut verify = U16OnesComplement::with_pseudo_header(&ph, 17, udp.len());
+        verify.write_all(&udp[..6]).unwrap();
+        verify.write_all(&chksum.to_be_bytes()).unwrap();
+        verify.write_all(&udp[8..]).unwrap();
+        assert_eq!(verify.checksum(), 0);
+    }
+
+    fn check<C: Checksum + Default>(data: &[u8]) -> C::Output {
+        let mut acc = C::default();
+        for chunk in data.chunks(3) {
+            acc.write_all(chunk).unwrap();
+        }
+        acc.checksum()
+    }
+
+    #[test]
+    fn check_values() {
+        let data = b"123456789";
+        assert_eq!(check::<Crc32>(data), 0xcbf43926);
+        assert_eq!(check::<Crc16Ccitt>(data), 0x29b1);
+        assert_eq!(check::<Fletcher16>(b"abcde"), 0xc8f0);
+        assert_eq!(check::<Fletcher32>(b"abcde"), 0x4ff029c7);
+        assert_eq!(check::<Adler32>(b"Wikipedia"), 0x11e60398);
+
+        let mut crc = Crc32::castagnoli();
+        crc.write_all(data).unwrap();
+        assert_eq!(crc.checksum(), 0xe3069283);
+
+        let mut crc = Crc16Ccitt::with_initial(0);
+        crc.write_all(data).unwrap();
+        assert_eq!(crc.checksum(), 0x31c3);
+    }
+
+    #[test]
+    fn crc_encoder() {
+        let mut out = Vec::new();
+        let mut enc = ChecksumEncoder::with_accumulator(&mut out, Crc32::new());
+        enc.write_all(b"1234").unwrap();
+        enc.write_all(b"56789").unwrap();
+        assert_eq!(enc.checksum(), 0xcbf43926);
+        assert_eq!(out, b"123456789");
+    }
+
+    #[test]
+    fn tee_hash_and_crc() {
+        use crate::hash::Sha256;
+
+        let mut out = Vec::new();
+        let mut enc = ChecksumEncoder::with_accumulator(&mut out, Tee(Sha256::new(), Crc32::new()));
+        enc.write_all(b"123456789").unwrap();
+        let (sha, crc) = enc.checksum();
+        assert_eq!(enc.bytes_written(), 9);
+        assert_eq!(crc, 0xcbf43926);
+        assert_eq!(sha, Sha256::digest(b"123456789"));
+        assert_eq!(&out[..], b"123456789");
+    }
+}
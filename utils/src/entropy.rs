@@ -0,0 +1,240 @@
+//! Shannon entropy and coarse content classification of byte slices.
+//!
+//! These are heuristics for labelling payloads that no dissector
+//! recognized. High entropy data is reported as compressed when it starts
+//! with the magic number of a known compression format, and as encrypted
+//! otherwise, so compressed data in an unknown container will be
+//! misreported as encrypted.
+
+use std::fmt;
+
+/// Payloads shorter than this are never reported as compressed or
+/// encrypted, since a handful of bytes says little about the byte
+/// distribution.
+pub const MIN_HIGH_ENTROPY_LEN: usize = 32;
+
+/// Fraction of the maximum possible entropy, for the length of the data,
+/// above which data is considered random looking.
+const HIGH_ENTROPY_RATIO: f64 = 0.9;
+
+/// Minimum fraction of printable characters for data to be text.
+const TEXT_RATIO: f64 = 0.95;
+
+const COMPRESSION_MAGIC: &[&[u8]] = &[
+    &[0x1f, 0x8b],                         // gzip
+    &[0x28, 0xb5, 0x2f, 0xfd],             // zstd
+    &[0xfd, b'7', b'z', b'X', b'Z', 0x00], // xz
+    b"BZh",                                // bzip2
+    &[0x04, 0x22, 0x4d, 0x18],             // lz4 frame
+    b"PK\x03\x04",                         // zip
+    &[0x78, 0x01],                         // zlib, no compression
+    &[0x78, 0x5e],                         // zlib, fast
+    &[0x78, 0x9c],                         // zlib, default
+    &[0x78, 0xda],                         // zlib, best
+];
+
+/// Coarse classification of the content of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentClass {
+    Empty,
+    /// Printable ASCII or UTF-8 text
+    Text,
+    /// Structured binary data, with a skewed byte distribution
+    Binary,
+    /// High entropy data starting with a known compression format magic
+    Compressed,
+    /// High entropy data that is not recognizably compressed
+    Encrypted,
+}
+
+impl ContentClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Empty => "Empty",
+            Self::Text => "Text",
+            Self::Binary => "Binary",
+            Self::Compressed => "Compressed",
+            Self::Encrypted => "Encrypted",
+        }
+    }
+}
+
+impl fmt::Display for ContentClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Counts of each byte value in some data.
+#[derive(Debug, Clone)]
+pub struct ByteHistogram {
+    counts: [u64; 256],
+    total: u64,
+}
+
+impl ByteHistogram {
+    pub fn new() -> Self {
+        Self {
+            counts: [0; 256],
+            total: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.counts[*byte as usize] += 1;
+        }
+        self.total += data.len() as u64;
+    }
+
+    pub fn count(&self, byte: u8) -> u64 {
+        self.counts[byte as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Shannon entropy in bits per byte, between 0 and 8.
+    pub fn entropy(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let total = self.total as f64;
+        let entropy: f64 = self
+            .counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / total;
+                -p * p.log2()
+            })
+            .sum();
+        // Avoid reporting -0.0 for data made of a single byte value
+        entropy.max(0.0)
+    }
+
+    /// Entropy as a fraction of the maximum possible for the amount of data
+    /// seen, which is less than 8 bits per byte for fewer than 256 bytes.
+    pub fn normalized_entropy(&self) -> f64 {
+        let max = (self.total.min(256) as f64).log2();
+        if max == 0.0 {
+            0.0
+        } else {
+            self.entropy() / max
+        }
+    }
+}
+
+impl Default for ByteHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte, between 0 and 8.
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut hist = ByteHistogram::new();
+    hist.update(data);
+    hist.entropy()
+}
+
+/// Classifies `data` as text, structured binary, compressed or encrypted.
+pub fn classify(data: &[u8]) -> ContentClass {
+    if data.is_empty() {
+        return ContentClass::Empty;
+    }
+    if is_text(data) {
+        return ContentClass::Text;
+    }
+    if data.len() >= MIN_HIGH_ENTROPY_LEN {
+        let mut hist = ByteHistogram::new();
+        hist.update(data);
+        if hist.normalized_entropy() >= HIGH_ENTROPY_RATIO {
+            return if has_compression_magic(data) {
+                ContentClass::Compressed
+            } else {
+                ContentClass::Encrypted
+            };
+        }
+    }
+    ContentClass::Binary
+}
+
+/// True if `data` starts with the magic number of a common compression
+/// format (gzip, zlib, zstd, xz, bzip2, lz4 or zip).
+pub fn has_compression_magic(data: &[u8]) -> bool {
+    COMPRESSION_MAGIC
+        .iter()
+        .any(|magic| data.starts_with(magic))
+}
+
+fn is_text(data: &[u8]) -> bool {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        // Tolerate a multi-byte character cut off at the end of the data
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&data[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    let mut total = 0usize;
+    let mut printable = 0usize;
+    for c in text.chars() {
+        total += 1;
+        if !c.is_control() || matches!(c, '\t' | '\n' | '\r') {
+            printable += 1;
+        }
+    }
+    total > 0 && printable as f64 >= total as f64 * TEXT_RATIO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift, so the test data is deterministic
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn entropy_bounds() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        assert!((entropy(&[0, 1, 0, 1]) - 1.0).abs() < 1e-9);
+        let all: Vec<u8> = (0..=255).collect();
+        assert!((entropy(&all) - 8.0).abs() < 1e-9);
+        assert!(entropy(&noise(4096)) > 7.9);
+    }
+
+    #[test]
+    fn classification() {
+        assert_eq!(classify(b""), ContentClass::Empty);
+        assert_eq!(
+            classify(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"),
+            ContentClass::Text
+        );
+        assert_eq!(
+            classify("h\u{e9}llo w\u{f6}rld".as_bytes()),
+            ContentClass::Text
+        );
+        assert_eq!(
+            classify(&[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0xff, 0xff]),
+            ContentClass::Binary
+        );
+        assert_eq!(classify(&noise(512)), ContentClass::Encrypted);
+        let mut gzip = vec![0x1f, 0x8b, 0x08, 0x00];
+        gzip.extend(noise(512));
+        assert_eq!(classify(&gzip), ContentClass::Compressed);
+        assert_eq!(classify(&noise(16)), ContentClass::Binary);
+    }
+}
@@ -24,7 +24,7 @@ impl<T: PartialOrd + Copy> IntervalSet<T> {
             return Pos::Above(0);
         }
         let mut idx = self.0.len() / 2;
-        let mut offset = (idx + 1) / 2;
+        let mut offset = idx.div_ceil(2);
         while offset != 0 {
             if self.0[idx].start > value {
                 idx -= offset;
@@ -1,6 +1,10 @@
+pub mod bits;
 pub mod checksum;
 mod counting_encoder;
+pub mod entropy;
+pub mod hash;
 mod interval_set;
+pub mod meter;
 
 pub use counting_encoder::CountingEncoder;
 pub use interval_set::IntervalSet;
@@ -0,0 +1,233 @@
+//! Packet and throughput rates over a sliding window of time.
+//!
+//! A `Meter` counts packets into fixed size time buckets, keyed by packet
+//! timestamp, and reports rates over the most recent buckets. Since time
+//! only moves forward with the timestamps it is given, the same meter works
+//! for replaying a capture file and for live captures. For live captures,
+//! call `Meter::advance` with the current time so the rates fall off when
+//! traffic stops.
+
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// Packet and throughput rates, per second.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rate {
+    pub packets_per_sec: f64,
+    pub bits_per_sec: f64,
+}
+
+/// Counts packets over a sliding window of time.
+#[derive(Debug, Clone)]
+pub struct Meter {
+    bucket_nanos: u128,
+    window_buckets: u128,
+    buckets: VecDeque<Bucket>,
+    first: Option<u128>,
+    now: Option<u128>,
+    total_packets: u64,
+    total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    index: u128,
+    packets: u64,
+    bytes: u64,
+}
+
+impl Rate {
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bits_per_sec / 8.0
+    }
+}
+
+impl Meter {
+    /// Creates a meter averaging over `window`, which is divided into
+    /// buckets of `resolution`. `window` is rounded up to a whole number of
+    /// buckets.
+    ///
+    /// # Panics
+    /// Panics if `resolution` is zero.
+    pub fn new(window: Duration, resolution: Duration) -> Self {
+        let bucket_nanos = resolution.as_nanos();
+        assert!(bucket_nanos > 0, "meter resolution must be non-zero");
+        Self {
+            bucket_nanos,
+            window_buckets: window.as_nanos().div_ceil(bucket_nanos).max(1),
+            buckets: VecDeque::new(),
+            first: None,
+            now: None,
+            total_packets: 0,
+            total_bytes: 0,
+        }
+    }
+
+    /// Creates a meter averaging over `window`, divided into ten buckets.
+    pub fn with_window(window: Duration) -> Self {
+        Self::new(window, (window / 10).max(Duration::from_nanos(1)))
+    }
+
+    pub fn window(&self) -> Duration {
+        nanos_to_duration(self.window_buckets * self.bucket_nanos)
+    }
+
+    pub fn resolution(&self) -> Duration {
+        nanos_to_duration(self.bucket_nanos)
+    }
+
+    /// Counts a packet of `len` bytes captured at `ts`. Packets older than
+    /// the window are only counted in the totals.
+    pub fn record(&mut self, ts: SystemTime, len: usize) {
+        let len = len as u64;
+        self.total_packets += 1;
+        self.total_bytes += len;
+
+        let index = self.index(ts);
+        self.first = Some(self.first.map_or(index, |first| first.min(index)));
+        self.advance_to(index);
+        if index < self.window_start() {
+            return;
+        }
+        let pos = self.buckets.partition_point(|bucket| bucket.index < index);
+        match self.buckets.get_mut(pos) {
+            Some(bucket) if bucket.index == index => {
+                bucket.packets += 1;
+                bucket.bytes += len;
+            }
+            _ => self.buckets.insert(
+                pos,
+                Bucket {
+                    index,
+                    packets: 1,
+                    bytes: len,
+                },
+            ),
+        }
+    }
+
+    /// Moves the end of the window forward to `now`, without counting a
+    /// packet. Does nothing if `now` is before the latest timestamp seen.
+    pub fn advance(&mut self, now: SystemTime) {
+        let index = self.index(now);
+        self.advance_to(index);
+    }
+
+    /// Number of packets in the window.
+    pub fn packets(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.packets).sum()
+    }
+
+    /// Number of bytes in the window.
+    pub fn bytes(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.bytes).sum()
+    }
+
+    pub fn total_packets(&self) -> u64 {
+        self.total_packets
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Rates over the window. Until a full window has passed since the
+    /// first packet, rates are over the time since the first packet.
+    pub fn rate(&self) -> Rate {
+        let (first, now) = match (self.first, self.now) {
+            (Some(first), Some(now)) => (first, now),
+            _ => return Rate::default(),
+        };
+        let buckets = (now + 1 - first.min(now)).min(self.window_buckets);
+        rate(self.packets(), self.bytes(), buckets * self.bucket_nanos)
+    }
+
+    /// Rates over all time since the first packet.
+    pub fn average(&self) -> Rate {
+        let (first, now) = match (self.first, self.now) {
+            (Some(first), Some(now)) => (first, now),
+            _ => return Rate::default(),
+        };
+        rate(
+            self.total_packets,
+            self.total_bytes,
+            (now + 1 - first.min(now)) * self.bucket_nanos,
+        )
+    }
+
+    fn index(&self, ts: SystemTime) -> u128 {
+        ts.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+            / self.bucket_nanos
+    }
+
+    fn window_start(&self) -> u128 {
+        self.now
+            .map_or(0, |now| (now + 1).saturating_sub(self.window_buckets))
+    }
+
+    fn advance_to(&mut self, index: u128) {
+        if self.now.is_some_and(|now| now >= index) {
+            return;
+        }
+        self.now = Some(index);
+        let start = self.window_start();
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| bucket.index < start)
+        {
+            let _ = self.buckets.pop_front();
+        }
+    }
+}
+
+fn rate(packets: u64, bytes: u64, nanos: u128) -> Rate {
+    let secs = nanos as f64 / 1e9;
+    Rate {
+        packets_per_sec: packets as f64 / secs,
+        bits_per_sec: (bytes * 8) as f64 / secs,
+    }
+}
+
+fn nanos_to_duration(nanos: u128) -> Duration {
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn meter_rates() {
+        let at = |millis: u64| SystemTime::UNIX_EPOCH + Duration::from_millis(millis);
+        let mut meter = Meter::new(Duration::from_secs(2), Duration::from_secs(1));
+        assert_eq!(meter.rate(), Rate::default());
+
+        meter.record(at(100), 100);
+        meter.record(at(900), 100);
+        let rate = meter.rate();
+        assert_eq!(rate.packets_per_sec, 2.0);
+        assert_eq!(rate.bits_per_sec, 1600.0);
+        assert_eq!(rate.bytes_per_sec(), 200.0);
+
+        meter.record(at(1_500), 200);
+        assert_eq!(meter.rate().packets_per_sec, 1.5);
+        meter.record(at(2_100), 200);
+        meter.record(at(500), 50);
+        assert_eq!((meter.packets(), meter.bytes()), (2, 400));
+        assert_eq!((meter.total_packets(), meter.total_bytes()), (5, 650));
+        assert_eq!(meter.rate().bits_per_sec, 1600.0);
+        assert_eq!(meter.average().packets_per_sec, 5.0 / 3.0);
+
+        meter.advance(at(3_000));
+        assert_eq!(meter.packets(), 1);
+        meter.advance(at(10_000));
+        assert_eq!(meter.rate(), Rate::default());
+        assert_eq!(meter.window(), Duration::from_secs(2));
+    }
+}